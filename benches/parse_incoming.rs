@@ -0,0 +1,85 @@
+//! Compares parsing the CLI's output with serde_json and with simd-json.
+//!
+//! Run with `cargo bench --features simd-json --bench parse_incoming`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use clauders::proto::Incoming;
+use serde_json::json;
+
+/// Messages of a session that reads and greps large files: tool results
+/// dominate the bytes read.
+fn session(tool_result_bytes: usize) -> Vec<String> {
+    let output = "src/lib.rs:42:    let value = serde_json::from_str(&line)?;\n"
+        .repeat(tool_result_bytes / 60 + 1);
+    (0..20)
+        .flat_map(|i| {
+            let id = format!("toolu_{i}");
+            [
+                json!({
+                    "type": "assistant",
+                    "message": {
+                        "id": format!("msg_{i}"),
+                        "type": "message",
+                        "role": "assistant",
+                        "model": "claude-sonnet-4-5",
+                        "content": [
+                            {"type": "text", "text": "Searching for the parser."},
+                            {"type": "tool_use", "id": id, "name": "Grep",
+                             "input": {"pattern": "from_str", "path": "src"}},
+                        ],
+                        "stop_reason": null,
+                        "usage": {"input_tokens": 1200, "output_tokens": 40},
+                    },
+                    "parent_tool_use_id": null,
+                    "session_id": "bench",
+                }),
+                json!({
+                    "type": "user",
+                    "message": {
+                        "role": "user",
+                        "content": [
+                            {"type": "tool_result", "tool_use_id": id, "content": output,
+                             "is_error": false},
+                        ],
+                    },
+                    "parent_tool_use_id": null,
+                    "session_id": "bench",
+                }),
+            ]
+        })
+        .map(|message| message.to_string())
+        .collect()
+}
+
+fn measure(lines: &[String], parse: impl Fn(&str) -> Incoming) -> Duration {
+    let bytes = lines.iter().map(String::len).sum::<usize>();
+    let iterations = (256 * 1024 * 1024 / bytes).max(3);
+    let started = Instant::now();
+    for _ in 0..iterations {
+        for line in lines {
+            black_box(parse(black_box(line)));
+        }
+    }
+    started.elapsed() / iterations as u32
+}
+
+fn main() {
+    for size in [1024, 64 * 1024, 1024 * 1024] {
+        let lines = session(size);
+        let bytes = lines.iter().map(String::len).sum::<usize>();
+        let serde = measure(&lines, |line| serde_json::from_str(line).unwrap());
+        let simd = measure(&lines, |line| Incoming::parse(line).unwrap());
+        let throughput = |elapsed: Duration| bytes as f64 / elapsed.as_secs_f64() / 1e6;
+        println!(
+            "tool results of {:>7} bytes: serde_json {:>9.2?} ({:>6.0} MB/s), simd-json {:>9.2?} ({:>6.0} MB/s), {:.2}x",
+            size,
+            serde,
+            throughput(serde),
+            simd,
+            throughput(simd),
+            serde.as_secs_f64() / simd.as_secs_f64(),
+        );
+    }
+}
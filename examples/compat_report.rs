@@ -0,0 +1,21 @@
+//! Checks that the installed Claude CLI speaks a wire format this crate can parse.
+//!
+//! Run with:
+//! ```sh
+//! cargo run --example compat_report --features compat
+//! ```
+//!
+//! Exits with a non-zero status when any message fails to parse.
+
+use clauders::{Options, compat};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let report = compat::run(Options::new()).await?;
+    print!("{report}");
+
+    if !report.is_compatible() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -93,10 +93,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Conversation Summary");
     println!("--------------------");
     println!("Total turns: {}", conv.history().len());
-    for (i, turn) in conv.history().iter().enumerate() {
+    for turn in conv.history() {
         let preview = turn.text().chars().take(50).collect::<String>();
         let ellipsis = if turn.text().len() > 50 { "..." } else { "" };
-        println!("Turn {}: {} -> {}{}", i + 1, turn.prompt, preview, ellipsis);
+        println!(
+            "Turn {}: {} -> {}{}",
+            turn.id, turn.prompt, preview, ellipsis
+        );
     }
 
     Ok(())
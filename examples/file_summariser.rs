@@ -1,8 +1,7 @@
 use std::io::{self, Write};
 use std::path::PathBuf;
 
-use clauders::{Client, Options, Responses};
-use futures::StreamExt;
+use clauders::{Client, ConsoleReporter, Options};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -45,38 +44,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     client.query(&prompt).await?;
 
-    let mut stream = std::pin::pin!(client.receive());
-    let mut responses = Responses::new();
-
-    while let Some(result) = stream.next().await {
-        let response = result?;
-
-        if let Some(text) = response.as_text() {
-            print!("{}", text.content());
-            io::stdout().flush()?;
-        }
-
-        if let Some(err) = response.as_error() {
-            eprintln!("\nError: {}", err.message());
-        }
-
-        responses.push(response);
-    }
-
-    println!();
-
-    if let Some(complete) = responses.completion() {
-        println!();
-        println!("---");
-        println!(
-            "Completed in {:.2}s | {} turns",
-            complete.duration_ms() as f64 / 1000.0,
-            complete.num_turns()
-        );
-        if let Some(cost) = complete.total_cost_usd() {
-            println!("Cost: ${:.4}", cost);
-        }
-    }
+    client.dispatch_to(&ConsoleReporter::new()).await?;
 
     Ok(())
 }
@@ -135,23 +135,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         vec![ping_tool(), dns_lookup_tool(), traceroute_tool()],
     ));
 
+    let system_prompt = format!(
+        "You are a network diagnostics assistant. You have access to network diagnostic \
+         tools via the MCP server 'network_tools'. Use these tools to analyze hosts:\n\n\
+         {}\n\
+         After gathering data, provide a clear summary report including:\n\
+         - DNS resolution results\n\
+         - Connectivity status and latency statistics\n\
+         - Network path analysis\n\
+         - Any issues or anomalies detected",
+        network_server.describe_for_prompt()
+    );
+
     let client = Client::new(
         Options::new()
             .model(Model::Haiku)
             .debug(true)
             .with_mcp_server("network_tools", network_server)
-            .system_prompt(
-                "You are a network diagnostics assistant. You have access to network diagnostic \
-                 tools via the MCP server 'network_tools'. Use these tools to analyze hosts:\n\n\
-                 - mcp__network_tools__dns_lookup: Look up DNS records\n\
-                 - mcp__network_tools__ping: Test connectivity and latency\n\
-                 - mcp__network_tools__traceroute: Trace the network path\n\n\
-                 After gathering data, provide a clear summary report including:\n\
-                 - DNS resolution results\n\
-                 - Connectivity status and latency statistics\n\
-                 - Network path analysis\n\
-                 - Any issues or anomalies detected",
-            ),
+            .system_prompt(system_prompt),
     )
     .await?;
 
@@ -186,9 +187,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         if let Some(tool_result) = response.as_tool_result() {
-            if let Some(content) = tool_result.content()
-                && let Some(text) = extract_tool_text(content)
-            {
+            if let Some(text) = tool_result.text() {
                 let preview = truncate(&text.replace('\n', " "), 80);
                 if tool_result.is_error() {
                     println!("[Error: {}]", preview);
@@ -231,12 +230,3 @@ fn truncate(s: &str, max_len: usize) -> String {
         s.to_owned()
     }
 }
-
-fn extract_tool_text(content: &serde_json::Value) -> Option<String> {
-    content
-        .as_array()
-        .and_then(|a| a.first())
-        .and_then(|v| v.get("text"))
-        .and_then(|t| t.as_str())
-        .map(|s| s.to_owned())
-}
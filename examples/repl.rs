@@ -0,0 +1,12 @@
+use clauders::repl::Repl;
+use clauders::{Client, Options};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = Client::new(Options::new()).await?;
+
+    println!("Chatting with Claude. Ctrl-C interrupts a response; /exit quits.");
+    Repl::new().prompt("you> ").run(&client).await?;
+
+    Ok(())
+}
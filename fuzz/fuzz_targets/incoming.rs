@@ -0,0 +1,87 @@
+//! Feeds arbitrary lines to the parser of the CLI's output, and whatever
+//! parses through the conversion into responses.
+//!
+//! Run with `cargo +nightly fuzz run incoming` from the repository root. The
+//! corpus in `fuzz/corpus/incoming` is seeded with messages recorded from CLI
+//! sessions, one per file.
+
+#![no_main]
+
+use std::time::{Duration, SystemTime};
+
+use clauders::proto::Incoming;
+use clauders::{PartialResponse, RateLimitResponse, Response, Responses};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(line) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(incoming) = Incoming::parse(line) else {
+        return;
+    };
+
+    // A parsed message, unknown fields included, is written back in a form
+    // that parses again.
+    let written = serde_json::to_string(&incoming).expect("a parsed message serialises");
+    Incoming::parse(&written).expect("a serialised message parses");
+
+    let responses = match incoming {
+        Incoming::RateLimitEvent(event) => {
+            vec![Response::RateLimit(RateLimitResponse::from(event))]
+        }
+        Incoming::StreamEvent(event) => vec![Response::Partial(PartialResponse::from(event))],
+        other => other
+            .to_message()
+            .map(|message| Response::from_message(&message))
+            .unwrap_or_default(),
+    };
+    for response in &responses {
+        inspect(response);
+    }
+
+    let mut recorded = Responses::new();
+    for response in responses {
+        recorded.push(response.into_owned());
+    }
+    let _ = recorded.timings();
+    let _ = recorded.text_content();
+    let mut ndjson = Vec::new();
+    recorded
+        .write_ndjson(&mut ndjson)
+        .expect("responses serialise");
+    let _ = Responses::read_ndjson(ndjson.as_slice());
+});
+
+/// Calls the accessors that interpret a response's fields.
+fn inspect(response: &Response) {
+    let _ = response.to_json();
+    match response {
+        Response::ToolUse(tool_use) => {
+            let _ = tool_use.input_as::<serde_json::Value>();
+        }
+        Response::ToolResult(result) => {
+            let _ = result.text();
+            let _ = result.blocks();
+            let _ = result.error_kind();
+        }
+        Response::Error(error) => {
+            let _ = error.message();
+        }
+        Response::RateLimit(rate_limit) => {
+            let _ =
+                rate_limit.backoff_delay_at(SystemTime::UNIX_EPOCH + Duration::from_secs(1 << 31));
+        }
+        Response::Partial(partial) => {
+            let _ = partial.text_delta();
+            let _ = partial.input_json_delta();
+            let _ = partial.tool_use_started();
+            let _ = partial.index();
+        }
+        Response::Complete(complete) => {
+            let _ = complete.denied_tools();
+            let _ = complete.model_usage();
+        }
+        _ => {}
+    }
+}
@@ -1,5 +1,7 @@
 //! Agent configuration for Claude Code subagents.
 
+use std::collections::HashMap;
+
 use serde::Serialize;
 
 use crate::model::Model;
@@ -10,6 +12,12 @@ use crate::model::Model;
 /// models, and tool access. They are passed to the Claude CLI via the
 /// `--agents` flag.
 ///
+/// The description and prompt may contain template variables such as
+/// `{{branch}}`, which are filled in from
+/// [`Options::with_agent_variable`](crate::Options::with_agent_variable) when
+/// the client is constructed, so one definition can be reused per task.
+/// Families of related agents can share a base with [`extends`](Self::extends).
+///
 /// # Example
 ///
 /// ```
@@ -83,4 +91,183 @@ impl Agent {
         self.set_tools(tools);
         self
     }
+
+    /// Builds on `base`: its prompt comes first, followed by this agent's,
+    /// the tools are the union of both, and the model is this agent's or,
+    /// if unset, the base's.
+    ///
+    /// An agent with no tools inherits every tool, so the union is empty when
+    /// either side is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clauders::Agent;
+    ///
+    /// let base = Agent::new("Works on this repository", "Follow CONTRIBUTING.md.")
+    ///     .with_tools(["Read", "Grep"]);
+    /// let reviewer = Agent::new("Reviews changes", "Review the staged diff.")
+    ///     .with_tools(["Bash"])
+    ///     .extends(&base);
+    ///
+    /// assert_eq!(reviewer.prompt(), "Follow CONTRIBUTING.md.\n\nReview the staged diff.");
+    /// assert_eq!(reviewer.tools(), ["Read", "Grep", "Bash"]);
+    /// ```
+    #[must_use]
+    pub fn extends(self, base: &Agent) -> Self {
+        self.extends_with(base, ToolInheritance::Union)
+    }
+
+    /// Like [`extends`](Self::extends), combining the tools as `tools` says.
+    #[must_use]
+    pub fn extends_with(mut self, base: &Agent, tools: ToolInheritance) -> Self {
+        self.prompt = match (base.prompt.is_empty(), self.prompt.is_empty()) {
+            (true, _) => self.prompt,
+            (false, true) => base.prompt.clone(),
+            (false, false) => format!("{}\n\n{}", base.prompt, self.prompt),
+        };
+        self.model = self.model.or_else(|| base.model.clone());
+        self.tools = match tools {
+            ToolInheritance::Union if self.tools.is_empty() || base.tools.is_empty() => Vec::new(),
+            ToolInheritance::Union => {
+                let mut union = base.tools.clone();
+                for tool in self.tools {
+                    if !union.contains(&tool) {
+                        union.push(tool);
+                    }
+                }
+                union
+            }
+            ToolInheritance::Intersection if self.tools.is_empty() => base.tools.clone(),
+            ToolInheritance::Intersection if base.tools.is_empty() => self.tools,
+            ToolInheritance::Intersection => self
+                .tools
+                .into_iter()
+                .filter(|tool| base.tools.contains(tool))
+                .collect(),
+        };
+        self
+    }
+
+    /// The template variables in the description and prompt, in order of
+    /// first use.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for text in [&self.description, &self.prompt] {
+            for (_, name) in placeholders(text) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// This agent with its template variables replaced by their values.
+    ///
+    /// Variables without a value are left as written.
+    pub fn resolve(&self, variables: &HashMap<String, String>) -> Self {
+        Self {
+            description: substitute(&self.description, variables),
+            prompt: substitute(&self.prompt, variables),
+            ..self.clone()
+        }
+    }
+}
+
+/// How [`Agent::extends_with`] combines an agent's tools with its base's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToolInheritance {
+    /// Tools from either agent.
+    #[default]
+    Union,
+    /// Only tools both agents list, narrowing the base to the extension.
+    Intersection,
+}
+
+/// Finds each `{{name}}` in `text`, returning its byte range and the name.
+/// Names are ASCII letters, digits, `_` and `-`, and may be padded with
+/// spaces inside the braces.
+fn placeholders(text: &str) -> impl Iterator<Item = (std::ops::Range<usize>, &str)> {
+    let mut from = 0;
+    std::iter::from_fn(move || {
+        while let Some(offset) = text[from..].find("{{") {
+            let start = from + offset;
+            let Some(len) = text[start + 2..].find("}}") else {
+                from = text.len();
+                return None;
+            };
+            let end = start + 2 + len + 2;
+            let name = text[start + 2..end - 2].trim();
+            if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                from = end;
+                return Some((start..end, name));
+            }
+            from = start + 1;
+        }
+        None
+    })
+}
+
+fn substitute(text: &str, variables: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, name) in placeholders(text) {
+        if let Some(value) = variables.get(name) {
+            out.push_str(&text[last..range.start]);
+            out.push_str(value);
+            last = range.end;
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extends_with_intersection() {
+        let base = Agent::new("base", "Be careful.")
+            .with_model(Model::Sonnet)
+            .with_tools(["Read", "Grep", "Bash"]);
+        let agent = Agent::new("narrow", "")
+            .with_tools(["Grep", "Write"])
+            .extends_with(&base, ToolInheritance::Intersection);
+        assert_eq!(agent.prompt(), "Be careful.");
+        assert_eq!(agent.model(), Some(&Model::Sonnet));
+        assert_eq!(agent.tools(), ["Grep"]);
+
+        let all = Agent::new("all", "Anything.").extends(&base);
+        assert!(all.tools().is_empty());
+    }
+
+    #[test]
+    fn test_variables_in_order_of_first_use() {
+        let agent = Agent::new(
+            "Works on {{ ticket }}",
+            "Check out {{branch}} and close {{ticket}}. Leave {{not a var}} and {{}} alone.",
+        );
+        assert_eq!(agent.variables(), ["ticket", "branch"]);
+    }
+
+    #[test]
+    fn test_resolve_fills_known_variables() {
+        let agent = Agent::new(
+            "Works on {{ticket}}",
+            "Push to {{branch}} for {{ ticket }}.",
+        )
+        .with_tools(["Bash"]);
+        let variables = HashMap::from([("ticket".to_owned(), "ENG-42".to_owned())]);
+
+        let resolved = agent.resolve(&variables);
+        assert_eq!(resolved.description(), "Works on ENG-42");
+        assert_eq!(resolved.prompt(), "Push to {{branch}} for ENG-42.");
+        assert_eq!(resolved.tools(), ["Bash"]);
+    }
 }
@@ -0,0 +1,545 @@
+//! Human-in-the-loop approval of tool calls.
+//!
+//! An [`ApprovalBroker`] set with [`Options::approvals`](crate::Options::approvals)
+//! receives every tool call the CLI would otherwise prompt the user about,
+//! and every [`PreToolUseDecision::Ask`](crate::PreToolUseDecision::Ask)
+//! returned by a hook, and forwards it to an [`Approver`]: a terminal prompt
+//! ([`CliApprover`]), an HTTP endpoint ([`WebhookApprover`], with the
+//! `webhooks` feature) or any async function ([`ApprovalBroker::from_fn`]).
+//!
+//! Approvers that take longer than the broker's timeout, or fail, get the
+//! broker's default decision, which denies the call unless changed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use clauders::approvals::{ApprovalBroker, CliApprover};
+//! use clauders::{Client, Decision, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let broker = ApprovalBroker::new(CliApprover::new())
+//!     .timeout(Duration::from_secs(60))
+//!     .on_timeout(Decision::deny("nobody approved the call in time"));
+//! let client = Client::new(Options::new().approvals(broker)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{Value, json};
+
+use crate::error::Error;
+use crate::hooks::PreToolUseOutput;
+use crate::permissions::{Decision, PermissionContext, PermissionRule, PermissionUpdate};
+use crate::tool::ToolInput;
+
+/// A tool call waiting for approval.
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    tool_name: String,
+    input: ToolInput,
+    reason: Option<String>,
+    blocked_path: Option<String>,
+    suggested_rules: Vec<PermissionRule>,
+    suggestions: Vec<PermissionUpdate>,
+}
+
+impl ApprovalRequest {
+    pub fn new(tool_name: impl Into<String>, input: ToolInput) -> Self {
+        Self {
+            tool_name: tool_name.into(),
+            input,
+            reason: None,
+            blocked_path: None,
+            suggested_rules: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub(crate) fn from_context(context: &PermissionContext) -> Self {
+        let mut approval = Self::new(context.tool_name(), context.input().clone());
+        approval.blocked_path = context.blocked_path().map(str::to_owned);
+        approval.reason = context
+            .extra()
+            .get("decision_reason")
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+        approval.suggested_rules = context.suggested_rules().to_vec();
+        approval.suggestions = context.suggestions().to_vec();
+        approval
+    }
+
+    // Getters
+    pub fn tool_name(&self) -> &str {
+        &self.tool_name
+    }
+
+    pub fn input(&self) -> &ToolInput {
+        &self.input
+    }
+
+    /// Why approval is needed, such as the reason given by a hook that
+    /// asked for it.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// The path outside the allowed directories that the call touches.
+    pub fn blocked_path(&self) -> Option<&str> {
+        self.blocked_path.as_deref()
+    }
+
+    pub fn suggested_rules(&self) -> &[PermissionRule] {
+        &self.suggested_rules
+    }
+
+    /// The permission updates the CLI suggests. Return the accepted ones
+    /// with [`Decision::allow_with_updates`] to apply them.
+    pub fn suggestions(&self) -> &[PermissionUpdate] {
+        &self.suggestions
+    }
+
+    // Builders
+    #[must_use]
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_blocked_path(mut self, path: impl Into<String>) -> Self {
+        self.blocked_path = Some(path.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_suggested_rules(mut self, rules: Vec<PermissionRule>) -> Self {
+        self.suggested_rules = rules;
+        self
+    }
+
+    #[must_use]
+    pub fn with_suggestions(mut self, suggestions: Vec<PermissionUpdate>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    #[cfg(feature = "webhooks")]
+    fn to_json(&self) -> Value {
+        json!({
+            "tool_name": self.tool_name,
+            "input": self.input.as_value(),
+            "reason": self.reason,
+            "blocked_path": self.blocked_path,
+        })
+    }
+}
+
+/// Something that decides whether a tool call may go ahead.
+#[async_trait]
+pub trait Approver: Send + Sync {
+    async fn approve(&self, request: &ApprovalRequest) -> Result<Decision, Error>;
+}
+
+/// An [`Approver`] backed by an async function.
+pub struct FnApprover<F>(F);
+
+#[async_trait]
+impl<F, Fut> Approver for FnApprover<F>
+where
+    F: Fn(ApprovalRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = Decision> + Send,
+{
+    async fn approve(&self, request: &ApprovalRequest) -> Result<Decision, Error> {
+        Ok((self.0)(request.clone()).await)
+    }
+}
+
+/// Asks on the terminal: describes the call on stderr and reads `y` or `n`
+/// from stdin.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CliApprover;
+
+impl CliApprover {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn prompt(request: &ApprovalRequest) -> String {
+        let mut text = format!(
+            "Claude wants to use {}: {}\n",
+            request.tool_name,
+            request.input.as_value()
+        );
+        if let Some(reason) = &request.reason {
+            text.push_str(&format!("Reason: {reason}\n"));
+        }
+        if let Some(path) = &request.blocked_path {
+            text.push_str(&format!("Outside the allowed directories: {path}\n"));
+        }
+        text.push_str("Allow? [y/N] ");
+        text
+    }
+}
+
+#[async_trait]
+impl Approver for CliApprover {
+    async fn approve(&self, request: &ApprovalRequest) -> Result<Decision, Error> {
+        let prompt = Self::prompt(request);
+        let answer = tokio::task::spawn_blocking(move || -> std::io::Result<String> {
+            let mut stderr = std::io::stderr().lock();
+            stderr.write_all(prompt.as_bytes())?;
+            stderr.flush()?;
+            let mut answer = String::new();
+            std::io::stdin().lock().read_line(&mut answer)?;
+            Ok(answer)
+        })
+        .await
+        .map_err(|e| Error::Io(std::io::Error::other(e)))??;
+
+        Ok(match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => Decision::allow(),
+            _ => Decision::deny("The user denied this tool call."),
+        })
+    }
+}
+
+/// Asks an HTTP endpoint.
+///
+/// The request is POSTed as `{"tool_name", "input", "reason", "blocked_path"}`
+/// and the endpoint answers with `{"decision": "allow" | "deny", "message"?,
+/// "updated_input"?, "interrupt"?}`. The request is held open until someone
+/// decides, so the endpoint must answer within the broker's timeout.
+#[cfg(feature = "webhooks")]
+#[derive(Debug, Clone)]
+pub struct WebhookApprover {
+    url: String,
+    http: reqwest::Client,
+}
+
+#[cfg(feature = "webhooks")]
+impl WebhookApprover {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    fn decision(body: &Value) -> Result<Decision, Error> {
+        let message = body["message"].as_str().unwrap_or("Denied by approver.");
+        match body["decision"].as_str() {
+            Some("allow") => Ok(match body.get("updated_input") {
+                Some(input) if !input.is_null() => Decision::allow_with_input(input.clone().into()),
+                _ => Decision::allow(),
+            }),
+            Some("deny") if body["interrupt"].as_bool().unwrap_or_default() => {
+                Ok(Decision::deny_and_interrupt(message))
+            }
+            Some("deny") => Ok(Decision::deny(message)),
+            _ => Err(Error::ProtocolError(format!(
+                "approval webhook returned no decision: {body}"
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+#[async_trait]
+impl Approver for WebhookApprover {
+    async fn approve(&self, request: &ApprovalRequest) -> Result<Decision, Error> {
+        let body = self
+            .http
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(request.to_json().to_string())
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| Error::ConnectionError(format!("approval request failed: {e}")))?
+            .text()
+            .await
+            .map_err(|e| Error::ConnectionError(format!("approval request failed: {e}")))?;
+        Self::decision(&serde_json::from_str(&body)?)
+    }
+}
+
+/// Forwards approval requests to an [`Approver`], bounding how long it may
+/// take.
+#[derive(Clone)]
+pub struct ApprovalBroker {
+    approver: Arc<dyn Approver>,
+    timeout: Duration,
+    on_timeout: Decision,
+}
+
+impl ApprovalBroker {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+    pub fn new(approver: impl Approver + 'static) -> Self {
+        Self {
+            approver: Arc::new(approver),
+            timeout: Self::DEFAULT_TIMEOUT,
+            on_timeout: Decision::deny("The tool call was not approved in time."),
+        }
+    }
+
+    /// Approves calls with an async function.
+    pub fn from_fn<F, Fut>(approve: F) -> Self
+    where
+        F: Fn(ApprovalRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Decision> + Send + 'static,
+    {
+        Self::new(FnApprover(approve))
+    }
+
+    /// How long the approver has to decide. Defaults to five minutes.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The decision used when the approver times out or fails.
+    #[must_use]
+    pub fn on_timeout(mut self, decision: Decision) -> Self {
+        self.on_timeout = decision;
+        self
+    }
+
+    /// Asks the approver about `request`, waiting for a
+    /// [deferred](Decision::Deferred) decision. Always returns an allow or a
+    /// deny.
+    pub async fn decide(&self, request: &ApprovalRequest) -> Decision {
+        let approve = async {
+            match self.approver.approve(request).await? {
+                Decision::Deferred(deferred) => Ok(deferred.wait().await),
+                decision => Ok::<_, Error>(decision),
+            }
+        };
+        match tokio::time::timeout(self.timeout, approve).await {
+            Ok(Ok(Decision::Ask { reason })) => {
+                tracing::warn!(tool = %request.tool_name, %reason, "approver asked instead of deciding");
+                self.on_timeout.clone()
+            }
+            Ok(Ok(decision)) => decision,
+            Ok(Err(e)) => {
+                tracing::warn!(tool = %request.tool_name, error = %e, "approver failed");
+                self.on_timeout.clone()
+            }
+            Err(_) => {
+                tracing::warn!(tool = %request.tool_name, timeout = ?self.timeout, "approval timed out");
+                self.on_timeout.clone()
+            }
+        }
+    }
+
+    /// Resolves a hook's [`Ask`](crate::PreToolUseDecision::Ask) into an
+    /// allow or deny.
+    pub(crate) async fn resolve_ask(
+        &self,
+        tool_name: &str,
+        input: ToolInput,
+        output: PreToolUseOutput,
+    ) -> PreToolUseOutput {
+        let mut request =
+            ApprovalRequest::new(tool_name, output.updated_input().cloned().unwrap_or(input));
+        if let Some(reason) = output.reason() {
+            request = request.with_reason(reason);
+        }
+        match self.decide(&request).await {
+            Decision::Allow { updated_input, .. } => {
+                let mut output = PreToolUseOutput::allow();
+                if let Some(input) = updated_input {
+                    output.set_updated_input(input);
+                }
+                output
+            }
+            Decision::Deny { message, .. } | Decision::Ask { reason: message } => {
+                PreToolUseOutput::deny(message)
+            }
+            // The broker waits for deferred decisions.
+            Decision::Deferred(_) => PreToolUseOutput::deny("The tool call was not decided."),
+        }
+    }
+}
+
+impl fmt::Debug for ApprovalBroker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApprovalBroker")
+            .field("timeout", &self.timeout)
+            .field("on_timeout", &self.on_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The `can_use_tool` response body for `decision`.
+pub(crate) fn permission_response(decision: Decision, input: &Value) -> Value {
+    match decision {
+        Decision::Allow {
+            updated_input,
+            updated_permissions,
+        } => {
+            let mut response = json!({
+                "behavior": "allow",
+                "updatedInput": updated_input.map_or_else(|| input.clone(), ToolInput::into_value),
+            });
+            if !updated_permissions.is_empty() {
+                response["updatedPermissions"] = json!(updated_permissions);
+            }
+            response
+        }
+        Decision::Deny { message, interrupt } => json!({
+            "behavior": "deny",
+            "message": message,
+            "interrupt": interrupt,
+        }),
+        // Nobody was left to ask.
+        Decision::Ask { reason } => json!({
+            "behavior": "deny",
+            "message": reason,
+            "interrupt": false,
+        }),
+        Decision::Deferred(_) => json!({
+            "behavior": "deny",
+            "message": "The tool call was not decided.",
+            "interrupt": false,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hooks::PreToolUseDecision;
+    use crate::permissions::DeferredDecision;
+    use crate::proto::control::PermissionRequest;
+
+    /// Answers a `can_use_tool` request with the broker, as the client does.
+    async fn respond(broker: &ApprovalBroker, request: &PermissionRequest) -> Value {
+        let context = PermissionContext::from_request(request);
+        let decision = broker
+            .decide(&ApprovalRequest::from_context(&context))
+            .await;
+        permission_response(decision, request.input())
+    }
+
+    #[tokio::test]
+    async fn test_broker_decides_and_times_out() {
+        let broker = ApprovalBroker::from_fn(|request: ApprovalRequest| async move {
+            if request.tool_name() == "Bash" {
+                Decision::deny("no shell")
+            } else {
+                Decision::allow()
+            }
+        });
+
+        let request = PermissionRequest::new("Bash", json!({"command": "ls"}));
+        assert_eq!(
+            respond(&broker, &request).await,
+            json!({"behavior": "deny", "message": "no shell", "interrupt": false})
+        );
+        let request = PermissionRequest::new("Read", json!({"file_path": "a.rs"}));
+        assert_eq!(
+            respond(&broker, &request).await,
+            json!({"behavior": "allow", "updatedInput": {"file_path": "a.rs"}})
+        );
+
+        let output = broker
+            .resolve_ask(
+                "Write",
+                json!({"file_path": "a.rs"}).into(),
+                PreToolUseOutput::ask("writes outside src"),
+            )
+            .await;
+        assert_eq!(output.decision(), Some(PreToolUseDecision::Allow));
+
+        let slow = ApprovalBroker::from_fn(|_| async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Decision::allow()
+        })
+        .timeout(Duration::from_millis(10));
+        let Decision::Deny { message, .. } = slow
+            .decide(&ApprovalRequest::new("Bash", json!({}).into()))
+            .await
+        else {
+            panic!("expected the default decision");
+        };
+        assert!(message.contains("in time"));
+    }
+
+    #[tokio::test]
+    async fn test_broker_returns_accepted_suggestions() {
+        let request: PermissionRequest = serde_json::from_value(json!({
+            "tool_name": "Bash",
+            "input": {"command": "npm test"},
+            "permission_suggestions": [
+                {
+                    "type": "addRules",
+                    "rules": [{"toolName": "Bash", "ruleContent": "npm test:*"}],
+                    "behavior": "allow",
+                    "destination": "localSettings"
+                },
+                {"type": "setMode", "mode": "acceptEdits", "destination": "session"},
+                {"type": "somethingNew"}
+            ]
+        }))
+        .unwrap();
+        let approval = ApprovalRequest::from_context(&PermissionContext::from_request(&request));
+        assert_eq!(approval.suggestions().len(), 3);
+        assert_eq!(approval.suggestions()[2], PermissionUpdate::Other);
+        assert_eq!(
+            approval.suggested_rules(),
+            [PermissionRule::new("Bash").with_rule("npm test:*")]
+        );
+
+        let broker = ApprovalBroker::from_fn(|request: ApprovalRequest| async move {
+            Decision::allow_with_updates(request.suggestions()[..1].to_vec())
+        });
+        assert_eq!(
+            respond(&broker, &request).await,
+            json!({
+                "behavior": "allow",
+                "updatedInput": {"command": "npm test"},
+                "updatedPermissions": [{
+                    "type": "addRules",
+                    "rules": [{"toolName": "Bash", "ruleContent": "npm test:*"}],
+                    "behavior": "allow",
+                    "destination": "localSettings"
+                }]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broker_waits_for_deferred_decisions() {
+        let (deferred, sender) = DeferredDecision::channel();
+        let broker = ApprovalBroker::from_fn(move |_| {
+            let deferred = deferred.clone();
+            async move { Decision::Deferred(deferred) }
+        });
+        let request = ApprovalRequest::new("Bash", json!({"command": "ls"}).into());
+        let (decision, ()) = tokio::join!(broker.decide(&request), async {
+            sender.send(Decision::deny("reviewed")).unwrap();
+        });
+        let Decision::Deny { message, .. } = decision else {
+            panic!("expected the reviewer's decision");
+        };
+        assert_eq!(message, "reviewed");
+
+        let asking = ApprovalBroker::from_fn(|_| async { Decision::ask("not sure") })
+            .on_timeout(Decision::deny("default"));
+        let Decision::Deny { message, .. } = asking.decide(&request).await else {
+            panic!("expected the default decision");
+        };
+        assert_eq!(message, "default");
+    }
+}
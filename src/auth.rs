@@ -0,0 +1,296 @@
+//! Choosing how the CLI authenticates with the Anthropic API.
+//!
+//! Left alone, the CLI uses whatever it finds: `ANTHROPIC_API_KEY` if the
+//! service happens to have it set, otherwise the login saved by
+//! `claude login`. [`Options::auth`](crate::Options::auth) pins the source
+//! instead, clearing the variables of the other sources from the CLI's
+//! environment, and [`Options::validate`](crate::Options::validate) fails with
+//! [`ConfigError::MissingCredentials`] when the chosen source has nothing to
+//! offer.
+//!
+//! Credentials can also expire mid-session, such as an OAuth token reaching
+//! the end of its lifetime. With [`Options::refresh_auth`](crate::Options::refresh_auth),
+//! a conversation turn that fails with
+//! [`AssistantError::AuthenticationFailed`](crate::AssistantError::AuthenticationFailed)
+//! asks an [`AuthRefresh`] for new credentials, restarts the CLI resuming
+//! the same session and sends the turn again. A turn the CLI had already
+//! started answering is not sent again, since the session holds it; it ends
+//! with what it got and the next turn runs on the new credentials.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::auth::AuthSource;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! // Bill the service's key, kept under a name the CLI does not read.
+//! let options = Options::new().auth(AuthSource::ApiKeyEnv("ACME_ANTHROPIC_KEY".to_owned()));
+//! let client = Client::new(options).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+use crate::error::{ConfigError, Error};
+
+const API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+const OAUTH_TOKEN_ENV: &str = "CLAUDE_CODE_OAUTH_TOKEN";
+
+/// Where the CLI gets its credentials.
+#[derive(Clone, PartialEq, Eq)]
+pub enum AuthSource {
+    /// An API key passed directly.
+    ApiKey(String),
+    /// An API key read from the named variable of this process's
+    /// environment.
+    ApiKeyEnv(String),
+    /// A long-lived OAuth token from `claude setup-token`, in
+    /// `CLAUDE_CODE_OAUTH_TOKEN`.
+    OAuth,
+    /// The login saved by `claude login`, in the macOS keychain or the
+    /// CLI's credentials file.
+    Keychain,
+}
+
+impl std::fmt::Debug for AuthSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ApiKey(_) => f.write_str("ApiKey([redacted])"),
+            Self::ApiKeyEnv(name) => f.debug_tuple("ApiKeyEnv").field(name).finish(),
+            Self::OAuth => f.write_str("OAuth"),
+            Self::Keychain => f.write_str("Keychain"),
+        }
+    }
+}
+
+impl AuthSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ApiKey(_) => "api key",
+            Self::ApiKeyEnv(_) => "api key variable",
+            Self::OAuth => "oauth token",
+            Self::Keychain => "keychain",
+        }
+    }
+
+    /// The environment variables for the CLI process.
+    pub(crate) fn env(&self) -> Vec<(String, String)> {
+        let key = match self {
+            Self::ApiKey(key) => Some(key.clone()),
+            Self::ApiKeyEnv(name) => std::env::var(name).ok(),
+            Self::OAuth | Self::Keychain => None,
+        };
+        key.map(|key| (API_KEY_ENV.to_owned(), key))
+            .into_iter()
+            .collect()
+    }
+
+    /// Variables of the other sources, removed from the CLI's environment so
+    /// it cannot fall back to them.
+    pub(crate) fn removed_env(&self) -> Vec<String> {
+        let names: &[&str] = match self {
+            Self::ApiKey(_) | Self::ApiKeyEnv(_) => &[OAUTH_TOKEN_ENV],
+            Self::OAuth => &[API_KEY_ENV],
+            Self::Keychain => &[API_KEY_ENV, OAUTH_TOKEN_ENV],
+        };
+        names.iter().map(|name| (*name).to_owned()).collect()
+    }
+
+    /// Checks that the source has credentials, looking in `env`, the
+    /// variables set for the CLI on top of the process environment.
+    pub(crate) fn validate(&self, env: &[(String, String)]) -> Result<(), ConfigError> {
+        let missing = |reason: String| ConfigError::MissingCredentials {
+            auth: self.name().to_owned(),
+            reason,
+        };
+        match self {
+            Self::ApiKey(key) if key.is_empty() => Err(missing("the API key is empty".to_owned())),
+            Self::ApiKey(_) => Ok(()),
+            Self::ApiKeyEnv(name) => match std::env::var(name) {
+                Ok(key) if !key.is_empty() => Ok(()),
+                _ => Err(missing(format!("{name} is not set"))),
+            },
+            Self::OAuth => {
+                let set = env
+                    .iter()
+                    .any(|(key, value)| key == OAUTH_TOKEN_ENV && !value.is_empty())
+                    || std::env::var_os(OAUTH_TOKEN_ENV).is_some_and(|value| !value.is_empty());
+                if set {
+                    return Ok(());
+                }
+                Err(missing(format!(
+                    "{OAUTH_TOKEN_ENV} is not set; create a token with `claude setup-token`"
+                )))
+            }
+            // The keychain cannot be inspected without prompting the user.
+            Self::Keychain if cfg!(target_os = "macos") => Ok(()),
+            Self::Keychain => {
+                if credentials_file().is_some_and(|path| path.is_file()) {
+                    return Ok(());
+                }
+                Err(missing(
+                    "no saved login found; run `claude login` as this user".to_owned(),
+                ))
+            }
+        }
+    }
+}
+
+/// Callback returning the variables to restart the CLI with once its
+/// credentials have expired.
+pub type RefreshCallback =
+    Arc<dyn Fn() -> BoxFuture<'static, Result<Vec<(String, String)>, Error>> + Send + Sync>;
+
+/// How a conversation recovers from expired credentials.
+///
+/// Without a callback the CLI is simply restarted, which picks up a login
+/// renewed elsewhere, such as by `claude login` or a credentials file
+/// rotated by another process.
+///
+/// ```no_run
+/// use clauders::auth::AuthRefresh;
+/// use clauders::Options;
+///
+/// # async fn fetch_token() -> Result<String, clauders::Error> { unimplemented!() }
+/// let options = Options::new().refresh_auth(AuthRefresh::new().on_refresh(|| async {
+///     let token = fetch_token().await?;
+///     Ok(vec![("CLAUDE_CODE_OAUTH_TOKEN".to_owned(), token)])
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct AuthRefresh {
+    callback: Option<RefreshCallback>,
+    max_retries: u32,
+}
+
+impl std::fmt::Debug for AuthRefresh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthRefresh")
+            .field("callback", &self.callback.is_some())
+            .field("max_retries", &self.max_retries)
+            .finish()
+    }
+}
+
+impl AuthRefresh {
+    pub const DEFAULT_MAX_RETRIES: u32 = 1;
+
+    pub fn new() -> Self {
+        Self {
+            callback: None,
+            max_retries: Self::DEFAULT_MAX_RETRIES,
+        }
+    }
+
+    /// Calls `f` for fresh credentials before restarting the CLI. The
+    /// variables it returns replace those the CLI was started with; an
+    /// error fails the turn.
+    #[must_use]
+    pub fn on_refresh<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<(String, String)>, Error>> + Send + 'static,
+    {
+        self.callback = Some(Arc::new(move || Box::pin(f())));
+        self
+    }
+
+    /// Retries a turn at most `retries` times before returning its failed
+    /// responses.
+    #[must_use]
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// The variables to restart the CLI with.
+    pub(crate) async fn refresh(&self) -> Result<Vec<(String, String)>, Error> {
+        match &self.callback {
+            Some(callback) => callback().await,
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Default for AuthRefresh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The file `claude login` saves credentials to outside macOS.
+fn credentials_file() -> Option<PathBuf> {
+    let dir = match std::env::var_os("CLAUDE_CONFIG_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".claude"),
+    };
+    Some(dir.join(".credentials.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_source_env_and_validation() {
+        let key = AuthSource::ApiKey("sk-ant-secret".to_owned());
+        assert_eq!(
+            key.env(),
+            [("ANTHROPIC_API_KEY".to_owned(), "sk-ant-secret".to_owned())]
+        );
+        assert_eq!(key.removed_env(), ["CLAUDE_CODE_OAUTH_TOKEN"]);
+        assert!(!format!("{key:?}").contains("secret"));
+        assert!(key.validate(&[]).is_ok());
+
+        let unset = AuthSource::ApiKeyEnv(format!(
+            "CLAUDERS_UNSET_{}",
+            crate::util::uuid_v7().replace('-', "")
+        ));
+        assert!(unset.env().is_empty());
+        assert!(matches!(
+            unset.validate(&[]),
+            Err(ConfigError::MissingCredentials { .. })
+        ));
+
+        let token = [("CLAUDE_CODE_OAUTH_TOKEN".to_owned(), "t".to_owned())];
+        assert!(AuthSource::OAuth.validate(&token).is_ok());
+        assert_eq!(AuthSource::OAuth.removed_env(), ["ANTHROPIC_API_KEY"]);
+    }
+
+    #[tokio::test]
+    async fn test_auth_refresh_restarts_with_new_env() {
+        assert!(AuthRefresh::new().refresh().await.unwrap().is_empty());
+        let refresh = AuthRefresh::new().max_retries(2).on_refresh(|| async {
+            Ok(vec![(
+                "CLAUDE_CODE_OAUTH_TOKEN".to_owned(),
+                "new".to_owned(),
+            )])
+        });
+        assert_eq!(refresh.retries(), 2);
+        let env = refresh.refresh().await.unwrap();
+
+        let mut options = crate::Options::new()
+            .env([("CLAUDE_CODE_OAUTH_TOKEN", "old"), ("LOG", "1")])
+            .resume("session-1")
+            .to_transport_options();
+        options.resume_with(Some("session-2".to_owned()), env);
+        assert_eq!(
+            options.env(),
+            [
+                ("LOG".to_owned(), "1".to_owned()),
+                ("CLAUDE_CODE_OAUTH_TOKEN".to_owned(), "new".to_owned())
+            ]
+        );
+        assert_eq!(options.resume(), Some("session-2"));
+    }
+}
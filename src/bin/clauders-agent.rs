@@ -0,0 +1,219 @@
+//! A headless coding agent built on the library.
+//!
+//! Runs a task in a repository, prints the agent's text and tool calls, and
+//! ends with the session id to pass to `--resume` for a follow-up. Meant as
+//! a starting point to fork as much as a tool.
+//!
+//! Run with:
+//! ```sh
+//! cargo run --features agent-bin --bin clauders-agent -- \
+//!     --cwd path/to/repo --budget 2 --policy policy.toml "Fix the failing test"
+//! ```
+//!
+//! Without a task on the command line, the task is read from stdin. Exits
+//! with a non-zero status when the session ends in an error, such as
+//! running out of budget or turns.
+
+use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clauders::logging::{TracingConfig, init_tracing};
+use clauders::policy::Policy;
+use clauders::{Client, ConsoleReporter, Options, PermissionMode, dispatch};
+use tracing::Level;
+
+const USAGE: &str = "\
+Usage: clauders-agent [OPTIONS] [TASK]...
+
+Options:
+  --cwd <DIR>          Run in DIR instead of the current directory
+  --model <MODEL>      The model to use, such as sonnet or opus
+  --resume <SESSION>   Continue the session SESSION
+  --budget <USD>       Stop once the session has cost USD
+  --max-turns <N>      Stop after N agentic turns
+  --policy <FILE>      Apply the TOML or JSON policy in FILE
+  --plan               Only plan the change, without editing files
+  --no-color           Print without ANSI colors
+  -v, --verbose        Print the library's diagnostics to stderr
+  -h, --help           Print this help";
+
+#[derive(Debug, Default)]
+struct Args {
+    cwd: Option<PathBuf>,
+    model: Option<String>,
+    resume: Option<String>,
+    budget: Option<f64>,
+    max_turns: Option<u32>,
+    policy: Option<PathBuf>,
+    plan: bool,
+    no_color: bool,
+    verbose: bool,
+    help: bool,
+    task: Vec<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+            match arg.as_str() {
+                "--cwd" => parsed.cwd = Some(value("--cwd")?.into()),
+                "--model" => parsed.model = Some(value("--model")?),
+                "--resume" => parsed.resume = Some(value("--resume")?),
+                "--budget" => {
+                    let budget = value("--budget")?;
+                    parsed.budget = Some(
+                        budget
+                            .parse()
+                            .map_err(|_| format!("invalid budget: {budget}"))?,
+                    );
+                }
+                "--max-turns" => {
+                    let turns = value("--max-turns")?;
+                    parsed.max_turns = Some(
+                        turns
+                            .parse()
+                            .map_err(|_| format!("invalid turn count: {turns}"))?,
+                    );
+                }
+                "--policy" => parsed.policy = Some(value("--policy")?.into()),
+                "--plan" => parsed.plan = true,
+                "--no-color" => parsed.no_color = true,
+                "-v" | "--verbose" => parsed.verbose = true,
+                "-h" | "--help" => parsed.help = true,
+                "--" => parsed.task.extend(args.by_ref()),
+                flag if flag.starts_with('-') => return Err(format!("unknown option: {flag}")),
+                _ => parsed.task.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn options(&self, policy: Option<&Policy>) -> Options {
+        let mut options = Options::new().permission_mode(if self.plan {
+            PermissionMode::Plan
+        } else {
+            PermissionMode::AcceptEdits
+        });
+        if let Some(cwd) = &self.cwd {
+            options = options.cwd(cwd);
+        }
+        if let Some(model) = self.model.as_deref() {
+            options = options.model(model);
+        }
+        if let Some(session) = &self.resume {
+            options = options.resume(session);
+        }
+        if let Some(budget) = self.budget {
+            options = options.max_budget_usd(budget);
+        }
+        if let Some(turns) = self.max_turns {
+            options = options.max_turns(turns);
+        }
+        if let Some(policy) = policy {
+            options = options.policy(policy);
+        }
+        options
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(args).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if args.help {
+        println!("{USAGE}");
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.verbose {
+        init_tracing(TracingConfig::new().level(Level::DEBUG))?;
+    }
+
+    let task = if args.task.is_empty() {
+        let mut task = String::new();
+        io::stdin().read_to_string(&mut task)?;
+        task
+    } else {
+        args.task.join(" ")
+    };
+    let task = task.trim();
+    if task.is_empty() {
+        eprintln!("no task given\n\n{USAGE}");
+        return Ok(ExitCode::from(2));
+    }
+
+    let policy = args.policy.as_ref().map(Policy::from_file).transpose()?;
+    let client = Client::new(args.options(policy.as_ref())).await?;
+    let mut conv = client.conversation();
+    if let Some(policy) = &policy {
+        conv = conv.with_guardrails(policy.guardrails());
+    }
+
+    let reporter = ConsoleReporter::new().colors(!args.no_color && io::stdout().is_terminal());
+    let responses = conv.turn(task).send().await?;
+    for response in &responses {
+        dispatch(&reporter, response).await;
+    }
+
+    let Some(completion) = responses.completion() else {
+        return Err("the session ended without a result".into());
+    };
+    eprintln!();
+    eprintln!(
+        "session {} ({} turns, ${:.4})",
+        completion.session_id(),
+        completion.num_turns(),
+        conv.total_cost_usd()
+    );
+    eprintln!(
+        "continue with: clauders-agent --resume {} <TASK>",
+        completion.session_id()
+    );
+
+    Ok(if completion.is_error() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| (*arg).to_owned()))
+    }
+
+    #[test]
+    fn test_parses_flags_and_task() {
+        let args = parse(&[
+            "--budget", "2.5", "--resume", "abc", "Fix", "the", "--", "--bug",
+        ])
+        .unwrap();
+        assert_eq!(args.budget, Some(2.5));
+        assert_eq!(args.resume.as_deref(), Some("abc"));
+        assert_eq!(args.task, ["Fix", "the", "--bug"]);
+
+        assert!(parse(&["--budget"]).is_err());
+        assert!(parse(&["--max-turns", "many"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! Content block boundaries in streamed partial messages.
+//!
+//! With [`Options::include_partial_messages`](crate::Options::include_partial_messages),
+//! a message arrives as raw [`PartialResponse`] events whose deltas only
+//! carry the index of the block they extend. A [`BlockTracker`] turns them
+//! into [`BlockEvent`]s saying when each text, thinking or tool block starts
+//! and ends, so a UI can open a collapsible for thinking, close it when the
+//! thinking ends and render the text that follows in its own section.
+//! [`TurnBuilder::on_block`](crate::TurnBuilder::on_block) tracks the blocks
+//! of a turn.
+
+use std::collections::HashMap;
+
+use crate::response::PartialResponse;
+
+/// The kind of a content block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockKind {
+    Text,
+    Thinking,
+    ToolUse,
+    /// A block of a type this crate does not model.
+    Other,
+}
+
+impl BlockKind {
+    fn from_type(block_type: &str) -> Self {
+        match block_type {
+            "text" => Self::Text,
+            "thinking" | "redacted_thinking" => Self::Thinking,
+            "tool_use" | "server_tool_use" => Self::ToolUse,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// A content block starting, growing or ending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockEvent<'a> {
+    Start {
+        index: u64,
+        kind: BlockKind,
+    },
+    /// Text, thinking or a fragment of a tool call's JSON input appended to
+    /// the block.
+    Delta {
+        index: u64,
+        kind: BlockKind,
+        content: &'a str,
+    },
+    Stop {
+        index: u64,
+        kind: BlockKind,
+    },
+}
+
+impl BlockEvent<'_> {
+    /// The position of the block in its message.
+    pub fn index(&self) -> u64 {
+        match *self {
+            Self::Start { index, .. } | Self::Delta { index, .. } | Self::Stop { index, .. } => {
+                index
+            }
+        }
+    }
+
+    pub fn kind(&self) -> BlockKind {
+        match *self {
+            Self::Start { kind, .. } | Self::Delta { kind, .. } | Self::Stop { kind, .. } => kind,
+        }
+    }
+}
+
+/// Follows the blocks of the main agent's streamed messages.
+///
+/// Events of subagents, which stream their own messages with their own
+/// block indices, are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct BlockTracker {
+    open: HashMap<u64, BlockKind>,
+}
+
+impl BlockTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The block event `partial` stands for, if any.
+    pub fn update<'a>(&mut self, partial: &'a PartialResponse) -> Option<BlockEvent<'a>> {
+        if partial.parent_tool_use_id().is_some() {
+            return None;
+        }
+        match partial.event_type()? {
+            "message_start" => {
+                self.open.clear();
+                None
+            }
+            "content_block_start" => {
+                let index = partial.index()?;
+                let block_type = partial.event().get("content_block")?.get("type")?;
+                let kind = BlockKind::from_type(block_type.as_str()?);
+                self.open.insert(index, kind);
+                Some(BlockEvent::Start { index, kind })
+            }
+            "content_block_delta" => {
+                let index = partial.index()?;
+                let delta = partial.event().get("delta")?;
+                let (kind, field) = match delta.get("type")?.as_str()? {
+                    "text_delta" => (BlockKind::Text, "text"),
+                    "thinking_delta" => (BlockKind::Thinking, "thinking"),
+                    "input_json_delta" => (BlockKind::ToolUse, "partial_json"),
+                    _ => return None,
+                };
+                let content = delta.get(field)?.as_str()?;
+                Some(BlockEvent::Delta {
+                    index,
+                    kind,
+                    content,
+                })
+            }
+            "content_block_stop" => {
+                let index = partial.index()?;
+                let kind = self.open.remove(&index).unwrap_or(BlockKind::Other);
+                Some(BlockEvent::Stop { index, kind })
+            }
+            _ => None,
+        }
+    }
+
+    /// The kinds of the blocks started and not yet stopped, by index.
+    pub fn open(&self) -> &HashMap<u64, BlockKind> {
+        &self.open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_tracks_interleaved_thinking_and_text() {
+        let partials = [
+            json!({"event": {"type": "message_start", "message": {}}}),
+            json!({"event": {"type": "content_block_start", "index": 0, "content_block": {"type": "thinking", "thinking": ""}}}),
+            json!({"event": {"type": "content_block_delta", "index": 0, "delta": {"type": "thinking_delta", "thinking": "Hmm."}}}),
+            json!({"event": {"type": "content_block_delta", "index": 0, "delta": {"type": "signature_delta", "signature": "sig"}}}),
+            json!({"event": {"type": "content_block_stop", "index": 0}}),
+            json!({"event": {"type": "content_block_start", "index": 1, "content_block": {"type": "text", "text": ""}}}),
+            json!({"event": {"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Nested"}}, "parent_tool_use_id": "task_1"}),
+            json!({"event": {"type": "content_block_delta", "index": 1, "delta": {"type": "text_delta", "text": "Done"}}}),
+            json!({"event": {"type": "content_block_stop", "index": 1}}),
+            json!({"event": {"type": "message_stop"}}),
+        ]
+        .map(|value| serde_json::from_value::<PartialResponse>(value).unwrap());
+
+        let mut tracker = BlockTracker::new();
+        let events = partials
+            .iter()
+            .filter_map(|partial| tracker.update(partial))
+            .collect::<Vec<_>>();
+
+        use BlockEvent::*;
+        use BlockKind::*;
+        assert_eq!(
+            events,
+            [
+                Start {
+                    index: 0,
+                    kind: Thinking
+                },
+                Delta {
+                    index: 0,
+                    kind: Thinking,
+                    content: "Hmm."
+                },
+                Stop {
+                    index: 0,
+                    kind: Thinking
+                },
+                Start {
+                    index: 1,
+                    kind: Text
+                },
+                Delta {
+                    index: 1,
+                    kind: Text,
+                    content: "Done"
+                },
+                Stop {
+                    index: 1,
+                    kind: Text
+                },
+            ]
+        );
+        assert!(tracker.open().is_empty());
+    }
+}
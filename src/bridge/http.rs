@@ -0,0 +1,221 @@
+//! An HTTP server streaming a session's responses over server-sent events.
+//!
+//! | Route             | Description                                              |
+//! |-------------------|----------------------------------------------------------|
+//! | `POST /query`     | Starts a turn with `{"prompt": "..."}`; 409 while busy   |
+//! | `POST /interrupt` | Interrupts the running turn                              |
+//! | `GET /events`     | SSE stream of response events for every turn             |
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use clauders::{Client, Options};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let client = Arc::new(Client::new(Options::new()).await?);
+//!     clauders::bridge::http::serve(client, "127.0.0.1:3000").await?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::broadcast;
+
+use crate::client::Client;
+
+const EVENT_BUFFER: usize = 1024;
+
+#[derive(Clone)]
+struct BridgeState {
+    client: Arc<Client>,
+    events: broadcast::Sender<Value>,
+    busy: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryRequest {
+    prompt: String,
+}
+
+/// Builds the bridge's routes, for mounting into an existing application.
+pub fn router(client: Arc<Client>) -> Router {
+    let (events, _) = broadcast::channel(EVENT_BUFFER);
+    let state = BridgeState {
+        client,
+        events,
+        busy: Arc::new(AtomicBool::new(false)),
+    };
+
+    Router::new()
+        .route("/query", post(query))
+        .route("/interrupt", post(interrupt))
+        .route("/events", get(stream_events))
+        .with_state(state)
+}
+
+/// Serves the bridge on `addr` until the server fails.
+pub async fn serve(
+    client: Arc<Client>,
+    addr: impl tokio::net::ToSocketAddrs,
+) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(addr = ?listener.local_addr()?, "serving HTTP bridge");
+    axum::serve(listener, router(client)).await
+}
+
+async fn query(
+    State(state): State<BridgeState>,
+    Json(request): Json<QueryRequest>,
+) -> (StatusCode, Json<Value>) {
+    if state.busy.swap(true, Ordering::AcqRel) {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "a turn is already in progress"})),
+        );
+    }
+
+    if let Err(e) = state.client.query(&request.prompt).await {
+        state.busy.store(false, Ordering::Release);
+        return (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": e.to_string()})),
+        );
+    }
+
+    tokio::spawn(async move {
+        let mut stream = std::pin::pin!(state.client.receive());
+        while let Some(result) = stream.next().await {
+            let event = match result {
+                Ok(response) => response.to_json(),
+                Err(e) => json!({"type": "error", "message": e.to_string()}),
+            };
+            // Having no subscribers is fine; the event is simply dropped.
+            let _ = state.events.send(event);
+        }
+        state.busy.store(false, Ordering::Release);
+    });
+
+    (StatusCode::ACCEPTED, Json(json!({"accepted": true})))
+}
+
+async fn interrupt(State(state): State<BridgeState>) -> (StatusCode, Json<Value>) {
+    match state.client.interrupt().await {
+        Ok(()) => (StatusCode::OK, Json(json!({"interrupted": true}))),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": e.to_string()})),
+        ),
+    }
+}
+
+async fn stream_events(
+    State(state): State<BridgeState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.events.subscribe();
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(event) => Event::default().json_data(event).unwrap_or_default(),
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                Event::default().event("lagged").data(n.to_string())
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        };
+        Some((Ok(event), receiver))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::fake_cli::{FakeCli, assistant_text, result};
+    use crate::options::Options;
+
+    async fn post(addr: SocketAddr, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    /// Reads from `stream` until what has arrived so far contains `needle`.
+    async fn read_until(stream: &mut TcpStream, received: &mut String, needle: &str) {
+        let mut buf = [0; 4096];
+        while !received.contains(needle) {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "stream ended before {needle:?}: {received}");
+            received.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_streams_responses_as_events() {
+        // The turn ends only once interrupted.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{}'
+next
+case "$line" in *'"subtype":"interrupt"'*) reply '{{}}' ;; *) exit 1 ;; esac
+emit '{}'
+drain"#,
+            assistant_text("hello"),
+            result(),
+        ));
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, router(Arc::new(client))).into_future());
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            // Subscribe before the turn starts; the headers arrive once it has.
+            let mut events = TcpStream::connect(addr).await.unwrap();
+            events
+                .write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let mut received = String::new();
+            read_until(&mut events, &mut received, "\r\n\r\n").await;
+            assert!(received.starts_with("HTTP/1.1 200"), "{received}");
+            assert!(received.contains("text/event-stream"), "{received}");
+
+            let accepted = post(addr, "/query", r#"{"prompt":"hi"}"#).await;
+            assert!(accepted.starts_with("HTTP/1.1 202"), "{accepted}");
+            read_until(&mut events, &mut received, r#""content":"hello""#).await;
+
+            let busy = post(addr, "/query", r#"{"prompt":"again"}"#).await;
+            assert!(busy.starts_with("HTTP/1.1 409"), "{busy}");
+
+            let interrupted = post(addr, "/interrupt", "").await;
+            assert!(interrupted.starts_with("HTTP/1.1 200"), "{interrupted}");
+            read_until(&mut events, &mut received, r#""type":"complete""#).await;
+        })
+        .await
+        .unwrap();
+    }
+}
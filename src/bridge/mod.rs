@@ -0,0 +1,9 @@
+//! Bridges that expose a [`Client`](crate::Client) to other processes.
+//!
+//! Responses are forwarded as the JSON events produced by
+//! [`Response::to_json`](crate::Response::to_json).
+
+#[cfg(feature = "bridge-http")]
+pub mod http;
+#[cfg(feature = "bridge-ws")]
+pub mod ws;
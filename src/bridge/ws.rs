@@ -0,0 +1,181 @@
+//! A WebSocket server giving each connection its own session.
+//!
+//! Every connection to `GET /ws` spawns a fresh [`Client`] from the bridge's
+//! [`Options`]; closing the socket interrupts any running turn and drops
+//! the session. Clients send text frames holding one of:
+//!
+//! ```json
+//! {"type": "query", "prompt": "..."}
+//! {"type": "interrupt"}
+//! ```
+//!
+//! and receive each response of the running turn as a JSON text frame.
+//! Failures are reported as `{"type": "error", "message": "..."}` frames
+//! rather than by closing the socket.
+//!
+//! ```no_run
+//! use clauders::Options;
+//!
+//! #[tokio::main]
+//! async fn main() -> std::io::Result<()> {
+//!     clauders::bridge::ws::serve(Options::new(), "127.0.0.1:3000").await
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::Router;
+use axum::extract::State;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::Response as HttpResponse;
+use axum::routing::get;
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+
+use crate::client::Client;
+use crate::options::Options;
+
+const FRAME_BUFFER: usize = 256;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Command {
+    Query { prompt: String },
+    Interrupt,
+}
+
+/// Builds the bridge's routes, for mounting into an existing application.
+pub fn router(options: Options) -> Router {
+    Router::new()
+        .route("/ws", get(upgrade))
+        .with_state(Arc::new(options))
+}
+
+/// Serves the bridge on `addr` until the server fails.
+pub async fn serve(options: Options, addr: impl tokio::net::ToSocketAddrs) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(addr = ?listener.local_addr()?, "serving WebSocket bridge");
+    axum::serve(listener, router(options)).await
+}
+
+async fn upgrade(State(options): State<Arc<Options>>, ws: WebSocketUpgrade) -> HttpResponse {
+    ws.on_upgrade(move |socket| handle_socket(socket, Options::clone(&options)))
+}
+
+async fn handle_socket(socket: WebSocket, options: Options) {
+    let (mut sink, mut incoming) = socket.split();
+    let (frames, mut outgoing) = mpsc::channel::<Value>(FRAME_BUFFER);
+
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = outgoing.recv().await {
+            if sink
+                .send(Message::Text(frame.to_string().into()))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let client = match Client::new(options).await {
+        Ok(client) => Arc::new(client),
+        Err(e) => {
+            let _ = frames.send(error_frame(e)).await;
+            drop(frames);
+            let _ = writer.await;
+            return;
+        }
+    };
+    let busy = Arc::new(AtomicBool::new(false));
+
+    while let Some(Ok(message)) = incoming.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let command = match serde_json::from_str::<Command>(&text) {
+            Ok(command) => command,
+            Err(e) => {
+                let _ = frames.send(error_frame(e)).await;
+                continue;
+            }
+        };
+
+        match command {
+            Command::Query { prompt } => {
+                if busy.swap(true, Ordering::AcqRel) {
+                    let _ = frames
+                        .send(error_frame("a turn is already in progress"))
+                        .await;
+                    continue;
+                }
+                tokio::spawn(run_turn(
+                    Arc::clone(&client),
+                    prompt,
+                    frames.clone(),
+                    Arc::clone(&busy),
+                ));
+            }
+            Command::Interrupt => {
+                if let Err(e) = client.interrupt().await {
+                    let _ = frames.send(error_frame(e)).await;
+                }
+            }
+        }
+    }
+
+    tracing::debug!("WebSocket closed, ending session");
+    if busy.load(Ordering::Acquire) {
+        let _ = client.interrupt().await;
+    }
+    writer.abort();
+}
+
+async fn run_turn(
+    client: Arc<Client>,
+    prompt: String,
+    frames: mpsc::Sender<Value>,
+    busy: Arc<AtomicBool>,
+) {
+    if let Err(e) = client.query(&prompt).await {
+        let _ = frames.send(error_frame(e)).await;
+    } else {
+        let mut stream = std::pin::pin!(client.receive());
+        while let Some(result) = stream.next().await {
+            let frame = match result {
+                Ok(response) => response.to_json(),
+                Err(e) => error_frame(e),
+            };
+            if frames.send(frame).await.is_err() {
+                break;
+            }
+        }
+    }
+    busy.store(false, Ordering::Release);
+}
+
+fn error_frame(message: impl std::fmt::Display) -> Value {
+    json!({"type": "error", "message": message.to_string()})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let query: Command = serde_json::from_str(r#"{"type":"query","prompt":"hi"}"#).unwrap();
+        assert!(matches!(query, Command::Query { prompt } if prompt == "hi"));
+
+        let interrupt: Command = serde_json::from_str(r#"{"type":"interrupt"}"#).unwrap();
+        assert!(matches!(interrupt, Command::Interrupt));
+
+        assert!(serde_json::from_str::<Command>(r#"{"type":"shutdown"}"#).is_err());
+    }
+}
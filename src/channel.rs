@@ -0,0 +1,215 @@
+//! A channel-based front end for a [`Client`].
+//!
+//! [`Client::channel_pair`] moves the client into a background task and hands
+//! back a plain command sender and event receiver. This suits GUI event loops
+//! that poll channels rather than holding a response stream across awaits.
+//!
+//! ```no_run
+//! use clauders::channel::{Command, Event};
+//! use clauders::{Client, Options, Response};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let client = Client::new(Options::new()).await?;
+//! let (commands, mut events) = client.channel_pair();
+//!
+//! commands
+//!     .send(Command::Query("Hello".to_owned()))
+//!     .await
+//!     .expect("driver stopped");
+//!
+//! while let Some(event) = events.recv().await {
+//!     match event {
+//!         Event::Response(Response::Text(text)) => print!("{}", text.content()),
+//!         Event::Response(Response::Complete(_)) => break,
+//!         Event::Error(e) => eprintln!("{e}"),
+//!         _ => {}
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use tokio::sync::mpsc;
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::response::Response;
+
+const CHANNEL_CAPACITY: usize = 64;
+
+/// An instruction for the driver task.
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Sends a prompt. Prompts sent while a turn is running are queued and
+    /// started once it completes.
+    Query(String),
+    /// Steers the running turn with an additional message. Without a running
+    /// turn, this is queued like a prompt.
+    Steer(String),
+    /// Interrupts the running turn, if any.
+    Interrupt,
+}
+
+/// An event emitted by the driver task.
+#[derive(Debug)]
+pub enum Event {
+    Response(Response),
+    Error(Error),
+}
+
+impl Client {
+    /// Moves the client into a background task driven through channels.
+    ///
+    /// The task runs until the command sender is dropped and any running turn
+    /// has finished, or until the event receiver is dropped.
+    pub fn channel_pair(self) -> (mpsc::Sender<Command>, mpsc::Receiver<Event>) {
+        let (command_tx, command_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (event_tx, event_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(drive(self, command_rx, event_tx));
+        (command_tx, event_rx)
+    }
+}
+
+async fn drive(client: Client, mut commands: mpsc::Receiver<Command>, events: mpsc::Sender<Event>) {
+    let mut queued = VecDeque::<String>::new();
+    let mut turn: Option<BoxStream<'_, Result<Response, Error>>> = None;
+    let mut commands_open = true;
+
+    loop {
+        if turn.is_none() {
+            if let Some(prompt) = queued.pop_front() {
+                match client.query(&prompt).await {
+                    Ok(()) => turn = Some(client.receive().boxed()),
+                    Err(e) => {
+                        if events.send(Event::Error(e)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                continue;
+            }
+            if !commands_open {
+                break;
+            }
+        }
+
+        tokio::select! {
+            command = commands.recv(), if commands_open => match command {
+                Some(Command::Query(prompt)) => queued.push_back(prompt),
+                Some(Command::Steer(text)) if turn.is_some() => {
+                    if let Err(e) = client.steer(&text).await
+                        && events.send(Event::Error(e)).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                Some(Command::Steer(text)) => queued.push_back(text),
+                Some(Command::Interrupt) => {
+                    if turn.is_some()
+                        && let Err(e) = client.interrupt().await
+                        && events.send(Event::Error(e)).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                None => commands_open = false,
+            },
+            next = async { turn.as_mut()?.next().await }, if turn.is_some() => {
+                let event = match next {
+                    Some(Ok(response)) => Event::Response(response),
+                    Some(Err(e)) => Event::Error(e),
+                    None => {
+                        turn = None;
+                        continue;
+                    }
+                };
+                if events.send(event).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    tracing::debug!("channel driver stopped");
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::fake_cli::{FakeCli, assistant_text, result};
+    use crate::options::Options;
+
+    async fn client(cli: &FakeCli) -> Client {
+        Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_queued_prompts_run_in_order() {
+        let cli = FakeCli::initialized(&format!(
+            "next\nemit '{}'\nemit '{2}'\nnext\nemit '{}'\nemit '{2}'\ndrain",
+            assistant_text("one"),
+            assistant_text("two"),
+            result(),
+        ));
+        let (commands, mut events) = client(&cli).await.channel_pair();
+
+        commands
+            .send(Command::Query("first".to_owned()))
+            .await
+            .unwrap();
+        commands
+            .send(Command::Query("second".to_owned()))
+            .await
+            .unwrap();
+
+        let mut texts = Vec::new();
+        let mut completed = 0;
+        while completed < 2 {
+            match events.recv().await.unwrap() {
+                Event::Response(Response::Text(text)) => texts.push(text.content().to_owned()),
+                Event::Response(Response::Complete(_)) => completed += 1,
+                Event::Response(_) => {}
+                Event::Error(e) => panic!("{e}"),
+            }
+        }
+        assert_eq!(texts, ["one", "two"]);
+    }
+
+    #[tokio::test]
+    async fn test_events_close_once_commands_are_dropped() {
+        let cli = FakeCli::initialized("drain");
+        let (commands, mut events) = client(&cli).await.channel_pair();
+
+        drop(commands);
+        let next = tokio::time::timeout(Duration::from_secs(10), events.recv()).await;
+        assert!(next.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_commands_close_once_events_are_dropped() {
+        let cli = FakeCli::initialized(&format!(
+            "next\nemit '{}'\nemit '{}'\ndrain",
+            assistant_text("one"),
+            result(),
+        ));
+        let (commands, events) = client(&cli).await.channel_pair();
+
+        // The driver notices when it next has an event to send.
+        drop(events);
+        commands
+            .send(Command::Query("first".to_owned()))
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(10), commands.closed())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//! Reading and editing a project's `CLAUDE.md`.
+//!
+//! Provisioning tools usually own only part of the instructions file. They
+//! can keep that part in a managed section, delimited by HTML comment
+//! markers that Claude ignores:
+//!
+//! ```text
+//! <!-- clauders:begin style -->
+//! Use British spelling in comments.
+//! <!-- clauders:end style -->
+//! ```
+//!
+//! [`ClaudeMd::set_section`] replaces a section's body in place, or appends
+//! the section if it is not present yet, leaving the rest of the file as the
+//! user wrote it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::claude_md::ClaudeMd;
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let mut file = ClaudeMd::load(".").await?;
+//! file.set_section("style", "Use British spelling in comments.")?;
+//! file.save().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// The instructions file's name within a project directory.
+pub const FILE_NAME: &str = "CLAUDE.md";
+
+/// A `CLAUDE.md` file held in memory.
+#[derive(Debug, Clone)]
+pub struct ClaudeMd {
+    path: PathBuf,
+    content: String,
+}
+
+impl ClaudeMd {
+    /// Reads `CLAUDE.md` from the project directory `dir`.
+    ///
+    /// A missing file loads as empty, and is created by [`save`](Self::save).
+    pub async fn load(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::load_file(dir.as_ref().join(FILE_NAME)).await
+    }
+
+    /// Reads an instructions file at an explicit path, such as
+    /// `~/.claude/CLAUDE.md` or `.claude/CLAUDE.md`.
+    pub async fn load_file(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, content })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Replaces the whole file.
+    pub fn set_content(&mut self, content: impl Into<String>) {
+        self.content = content.into();
+    }
+
+    /// Appends `text` as a new paragraph.
+    pub fn append(&mut self, text: &str) {
+        self.start_paragraph();
+        self.content.push_str(text.trim_end());
+        self.content.push('\n');
+    }
+
+    /// Returns the body of the managed section `name`, without its markers.
+    pub fn section(&self, name: &str) -> Result<Option<&str>, Error> {
+        Ok(self
+            .find_section(name)?
+            .map(|section| self.content[section.body].trim_matches('\n')))
+    }
+
+    /// Names of the managed sections, in file order.
+    pub fn sections(&self) -> Vec<&str> {
+        self.content
+            .lines()
+            .filter_map(|line| {
+                line.trim()
+                    .strip_prefix("<!-- clauders:begin ")?
+                    .strip_suffix(" -->")
+            })
+            .collect()
+    }
+
+    /// Sets the body of the managed section `name`, appending the section
+    /// if the file does not have it yet.
+    pub fn set_section(&mut self, name: &str, body: &str) -> Result<(), Error> {
+        let body = body.trim_matches('\n');
+        match self.find_section(name)? {
+            Some(section) => {
+                let replacement = if body.is_empty() {
+                    "\n".to_owned()
+                } else {
+                    format!("\n{body}\n")
+                };
+                self.content.replace_range(section.body, &replacement);
+            }
+            None => {
+                self.start_paragraph();
+                self.content.push_str(&begin_marker(name));
+                self.content.push('\n');
+                if !body.is_empty() {
+                    self.content.push_str(body);
+                    self.content.push('\n');
+                }
+                self.content.push_str(&end_marker(name));
+                self.content.push('\n');
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the managed section `name` and its markers, returning whether
+    /// it was present.
+    pub fn remove_section(&mut self, name: &str) -> Result<bool, Error> {
+        let Some(section) = self.find_section(name)? else {
+            return Ok(false);
+        };
+        let mut end = section.whole.end;
+        if self.content[end..].starts_with('\n') {
+            end += 1;
+        }
+        let mut start = section.whole.start;
+        // Drop the blank line that separated the section from the text above.
+        if self.content[..start].ends_with("\n\n") {
+            start -= 1;
+        }
+        self.content.replace_range(start..end, "");
+        Ok(true)
+    }
+
+    /// Writes the file back, creating parent directories as needed.
+    pub async fn save(&self) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&self.path, &self.content).await?;
+        Ok(())
+    }
+
+    fn start_paragraph(&mut self) {
+        if self.content.is_empty() {
+            return;
+        }
+        if !self.content.ends_with('\n') {
+            self.content.push('\n');
+        }
+        if !self.content.ends_with("\n\n") {
+            self.content.push('\n');
+        }
+    }
+
+    fn find_section(&self, name: &str) -> Result<Option<Section>, Error> {
+        let begin = begin_marker(name);
+        let Some(start) = self.content.find(&begin) else {
+            return Ok(None);
+        };
+        let body_start = start + begin.len();
+        let end = end_marker(name);
+        let Some(offset) = self.content[body_start..].find(&end) else {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: section '{name}' has no closing marker",
+                    self.path.display()
+                ),
+            )));
+        };
+        let body_end = body_start + offset;
+        Ok(Some(Section {
+            whole: start..body_end + end.len(),
+            body: body_start..body_end,
+        }))
+    }
+}
+
+struct Section {
+    whole: Range<usize>,
+    body: Range<usize>,
+}
+
+fn begin_marker(name: &str) -> String {
+    format!("<!-- clauders:begin {name} -->")
+}
+
+fn end_marker(name: &str) -> String {
+    format!("<!-- clauders:end {name} -->")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str) -> ClaudeMd {
+        ClaudeMd {
+            path: PathBuf::from(FILE_NAME),
+            content: content.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_sections_are_added_replaced_and_removed() {
+        let mut md = file("# Project\n\nHand-written notes.");
+        md.set_section("style", "Use tabs.").unwrap();
+        assert_eq!(
+            md.content(),
+            "# Project\n\nHand-written notes.\n\n\
+             <!-- clauders:begin style -->\nUse tabs.\n<!-- clauders:end style -->\n"
+        );
+
+        md.set_section("style", "Use spaces.\nWrap at 100.")
+            .unwrap();
+        assert_eq!(
+            md.section("style").unwrap(),
+            Some("Use spaces.\nWrap at 100.")
+        );
+        assert_eq!(md.sections(), ["style"]);
+
+        assert!(md.remove_section("style").unwrap());
+        assert_eq!(md.content(), "# Project\n\nHand-written notes.\n");
+        assert!(!md.remove_section("style").unwrap());
+    }
+
+    #[test]
+    fn test_unterminated_section_is_an_error() {
+        let mut md = file("<!-- clauders:begin style -->\nUse tabs.\n");
+        assert!(md.section("style").is_err());
+        assert!(md.set_section("style", "Use spaces.").is_err());
+    }
+}
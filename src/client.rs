@@ -1,25 +1,62 @@
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-
-use async_stream::stream;
-use futures::StreamExt;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+#[cfg(feature = "schema")]
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde_json::{Value, json};
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{Mutex, RwLock, mpsc, oneshot, watch};
 use tokio_stream::Stream;
 
+use crate::approvals::{self, ApprovalBroker, ApprovalRequest};
+use crate::auth::AuthRefresh;
 use crate::conversation::Conversation;
-use crate::error::Error;
-use crate::hooks::{Hooks, PostToolUseInput, PreToolUseInput, StopInput, UserPromptSubmitInput};
-use crate::mcp_server::McpServer;
+use crate::denials::{DenialEvent, DenialReporter, DenialSource};
+use crate::deterministic::{Clock, IdGenerator};
+use crate::error::{ConfigError, Error};
+use crate::handler::{DispatchQueue, Handler, Overflow, dispatch};
+use crate::hooks::stop::ContinuationLimit;
+use crate::hooks::{
+    HookDescription, HookErrorEvent, HookEvent, Hooks, PostToolUseInput, PreToolUseDecision,
+    PreToolUseInput, StopInput, UserPromptSubmitInput,
+};
+#[cfg(feature = "schema")]
+use crate::json_items::ItemSplitter;
+use crate::limits::MemoryStats;
+use crate::mcp_peer::{PeerRequest, PeerRequests};
+use crate::mcp_server::{McpServer, is_tool_call};
 use crate::options::Options;
-use crate::proto::control::{HookCallbackRequest, Request, ResponseEnvelope};
+use crate::permissions::{Decision, PermissionCallbacks, PermissionContext, PermissionMode};
+use crate::prepared::PreparedMessage;
+use crate::profile::{PermissionProfile, TurnProfile};
+use crate::proto::control::{
+    CancelRequestEnvelope, ErrorCode, ErrorDetail, ErrorResponse, HookCallbackRequest,
+    McpMessageRequest, PermissionRequest, Request, ResponseEnvelope,
+};
 use crate::proto::{
-    ContentBlock, Incoming, Message, OutgoingUserMessage, RequestEnvelope, UserContent,
+    ContentBlock, ControlRequestEnvelope, Incoming, InitMessage, InitializeResponse,
+    McpServerStatus, Message, OutgoingUserMessage, RequestEnvelope, UserContent,
+};
+use crate::quota::QuotaBucket;
+use crate::response::{
+    CompleteResponse, PartialResponse, RateLimitResponse, Response, ResponseMeta, Responses,
 };
-use crate::response::{RateLimitResponse, Response, Responses};
-use crate::transport::Transport;
+use crate::signal::{Signal, Signals};
+use crate::sync::{ClaimSet, Correlation, Latch};
+use crate::task_pool::{BackgroundTasks, TaskPool, TaskPoolStats};
+use crate::tenant::Workspace;
+use crate::tool::ToolContext;
+use crate::transport::{CommandPreview, Transport, TransportOptions, TransportWriter};
+
+/// How long [`Client::close`] gives background tasks and the CLI to finish.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Tracks which hook type and index a callback ID maps to.
 #[derive(Debug, Clone)]
@@ -30,6 +67,62 @@ enum HookCallbackEntry {
     Stop(usize),
 }
 
+impl HookCallbackEntry {
+    fn event(&self) -> HookEvent {
+        match self {
+            Self::PreToolUse(_) => HookEvent::PreToolUse,
+            Self::PostToolUse(_) => HookEvent::PostToolUse,
+            Self::UserPromptSubmit(_) => HookEvent::UserPromptSubmit,
+            Self::Stop(_) => HookEvent::Stop,
+        }
+    }
+}
+
+/// Where [`Client::receive`] is in the current turn.
+struct Receiving {
+    /// Responses from the last message that have not been returned yet.
+    pending: VecDeque<Response>,
+    done: bool,
+    /// The turn's duration limit and when it runs out.
+    deadline: Option<(Duration, tokio::time::Instant)>,
+    /// Set once the turn was interrupted for running past its deadline.
+    exceeded: Option<Duration>,
+}
+
+impl Receiving {
+    fn within(limit: Option<Duration>) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            done: false,
+            deadline: limit.map(|limit| (limit, tokio::time::Instant::now() + limit)),
+            exceeded: None,
+        }
+    }
+}
+
+/// State of [`Client::receive_items`].
+#[cfg(feature = "schema")]
+struct Items<S> {
+    responses: Pin<Box<S>>,
+    /// The content block of the `StructuredOutput` tool call.
+    block_index: Option<u64>,
+    splitter: ItemSplitter,
+    /// Items read from the partial input but not yet yielded.
+    streamed: VecDeque<String>,
+    /// Items of the final result not yet yielded.
+    remaining: std::vec::IntoIter<Value>,
+    yielded: usize,
+}
+
+/// Where [`Client::receive_structured`] is in the turn's structured output.
+struct Snapshots<S> {
+    responses: Pin<Box<S>>,
+    /// The content block of the `StructuredOutput` tool call.
+    block_index: Option<u64>,
+    input: String,
+    last: Option<Value>,
+}
+
 /// Client for interacting with the Claude Code CLI.
 ///
 /// Manages a subprocess running the Claude CLI and provides methods for
@@ -49,37 +142,209 @@ enum HookCallbackEntry {
 /// ```
 pub struct Client {
     transport: Mutex<Transport>,
+    /// Writes to the CLI's input without waiting for the reader, which holds
+    /// `transport` while it waits for output.
+    writer: Arc<std::sync::Mutex<TransportWriter>>,
+    /// Control responses for the writer task to send.
+    responses: mpsc::UnboundedSender<ResponseEnvelope>,
+    tasks: BackgroundTasks,
+    /// The options the CLI was last started with, reused by [`Client::restart`].
+    transport_options: std::sync::Mutex<TransportOptions>,
+    auth_refresh: Option<AuthRefresh>,
+    /// Messages read while waiting for a control response, not yet consumed by `receive`.
+    pending: std::sync::Mutex<VecDeque<Incoming>>,
+    /// Control requests awaiting a response from the CLI, keyed by request id.
+    outstanding: Correlation<String, oneshot::Sender<crate::proto::Response>>,
+    control_timeout: Duration,
+    /// What the running CLI answered to the initialize handshake; replaced
+    /// whenever the CLI is restarted.
+    initialize_response: std::sync::RwLock<Option<InitializeResponse>>,
+    registered_hooks: std::sync::RwLock<Vec<HookDescription>>,
     session_id: RwLock<Option<String>>,
-    responded_tool_ids: Mutex<HashSet<String>>,
+    mcp_server_status: RwLock<HashMap<String, McpServerStatus>>,
+    server_info: RwLock<Option<crate::proto::ServerInfo>>,
+    responded_tool_ids: ClaimSet,
     mcp_servers: HashMap<String, Arc<McpServer>>,
+    tool_context: ToolContext,
+    peer_requests: PeerRequests,
+    signals: Arc<Signals>,
+    /// Set once the client starts shutting down, ending every read.
+    shutdown: watch::Sender<bool>,
+    mcp_shut_down: Latch,
     hooks: Option<Hooks>,
     hook_callbacks: HashMap<String, HookCallbackEntry>,
     json_schema: Option<String>,
+    /// The property structured output is wrapped in, for non-object schemas.
+    json_schema_wrapper: Option<&'static str>,
+    /// Set when the CLI lacks `--json-schema` and the schema is asked for in
+    /// the system prompt instead.
+    schema_in_prompt: bool,
+    log_tool_stats: bool,
+    hide_thinking: bool,
+    max_tool_calls: Option<u32>,
+    max_turn_duration: Option<Duration>,
+    continuation_limit: Option<Arc<ContinuationLimit>>,
+    hook_pool: Arc<TaskPool<ResponseEnvelope>>,
+    tool_pool: Arc<TaskPool<ResponseEnvelope>>,
+    #[cfg(feature = "webhooks")]
+    webhooks: Option<crate::webhook::Notifier>,
+    approvals: Option<ApprovalBroker>,
+    permission_callbacks: PermissionCallbacks,
+    denials: DenialReporter,
+    profile: Option<PermissionProfile>,
+    turn_profile: Option<TurnProfile>,
+    permission_mode: PermissionMode,
+    current_permission_mode: std::sync::Mutex<PermissionMode>,
+    id_generator: IdGenerator,
+    clock: Clock,
+    dispatch_queue: DispatchQueue,
+    sequence: AtomicU64,
+    bypass_acknowledged: bool,
+    cwd: PathBuf,
+    tenant_id: Option<String>,
+    labels: BTreeMap<String, String>,
+    quotas: Vec<QuotaBucket>,
+    /// The options the client was created with, less its hooks, for
+    /// sessions run alongside its own.
+    options: Options,
+    /// Declared last so it is removed after the CLI process is stopped.
+    _workspace: Option<Workspace>,
 }
 
 impl Client {
     /// Creates a new client with the given options.
     ///
-    /// Spawns a Claude CLI subprocess and establishes communication channels.
-    /// Sends an initialize control request to enable SDK MCP servers.
+    /// Validates the options, spawns a Claude CLI subprocess and establishes
+    /// communication channels. Sends an initialize control request to enable
+    /// SDK MCP servers.
     pub async fn new(mut options: Options) -> Result<Self, Error> {
-        let transport_options = options.to_transport_options();
+        options.validate()?;
+
+        let workspace = options.create_workspace()?;
+        let mut transport_options = options.to_transport_options();
+        let schema_in_prompt = transport_options.json_schema().is_some()
+            && !crate::transport::cli_supports_flag(&transport_options, "--json-schema").await;
+        if schema_in_prompt {
+            tracing::warn!(
+                "the claude CLI does not support --json-schema; asking for structured output \
+                 in the system prompt and extracting it from the reply"
+            );
+            transport_options.embed_json_schema();
+        }
         let transport = Transport::new(&transport_options).await?;
 
         let mcp_servers = options.mcp_servers().clone();
-        let hooks = options.take_hooks();
+        let hooks = options.take_hooks()?;
+        let profile = options.profile_ref().cloned();
+        let turn_profile = options.take_turn_profile();
+        let permission_mode = options
+            .permission_mode_setting()
+            .unwrap_or(PermissionMode::Default);
         let json_schema = options.json_schema().map(|s| s.to_owned());
+        let json_schema_wrapper = options.json_schema_wrapper();
+        let control_timeout = options.control_timeout_or_default();
+        let log_tool_stats = options.log_tool_stats_enabled();
+        let hide_thinking = options.hide_thinking_enabled();
+        let max_tool_calls = options.max_tool_calls_limit();
+        let max_turn_duration = options.max_turn_duration_limit();
+        let continuation_limit = options
+            .max_continuations_limit()
+            .map(|limit| Arc::new(ContinuationLimit::new(limit)));
+        let hook_pool = Arc::new(TaskPool::new(options.max_concurrent_hooks_limit()));
+        let tool_pool = Arc::new(TaskPool::new(options.max_concurrent_tool_calls_limit()));
+        let writer = Arc::new(std::sync::Mutex::new(transport.writer()));
+        let (responses, pending_responses) = mpsc::unbounded_channel();
+        let tasks = BackgroundTasks::new();
+        tasks.spawn(write_responses(
+            Arc::clone(&writer),
+            Arc::clone(&hook_pool),
+            Arc::clone(&tool_pool),
+            pending_responses,
+        ));
+        #[cfg(feature = "webhooks")]
+        let webhooks = options.webhook_notifier();
+        let approvals = options.approval_broker();
+        let permission_callbacks = options.permission_callbacks_ref().clone();
+        let denials = options.denial_reporter().clone();
+        let auth_refresh = options.auth_refresh().cloned();
+        let tenant_id = options.tenant_id().map(str::to_owned);
+        let labels = options.labels_ref().clone();
+        let quotas = options.quotas().to_vec();
+        let id_generator = options.id_generator_ref().clone();
+        let clock = options.clock_ref().clone();
+        let dispatch_queue = options.dispatch_queue_config();
+        let bypass_acknowledged = match options.bypass_acknowledgement() {
+            Some(ack) => {
+                tracing::warn!(reason = %ack.reason(), "permission checks bypassed");
+                true
+            }
+            None => false,
+        };
+        let cwd = match options.cwd_path() {
+            Some(cwd) => cwd.to_path_buf(),
+            None => std::env::current_dir()?,
+        };
+        let tool_context = match options.mcp_roots_ref() {
+            [] => ToolContext::new().with_roots([cwd.clone()]),
+            roots => ToolContext::new().with_roots(roots.iter().cloned()),
+        };
 
         let hook_callbacks = Self::build_hook_callbacks(&hooks);
 
         let client = Self {
             transport: Mutex::new(transport),
+            writer,
+            responses,
+            tasks,
+            transport_options: std::sync::Mutex::new(transport_options),
+            auth_refresh,
+            pending: std::sync::Mutex::new(VecDeque::new()),
+            outstanding: Correlation::new(),
+            control_timeout,
+            initialize_response: std::sync::RwLock::new(None),
+            registered_hooks: std::sync::RwLock::new(Vec::new()),
             session_id: RwLock::new(None),
-            responded_tool_ids: Mutex::new(HashSet::new()),
+            mcp_server_status: RwLock::new(HashMap::new()),
+            server_info: RwLock::new(None),
+            responded_tool_ids: ClaimSet::new(),
             mcp_servers,
+            tool_context,
+            peer_requests: PeerRequests::new(),
+            signals: Arc::new(Signals::new()),
+            shutdown: watch::Sender::new(false),
+            mcp_shut_down: Latch::new(),
             hooks,
             hook_callbacks,
             json_schema,
+            json_schema_wrapper,
+            schema_in_prompt,
+            log_tool_stats,
+            hide_thinking,
+            max_tool_calls,
+            max_turn_duration,
+            continuation_limit,
+            hook_pool,
+            tool_pool,
+            #[cfg(feature = "webhooks")]
+            webhooks,
+            approvals,
+            permission_callbacks,
+            denials,
+            profile,
+            turn_profile,
+            permission_mode,
+            current_permission_mode: std::sync::Mutex::new(permission_mode),
+            id_generator,
+            clock,
+            dispatch_queue,
+            sequence: AtomicU64::new(0),
+            bypass_acknowledged,
+            cwd,
+            tenant_id,
+            labels,
+            quotas,
+            options,
+            _workspace: workspace,
         };
 
         client.initialize().await?;
@@ -87,6 +352,41 @@ impl Client {
         Ok(client)
     }
 
+    /// Checks `options` as [`new`](Self::new) would, without starting the CLI,
+    /// and returns the command that would be run.
+    ///
+    /// Fails if the options are invalid, the structured output schema is
+    /// unusable or the CLI cannot be found, so agent configurations can be
+    /// checked in CI.
+    pub async fn dry_run(mut options: Options) -> Result<CommandPreview, Error> {
+        options.validate()?;
+        options.take_hooks()?;
+        if let Some(schema) = options.json_schema() {
+            let schema = serde_json::from_str::<Value>(schema)?;
+            if schema.as_object().is_none_or(|s| s.is_empty()) {
+                return Err(ConfigError::InvalidJsonSchema {
+                    reason: "the schema must be a non-empty object".to_owned(),
+                }
+                .into());
+            }
+        }
+
+        let mut transport_options = options.to_transport_options();
+        let launcher = crate::locate::resolve(transport_options.cli_path()).await;
+        if !launcher.is_found() {
+            return Err(Error::CliNotFound(format!(
+                "no claude CLI at '{}'; make sure 'claude' is installed",
+                launcher.program.display()
+            )));
+        }
+        if transport_options.json_schema().is_some()
+            && !crate::transport::cli_supports_flag(&transport_options, "--json-schema").await
+        {
+            transport_options.embed_json_schema();
+        }
+        Ok(Transport::preview(&transport_options, launcher))
+    }
+
     /// Builds a mapping from callback IDs to hook entries.
     fn build_hook_callbacks(hooks: &Option<Hooks>) -> HashMap<String, HookCallbackEntry> {
         let mut callbacks = HashMap::new();
@@ -94,29 +394,14 @@ impl Client {
             return callbacks;
         };
 
-        let mut id = 0;
-
-        for (idx, _) in hooks.pre_tool_use_hooks().enumerate() {
-            callbacks.insert(format!("hook_{id}"), HookCallbackEntry::PreToolUse(idx));
-            id += 1;
-        }
-
-        for (idx, _) in hooks.post_tool_use_hooks().enumerate() {
-            callbacks.insert(format!("hook_{id}"), HookCallbackEntry::PostToolUse(idx));
-            id += 1;
-        }
-
-        for (idx, _) in hooks.user_prompt_submit_hooks().enumerate() {
-            callbacks.insert(
-                format!("hook_{id}"),
-                HookCallbackEntry::UserPromptSubmit(idx),
-            );
-            id += 1;
-        }
-
-        for (idx, _) in hooks.stop_hooks().enumerate() {
-            callbacks.insert(format!("hook_{id}"), HookCallbackEntry::Stop(idx));
-            id += 1;
+        for (event, idx, id) in hooks.callback_ids() {
+            let entry = match event {
+                HookEvent::PreToolUse => HookCallbackEntry::PreToolUse(idx),
+                HookEvent::PostToolUse => HookCallbackEntry::PostToolUse(idx),
+                HookEvent::UserPromptSubmit => HookCallbackEntry::UserPromptSubmit(idx),
+                HookEvent::Stop => HookCallbackEntry::Stop(idx),
+            };
+            callbacks.insert(id, entry);
         }
 
         callbacks
@@ -125,8 +410,9 @@ impl Client {
     async fn initialize(&self) -> Result<(), Error> {
         let mut init_request = crate::proto::control::InitializeRequest::new();
 
-        if let Some(hooks) = self.build_hooks_config() {
-            init_request = init_request.with_hooks(hooks);
+        let hooks = self.hooks.as_ref().map(Hooks::describe);
+        if let Some(hooks) = &hooks {
+            init_request = init_request.with_hooks(Self::build_hooks_config(hooks));
         }
 
         let mcp_names = self.mcp_servers.keys().cloned().collect::<Vec<_>>();
@@ -134,140 +420,190 @@ impl Client {
             init_request = init_request.with_sdk_mcp_servers(mcp_names);
         }
 
-        let request = crate::proto::Request::Initialize(init_request);
-        let envelope = RequestEnvelope::new(request);
-        self.transport.lock().await.send_request(&envelope).await?;
-        tracing::debug!("sent initialize control request, waiting for response");
+        tracing::debug!("sending initialize control request");
+        let response = self
+            .send_control(crate::proto::Request::Initialize(init_request))
+            .await
+            .map_err(|e| match e {
+                Error::ControlError {
+                    request_id,
+                    message,
+                } => Error::ControlError {
+                    request_id,
+                    message: format!("initialize rejected: {message}"),
+                },
+                e => e,
+            })?;
 
-        loop {
-            let incoming = {
-                let mut transport = self.transport.lock().await;
-                transport.receive().await
-            };
+        self.check_initialization_messages()?;
 
-            match incoming {
-                Ok(Some(incoming)) => {
-                    match &incoming {
-                        Incoming::System(crate::proto::SystemMessage::HookResponse(hook))
-                            if hook.outcome() == Some("error") =>
-                        {
-                            return Err(Error::ProtocolError(format!(
-                                "hook failed during initialization: hook={} event={} exit_code={:?}",
-                                hook.hook_name().unwrap_or("unknown"),
-                                hook.hook_event().unwrap_or("unknown"),
-                                hook.exit_code()
-                            )));
-                        }
-                        Incoming::System(crate::proto::SystemMessage::Error(err)) => {
-                            return Err(Error::ProtocolError(format!(
-                                "system error during initialization: {}",
-                                err.error()
-                            )));
-                        }
-                        _ => {}
-                    }
+        let response = match response {
+            Some(data) => serde_json::from_value::<InitializeResponse>(data)
+                .map_err(|e| Error::ProtocolError(format!("malformed initialize response: {e}")))?,
+            None => InitializeResponse::default(),
+        };
+        tracing::debug!(
+            commands = response.commands().len(),
+            output_style = ?response.output_style(),
+            "received initialize response"
+        );
 
-                    if let Some(ctrl) = incoming.as_control_request() {
-                        let response = match ctrl.request() {
-                            Request::McpMessage(mcp_req) => {
-                                self.handle_mcp_message(
-                                    ctrl.request_id(),
-                                    mcp_req.server_name(),
-                                    mcp_req.message(),
-                                )
-                                .await
-                            }
-                            Request::HookCallback(hook_req) => {
-                                self.handle_hook_callback(ctrl.request_id(), hook_req).await
-                            }
-                            _ => continue,
-                        };
-                        let mut transport = self.transport.lock().await;
-                        if let Err(e) = transport.send_response(&response).await {
-                            tracing::warn!(error = %e, "failed to send control response during initialization");
-                        }
-                        continue;
-                    }
+        let hooks = hooks.unwrap_or_default();
+        if let Some(hook) = hooks
+            .iter()
+            .find(|hook| response.registers_hook_callback(hook.callback_id()) == Some(false))
+        {
+            return Err(Error::HookError {
+                callback_id: hook.callback_id().to_owned(),
+                message: format!("the CLI did not register the {} hook", hook.event()),
+            });
+        }
 
-                    if let Some(response) = incoming.as_control_response() {
-                        match response.response() {
-                            crate::proto::Response::Success(success) => {
-                                tracing::debug!(
-                                    request_id = %success.request_id(),
-                                    "received initialize response"
-                                );
-                                return Ok(());
-                            }
-                            crate::proto::Response::Error(err) => {
-                                return Err(Error::ControlError {
-                                    request_id: err.request_id().to_owned(),
-                                    message: err.error().message().to_owned(),
-                                });
-                            }
-                        }
-                    }
+        *self
+            .initialize_response
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(response);
+
+        for hook in &hooks {
+            tracing::debug!(
+                event = %hook.event(),
+                name = hook.name(),
+                matcher = hook.matcher(),
+                callback_id = hook.callback_id(),
+                "hook registered"
+            );
+        }
+        *self
+            .registered_hooks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = hooks;
+        Ok(())
+    }
 
-                    tracing::debug!("initialization loop: skipping non-control message");
-                }
-                Ok(None) => {
-                    return Err(Error::ProtocolError(
-                        "stream ended during initialization".to_owned(),
-                    ));
+    /// Fails if a hook or system error was reported while initializing,
+    /// taking the messages reporting it out of the queue so `receive` does
+    /// not yield them again.
+    fn check_initialization_messages(&self) -> Result<(), Error> {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut failure = None;
+        pending.retain(|incoming| {
+            let error = match incoming {
+                Incoming::System(crate::proto::SystemMessage::HookResponse(hook))
+                    if hook.outcome() == Some("error") =>
+                {
+                    Error::ProtocolError(format!(
+                        "hook failed during initialization: hook={} event={} exit_code={:?}",
+                        hook.hook_name().unwrap_or("unknown"),
+                        hook.hook_event().unwrap_or("unknown"),
+                        hook.exit_code()
+                    ))
                 }
-                Err(e) => return Err(e),
-            }
-        }
+                Incoming::System(crate::proto::SystemMessage::Error(err)) => Error::ProtocolError(
+                    format!("system error during initialization: {}", err.error()),
+                ),
+                _ => return true,
+            };
+            failure.get_or_insert(error);
+            false
+        });
+        failure.map_or(Ok(()), Err)
     }
 
-    fn build_hooks_config(&self) -> Option<HashMap<String, Value>> {
-        let hooks = self.hooks.as_ref()?;
-        let mut result = HashMap::new();
+    /// Returns the CLI's response to the initialize handshake.
+    pub fn initialize_response(&self) -> Option<InitializeResponse> {
+        self.initialize_response
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
 
-        if hooks.has_pre_tool_use_hooks() {
-            let entries = hooks
-                .pre_tool_use_hooks()
-                .enumerate()
-                .map(|(id, (pattern, _))| {
-                    json!({"matcher": pattern, "hookCallbackIds": [format!("hook_{id}")]})
-                })
-                .collect::<Vec<_>>();
-            result.insert("PreToolUse".to_owned(), json!(entries));
-        }
+    /// Returns the hooks the CLI accepted while initializing, as sent.
+    ///
+    /// A hook that never fires is usually missing here, or has a
+    /// [matcher](HookDescription::matcher) that does not match the tool.
+    pub fn registered_hooks(&self) -> Vec<HookDescription> {
+        self.registered_hooks
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
 
-        if hooks.has_post_tool_use_hooks() {
-            let base_id = hooks.pre_tool_use_hooks().len();
-            let entries = hooks
-                .post_tool_use_hooks()
-                .enumerate()
-                .map(|(idx, (pattern, _))| {
-                    json!({"matcher": pattern, "hookCallbackIds": [format!("hook_{}", base_id + idx)]})
-                })
-                .collect::<Vec<_>>();
-            result.insert("PostToolUse".to_owned(), json!(entries));
-        }
-
-        if hooks.has_user_prompt_submit_hooks() {
-            let base_id = hooks.pre_tool_use_hooks().len() + hooks.post_tool_use_hooks().len();
-            let ids = (0..hooks.user_prompt_submit_hooks().len())
-                .map(|i| format!("hook_{}", base_id + i))
-                .collect::<Vec<_>>();
-            result.insert(
-                "UserPromptSubmit".to_owned(),
-                json!([{ "hookCallbackIds": ids }]),
-            );
+    /// Returns how many hook callbacks are waiting, running, and done but
+    /// not yet answered.
+    pub fn hook_pool_stats(&self) -> TaskPoolStats {
+        self.hook_pool.stats()
+    }
+
+    /// Returns how many MCP tool calls are waiting, running, and done but not
+    /// yet answered.
+    pub fn tool_pool_stats(&self) -> TaskPoolStats {
+        self.tool_pool.stats()
+    }
+
+    /// Returns how much the client is holding in its buffers, for sizing
+    /// them with [`Options::limits`](crate::Options::limits).
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            pending_messages: self
+                .pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .len(),
+            outstanding_requests: self.outstanding.len(),
+            responded_tool_calls: self.responded_tool_ids.len(),
+            hook_pool: self.hook_pool.stats(),
+            tool_pool: self.tool_pool.stats(),
+            interned_strings: crate::proto::intern::interned_count(),
         }
+    }
 
-        if hooks.has_stop_hooks() {
-            let base_id = hooks.pre_tool_use_hooks().len()
-                + hooks.post_tool_use_hooks().len()
-                + hooks.user_prompt_submit_hooks().len();
-            let ids = (0..hooks.stop_hooks().len())
-                .map(|i| format!("hook_{}", base_id + i))
-                .collect::<Vec<_>>();
-            result.insert("Stop".to_owned(), json!([{ "hookCallbackIds": ids }]));
+    /// Builds the `hooks` field of the initialize request.
+    fn build_hooks_config(hooks: &[HookDescription]) -> HashMap<String, Value> {
+        let mut result = HashMap::<String, Value>::new();
+        for hook in hooks {
+            let entries = result
+                .entry(hook.event().to_string())
+                .or_insert_with(|| json!([]));
+            let Some(entries) = entries.as_array_mut() else {
+                continue;
+            };
+            match hook.event() {
+                // Tool hooks each get an entry for their matcher.
+                HookEvent::PreToolUse | HookEvent::PostToolUse => entries.push(json!({
+                    "matcher": hook.matcher(),
+                    "hookCallbackIds": [hook.callback_id()],
+                })),
+                HookEvent::UserPromptSubmit | HookEvent::Stop => match entries.first_mut() {
+                    Some(entry) => {
+                        if let Some(ids) = entry["hookCallbackIds"].as_array_mut() {
+                            ids.push(json!(hook.callback_id()));
+                        }
+                    }
+                    None => entries.push(json!({"hookCallbackIds": [hook.callback_id()]})),
+                },
+            }
         }
+        result
+    }
 
-        Some(result)
+    /// Returns the directory the CLI runs in.
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// The options the client was created with, less its hooks.
+    pub(crate) fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Returns the default limit on tool calls per conversation turn.
+    pub fn max_tool_calls(&self) -> Option<u32> {
+        self.max_tool_calls
+    }
+
+    /// The default limit on how long a conversation turn may run.
+    pub fn max_turn_duration(&self) -> Option<Duration> {
+        self.max_turn_duration
     }
 
     /// Returns the current session ID, if one has been established.
@@ -275,6 +611,49 @@ impl Client {
         self.session_id.read().await.clone()
     }
 
+    /// Returns the status the CLI reported for the named MCP server.
+    ///
+    /// Statuses become available once the session's init message has been
+    /// received; before that this returns `None`.
+    pub async fn mcp_server_status(&self, name: &str) -> Option<McpServerStatus> {
+        self.mcp_server_status.read().await.get(name).cloned()
+    }
+
+    /// Records the MCP server statuses from the init message and verifies
+    /// that every SDK server registered through [`Options`] was accepted.
+    async fn verify_mcp_servers(&self, init: &InitMessage) -> Result<(), Error> {
+        {
+            let mut statuses = self.mcp_server_status.write().await;
+            statuses.clear();
+            statuses.extend(
+                init.mcp_servers()
+                    .iter()
+                    .map(|s| (s.name().to_owned(), s.clone())),
+            );
+        }
+
+        for name in self.mcp_servers.keys() {
+            match init.mcp_server(name) {
+                Some(status) if status.status().is_failed() => {
+                    tracing::error!(server = %name, status = %status.status(), "MCP server rejected by CLI");
+                    return Err(Error::McpServerUnavailable {
+                        name: name.clone(),
+                        status: status.status().to_string(),
+                    });
+                }
+                Some(status) if !status.status().is_connected() => {
+                    tracing::warn!(server = %name, status = %status.status(), "MCP server not connected");
+                }
+                Some(_) => {}
+                None => {
+                    tracing::warn!(server = %name, "MCP server missing from init message");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Creates a new conversation session for multi-turn interactions.
     ///
     /// The returned [`Conversation`] provides a builder-style API for:
@@ -311,17 +690,67 @@ impl Client {
     }
 
     /// Sends a text query to Claude.
+    ///
+    /// Fails with [`Error::QuotaExceeded`] if one of the client's
+    /// [quota buckets](crate::quota) is exhausted.
     pub async fn query(&self, prompt: &str) -> Result<(), Error> {
+        self.check_quotas()?;
+        self.signals.turn_started();
         let msg = OutgoingUserMessage::text(prompt);
         let json = serde_json::to_value(&msg)?;
-        self.transport.lock().await.send(&json).await
+        self.writer().send(&json).await
+    }
+
+    fn check_quotas(&self) -> Result<(), Error> {
+        self.quotas.iter().try_for_each(QuotaBucket::check)
+    }
+
+    /// Charges a completed turn to the client's quota buckets.
+    fn charge_quotas(&self, response: &Response) {
+        let Some(cost) = response.as_complete().and_then(|c| c.total_cost_usd()) else {
+            return;
+        };
+        for bucket in &self.quotas {
+            if let Err(e) = bucket.charge(cost) {
+                tracing::warn!(
+                    tenant = self.tenant_id.as_deref().unwrap_or_default(),
+                    labels = ?self.labels,
+                    error = %e,
+                    "quota exhausted"
+                );
+            }
+        }
+    }
+
+    /// Sends an additional user message while Claude is mid-turn.
+    ///
+    /// The CLI reads the message between steps of the running turn, so it can
+    /// redirect Claude without interrupting. Responses keep arriving on the
+    /// current [`receive`](Self::receive) stream. Unlike
+    /// [`query`](Self::query), it does not start a turn: quotas are not
+    /// checked, and an interrupt requested for the running turn still applies.
+    pub async fn steer(&self, text: &str) -> Result<(), Error> {
+        tracing::debug!(text, "steering turn");
+        let msg = OutgoingUserMessage::text(text);
+        let json = serde_json::to_value(&msg)?;
+        self.writer().send(&json).await
     }
 
     /// Sends a message with structured content to Claude.
     pub async fn send_message(&self, content: UserContent) -> Result<(), Error> {
+        self.check_quotas()?;
+        self.signals.turn_started();
         let msg = OutgoingUserMessage::new(content);
         let json = serde_json::to_value(&msg)?;
-        self.transport.lock().await.send(&json).await
+        self.writer().send(&json).await
+    }
+
+    /// Sends a message serialised ahead of time, writing it without
+    /// serialising it again.
+    pub async fn send_prepared(&self, message: &PreparedMessage) -> Result<(), Error> {
+        self.check_quotas()?;
+        self.signals.turn_started();
+        self.writer().send_line(message.as_str()).await
     }
 
     /// Responds to a tool use request from Claude.
@@ -333,199 +762,1076 @@ impl Client {
         content: Value,
         is_error: bool,
     ) -> Result<(), Error> {
-        let mut responded = self.responded_tool_ids.lock().await;
-        if responded.contains(tool_use_id) {
+        if !self.responded_tool_ids.claim(tool_use_id) {
             tracing::warn!(tool_use_id, "already responded to tool, skipping");
             return Ok(());
         }
 
-        let tool_result = ContentBlock::ToolResult(
+        let tool_result = ContentBlock::ToolResult(Arc::new(
             crate::proto::content_block::ToolResult::new(tool_use_id)
                 .with_content(content)
                 .with_error(is_error),
-        );
+        ));
 
         let msg = OutgoingUserMessage::new(UserContent::Blocks(vec![tool_result]));
-        let json = serde_json::to_value(&msg)?;
-        self.transport.lock().await.send(&json).await?;
-        responded.insert(tool_use_id.to_owned());
-        Ok(())
+        let sent = match serde_json::to_value(&msg) {
+            Ok(json) => self.writer().send(&json).await,
+            Err(e) => Err(e.into()),
+        };
+        if sent.is_err() {
+            // Not answered, so it may be tried again.
+            self.responded_tool_ids.release(tool_use_id);
+        }
+        sent
     }
 
     /// Clears the set of tool IDs that have been responded to.
     pub async fn clear_tool_response_tracking(&self) {
-        self.responded_tool_ids.lock().await.clear();
+        self.responded_tool_ids.clear();
     }
 
     /// Returns a stream of responses from Claude.
     ///
     /// The stream ends when a [`Response::Complete`] is received or the connection closes.
+    /// With [`Options::max_turn_duration`](crate::Options::max_turn_duration),
+    /// a turn still running once the limit has passed since the stream was
+    /// created is interrupted; the stream then ends with
+    /// [`Error::TurnDurationExceeded`] after the rest of the turn.
     pub fn receive(&self) -> impl Stream<Item = Result<Response, Error>> + '_ {
-        stream! {
-            loop {
-                let incoming = {
-                    let mut transport = self.transport.lock().await;
-                    transport.receive().await
-                };
+        self.receive_within(self.max_turn_duration)
+    }
 
-                match incoming {
-                    Ok(Some(incoming)) => {
-                        if let Some(ctrl) = incoming.as_control_request() {
-                            let response = match ctrl.request() {
-                                Request::McpMessage(mcp_req) => {
-                                    self.handle_mcp_message(
-                                        ctrl.request_id(),
-                                        mcp_req.server_name(),
-                                        mcp_req.message(),
-                                    )
-                                    .await
-                                }
-                                Request::HookCallback(hook_req) => {
-                                    self.handle_hook_callback(ctrl.request_id(), hook_req)
-                                        .await
-                                }
-                                _ => continue,
-                            };
-                            let mut transport = self.transport.lock().await;
-                            if let Err(e) = transport.send_response(&response).await {
-                                tracing::warn!(error = %e, "failed to send control response");
-                            }
-                            continue;
+    /// Like [`receive`](Self::receive), interrupting the turn once `limit`
+    /// has passed rather than the client's default.
+    fn receive_within(
+        &self,
+        limit: Option<Duration>,
+    ) -> impl Stream<Item = Result<Response, Error>> + '_ {
+        futures::stream::unfold(Receiving::within(limit), move |mut state| async move {
+            let item = self.next_response(&mut state).await?;
+            Some((item, state))
+        })
+    }
+
+    /// Reads from the CLI until [`receive`](Self::receive) has a response to
+    /// return, answering control requests on the way.
+    async fn next_response(&self, state: &mut Receiving) -> Option<Result<Response, Error>> {
+        loop {
+            while let Some(response) = state.pending.pop_front() {
+                if self.hide_thinking && matches!(response, Response::Thinking(_)) {
+                    continue;
+                }
+                #[cfg(feature = "webhooks")]
+                if let Some(webhooks) = &self.webhooks {
+                    webhooks.observe(&response);
+                }
+                self.charge_quotas(&response);
+                if matches!(response, Response::Complete(_)) {
+                    state.pending.clear();
+                    state.done = true;
+                }
+                return Some(Ok(response));
+            }
+
+            if state.done {
+                let limit = state.exceeded.take()?;
+                return Some(Err(Error::TurnDurationExceeded { limit }));
+            }
+
+            let incoming = match state.deadline.filter(|_| state.exceeded.is_none()) {
+                Some((limit, deadline)) => tokio::select! {
+                    incoming = self.next_incoming() => incoming,
+                    () = tokio::time::sleep_until(deadline) => {
+                        tracing::warn!(?limit, "turn ran too long, interrupting");
+                        if let Err(e) = self.writer().interrupt().await {
+                            state.done = true;
+                            return Some(Err(e));
+                        }
+                        state.exceeded = Some(limit);
+                        continue;
+                    }
+                },
+                None => self.next_incoming().await,
+            };
+            match incoming {
+                Ok(Some(incoming)) => {
+                    if let Some(ctrl) = incoming.as_control_request() {
+                        self.handle_control_request(ctrl).await;
+                        continue;
+                    }
+
+                    if let Some(response) = incoming.as_control_response() {
+                        self.resolve_control_response(response.response());
+                        continue;
+                    }
+
+                    if let Some(cancel) = incoming.as_control_cancel_request() {
+                        let request_id = cancel.request_id();
+                        if self.hook_pool.cancel(request_id) || self.tool_pool.cancel(request_id) {
+                            tracing::debug!(%request_id, "control request cancelled by the CLI");
+                        } else {
+                            // The request it cancels has already been answered.
+                            tracing::debug!(%request_id, "late control cancel request");
                         }
+                        continue;
+                    }
 
-                        if let Incoming::RateLimitEvent(event) = incoming {
-                            tracing::trace!(
-                                status = %event.status(),
-                                utilization = ?event.utilization(),
-                                resets_at = ?event.resets_at(),
-                                "rate limit event",
+                    if let Incoming::RateLimitEvent(event) = incoming {
+                        tracing::trace!(
+                            status = %event.status(),
+                            utilization = ?event.utilization(),
+                            resets_at = ?event.resets_at(),
+                            "rate limit event",
+                        );
+                        let response = RateLimitResponse::from(event);
+                        if let Some(delay) = response.backoff_delay_at(self.clock.now()) {
+                            tracing::warn!(
+                                delay_secs = delay.as_secs_f64(),
+                                "rate limited, backing off"
                             );
-                            let response = RateLimitResponse::from(event);
-                            if let Some(delay) = response.backoff_delay() {
-                                tracing::warn!(delay_secs = delay.as_secs_f64(), "rate limited, backing off");
-                                tokio::time::sleep(delay).await;
-                            }
-                            yield Ok(Response::RateLimit(response));
-                            continue;
+                            tokio::time::sleep(delay).await;
                         }
+                        return Some(Ok(Response::RateLimit(response)));
+                    }
 
-                        if let Some(msg) = incoming.to_message() {
-                            if let Message::System(crate::proto::SystemMessage::Init(init)) = &msg
-                                && let Some(sid) = init.session_id()
-                            {
-                                *self.session_id.write().await = Some(sid.to_owned());
-                                tracing::debug!(session_id = %sid, "session initialized");
-                            }
+                    if let Incoming::StreamEvent(event) = incoming {
+                        return Some(Ok(Response::Partial(PartialResponse::from(event))));
+                    }
 
-                            for response in Response::from_message(&msg) {
-                                let is_complete = matches!(response, Response::Complete(_));
-                                yield Ok(response);
-                                if is_complete {
-                                    return;
+                    if let Some(msg) = incoming.to_message() {
+                        if let Message::System(crate::proto::SystemMessage::Init(init)) = &msg {
+                            if let Some(sid) = init.session_id() {
+                                let previous =
+                                    self.session_id.write().await.replace(sid.to_owned());
+                                if previous.as_deref() != Some(sid) {
+                                    self.server_info.write().await.take();
                                 }
+                                tracing::debug!(session_id = %sid, "session initialized");
+                            }
+                            if let Err(e) = self.verify_mcp_servers(init).await {
+                                state.done = true;
+                                return Some(Err(e));
                             }
                         }
+
+                        state.pending.extend(Response::from_message(&msg));
                     }
-                    Ok(None) => {
-                        tracing::info!("stream ended (EOF)");
-                        return;
-                    }
-                    Err(e) => {
-                        yield Err(e);
-                        return;
-                    }
+                }
+                Ok(None) => {
+                    tracing::info!("stream ended (EOF)");
+                    state.done = true;
+                }
+                Err(e) => {
+                    state.done = true;
+                    return Some(Err(e));
                 }
             }
         }
     }
 
-    async fn handle_mcp_message(
+    /// Like [`receive`](Self::receive), pairing each response with the time
+    /// it was received and its sequence number.
+    pub fn receive_with_meta(
         &self,
-        request_id: &str,
-        server_name: &str,
-        message: &Value,
-    ) -> ResponseEnvelope {
-        tracing::debug!(server_name, "handling MCP message");
-
-        match self.mcp_servers.get(server_name) {
-            Some(server) => {
-                let mcp_response = server.handle_json_message(message).await;
-                let response_data = json!({ "mcp_response": mcp_response });
-                ResponseEnvelope::success(request_id, Some(response_data))
-            }
-            None => {
-                tracing::warn!(server_name, "MCP server not found");
-                let error_response = json!({
-                    "mcp_response": {
-                        "jsonrpc": "2.0",
-                        "id": null,
-                        "error": {
-                            "code": -32601,
-                            "message": format!("MCP server '{}' not found", server_name)
-                        }
-                    }
-                });
-                ResponseEnvelope::success(request_id, Some(error_response))
-            }
-        }
+    ) -> impl Stream<Item = Result<(ResponseMeta, Response), Error>> + '_ {
+        self.receive_with_meta_within(self.max_turn_duration)
     }
 
-    async fn handle_hook_callback(
+    /// Like [`receive_with_meta`](Self::receive_with_meta), interrupting the
+    /// turn once `limit` has passed rather than the client's default.
+    pub(crate) fn receive_with_meta_within(
         &self,
-        request_id: &str,
-        hook_req: &HookCallbackRequest,
-    ) -> ResponseEnvelope {
-        let callback_id = hook_req.callback_id();
-        let input = hook_req.input();
-
-        tracing::debug!(callback_id, "handling hook callback");
+        limit: Option<Duration>,
+    ) -> impl Stream<Item = Result<(ResponseMeta, Response), Error>> + '_ {
+        self.receive_within(limit).map(|result| {
+            result.map(|response| {
+                let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+                (ResponseMeta::new(sequence, self.clock.now()), response)
+            })
+        })
+    }
 
-        let Some(entry) = self.hook_callbacks.get(callback_id) else {
-            tracing::warn!(callback_id, "hook callback not found");
-            return ResponseEnvelope::success(request_id, Some(json!({})));
+    /// Dispatches the current turn's responses to `handler`, returning once
+    /// the turn completes.
+    ///
+    /// Responses are read from the CLI into a bounded queue while the
+    /// handler works through them, so a slow handler does not stall the CLI
+    /// on a full stdout pipe. The queue's size and overflow behaviour come
+    /// from [`Options::dispatch_queue`](crate::Options::dispatch_queue).
+    pub async fn dispatch_to<H: Handler + ?Sized>(&self, handler: &H) -> Result<(), Error> {
+        let capacity = self.dispatch_queue.capacity();
+        let overflow = self.dispatch_queue.overflow();
+        let (tx, mut rx) = mpsc::channel::<Response>(capacity);
+
+        let reader = async move {
+            let mut stream = std::pin::pin!(self.receive());
+            let mut dropped = 0usize;
+            while let Some(result) = stream.next().await {
+                let response = match tx.try_send(result?) {
+                    Ok(()) => continue,
+                    Err(mpsc::error::TrySendError::Closed(_)) => break,
+                    Err(mpsc::error::TrySendError::Full(response)) => response,
+                };
+                match overflow {
+                    Overflow::DropDeltas if matches!(response, Response::Partial(_)) => {
+                        dropped += 1;
+                        continue;
+                    }
+                    Overflow::Block | Overflow::DropDeltas => {}
+                    Overflow::Error => {
+                        // Read out the rest of the turn, so it does not
+                        // reach the next one's receiver.
+                        while let Some(result) = stream.next().await {
+                            result?;
+                        }
+                        return Err(Error::DispatchQueueFull { capacity });
+                    }
+                }
+                if tx.send(response).await.is_err() {
+                    break;
+                }
+            }
+            if dropped > 0 {
+                tracing::warn!(
+                    dropped,
+                    "dropped partial responses while the handler was busy"
+                );
+            }
+            Ok(())
         };
-
-        let Some(hooks) = &self.hooks else {
-            tracing::warn!("hooks not available");
-            return ResponseEnvelope::success(request_id, Some(json!({})));
+        let dispatcher = async {
+            while let Some(response) = rx.recv().await {
+                dispatch(handler, &response).await;
+            }
         };
 
-        let session_id = input["session_id"].as_str().unwrap_or_default();
-        let transcript_path = input["transcript_path"].as_str().unwrap_or_default();
-
-        let response_data = match entry {
-            HookCallbackEntry::PreToolUse(idx) => {
-                let tool_name = input["tool_name"].as_str().unwrap_or_default();
-                let tool_input = input["tool_input"].clone();
+        let (result, ()) = tokio::join!(reader, dispatcher);
+        result
+    }
 
-                let hook_input =
-                    PreToolUseInput::new(session_id, transcript_path, tool_name, tool_input.into());
+    /// Returns a stream of the text Claude writes, ending on completion.
+    ///
+    /// All other responses are dropped; use [`Client::receive`] when tool
+    /// calls or the final result are needed.
+    pub fn text_stream(&self) -> impl Stream<Item = Result<String, Error>> + '_ {
+        self.receive().filter_map(|response| async move {
+            match response {
+                Ok(Response::Text(text)) => Some(Ok(text.content().to_owned())),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
 
-                if let Some((_, callback)) = hooks.get_pre_tool_use_hook(*idx) {
-                    let output = callback(hook_input).await;
-                    output.to_hook_response()
-                } else {
-                    json!({})
-                }
+    /// Returns a stream of Claude's thinking, ending on completion.
+    ///
+    /// Text and all other responses are dropped.
+    pub fn thinking_stream(&self) -> impl Stream<Item = Result<String, Error>> + '_ {
+        self.receive().filter_map(|response| async move {
+            match response {
+                Ok(Response::Thinking(thinking)) => Some(Ok(thinking.content().to_owned())),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
             }
-            HookCallbackEntry::PostToolUse(idx) => {
-                let tool_name = input["tool_name"].as_str().unwrap_or_default();
-                let tool_input = input["tool_input"].clone();
-                let tool_response = input["tool_response"].clone();
+        })
+    }
 
-                let hook_input = PostToolUseInput::new(
-                    session_id,
-                    transcript_path,
-                    tool_name,
+    /// Returns a stream of progressively filled snapshots of the turn's
+    /// structured output, ending with the final value.
+    ///
+    /// Requires a JSON schema to be configured. Intermediate snapshots are
+    /// parsed from the partial input of the `StructuredOutput` tool call, so
+    /// they are only produced when
+    /// [`Options::include_partial_messages`](crate::Options::include_partial_messages)
+    /// is enabled; otherwise only the final value is yielded. Snapshots that
+    /// do not deserialize into `T` are skipped, so fields that arrive late
+    /// should be optional or `#[serde(default)]`.
+    pub fn receive_structured<T>(&self) -> impl Stream<Item = Result<T, Error>> + '_
+    where
+        T: DeserializeOwned + 'static,
+    {
+        if self.json_schema.is_none() {
+            return futures::stream::once(async { Err(Error::NoSchemaConfigured) }).left_stream();
+        }
+
+        let snapshots = Snapshots {
+            responses: Box::pin(self.receive()),
+            block_index: None,
+            input: String::new(),
+            last: None,
+        };
+        futures::stream::unfold(Some(snapshots), move |state| async move {
+            let mut state = state?;
+            while let Some(response) = state.responses.next().await {
+                match response {
+                    Ok(Response::Partial(partial)) if partial.parent_tool_use_id().is_none() => {
+                        if partial.tool_use_started() == Some(crate::util::STRUCTURED_OUTPUT_TOOL) {
+                            state.block_index = partial.index();
+                            state.input.clear();
+                            continue;
+                        }
+                        let Some(fragment) = partial.input_json_delta() else {
+                            continue;
+                        };
+                        if state.block_index.is_none() || partial.index() != state.block_index {
+                            continue;
+                        }
+                        state.input.push_str(fragment);
+                        if let Some(value) = crate::partial_json::parse(&state.input)
+                            && state.last.as_ref() != Some(&value)
+                            && let Ok(snapshot) =
+                                serde_json::from_value::<T>(crate::util::unwrap_structured_output(
+                                    value.clone(),
+                                    self.json_schema_wrapper,
+                                ))
+                        {
+                            state.last = Some(value);
+                            return Some((Ok(snapshot), Some(state)));
+                        }
+                    }
+                    Ok(Response::Complete(complete)) => {
+                        let item = match self.structured_output(&complete) {
+                            Some(value) if state.last.as_ref() == Some(&value) => return None,
+                            Some(value) => {
+                                serde_json::from_value::<T>(crate::util::unwrap_structured_output(
+                                    value,
+                                    self.json_schema_wrapper,
+                                ))
+                                .map_err(Error::from)
+                            }
+                            None => Err(Error::ProtocolError(
+                                "no structured output in response".to_owned(),
+                            )),
+                        };
+                        return Some((item, None));
+                    }
+                    Ok(_) => {}
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
+            None
+        })
+        .right_stream()
+    }
+
+    /// Returns a stream of the items of the turn's structured output, one at
+    /// a time, for schemas built from a `Vec<T>`.
+    ///
+    /// With [`Options::include_partial_messages`](crate::Options::include_partial_messages),
+    /// each item is yielded as soon as its JSON is complete in the partial
+    /// input of the `StructuredOutput` tool call, and only the item being
+    /// read is buffered. Items the stream did not deliver, or all of them
+    /// without partial messages, are taken from the final result and
+    /// deserialized one by one as the stream is polled, each JSON value
+    /// dropped once read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use clauders::{Client, Options};
+    /// use futures::StreamExt;
+    /// use schemars::JsonSchema;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Debug, Deserialize, JsonSchema)]
+    /// struct Invoice {
+    ///     number: String,
+    ///     total: f64,
+    /// }
+    ///
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// let client = Client::new(
+    ///     Options::new()
+    ///         .with_json_schema::<Vec<Invoice>>()
+    ///         .include_partial_messages(true),
+    /// )
+    /// .await?;
+    /// client.query("List every invoice in invoices/").await?;
+    ///
+    /// let mut invoices = std::pin::pin!(client.receive_items::<Invoice>());
+    /// while let Some(invoice) = invoices.next().await {
+    ///     println!("{:?}", invoice?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "schema")]
+    pub fn receive_items<T>(&self) -> impl Stream<Item = Result<T, Error>> + '_
+    where
+        T: DeserializeOwned + 'static,
+    {
+        if self.json_schema.is_none() {
+            return futures::stream::once(async { Err(Error::NoSchemaConfigured) }).left_stream();
+        }
+
+        let items = Items {
+            responses: Box::pin(self.receive()),
+            block_index: None,
+            splitter: ItemSplitter::new(self.json_schema_wrapper),
+            streamed: VecDeque::new(),
+            remaining: Vec::new().into_iter(),
+            yielded: 0,
+        };
+        futures::stream::unfold(Some(items), move |state| async move {
+            let mut state = state?;
+            loop {
+                if let Some(item) = state.streamed.pop_front() {
+                    state.yielded += 1;
+                    let item = serde_json::from_str::<T>(&item).map_err(Error::from);
+                    return Some((item, Some(state)));
+                }
+                let Some(response) = state.responses.next().await else {
+                    // The final result ended the responses.
+                    let item = state.remaining.next()?;
+                    let item = serde_json::from_value::<T>(item).map_err(Error::from);
+                    return Some((item, Some(state)));
+                };
+                match response {
+                    Ok(Response::Partial(partial)) if partial.parent_tool_use_id().is_none() => {
+                        if partial.tool_use_started() == Some(crate::util::STRUCTURED_OUTPUT_TOOL) {
+                            state.block_index = partial.index();
+                            state.splitter = ItemSplitter::new(self.json_schema_wrapper);
+                            continue;
+                        }
+                        if let Some(fragment) = partial.input_json_delta()
+                            && state.block_index.is_some()
+                            && partial.index() == state.block_index
+                        {
+                            state.streamed.extend(state.splitter.push(fragment));
+                        }
+                    }
+                    Ok(Response::Complete(complete)) => {
+                        let output = self.structured_output(&complete).map(|output| {
+                            crate::util::unwrap_structured_output(output, self.json_schema_wrapper)
+                        });
+                        let Some(Value::Array(items)) = output else {
+                            let error = Error::ProtocolError(
+                                "no structured output array in response".to_owned(),
+                            );
+                            return Some((Err(error), None));
+                        };
+                        state.remaining = items.into_iter();
+                        if state.yielded > 0 {
+                            state.remaining.nth(state.yielded - 1);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
+        })
+        .right_stream()
+    }
+
+    fn log_tool_stats(&self) {
+        for (server_name, server) in &self.mcp_servers {
+            for (tool_name, stats) in server.stats() {
+                tracing::info!(
+                    tenant = self.tenant_id.as_deref().unwrap_or_default(),
+                    labels = ?self.labels,
+                    server = %server_name,
+                    tool = %tool_name,
+                    calls = stats.calls(),
+                    errors = stats.errors(),
+                    avg_duration_ms = stats.average_duration().as_millis() as u64,
+                    "tool usage",
+                );
+            }
+        }
+    }
+
+    /// Reads the next incoming message, preferring messages buffered while a
+    /// control request was waiting for its response.
+    async fn next_incoming(&self) -> Result<Option<Incoming>, Error> {
+        let buffered = self
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop_front();
+        match buffered {
+            Some(incoming) => Ok(Some(incoming)),
+            None => self.read_incoming().await,
+        }
+    }
+
+    /// Reads the next message from the CLI, meanwhile sending the responses
+    /// of hook callbacks and tool calls as they finish.
+    async fn read_incoming(&self) -> Result<Option<Incoming>, Error> {
+        loop {
+            tokio::select! {
+                biased;
+                () = self.shutdown_requested() => return Ok(None),
+                signal = self.signals.next() => self.handle_signal(signal).await,
+                request = self.peer_requests.next() => self.send_peer_request(request).await,
+                incoming = async { self.transport.lock().await.receive().await } => {
+                    match &incoming {
+                        Ok(None) => self.shutdown_mcp_servers().await,
+                        Ok(Some(Incoming::Result(result))) => {
+                            self.denials
+                                .report_result(result.permission_denials().unwrap_or_default());
+                            self.signals.turn_completed();
+                        }
+                        _ => {}
+                    }
+                    return incoming;
+                }
+            }
+        }
+    }
+
+    /// Carries out a request made by [`signal::interrupt_on_ctrl_c`](crate::signal::interrupt_on_ctrl_c).
+    async fn handle_signal(&self, signal: Signal) {
+        let result = match signal {
+            Signal::Interrupt => self.writer().interrupt().await,
+            Signal::ForceClose => self.transport.lock().await.kill().map_err(Error::from),
+        };
+        if let Err(e) = result {
+            tracing::warn!(?signal, error = %e, "failed to signal the claude CLI");
+        }
+    }
+
+    /// Requests that the reader interrupts the turn or kills the CLI.
+    pub(crate) fn signals(&self) -> Arc<Signals> {
+        Arc::clone(&self.signals)
+    }
+
+    /// Handles a CLI → SDK control request and sends the response back.
+    ///
+    /// A `can_use_tool` prompt may wait on a person for a long time, so while
+    /// it is pending the CLI can cancel it, such as when the turn is
+    /// interrupted, and no response is sent. Hook callbacks and tool calls
+    /// run on tasks of their own, aborted when the CLI cancels them.
+    async fn handle_control_request(&self, ctrl: &ControlRequestEnvelope) {
+        let response = match ctrl.request() {
+            Request::McpMessage(mcp_req) if is_tool_call(mcp_req.message()) => {
+                // Tool calls of one turn run side by side.
+                let server = self.mcp_servers.get(mcp_req.server_name()).cloned();
+                let request_id = ctrl.request_id().to_owned();
+                let server_name = mcp_req.server_name().to_owned();
+                let message = mcp_req.message().clone();
+                let ctx = self.mcp_context(&server_name);
+                self.tool_pool.spawn(
+                    ctrl.request_id(),
+                    async move {
+                        Self::mcp_response(
+                            &request_id,
+                            &server_name,
+                            server.as_deref(),
+                            &message,
+                            ctx,
+                        )
+                        .await
+                    }
+                    .boxed(),
+                );
+                return;
+            }
+            Request::McpMessage(mcp_req) => {
+                let server = self.mcp_servers.get(mcp_req.server_name());
+                Self::mcp_response(
+                    ctrl.request_id(),
+                    mcp_req.server_name(),
+                    server.map(Arc::as_ref),
+                    mcp_req.message(),
+                    self.mcp_context(mcp_req.server_name()),
+                )
+                .await
+            }
+            Request::HookCallback(hook_req) => {
+                // Answered once the hook finishes, while reading goes on.
+                self.hook_pool.spawn(
+                    ctrl.request_id(),
+                    self.hook_task(ctrl.request_id(), hook_req),
+                );
+                return;
+            }
+            Request::CanUseTool(permission_req) => {
+                tokio::select! {
+                    biased;
+                    response = self.handle_permission_request(ctrl.request_id(), permission_req) => {
+                        response
+                    }
+                    () = self.watch_cancel(ctrl.request_id()) => {
+                        tracing::debug!(
+                            request_id = %ctrl.request_id(),
+                            tool_name = permission_req.tool_name(),
+                            "permission request cancelled by the CLI"
+                        );
+                        return;
+                    }
+                }
+            }
+            other => {
+                tracing::debug!(request = ?other, "ignoring unsupported control request");
+                return;
+            }
+        };
+        self.send_response(response);
+    }
+
+    /// Runs the shutdown hooks of the in-process MCP servers, once.
+    async fn shutdown_mcp_servers(&self) {
+        if !self.mcp_shut_down.set() {
+            return;
+        }
+        for server in self.mcp_servers.values() {
+            server.shutdown().await;
+        }
+    }
+
+    /// Closes the session, letting the in-process MCP servers clean up
+    /// before the CLI is stopped.
+    ///
+    /// Shuts down as [`shutdown_gracefully`](Self::shutdown_gracefully)
+    /// does, within [`DEFAULT_SHUTDOWN_TIMEOUT`], logging any failure.
+    pub async fn close(self) {
+        if let Err(e) = self.shutdown_gracefully(DEFAULT_SHUTDOWN_TIMEOUT).await {
+            tracing::warn!(error = %e, "failed to shut down the claude CLI");
+        }
+    }
+
+    /// Shuts the client down, giving everything it runs in the background
+    /// until `timeout` to finish.
+    ///
+    /// Reads in progress end as if the CLI had disconnected. Hook callbacks,
+    /// tool calls and webhook deliveries still running are awaited, then
+    /// aborted; their responses are not sent. The MCP servers' shutdown
+    /// hooks run, and the CLI's input is closed so it exits, or is killed
+    /// once the time is up. Shutting down again does nothing.
+    ///
+    /// Dropping the client instead aborts its background tasks and kills the
+    /// CLI at once.
+    pub async fn shutdown_gracefully(&self, timeout: Duration) -> Result<(), Error> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let remaining = || deadline.saturating_duration_since(tokio::time::Instant::now());
+        self.shutdown.send_replace(true);
+
+        let aborted =
+            self.hook_pool.shutdown(remaining()).await + self.tool_pool.shutdown(remaining()).await;
+        if aborted > 0 {
+            tracing::warn!(aborted, "aborted hook callbacks and tool calls at shutdown");
+        }
+        self.tasks.shutdown(Duration::ZERO).await;
+        #[cfg(feature = "webhooks")]
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.shutdown(remaining()).await;
+        }
+        self.shutdown_mcp_servers().await;
+        self.transport.lock().await.shutdown(remaining()).await
+    }
+
+    /// Completes once [`shutdown_gracefully`](Self::shutdown_gracefully) is
+    /// called.
+    async fn shutdown_requested(&self) {
+        let mut shutdown = self.shutdown.subscribe();
+        let _ = shutdown.wait_for(|&requested| requested).await;
+    }
+
+    /// The context tools of the server `server_name` are called with.
+    fn mcp_context(&self, server_name: &str) -> ToolContext {
+        self.tool_context
+            .clone()
+            .with_peer(self.peer_requests.peer(server_name))
+    }
+
+    /// Sends a request from an in-process MCP server to the CLI. Its response
+    /// is routed back like that of any other control request.
+    async fn send_peer_request(&self, request: PeerRequest) {
+        let PeerRequest {
+            server_name,
+            message,
+            reply,
+        } = request;
+        let envelope = RequestEnvelope::new_with(
+            self.id_generator.next_id(),
+            Request::McpMessage(McpMessageRequest::new(server_name, message)),
+        );
+        self.outstanding
+            .register(envelope.request_id().to_owned(), reply);
+        let sent = self.writer().send_request(&envelope).await;
+        if let Err(e) = sent {
+            tracing::warn!(error = %e, "failed to send MCP request to the CLI");
+            // Dropping the waiter fails the request.
+            self.outstanding.take(&envelope.request_id().to_owned());
+        }
+    }
+
+    /// Hands a control response to the writer task, so reading goes on
+    /// while it is sent.
+    fn send_response(&self, response: ResponseEnvelope) {
+        if self.responses.send(response).is_err() {
+            tracing::warn!("failed to send control response: the client is shut down");
+        }
+    }
+
+    /// The writer of the running CLI's input.
+    fn writer(&self) -> TransportWriter {
+        self.writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Reads messages while the control request `request_id` from the CLI is
+    /// being handled, returning once the CLI cancels it.
+    ///
+    /// Control responses are routed to their waiters and everything else is
+    /// queued for [`receive`](Self::receive), including further control
+    /// requests, which are handled in order once this one is done.
+    async fn watch_cancel(&self, request_id: &str) {
+        loop {
+            let incoming = self.read_incoming().await;
+            match incoming {
+                Ok(Some(Incoming::ControlCancelRequest(cancel)))
+                    if cancel.request_id() == request_id =>
+                {
+                    return;
+                }
+                Ok(Some(Incoming::ControlResponse(resp))) => {
+                    self.resolve_control_response(resp.response());
+                }
+                Ok(Some(other)) => {
+                    self.pending
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push_back(other);
+                }
+                // The reader sees the end of the stream once the request is
+                // answered.
+                Ok(None) => return std::future::pending().await,
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to read while awaiting a permission decision");
+                    return std::future::pending().await;
+                }
+            }
+        }
+    }
+
+    /// Sends a cancellation of the outstanding control request `request_id`
+    /// to the CLI. The task waiting for its response gets
+    /// [`Error::ControlError`] right away.
+    pub async fn cancel_control_request(&self, request_id: &str) -> Result<(), Error> {
+        let waiter = self.outstanding.take(&request_id.to_owned());
+        if let Some(tx) = waiter {
+            let _ = tx.send(crate::proto::Response::Error(ErrorResponse::new(
+                request_id,
+                ErrorDetail::new(ErrorCode::InternalError.to_i32(), "request cancelled"),
+            )));
+        }
+        self.writer()
+            .send_cancel(&CancelRequestEnvelope::new(request_id))
+            .await
+    }
+
+    /// Routes a control response to the task waiting on its request id.
+    fn resolve_control_response(&self, response: &crate::proto::Response) {
+        let waiter = self.outstanding.take(&response.request_id().to_owned());
+        match waiter {
+            Some(tx) => {
+                let _ = tx.send(response.clone());
+            }
+            None => {
+                tracing::debug!(
+                    request_id = %response.request_id(),
+                    "control response for unknown request"
+                );
+            }
+        }
+    }
+
+    /// Returns the ids of control requests still awaiting a response.
+    pub fn outstanding_control_requests(&self) -> Vec<String> {
+        self.outstanding.ids()
+    }
+
+    /// Sends a control request and waits for the matching response.
+    ///
+    /// Messages that arrive in the meantime are buffered for [`receive`](Self::receive),
+    /// and control requests from the CLI are answered as usual. Fails with
+    /// [`Error::Timeout`] if no response arrives within the configured control timeout.
+    pub(crate) async fn send_control(&self, request: Request) -> Result<Option<Value>, Error> {
+        let envelope = RequestEnvelope::new_with(self.id_generator.next_id(), request);
+        let request_id = envelope.request_id().to_owned();
+
+        let (tx, mut rx) = oneshot::channel();
+        self.outstanding.register(request_id.clone(), tx);
+
+        let result = async {
+            self.writer().send_request(&envelope).await?;
+            let deadline = tokio::time::Instant::now() + self.control_timeout;
+            self.await_control_response(&mut rx, deadline)
+                .await?
+                .ok_or_else(|| {
+                    Error::Timeout(format!(
+                        "no response to control request {request_id} within {:?}",
+                        self.control_timeout
+                    ))
+                })
+        }
+        .await;
+
+        self.outstanding.take(&request_id);
+
+        match result? {
+            crate::proto::Response::Success(success) => Ok(success.response().cloned()),
+            crate::proto::Response::Error(err) => Err(Error::ControlError {
+                request_id: err.request_id().to_owned(),
+                message: err.error().message().to_owned(),
+            }),
+        }
+    }
+
+    /// Reads messages until the response `rx` waits for arrives, or returns
+    /// `None` once `deadline` passes.
+    ///
+    /// Only the reads race the response and the deadline: a message read is
+    /// always routed, so a control request taken off the transport is never
+    /// dropped unanswered.
+    async fn await_control_response(
+        &self,
+        rx: &mut oneshot::Receiver<crate::proto::Response>,
+        deadline: tokio::time::Instant,
+    ) -> Result<Option<crate::proto::Response>, Error> {
+        loop {
+            let incoming = tokio::select! {
+                biased;
+                response = &mut *rx => {
+                    return response.map(Some).map_err(|_| {
+                        Error::ConnectionError("control response channel closed".to_owned())
+                    });
+                }
+                incoming = self.read_incoming() => incoming?,
+                () = tokio::time::sleep_until(deadline) => return Ok(None),
+            };
+            self.route_control_wait(incoming).await?;
+        }
+    }
+
+    /// Routes a message read on behalf of a control request waiter.
+    ///
+    /// Control responses go to their waiters and control requests are
+    /// handled, except permission requests, which may wait on a decision
+    /// for as long as the user takes; those are queued for
+    /// [`receive`](Self::receive) along with everything else.
+    async fn route_control_wait(&self, incoming: Option<Incoming>) -> Result<(), Error> {
+        let ctrl = match incoming {
+            Some(Incoming::ControlResponse(resp)) => {
+                self.resolve_control_response(resp.response());
+                return Ok(());
+            }
+            Some(Incoming::ControlRequest(ctrl))
+                if !matches!(ctrl.request(), Request::CanUseTool(_)) =>
+            {
+                ctrl
+            }
+            Some(other) => {
+                self.pending
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push_back(other);
+                return Ok(());
+            }
+            None => return Err(Error::ConnectionError("stream ended".to_owned())),
+        };
+        self.handle_control_request(&ctrl).await;
+        Ok(())
+    }
+
+    async fn mcp_response(
+        request_id: &str,
+        server_name: &str,
+        server: Option<&McpServer>,
+        message: &Value,
+        ctx: ToolContext,
+    ) -> ResponseEnvelope {
+        tracing::debug!(server_name, "handling MCP message");
+
+        match server {
+            Some(server) => {
+                let mcp_response = server.handle_json_message_with(message, ctx).await;
+                let response_data = json!({ "mcp_response": mcp_response });
+                ResponseEnvelope::success(request_id, Some(response_data))
+            }
+            None => {
+                tracing::warn!(server_name, "MCP server not found");
+                let error_response = json!({
+                    "mcp_response": {
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": {
+                            "code": -32601,
+                            "message": format!("MCP server '{}' not found", server_name)
+                        }
+                    }
+                });
+                ResponseEnvelope::success(request_id, Some(error_response))
+            }
+        }
+    }
+
+    async fn handle_permission_request(
+        &self,
+        request_id: &str,
+        permission_req: &PermissionRequest,
+    ) -> ResponseEnvelope {
+        let tool_name = permission_req.tool_name();
+        tracing::debug!(tool_name, "handling permission request");
+
+        let mut context = PermissionContext::from_request(permission_req).with_permission_mode(
+            *self
+                .current_permission_mode
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        if let Some(session_id) = self.session_id().await {
+            context.set_session_id(Some(session_id));
+        }
+
+        // A deferred decision is awaited until the CLI cancels the request.
+        let decision = match self.permission_callbacks.decide(&context) {
+            Some(Decision::Deferred(deferred)) => Some(deferred.wait().await),
+            decision => decision,
+        };
+        let (source, decision) = match (decision, &self.approvals) {
+            (Some(Decision::Ask { reason }), Some(broker)) => {
+                let request = ApprovalRequest::from_context(&context).with_reason(reason);
+                (DenialSource::Approver, broker.decide(&request).await)
+            }
+            (Some(decision), _) => (DenialSource::PermissionCallback, decision),
+            (None, Some(broker)) => (
+                DenialSource::Approver,
+                broker
+                    .decide(&ApprovalRequest::from_context(&context))
+                    .await,
+            ),
+            (None, None) => {
+                tracing::warn!(tool_name, "permission request without an approval broker");
+                (
+                    DenialSource::Unhandled,
+                    Decision::deny(format!("Tool '{tool_name}' not allowed")),
+                )
+            }
+        };
+        let response_data = approvals::permission_response(decision, permission_req.input());
+        if response_data["behavior"] == "deny" {
+            let rule = match source {
+                DenialSource::PermissionCallback => self.permission_callbacks.rule_for(tool_name),
+                _ => None,
+            };
+            self.denials.report(
+                DenialEvent::new(source, tool_name, permission_req.input().clone())
+                    .with_tool_use_id(
+                        permission_req
+                            .extra()
+                            .get("tool_use_id")
+                            .and_then(Value::as_str)
+                            .map(str::to_owned),
+                    )
+                    .with_rule(rule)
+                    .with_reason(response_data["message"].as_str().map(str::to_owned)),
+            );
+        }
+        ResponseEnvelope::success(request_id, Some(response_data))
+    }
+
+    /// Prepares the answer to a hook callback from the CLI, to be run on the
+    /// hook pool.
+    fn hook_task(
+        &self,
+        request_id: &str,
+        hook_req: &HookCallbackRequest,
+    ) -> BoxFuture<'static, ResponseEnvelope> {
+        let callback_id = hook_req.callback_id().to_owned();
+        let input = hook_req.input();
+
+        tracing::debug!(callback_id, "handling hook callback");
+
+        let empty = ResponseEnvelope::success(request_id, Some(json!({})));
+        let Some(entry) = self.hook_callbacks.get(&callback_id) else {
+            tracing::warn!(callback_id, "hook callback not found");
+            return std::future::ready(empty).boxed();
+        };
+
+        let Some(hooks) = &self.hooks else {
+            tracing::warn!(callback_id, "hooks not available");
+            return std::future::ready(empty).boxed();
+        };
+
+        let session_id = input["session_id"].as_str().unwrap_or_default();
+        let transcript_path = input["transcript_path"].as_str().unwrap_or_default();
+        let tool_use_id = hook_req
+            .tool_use_id()
+            .or_else(|| input["tool_use_id"].as_str());
+
+        let hook: BoxFuture<'static, Value> = match entry {
+            HookCallbackEntry::PreToolUse(idx) => {
+                let tool_name = input["tool_name"].as_str().unwrap_or_default();
+                let tool_input = input["tool_input"].clone();
+
+                let mut hook_input = PreToolUseInput::new(
+                    session_id,
+                    transcript_path,
+                    tool_name,
+                    tool_input.clone().into(),
+                );
+                if let Some(id) = tool_use_id {
+                    hook_input = hook_input.with_tool_use_id(id);
+                }
+
+                if let Some(hook) = hooks.get_pre_tool_use_hook(*idx) {
+                    let callback = hook.callback().clone();
+                    let rule = hook.name().map(str::to_owned);
+                    let approvals = self.approvals.clone();
+                    let denials = self.denials.clone();
+                    let tool_name = tool_name.to_owned();
+                    let tool_use_id = tool_use_id.map(str::to_owned);
+                    async move {
+                        let output = callback(hook_input).await;
+                        let (source, output) = match approvals {
+                            Some(broker) if output.decision() == Some(PreToolUseDecision::Ask) => (
+                                DenialSource::Approver,
+                                broker
+                                    .resolve_ask(&tool_name, tool_input.clone().into(), output)
+                                    .await,
+                            ),
+                            _ => (DenialSource::Hook, output),
+                        };
+                        if output.decision() == Some(PreToolUseDecision::Deny) {
+                            denials.report(
+                                DenialEvent::new(source, tool_name, tool_input)
+                                    .with_tool_use_id(tool_use_id)
+                                    .with_rule(rule)
+                                    .with_reason(output.reason().map(str::to_owned)),
+                            );
+                        }
+                        output.to_hook_response()
+                    }
+                    .boxed()
+                } else {
+                    return std::future::ready(empty).boxed();
+                }
+            }
+            HookCallbackEntry::PostToolUse(idx) => {
+                let tool_name = input["tool_name"].as_str().unwrap_or_default();
+                let tool_input = input["tool_input"].clone();
+                let tool_response = input["tool_response"].clone();
+
+                let mut hook_input = PostToolUseInput::new(
+                    session_id,
+                    transcript_path,
+                    tool_name,
                     tool_input.into(),
                     tool_response,
                 );
+                if let Some(id) = tool_use_id {
+                    hook_input = hook_input.with_tool_use_id(id);
+                }
 
-                if let Some((_, callback)) = hooks.get_post_tool_use_hook(*idx) {
-                    let output = callback(hook_input).await;
-                    output.to_hook_response()
+                if let Some(hook) = hooks.get_post_tool_use_hook(*idx) {
+                    let callback = hook.callback().clone();
+                    async move { callback(hook_input).await.to_hook_response() }.boxed()
                 } else {
-                    json!({})
+                    return std::future::ready(empty).boxed();
                 }
             }
             HookCallbackEntry::UserPromptSubmit(idx) => {
@@ -533,11 +1839,11 @@ impl Client {
 
                 let hook_input = UserPromptSubmitInput::new(session_id, transcript_path, prompt);
 
-                if let Some(callback) = hooks.user_prompt_submit_hooks().nth(*idx) {
-                    let output = callback(hook_input).await;
-                    output.to_hook_response()
+                if let Some(hook) = hooks.get_user_prompt_submit_hook(*idx) {
+                    let callback = hook.callback().clone();
+                    async move { callback(hook_input).await.to_hook_response() }.boxed()
                 } else {
-                    json!({})
+                    return std::future::ready(empty).boxed();
                 }
             }
             HookCallbackEntry::Stop(idx) => {
@@ -545,16 +1851,65 @@ impl Client {
 
                 let hook_input = StopInput::new(session_id, transcript_path, stop_hook_active);
 
-                if let Some(callback) = hooks.stop_hooks().nth(*idx) {
-                    let output = callback(hook_input).await;
-                    output.to_hook_response()
+                if let Some(hook) = hooks.get_stop_hook(*idx) {
+                    let callback = hook.callback().clone();
+                    let limit = self.continuation_limit.clone();
+                    async move {
+                        let output = callback(hook_input).await;
+                        match limit {
+                            Some(limit) => limit.check(stop_hook_active, output).to_hook_response(),
+                            None => output.to_hook_response(),
+                        }
+                    }
+                    .boxed()
                 } else {
-                    json!({})
+                    return std::future::ready(empty).boxed();
                 }
             }
         };
 
-        ResponseEnvelope::success(request_id, Some(response_data))
+        let request_id = request_id.to_owned();
+        let event = entry.event();
+        let on_error = hooks.error_callback().cloned();
+        async move {
+            match Self::run_hook(&callback_id, hook).await {
+                Ok(response) => ResponseEnvelope::success(&request_id, Some(response)),
+                Err(e) => {
+                    tracing::error!(callback_id, error = %e, "hook callback failed");
+                    if let Some(on_error) = on_error
+                        && let Error::HookError { message, .. } = &e
+                    {
+                        on_error(&HookErrorEvent::new(event, &callback_id, message));
+                    }
+                    ResponseEnvelope::error(&request_id, ErrorCode::InternalError, e.to_string())
+                }
+            }
+        }
+        .boxed()
+    }
+
+    /// Returns the continuation limit if Stop hooks exceeded it since the
+    /// last call.
+    pub(crate) fn take_continuation_limit_exceeded(&self) -> Option<u32> {
+        self.continuation_limit
+            .as_deref()
+            .and_then(ContinuationLimit::take_exceeded)
+    }
+
+    /// Runs a hook callback on its own task, so a panicking hook is reported
+    /// back to the CLI as a failed callback rather than unwinding through the
+    /// receive loop.
+    async fn run_hook<F, T>(callback_id: &str, hook: F) -> Result<T, Error>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        crate::util::spawn_isolated(hook)
+            .await
+            .map_err(|e| Error::HookError {
+                callback_id: callback_id.to_owned(),
+                message: crate::util::join_error_message(e),
+            })
     }
 
     /// Receives all responses until completion, collecting them into a vector.
@@ -622,12 +1977,14 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
+    #[cfg(feature = "schema")]
     pub async fn query_once_as<T>(&self, prompt: &str) -> Result<(T, Responses), Error>
     where
         T: DeserializeOwned + JsonSchema,
     {
         // Verify schema matches (use same stripped format as with_json_schema)
-        let expected_schema = crate::util::schema_for_structured_output::<T>().to_string();
+        let (expected_schema, wrapper) = crate::util::structured_output_schema::<T>();
+        let expected_schema = expected_schema.to_string();
         match &self.json_schema {
             Some(configured) if configured == &expected_schema => {}
             Some(configured) => {
@@ -643,73 +2000,1178 @@ impl Client {
 
         self.query(prompt).await?;
         let responses = Responses::from(self.receive_all().await?);
+        let result = self.parse_structured_output(&responses, wrapper)?;
+        Ok((result, responses))
+    }
 
-        // The structured output comes from the result message's structuredOutput field
+    /// Deserializes the structured output of a finished turn.
+    #[cfg(feature = "schema")]
+    pub(crate) fn parse_structured_output<T: DeserializeOwned>(
+        &self,
+        responses: &Responses,
+        wrapper: Option<&str>,
+    ) -> Result<T, Error> {
         let structured_output = responses
             .completion()
-            .and_then(|c| c.structured_output())
-            .cloned()
+            .and_then(|c| self.structured_output(c))
             .ok_or_else(|| Error::ProtocolError("no structured output in response".to_owned()))?;
 
-        let result = serde_json::from_value::<T>(structured_output)?;
+        Ok(serde_json::from_value::<T>(
+            crate::util::unwrap_structured_output(structured_output, wrapper),
+        )?)
+    }
 
-        Ok((result, responses))
+    /// Whether the CLI lacks `--json-schema`, so the schema is asked for in
+    /// the system prompt and structured output is extracted from the reply.
+    pub fn schema_in_prompt(&self) -> bool {
+        self.schema_in_prompt
+    }
+
+    /// The structured output of a turn: the result's `structured_output`,
+    /// or the JSON at the end of the reply when the schema was asked for in
+    /// the prompt.
+    pub(crate) fn structured_output(&self, complete: &CompleteResponse) -> Option<Value> {
+        if let Some(output) = complete.structured_output() {
+            return Some(output.clone());
+        }
+        if !self.schema_in_prompt {
+            return None;
+        }
+        crate::util::extract_json(complete.result_text()?)
     }
 
     /// Sends an interrupt signal to stop the current operation.
     pub async fn interrupt(&self) -> Result<(), Error> {
-        self.transport.lock().await.interrupt().await
+        self.writer().interrupt().await
     }
 
     /// Sets the permission mode for tool execution.
+    ///
+    /// Waits for the CLI to acknowledge the change and returns
+    /// [`Error::ControlError`] if it is rejected. Switching to
+    /// [`PermissionMode::BypassPermissions`](crate::PermissionMode::BypassPermissions)
+    /// fails with [`ConfigError::UnacknowledgedBypass`](crate::ConfigError::UnacknowledgedBypass)
+    /// unless the client was created with
+    /// [`Options::bypass_permissions`](crate::Options::bypass_permissions).
     pub async fn set_permission_mode(
         &self,
         mode: crate::proto::PermissionMode,
     ) -> Result<(), Error> {
+        if mode == crate::proto::PermissionMode::BypassPermissions && !self.bypass_acknowledged {
+            return Err(crate::error::ConfigError::UnacknowledgedBypass.into());
+        }
         let request = crate::proto::Request::SetPermissionMode(
             crate::proto::control::SetPermissionModeRequest::new(mode),
         );
-        let envelope = RequestEnvelope::new(request);
-        self.transport.lock().await.send_request(&envelope).await
+        self.send_control(request).await?;
+        *self
+            .current_permission_mode
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = mode;
+        Ok(())
+    }
+
+    /// Stops the CLI and starts it again, resuming the current session.
+    ///
+    /// Variables in `env` are set on top of those the CLI was started with,
+    /// and kept for later restarts. Responses not yet received from the old
+    /// process are lost.
+    pub async fn restart(&self, env: Vec<(String, String)>) -> Result<(), Error> {
+        let session_id = self.session_id().await;
+        let options = {
+            let mut options = self
+                .transport_options
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            options.resume_with(session_id.clone(), env);
+            options.clone()
+        };
+        tracing::info!(session_id = ?session_id, "restarting claude CLI");
+        self.respawn(&options).await
+    }
+
+    /// Stops the CLI and starts it on a fork of the current session that
+    /// ends at the message `message_id`, so the next prompt continues from
+    /// there.
+    pub(crate) async fn fork_at(&self, message_id: &str) -> Result<(), Error> {
+        let Some(session_id) = self.session_id().await else {
+            return Err(Error::ProtocolError(
+                "cannot fork before the session has started".to_owned(),
+            ));
+        };
+        let options = {
+            let mut options = self
+                .transport_options
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            options.fork_at(session_id.clone(), message_id.to_owned());
+            options.clone()
+        };
+        tracing::info!(%session_id, message_id, "forking claude CLI session");
+        self.respawn(&options).await
+    }
+
+    /// Replaces the CLI with one started with `options`.
+    async fn respawn(&self, options: &TransportOptions) -> Result<(), Error> {
+        let transport = Transport::new(options).await?;
+        *self.writer.lock().unwrap_or_else(PoisonError::into_inner) = transport.writer();
+        let old = std::mem::replace(&mut *self.transport.lock().await, transport);
+        drop(old);
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        // Waiters of the old CLI fail rather than wait for the new one.
+        self.outstanding.drain();
+        // What the old CLI reported does not describe the new one.
+        self.initialize_response
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        self.registered_hooks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.server_info.write().await.take();
+        self.initialize().await
+    }
+
+    /// How turns recover from expired credentials, if they do.
+    pub(crate) fn auth_refresh(&self) -> Option<&AuthRefresh> {
+        self.auth_refresh.as_ref()
+    }
+
+    /// The id of the [`Tenant`](crate::tenant::Tenant) the client works for.
+    pub fn tenant_id(&self) -> Option<&str> {
+        self.tenant_id.as_deref()
+    }
+
+    /// The labels set with [`Options::label`].
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+
+    /// The permission profile the client was created with.
+    pub fn profile(&self) -> Option<&PermissionProfile> {
+        self.profile.as_ref()
+    }
+
+    /// Narrows the client's permissions to `profile` until
+    /// [`leave_turn_profile`](Self::leave_turn_profile).
+    pub(crate) async fn enter_turn_profile(&self, profile: PermissionProfile) -> Result<(), Error> {
+        let Some(turn_profile) = &self.turn_profile else {
+            return Err(crate::error::ConfigError::ProfileRequired.into());
+        };
+        if let Some(mode) = profile.mode() {
+            self.set_permission_mode(mode).await?;
+        }
+        turn_profile.set(Some(profile));
+        Ok(())
+    }
+
+    /// Restores the client's own permissions after a turn profile.
+    pub(crate) async fn leave_turn_profile(
+        &self,
+        profile: &PermissionProfile,
+    ) -> Result<(), Error> {
+        if let Some(turn_profile) = &self.turn_profile {
+            turn_profile.set(None);
+        }
+        if profile.mode().is_some() {
+            self.set_permission_mode(self.permission_mode).await?;
+        }
+        Ok(())
     }
 
     /// Sets the Claude model to use for subsequent queries.
+    ///
+    /// Waits for the CLI to acknowledge the change and returns
+    /// [`Error::ControlError`] if it is rejected.
     pub async fn set_model(&self, model: &str) -> Result<(), Error> {
         let request =
             crate::proto::Request::SetModel(crate::proto::control::SetModelRequest::new(model));
-        let envelope = RequestEnvelope::new(request);
-        self.transport.lock().await.send_request(&envelope).await
+        self.send_control(request).await.map(|_| ())
     }
 
     /// Retrieves information about the Claude Code server.
+    ///
+    /// The result is cached after the first successful call and invalidated
+    /// when the CLI starts a new session; use
+    /// [`refresh_server_info`](Self::refresh_server_info) to force a round-trip.
     pub async fn get_server_info(&self) -> Result<crate::proto::ServerInfo, Error> {
-        let request = crate::proto::Request::GetServerInfo;
-        let envelope = RequestEnvelope::new(request);
+        if let Some(info) = self.server_info.read().await.as_ref() {
+            return Ok(info.clone());
+        }
+        self.refresh_server_info().await
+    }
 
-        let mut transport = self.transport.lock().await;
-        transport.send_request(&envelope).await?;
+    /// Returns the typed capabilities advertised by the Claude Code server.
+    pub async fn capabilities(&self) -> Result<crate::proto::Capabilities, Error> {
+        let info = self.get_server_info().await?;
+        Ok(crate::proto::Capabilities::from(&info))
+    }
 
-        loop {
-            match transport.receive().await? {
-                Some(Incoming::ControlResponse(resp)) => match resp.response() {
-                    crate::proto::Response::Success(success) => {
-                        if let Some(data) = success.response() {
-                            let info =
-                                serde_json::from_value::<crate::proto::ServerInfo>(data.clone())?;
-                            return Ok(info);
-                        }
-                        return Err(Error::ProtocolError("empty response".to_owned()));
-                    }
-                    crate::proto::Response::Error(err) => {
-                        return Err(Error::ControlError {
-                            request_id: err.request_id().to_owned(),
-                            message: err.error().message().to_owned(),
-                        });
+    /// Queries the Claude Code server for fresh information, replacing the cache.
+    pub async fn refresh_server_info(&self) -> Result<crate::proto::ServerInfo, Error> {
+        let data = self
+            .send_control(crate::proto::Request::GetServerInfo)
+            .await?
+            .ok_or_else(|| Error::ProtocolError("empty response".to_owned()))?;
+        let info = serde_json::from_value::<crate::proto::ServerInfo>(data)?;
+        *self.server_info.write().await = Some(info.clone());
+        Ok(info)
+    }
+}
+
+/// Sends control responses as they are ready: those of hook callbacks and
+/// tool calls as their tasks finish, and those handed over with
+/// `responses`. Ends once `responses` closes.
+async fn write_responses(
+    writer: Arc<std::sync::Mutex<TransportWriter>>,
+    hook_pool: Arc<TaskPool<ResponseEnvelope>>,
+    tool_pool: Arc<TaskPool<ResponseEnvelope>>,
+    mut responses: mpsc::UnboundedReceiver<ResponseEnvelope>,
+) {
+    loop {
+        let response = tokio::select! {
+            response = hook_pool.next_completed() => response,
+            response = tool_pool.next_completed() => response,
+            response = responses.recv() => match response {
+                Some(response) => response,
+                None => return,
+            },
+        };
+        let writer = writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Err(e) = writer.send_response(&response).await {
+            tracing::warn!(error = %e, "failed to send control response");
+        }
+    }
+}
+
+impl Drop for Client {
+    /// Logs the tool usage statistics if asked to, and runs the MCP servers'
+    /// shutdown hooks on the current runtime, unless [`close`](Client::close)
+    /// or a disconnect already did.
+    fn drop(&mut self) {
+        if self.log_tool_stats {
+            self.log_tool_stats();
+        }
+        if self.mcp_servers.is_empty() || !self.mcp_shut_down.set() {
+            return;
+        }
+        let servers = self.mcp_servers.values().cloned().collect::<Vec<_>>();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    for server in servers {
+                        server.shutdown().await;
                     }
-                },
-                Some(_) => continue,
-                None => return Err(Error::ConnectionError("stream ended".to_owned())),
+                });
             }
+            Err(_) => tracing::warn!("no runtime to run MCP server shutdown hooks on"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncBufReadExt;
+
+    use super::*;
+    #[cfg(unix)]
+    use crate::fake_cli::{FakeCli, assistant_text, result};
+    use crate::hooks::{PreToolUseOutput, StopOutput};
+    use crate::tool::Tool;
+
+    #[tokio::test]
+    async fn test_run_hook_reports_panic_as_hook_error() {
+        let result = Client::run_hook("stop_0", async { panic!("boom") }).await;
+        match result {
+            Err(Error::HookError {
+                callback_id,
+                message,
+            }) => {
+                assert_eq!(callback_id, "stop_0");
+                assert!(message.contains("boom"));
+            }
+            other => panic!("expected hook error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_run_side_by_side() {
+        let tool = crate::tool::Tool::new("wait", "waits", json!({}), None, |_| async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(crate::tool::Tool::text_result("done"))
+        });
+        let server = Arc::new(McpServer::new("test", vec![tool]));
+        let pool = TaskPool::new(2);
+        // The paused clock jumps straight to each sleep's end once every
+        // task is waiting.
+        tokio::time::pause();
+        let started = tokio::time::Instant::now();
+        for id in ["1", "2"] {
+            let server = server.clone();
+            let message = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": {"name": "wait"},
+            });
+            assert!(is_tool_call(&message));
+            pool.spawn(
+                id,
+                async move {
+                    Client::mcp_response(id, "test", Some(&server), &message, ToolContext::new())
+                        .await
+                }
+                .boxed(),
+            );
+        }
+        pool.next_completed().await;
+        pool.next_completed().await;
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert_eq!(server.tool_stats("wait").unwrap().calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_responses_are_written_without_the_reader() {
+        let (stdin, cli) = tokio::io::duplex(4096);
+        let writer = Arc::new(std::sync::Mutex::new(TransportWriter::new(
+            Box::new(stdin),
+            Default::default(),
+        )));
+        let hook_pool = Arc::new(TaskPool::new(1));
+        let tool_pool = Arc::new(TaskPool::new(1));
+        let (responses, pending) = mpsc::unbounded_channel();
+        let task = tokio::spawn(write_responses(
+            writer,
+            Arc::clone(&hook_pool),
+            tool_pool,
+            pending,
+        ));
+
+        hook_pool.spawn(
+            "hook",
+            std::future::ready(ResponseEnvelope::success("hook", None)).boxed(),
+        );
+        responses
+            .send(ResponseEnvelope::success("permission", Some(json!({}))))
+            .unwrap();
+        let mut lines = tokio::io::BufReader::new(cli).lines();
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let line = lines.next_line().await.unwrap().unwrap();
+            let sent = serde_json::from_str::<Value>(&line).unwrap();
+            ids.push(sent["response"]["request_id"].as_str().unwrap().to_owned());
+        }
+        ids.sort();
+        assert_eq!(ids, ["hook", "permission"]);
+
+        drop(responses);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_checks_the_cli_exists() {
+        let missing = std::env::temp_dir().join(format!("clauders-cli-{}", crate::util::uuid_v7()));
+        let err = Client::dry_run(Options::new().cli_path(&missing))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CliNotFound(_)));
+
+        std::fs::write(&missing, "").unwrap();
+        let preview = Client::dry_run(Options::new().cli_path(&missing))
+            .await
+            .unwrap();
+        assert_eq!(preview.program(), missing);
+        std::fs::remove_file(&missing).unwrap();
+    }
+
+    #[test]
+    fn test_hooks_config_from_descriptions() {
+        let hooks = Hooks::new()
+            .on_pre_tool_use("Bash", |_| async { PreToolUseOutput::allow() })
+            .on_pre_tool_use_named::<_, &str, _, _>("guard", None, |_| async {
+                PreToolUseOutput::allow()
+            })
+            .on_stop(|_| async { StopOutput::pass() })
+            .on_stop_named("verify", |_| async { StopOutput::pass() });
+        let descriptions = hooks.describe();
+        assert_eq!(
+            serde_json::to_value(&descriptions[..2]).unwrap(),
+            json!([
+                {"event": "PreToolUse", "matcher": "Bash", "callback_id": "pre_tool_use_0"},
+                {"event": "PreToolUse", "name": "guard", "callback_id": "pre_tool_use:guard"},
+            ])
+        );
+        assert_eq!(
+            json!(Client::build_hooks_config(&descriptions)),
+            json!({
+                "PreToolUse": [
+                    {"matcher": "Bash", "hookCallbackIds": ["pre_tool_use_0"]},
+                    {"matcher": null, "hookCallbackIds": ["pre_tool_use:guard"]},
+                ],
+                "Stop": [{"hookCallbackIds": ["stop_0", "stop:verify"]}],
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_control_responses_are_matched_by_request_id() {
+        let cli = FakeCli::initialized(
+            r#"next
+emit '{"type":"control_response","response":{"subtype":"success","request_id":"other","response":{"version":"0.0"}}}'
+reply '{"version":"2.0"}'
+drain"#,
+        );
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let info = client.refresh_server_info().await.unwrap();
+        assert_eq!(info.version(), "2.0");
+        assert!(client.outstanding_control_requests().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_control_request_times_out() {
+        let cli = FakeCli::initialized("next\ndrain");
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .control_timeout(Duration::from_millis(200));
+        let client = Client::new(options).await.unwrap();
+        let err = client.refresh_server_info().await.unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)), "{err:?}");
+        assert!(client.outstanding_control_requests().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_requests_read_while_awaiting_a_response_are_answered() {
+        // The CLI asks for a permission decision and an MCP tool listing
+        // while a control request is outstanding, and answers it only once
+        // the listing is answered. The permission request is queued and
+        // answered by the next reader.
+        let cli = FakeCli::initialized(&format!(
+            r#"next; ours=$id
+emit '{{"type":"control_request","request_id":"perm_1","request":{{"subtype":"can_use_tool","tool_name":"Read","input":{{}}}}}}'
+emit '{{"type":"control_request","request_id":"mcp_1","request":{{"subtype":"mcp_message","server_name":"tools","message":{{"jsonrpc":"2.0","id":1,"method":"tools/list"}}}}}}'
+next
+case "$line" in *mcp_1*) id=$ours; reply '{{"version":"2.0"}}' ;; esac
+next
+case "$line" in *perm_1*allow*) emit '{}' ;; esac
+emit '{}'
+drain"#,
+            assistant_text("allowed"),
+            result(),
+        ));
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .with_mcp_server("tools", Arc::new(McpServer::new("tools", Vec::new())))
+            .permission_callbacks(PermissionCallbacks::new().fallback(|_| Decision::allow()));
+        let client = Client::new(options).await.unwrap();
+
+        let info = client.refresh_server_info().await.unwrap();
+        assert_eq!(info.version(), "2.0");
+
+        let responses = client.receive().collect::<Vec<_>>().await;
+        let text = responses
+            .iter()
+            .filter_map(|r| r.as_ref().ok()?.as_text().map(|t| t.content().to_owned()))
+            .collect::<String>();
+        assert_eq!(text, "allowed");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_initialize_checks_hook_registration() {
+        let options = |cli: &FakeCli| {
+            Options::new().cli_path(cli.path()).cwd(cli.dir()).hooks(
+                Hooks::new().on_pre_tool_use("Bash", |_| async { PreToolUseOutput::allow() }),
+            )
+        };
+
+        let cli = FakeCli::new(
+            r#"next; reply '{"hooks":{"PreToolUse":[{"matcher":"Bash","hookCallbackIds":["pre_tool_use_0"]}]}}'
+drain"#,
+        );
+        let client = Client::new(options(&cli)).await.unwrap();
+        assert_eq!(client.registered_hooks().len(), 1);
+
+        let cli = FakeCli::new(
+            r#"next; reply '{"hooks":{"PreToolUse":[]}}'
+drain"#,
+        );
+        let err = Client::new(options(&cli)).await.err().unwrap();
+        assert!(
+            matches!(&err, Error::HookError { callback_id, .. } if callback_id == "pre_tool_use_0"),
+            "{err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_initialization_errors_are_not_received_again() {
+        // The restarted CLI reports an error while initializing.
+        let cli = FakeCli::new(
+            r#"if [ -e started ]; then
+    next
+    emit '{"type":"system","subtype":"error","error":"bad settings"}'
+    reply '{}'
+else
+    touch started
+    next; reply '{}'
+fi
+drain"#,
+        );
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+
+        let err = client.restart(Vec::new()).await.unwrap_err();
+        assert!(err.to_string().contains("bad settings"), "{err}");
+        assert_eq!(client.memory_stats().pending_messages, 0);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_panicking_hook_is_reported_and_the_session_continues() {
+        let cli = FakeCli::initialized(&format!(
+            r#"emit '{{"type":"control_request","request_id":"hook_1","request":{{"subtype":"hook_callback","callback_id":"pre_tool_use_0","input":{{"tool_name":"Bash","tool_input":{{}}}}}}}}'
+next
+case "$line" in *hook_1*error*) emit '{}' ;; esac
+emit '{}'
+drain"#,
+            assistant_text("still here"),
+            result(),
+        ));
+        let errors = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hooks = Hooks::new()
+            .on_pre_tool_use("Bash", |_| async { panic!("boom") })
+            .on_error({
+                let errors = Arc::clone(&errors);
+                move |event| errors.lock().unwrap().push(event.clone())
+            });
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .hooks(hooks);
+        let client = Client::new(options).await.unwrap();
+
+        let responses = client.receive().collect::<Vec<_>>().await;
+        let text = responses
+            .iter()
+            .filter_map(|r| r.as_ref().ok()?.as_text().map(|t| t.content().to_owned()))
+            .collect::<String>();
+        assert_eq!(text, "still here");
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].event(), HookEvent::PreToolUse);
+        assert_eq!(errors[0].callback_id(), "pre_tool_use_0");
+        assert!(
+            errors[0].message().contains("boom"),
+            "{}",
+            errors[0].message()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rejected_sdk_mcp_server_fails_the_turn() {
+        let init = |status: &str| {
+            format!(
+                r#"{{"type":"system","subtype":"init","session_id":"s1","mcp_servers":[{{"name":"tools","status":"{status}"}}]}}"#
+            )
+        };
+        let options = |cli: &FakeCli| {
+            Options::new()
+                .cli_path(cli.path())
+                .cwd(cli.dir())
+                .with_mcp_server("tools", Arc::new(McpServer::new("tools", Vec::new())))
+        };
+
+        let cli = FakeCli::initialized(&format!("emit '{}'\ndrain", init("failed")));
+        let client = Client::new(options(&cli)).await.unwrap();
+        let responses = client.receive().collect::<Vec<_>>().await;
+        assert!(
+            matches!(
+                responses.last(),
+                Some(Err(Error::McpServerUnavailable { name, status }))
+                    if name == "tools" && status == "failed"
+            ),
+            "{responses:?}"
+        );
+
+        // A server that needs authentication is only warned about.
+        let cli = FakeCli::initialized(&format!(
+            "emit '{}'\nemit '{}'\ndrain",
+            init("needs-auth"),
+            result(),
+        ));
+        let client = Client::new(options(&cli)).await.unwrap();
+        let responses = client.receive().collect::<Vec<_>>().await;
+        assert!(responses.iter().all(Result::is_ok), "{responses:?}");
+        let status = client.mcp_server_status("tools").await.unwrap();
+        assert_eq!(status.status(), &crate::proto::McpServerState::NeedsAuth);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_tools_sample_through_the_client() {
+        use crate::sampling::{SamplingMessage, SamplingParams};
+
+        let cli = FakeCli::initialized(&format!(
+            r#"emit '{{"type":"control_request","request_id":"mcp_1","request":{{"subtype":"mcp_message","server_name":"triage","message":{{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{{"name":"classify","arguments":{{}}}}}}}}}}'
+next
+case "$line" in *sampling/createMessage*'Is this a bug?'*'"subtype":"mcp_message"'*) reply '{{"mcp_response":{{"jsonrpc":"2.0","id":"1","result":{{"role":"assistant","content":{{"type":"text","text":"yes"}},"model":"claude-haiku","stopReason":"endTurn"}}}}}}' ;;
+    *) exit 1 ;;
+esac
+next
+case "$line" in *mcp_1*yes*) emit '{}' ;; esac
+emit '{}'
+drain"#,
+            assistant_text("sampled"),
+            result(),
+        ));
+        let tool = Tool::with_context("classify", "", json!({}), None, |_, ctx| async move {
+            let messages = [SamplingMessage::user("Is this a bug?")];
+            let sampled = ctx.sample(&messages, &SamplingParams::new(16)).await?;
+            Ok(Tool::text_result(sampled.text()))
+        });
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .with_mcp_server("triage", Arc::new(McpServer::new("triage", vec![tool])));
+        let client = Client::new(options).await.unwrap();
+
+        let responses = client.receive().collect::<Vec<_>>().await;
+        let text = responses
+            .iter()
+            .filter_map(|r| r.as_ref().ok()?.as_text().map(|t| t.content().to_owned()))
+            .collect::<String>();
+        assert_eq!(text, "sampled");
+    }
+
+    #[cfg(all(unix, feature = "schema"))]
+    #[tokio::test]
+    async fn test_tools_elicit_through_the_client() {
+        use crate::tool::Elicitation;
+
+        #[derive(serde::Deserialize, schemars::JsonSchema)]
+        struct Credentials {
+            user: String,
+        }
+
+        let cli = FakeCli::initialized(&format!(
+            r#"emit '{{"type":"control_request","request_id":"mcp_1","request":{{"subtype":"mcp_message","server_name":"auth","message":{{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{{"name":"login","arguments":{{}}}}}}}}}}'
+next
+case "$line" in
+    *elicitation/create*'"message":"Log in"'*'"subtype":"mcp_message"'*) reply '{{"mcp_response":{{"jsonrpc":"2.0","id":"1","result":{{"action":"accept","content":{{"user":"ada"}}}}}}}}' ;;
+    *) exit 1 ;;
+esac
+next
+case "$line" in *mcp_1*'hello ada'*) emit '{}' ;; esac
+emit '{}'
+drain"#,
+            assistant_text("logged in"),
+            result(),
+        ));
+        let tool = Tool::with_context("login", "", json!({}), None, |_, ctx| async move {
+            match ctx.elicit::<Credentials>("Log in").await? {
+                Elicitation::Accepted(credentials) => {
+                    Ok(Tool::text_result(&format!("hello {}", credentials.user)))
+                }
+                _ => Ok(Tool::error_result("declined")),
+            }
+        });
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .with_mcp_server("auth", Arc::new(McpServer::new("auth", vec![tool])));
+        let client = Client::new(options).await.unwrap();
+
+        let responses = client.receive().collect::<Vec<_>>().await;
+        let text = responses
+            .iter()
+            .filter_map(|r| r.as_ref().ok()?.as_text().map(|t| t.content().to_owned()))
+            .collect::<String>();
+        assert_eq!(text, "logged in");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_restart_replaces_what_the_old_cli_reported() {
+        let cli = FakeCli::new(
+            r#"if [ -e started ]; then
+    next; reply '{"commands":[{"name":"second"}]}'
+    next; reply '{"version":"2"}'
+else
+    touch started
+    next; reply '{"commands":[{"name":"first"}]}'
+    next; reply '{"version":"1"}'
+fi
+drain"#,
+        );
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        assert!(client.initialize_response().unwrap().has_command("first"));
+        assert_eq!(client.get_server_info().await.unwrap().version(), "1");
+
+        client.restart(Vec::new()).await.unwrap();
+        let response = client.initialize_response().unwrap();
+        assert!(response.has_command("second") && !response.has_command("first"));
+        assert_eq!(client.get_server_info().await.unwrap().version(), "2");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_server_info_is_cached_until_refreshed() {
+        // A second request for the cached info would be answered with a
+        // different version.
+        let cli = FakeCli::initialized(
+            r#"next; reply '{"version":"1"}'
+next; reply '{"version":"2"}'
+drain"#,
+        );
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        assert_eq!(client.get_server_info().await.unwrap().version(), "1");
+        assert_eq!(client.get_server_info().await.unwrap().version(), "1");
+
+        assert_eq!(client.refresh_server_info().await.unwrap().version(), "2");
+        assert_eq!(client.get_server_info().await.unwrap().version(), "2");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_server_info_is_fetched_again_for_a_new_session() {
+        let cli = FakeCli::initialized(&format!(
+            r#"next; reply '{{"version":"1"}}'
+next
+emit '{{"type":"system","subtype":"init","session_id":"s2"}}'
+emit '{}'
+next; reply '{{"version":"2"}}'
+drain"#,
+            result(),
+        ));
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        assert_eq!(client.get_server_info().await.unwrap().version(), "1");
+
+        client.query("hi").await.unwrap();
+        client.receive().collect::<Vec<_>>().await;
+        assert_eq!(client.get_server_info().await.unwrap().version(), "2");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_steering_keeps_a_requested_interrupt() {
+        // The CLI ends the turn only once it reads the steer and then the
+        // interrupt.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{}'
+next
+case "$line" in *'"also check the tests"'*) ;; *) exit 1 ;; esac
+next
+case "$line" in *'"subtype":"interrupt"'*) reply '{{}}' ;; *) exit 1 ;; esac
+emit '{}'
+drain"#,
+            assistant_text("working"),
+            result(),
+        ));
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+
+        client.query("fix the bug").await.unwrap();
+        let mut stream = std::pin::pin!(client.receive());
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.as_text().unwrap().content(), "working");
+
+        client.signals().request_interrupt();
+        client.steer("also check the tests").await.unwrap();
+        let rest = tokio::time::timeout(Duration::from_secs(10), stream.collect::<Vec<_>>())
+            .await
+            .expect("the interrupt was dropped");
+        assert!(rest.last().unwrap().as_ref().unwrap().is_complete());
+    }
+
+    /// Records the responses dispatched to it, holding the first one until
+    /// `open` returns true.
+    #[cfg(unix)]
+    struct Recorder<F> {
+        open: F,
+        seen: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[cfg(unix)]
+    impl<F: Fn() -> bool + Send + Sync> Recorder<F> {
+        fn new(open: F) -> Self {
+            Self {
+                open,
+                seen: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+
+        async fn record(&self, seen: String) {
+            while !(self.open)() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            self.seen.lock().unwrap().push(seen);
+        }
+
+        fn seen(&self) -> Vec<String> {
+            self.seen.lock().unwrap().clone()
+        }
+    }
+
+    #[cfg(unix)]
+    #[async_trait::async_trait]
+    impl<F: Fn() -> bool + Send + Sync> Handler for Recorder<F> {
+        async fn on_text(&self, text: &crate::response::TextResponse) {
+            self.record(text.content().to_owned()).await;
+        }
+
+        async fn on_partial(&self, _partial: &PartialResponse) {
+            self.record("partial".to_owned()).await;
+        }
+
+        async fn on_complete(&self, _complete: &CompleteResponse) {
+            self.record("complete".to_owned()).await;
+        }
+    }
+
+    #[cfg(unix)]
+    const DELTA: &str = r#"{"type":"stream_event","event":{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"x"}},"session_id":"s1","uuid":"u"}"#;
+
+    #[cfg(unix)]
+    fn dispatching(cli: &FakeCli, overflow: Overflow) -> Options {
+        Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .dispatch_queue(DispatchQueue::new(1, overflow))
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dispatch_blocks_on_a_full_queue() {
+        // The handler holds the first response until the CLI has written
+        // the whole turn.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{}'
+emit '{}'
+emit '{}'
+emit '{}'
+touch written
+drain"#,
+            assistant_text("a"),
+            assistant_text("b"),
+            assistant_text("c"),
+            result(),
+        ));
+        let written = cli.dir().join("written");
+        let client = Client::new(dispatching(&cli, Overflow::Block))
+            .await
+            .unwrap();
+
+        client.query("hi").await.unwrap();
+        let recorder = Recorder::new(|| written.exists());
+        client.dispatch_to(&recorder).await.unwrap();
+        assert_eq!(recorder.seen(), ["a", "b", "c", "complete"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dispatch_drops_deltas_on_a_full_queue() {
+        // The handler holds the first response until the turn has been read.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{}'
+emit '{DELTA}'
+emit '{DELTA}'
+emit '{DELTA}'
+emit '{DELTA}'
+emit '{}'
+drain"#,
+            assistant_text("a"),
+            result(),
+        ));
+        let client = Client::new(dispatching(&cli, Overflow::DropDeltas))
+            .await
+            .unwrap();
+
+        client.query("hi").await.unwrap();
+        let signals = client.signals();
+        let recorder = Recorder::new(|| signals.turns_completed() > 0);
+        client.dispatch_to(&recorder).await.unwrap();
+        let seen = recorder.seen();
+        assert_eq!(seen.first().map(String::as_str), Some("a"));
+        assert_eq!(seen.last().map(String::as_str), Some("complete"));
+        assert!(seen.len() <= 3, "{seen:?}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dispatch_fails_on_a_full_queue_after_reading_the_turn() {
+        // The handler holds the first response until the turn has been read;
+        // the next turn answers only the next prompt.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{}'
+emit '{}'
+emit '{}'
+emit '{}'
+next
+case "$line" in *'"again"'*) emit '{}' ;; *) exit 1 ;; esac
+emit '{}'
+drain"#,
+            assistant_text("a"),
+            assistant_text("b"),
+            assistant_text("c"),
+            result(),
+            assistant_text("d"),
+            result(),
+        ));
+        let client = Client::new(dispatching(&cli, Overflow::Error))
+            .await
+            .unwrap();
+
+        client.query("hi").await.unwrap();
+        let signals = client.signals();
+        let recorder = Recorder::new(|| signals.turns_completed() > 0);
+        let err = client.dispatch_to(&recorder).await.unwrap_err();
+        assert!(
+            matches!(err, Error::DispatchQueueFull { capacity: 1 }),
+            "{err:?}"
+        );
+
+        client.query("again").await.unwrap();
+        let text = client
+            .text_stream()
+            .map(Result::unwrap)
+            .collect::<String>()
+            .await;
+        assert_eq!(text, "d");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_receive_interrupts_a_turn_past_the_duration_limit() {
+        // The CLI ends the turn only once interrupted.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{}'
+next
+case "$line" in *'"subtype":"interrupt"'*) reply '{{}}' ;; *) exit 1 ;; esac
+emit '{}'
+drain"#,
+            assistant_text("working"),
+            result(),
+        ));
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .max_turn_duration(Duration::from_secs(600));
+        let client = Client::new(options).await.unwrap();
+
+        // The clock jumps to the deadline whenever the client waits on the CLI.
+        tokio::time::pause();
+        let started = tokio::time::Instant::now();
+        client.query("hi").await.unwrap();
+        let mut responses = client.receive().collect::<Vec<_>>().await;
+        assert!(started.elapsed() >= Duration::from_secs(600));
+        let err = responses.pop().unwrap().unwrap_err();
+        assert!(
+            matches!(err, Error::TurnDurationExceeded { limit } if limit == Duration::from_secs(600)),
+            "{err:?}"
+        );
+        assert!(responses.pop().unwrap().unwrap().is_complete());
+    }
+
+    /// A sender for a task to hold until it ends.
+    #[cfg(unix)]
+    type Held = Arc<std::sync::Mutex<Option<oneshot::Sender<()>>>>;
+
+    /// A sender held by a task until it ends, and the receiver noticing it.
+    #[cfg(unix)]
+    fn held() -> (Held, oneshot::Receiver<()>) {
+        let (tx, rx) = oneshot::channel();
+        (Arc::new(std::sync::Mutex::new(Some(tx))), rx)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancelled_hook_callbacks_and_tool_calls_are_aborted() {
+        let cli = FakeCli::initialized(&format!(
+            r#"emit '{{"type":"control_request","request_id":"hook_1","request":{{"subtype":"hook_callback","callback_id":"pre_tool_use_0","input":{{"tool_name":"Bash","tool_input":{{}}}}}}}}'
+emit '{{"type":"control_request","request_id":"mcp_1","request":{{"subtype":"mcp_message","server_name":"tools","message":{{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{{"name":"wait","arguments":{{}}}}}}}}}}'
+until [ -e hook_started ] && [ -e tool_started ]; do sleep 0.01; done
+emit '{{"type":"control_cancel_request","request_id":"hook_1"}}'
+emit '{{"type":"control_cancel_request","request_id":"mcp_1"}}'
+emit '{}'
+emit '{}'
+drain"#,
+            assistant_text("cancelled"),
+            result(),
+        ));
+        // Each task notes it started, and holds a sender until it ends.
+        let (hook_held, hook_ended) = held();
+        let (tool_held, tool_ended) = held();
+        let hook_started = cli.dir().join("hook_started");
+        let tool_started = cli.dir().join("tool_started");
+        let hooks = Hooks::new().on_pre_tool_use("Bash", move |_| {
+            let held = hook_held.lock().unwrap().take();
+            std::fs::write(&hook_started, "").unwrap();
+            async move {
+                let _held = held;
+                std::future::pending().await
+            }
+        });
+        let tool = Tool::new("wait", "waits", json!({}), None, move |_| {
+            let held = tool_held.lock().unwrap().take();
+            std::fs::write(&tool_started, "").unwrap();
+            async move {
+                let _held = held;
+                std::future::pending().await
+            }
+        });
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .hooks(hooks)
+            .with_mcp_server("tools", Arc::new(McpServer::new("tools", vec![tool])));
+        let client = Client::new(options).await.unwrap();
+
+        let text = client
+            .text_stream()
+            .map(Result::unwrap)
+            .collect::<String>()
+            .await;
+        assert_eq!(text, "cancelled");
+        let ended = tokio::time::timeout(Duration::from_secs(10), async {
+            (hook_ended.await, tool_ended.await)
+        });
+        assert!(matches!(ended.await, Ok((Err(_), Err(_)))));
+        assert_eq!(client.hook_pool_stats(), TaskPoolStats::default());
+        assert_eq!(client.tool_pool_stats(), TaskPoolStats::default());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancelling_a_control_request_fails_its_waiter() {
+        // The CLI never answers the request, and notes the cancellation.
+        let cli = FakeCli::initialized(
+            r#"next; ours=$id
+next
+case "$line" in *"$ours"*control_cancel_request*) touch cancelled ;; esac
+drain"#,
+        );
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+
+        let cancel = async {
+            let request_id = loop {
+                if let Some(id) = client.outstanding_control_requests().pop() {
+                    break id;
+                }
+                tokio::task::yield_now().await;
+            };
+            client.cancel_control_request(&request_id).await.unwrap();
+            assert!(client.outstanding_control_requests().is_empty());
+        };
+        let (info, ()) = tokio::join!(client.refresh_server_info(), cancel);
+        let err = info.unwrap_err();
+        assert!(
+            matches!(&err, Error::ControlError { message, .. } if message == "request cancelled"),
+            "{err:?}"
+        );
+
+        let cancelled = cli.dir().join("cancelled");
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !cancelled.exists() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the CLI did not see the cancellation");
+    }
+
+    /// Counts the tool usage lines logged on its thread.
+    #[cfg(unix)]
+    #[derive(Default)]
+    struct ToolUsageLines(Arc<std::sync::atomic::AtomicUsize>);
+
+    #[cfg(unix)]
+    impl tracing::Subscriber for ToolUsageLines {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            if event.metadata().fields().field("avg_duration_ms").is_some() {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        fn enter(&self, _span: &tracing::span::Id) {}
+
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_tool_stats_are_logged_once_the_client_is_dropped() {
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{{"type":"control_request","request_id":"mcp_1","request":{{"subtype":"mcp_message","server_name":"tools","message":{{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{{"name":"noop","arguments":{{}}}}}}}}}}'
+next
+emit '{result}'
+next
+emit '{result}'
+drain"#,
+            result = result(),
+        ));
+        let tool = Tool::new("noop", "", json!({}), None, |_| async {
+            Ok(Tool::text_result("ok"))
+        });
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .log_tool_stats(true)
+            .with_mcp_server("tools", Arc::new(McpServer::new("tools", vec![tool])));
+        let lines = ToolUsageLines::default();
+        let logged = Arc::clone(&lines.0);
+        let _guard = tracing::subscriber::set_default(lines);
+        let client = Client::new(options).await.unwrap();
+
+        for prompt in ["one", "two"] {
+            client.query(prompt).await.unwrap();
+            client.receive_all().await.unwrap();
         }
+        assert_eq!(logged.load(Ordering::Relaxed), 0);
+        drop(client);
+        assert_eq!(logged.load(Ordering::Relaxed), 1);
     }
 }
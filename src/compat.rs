@@ -0,0 +1,216 @@
+//! Wire-format compatibility checks against the installed CLI.
+//!
+//! [`run`] drives a short canonical session over the raw transport and
+//! checks that every line the CLI writes parses as an [`Incoming`] message.
+//! The resulting [`CompatReport`] records the CLI version and each message
+//! type seen, so reports from different CLI versions can be compared. The
+//! `compat_report` example prints one for the local installation.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::options::Options;
+use crate::proto::control::{InitializeRequest, Request, RequestEnvelope};
+use crate::proto::{Incoming, OutgoingUserMessage};
+use crate::transport::Transport;
+
+const SCENARIO_PROMPT: &str =
+    "Run `echo clauders-compat` with the Bash tool, then reply with the single word: done";
+
+const SCENARIO_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// A line the CLI wrote that could not be parsed.
+#[derive(Debug, Clone)]
+pub struct ParseFailure {
+    kind: String,
+    error: String,
+    line: String,
+}
+
+impl ParseFailure {
+    /// The line's message type, or `"<invalid json>"`.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+}
+
+/// The outcome of a compatibility run.
+#[derive(Debug, Clone, Default)]
+pub struct CompatReport {
+    cli_version: String,
+    seen: BTreeMap<String, usize>,
+    failures: Vec<ParseFailure>,
+}
+
+impl CompatReport {
+    pub fn new(cli_version: impl Into<String>) -> Self {
+        Self {
+            cli_version: cli_version.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn cli_version(&self) -> &str {
+        &self.cli_version
+    }
+
+    /// How often each message type was seen, keyed as `type` or
+    /// `type/subtype` (for stream events, the inner event type).
+    pub fn seen(&self) -> &BTreeMap<String, usize> {
+        &self.seen
+    }
+
+    pub fn failures(&self) -> &[ParseFailure] {
+        &self.failures
+    }
+
+    /// Whether every line parsed and the session produced a result.
+    pub fn is_compatible(&self) -> bool {
+        self.failures.is_empty() && self.seen.contains_key("result/success")
+    }
+
+    /// Classifies and parses one line of CLI output.
+    pub fn record(&mut self, line: &str) {
+        let kind = match serde_json::from_str::<Value>(line) {
+            Ok(value) => message_kind(&value),
+            Err(e) => {
+                self.failures.push(ParseFailure {
+                    kind: "<invalid json>".to_owned(),
+                    error: e.to_string(),
+                    line: line.to_owned(),
+                });
+                return;
+            }
+        };
+
+        if let Err(e) = serde_json::from_str::<Incoming>(line) {
+            self.failures.push(ParseFailure {
+                kind: kind.clone(),
+                error: e.to_string(),
+                line: line.to_owned(),
+            });
+        }
+        *self.seen.entry(kind).or_default() += 1;
+    }
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "claude CLI {}", self.cli_version)?;
+        let verdict = if self.is_compatible() {
+            "compatible"
+        } else {
+            "INCOMPATIBLE"
+        };
+        writeln!(f, "status: {verdict}")?;
+        writeln!(f, "message types:")?;
+        for (kind, count) in &self.seen {
+            writeln!(f, "  {kind:<32} {count}")?;
+        }
+        if !self.failures.is_empty() {
+            writeln!(f, "parse failures:")?;
+            for failure in &self.failures {
+                writeln!(f, "  {}: {}", failure.kind, failure.error)?;
+                writeln!(f, "    {}", failure.line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn message_kind(value: &Value) -> String {
+    let field =
+        |value: &Value, key: &str| value.get(key).and_then(Value::as_str).map(str::to_owned);
+    let kind = field(value, "type").unwrap_or_else(|| "<untyped>".to_owned());
+    let detail = match kind.as_str() {
+        "stream_event" => value.get("event").and_then(|event| field(event, "type")),
+        "control_request" => value.get("request").and_then(|r| field(r, "subtype")),
+        "control_response" => value.get("response").and_then(|r| field(r, "subtype")),
+        _ => field(value, "subtype"),
+    };
+    match detail {
+        Some(detail) => format!("{kind}/{detail}"),
+        None => kind,
+    }
+}
+
+/// Returns the installed CLI's version string.
+pub async fn cli_version() -> Result<String, Error> {
+    let output = tokio::process::Command::new("claude")
+        .arg("--version")
+        .output()
+        .await
+        .map_err(|e| Error::CliNotFound(format!("failed to run claude --version: {e}")))?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Runs the canonical scenario against the installed CLI.
+///
+/// The scenario initializes a session with partial messages enabled and asks
+/// Claude to make one Bash call, covering system, assistant, user, stream and
+/// result messages as well as control responses. `options` is applied first,
+/// so it can set the model or working directory.
+pub async fn run(options: Options) -> Result<CompatReport, Error> {
+    let options = options
+        .allowed_tool("Bash")
+        .include_partial_messages(true)
+        .max_turns(3);
+    let mut report = CompatReport::new(cli_version().await?);
+    let mut transport = Transport::new(&options.to_transport_options()).await?;
+
+    let scenario = async {
+        let initialize = RequestEnvelope::new(Request::Initialize(InitializeRequest::new()));
+        transport.send_request(&initialize).await?;
+        let prompt = serde_json::to_value(OutgoingUserMessage::text(SCENARIO_PROMPT))?;
+        transport.send(&prompt).await?;
+
+        while let Some(line) = transport.receive_line().await? {
+            report.record(&line);
+            let is_result = serde_json::from_str::<Value>(&line)
+                .is_ok_and(|value| value.get("type").and_then(Value::as_str) == Some("result"));
+            if is_result {
+                break;
+            }
+        }
+        Ok::<_, Error>(())
+    };
+    tokio::time::timeout(SCENARIO_TIMEOUT, scenario)
+        .await
+        .map_err(|_| Error::Timeout("compatibility scenario did not finish".to_owned()))??;
+
+    transport.close().await?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_classifies_and_flags_lines() {
+        let mut report = CompatReport::new("2.0.0 (Claude Code)");
+        report.record(r#"{"type":"stream_event","event":{"type":"message_start"},"session_id":"s","uuid":"u"}"#);
+        report.record(
+            r#"{"type":"control_response","response":{"subtype":"success","request_id":"1"}}"#,
+        );
+        report.record("not json");
+
+        assert_eq!(report.seen().get("stream_event/message_start"), Some(&1));
+        assert_eq!(report.seen().get("control_response/success"), Some(&1));
+        assert_eq!(report.failures().len(), 1);
+        assert_eq!(report.failures()[0].kind(), "<invalid json>");
+        assert!(!report.is_compatible());
+    }
+}
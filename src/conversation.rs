@@ -28,22 +28,127 @@
 //! }
 //! ```
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use futures::StreamExt;
+#[cfg(feature = "schema")]
 use schemars::JsonSchema;
+#[cfg(feature = "schema")]
 use serde::de::DeserializeOwned;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tracing::Instrument;
 
+use crate::blocks::{BlockEvent, BlockKind, BlockTracker};
 use crate::client::Client;
 use crate::error::Error;
-use crate::response::{Responses, ToolUseResponse};
+#[cfg(feature = "guardrails")]
+use crate::guardrails::{GuardrailEvent, Guardrails};
+use crate::hooks::StopOutput;
+use crate::model::Model;
+use crate::profile::PermissionProfile;
+use crate::recovery::{RecoveryPolicy, RecoveryStep, RecoveryTracker};
+use crate::response::{ErrorResponse, Response, Responses, Timings, ToolUseResponse};
+use crate::stall::{StallAction, StallDetector, StallPolicy, Stalled};
+#[cfg(feature = "schema")]
+use crate::structured::Validated;
+use crate::tool::BuiltinTool;
+use crate::watch::FileChanges;
+#[cfg(feature = "watch")]
+use crate::watch::FileWatcher;
 
 /// A multi-turn conversation session with builder configuration.
 ///
 /// Tracks conversation history on the client side while the CLI manages
 /// the actual session state. History is provided for user convenience
 /// to inspect previous turns.
+///
+/// A conversation from [`Client::conversation`] borrows the client. One
+/// created with [`Conversation::owned`] shares it instead, so it can be
+/// stored or moved to another task; see [`OwnedConversation`].
 pub struct Conversation<'a> {
-    client: &'a Client,
+    client: ClientRef<'a>,
     history: Vec<Turn>,
+    history_mode: HistoryMode,
+    max_history: Option<usize>,
+    turns_started: u64,
+    /// The parent of every turn recorded, by id, kept whatever the history
+    /// mode so the shape of the tree survives trimming.
+    parents: BTreeMap<u64, Option<u64>>,
+    /// The turn the next one continues from.
+    head: Option<u64>,
+    summary: Option<String>,
+    context_providers: Vec<ContextProvider<'a>>,
+    recovery: Option<RecoveryPolicy>,
+    #[cfg(feature = "guardrails")]
+    guardrails: Option<Guardrails>,
+    steering_tx: mpsc::UnboundedSender<String>,
+    steering_rx: mpsc::UnboundedReceiver<String>,
+    queued: VecDeque<String>,
+    queue_tx: mpsc::WeakUnboundedSender<String>,
+    queue_rx: mpsc::UnboundedReceiver<String>,
+    #[cfg(feature = "watch")]
+    watcher: Option<FileWatcher>,
+}
+
+/// How much of each turn a [`Conversation`] keeps in its history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistoryMode {
+    /// Every response of the turn.
+    #[default]
+    Full,
+    /// The turn's text and completion, enough for [`Turn::text`] and the
+    /// cost accounting, without tool calls, thinking or partial messages.
+    SummariesOnly,
+    /// Nothing; [`Conversation::history`] stays empty.
+    Off,
+}
+
+/// A conversation that shares its client rather than borrowing it, for
+/// services that keep conversations in maps or move them between tasks.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+///
+/// use clauders::{Client, Conversation, OwnedConversation, Options};
+///
+/// # async fn example() -> Result<(), clauders::Error> {
+/// let client = Arc::new(Client::new(Options::new()).await?);
+/// let mut conversations = HashMap::<u64, OwnedConversation>::new();
+///
+/// let conv = conversations
+///     .entry(42)
+///     .or_insert_with(|| Conversation::owned(Arc::clone(&client)));
+/// conv.say("Hello").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub type OwnedConversation = Conversation<'static>;
+
+/// The client of a conversation, borrowed or shared.
+#[derive(Clone)]
+enum ClientRef<'a> {
+    Borrowed(&'a Client),
+    Shared(Arc<Client>),
+}
+
+impl Deref for ClientRef<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        match self {
+            Self::Borrowed(client) => client,
+            Self::Shared(client) => client,
+        }
+    }
 }
 
 /// A single turn in the conversation.
@@ -51,10 +156,19 @@ pub struct Conversation<'a> {
 /// Contains the prompt that was sent and all responses received.
 #[derive(Debug, Clone)]
 pub struct Turn {
+    /// The turn's position in the conversation, starting at 1. Ids are never
+    /// reused and stay the same when earlier turns leave the history.
+    pub id: u64,
+    /// The turn this one continued from, or `None` for the first turn of
+    /// the conversation. Turns share a parent after
+    /// [`Conversation::branch_from`].
+    pub parent: Option<u64>,
     /// The prompt that was sent for this turn
     pub prompt: String,
     /// All responses received for this turn
     pub responses: Responses,
+    /// Files changed on disk while the turn ran, if the conversation watches files
+    pub file_changes: Option<FileChanges>,
 }
 
 impl Turn {
@@ -62,11 +176,32 @@ impl Turn {
     pub fn text(&self) -> String {
         self.responses.text_content()
     }
+
+    /// Returns the client-observed timings of this turn.
+    pub fn timings(&self) -> Timings {
+        self.responses.timings()
+    }
 }
 
+/// Progress of the turns run by [`Conversation::run_queue`].
+#[derive(Debug)]
+pub enum QueueEvent<'a> {
+    /// A queued prompt is about to be sent.
+    Started { prompt: &'a str },
+    /// A turn completed.
+    Finished(&'a Turn),
+    /// A turn failed; the queue moves on to the next prompt.
+    Failed { prompt: &'a str, error: &'a Error },
+}
+
+type ContextProvider<'a> = Box<dyn Fn() -> String + Send + Sync + 'a>;
 type TextCallback<'a> = Box<dyn FnMut(&str) + Send + 'a>;
 type ThinkingCallback<'a> = Box<dyn FnMut(&str) + Send + 'a>;
+type BlockCallback<'a> = Box<dyn FnMut(&BlockEvent<'_>) + Send + 'a>;
 type ToolUseCallback<'a> = Box<dyn FnMut(&ToolUseResponse) + Send + 'a>;
+type StalledCallback<'a> = Box<dyn FnMut(&Stalled) + Send + 'a>;
+#[cfg(feature = "guardrails")]
+type GuardrailCallback<'a> = Box<dyn FnMut(&GuardrailEvent) + Send + 'a>;
 
 /// Builder for configuring and executing a single conversation turn.
 ///
@@ -79,16 +214,234 @@ pub struct TurnBuilder<'a, 'c> {
     prompt: String,
     on_text: Option<TextCallback<'a>>,
     on_thinking: Option<ThinkingCallback<'a>>,
+    on_block: Option<BlockCallback<'a>>,
     on_tool_use: Option<ToolUseCallback<'a>>,
+    on_stalled: Option<StalledCallback<'a>>,
+    #[cfg(feature = "guardrails")]
+    on_guardrail: Option<GuardrailCallback<'a>>,
+    stall_policy: Option<StallPolicy>,
     collect: bool,
+    suppress_thinking: bool,
+    max_tool_calls: Option<u32>,
+    max_duration: Option<Duration>,
+    spool: Option<PathBuf>,
+    profile: Option<PermissionProfile>,
+}
+
+impl Conversation<'static> {
+    /// Creates a conversation sharing `client`.
+    pub fn owned(client: Arc<Client>) -> Self {
+        Self::with_client(ClientRef::Shared(client))
+    }
 }
 
 impl<'a> Conversation<'a> {
     /// Creates a new conversation session.
     pub(crate) fn new(client: &'a Client) -> Self {
+        Self::with_client(ClientRef::Borrowed(client))
+    }
+
+    fn with_client(client: ClientRef<'a>) -> Self {
+        let (steering_tx, steering_rx) = mpsc::unbounded_channel();
+        let (queue_tx, queue_rx) = mpsc::unbounded_channel();
         Self {
             client,
             history: Vec::new(),
+            history_mode: HistoryMode::default(),
+            max_history: None,
+            turns_started: 0,
+            parents: BTreeMap::new(),
+            head: None,
+            summary: None,
+            context_providers: Vec::new(),
+            recovery: None,
+            #[cfg(feature = "guardrails")]
+            guardrails: None,
+            steering_tx,
+            steering_rx,
+            queued: VecDeque::new(),
+            queue_tx: queue_tx.downgrade(),
+            queue_rx,
+            #[cfg(feature = "watch")]
+            watcher: None,
+        }
+    }
+
+    /// Records the files changed under the client's working directory during
+    /// each turn, available as [`Turn::file_changes`].
+    #[cfg(feature = "watch")]
+    pub fn watch_files(mut self) -> Result<Self, Error> {
+        self.watcher = Some(FileWatcher::new(self.client.cwd())?);
+        Ok(self)
+    }
+
+    /// Adds a provider whose output is prepended to every prompt in this conversation.
+    ///
+    /// Providers run each time a turn is sent, so they can report state that
+    /// changes between turns, such as the current git status or time. Empty
+    /// output is skipped. The turn's recorded prompt excludes the context.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clauders::{Client, Options};
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// # let client = Client::new(Options::new()).await?;
+    /// let mut conv = client
+    ///     .conversation()
+    ///     .with_context_provider(|| format!("Current time: {:?}", std::time::SystemTime::now()));
+    ///
+    /// conv.say("What should I work on next?").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_context_provider<F>(mut self, provider: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'a,
+    {
+        self.add_context_provider(provider);
+        self
+    }
+
+    pub fn add_context_provider<F>(&mut self, provider: F)
+    where
+        F: Fn() -> String + Send + Sync + 'a,
+    {
+        self.context_providers.push(Box::new(provider));
+    }
+
+    /// Reacts to tools that keep failing during a turn, as `policy` directs.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clauders::{Client, Options, RecoveryAction, RecoveryPolicy, ToolErrorKind};
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// # let client = Client::new(Options::new()).await?;
+    /// let mut conv = client.conversation().with_recovery(
+    ///     RecoveryPolicy::new()
+    ///         .max_failures(3)
+    ///         .on_kind(ToolErrorKind::PermissionDenied, RecoveryAction::DisableTool),
+    /// );
+    ///
+    /// conv.say("Update the dependencies and run the tests").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_recovery(mut self, policy: RecoveryPolicy) -> Self {
+        self.set_recovery(policy);
+        self
+    }
+
+    pub fn set_recovery(&mut self, policy: RecoveryPolicy) {
+        self.recovery = Some(policy);
+    }
+
+    /// Checks each text response of every turn against `guardrails`; see the
+    /// [`guardrails`](crate::guardrails) module.
+    #[cfg(feature = "guardrails")]
+    #[must_use]
+    pub fn with_guardrails(mut self, guardrails: Guardrails) -> Self {
+        self.set_guardrails(guardrails);
+        self
+    }
+
+    #[cfg(feature = "guardrails")]
+    pub fn set_guardrails(&mut self, guardrails: Guardrails) {
+        self.guardrails = Some(guardrails);
+    }
+
+    /// Keeps only the `turns` most recent turns in the history, bounding its
+    /// memory in long sessions.
+    #[must_use]
+    pub fn max_history(mut self, turns: usize) -> Self {
+        self.max_history = Some(turns);
+        self.trim_history();
+        self
+    }
+
+    /// Sets how much of each turn is kept in the history.
+    ///
+    /// The responses returned by each turn are unaffected. With
+    /// [`HistoryMode::Off`], the cost helpers such as
+    /// [`total_cost_usd`](Self::total_cost_usd) and profile budgets see no
+    /// turns.
+    #[must_use]
+    pub fn history_mode(mut self, mode: HistoryMode) -> Self {
+        self.history_mode = mode;
+        if mode == HistoryMode::Off {
+            self.history.clear();
+        }
+        self
+    }
+
+    /// Adds a finished turn to the history as the history mode directs,
+    /// returning its responses.
+    fn record(&mut self, turn: Turn) -> Responses {
+        self.parents.insert(turn.id, turn.parent);
+        self.head = Some(turn.id);
+        match self.history_mode {
+            HistoryMode::Full => {
+                let responses = turn.responses.clone();
+                self.history.push(turn);
+                self.trim_history();
+                responses
+            }
+            HistoryMode::SummariesOnly => {
+                self.history.push(Turn {
+                    id: turn.id,
+                    parent: turn.parent,
+                    prompt: turn.prompt,
+                    responses: summary_of(&turn.responses),
+                    file_changes: turn.file_changes,
+                });
+                self.trim_history();
+                turn.responses
+            }
+            HistoryMode::Off => turn.responses,
+        }
+    }
+
+    fn trim_history(&mut self) {
+        if let Some(max) = self.max_history {
+            let excess = self.history.len().saturating_sub(max);
+            self.history.drain(..excess);
+        }
+    }
+
+    /// The turn just run with `prompt`, from the history if it is kept there
+    /// in full.
+    fn completed_turn(&self, prompt: &str, responses: Responses) -> Cow<'_, Turn> {
+        match self.history.last() {
+            Some(turn) if self.history_mode == HistoryMode::Full => Cow::Borrowed(turn),
+            last => Cow::Owned(Turn {
+                id: self.turns_started,
+                parent: self.parents.get(&self.turns_started).copied().flatten(),
+                prompt: prompt.to_owned(),
+                responses,
+                file_changes: last.and_then(|turn| turn.file_changes.clone()),
+            }),
+        }
+    }
+
+    /// Prepends the output of the context providers to `prompt`.
+    fn with_context(&self, prompt: &str) -> String {
+        let context = self
+            .context_providers
+            .iter()
+            .map(|provider| provider())
+            .filter(|context| !context.trim().is_empty())
+            .collect::<Vec<_>>();
+
+        if context.is_empty() {
+            prompt.to_owned()
+        } else {
+            format!(
+                "<context>\n{}\n</context>\n\n{prompt}",
+                context.join("\n\n")
+            )
         }
     }
 
@@ -114,13 +467,25 @@ impl<'a> Conversation<'a> {
     /// # }
     /// ```
     pub fn turn(&mut self, prompt: impl Into<String>) -> TurnBuilder<'_, 'a> {
+        let max_tool_calls = self.client.max_tool_calls();
+        let max_duration = self.client.max_turn_duration();
         TurnBuilder {
             conversation: self,
             prompt: prompt.into(),
             on_text: None,
             on_thinking: None,
+            on_block: None,
             on_tool_use: None,
+            on_stalled: None,
+            #[cfg(feature = "guardrails")]
+            on_guardrail: None,
+            stall_policy: None,
             collect: true,
+            suppress_thinking: false,
+            max_tool_calls,
+            max_duration,
+            spool: None,
+            profile: None,
         }
     }
 
@@ -148,6 +513,219 @@ impl<'a> Conversation<'a> {
         self.turn(prompt).send_text().await
     }
 
+    /// Hands `prompt` to the subagent `agent_name` and returns the
+    /// subagent's own responses.
+    ///
+    /// The turn asks the main agent to start the subagent with the Task
+    /// tool, and the responses returned are those the CLI tagged with that
+    /// tool use's id, ending with the subagent's answer. The whole turn is
+    /// recorded in the history as usual. Fails with
+    /// [`Error::NotDelegated`] if the main agent did not start the subagent.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clauders::{Agent, Client, Options};
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// let reviewer = Agent::new("Reviews code for issues", "You are a code reviewer");
+    /// let client = Client::new(Options::new().with_agent("reviewer", reviewer)).await?;
+    /// let mut conv = client.conversation();
+    ///
+    /// let review = conv.delegate("reviewer", "Review src/lib.rs").await?;
+    /// println!("{}", review.text_content());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn delegate(&mut self, agent_name: &str, prompt: &str) -> Result<Responses, Error> {
+        let responses = self
+            .turn(delegation_prompt(agent_name, prompt))
+            .send()
+            .await?;
+        let task = responses
+            .iter()
+            .filter_map(Response::as_tool_use)
+            .find(|tool_use| is_delegation(tool_use, agent_name))
+            .ok_or_else(|| Error::NotDelegated {
+                agent: agent_name.to_owned(),
+            })?;
+        Ok(responses.subagent(task.id()))
+    }
+
+    /// Returns a sender for steering the conversation from another task.
+    ///
+    /// Each message is passed to [`Client::steer`] while a turn is running.
+    /// Messages sent between turns are held until the next turn starts and
+    /// then delivered right after its prompt.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clauders::{Client, Options};
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// # let client = Client::new(Options::new()).await?;
+    /// let mut conv = client.conversation();
+    /// let steering = conv.steering();
+    ///
+    /// tokio::spawn(async move {
+    ///     tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+    ///     let _ = steering.send("Focus on the parser module only.".to_owned());
+    /// });
+    ///
+    /// conv.say("Refactor the crate for readability").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn steering(&self) -> mpsc::UnboundedSender<String> {
+        self.steering_tx.clone()
+    }
+
+    /// Adds a prompt to the queue run by [`run_queue`](Self::run_queue).
+    pub fn enqueue(&mut self, prompt: impl Into<String>) {
+        self.queued.push_back(prompt.into());
+    }
+
+    /// Returns a sender for queueing prompts from other tasks.
+    ///
+    /// Prompts sent through it are run by [`run_queue`](Self::run_queue)
+    /// after those added with [`enqueue`](Self::enqueue), in the order they
+    /// arrive.
+    pub fn prompt_queue(&mut self) -> mpsc::UnboundedSender<String> {
+        if let Some(tx) = self.queue_tx.upgrade() {
+            return tx;
+        }
+        // Every earlier sender was dropped, closing the channel for good.
+        while let Ok(prompt) = self.queue_rx.try_recv() {
+            self.queued.push_back(prompt);
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.queue_tx = tx.downgrade();
+        self.queue_rx = rx;
+        tx
+    }
+
+    /// Runs queued prompts one turn at a time, returning the number of turns run.
+    ///
+    /// Serialising turns here keeps producers on several tasks from racing
+    /// [`Client::query`] on the shared transport. Runs until the queue is
+    /// empty and every sender from [`prompt_queue`](Self::prompt_queue) has
+    /// been dropped. A failed turn is reported and does not stop the queue.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clauders::{Client, Options};
+    /// # use clauders::conversation::QueueEvent;
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// # let client = Client::new(Options::new()).await?;
+    /// let mut conv = client.conversation();
+    /// let queue = conv.prompt_queue();
+    ///
+    /// for file in ["lib.rs", "client.rs"] {
+    ///     let queue = queue.clone();
+    ///     tokio::spawn(async move {
+    ///         let _ = queue.send(format!("Review src/{file}"));
+    ///     });
+    /// }
+    /// drop(queue);
+    ///
+    /// conv.run_queue(|event| {
+    ///     if let QueueEvent::Finished(turn) = event {
+    ///         println!("{}: {}", turn.prompt, turn.text());
+    ///     }
+    /// })
+    /// .await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_queue<F>(&mut self, mut on_event: F) -> usize
+    where
+        F: FnMut(QueueEvent<'_>),
+    {
+        let mut turns = 0;
+        loop {
+            let prompt = match self.queued.pop_front() {
+                Some(prompt) => prompt,
+                None => match self.queue_rx.recv().await {
+                    Some(prompt) => prompt,
+                    None => return turns,
+                },
+            };
+
+            on_event(QueueEvent::Started { prompt: &prompt });
+            turns += 1;
+            match self.turn(prompt.as_str()).send().await {
+                Ok(responses) => {
+                    on_event(QueueEvent::Finished(
+                        &self.completed_turn(&prompt, responses),
+                    ));
+                }
+                Err(error) => on_event(QueueEvent::Failed {
+                    prompt: &prompt,
+                    error: &error,
+                }),
+            }
+        }
+    }
+
+    /// Runs `prompt`, then keeps the agent working while `check` blocks it
+    /// from stopping, for at most `max_iterations` turns in all.
+    ///
+    /// `check` is a Stop hook run by the client after each turn: when it
+    /// returns [`StopOutput::continue_with`], its instruction is sent as the
+    /// next turn. Returns the text of the last turn, or fails with
+    /// [`Error::ContinuationLimitExceeded`] if `check` still blocks after
+    /// `max_iterations` turns. Stop hooks registered with
+    /// [`Options::hooks`](crate::Options::hooks) run inside each turn
+    /// instead, as the CLI ends it; see
+    /// [`Options::max_continuations`](crate::Options::max_continuations).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clauders::{Client, Options, StopOutput};
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// # let client = Client::new(Options::new()).await?;
+    /// let mut conv = client.conversation();
+    ///
+    /// conv.run_until_done("Fix the failing tests", 5, |turn| {
+    ///     if turn.text().contains("All tests pass") {
+    ///         StopOutput::pass()
+    ///     } else {
+    ///         StopOutput::continue_with("Run the tests again and fix what still fails.")
+    ///     }
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_until_done<F>(
+        &mut self,
+        prompt: impl Into<String>,
+        max_iterations: u32,
+        mut check: F,
+    ) -> Result<String, Error>
+    where
+        F: FnMut(&Turn) -> StopOutput,
+    {
+        let mut prompt = prompt.into();
+        let mut iterations = 0;
+        loop {
+            let responses = self.turn(prompt.as_str()).send().await?;
+            iterations += 1;
+            let turn = self.completed_turn(&prompt, responses);
+            let output = check(&turn);
+            let Some(instruction) = output.continuation() else {
+                return Ok(turn.text());
+            };
+            if iterations >= max_iterations {
+                return Err(Error::ContinuationLimitExceeded {
+                    limit: max_iterations.saturating_sub(1),
+                });
+            }
+            prompt = instruction.to_owned();
+        }
+    }
+
     /// Returns the conversation history.
     ///
     /// Each entry represents a single turn (prompt + responses).
@@ -155,6 +733,71 @@ impl<'a> Conversation<'a> {
         &self.history
     }
 
+    /// Returns the turn with the given [id](Turn::id), if it is still in
+    /// the history.
+    pub fn turn_by_id(&self, id: u64) -> Option<&Turn> {
+        self.history
+            .binary_search_by_key(&id, |turn| turn.id)
+            .ok()
+            .map(|index| &self.history[index])
+    }
+
+    /// Returns the id of the turn the next one continues from: the last
+    /// turn run, or the one last [branched from](Self::branch_from).
+    pub fn head(&self) -> Option<u64> {
+        self.head
+    }
+
+    /// Returns the ids of the turns that continued from turn `id`, in the
+    /// order they ran.
+    pub fn children(&self, id: u64) -> Vec<u64> {
+        children(&self.parents, id)
+    }
+
+    /// Returns the ids of the turns leading to turn `id`, from the first
+    /// turn of the conversation to `id` itself. Empty if the turn is unknown.
+    pub fn branch(&self, id: u64) -> Vec<u64> {
+        branch(&self.parents, id)
+    }
+
+    /// Returns the ids of the turns no other turn continued from, the tip of
+    /// each branch explored.
+    pub fn leaves(&self) -> Vec<u64> {
+        leaves(&self.parents)
+    }
+
+    /// Makes the next turn continue from turn `id` rather than the last one,
+    /// forking the CLI session at the end of that turn. The turns after it
+    /// stay in the history as a separate branch.
+    ///
+    /// The turn must still be in the history with at least one assistant
+    /// message, so this needs a [history mode](HistoryMode) other than
+    /// [`HistoryMode::Off`].
+    pub async fn branch_from(&mut self, id: u64) -> Result<(), Error> {
+        let cannot_branch = |reason: &str| Error::CannotBranch {
+            id,
+            reason: reason.to_owned(),
+        };
+        let turn = self
+            .turn_by_id(id)
+            .ok_or_else(|| cannot_branch("the turn is not in the history"))?;
+        let message_id = turn
+            .responses
+            .iter()
+            .filter_map(|response| match response {
+                Response::Text(text) => text.message_id(),
+                Response::ToolUse(tool_use) => tool_use.message_id(),
+                _ => None,
+            })
+            .last()
+            .ok_or_else(|| cannot_branch("the turn has no assistant message"))?
+            .to_owned();
+
+        self.client.fork_at(&message_id).await?;
+        self.head = Some(id);
+        Ok(())
+    }
+
     /// Returns the last turn in the conversation, if any.
     pub fn last(&self) -> Option<&Turn> {
         self.history.last()
@@ -166,11 +809,105 @@ impl<'a> Conversation<'a> {
     /// of previous turns.
     pub fn clear_history(&mut self) {
         self.history.clear();
+        self.summary = None;
+    }
+
+    /// Returns the summary of turns removed by [`compact_history`](Self::compact_history).
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    /// Summarises the recorded history in at most `max_tokens` tokens.
+    ///
+    /// The summary is written by Haiku in a separate, tool-less session,
+    /// started with the client's options, so it neither adds to this conversation's CLI session nor costs a turn of
+    /// the main model. Any earlier summary from
+    /// [`compact_history`](Self::compact_history) is folded in.
+    pub async fn summarise(&self, max_tokens: u32) -> Result<String, Error> {
+        self.summarise_turns(&self.history, max_tokens).await
+    }
+
+    /// Replaces all but the `keep_recent` most recent turns with a summary of
+    /// at most `max_tokens` tokens, bounding the memory used by the history.
+    ///
+    /// This only affects the client-side record; the CLI session keeps its
+    /// own context and compacts it independently.
+    pub async fn compact_history(
+        &mut self,
+        max_tokens: u32,
+        keep_recent: usize,
+    ) -> Result<(), Error> {
+        let split = self.history.len().saturating_sub(keep_recent);
+        if split == 0 {
+            return Ok(());
+        }
+        let summary = self
+            .summarise_turns(&self.history[..split], max_tokens)
+            .await?;
+        self.history.drain(..split);
+        self.summary = Some(summary);
+        Ok(())
+    }
+
+    async fn summarise_turns(&self, turns: &[Turn], max_tokens: u32) -> Result<String, Error> {
+        let mut transcript = String::new();
+        if let Some(summary) = &self.summary {
+            transcript.push_str(&format!("Summary of earlier turns:\n{summary}\n\n"));
+        }
+        for turn in turns {
+            transcript.push_str(&format!(
+                "User: {}\nAssistant: {}\n\n",
+                turn.prompt,
+                turn.text()
+            ));
+        }
+
+        // Run as the client's own CLI does, with its path, environment and
+        // credentials.
+        let options = self
+            .client
+            .options()
+            .clone()
+            .side_session()
+            .model(Model::Haiku)
+            .cwd(self.client.cwd())
+            .disable_tools()
+            .max_turns(1)
+            .env_var("CLAUDE_CODE_MAX_OUTPUT_TOKENS", max_tokens.to_string())
+            .system_prompt(format!(
+                "Summarise the conversation transcript you are given in at most \
+                 {max_tokens} tokens. Keep decisions, facts, open questions and \
+                 file names; drop pleasantries. Reply with the summary only."
+            ));
+        let summariser = Client::new(options).await?;
+        let (summary, _) = summariser.query_once(&transcript).await?;
+        Ok(summary.trim().to_owned())
+    }
+
+    /// Returns the cost in USD of each model used across the recorded turns,
+    /// including models used by subagents.
+    pub fn cost_by_model(&self) -> HashMap<String, f64> {
+        let mut costs = HashMap::new();
+        for turn in &self.history {
+            for (model, usage) in turn.responses.usage_by_model() {
+                *costs.entry(model).or_insert(0.0) += usage.cost_usd();
+            }
+        }
+        costs
+    }
+
+    /// Returns the cost in USD of the recorded turns, as reported by their
+    /// completions.
+    pub fn total_cost_usd(&self) -> f64 {
+        self.history
+            .iter()
+            .filter_map(|turn| turn.responses.completion()?.total_cost_usd())
+            .sum()
     }
 
     /// Returns a reference to the underlying client.
     pub fn client(&self) -> &Client {
-        self.client
+        &self.client
     }
 }
 
@@ -227,6 +964,41 @@ impl<'a, 'c> TurnBuilder<'a, 'c> {
         self
     }
 
+    /// Sets a callback for content blocks starting, growing and ending as
+    /// the main agent's messages stream.
+    ///
+    /// Requires [`Options::include_partial_messages`]; the events are tracked
+    /// with a [`BlockTracker`](crate::blocks::BlockTracker).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clauders::{Client, Options};
+    /// use clauders::blocks::{BlockEvent, BlockKind};
+    ///
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// # let client = Client::new(Options::new().include_partial_messages(true)).await?;
+    /// # let mut conv = client.conversation();
+    /// conv.turn("Solve this problem")
+    ///     .on_block(|event| match event {
+    ///         BlockEvent::Start { kind: BlockKind::Thinking, .. } => println!("<details>"),
+    ///         BlockEvent::Stop { kind: BlockKind::Thinking, .. } => println!("</details>"),
+    ///         BlockEvent::Delta { content, .. } => print!("{content}"),
+    ///         _ => {}
+    ///     })
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_block<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&BlockEvent<'_>) + Send + 'a,
+    {
+        self.on_block = Some(Box::new(f));
+        self
+    }
+
     /// Sets a callback for tool use events.
     ///
     /// The callback is called when Claude uses a tool.
@@ -253,6 +1025,57 @@ impl<'a, 'c> TurnBuilder<'a, 'c> {
         self
     }
 
+    /// Watches the turn for runs of tool calls without any text, reacting
+    /// as the policy's [`StallAction`] directs.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use clauders::{Client, Options, StallAction, StallPolicy};
+    /// # async fn example() -> Result<(), clauders::Error> {
+    /// # let client = Client::new(Options::new()).await?;
+    /// # let mut conv = client.conversation();
+    /// conv.turn("Fix the failing tests")
+    ///     .detect_stalls(
+    ///         StallPolicy::new()
+    ///             .max_events(20)
+    ///             .action(StallAction::Steer("Summarize what you have found so far.".into())),
+    ///     )
+    ///     .on_stalled(|stall| eprintln!("[stalled after {} tool events]", stall.events()))
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn detect_stalls(mut self, policy: StallPolicy) -> Self {
+        self.stall_policy = Some(policy);
+        self
+    }
+
+    /// Sets a callback for stalls detected by [`detect_stalls`](Self::detect_stalls).
+    ///
+    /// The callback runs before the policy's action is taken.
+    pub fn on_stalled<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&Stalled) + Send + 'a,
+    {
+        self.on_stalled = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a callback for violations of the conversation's
+    /// [guardrails](Conversation::with_guardrails).
+    ///
+    /// The callback runs before the guardrail's action is taken.
+    #[cfg(feature = "guardrails")]
+    pub fn on_guardrail<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&GuardrailEvent) + Send + 'a,
+    {
+        self.on_guardrail = Some(Box::new(f));
+        self
+    }
+
     /// Controls whether responses are collected.
     ///
     /// When set to `false`, responses are not stored in the turn's response
@@ -265,6 +1088,65 @@ impl<'a, 'c> TurnBuilder<'a, 'c> {
         self
     }
 
+    /// Drops thinking from this turn: `on_thinking` is not called, `on_block`
+    /// is not called for thinking blocks and thinking responses are left out
+    /// of the collected responses and history.
+    pub fn suppress_thinking(mut self) -> Self {
+        self.suppress_thinking = true;
+        self
+    }
+
+    /// Limits how many tools Claude may call during this turn, overriding
+    /// [`Options::max_tool_calls`](crate::Options::max_tool_calls).
+    ///
+    /// When the limit is exceeded the turn is interrupted, callbacks stop
+    /// firing, and [`send`](Self::send) fails with
+    /// [`Error::ToolCallLimitExceeded`]. The responses received up to the
+    /// interruption are still recorded in the conversation history.
+    pub fn max_tool_calls(mut self, limit: u32) -> Self {
+        self.max_tool_calls = Some(limit);
+        self
+    }
+
+    /// Limits how long this turn may run, overriding
+    /// [`Options::max_turn_duration`](crate::Options::max_turn_duration).
+    ///
+    /// When the limit is exceeded the turn is interrupted and
+    /// [`send`](Self::send) fails with [`Error::TurnDurationExceeded`],
+    /// with the responses received until then recorded in the history.
+    pub fn max_duration(mut self, limit: Duration) -> Self {
+        self.max_duration = Some(limit);
+        self
+    }
+
+    /// Writes text and thinking to the file at `path` as they arrive instead
+    /// of keeping them in memory, for turns that produce very long output.
+    ///
+    /// The file is created, or truncated if it exists. Thinking is wrapped
+    /// in `<thinking>` tags. Callbacks still receive every chunk, and the
+    /// returned responses keep everything else, such as tool calls and the
+    /// completion, though the completion's
+    /// [`result_text`](crate::CompleteResponse::result_text) may still hold
+    /// the final message.
+    pub fn spool_to(mut self, path: impl AsRef<Path>) -> Self {
+        self.spool = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Narrows the client's permissions to `profile` for this turn.
+    ///
+    /// Tool calls the profile does not allow are denied, its permission
+    /// mode is set for the turn and restored afterwards, and if the
+    /// conversation has already spent the profile's budget, [`send`](Self::send)
+    /// fails with [`Error::ProfileBudgetExceeded`] without sending the
+    /// prompt. Requires a client created with
+    /// [`Options::profile`](crate::Options::profile); the client's own
+    /// profile still applies.
+    pub fn profile(mut self, profile: PermissionProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
     /// Executes the turn and returns the full response collection.
     ///
     /// This method:
@@ -273,50 +1155,329 @@ impl<'a, 'c> TurnBuilder<'a, 'c> {
     /// 3. Collects responses (if enabled)
     /// 4. Adds the turn to conversation history
     /// 5. Returns the collected responses
+    ///
+    /// Logs emitted while the turn runs are in a `turn` span carrying its
+    /// [id](Turn::id) as `turn_id`.
     pub async fn send(self) -> Result<Responses, Error> {
+        self.conversation.turns_started += 1;
+        let id = self.conversation.turns_started;
+        self.send_with_profile(id)
+            .instrument(tracing::info_span!("turn", turn_id = id))
+            .await
+    }
+
+    async fn send_with_profile(mut self, id: u64) -> Result<Responses, Error> {
+        let Some(profile) = self.profile.take() else {
+            return self.run(id).await;
+        };
+        if let Some(budget_usd) = profile.budget_usd() {
+            let spent_usd = self.conversation.total_cost_usd();
+            if spent_usd >= budget_usd {
+                return Err(Error::ProfileBudgetExceeded {
+                    profile: profile.name().to_owned(),
+                    budget_usd,
+                    spent_usd,
+                });
+            }
+        }
+
+        let client = self.conversation.client.clone();
+        client.enter_turn_profile(profile.clone()).await?;
+        let result = self.run(id).await;
+        client.leave_turn_profile(&profile).await?;
+        result
+    }
+
+    async fn run(self, id: u64) -> Result<Responses, Error> {
         let TurnBuilder {
             conversation,
             prompt,
             mut on_text,
             mut on_thinking,
+            mut on_block,
             mut on_tool_use,
+            mut on_stalled,
+            #[cfg(feature = "guardrails")]
+            mut on_guardrail,
+            stall_policy,
             collect,
+            suppress_thinking,
+            max_tool_calls,
+            max_duration,
+            spool,
+            profile: _,
         } = self;
 
-        conversation.client.query(&prompt).await?;
-
+        let message = conversation.with_context(&prompt);
+        #[cfg(feature = "watch")]
+        if let Some(watcher) = &conversation.watcher {
+            watcher.clear();
+        }
+        let spool_path = spool;
+        let mut spool = match &spool_path {
+            Some(path) => Some(BufWriter::new(tokio::fs::File::create(path).await?)),
+            None => None,
+        };
+        let client = conversation.client.clone();
+        let started = Instant::now();
         let mut responses = Responses::new();
-        let mut stream = std::pin::pin!(conversation.client.receive());
+        responses.set_started(started);
+        let mut tool_calls = 0;
+        let mut interrupted = false;
+        let mut limit_exceeded = false;
+        let deadline = max_duration.map(|limit| tokio::time::Instant::from_std(started) + limit);
+        let mut duration_exceeded = false;
+        let mut detector = stall_policy.map(|policy| StallDetector::new(policy, Instant::now()));
+        let mut recovery = conversation.recovery.clone().map(RecoveryTracker::new);
+        #[cfg(feature = "guardrails")]
+        let guardrails = conversation.guardrails.clone();
+        let mut aborted = None;
+        let mut blocks = BlockTracker::new();
+        // Forget a limit exceeded outside of any turn.
+        client.take_continuation_limit_exceeded();
+        let mut auth_retries = 0;
+        let outcome = async {
+            loop {
+                client.query(&message).await?;
+                let mut auth_failed = false;
+                // Whether the CLI answered the prompt, and so recorded it in
+                // the session's transcript.
+                let mut answered = false;
+                // The turn enforces its own duration limit, which may differ
+                // from the client's.
+                let mut stream = std::pin::pin!(client.receive_with_meta_within(None));
 
-        while let Some(result) = stream.next().await {
-            let response = result?;
+                loop {
+                    let result = tokio::select! {
+                        next = stream.next() => match next {
+                            Some(result) => result,
+                            None => break,
+                        },
+                        Some(text) = conversation.steering_rx.recv(), if !interrupted => {
+                            client.steer(&text).await?;
+                            continue;
+                        }
+                        () = sleep_until(deadline), if !interrupted => {
+                            tracing::warn!(limit = ?max_duration, "turn ran too long, interrupting");
+                            client.interrupt().await?;
+                            interrupted = true;
+                            duration_exceeded = true;
+                            continue;
+                        }
+                    };
+                    let (meta, response) = result?;
+                    if response
+                        .as_error()
+                        .is_some_and(ErrorResponse::is_authentication_failed)
+                    {
+                        auth_failed = true;
+                    }
+                    answered |= response.as_text().is_some()
+                        || response.as_thinking().is_some()
+                        || response.as_tool_use().is_some()
+                        || response.as_tool_result().is_some();
+                    if suppress_thinking && response.as_thinking().is_some() {
+                        continue;
+                    }
 
-            if let Some(text) = response.as_text()
-                && let Some(ref mut cb) = on_text
-            {
-                cb(text.content());
-            }
-            if let Some(thinking) = response.as_thinking()
-                && let Some(ref mut cb) = on_thinking
-            {
-                cb(thinking.content());
-            }
-            if let Some(tool_use) = response.as_tool_use()
-                && let Some(ref mut cb) = on_tool_use
-            {
-                cb(tool_use);
-            }
+                    if response.as_tool_use().is_some() {
+                        tool_calls += 1;
+                        if !interrupted && max_tool_calls.is_some_and(|limit| tool_calls > limit) {
+                            tracing::warn!(tool_calls, "tool call limit exceeded, interrupting turn");
+                            client.interrupt().await?;
+                            interrupted = true;
+                            limit_exceeded = true;
+                        }
+                    }
+                    if !interrupted
+                        && let Some(detector) = &mut detector
+                        && let Some(stall) = detector.observe(&response, Instant::now())
+                    {
+                        tracing::warn!(
+                            events = stall.events(),
+                            elapsed_secs = stall.elapsed().as_secs_f64(),
+                            "turn stalled"
+                        );
+                        if let Some(ref mut cb) = on_stalled {
+                            cb(&stall);
+                        }
+                        match detector.action() {
+                            StallAction::Notify => {}
+                            StallAction::Steer(message) => client.steer(message).await?,
+                            StallAction::Interrupt => {
+                                client.interrupt().await?;
+                                interrupted = true;
+                            }
+                        }
+                    }
+                    if !interrupted
+                        && let Some(tracker) = &mut recovery
+                        && let Some(step) = tracker.observe(&response)
+                    {
+                        match step {
+                            RecoveryStep::Steer(message) => client.steer(&message).await?,
+                            RecoveryStep::Abort { tool, failures } => {
+                                tracing::warn!(%tool, failures, "tool keeps failing, aborting turn");
+                                client.interrupt().await?;
+                                interrupted = true;
+                                aborted = Some(Error::ToolRecoveryAborted { tool, failures });
+                            }
+                        }
+                    }
+                    #[cfg(feature = "guardrails")]
+                    let response = match (&guardrails, response) {
+                        (Some(guardrails), Response::Text(text)) if !interrupted => {
+                            let report = guardrails.check(text.content()).await;
+                            for event in report.events() {
+                                tracing::warn!(
+                                    guardrail = event.guardrail(),
+                                    action = ?event.action(),
+                                    "guardrail violated"
+                                );
+                                if let Some(ref mut cb) = on_guardrail {
+                                    cb(event);
+                                }
+                            }
+                            if let Some(event) = report.interrupting() {
+                                client.interrupt().await?;
+                                interrupted = true;
+                                aborted = Some(Error::GuardrailTripped {
+                                    guardrail: event.guardrail().to_owned(),
+                                });
+                            }
+                            match report.into_redacted() {
+                                Some(redacted) => Response::Text(text.with_content(redacted)),
+                                None => Response::Text(text),
+                            }
+                        }
+                        (_, response) => response,
+                    };
+                    if interrupted {
+                        // Drain the rest of the turn so the next one starts cleanly.
+                        if collect {
+                            responses.push_with_meta(meta, response);
+                        }
+                        continue;
+                    }
 
-            if collect {
-                responses.push(response);
+                    if let Some(text) = response.as_text()
+                        && let Some(ref mut cb) = on_text
+                    {
+                        cb(text.content());
+                    }
+                    if let Some(thinking) = response.as_thinking()
+                        && let Some(ref mut cb) = on_thinking
+                    {
+                        cb(thinking.content());
+                    }
+                    if let Some(tool_use) = response.as_tool_use()
+                        && let Some(ref mut cb) = on_tool_use
+                    {
+                        cb(tool_use);
+                    }
+                    if let Response::Partial(partial) = &response
+                        && let Some(ref mut cb) = on_block
+                        && let Some(event) = blocks.update(partial)
+                        && !(suppress_thinking && event.kind() == BlockKind::Thinking)
+                    {
+                        cb(&event);
+                    }
+
+                    if let Some(spool) = &mut spool {
+                        match &response {
+                            Response::Text(text) => {
+                                spool.write_all(text.content().as_bytes()).await?;
+                                continue;
+                            }
+                            Response::Thinking(thinking) => {
+                                let block =
+                                    format!("<thinking>\n{}\n</thinking>\n", thinking.content());
+                                spool.write_all(block.as_bytes()).await?;
+                                continue;
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    if collect {
+                        responses.push_with_meta(meta, response);
+                    }
+                }
+
+                let Some(refresh) = client
+                    .auth_refresh()
+                    .filter(|_| auth_failed && !interrupted)
+                else {
+                    break;
+                };
+                if auth_retries >= refresh.retries() {
+                    break;
+                }
+                auth_retries += 1;
+                tracing::warn!(
+                    attempt = auth_retries,
+                    "authentication expired, restarting CLI"
+                );
+                let env = refresh.refresh().await?;
+                client.restart(env).await?;
+                if answered {
+                    // The resumed session already holds the prompt, so
+                    // sending it again would repeat it. The turn ends with
+                    // what it got, and the next one runs on the new
+                    // credentials.
+                    break;
+                }
+                responses = Responses::new();
+                responses.set_started(started);
+                tool_calls = 0;
+                // The retried turn's output replaces the first attempt's.
+                if let (Some(spool), Some(path)) = (&mut spool, &spool_path) {
+                    *spool = BufWriter::new(tokio::fs::File::create(path).await?);
+                }
             }
+            Ok::<(), Error>(())
         }
+        .await;
+        // Whatever ended the turn, write out the text spooled so far.
+        let flushed = match &mut spool {
+            Some(spool) => spool.flush().await,
+            None => Ok(()),
+        };
+        outcome?;
+        flushed?;
 
-        conversation.history.push(Turn {
+        #[cfg(feature = "watch")]
+        let file_changes = match &conversation.watcher {
+            Some(watcher) => Some(watcher.take().await),
+            None => None,
+        };
+        #[cfg(not(feature = "watch"))]
+        let file_changes = None;
+
+        let responses = conversation.record(Turn {
+            id,
+            parent: conversation.head,
             prompt,
-            responses: responses.clone(),
+            responses,
+            file_changes,
         });
 
+        if let Some(limit) = max_tool_calls.filter(|_| limit_exceeded) {
+            return Err(Error::ToolCallLimitExceeded {
+                limit,
+                calls: tool_calls,
+            });
+        }
+        if let Some(limit) = max_duration.filter(|_| duration_exceeded) {
+            return Err(Error::TurnDurationExceeded { limit });
+        }
+        if let Some(error) = aborted {
+            return Err(error);
+        }
+        if let Some(limit) = client.take_continuation_limit_exceeded() {
+            return Err(Error::ContinuationLimitExceeded { limit });
+        }
+
         Ok(responses)
     }
 
@@ -360,39 +1521,725 @@ impl<'a, 'c> TurnBuilder<'a, 'c> {
     /// # Ok(())
     /// # }
     /// ```
+    #[cfg(feature = "schema")]
     pub async fn send_as<T>(self) -> Result<T, Error>
     where
         T: DeserializeOwned + JsonSchema,
     {
+        let client = self.conversation.client.clone();
         let responses = self.send().await?;
 
-        // The structured output comes from the result message's structuredOutput field
         let structured_output = responses
             .completion()
-            .and_then(|c| c.structured_output())
-            .cloned()
+            .and_then(|c| client.structured_output(c))
             .ok_or_else(|| Error::ProtocolError("no structured output in response".to_owned()))?;
 
-        let result = serde_json::from_value::<T>(structured_output)?;
+        let (_, wrapper) = crate::util::structured_output_schema::<T>();
+        let result = serde_json::from_value::<T>(crate::util::unwrap_structured_output(
+            structured_output,
+            wrapper,
+        ))?;
 
         Ok(result)
     }
+
+    /// Like [`send_as`](Self::send_as), but checks the output against `T`'s
+    /// schema first, coercing values that convert without loss, and reports
+    /// how well it matched.
+    ///
+    /// Output that cannot be read as `T` even after coercion fails with
+    /// [`Error::StructuredOutputRejected`], which carries the raw output and
+    /// the schema violations.
+    #[cfg(feature = "schema")]
+    pub async fn send_as_validated<T>(self) -> Result<Validated<T>, Error>
+    where
+        T: DeserializeOwned + JsonSchema,
+    {
+        let client = self.conversation.client.clone();
+        let responses = self.send().await?;
+
+        let structured_output = responses
+            .completion()
+            .and_then(|c| client.structured_output(c))
+            .ok_or_else(|| Error::ProtocolError("no structured output in response".to_owned()))?;
+
+        let (_, wrapper) = crate::util::structured_output_schema::<T>();
+        crate::structured::validate(crate::util::unwrap_structured_output(
+            structured_output,
+            wrapper,
+        ))
+    }
+}
+
+fn children(parents: &BTreeMap<u64, Option<u64>>, id: u64) -> Vec<u64> {
+    parents
+        .iter()
+        .filter(|(_, parent)| **parent == Some(id))
+        .map(|(child, _)| *child)
+        .collect()
+}
+
+fn branch(parents: &BTreeMap<u64, Option<u64>>, id: u64) -> Vec<u64> {
+    let mut path = Vec::new();
+    let mut next = parents.contains_key(&id).then_some(id);
+    while let Some(id) = next {
+        path.push(id);
+        next = parents.get(&id).copied().flatten();
+    }
+    path.reverse();
+    path
+}
+
+fn leaves(parents: &BTreeMap<u64, Option<u64>>) -> Vec<u64> {
+    let continued = parents.values().flatten().collect::<BTreeSet<_>>();
+    parents
+        .keys()
+        .filter(|id| !continued.contains(id))
+        .copied()
+        .collect()
+}
+
+/// The prompt asking the main agent to pass `prompt` on to `agent_name`.
+fn delegation_prompt(agent_name: &str, prompt: &str) -> String {
+    format!(
+        "Use the {} tool with subagent_type \"{agent_name}\" to carry out the task below. \
+         Pass the task on as written and do not work on it yourself.\n\n\
+         <task>\n{prompt}\n</task>",
+        BuiltinTool::Task,
+    )
+}
+
+/// Whether `tool_use` is the main agent starting the subagent `agent_name`.
+fn is_delegation(tool_use: &ToolUseResponse, agent_name: &str) -> bool {
+    tool_use.parent_tool_use_id().is_none()
+        && tool_use.name() == BuiltinTool::Task.as_str()
+        && tool_use
+            .input()
+            .get("subagent_type")
+            .and_then(|t| t.as_str())
+            == Some(agent_name)
+}
+
+/// The responses of a turn kept by [`HistoryMode::SummariesOnly`].
+fn summary_of(responses: &Responses) -> Responses {
+    responses.project(|response| {
+        matches!(response, Response::Text(_) | Response::Complete(_)).then(|| response.clone())
+    })
+}
+
+/// Sleeps until `deadline`, or forever without one.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(unix)]
+    use crate::auth::AuthRefresh;
+    #[cfg(unix)]
+    use crate::fake_cli::{FakeCli, assistant_text, assistant_tool_use, result};
+    #[cfg(unix)]
+    use crate::options::Options;
 
     // Note: These tests require mocking or integration with Claude CLI
     // For now, we just test the basic structure
 
+    #[test]
+    fn test_owned_conversation_can_move_between_tasks() {
+        fn assert_send_static<T: Send + 'static>() {}
+        assert_send_static::<OwnedConversation>();
+    }
+
+    #[test]
+    fn test_summary_keeps_text_and_completion() {
+        let responses = [
+            serde_json::json!({"type": "thinking", "thinking": "hmm", "signature": ""}),
+            serde_json::json!({"type": "text", "text": "Done"}),
+            serde_json::json!({"type": "tool_use", "id": "t1", "name": "Read", "input": {}}),
+            serde_json::json!({
+                "type": "complete",
+                "subtype": "success",
+                "duration_ms": 10,
+                "duration_api_ms": 8,
+                "is_error": false,
+                "num_turns": 1,
+                "session_id": "s",
+                "total_cost_usd": 0.5
+            }),
+        ]
+        .into_iter()
+        .map(|value| serde_json::from_value::<Response>(value).unwrap())
+        .fold(Responses::new(), |mut responses, response| {
+            responses.push(response);
+            responses
+        });
+
+        let summary = summary_of(&responses);
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary.text_content(), "Done");
+        assert_eq!(summary.completion().unwrap().total_cost_usd(), Some(0.5));
+    }
+
+    #[test]
+    fn test_branch_tree_traversal() {
+        // 1 - 2 - 3
+        //      \
+        //       4 - 5
+        let parents = BTreeMap::from([
+            (1, None),
+            (2, Some(1)),
+            (3, Some(2)),
+            (4, Some(2)),
+            (5, Some(4)),
+        ]);
+        assert_eq!(children(&parents, 2), [3, 4]);
+        assert!(children(&parents, 5).is_empty());
+        assert_eq!(branch(&parents, 5), [1, 2, 4, 5]);
+        assert_eq!(branch(&parents, 3), [1, 2, 3]);
+        assert!(branch(&parents, 9).is_empty());
+        assert_eq!(leaves(&parents), [3, 5]);
+    }
+
     #[test]
     fn test_turn_text() {
         let turn = Turn {
+            id: 1,
+            parent: None,
             prompt: "Hello".to_string(),
             responses: Responses::new(),
+            file_changes: None,
         };
         assert_eq!(turn.text(), "");
         assert_eq!(turn.prompt, "Hello");
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_summariser_runs_with_the_clients_options() {
+        // The summariser is told apart by its system prompt, and notes how it
+        // was started.
+        let cli = FakeCli::new(&format!(
+            r#"case "$*" in
+*Summarise*)
+    printf '%s\n' "$@" > summariser_args
+    printf '%s' "$CLAUDERS_TEST_MARKER" > summariser_env
+    text='{summary}' ;;
+*) text='{done}' ;;
+esac
+next; reply '{{}}'
+next
+emit "$text"
+emit '{result}'
+drain"#,
+            summary = assistant_text("They said hi."),
+            done = assistant_text("done"),
+            result = result(),
+        ));
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .env_var("CLAUDERS_TEST_MARKER", "parent")
+            .fallback_model(Model::Sonnet)
+            .max_turns(7)
+            .resume("earlier");
+        let client = Client::new(options).await.unwrap();
+        let mut conv = client.conversation();
+        conv.turn("hi").send().await.unwrap();
+
+        assert_eq!(conv.summarise(100).await.unwrap(), "They said hi.");
+        let args = std::fs::read_to_string(cli.dir().join("summariser_args")).unwrap();
+        let args = args.lines().collect::<Vec<_>>();
+        let value = |flag: &str| {
+            let at = args.iter().position(|arg| *arg == flag)?;
+            args.get(at + 1).copied()
+        };
+        assert_eq!(value("--max-turns"), Some("1"));
+        assert_eq!(value("--model"), Some("haiku"));
+        assert!(value("--fallback-model").is_some(), "{args:?}");
+        assert!(value("--resume").is_none(), "{args:?}");
+        assert_eq!(
+            std::fs::read_to_string(cli.dir().join("summariser_env")).unwrap(),
+            "parent"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_turn_past_its_duration_limit_is_interrupted() {
+        // The CLI ends the turn only once interrupted.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{}'
+next
+case "$line" in *'"subtype":"interrupt"'*) reply '{{}}' ;; *) exit 1 ;; esac
+emit '{}'
+drain"#,
+            assistant_text("working"),
+            result(),
+        ));
+        // The turn's own limit replaces the client's.
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .max_turn_duration(Duration::from_secs(60));
+        let client = Client::new(options).await.unwrap();
+        let mut conv = client.conversation();
+
+        // The clock jumps to the deadline whenever the turn waits on the CLI.
+        tokio::time::pause();
+        let started = tokio::time::Instant::now();
+        let err = conv
+            .turn("hi")
+            .max_duration(Duration::from_secs(600))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() >= Duration::from_secs(600));
+        assert!(
+            matches!(err, Error::TurnDurationExceeded { limit } if limit == Duration::from_secs(600)),
+            "{err:?}"
+        );
+        assert_eq!(conv.history().len(), 1);
+    }
+
+    /// An assistant message failing authentication, as the CLI writes it.
+    #[cfg(unix)]
+    const AUTH_FAILED: &str = r#"{"type":"assistant","message":{"id":"msg_2","type":"message","role":"assistant","model":"claude-sonnet-4-5","content":[],"error":"authentication_failed","stop_reason":null,"usage":{"input_tokens":1,"output_tokens":1}},"parent_tool_use_id":null,"session_id":"s1"}"#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spool_is_flushed_when_the_turn_ends() {
+        let cli = FakeCli::initialized(&format!(
+            "next\nemit '{}'\nemit '{}'\ndrain",
+            assistant_text("hello"),
+            result(),
+        ));
+        let spool = cli.dir().join("spool.txt");
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation();
+
+        conv.turn("hi").spool_to(&spool).send().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&spool).unwrap(), "hello");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spool_is_flushed_when_the_turn_fails() {
+        // The CLI writes a malformed message mid-turn, after some text.
+        let cli = FakeCli::initialized(&format!(
+            "next\nemit '{}'\nemit '{{\"type\":'\ndrain",
+            assistant_text("partial"),
+        ));
+        let spool = cli.dir().join("spool.txt");
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation();
+
+        assert!(conv.turn("hi").spool_to(&spool).send().await.is_err());
+        assert_eq!(std::fs::read_to_string(&spool).unwrap(), "partial");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spool_keeps_only_the_retried_turn() {
+        // The first run fails authentication before answering; the restarted
+        // one answers the prompt sent again.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+if [ -e restarted ]; then
+    case "$line" in *'"hi"'*) emit '{second}' ;; esac
+else
+    touch restarted
+    emit '{auth_failed}'
+fi
+emit '{result}'
+drain"#,
+            auth_failed = AUTH_FAILED,
+            second = assistant_text("second"),
+            result = result(),
+        ));
+        let spool = cli.dir().join("spool.txt");
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .refresh_auth(AuthRefresh::new());
+        let client = Client::new(options).await.unwrap();
+        let mut conv = client.conversation();
+
+        conv.turn("hi").spool_to(&spool).send().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&spool).unwrap(), "second");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_answered_turn_is_not_sent_again_after_refreshing_auth() {
+        // The first run fails authentication after answering. The restarted
+        // one notes whether the next prompt it reads is the first one again.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+if [ -e restarted ]; then
+    case "$line" in *'"hi"'*) touch resent ;; esac
+    emit '{later}'
+else
+    touch restarted
+    emit '{first}'
+    emit '{auth_failed}'
+fi
+emit '{result}'
+drain"#,
+            auth_failed = AUTH_FAILED,
+            first = assistant_text("first"),
+            later = assistant_text("later"),
+            result = result(),
+        ));
+        let options = Options::new()
+            .cli_path(cli.path())
+            .cwd(cli.dir())
+            .refresh_auth(AuthRefresh::new());
+        let client = Client::new(options).await.unwrap();
+        let mut conv = client.conversation();
+
+        let responses = conv.turn("hi").send().await.unwrap();
+        assert_eq!(responses.text_content(), "first");
+        let responses = conv.turn("again").send().await.unwrap();
+        assert_eq!(responses.text_content(), "later");
+        assert!(!cli.dir().join("resent").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_turn_crossing_the_tool_call_limit_is_interrupted() {
+        // The turn ends once the CLI sees the interrupt.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+emit '{}'
+emit '{}'
+emit '{}'
+next
+case "$line" in *'"subtype":"interrupt"'*) emit '{}' ;; esac
+drain"#,
+            assistant_tool_use("t1", "Read"),
+            assistant_tool_use("t2", "Read"),
+            assistant_tool_use("t3", "Read"),
+            result(),
+        ));
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation();
+
+        let err = conv.turn("hi").max_tool_calls(2).send().await.unwrap_err();
+        assert!(
+            matches!(err, Error::ToolCallLimitExceeded { limit: 2, calls: 3 }),
+            "{err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_turn_reaching_the_tool_call_limit_completes() {
+        let cli = FakeCli::initialized(&format!(
+            "next\nemit '{}'\nemit '{}'\nemit '{}'\ndrain",
+            assistant_tool_use("t1", "Read"),
+            assistant_tool_use("t2", "Read"),
+            result(),
+        ));
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation();
+
+        let responses = conv.turn("hi").max_tool_calls(2).send().await.unwrap();
+        assert_eq!(responses.tool_uses().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_context_is_prepended_to_each_prompt_when_sent() {
+        // The CLI records each user message it is sent.
+        let cli = FakeCli::initialized(&format!(
+            "next\nprintf '%s\\n' \"$line\" >> sent\nemit '{0}'\n\
+             next\nprintf '%s\\n' \"$line\" >> sent\nemit '{0}'\ndrain",
+            result(),
+        ));
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let sends = std::sync::atomic::AtomicUsize::new(0);
+        let mut conv = client
+            .conversation()
+            .with_context_provider(|| {
+                let n = sends.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                format!("send {n}")
+            })
+            .with_context_provider(|| "  ".to_owned());
+
+        conv.say("first").await.unwrap();
+        conv.say("second").await.unwrap();
+
+        let sent = std::fs::read_to_string(cli.dir().join("sent")).unwrap();
+        let contents = sent
+            .lines()
+            .map(|line| {
+                let message: serde_json::Value = serde_json::from_str(line).unwrap();
+                message["message"]["content"].as_str().unwrap().to_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            contents,
+            [
+                "<context>\nsend 1\n</context>\n\nfirst",
+                "<context>\nsend 2\n</context>\n\nsecond",
+            ]
+        );
+        assert_eq!(conv.history()[0].prompt, "first");
+        assert_eq!(conv.history()[1].prompt, "second");
+    }
+
+    /// A CLI answering each prompt with the prompt itself, or with a
+    /// malformed message for prompts starting with "fail".
+    #[cfg(unix)]
+    fn echoing_cli() -> FakeCli {
+        FakeCli::initialized(&format!(
+            r#"while :; do
+next
+prompt=$(printf '%s\n' "$line" | sed -n 's/.*"content":"\([^"]*\)".*/\1/p')
+case "$prompt" in
+fail*) emit '{{"type":' ;;
+*) emit "$(printf '%s' '{}' | sed "s/PROMPT/$prompt/")"; emit '{}' ;;
+esac
+done"#,
+            assistant_text("PROMPT"),
+            result(),
+        ))
+    }
+
+    /// Runs the queue, describing each event as a line.
+    #[cfg(unix)]
+    async fn run_queue_events(conv: &mut Conversation<'_>) -> (usize, Vec<String>) {
+        let mut events = Vec::new();
+        let turns = conv
+            .run_queue(|event| {
+                events.push(match event {
+                    QueueEvent::Started { prompt } => format!("started {prompt}"),
+                    QueueEvent::Finished(turn) => {
+                        format!("finished {}: {}", turn.prompt, turn.text())
+                    }
+                    QueueEvent::Failed { prompt, .. } => format!("failed {prompt}"),
+                })
+            })
+            .await;
+        (turns, events)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_queue_runs_enqueued_prompts_before_sent_ones() {
+        let cli = echoing_cli();
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation();
+
+        let queue = conv.prompt_queue();
+        queue.send("three".to_owned()).unwrap();
+        drop(queue);
+        conv.enqueue("one");
+        conv.enqueue("two");
+
+        let (turns, events) = run_queue_events(&mut conv).await;
+        assert_eq!(turns, 3);
+        assert_eq!(
+            events,
+            [
+                "started one",
+                "finished one: one",
+                "started two",
+                "finished two: two",
+                "started three",
+                "finished three: three",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_queue_runs_one_turn_at_a_time() {
+        let cli = echoing_cli();
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation();
+
+        let queue = conv.prompt_queue();
+        for n in 0..5 {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.send(format!("p{n}")).unwrap() });
+        }
+        drop(queue);
+
+        // Each turn finishes, with its own reply, before the next starts.
+        let (turns, events) = run_queue_events(&mut conv).await;
+        assert_eq!(turns, 5);
+        let mut prompts = events
+            .chunks(2)
+            .map(|pair| {
+                let prompt = pair[0].strip_prefix("started ").unwrap();
+                assert_eq!(pair[1], format!("finished {prompt}: {prompt}"));
+                prompt.to_owned()
+            })
+            .collect::<Vec<_>>();
+        prompts.sort();
+        assert_eq!(prompts, ["p0", "p1", "p2", "p3", "p4"]);
+        assert_eq!(conv.history().len(), 5);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_queue_reports_a_failed_turn_and_moves_on() {
+        let cli = echoing_cli();
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation();
+
+        conv.enqueue("one");
+        conv.enqueue("fail");
+        conv.enqueue("two");
+
+        let (turns, events) = run_queue_events(&mut conv).await;
+        assert_eq!(turns, 3);
+        assert_eq!(
+            events,
+            [
+                "started one",
+                "finished one: one",
+                "started fail",
+                "failed fail",
+                "started two",
+                "finished two: two",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_turn_ids_are_not_reused_once_trimmed() {
+        let cli = echoing_cli();
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation().max_history(2);
+
+        conv.say("one").await.unwrap();
+        assert!(conv.say("fail").await.is_err());
+        conv.say("two").await.unwrap();
+        conv.say("three").await.unwrap();
+
+        // The failed turn used up id 2, and turn 1 left the history.
+        let ids = conv
+            .history()
+            .iter()
+            .map(|turn| turn.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [3, 4]);
+        assert_eq!(conv.turn_by_id(3).unwrap().prompt, "two");
+        assert_eq!(conv.turn_by_id(4).unwrap().prompt, "three");
+        assert!(conv.turn_by_id(1).is_none());
+        assert!(conv.turn_by_id(2).is_none());
+    }
+
+    /// Records the `turn_id` of the innermost span each event with a `text`
+    /// field is logged in.
+    #[cfg(unix)]
+    #[derive(Default)]
+    struct TurnIds {
+        spans: std::sync::Mutex<Vec<Option<u64>>>,
+        entered: std::sync::Mutex<Vec<usize>>,
+        logged: Arc<std::sync::Mutex<Vec<Option<u64>>>>,
+    }
+
+    #[cfg(unix)]
+    struct TurnId(Option<u64>);
+
+    #[cfg(unix)]
+    impl tracing::field::Visit for TurnId {
+        fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+            if field.name() == "turn_id" {
+                self.0 = Some(value);
+            }
+        }
+
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    #[cfg(unix)]
+    impl tracing::Subscriber for TurnIds {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut turn_id = TurnId(None);
+            span.record(&mut turn_id);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(turn_id.0);
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            if event.metadata().fields().field("text").is_none() {
+                return;
+            }
+            let turn_id = self
+                .entered
+                .lock()
+                .unwrap()
+                .last()
+                .and_then(|&index| self.spans.lock().unwrap()[index]);
+            self.logged.lock().unwrap().push(turn_id);
+        }
+
+        fn enter(&self, span: &tracing::span::Id) {
+            let index = span.into_u64() as usize - 1;
+            self.entered.lock().unwrap().push(index);
+        }
+
+        fn exit(&self, _span: &tracing::span::Id) {
+            self.entered.lock().unwrap().pop();
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_logs_during_a_turn_carry_its_id() {
+        let cli = echoing_cli();
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+        let mut conv = client.conversation();
+        conv.say("one").await.unwrap();
+
+        let turn_ids = TurnIds::default();
+        let logged = Arc::clone(&turn_ids.logged);
+        let _guard = tracing::subscriber::set_default(turn_ids);
+        conv.turn("two")
+            .on_text(|text| tracing::info!(text, "received"))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(*logged.lock().unwrap(), [Some(2)]);
+    }
 }
@@ -0,0 +1,256 @@
+//! Denied tool calls, reported as one stream of typed events.
+//!
+//! A tool call can be refused in several places: by a PreToolUse hook, by a
+//! [permission callback](crate::permissions::PermissionCallbacks), by the
+//! [approval broker](crate::approvals::ApprovalBroker), or by the CLI itself,
+//! which lists the calls it refused in each turn's result. Every refusal is
+//! logged and passed to the callback set with
+//! [`Options::on_denial`](crate::Options::on_denial) as a [`DenialEvent`],
+//! whichever of them made it. A call reported by the SDK is not reported
+//! again when the CLI lists it in the result.
+//!
+//! ```no_run
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let client = Client::new(Options::new().on_denial(|event| {
+//!     eprintln!(
+//!         "denied {} ({:?}, rule {:?}): {:?}",
+//!         event.tool_name(),
+//!         event.source(),
+//!         event.rule(),
+//!         event.reason(),
+//!     );
+//! }))
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde_json::Value;
+
+use crate::proto::PermissionDenial;
+
+/// Called with every denied tool call.
+pub type DenialCallback = Arc<dyn Fn(&DenialEvent) + Send + Sync>;
+
+/// Where a tool call was denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DenialSource {
+    /// A PreToolUse hook denied the call.
+    Hook,
+    /// A permission callback denied the call.
+    PermissionCallback,
+    /// The approval broker denied the call, or nobody approved it in time.
+    Approver,
+    /// Nothing decided the permission request, so it was denied by default.
+    Unhandled,
+    /// The CLI reported the call as denied in the turn's result, without the
+    /// SDK having denied it.
+    Cli,
+}
+
+/// A denied tool call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenialEvent {
+    source: DenialSource,
+    tool_name: String,
+    tool_use_id: Option<String>,
+    rule: Option<String>,
+    reason: Option<String>,
+    input: Value,
+}
+
+impl DenialEvent {
+    pub(crate) fn new(source: DenialSource, tool_name: impl Into<String>, input: Value) -> Self {
+        Self {
+            source,
+            tool_name: tool_name.into(),
+            tool_use_id: None,
+            rule: None,
+            reason: None,
+            input,
+        }
+    }
+
+    pub(crate) fn from_cli(denial: &PermissionDenial) -> Self {
+        Self::new(
+            DenialSource::Cli,
+            denial.tool_name(),
+            denial.tool_input().clone(),
+        )
+        .with_tool_use_id(Some(denial.tool_use_id().to_owned()))
+    }
+
+    #[must_use]
+    pub(crate) fn with_tool_use_id(mut self, tool_use_id: Option<String>) -> Self {
+        self.tool_use_id = tool_use_id;
+        self
+    }
+
+    #[must_use]
+    pub(crate) fn with_rule(mut self, rule: Option<String>) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    #[must_use]
+    pub(crate) fn with_reason(mut self, reason: Option<String>) -> Self {
+        self.reason = reason;
+        self
+    }
+
+    pub fn source(&self) -> DenialSource {
+        self.source
+    }
+
+    pub fn tool_name(&self) -> &str {
+        &self.tool_name
+    }
+
+    pub fn tool_use_id(&self) -> Option<&str> {
+        self.tool_use_id.as_deref()
+    }
+
+    /// What denied the call: the name of the hook, or the tool selector of
+    /// the permission callback (`"fallback"` for the fallback callback).
+    /// `None` when the denier has no name.
+    pub fn rule(&self) -> Option<&str> {
+        self.rule.as_deref()
+    }
+
+    /// The reason given to Claude, if any.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// The input of the denied call.
+    pub fn input(&self) -> &Value {
+        &self.input
+    }
+}
+
+/// Logs denials and passes them to the callback, reporting each tool call
+/// once.
+#[derive(Clone, Default)]
+pub(crate) struct DenialReporter {
+    callback: Option<DenialCallback>,
+    /// Tool use ids denied by the SDK since the last result.
+    reported: Arc<Mutex<HashSet<String>>>,
+}
+
+impl DenialReporter {
+    pub(crate) fn new(callback: DenialCallback) -> Self {
+        Self {
+            callback: Some(callback),
+            reported: Arc::default(),
+        }
+    }
+
+    pub(crate) fn report(&self, event: DenialEvent) {
+        if let Some(id) = &event.tool_use_id {
+            self.reported
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(id.clone());
+        }
+        self.emit(&event);
+    }
+
+    /// Reports the denials the CLI listed in a turn's result that the SDK has
+    /// not reported already, and starts the next turn afresh.
+    pub(crate) fn report_result(&self, denials: &[PermissionDenial]) {
+        let reported =
+            std::mem::take(&mut *self.reported.lock().unwrap_or_else(PoisonError::into_inner));
+        for event in unreported(denials, &reported) {
+            self.emit(&event);
+        }
+    }
+
+    fn emit(&self, event: &DenialEvent) {
+        tracing::info!(
+            source = ?event.source,
+            tool = %event.tool_name,
+            tool_use_id = event.tool_use_id.as_deref(),
+            rule = event.rule.as_deref(),
+            reason = event.reason.as_deref(),
+            "tool call denied"
+        );
+        if let Some(callback) = &self.callback {
+            callback(event);
+        }
+    }
+}
+
+impl std::fmt::Debug for DenialReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DenialReporter")
+            .field("callback", &self.callback.as_ref().map(|_| "<callback>"))
+            .finish_non_exhaustive()
+    }
+}
+
+/// The events for CLI-reported denials not among the `reported` tool use ids.
+fn unreported(denials: &[PermissionDenial], reported: &HashSet<String>) -> Vec<DenialEvent> {
+    denials
+        .iter()
+        .filter(|denial| !reported.contains(denial.tool_use_id()))
+        .map(DenialEvent::from_cli)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_cli_denials_skip_those_already_reported() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let reporter = DenialReporter::new({
+            let events = Arc::clone(&events);
+            Arc::new(move |event: &DenialEvent| events.lock().unwrap().push(event.clone()))
+        });
+
+        reporter.report(
+            DenialEvent::new(DenialSource::Hook, "Bash", json!({"command": "rm -rf /"}))
+                .with_tool_use_id(Some("toolu_1".to_owned()))
+                .with_rule(Some("policy_bash".to_owned()))
+                .with_reason(Some("rm is not allowed".to_owned())),
+        );
+        let denials = serde_json::from_value::<Vec<PermissionDenial>>(json!([
+            {"tool_name": "Bash", "tool_use_id": "toolu_1", "tool_input": {"command": "rm -rf /"}},
+            {"tool_name": "WebFetch", "tool_use_id": "toolu_2", "tool_input": {"url": "x"}},
+        ]))
+        .unwrap();
+        reporter.report_result(&denials);
+
+        let events = events.lock().unwrap();
+        let seen = events
+            .iter()
+            .map(|e| (e.source(), e.tool_name(), e.tool_use_id(), e.rule()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            seen,
+            [
+                (
+                    DenialSource::Hook,
+                    "Bash",
+                    Some("toolu_1"),
+                    Some("policy_bash")
+                ),
+                (DenialSource::Cli, "WebFetch", Some("toolu_2"), None),
+            ]
+        );
+        assert_eq!(events[1].input(), &json!({"url": "x"}));
+        drop(events);
+
+        // The result clears what was reported, so the next turn starts afresh.
+        assert_eq!(unreported(&denials, &HashSet::new()).len(), 2);
+        assert!(reporter.reported.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+//! Injectable sources of request ids and wall-clock time.
+//!
+//! Clients default to UUIDv7 request ids and the system clock. Tests that
+//! compare recorded traffic against golden files can swap in
+//! [`IdGenerator::seeded`] and [`Clock::fixed`] through
+//! [`Options::deterministic`](crate::Options::deterministic) so every run
+//! produces the same bytes.
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// Produces the ids of control requests sent to the CLI.
+#[derive(Clone)]
+pub struct IdGenerator(Arc<dyn Fn() -> String + Send + Sync>);
+
+impl IdGenerator {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// Time-ordered random UUIDv7s, the default.
+    pub fn uuid_v7() -> Self {
+        Self::new(crate::util::uuid_v7)
+    }
+
+    /// UUIDs derived from `seed` and a counter, identical on every run.
+    pub fn seeded(seed: u64) -> Self {
+        let counter = AtomicU64::new(0);
+        Self::new(move || {
+            let n = counter.fetch_add(1, Ordering::Relaxed);
+            crate::util::format_uuid(u128::from(seed) << 64 | u128::from(n))
+        })
+    }
+
+    pub fn next_id(&self) -> String {
+        (self.0)()
+    }
+}
+
+impl Default for IdGenerator {
+    fn default() -> Self {
+        Self::uuid_v7()
+    }
+}
+
+impl fmt::Debug for IdGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdGenerator").finish_non_exhaustive()
+    }
+}
+
+/// Reports the current wall-clock time.
+#[derive(Clone)]
+pub struct Clock(Arc<dyn Fn() -> SystemTime + Send + Sync>);
+
+impl Clock {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn() -> SystemTime + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// The system clock, the default.
+    pub fn system() -> Self {
+        Self::new(SystemTime::now)
+    }
+
+    /// A clock stopped at `time`.
+    pub fn fixed(time: SystemTime) -> Self {
+        Self::new(move || time)
+    }
+
+    pub fn now(&self) -> SystemTime {
+        (self.0)()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_ids_repeat_across_generators() {
+        let a = IdGenerator::seeded(7);
+        let b = IdGenerator::seeded(7);
+        let first = [a.next_id(), a.next_id()];
+
+        assert_ne!(first[0], first[1]);
+        assert_eq!(first, [b.next_id(), b.next_id()]);
+        assert_ne!(first[0], IdGenerator::seeded(8).next_id());
+        assert_eq!(first[1], "00000000-0000-0007-0000-000000000001");
+    }
+
+    #[test]
+    fn test_uuid_v7_ids_are_versioned_and_unique() {
+        let ids = IdGenerator::uuid_v7();
+        let (a, b) = (ids.next_id(), ids.next_id());
+
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 36);
+        assert_eq!(&a[14..15], "7");
+        assert!(matches!(&a[19..20], "8" | "9" | "a" | "b"));
+    }
+}
@@ -1,37 +1,154 @@
 use thiserror::Error;
 
+/// An error from the client, the CLI or the protocol between them.
+///
+/// More variants may be added without a major release, so matches need a
+/// wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
+    #[error("cannot branch from turn {id}: {reason}")]
+    CannotBranch { id: u64, reason: String },
     #[error("Claude Code not found: {0}")]
     CliNotFound(String),
     #[error("connection error: {0}")]
     ConnectionError(String),
+    #[error("configuration error: {0}")]
+    ConfigError(#[from] ConfigError),
     #[error("control error (request_id={request_id}): {message}")]
     ControlError { request_id: String, message: String },
+    #[error("Stop hooks kept the agent working past {limit} continuations")]
+    ContinuationLimitExceeded { limit: u32 },
     #[error("hook error (callback_id={callback_id}): {message}")]
     HookError {
         callback_id: String,
         message: String,
     },
+    #[error("handler dispatch queue is full ({capacity} responses)")]
+    DispatchQueueFull { capacity: usize },
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("MCP server '{name}' was not registered by the CLI (status: {status})")]
+    McpServerUnavailable { name: String, status: String },
+    #[error("the CLI wrote a message longer than {limit} bytes")]
+    MessageTooLarge { limit: usize },
     #[error(
         "no output schema configured; use Options::with_json_schema::<T>() when creating the client"
     )]
     NoSchemaConfigured,
+    #[error("no route accepts the task")]
+    NoRoute,
+    #[error("the task was not handed to agent '{agent}'")]
+    NotDelegated { agent: String },
     #[error("permission denied for tool '{tool_name}': {message}")]
     PermissionDenied { tool_name: String, message: String },
+    #[error("profile '{profile}' budget of ${budget_usd:.2} is spent (${spent_usd:.2})")]
+    ProfileBudgetExceeded {
+        profile: String,
+        budget_usd: f64,
+        spent_usd: f64,
+    },
     #[error("process error: {0}")]
     ProcessError(String),
     #[error("protocol error: {0}")]
     ProtocolError(String),
+    #[error("quota '{bucket}' of ${limit_usd:.2} is exhausted (${spent_usd:.2} spent)")]
+    QuotaExceeded {
+        bucket: String,
+        limit_usd: f64,
+        spent_usd: f64,
+    },
     #[error("schema mismatch: configured schema does not match requested type")]
     SchemaMismatch {
         expected: String,
         configured: String,
     },
+    #[cfg(feature = "store")]
+    #[error("session store error: {0}")]
+    Store(#[from] rusqlite::Error),
+    /// Structured output that could not be read as the requested type,
+    /// even after coercion.
+    #[cfg(feature = "schema")]
+    #[error("structured output does not fit the requested type: {source}")]
+    StructuredOutputRejected {
+        raw: serde_json::Value,
+        violations: Vec<crate::structured::Violation>,
+        source: serde_json::Error,
+    },
+    #[error("turn interrupted after {calls} tool calls (limit: {limit})")]
+    ToolCallLimitExceeded { limit: u32, calls: u32 },
+    #[error("turn interrupted after running longer than {limit:?}")]
+    TurnDurationExceeded { limit: std::time::Duration },
+    #[error("turn aborted after tool '{tool}' failed {failures} times in a row")]
+    ToolRecoveryAborted { tool: String, failures: u32 },
+    #[error("turn interrupted by guardrail '{guardrail}'")]
+    GuardrailTripped { guardrail: String },
     #[error("timeout: {0}")]
     Timeout(String),
 }
+
+/// Errors detected while validating [`Options`](crate::Options) before a client is created.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    #[error("MCP server '{server}' registers tool '{tool}' more than once")]
+    DuplicateTool { server: String, tool: String },
+    #[error("MCP tools '{first}' and '{second}' both resolve to '{qualified}'")]
+    ToolNameConflict {
+        qualified: String,
+        first: String,
+        second: String,
+    },
+    #[error("MCP server '{server}' tool '{tool}' conflicts with allowed built-in tool '{tool}'")]
+    BuiltinToolConflict { server: String, tool: String },
+    #[error("invalid tool selector '{selector}': {reason}")]
+    InvalidToolSelector { selector: String, reason: String },
+    #[error("agent '{name}' is registered more than once")]
+    DuplicateAgent { name: String },
+    #[error("agent '{agent}' uses template variable '{variable}', which has no value")]
+    UndefinedAgentVariable { agent: String, variable: String },
+    #[error("agent '{agent}' uses tool '{tool}', which is not in the allowed tools")]
+    AgentToolNotAllowed { agent: String, tool: String },
+    #[error("invalid budget: {reason}")]
+    InvalidBudget { reason: String },
+    #[error("hook name '{name}' is registered more than once for {event}")]
+    DuplicateHookName { event: String, name: String },
+    #[error(
+        "permission mode bypassPermissions requires Options::bypass_permissions with an acknowledgement"
+    )]
+    UnacknowledgedBypass,
+    #[error("thinking budget of {budget} tokens is outside {min}..={max} for model '{model}'")]
+    InvalidThinkingBudget {
+        model: String,
+        budget: u32,
+        min: u32,
+        max: u32,
+    },
+    #[error("invalid MCP server name '{name}': {reason}")]
+    InvalidMcpServerName { name: String, reason: String },
+    #[error("unusable structured output schema: {reason}")]
+    InvalidJsonSchema { reason: String },
+    #[error("invalid raw CLI flag '{flag}': {reason}")]
+    InvalidRawFlag { flag: String, reason: String },
+    #[error("invalid process limits: {reason}")]
+    InvalidProcessLimits { reason: String },
+    #[error("invalid limits: {reason}")]
+    InvalidLimits { reason: String },
+    #[error("invalid proxy URL '{url}': {reason}")]
+    InvalidProxyUrl { url: String, reason: String },
+    #[error("certificate file not found: {}", path.display())]
+    CertificateNotFound { path: std::path::PathBuf },
+    #[error("no credentials for {auth} authentication: {reason}")]
+    MissingCredentials { auth: String, reason: String },
+    #[error("{provider} provider is not usable: {reason}")]
+    ProviderCredentials { provider: String, reason: String },
+    #[error("per-turn permission profiles require a client created with Options::profile")]
+    ProfileRequired,
+    #[error("invalid policy: {reason}")]
+    InvalidPolicy { reason: String },
+    #[cfg(feature = "webhooks")]
+    #[error("invalid webhook URL '{url}': {reason}")]
+    InvalidWebhookUrl { url: String, reason: String },
+}
@@ -0,0 +1,435 @@
+//! Scoring responses against rubrics with a second model pass.
+//!
+//! A [`Judge`] asks a model to grade a response against each criterion of a
+//! [`Rubric`] on a 1–5 scale, returning a typed [`Verdict`]. Pairwise
+//! [`compare`](Judge::compare) asks which of two responses is better and,
+//! by default, asks again with the order swapped to cancel out position
+//! bias. Batches run through [`ExtractAll`], a few sessions at a time.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::eval::{Judge, Rubric};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let rubric = Rubric::new()
+//!     .criterion("correctness", "The answer is factually correct.")
+//!     .weighted("concision", "The answer has no filler.", 0.5);
+//!
+//! let verdict = Judge::new()
+//!     .score("What is the capital of France?", "Paris.", &rubric)
+//!     .await?;
+//! assert!(verdict.overall() > 0.8);
+//! # Ok(())
+//! # }
+//! ```
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::options::Options;
+use crate::recipes::ExtractAll;
+
+const SYSTEM_PROMPT: &str = "You are an impartial evaluator. Judge only what is asked, against \
+     the criteria given, and do not let the length or confidence of a response sway you. Do not \
+     use tools.";
+
+/// The highest score a criterion can receive.
+pub const MAX_SCORE: u8 = 5;
+
+/// One thing a response is judged on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Criterion {
+    name: String,
+    description: String,
+    weight: f64,
+}
+
+impl Criterion {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+}
+
+/// The criteria a [`Judge`] grades against.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rubric {
+    criteria: Vec<Criterion>,
+}
+
+impl Rubric {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a criterion with weight 1.
+    #[must_use]
+    pub fn criterion(self, name: impl Into<String>, description: impl Into<String>) -> Self {
+        self.weighted(name, description, 1.0)
+    }
+
+    /// Adds a criterion counting `weight` times towards the overall score.
+    #[must_use]
+    pub fn weighted(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        weight: f64,
+    ) -> Self {
+        self.criteria.push(Criterion {
+            name: name.into(),
+            description: description.into(),
+            weight,
+        });
+        self
+    }
+
+    pub fn criteria(&self) -> &[Criterion] {
+        &self.criteria
+    }
+
+    fn describe(&self) -> String {
+        let mut text = String::new();
+        for criterion in &self.criteria {
+            text.push_str(&format!(
+                "- {}: {}\n",
+                criterion.name, criterion.description
+            ));
+        }
+        text
+    }
+}
+
+/// A criterion's grade.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Score {
+    /// The criterion's name, exactly as given.
+    criterion: String,
+    /// From 1 (fails the criterion) to 5 (fully meets it).
+    score: u8,
+    /// One or two sentences justifying the score.
+    reasoning: String,
+}
+
+impl Score {
+    pub fn criterion(&self) -> &str {
+        &self.criterion
+    }
+
+    pub fn score(&self) -> u8 {
+        self.score
+    }
+
+    pub fn reasoning(&self) -> &str {
+        &self.reasoning
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct VerdictOutput {
+    scores: Vec<Score>,
+    /// Overall assessment in a few sentences.
+    summary: String,
+}
+
+/// A graded response.
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    scores: Vec<Score>,
+    summary: String,
+    overall: f64,
+}
+
+impl Verdict {
+    fn new(output: VerdictOutput, rubric: &Rubric) -> Self {
+        let (total, weights) = rubric
+            .criteria
+            .iter()
+            .filter_map(|criterion| {
+                let score = output
+                    .scores
+                    .iter()
+                    .find(|score| score.criterion == criterion.name)?;
+                let normalised =
+                    f64::from(score.score.clamp(1, MAX_SCORE) - 1) / f64::from(MAX_SCORE - 1);
+                Some((normalised * criterion.weight, criterion.weight))
+            })
+            .fold((0.0, 0.0), |(total, weights), (score, weight)| {
+                (total + score, weights + weight)
+            });
+        Self {
+            scores: output.scores,
+            summary: output.summary,
+            overall: if weights > 0.0 { total / weights } else { 0.0 },
+        }
+    }
+
+    pub fn scores(&self) -> &[Score] {
+        &self.scores
+    }
+
+    /// The grade for the criterion `name`.
+    pub fn score(&self, name: &str) -> Option<u8> {
+        self.scores
+            .iter()
+            .find(|score| score.criterion == name)
+            .map(Score::score)
+    }
+
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+
+    /// The weighted mean of the rubric's scores, from 0.0 (all 1s) to 1.0
+    /// (all 5s). Criteria the judge skipped are left out.
+    pub fn overall(&self) -> f64 {
+        self.overall
+    }
+}
+
+/// Which of two responses a comparison preferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Winner {
+    A,
+    B,
+    Tie,
+}
+
+impl Winner {
+    fn swapped(self) -> Self {
+        match self {
+            Self::A => Self::B,
+            Self::B => Self::A,
+            Self::Tie => Self::Tie,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ComparisonOutput {
+    winner: Winner,
+    /// Why the winner is better, or why they are equally good.
+    reasoning: String,
+}
+
+/// The outcome of a pairwise comparison.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    winner: Winner,
+    reasoning: Vec<String>,
+    consistent: bool,
+}
+
+impl Comparison {
+    pub fn winner(&self) -> Winner {
+        self.winner
+    }
+
+    /// The judge's reasoning, once per pass.
+    pub fn reasoning(&self) -> &[String] {
+        &self.reasoning
+    }
+
+    /// Whether both passes agreed. Disagreeing passes count as a tie.
+    pub fn is_consistent(&self) -> bool {
+        self.consistent
+    }
+}
+
+/// A prompt and the response to grade.
+#[derive(Debug, Clone)]
+pub struct Case {
+    prompt: String,
+    response: String,
+}
+
+impl Case {
+    pub fn new(prompt: impl Into<String>, response: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            response: response.into(),
+        }
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn response(&self) -> &str {
+        &self.response
+    }
+}
+
+/// Grades responses with a model.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Judge {
+    options: Options,
+    swap_check: bool,
+    concurrency: usize,
+}
+
+impl Judge {
+    pub fn new() -> Self {
+        Self::with_options(Options::new())
+    }
+
+    /// Runs the judge with `options`, such as a stronger model than the one
+    /// being evaluated. The judge adds its own schema and system prompt.
+    pub fn with_options(options: Options) -> Self {
+        Self {
+            options: options.read_only().append_system_prompt(SYSTEM_PROMPT),
+            swap_check: true,
+            concurrency: ExtractAll::DEFAULT_CONCURRENCY,
+        }
+    }
+
+    /// Whether [`compare`](Self::compare) asks a second time with the
+    /// responses swapped. On by default.
+    pub fn swap_check(mut self, enabled: bool) -> Self {
+        self.swap_check = enabled;
+        self
+    }
+
+    /// How many grading sessions a batch runs at once.
+    pub fn concurrency(mut self, sessions: usize) -> Self {
+        self.concurrency = sessions;
+        self
+    }
+
+    /// Grades `response` to `prompt` against `rubric`.
+    pub async fn score(
+        &self,
+        prompt: &str,
+        response: &str,
+        rubric: &Rubric,
+    ) -> Result<Verdict, Error> {
+        let client = Client::new(self.options.clone().with_json_schema::<VerdictOutput>()).await?;
+        let (output, _) = client
+            .query_once_as::<VerdictOutput>(&score_prompt(prompt, response, rubric))
+            .await?;
+        Ok(Verdict::new(output, rubric))
+    }
+
+    /// Grades every case against `rubric`, returning results in input order.
+    pub async fn score_all(
+        &self,
+        cases: impl IntoIterator<Item = Case>,
+        rubric: &Rubric,
+    ) -> Vec<Result<Verdict, Error>> {
+        ExtractAll::new(self.options.clone())
+            .concurrency(self.concurrency)
+            .run::<VerdictOutput, _, _>(cases, |case| {
+                score_prompt(&case.prompt, &case.response, rubric)
+            })
+            .await
+            .into_results()
+            .into_iter()
+            .map(|result| result.map(|output| Verdict::new(output, rubric)))
+            .collect()
+    }
+
+    /// Asks which of two responses to `prompt` better meets `rubric`.
+    pub async fn compare(
+        &self,
+        prompt: &str,
+        a: &str,
+        b: &str,
+        rubric: &Rubric,
+    ) -> Result<Comparison, Error> {
+        let first = self.compare_once(prompt, a, b, rubric).await?;
+        if !self.swap_check {
+            return Ok(Comparison {
+                winner: first.winner,
+                reasoning: vec![first.reasoning],
+                consistent: true,
+            });
+        }
+
+        let second = self.compare_once(prompt, b, a, rubric).await?;
+        let second_winner = second.winner.swapped();
+        let consistent = first.winner == second_winner;
+        Ok(Comparison {
+            winner: if consistent {
+                first.winner
+            } else {
+                Winner::Tie
+            },
+            reasoning: vec![first.reasoning, second.reasoning],
+            consistent,
+        })
+    }
+
+    async fn compare_once(
+        &self,
+        prompt: &str,
+        a: &str,
+        b: &str,
+        rubric: &Rubric,
+    ) -> Result<ComparisonOutput, Error> {
+        let client =
+            Client::new(self.options.clone().with_json_schema::<ComparisonOutput>()).await?;
+        let request = format!(
+            "Compare two responses to the same prompt against these criteria:\n{}\n\
+             <prompt>\n{prompt}\n</prompt>\n\n<response_a>\n{a}\n</response_a>\n\n\
+             <response_b>\n{b}\n</response_b>\n\nReport the better response as the winner, \
+             or a tie if neither is better.",
+            rubric.describe()
+        );
+        let (output, _) = client.query_once_as::<ComparisonOutput>(&request).await?;
+        Ok(output)
+    }
+}
+
+impl Default for Judge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn score_prompt(prompt: &str, response: &str, rubric: &Rubric) -> String {
+    format!(
+        "Grade the response to the prompt below against each of these criteria, scoring \
+         every criterion from 1 to {MAX_SCORE}:\n{}\n<prompt>\n{prompt}\n</prompt>\n\n\
+         <response>\n{response}\n</response>",
+        rubric.describe()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overall_is_weighted_and_normalised() {
+        let rubric =
+            Rubric::new()
+                .criterion("correct", "Is correct.")
+                .weighted("brief", "Is brief.", 3.0);
+        let output = serde_json::from_value::<VerdictOutput>(serde_json::json!({
+            "scores": [
+                {"criterion": "correct", "score": 5, "reasoning": "Right."},
+                {"criterion": "brief", "score": 1, "reasoning": "Rambles."},
+                {"criterion": "unknown", "score": 5, "reasoning": "Ignored."}
+            ],
+            "summary": "Correct but long."
+        }))
+        .unwrap();
+
+        let verdict = Verdict::new(output, &rubric);
+        assert_eq!(verdict.overall(), 0.25);
+        assert_eq!(verdict.score("brief"), Some(1));
+        assert_eq!(Winner::A.swapped(), Winner::B);
+    }
+}
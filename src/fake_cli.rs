@@ -0,0 +1,87 @@
+//! A stand-in for the claude CLI in tests, scripted in `sh`.
+//!
+//! The script's body runs after a prelude defining:
+//!
+//! - `next`: reads the next line from the client into `$line`, and the
+//!   request id it carries, if any, into `$id`; exits once stdin closes.
+//! - `reply JSON`: answers the request last read with a success response
+//!   carrying `JSON`.
+//! - `emit JSON`: writes a message as is.
+//! - `drain`: reads until stdin closes, so the client does not see the
+//!   stream end early.
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const PRELUDE: &str = r#"#!/bin/sh
+next() {
+    IFS= read -r line || exit 0
+    id=$(printf '%s\n' "$line" | sed -n 's/.*"request_id":"\([^"]*\)".*/\1/p')
+}
+reply() {
+    printf '{"type":"control_response","response":{"subtype":"success","request_id":"%s","response":%s}}\n' "$id" "$1"
+}
+emit() {
+    printf '%s\n' "$1"
+}
+drain() {
+    while IFS= read -r line; do :; done
+}
+"#;
+
+/// A fake CLI script, removed when dropped.
+pub(crate) struct FakeCli {
+    dir: PathBuf,
+}
+
+impl FakeCli {
+    pub(crate) fn new(body: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("clauders-fake-cli-{}", crate::util::uuid_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cli = Self { dir };
+        std::fs::write(cli.path(), format!("{PRELUDE}{body}\n")).unwrap();
+        std::fs::set_permissions(cli.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+        cli
+    }
+
+    /// Answers the initialize request with an empty response, then runs
+    /// `body`.
+    pub(crate) fn initialized(body: &str) -> Self {
+        Self::new(&format!("next; reply '{{}}'\n{body}"))
+    }
+
+    pub(crate) fn path(&self) -> PathBuf {
+        self.dir.join("claude")
+    }
+
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for FakeCli {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// An assistant message with `text`, as the CLI writes it.
+pub(crate) fn assistant_text(text: &str) -> String {
+    format!(
+        r#"{{"type":"assistant","message":{{"id":"msg_1","type":"message","role":"assistant","model":"claude-sonnet-4-5","content":[{{"type":"text","text":"{text}"}}],"stop_reason":null,"usage":{{"input_tokens":1,"output_tokens":1}}}},"parent_tool_use_id":null,"session_id":"s1"}}"#
+    )
+}
+
+/// An assistant message calling tool `name` with an empty input, as the CLI
+/// writes it.
+pub(crate) fn assistant_tool_use(id: &str, name: &str) -> String {
+    format!(
+        r#"{{"type":"assistant","message":{{"id":"msg_{id}","type":"message","role":"assistant","model":"claude-sonnet-4-5","content":[{{"type":"tool_use","id":"{id}","name":"{name}","input":{{}}}}],"stop_reason":null,"usage":{{"input_tokens":1,"output_tokens":1}}}},"parent_tool_use_id":null,"session_id":"s1"}}"#
+    )
+}
+
+/// A successful result ending a turn, as the CLI writes it.
+pub(crate) fn result() -> &'static str {
+    r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":1,"duration_api_ms":1,"num_turns":1,"result":"done","session_id":"s1","total_cost_usd":0.01,"usage":{"input_tokens":1,"output_tokens":1}}"#
+}
@@ -0,0 +1,397 @@
+//! Git safety helpers for agents that edit a repository.
+//!
+//! A [`Snapshot`] records the complete state of the working tree (including
+//! untracked, non-ignored files) without touching the index, `HEAD` or the
+//! stash. After a turn it can summarise what changed, and restore the
+//! recorded state if the turn failed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::git::Snapshot;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let client = Client::new(Options::new()).await?;
+//! let mut conv = client.conversation();
+//!
+//! let snapshot = Snapshot::capture(".").await?;
+//! let result = conv.turn("Fix the failing test").send().await;
+//!
+//! if snapshot.restore_if_failed(&result).await? {
+//!     eprintln!("turn failed, changes reverted");
+//! } else {
+//!     let responses = result?;
+//!     println!("{}", snapshot.diff().await?.touched_by(&responses));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::response::Responses;
+
+/// Tools whose input names the file they modify, and the input field holding it.
+const FILE_EDITING_TOOLS: &[(&str, &str)] = &[
+    ("Edit", "file_path"),
+    ("MultiEdit", "file_path"),
+    ("Write", "file_path"),
+    ("NotebookEdit", "notebook_path"),
+];
+
+/// The recorded state of a repository's working tree.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    root: PathBuf,
+    tree: String,
+}
+
+impl Snapshot {
+    /// Records the working tree of the repository containing `dir`.
+    pub async fn capture(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let root = git(dir.as_ref(), &["rev-parse", "--show-toplevel"], None).await?;
+        let root = PathBuf::from(root.trim());
+        let tree = write_tree(&root).await?;
+        Ok(Self { root, tree })
+    }
+
+    /// The repository's top-level directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The id of the git tree object holding the recorded state.
+    pub fn tree(&self) -> &str {
+        &self.tree
+    }
+
+    /// Compares the recorded state with the current working tree.
+    pub async fn diff(&self) -> Result<DiffSummary, Error> {
+        let current = write_tree(&self.root).await?;
+        diff_trees(&self.root, &self.tree, &current).await
+    }
+
+    /// Returns the working tree to the recorded state.
+    ///
+    /// Files created since the snapshot are deleted, and modified or deleted
+    /// files are rewritten. The index and `HEAD` are left untouched.
+    pub async fn restore(&self) -> Result<(), Error> {
+        let current = write_tree(&self.root).await?;
+        let added = diff_trees(&self.root, &self.tree, &current).await?;
+        for change in added
+            .changes()
+            .iter()
+            .filter(|c| c.kind == ChangeKind::Added)
+        {
+            let path = self.root.join(&change.path);
+            if let Err(e) = tokio::fs::remove_file(&path).await
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                return Err(e.into());
+            }
+        }
+
+        let index = TempIndex::new(&self.root).await?;
+        git(&self.root, &["read-tree", &self.tree], Some(index.path())).await?;
+        git(
+            &self.root,
+            &["checkout-index", "-a", "-f"],
+            Some(index.path()),
+        )
+        .await?;
+        tracing::info!(tree = %self.tree, "restored working tree from snapshot");
+        Ok(())
+    }
+
+    /// Restores the snapshot if the turn returned an error or its result
+    /// reported one. Returns whether the working tree was restored.
+    pub async fn restore_if_failed(
+        &self,
+        result: &Result<Responses, Error>,
+    ) -> Result<bool, Error> {
+        let failed = match result {
+            Err(_) => true,
+            Ok(responses) => responses.completion().is_none_or(|c| c.is_error()),
+        };
+        if failed {
+            self.restore().await?;
+        }
+        Ok(failed)
+    }
+}
+
+/// How a file changed between two states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl ChangeKind {
+    fn as_char(self) -> char {
+        match self {
+            Self::Added => 'A',
+            Self::Modified => 'M',
+            Self::Deleted => 'D',
+        }
+    }
+}
+
+/// A single changed file in a [`DiffSummary`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    path: PathBuf,
+    kind: ChangeKind,
+    additions: Option<u64>,
+    deletions: Option<u64>,
+}
+
+impl FileChange {
+    /// Path relative to the repository root.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    /// Lines added, or `None` for binary files.
+    pub fn additions(&self) -> Option<u64> {
+        self.additions
+    }
+
+    /// Lines removed, or `None` for binary files.
+    pub fn deletions(&self) -> Option<u64> {
+        self.deletions
+    }
+}
+
+/// The files changed since a [`Snapshot`] was taken.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    root: PathBuf,
+    changes: Vec<FileChange>,
+}
+
+impl DiffSummary {
+    pub fn changes(&self) -> &[FileChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Keeps only the files named in `Edit`, `MultiEdit`, `Write` or
+    /// `NotebookEdit` tool calls among `responses`.
+    pub fn touched_by(&self, responses: &Responses) -> Self {
+        let edited = responses
+            .tool_uses()
+            .filter_map(|tool_use| {
+                let (_, field) = FILE_EDITING_TOOLS
+                    .iter()
+                    .find(|(name, _)| *name == tool_use.name())?;
+                let path = Path::new(tool_use.input().get(*field)?.as_str()?);
+                Some(path.strip_prefix(&self.root).unwrap_or(path).to_path_buf())
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            root: self.root.clone(),
+            changes: self
+                .changes
+                .iter()
+                .filter(|change| edited.contains(&change.path))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl Display for DiffSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.changes.is_empty() {
+            return f.write_str("no changes");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} {}", change.kind.as_char(), change.path.display())?;
+            if let (Some(added), Some(deleted)) = (change.additions, change.deletions) {
+                write!(f, " (+{added} -{deleted})")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A throwaway index file, seeded from the repository's index so that
+/// unchanged files do not need to be rehashed.
+struct TempIndex(PathBuf);
+
+impl TempIndex {
+    async fn new(root: &Path) -> Result<Self, Error> {
+        let path = std::env::temp_dir().join(format!("clauders-index-{}", crate::util::uuid_v7()));
+        let index = git(root, &["rev-parse", "--git-path", "index"], None).await?;
+        let index = root.join(index.trim());
+        if tokio::fs::try_exists(&index).await? {
+            tokio::fs::copy(&index, &path).await?;
+        }
+        Ok(Self(path))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempIndex {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+async fn write_tree(root: &Path) -> Result<String, Error> {
+    let index = TempIndex::new(root).await?;
+    git(root, &["add", "--all"], Some(index.path())).await?;
+    let tree = git(root, &["write-tree"], Some(index.path())).await?;
+    Ok(tree.trim().to_owned())
+}
+
+/// Diffs two trees. Paths are read NUL-separated with `-z`, so they come
+/// back as is rather than C-quoted when they hold spaces, quotes or
+/// non-ASCII characters.
+async fn diff_trees(root: &Path, from: &str, to: &str) -> Result<DiffSummary, Error> {
+    let status = git(
+        root,
+        &["diff", "-z", "--no-renames", "--name-status", from, to],
+        None,
+    )
+    .await?;
+    let numstat = git(
+        root,
+        &["diff", "-z", "--no-renames", "--numstat", from, to],
+        None,
+    )
+    .await?;
+
+    let mut fields = status.split('\0');
+    let mut changes = Vec::new();
+    while let (Some(kind), Some(path)) = (fields.next(), fields.next()) {
+        let kind = match kind {
+            "A" => ChangeKind::Added,
+            "D" => ChangeKind::Deleted,
+            _ => ChangeKind::Modified,
+        };
+        changes.push(FileChange {
+            path: PathBuf::from(path),
+            kind,
+            additions: None,
+            deletions: None,
+        });
+    }
+
+    for record in numstat.split('\0') {
+        let mut parts = record.splitn(3, '\t');
+        let (Some(added), Some(deleted), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if let Some(change) = changes.iter_mut().find(|c| c.path == Path::new(path)) {
+            change.additions = added.parse().ok();
+            change.deletions = deleted.parse().ok();
+        }
+    }
+
+    Ok(DiffSummary {
+        root: root.to_path_buf(),
+        changes,
+    })
+}
+
+async fn git(dir: &Path, args: &[&str], index: Option<&Path>) -> Result<String, Error> {
+    let mut cmd = tokio::process::Command::new("git");
+    cmd.args(args).current_dir(dir).kill_on_drop(true);
+    if let Some(index) = index {
+        cmd.env("GIT_INDEX_FILE", index);
+    }
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| Error::ProcessError(format!("failed to run git: {e}")))?;
+    if !output.status.success() {
+        return Err(Error::ProcessError(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_diff_and_restore() {
+        let dir = std::env::temp_dir().join(format!("clauders-git-{}", crate::util::uuid_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "--quiet"], None).await.unwrap();
+        std::fs::write(dir.join("tracked.txt"), "one\n").unwrap();
+
+        let snapshot = Snapshot::capture(&dir).await.unwrap();
+        std::fs::write(dir.join("tracked.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(dir.join("new.txt"), "new\n").unwrap();
+
+        let diff = snapshot.diff().await.unwrap();
+        assert_eq!(diff.to_string(), "A new.txt (+1 -0)\nM tracked.txt (+1 -0)");
+
+        snapshot.restore().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("tracked.txt")).unwrap(),
+            "one\n"
+        );
+        assert!(!dir.join("new.txt").exists());
+        assert!(snapshot.diff().await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_removes_added_files_with_quoted_names() {
+        let dir = std::env::temp_dir().join(format!("clauders-git-{}", crate::util::uuid_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "--quiet"], None).await.unwrap();
+        git(&dir, &["config", "core.quotePath", "true"], None)
+            .await
+            .unwrap();
+
+        let snapshot = Snapshot::capture(&dir).await.unwrap();
+        std::fs::write(dir.join("with space.txt"), "a\n").unwrap();
+        std::fs::write(dir.join("caf\u{e9}.txt"), "b\n").unwrap();
+
+        let diff = snapshot.diff().await.unwrap();
+        assert_eq!(
+            diff.to_string(),
+            "A caf\u{e9}.txt (+1 -0)\nA with space.txt (+1 -0)"
+        );
+
+        snapshot.restore().await.unwrap();
+        assert!(!dir.join("with space.txt").exists());
+        assert!(!dir.join("caf\u{e9}.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,401 @@
+//! Content filters for Claude's output.
+//!
+//! [`Guardrails`] check each text response of a turn against validators:
+//! regular expressions, deny-listed topics, or async functions such as a call
+//! to a moderation service. A violation is flagged, redacted from the text the
+//! turn reports and records, or interrupts the turn, as its guardrail directs.
+//! Every violation is reported as a [`GuardrailEvent`] through
+//! [`TurnBuilder::on_guardrail`](crate::TurnBuilder::on_guardrail).
+//!
+//! Text is checked one response at a time, so a violation split across two
+//! responses is not caught.
+//!
+//! ```no_run
+//! use clauders::guardrails::{Guardrail, GuardrailAction, Guardrails};
+//! use clauders::{Client, Options};
+//! use regex::Regex;
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let client = Client::new(Options::new()).await?;
+//! let mut conv = client.conversation().with_guardrails(
+//!     Guardrails::new()
+//!         .guardrail(
+//!             Guardrail::regex("api-key", Regex::new(r"sk-[A-Za-z0-9]{20,}").unwrap())
+//!                 .action(GuardrailAction::Redact),
+//!         )
+//!         .guardrail(
+//!             Guardrail::topics("off-topic", ["medical advice"])
+//!                 .action(GuardrailAction::Interrupt),
+//!         ),
+//! );
+//!
+//! conv.turn("Summarise the deployment logs")
+//!     .on_guardrail(|event| eprintln!("[{}: {}]", event.guardrail(), event.matched()))
+//!     .send()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::ops::Range;
+use std::sync::Arc;
+
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use regex::Regex;
+
+/// Text that replaces redacted violations unless set with
+/// [`Guardrails::redaction`].
+pub const DEFAULT_REDACTION: &str = "[REDACTED]";
+
+/// Checks text, returning the byte ranges that violate it.
+pub type ValidatorCallback =
+    Arc<dyn Fn(String) -> BoxFuture<'static, Vec<Range<usize>>> + Send + Sync>;
+
+/// What a turn does with text that violates a guardrail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GuardrailAction {
+    /// Only reports the violation.
+    #[default]
+    Flag,
+    /// Replaces the violating text before it is reported or recorded.
+    Redact,
+    /// Interrupts the turn, which then fails with
+    /// [`Error::GuardrailTripped`](crate::Error::GuardrailTripped).
+    Interrupt,
+}
+
+#[derive(Clone)]
+enum Validator {
+    Pattern(Regex),
+    Custom(ValidatorCallback),
+}
+
+/// A named validator and the action taken on its violations.
+#[derive(Clone)]
+pub struct Guardrail {
+    name: String,
+    validator: Validator,
+    action: GuardrailAction,
+}
+
+impl std::fmt::Debug for Guardrail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let validator = match &self.validator {
+            Validator::Pattern(regex) => regex.as_str(),
+            Validator::Custom(_) => "<custom>",
+        };
+        f.debug_struct("Guardrail")
+            .field("name", &self.name)
+            .field("validator", &validator)
+            .field("action", &self.action)
+            .finish()
+    }
+}
+
+impl Guardrail {
+    /// Violated by every match of `regex`.
+    pub fn regex(name: impl Into<String>, regex: Regex) -> Self {
+        Self::with_validator(name, Validator::Pattern(regex))
+    }
+
+    /// Violated by any mention of `topics`, matched as whole words ignoring
+    /// case.
+    pub fn topics<I, S>(name: impl Into<String>, topics: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let alternatives = topics
+            .into_iter()
+            .map(|topic| regex::escape(topic.as_ref().trim()))
+            .filter(|topic| !topic.is_empty())
+            .collect::<Vec<_>>();
+        let pattern = if alternatives.is_empty() {
+            // Matches nothing.
+            r"[^\s\S]".to_owned()
+        } else {
+            format!(r"(?i)\b(?:{})\b", alternatives.join("|"))
+        };
+        let regex = Regex::new(&pattern).expect("escaped topics form a valid pattern");
+        Self::with_validator(name, Validator::Pattern(regex))
+    }
+
+    /// Violated by the byte ranges `validator` returns for the text. Ranges
+    /// outside the text or not on character boundaries are ignored.
+    pub fn custom<F, Fut>(name: impl Into<String>, validator: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Vec<Range<usize>>> + Send + 'static,
+    {
+        Self::with_validator(
+            name,
+            Validator::Custom(Arc::new(move |text| validator(text).boxed())),
+        )
+    }
+
+    fn with_validator(name: impl Into<String>, validator: Validator) -> Self {
+        Self {
+            name: name.into(),
+            validator,
+            action: GuardrailAction::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn action(mut self, action: GuardrailAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn violations(&self, text: &str) -> Vec<Range<usize>> {
+        match &self.validator {
+            Validator::Pattern(regex) => regex.find_iter(text).map(|m| m.range()).collect(),
+            Validator::Custom(validator) => validator(text.to_owned())
+                .await
+                .into_iter()
+                .filter(|range| !range.is_empty() && text.get(range.clone()).is_some())
+                .collect(),
+        }
+    }
+}
+
+/// A violation of a guardrail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuardrailEvent {
+    guardrail: String,
+    action: GuardrailAction,
+    span: Range<usize>,
+    matched: String,
+}
+
+impl GuardrailEvent {
+    /// The name of the guardrail violated.
+    pub fn guardrail(&self) -> &str {
+        &self.guardrail
+    }
+
+    pub fn action(&self) -> GuardrailAction {
+        self.action
+    }
+
+    /// The byte range of the violation in the text response, before any
+    /// redaction.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// The violating text.
+    pub fn matched(&self) -> &str {
+        &self.matched
+    }
+}
+
+/// The result of checking text against [`Guardrails`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GuardrailReport {
+    events: Vec<GuardrailEvent>,
+    redacted: Option<String>,
+}
+
+impl GuardrailReport {
+    /// The violations found, in the order of the guardrails and then of
+    /// their position in the text.
+    pub fn events(&self) -> &[GuardrailEvent] {
+        &self.events
+    }
+
+    /// The text with redacted violations replaced, if any were.
+    pub fn redacted(&self) -> Option<&str> {
+        self.redacted.as_deref()
+    }
+
+    pub(crate) fn into_redacted(self) -> Option<String> {
+        self.redacted
+    }
+
+    /// The first violation of a guardrail that interrupts the turn.
+    pub fn interrupting(&self) -> Option<&GuardrailEvent> {
+        self.events
+            .iter()
+            .find(|event| event.action == GuardrailAction::Interrupt)
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// A set of guardrails checked against each text response of a turn; see
+/// [`Conversation::with_guardrails`](crate::Conversation::with_guardrails).
+#[derive(Debug, Clone)]
+pub struct Guardrails {
+    guardrails: Vec<Guardrail>,
+    redaction: String,
+}
+
+impl Default for Guardrails {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Guardrails {
+    pub fn new() -> Self {
+        Self {
+            guardrails: Vec::new(),
+            redaction: DEFAULT_REDACTION.to_owned(),
+        }
+    }
+
+    #[must_use]
+    pub fn guardrail(mut self, guardrail: Guardrail) -> Self {
+        self.guardrails.push(guardrail);
+        self
+    }
+
+    /// Adds every guardrail of `guardrails`, such as those of a
+    /// [`PiiDetector`](crate::pii::PiiDetector).
+    #[must_use]
+    pub fn guardrails(mut self, guardrails: impl IntoIterator<Item = Guardrail>) -> Self {
+        self.guardrails.extend(guardrails);
+        self
+    }
+
+    /// Sets the text that replaces redacted violations.
+    #[must_use]
+    pub fn redaction(mut self, redaction: impl Into<String>) -> Self {
+        self.redaction = redaction.into();
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guardrails.is_empty()
+    }
+
+    /// Checks `text` against every guardrail.
+    pub async fn check(&self, text: &str) -> GuardrailReport {
+        let mut events = Vec::new();
+        for guardrail in &self.guardrails {
+            for span in guardrail.violations(text).await {
+                events.push(GuardrailEvent {
+                    guardrail: guardrail.name.clone(),
+                    action: guardrail.action,
+                    matched: text[span.clone()].to_owned(),
+                    span,
+                });
+            }
+        }
+        let redacted = redact(
+            text,
+            events
+                .iter()
+                .filter(|event| event.action == GuardrailAction::Redact)
+                .map(|event| event.span.clone()),
+            &self.redaction,
+        );
+        GuardrailReport { events, redacted }
+    }
+}
+
+/// Replaces `spans` of `text`, merging those that overlap. `None` if there
+/// is nothing to replace.
+fn redact(
+    text: &str,
+    spans: impl Iterator<Item = Range<usize>>,
+    redaction: &str,
+) -> Option<String> {
+    let mut spans = spans.collect::<Vec<_>>();
+    if spans.is_empty() {
+        return None;
+    }
+    spans.sort_by_key(|span| span.start);
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut copied = None;
+    for span in spans {
+        match copied {
+            Some(end) if span.start <= end => {}
+            _ => {
+                redacted.push_str(&text[copied.unwrap_or(0)..span.start]);
+                redacted.push_str(redaction);
+            }
+        }
+        copied = Some(copied.map_or(span.end, |end: usize| end.max(span.end)));
+    }
+    let copied = copied.unwrap_or(0);
+    redacted.push_str(&text[copied..]);
+    Some(redacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_guardrails_flag_redact_and_interrupt() {
+        let guardrails = Guardrails::new()
+            .guardrail(
+                Guardrail::regex("key", Regex::new(r"sk-\w+").unwrap())
+                    .action(GuardrailAction::Redact),
+            )
+            .guardrail(Guardrail::topics("topics", ["Crypto Trading", ""]))
+            .guardrail(
+                Guardrail::custom("custom", |text: String| async move {
+                    text.find("rm -rf")
+                        .map(|start| vec![start..start + 6, 0..9999])
+                        .unwrap_or_default()
+                })
+                .action(GuardrailAction::Interrupt),
+            );
+
+        let report = guardrails
+            .check("Use sk-abc or sk-def for crypto trading")
+            .await;
+        assert_eq!(
+            report.redacted(),
+            Some("Use [REDACTED] or [REDACTED] for crypto trading")
+        );
+        let matched = report
+            .events()
+            .iter()
+            .map(|e| (e.guardrail(), e.matched()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            matched,
+            [
+                ("key", "sk-abc"),
+                ("key", "sk-def"),
+                ("topics", "crypto trading")
+            ]
+        );
+        assert!(report.interrupting().is_none());
+
+        let report = guardrails.check("then rm -rf / and cryptotrading").await;
+        assert_eq!(report.events().len(), 1);
+        assert_eq!(report.interrupting().unwrap().span(), 5..11);
+        assert_eq!(report.redacted(), None);
+
+        assert!(guardrails.check("all good").await.is_clean());
+        assert!(
+            Guardrail::topics("none", Vec::<&str>::new())
+                .violations("anything")
+                .await
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_redact_merges_overlapping_spans() {
+        assert_eq!(
+            redact("abcdefgh", [0..2, 1..4, 6..8].into_iter(), "*"),
+            Some("*ef*".to_owned())
+        );
+        assert_eq!(redact("abc", std::iter::empty(), "*"), None);
+    }
+}
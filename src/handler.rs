@@ -1,8 +1,9 @@
 use async_trait::async_trait;
 
 use crate::response::{
-    CompleteResponse, ErrorResponse, HookLifecycleResponse, InitResponse, RateLimitResponse,
-    Response, TextResponse, ThinkingResponse, ToolResultResponse, ToolUseResponse,
+    CompleteResponse, ErrorResponse, HookLifecycleResponse, InitResponse, PartialResponse,
+    RateLimitResponse, Response, TextResponse, ThinkingResponse, ToolResultResponse,
+    ToolUseResponse,
 };
 
 #[async_trait]
@@ -16,11 +17,130 @@ pub trait Handler: Send + Sync {
     async fn on_rate_limit(&self, _rate_limit: &RateLimitResponse) {}
     async fn on_hook_started(&self, _hook: &HookLifecycleResponse) {}
     async fn on_hook_response(&self, _hook: &HookLifecycleResponse) {}
+    async fn on_partial(&self, _partial: &PartialResponse) {}
     async fn on_complete(&self, _complete: &CompleteResponse) {}
+
+    /// Combines this handler with `next`, which sees each response after it.
+    fn chain<H: Handler>(self, next: H) -> Chain<Self, H>
+    where
+        Self: Sized,
+    {
+        Chain(self, next)
+    }
+}
+
+/// Two handlers run one after the other, created by [`Handler::chain`].
+#[derive(Debug, Clone)]
+pub struct Chain<A, B>(A, B);
+
+#[async_trait]
+impl<A: Handler, B: Handler> Handler for Chain<A, B> {
+    async fn on_text(&self, text: &TextResponse) {
+        self.0.on_text(text).await;
+        self.1.on_text(text).await;
+    }
+
+    async fn on_tool_use(&self, tool_use: &ToolUseResponse) {
+        self.0.on_tool_use(tool_use).await;
+        self.1.on_tool_use(tool_use).await;
+    }
+
+    async fn on_tool_result(&self, tool_result: &ToolResultResponse) {
+        self.0.on_tool_result(tool_result).await;
+        self.1.on_tool_result(tool_result).await;
+    }
+
+    async fn on_thinking(&self, thinking: &ThinkingResponse) {
+        self.0.on_thinking(thinking).await;
+        self.1.on_thinking(thinking).await;
+    }
+
+    async fn on_init(&self, init: &InitResponse) {
+        self.0.on_init(init).await;
+        self.1.on_init(init).await;
+    }
+
+    async fn on_error(&self, error: &ErrorResponse) {
+        self.0.on_error(error).await;
+        self.1.on_error(error).await;
+    }
+
+    async fn on_rate_limit(&self, rate_limit: &RateLimitResponse) {
+        self.0.on_rate_limit(rate_limit).await;
+        self.1.on_rate_limit(rate_limit).await;
+    }
+
+    async fn on_hook_started(&self, hook: &HookLifecycleResponse) {
+        self.0.on_hook_started(hook).await;
+        self.1.on_hook_started(hook).await;
+    }
+
+    async fn on_hook_response(&self, hook: &HookLifecycleResponse) {
+        self.0.on_hook_response(hook).await;
+        self.1.on_hook_response(hook).await;
+    }
+
+    async fn on_partial(&self, partial: &PartialResponse) {
+        self.0.on_partial(partial).await;
+        self.1.on_partial(partial).await;
+    }
+
+    async fn on_complete(&self, complete: &CompleteResponse) {
+        self.0.on_complete(complete).await;
+        self.1.on_complete(complete).await;
+    }
 }
 
 pub struct DefaultHandler;
 
+/// What [`Client::dispatch_to`](crate::Client::dispatch_to) does when its
+/// queue of undispatched responses is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Stops reading from the CLI until the handler catches up.
+    #[default]
+    Block,
+    /// Drops [`Response::Partial`] deltas, blocking only for other responses.
+    DropDeltas,
+    /// Fails with [`Error::DispatchQueueFull`](crate::Error::DispatchQueueFull)
+    /// once the rest of the turn has been read and dropped.
+    Error,
+}
+
+/// Bounds the queue between the CLI reader and a slow [`Handler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DispatchQueue {
+    capacity: usize,
+    overflow: Overflow,
+}
+
+impl DispatchQueue {
+    /// Creates a queue holding up to `capacity` responses, at least one.
+    pub fn new(capacity: usize, overflow: Overflow) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            overflow,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+}
+
+impl Default for DispatchQueue {
+    fn default() -> Self {
+        Self::new(
+            crate::limits::Limits::DEFAULT_DISPATCH_QUEUE,
+            Overflow::Block,
+        )
+    }
+}
+
 #[async_trait]
 impl Handler for DefaultHandler {}
 
@@ -35,6 +155,7 @@ pub async fn dispatch<H: Handler + ?Sized>(handler: &H, response: &Response) {
         Response::RateLimit(r) => handler.on_rate_limit(r).await,
         Response::HookStarted(h) => handler.on_hook_started(h).await,
         Response::HookResponse(h) => handler.on_hook_response(h).await,
+        Response::Partial(p) => handler.on_partial(p).await,
         Response::Complete(c) => handler.on_complete(c).await,
     }
 }
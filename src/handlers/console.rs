@@ -0,0 +1,264 @@
+//! A handler printing sessions to a terminal.
+
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::handler::Handler;
+use crate::response::{
+    CompleteResponse, ErrorResponse, TextResponse, ToolResultResponse, ToolUseResponse,
+};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+
+/// Characters of a failed tool's output shown in its error line.
+const ERROR_PREVIEW_CHARS: usize = 80;
+
+/// Prints a turn to a terminal: the assistant's text as it arrives, a header
+/// for each tool call, diffs of the edits made by `Edit`, `MultiEdit` and
+/// `Write`, and a summary of the turn's duration, cost and token usage.
+///
+/// ```no_run
+/// use clauders::{Client, ConsoleReporter, Options};
+///
+/// # async fn example() -> Result<(), clauders::Error> {
+/// let client = Client::new(Options::new()).await?;
+/// client.query("Fix the typo in README.md").await?;
+/// client.dispatch_to(&ConsoleReporter::new()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ConsoleReporter {
+    out: Mutex<Box<dyn Write + Send>>,
+    colors: bool,
+}
+
+impl std::fmt::Debug for ConsoleReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConsoleReporter")
+            .field("colors", &self.colors)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ConsoleReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConsoleReporter {
+    /// Creates a reporter printing to stdout, with colors.
+    pub fn new() -> Self {
+        Self::with_writer(io::stdout())
+    }
+
+    /// Creates a reporter printing to `out`, with colors.
+    pub fn with_writer(out: impl Write + Send + 'static) -> Self {
+        Self {
+            out: Mutex::new(Box::new(out)),
+            colors: true,
+        }
+    }
+
+    /// Whether to style output with ANSI escapes. On by default.
+    #[must_use]
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.colors {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn write(&self, text: &str) {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        // Output is best effort; a closed terminal must not fail the turn.
+        let _ = out.write_all(text.as_bytes());
+        let _ = out.flush();
+    }
+
+    fn diff(&self, removed: &str, added: &str, output: &mut String) {
+        for line in removed.lines() {
+            output.push_str(&self.paint(RED, &format!("- {line}")));
+            output.push('\n');
+        }
+        for line in added.lines() {
+            output.push_str(&self.paint(GREEN, &format!("+ {line}")));
+            output.push('\n');
+        }
+    }
+}
+
+/// The most telling argument of a tool call, shown in its header.
+fn tool_subject(input: &Value) -> Option<&str> {
+    [
+        "file_path",
+        "notebook_path",
+        "command",
+        "pattern",
+        "url",
+        "query",
+        "path",
+    ]
+    .iter()
+    .find_map(|key| input.get(key).and_then(Value::as_str))
+}
+
+fn string<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+#[async_trait]
+impl Handler for ConsoleReporter {
+    async fn on_text(&self, text: &TextResponse) {
+        self.write(text.content());
+    }
+
+    async fn on_tool_use(&self, tool_use: &ToolUseResponse) {
+        let input = tool_use.input();
+        let mut output = format!("\n{}", self.paint(BOLD, &format!("[{}]", tool_use.name())));
+        if let Some(subject) = tool_subject(input) {
+            let subject = subject.lines().next().unwrap_or_default();
+            output.push(' ');
+            output.push_str(&self.paint(DIM, subject));
+        }
+        output.push('\n');
+
+        match tool_use.name() {
+            "Edit" => self.diff(
+                string(input, "old_string"),
+                string(input, "new_string"),
+                &mut output,
+            ),
+            "MultiEdit" => {
+                for edit in input
+                    .get("edits")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    self.diff(
+                        string(edit, "old_string"),
+                        string(edit, "new_string"),
+                        &mut output,
+                    );
+                }
+            }
+            "Write" => self.diff("", string(input, "content"), &mut output),
+            _ => {}
+        }
+        self.write(&output);
+    }
+
+    async fn on_tool_result(&self, tool_result: &ToolResultResponse) {
+        if !tool_result.is_error() {
+            return;
+        }
+        let text = tool_result.text().unwrap_or_default().replace('\n', " ");
+        let mut preview = text.chars().take(ERROR_PREVIEW_CHARS).collect::<String>();
+        if preview.len() < text.len() {
+            preview.push_str("...");
+        }
+        self.write(&format!(
+            "{}\n",
+            self.paint(RED, &format!("[error: {preview}]"))
+        ));
+    }
+
+    async fn on_error(&self, error: &ErrorResponse) {
+        self.write(&format!(
+            "\n{}\n",
+            self.paint(RED, &format!("Error: {}", error.message()))
+        ));
+    }
+
+    async fn on_complete(&self, complete: &CompleteResponse) {
+        let mut summary = format!(
+            "Completed in {:.2}s | {} turns",
+            complete.duration_ms() as f64 / 1000.0,
+            complete.num_turns()
+        );
+        if let Some(cost) = complete.total_cost_usd() {
+            summary.push_str(&format!(" | ${cost:.4}"));
+        }
+        if let Some(usage) = complete.usage() {
+            summary.push_str(&format!(
+                " | {} in / {} out tokens",
+                usage.input_tokens().unwrap_or_default(),
+                usage.output_tokens().unwrap_or_default()
+            ));
+        }
+        self.write(&format!("\n\n{}\n", self.paint(DIM, &summary)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::handler::dispatch;
+    use crate::response::Response;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_console_reporter_prints_diffs_and_errors() {
+        let buffer = Buffer::default();
+        let reporter = ConsoleReporter::with_writer(buffer.clone()).colors(false);
+
+        let edit = serde_json::from_value::<Response>(json!({
+            "type": "tool_use",
+            "id": "t1",
+            "name": "Edit",
+            "input": {"file_path": "src/lib.rs", "old_string": "a\nb", "new_string": "c"}
+        }))
+        .unwrap();
+        let failure = serde_json::from_value::<Response>(json!({
+            "type": "tool_result",
+            "tool_use_id": "t1",
+            "content": "no match\nfound",
+            "is_error": true
+        }))
+        .unwrap();
+        dispatch(&reporter, &edit).await;
+        dispatch(&reporter, &failure).await;
+
+        assert_eq!(
+            buffer.contents(),
+            "\n[Edit] src/lib.rs\n- a\n- b\n+ c\n[error: no match found]\n"
+        );
+    }
+}
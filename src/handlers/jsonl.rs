@@ -0,0 +1,183 @@
+//! A handler recording sessions as JSON lines.
+
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde_json::{Value, json};
+
+use crate::handler::Handler;
+use crate::response::{
+    CompleteResponse, ErrorResponse, HookLifecycleResponse, InitResponse, PartialResponse,
+    RateLimitResponse, Response, TextResponse, ThinkingResponse, ToolResultResponse,
+    ToolUseResponse,
+};
+
+/// Appends every response to a file as a JSON line, for observability
+/// pipelines that tail or ship such files.
+///
+/// Each line is the response's [`Response::to_json`] event with two more
+/// fields: `at_ms`, the time in milliseconds since the Unix epoch, and
+/// `session_id`, which is `null` until the session's init message is seen.
+///
+/// ```text
+/// {"at_ms":1760612345678,"session_id":"9f2c...","type":"text","content":"Hi",...}
+/// ```
+///
+/// Chain it with another handler to both show and record a session:
+///
+/// ```no_run
+/// use clauders::{Client, ConsoleReporter, Handler, JsonlSink, Options};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::new(Options::new()).await?;
+/// let handler = ConsoleReporter::new().chain(JsonlSink::new("events.jsonl")?);
+/// client.query("Hello").await?;
+/// client.dispatch_to(&handler).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct JsonlSink {
+    inner: Mutex<SinkState>,
+}
+
+#[derive(Debug)]
+struct SinkState {
+    file: LineWriter<File>,
+    session_id: Option<String>,
+    failed: bool,
+}
+
+impl JsonlSink {
+    /// Opens `path` for appending, creating it and its directory if needed.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = File::options().create(true).append(true).open(path)?;
+        Ok(Self {
+            inner: Mutex::new(SinkState {
+                file: LineWriter::new(file),
+                session_id: None,
+                failed: false,
+            }),
+        })
+    }
+
+    /// Appends `response`. Failures are reported once and never interrupt
+    /// the session.
+    fn record(&self, response: Response) {
+        let mut state = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        match &response {
+            Response::Init(init) => state.session_id = init.session_id().map(str::to_owned),
+            Response::Complete(complete) => {
+                state.session_id = Some(complete.session_id().to_owned());
+            }
+            _ => {}
+        }
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let mut entry = json!({ "at_ms": at_ms, "session_id": state.session_id });
+        if let (Some(entry), Value::Object(event)) = (entry.as_object_mut(), response.to_json()) {
+            entry.extend(event);
+        }
+        if let Err(e) = writeln!(state.file, "{entry}") {
+            if !state.failed {
+                tracing::warn!(error = %e, "failed to write event log");
+            }
+            state.failed = true;
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for JsonlSink {
+    async fn on_text(&self, text: &TextResponse) {
+        self.record(Response::Text(text.clone()));
+    }
+
+    async fn on_tool_use(&self, tool_use: &ToolUseResponse) {
+        self.record(Response::ToolUse(tool_use.clone()));
+    }
+
+    async fn on_tool_result(&self, tool_result: &ToolResultResponse) {
+        self.record(Response::ToolResult(tool_result.clone()));
+    }
+
+    async fn on_thinking(&self, thinking: &ThinkingResponse) {
+        self.record(Response::Thinking(thinking.clone()));
+    }
+
+    async fn on_init(&self, init: &InitResponse) {
+        self.record(Response::Init(init.clone()));
+    }
+
+    async fn on_error(&self, error: &ErrorResponse) {
+        self.record(Response::Error(error.clone()));
+    }
+
+    async fn on_rate_limit(&self, rate_limit: &RateLimitResponse) {
+        self.record(Response::RateLimit(rate_limit.clone()));
+    }
+
+    async fn on_hook_started(&self, hook: &HookLifecycleResponse) {
+        self.record(Response::HookStarted(hook.clone()));
+    }
+
+    async fn on_hook_response(&self, hook: &HookLifecycleResponse) {
+        self.record(Response::HookResponse(hook.clone()));
+    }
+
+    async fn on_partial(&self, partial: &PartialResponse) {
+        self.record(Response::Partial(partial.clone()));
+    }
+
+    async fn on_complete(&self, complete: &CompleteResponse) {
+        self.record(Response::Complete(complete.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::{DefaultHandler, dispatch};
+
+    #[tokio::test]
+    async fn test_jsonl_sink_records_events_with_session() {
+        let dir = std::env::temp_dir().join(format!("clauders-sink-{}", crate::util::uuid_v7()));
+        let path = dir.join("events.jsonl");
+        let handler = DefaultHandler.chain(JsonlSink::new(&path).unwrap());
+
+        for event in [
+            json!({"type": "text", "text": "early"}),
+            json!({"type": "init", "session_id": "s1"}),
+            json!({"type": "text", "text": "hello"}),
+        ] {
+            dispatch(
+                &handler,
+                &serde_json::from_value::<Response>(event).unwrap(),
+            )
+            .await;
+        }
+
+        let lines = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["session_id"], Value::Null);
+        assert_eq!(lines[1]["type"], "init");
+        assert_eq!(lines[2]["session_id"], "s1");
+        assert_eq!(lines[2]["content"], "hello");
+        assert!(lines[2]["at_ms"].as_u64().unwrap() > 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,14 @@
+//! Ready-made [`Handler`](crate::Handler)s.
+//!
+//! Handlers can be combined with [`Handler::chain`](crate::Handler::chain),
+//! such as to show a session on the terminal while recording it.
+
+pub mod console;
+pub mod jsonl;
+#[cfg(feature = "progress")]
+pub mod progress;
+
+pub use console::ConsoleReporter;
+pub use jsonl::JsonlSink;
+#[cfg(feature = "progress")]
+pub use progress::ProgressReporter;
@@ -0,0 +1,210 @@
+//! A live progress line for command-line tools.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::Value;
+
+use crate::handler::Handler;
+use crate::response::{CompleteResponse, PartialResponse, ToolUseResponse};
+
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shows a spinner for each turn with its tool calls, output tokens and the
+/// session's cost so far, and leaves a summary line when the turn completes.
+///
+/// Output tokens are counted live only when
+/// [`Options::include_partial_messages`](crate::Options::include_partial_messages)
+/// is enabled; otherwise they appear once the turn completes. Nothing is drawn
+/// when stderr is not a terminal.
+///
+/// ```no_run
+/// use clauders::handlers::ProgressReporter;
+/// use clauders::{Client, Options};
+///
+/// # async fn example() -> Result<(), clauders::Error> {
+/// let client = Client::new(Options::new().include_partial_messages(true)).await?;
+/// client.query("Run the tests").await?;
+/// client.dispatch_to(&ProgressReporter::new()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ProgressReporter {
+    bar: ProgressBar,
+    state: Mutex<Progress>,
+}
+
+#[derive(Debug, Default)]
+struct Progress {
+    running: bool,
+    tool_calls: u64,
+    /// Output tokens of the turn's finished messages.
+    settled_tokens: u64,
+    /// Output tokens of the message being streamed.
+    message_tokens: u64,
+    cost_usd: f64,
+}
+
+impl Progress {
+    fn message(&self) -> String {
+        format!(
+            "{} tool calls | {} tokens | ${:.4}",
+            self.tool_calls,
+            self.settled_tokens + self.message_tokens,
+            self.cost_usd
+        )
+    }
+}
+
+impl Default for ProgressReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressReporter {
+    /// Creates a reporter drawing to stderr.
+    pub fn new() -> Self {
+        Self::with_bar(ProgressBar::new_spinner())
+    }
+
+    /// Creates a reporter drawing with `bar`, such as one added to a
+    /// [`MultiProgress`](indicatif::MultiProgress). Its style is replaced.
+    pub fn with_bar(bar: ProgressBar) -> Self {
+        bar.set_style(
+            ProgressStyle::with_template("{spinner} [{elapsed}] {msg}")
+                .expect("progress template is valid"),
+        );
+        Self {
+            bar,
+            state: Mutex::new(Progress::default()),
+        }
+    }
+
+    /// Applies `update` to the progress, starting a new turn's spinner if
+    /// none is running.
+    fn update(&self, update: impl FnOnce(&mut Progress)) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if !state.running {
+            state.running = true;
+            state.tool_calls = 0;
+            state.settled_tokens = 0;
+            state.message_tokens = 0;
+            self.bar.reset();
+            self.bar.enable_steady_tick(TICK_INTERVAL);
+        }
+        update(&mut state);
+        self.bar.set_message(state.message());
+    }
+}
+
+#[async_trait]
+impl Handler for ProgressReporter {
+    async fn on_tool_use(&self, _tool_use: &ToolUseResponse) {
+        self.update(|progress| progress.tool_calls += 1);
+    }
+
+    async fn on_partial(&self, partial: &PartialResponse) {
+        let output_tokens = partial
+            .event()
+            .pointer("/usage/output_tokens")
+            .and_then(Value::as_u64);
+        self.update(|progress| match partial.event_type() {
+            Some("message_start") => {
+                progress.settled_tokens += progress.message_tokens;
+                progress.message_tokens = 0;
+            }
+            Some("message_delta") => {
+                if let Some(tokens) = output_tokens {
+                    progress.message_tokens = tokens;
+                }
+            }
+            _ => {}
+        });
+    }
+
+    async fn on_complete(&self, complete: &CompleteResponse) {
+        self.update(|progress| {
+            if let Some(tokens) = complete
+                .usage()
+                .and_then(|usage| usage.output_tokens())
+                .and_then(|tokens| u64::try_from(tokens).ok())
+            {
+                progress.settled_tokens = tokens;
+                progress.message_tokens = 0;
+            }
+            if let Some(cost) = complete.total_cost_usd() {
+                progress.cost_usd = cost;
+            }
+        });
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.running = false;
+        self.bar.println(format!(
+            "Completed in {:.2}s | {}",
+            complete.duration_ms() as f64 / 1000.0,
+            state.message()
+        ));
+        self.bar.finish_and_clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::handler::dispatch;
+    use crate::response::Response;
+
+    #[tokio::test]
+    async fn test_progress_reporter_counts_turn() {
+        let reporter = ProgressReporter::with_bar(ProgressBar::hidden());
+        for event in [
+            json!({"type": "tool_use", "id": "t1", "name": "Read", "input": {}}),
+            json!({"type": "partial", "event": {"type": "message_start"}}),
+            json!({"type": "partial", "event": {"type": "message_delta", "usage": {"output_tokens": 12}}}),
+            json!({"type": "partial", "event": {"type": "message_start"}}),
+            json!({"type": "partial", "event": {"type": "message_delta", "usage": {"output_tokens": 5}}}),
+            json!({"type": "tool_use", "id": "t2", "name": "Edit", "input": {}}),
+        ] {
+            dispatch(
+                &reporter,
+                &serde_json::from_value::<Response>(event).unwrap(),
+            )
+            .await;
+        }
+        assert_eq!(reporter.bar.message(), "2 tool calls | 17 tokens | $0.0000");
+
+        let complete = json!({
+            "type": "complete",
+            "subtype": "success",
+            "duration_ms": 1200,
+            "duration_api_ms": 1000,
+            "is_error": false,
+            "num_turns": 1,
+            "session_id": "s",
+            "total_cost_usd": 0.25,
+            "usage": {"input_tokens": 3, "output_tokens": 20}
+        });
+        dispatch(
+            &reporter,
+            &serde_json::from_value::<Response>(complete).unwrap(),
+        )
+        .await;
+        assert_eq!(reporter.bar.message(), "2 tool calls | 20 tokens | $0.2500");
+        assert!(reporter.bar.is_finished());
+
+        let next = json!({"type": "tool_use", "id": "t3", "name": "Read", "input": {}});
+        dispatch(
+            &reporter,
+            &serde_json::from_value::<Response>(next).unwrap(),
+        )
+        .await;
+        assert_eq!(reporter.bar.message(), "1 tool calls | 0 tokens | $0.2500");
+        assert!(!reporter.bar.is_finished());
+    }
+}
@@ -2,27 +2,187 @@ use std::fmt::{Debug, Display};
 use std::future::Future;
 use std::sync::Arc;
 
+use serde::Serialize;
+
+pub mod path_guard;
 pub mod post_tool_use;
 pub mod pre_tool_use;
+pub mod read_only;
+pub mod secrets;
 pub mod stop;
 pub mod user_prompt_submit;
 
+pub use path_guard::PathGuard;
 pub use post_tool_use::{
     PostToolUseCallback, PostToolUseDecision, PostToolUseInput, PostToolUseOutput,
 };
 pub use pre_tool_use::{PreToolUseCallback, PreToolUseDecision, PreToolUseInput, PreToolUseOutput};
+pub use read_only::ReadOnlyGuard;
+pub use secrets::{SecretFinding, SecretKind, SecretScanner};
 pub use stop::{StopCallback, StopDecision, StopInput, StopOutput};
 pub use user_prompt_submit::{
     UserPromptSubmitCallback, UserPromptSubmitDecision, UserPromptSubmitInput,
     UserPromptSubmitOutput,
 };
 
+/// The hook events SDK callbacks can be registered for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum HookEvent {
+    PreToolUse,
+    PostToolUse,
+    UserPromptSubmit,
+    Stop,
+}
+
+impl HookEvent {
+    /// The event name used on the wire.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PreToolUse => "PreToolUse",
+            Self::PostToolUse => "PostToolUse",
+            Self::UserPromptSubmit => "UserPromptSubmit",
+            Self::Stop => "Stop",
+        }
+    }
+
+    fn id_prefix(&self) -> &'static str {
+        match self {
+            Self::PreToolUse => "pre_tool_use",
+            Self::PostToolUse => "post_tool_use",
+            Self::UserPromptSubmit => "user_prompt_submit",
+            Self::Stop => "stop",
+        }
+    }
+
+    /// Builds the callback id sent to the CLI for a registered hook.
+    ///
+    /// Named hooks get an id derived from their name, so it stays the same
+    /// when other hooks are added or reordered. Unnamed hooks fall back to
+    /// their position among the hooks of the same event.
+    pub fn callback_id(&self, index: usize, name: Option<&str>) -> String {
+        match name {
+            Some(name) => format!("{}:{name}", self.id_prefix()),
+            None => format!("{}_{index}", self.id_prefix()),
+        }
+    }
+}
+
+impl Display for HookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A registered hook as it is sent to the CLI, from [`Hooks::describe`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HookDescription {
+    event: HookEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matcher: Option<String>,
+    callback_id: String,
+}
+
+impl HookDescription {
+    pub fn event(&self) -> HookEvent {
+        self.event
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The tool pattern the CLI matches calls against; `None` matches every
+    /// tool.
+    pub fn matcher(&self) -> Option<&str> {
+        self.matcher.as_deref()
+    }
+
+    /// The id the CLI calls the hook back with.
+    pub fn callback_id(&self) -> &str {
+        &self.callback_id
+    }
+}
+
+/// Called with every hook callback that failed.
+pub type HookErrorCallback = Arc<dyn Fn(&HookErrorEvent) + Send + Sync>;
+
+/// A hook callback that panicked or did not finish, from
+/// [`Hooks::on_error`]. The CLI is told the callback failed, and the session
+/// carries on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookErrorEvent {
+    event: HookEvent,
+    callback_id: String,
+    message: String,
+}
+
+impl HookErrorEvent {
+    pub(crate) fn new(
+        event: HookEvent,
+        callback_id: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            event,
+            callback_id: callback_id.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn event(&self) -> HookEvent {
+        self.event
+    }
+
+    /// The id the CLI called the hook back with.
+    pub fn callback_id(&self) -> &str {
+        &self.callback_id
+    }
+
+    /// Why the callback failed, with the panic message if it panicked.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// A callback registered for a hook event, with its optional name and tool matcher.
+#[derive(Clone)]
+pub struct HookRegistration<C> {
+    name: Option<String>,
+    matcher: Option<String>,
+    callback: C,
+}
+
+impl<C> HookRegistration<C> {
+    fn new(name: Option<String>, matcher: Option<String>, callback: C) -> Self {
+        Self {
+            name,
+            matcher,
+            callback,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn matcher(&self) -> Option<&str> {
+        self.matcher.as_deref()
+    }
+
+    pub fn callback(&self) -> &C {
+        &self.callback
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct Hooks {
-    pre_tool_use: Vec<(Option<String>, PreToolUseCallback)>,
-    post_tool_use: Vec<(Option<String>, PostToolUseCallback)>,
-    user_prompt_submit: Vec<UserPromptSubmitCallback>,
-    stop: Vec<StopCallback>,
+    pre_tool_use: Vec<HookRegistration<PreToolUseCallback>>,
+    post_tool_use: Vec<HookRegistration<PostToolUseCallback>>,
+    user_prompt_submit: Vec<HookRegistration<UserPromptSubmitCallback>>,
+    stop: Vec<HookRegistration<StopCallback>>,
+    on_error: Option<HookErrorCallback>,
 }
 
 impl Hooks {
@@ -38,9 +198,25 @@ impl Hooks {
         F: Fn(PreToolUseInput) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = PreToolUseOutput> + Send + 'static,
     {
-        let pattern = pattern.into().map(|s| s.to_string());
-        self.pre_tool_use
-            .push((pattern, Arc::new(move |input| Box::pin(callback(input)))));
+        self.add_pre_tool_use(pattern, callback);
+        self
+    }
+
+    /// Registers a named PreToolUse hook whose callback id is derived from `name`.
+    #[must_use]
+    pub fn on_pre_tool_use_named<P, S, F, Fut>(
+        mut self,
+        name: impl Into<String>,
+        pattern: P,
+        callback: F,
+    ) -> Self
+    where
+        P: Into<Option<S>>,
+        S: Display,
+        F: Fn(PreToolUseInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PreToolUseOutput> + Send + 'static,
+    {
+        self.add_pre_tool_use_named(name, pattern, callback);
         self
     }
 
@@ -52,9 +228,25 @@ impl Hooks {
         F: Fn(PostToolUseInput) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = PostToolUseOutput> + Send + 'static,
     {
-        let pattern = pattern.into().map(|s| s.to_string());
-        self.post_tool_use
-            .push((pattern, Arc::new(move |input| Box::pin(callback(input)))));
+        self.add_post_tool_use(pattern, callback);
+        self
+    }
+
+    /// Registers a named PostToolUse hook whose callback id is derived from `name`.
+    #[must_use]
+    pub fn on_post_tool_use_named<P, S, F, Fut>(
+        mut self,
+        name: impl Into<String>,
+        pattern: P,
+        callback: F,
+    ) -> Self
+    where
+        P: Into<Option<S>>,
+        S: Display,
+        F: Fn(PostToolUseInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PostToolUseOutput> + Send + 'static,
+    {
+        self.add_post_tool_use_named(name, pattern, callback);
         self
     }
 
@@ -64,8 +256,22 @@ impl Hooks {
         F: Fn(UserPromptSubmitInput) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = UserPromptSubmitOutput> + Send + 'static,
     {
-        self.user_prompt_submit
-            .push(Arc::new(move |input| Box::pin(callback(input))));
+        self.add_user_prompt_submit(callback);
+        self
+    }
+
+    /// Registers a named UserPromptSubmit hook whose callback id is derived from `name`.
+    #[must_use]
+    pub fn on_user_prompt_submit_named<F, Fut>(
+        mut self,
+        name: impl Into<String>,
+        callback: F,
+    ) -> Self
+    where
+        F: Fn(UserPromptSubmitInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = UserPromptSubmitOutput> + Send + 'static,
+    {
+        self.add_user_prompt_submit_named(name, callback);
         self
     }
 
@@ -75,8 +281,30 @@ impl Hooks {
         F: Fn(StopInput) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = StopOutput> + Send + 'static,
     {
-        self.stop
-            .push(Arc::new(move |input| Box::pin(callback(input))));
+        self.add_stop(callback);
+        self
+    }
+
+    /// Registers a named Stop hook whose callback id is derived from `name`.
+    #[must_use]
+    pub fn on_stop_named<F, Fut>(mut self, name: impl Into<String>, callback: F) -> Self
+    where
+        F: Fn(StopInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = StopOutput> + Send + 'static,
+    {
+        self.add_stop_named(name, callback);
+        self
+    }
+
+    /// Calls `callback` whenever a hook callback panics or does not finish.
+    /// The failure is also logged, and the CLI gets an error response for
+    /// the callback.
+    #[must_use]
+    pub fn on_error<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&HookErrorEvent) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(callback));
         self
     }
 
@@ -88,8 +316,30 @@ impl Hooks {
         Fut: Future<Output = PreToolUseOutput> + Send + 'static,
     {
         let pattern = pattern.into().map(|s| s.to_string());
-        self.pre_tool_use
-            .push((pattern, Arc::new(move |input| Box::pin(callback(input)))));
+        self.pre_tool_use.push(HookRegistration::new(
+            None,
+            pattern,
+            Arc::new(move |input| Box::pin(callback(input))),
+        ));
+    }
+
+    pub fn add_pre_tool_use_named<P, S, F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        pattern: P,
+        callback: F,
+    ) where
+        P: Into<Option<S>>,
+        S: Display,
+        F: Fn(PreToolUseInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PreToolUseOutput> + Send + 'static,
+    {
+        let pattern = pattern.into().map(|s| s.to_string());
+        self.pre_tool_use.push(HookRegistration::new(
+            Some(name.into()),
+            pattern,
+            Arc::new(move |input| Box::pin(callback(input))),
+        ));
     }
 
     pub fn add_post_tool_use<P, S, F, Fut>(&mut self, pattern: P, callback: F)
@@ -100,8 +350,30 @@ impl Hooks {
         Fut: Future<Output = PostToolUseOutput> + Send + 'static,
     {
         let pattern = pattern.into().map(|s| s.to_string());
-        self.post_tool_use
-            .push((pattern, Arc::new(move |input| Box::pin(callback(input)))));
+        self.post_tool_use.push(HookRegistration::new(
+            None,
+            pattern,
+            Arc::new(move |input| Box::pin(callback(input))),
+        ));
+    }
+
+    pub fn add_post_tool_use_named<P, S, F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        pattern: P,
+        callback: F,
+    ) where
+        P: Into<Option<S>>,
+        S: Display,
+        F: Fn(PostToolUseInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PostToolUseOutput> + Send + 'static,
+    {
+        let pattern = pattern.into().map(|s| s.to_string());
+        self.post_tool_use.push(HookRegistration::new(
+            Some(name.into()),
+            pattern,
+            Arc::new(move |input| Box::pin(callback(input))),
+        ));
     }
 
     pub fn add_user_prompt_submit<F, Fut>(&mut self, callback: F)
@@ -109,8 +381,23 @@ impl Hooks {
         F: Fn(UserPromptSubmitInput) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = UserPromptSubmitOutput> + Send + 'static,
     {
-        self.user_prompt_submit
-            .push(Arc::new(move |input| Box::pin(callback(input))));
+        self.user_prompt_submit.push(HookRegistration::new(
+            None,
+            None,
+            Arc::new(move |input| Box::pin(callback(input))),
+        ));
+    }
+
+    pub fn add_user_prompt_submit_named<F, Fut>(&mut self, name: impl Into<String>, callback: F)
+    where
+        F: Fn(UserPromptSubmitInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = UserPromptSubmitOutput> + Send + 'static,
+    {
+        self.user_prompt_submit.push(HookRegistration::new(
+            Some(name.into()),
+            None,
+            Arc::new(move |input| Box::pin(callback(input))),
+        ));
     }
 
     pub fn add_stop<F, Fut>(&mut self, callback: F)
@@ -118,51 +405,69 @@ impl Hooks {
         F: Fn(StopInput) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = StopOutput> + Send + 'static,
     {
-        self.stop
-            .push(Arc::new(move |input| Box::pin(callback(input))));
+        self.stop.push(HookRegistration::new(
+            None,
+            None,
+            Arc::new(move |input| Box::pin(callback(input))),
+        ));
+    }
+
+    pub fn add_stop_named<F, Fut>(&mut self, name: impl Into<String>, callback: F)
+    where
+        F: Fn(StopInput) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = StopOutput> + Send + 'static,
+    {
+        self.stop.push(HookRegistration::new(
+            Some(name.into()),
+            None,
+            Arc::new(move |input| Box::pin(callback(input))),
+        ));
     }
 
     pub fn user_prompt_submit_hooks(
         &self,
-    ) -> impl ExactSizeIterator<Item = &UserPromptSubmitCallback> {
+    ) -> impl ExactSizeIterator<Item = &HookRegistration<UserPromptSubmitCallback>> {
         self.user_prompt_submit.iter()
     }
 
-    pub fn get_user_prompt_submit_hook(&self, index: usize) -> Option<&UserPromptSubmitCallback> {
+    pub fn get_user_prompt_submit_hook(
+        &self,
+        index: usize,
+    ) -> Option<&HookRegistration<UserPromptSubmitCallback>> {
         self.user_prompt_submit.get(index)
     }
 
     pub fn post_tool_use_hooks(
         &self,
-    ) -> impl ExactSizeIterator<Item = &(Option<String>, PostToolUseCallback)> {
+    ) -> impl ExactSizeIterator<Item = &HookRegistration<PostToolUseCallback>> {
         self.post_tool_use.iter()
     }
 
     pub fn get_post_tool_use_hook(
         &self,
         index: usize,
-    ) -> Option<&(Option<String>, PostToolUseCallback)> {
+    ) -> Option<&HookRegistration<PostToolUseCallback>> {
         self.post_tool_use.get(index)
     }
 
     pub fn pre_tool_use_hooks(
         &self,
-    ) -> impl ExactSizeIterator<Item = &(Option<String>, PreToolUseCallback)> {
+    ) -> impl ExactSizeIterator<Item = &HookRegistration<PreToolUseCallback>> {
         self.pre_tool_use.iter()
     }
 
     pub fn get_pre_tool_use_hook(
         &self,
         index: usize,
-    ) -> Option<&(Option<String>, PreToolUseCallback)> {
+    ) -> Option<&HookRegistration<PreToolUseCallback>> {
         self.pre_tool_use.get(index)
     }
 
-    pub fn stop_hooks(&self) -> impl ExactSizeIterator<Item = &StopCallback> {
+    pub fn stop_hooks(&self) -> impl ExactSizeIterator<Item = &HookRegistration<StopCallback>> {
         self.stop.iter()
     }
 
-    pub fn get_stop_hook(&self, index: usize) -> Option<&StopCallback> {
+    pub fn get_stop_hook(&self, index: usize) -> Option<&HookRegistration<StopCallback>> {
         self.stop.get(index)
     }
 
@@ -181,6 +486,75 @@ impl Hooks {
     pub fn has_stop_hooks(&self) -> bool {
         !self.stop.is_empty()
     }
+
+    /// Returns the callback ids of all registered hooks, paired with their event
+    /// and position among the hooks of that event.
+    pub fn callback_ids(&self) -> Vec<(HookEvent, usize, String)> {
+        fn ids<C>(
+            event: HookEvent,
+            hooks: &[HookRegistration<C>],
+        ) -> impl Iterator<Item = (HookEvent, usize, String)> + '_ {
+            hooks
+                .iter()
+                .enumerate()
+                .map(move |(idx, h)| (event, idx, event.callback_id(idx, h.name())))
+        }
+
+        ids(HookEvent::PreToolUse, &self.pre_tool_use)
+            .chain(ids(HookEvent::PostToolUse, &self.post_tool_use))
+            .chain(ids(HookEvent::UserPromptSubmit, &self.user_prompt_submit))
+            .chain(ids(HookEvent::Stop, &self.stop))
+            .collect()
+    }
+
+    /// Describes every registered hook, in the order they are sent to the
+    /// CLI.
+    pub fn describe(&self) -> Vec<HookDescription> {
+        fn describe<C>(
+            event: HookEvent,
+            hooks: &[HookRegistration<C>],
+        ) -> impl Iterator<Item = HookDescription> + '_ {
+            hooks
+                .iter()
+                .enumerate()
+                .map(move |(idx, h)| HookDescription {
+                    event,
+                    name: h.name.clone(),
+                    matcher: h.matcher.clone(),
+                    callback_id: event.callback_id(idx, h.name()),
+                })
+        }
+
+        describe(HookEvent::PreToolUse, &self.pre_tool_use)
+            .chain(describe(HookEvent::PostToolUse, &self.post_tool_use))
+            .chain(describe(
+                HookEvent::UserPromptSubmit,
+                &self.user_prompt_submit,
+            ))
+            .chain(describe(HookEvent::Stop, &self.stop))
+            .collect()
+    }
+
+    pub(crate) fn error_callback(&self) -> Option<&HookErrorCallback> {
+        self.on_error.as_ref()
+    }
+
+    /// Returns the first hook name registered more than once for the same event.
+    pub(crate) fn duplicate_name(&self) -> Option<(HookEvent, String)> {
+        fn find<C>(event: HookEvent, hooks: &[HookRegistration<C>]) -> Option<(HookEvent, String)> {
+            let mut seen = std::collections::HashSet::new();
+            hooks
+                .iter()
+                .filter_map(HookRegistration::name)
+                .find(|name| !seen.insert(*name))
+                .map(|name| (event, name.to_owned()))
+        }
+
+        find(HookEvent::PreToolUse, &self.pre_tool_use)
+            .or_else(|| find(HookEvent::PostToolUse, &self.post_tool_use))
+            .or_else(|| find(HookEvent::UserPromptSubmit, &self.user_prompt_submit))
+            .or_else(|| find(HookEvent::Stop, &self.stop))
+    }
 }
 
 impl Debug for Hooks {
@@ -190,6 +564,7 @@ impl Debug for Hooks {
             .field("post_tool_use", &self.post_tool_use.len())
             .field("user_prompt_submit", &self.user_prompt_submit.len())
             .field("stop", &self.stop.len())
+            .field("on_error", &self.on_error.as_ref().map(|_| "<callback>"))
             .finish()
     }
 }
@@ -197,7 +572,9 @@ impl Debug for Hooks {
 impl From<PostToolUseCallback> for Hooks {
     fn from(callback: PostToolUseCallback) -> Self {
         let mut hooks = Self::new();
-        hooks.post_tool_use.push((None, callback));
+        hooks
+            .post_tool_use
+            .push(HookRegistration::new(None, None, callback));
         hooks
     }
 }
@@ -205,7 +582,9 @@ impl From<PostToolUseCallback> for Hooks {
 impl From<PreToolUseCallback> for Hooks {
     fn from(callback: PreToolUseCallback) -> Self {
         let mut hooks = Self::new();
-        hooks.pre_tool_use.push((None, callback));
+        hooks
+            .pre_tool_use
+            .push(HookRegistration::new(None, None, callback));
         hooks
     }
 }
@@ -213,7 +592,9 @@ impl From<PreToolUseCallback> for Hooks {
 impl From<UserPromptSubmitCallback> for Hooks {
     fn from(callback: UserPromptSubmitCallback) -> Self {
         let mut hooks = Self::new();
-        hooks.user_prompt_submit.push(callback);
+        hooks
+            .user_prompt_submit
+            .push(HookRegistration::new(None, None, callback));
         hooks
     }
 }
@@ -221,7 +602,7 @@ impl From<UserPromptSubmitCallback> for Hooks {
 impl From<StopCallback> for Hooks {
     fn from(callback: StopCallback) -> Self {
         let mut hooks = Self::new();
-        hooks.stop.push(callback);
+        hooks.stop.push(HookRegistration::new(None, None, callback));
         hooks
     }
 }
@@ -0,0 +1,194 @@
+//! A PreToolUse check confining file tools to a set of directories.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::tool::ToolInput;
+
+use super::PreToolUseOutput;
+
+/// Tools the guard inspects, and the input field naming the path they touch.
+const PATH_TOOLS: &[(&str, &str)] = &[
+    ("Read", "file_path"),
+    ("Write", "file_path"),
+    ("Edit", "file_path"),
+    ("MultiEdit", "file_path"),
+    ("NotebookEdit", "notebook_path"),
+    ("Glob", "path"),
+    ("Grep", "path"),
+];
+
+/// The hook matcher covering every tool the guard inspects.
+pub(crate) const PATH_GUARD_MATCHER: &str = "Read|Write|Edit|MultiEdit|NotebookEdit|Glob|Grep|Bash";
+
+/// Denies file operations that resolve outside a set of allowed roots.
+///
+/// Paths are resolved against the working directory one component at a
+/// time, following symlinks and `..` the way the OS would, so neither can be
+/// used to step outside a root. Paths that do not exist yet are checked
+/// through their nearest existing ancestor.
+///
+/// For `Bash`, every argument that looks like a path (contains `/`, starts
+/// with `~` or is `..`) is checked. This is a best-effort guard against
+/// accidents, not a sandbox: commands can still build paths at runtime.
+#[derive(Debug, Clone)]
+pub struct PathGuard {
+    roots: Vec<PathBuf>,
+    cwd: PathBuf,
+}
+
+impl PathGuard {
+    /// Creates a guard allowing `roots`, resolving relative paths against `cwd`.
+    pub fn new(roots: impl IntoIterator<Item = impl AsRef<Path>>, cwd: impl AsRef<Path>) -> Self {
+        let cwd = cwd.as_ref();
+        let roots = roots
+            .into_iter()
+            .map(|root| resolve(cwd, root.as_ref()).unwrap_or_else(|| cwd.join(root)))
+            .collect();
+        Self {
+            roots,
+            cwd: cwd.to_path_buf(),
+        }
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Checks a tool call, returning the reason it should be denied.
+    pub fn check(&self, tool_name: &str, input: &ToolInput) -> Result<(), String> {
+        if tool_name == "Bash" {
+            let command = input.get_string("command").unwrap_or_default();
+            return bash_paths(command).try_for_each(|path| self.check_path(&path));
+        }
+
+        let field = PATH_TOOLS
+            .iter()
+            .find(|(name, _)| *name == tool_name)
+            .map(|(_, field)| *field);
+        match field.and_then(|field| input.get_string(field)) {
+            Some(path) => self.check_path(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the hook output for a tool call: a denial naming the offending
+    /// path, or a pass-through that leaves the decision to other checks.
+    pub fn evaluate(&self, tool_name: &str, input: &ToolInput) -> PreToolUseOutput {
+        match self.check(tool_name, input) {
+            Ok(()) => PreToolUseOutput::new(),
+            Err(reason) => {
+                tracing::warn!(tool = %tool_name, %reason, "path guard denied tool call");
+                PreToolUseOutput::deny(reason)
+            }
+        }
+    }
+
+    fn check_path(&self, path: &str) -> Result<(), String> {
+        let expanded = expand_home(path);
+        let allowed = resolve(&self.cwd, &expanded)
+            .is_some_and(|resolved| self.roots.iter().any(|root| resolved.starts_with(root)));
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!("path '{path}' is outside the allowed directories"))
+        }
+    }
+}
+
+/// Resolves `path` against `base`, following symlinks in its existing prefix.
+///
+/// Returns `None` for a dangling symlink, whose target cannot be checked.
+fn resolve(base: &Path, path: &Path) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component.as_os_str()),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(part) => {
+                resolved.push(part);
+                match std::fs::canonicalize(&resolved) {
+                    Ok(canonical) => resolved = canonical,
+                    Err(_) if resolved.symlink_metadata().is_ok() => return None,
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+    Some(resolved)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Extracts the arguments of a shell command that look like paths.
+fn bash_paths(command: &str) -> impl Iterator<Item = String> + '_ {
+    command
+        .split(|c: char| c.is_whitespace() || matches!(c, ';' | '&' | '|' | '(' | ')'))
+        .map(|word| {
+            let word = strip_redirection(word);
+            let word = word.split_once('=').map_or(word, |(_, value)| value);
+            word.trim_matches(['"', '\'']).to_owned()
+        })
+        .filter(|word| {
+            !word.contains("://") && (word.contains('/') || word.starts_with('~') || word == "..")
+        })
+}
+
+/// Strips a leading redirection operator such as `>`, `2>>` or `<`.
+fn strip_redirection(word: &str) -> &str {
+    match word.find(['<', '>']) {
+        Some(pos) if word[..pos].bytes().all(|b| b.is_ascii_digit()) => {
+            word[pos..].trim_start_matches(['<', '>', '&'])
+        }
+        _ => word,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_path_guard_resolves_traversal_and_symlinks() {
+        let dir = std::env::temp_dir().join(format!("clauders-guard-{}", crate::util::uuid_v7()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join("outside")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("outside"), root.join("link")).unwrap();
+
+        let guard = PathGuard::new([&root], &root);
+        let read = |path: &str| ToolInput::new(json!({"file_path": path}));
+        let bash = |command: &str| ToolInput::new(json!({"command": command}));
+
+        assert!(guard.check("Read", &read("src/main.rs")).is_ok());
+        assert!(guard.check("Write", &read("src/new/file.rs")).is_ok());
+        assert!(guard.check("Read", &read("src/../../outside/x")).is_err());
+        assert!(guard.check("Read", &read("/etc/passwd")).is_err());
+        #[cfg(unix)]
+        assert!(guard.check("Edit", &read("link/secret")).is_err());
+        assert!(guard.check("WebFetch", &read("/etc/passwd")).is_ok());
+
+        assert!(guard.check("Bash", &bash("cargo test -p ./src")).is_ok());
+        assert!(guard.check("Bash", &bash("cat src/a && cat ../x")).is_err());
+        assert!(guard.check("Bash", &bash("echo hi >/tmp/out")).is_err());
+        assert!(
+            guard
+                .check("Bash", &bash("curl https://example.com/a"))
+                .is_ok()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use futures::future::BoxFuture;
 use serde_json::{Value, json};
 
-use crate::tool::ToolInput;
+use crate::tool::{ToolInput, ToolName};
 
 #[derive(Debug, Clone)]
 pub struct PostToolUseInput {
@@ -12,6 +12,7 @@ pub struct PostToolUseInput {
     tool_name: String,
     tool_input: ToolInput,
     tool_response: Value,
+    tool_use_id: Option<String>,
 }
 
 impl PostToolUseInput {
@@ -28,6 +29,7 @@ impl PostToolUseInput {
             tool_name: tool_name.into(),
             tool_input,
             tool_response,
+            tool_use_id: None,
         }
     }
 
@@ -43,6 +45,11 @@ impl PostToolUseInput {
         &self.tool_name
     }
 
+    /// The tool name split into its server and tool parts.
+    pub fn parsed_tool_name(&self) -> ToolName<'_> {
+        ToolName::parse(&self.tool_name)
+    }
+
     pub fn tool_input(&self) -> &ToolInput {
         &self.tool_input
     }
@@ -50,6 +57,20 @@ impl PostToolUseInput {
     pub fn tool_response(&self) -> &Value {
         &self.tool_response
     }
+
+    /// The id of the tool call, matching the
+    /// [`ToolUseResponse`](crate::ToolUseResponse) and
+    /// [`ToolResultResponse`](crate::ToolResultResponse) observed on the
+    /// response stream.
+    pub fn tool_use_id(&self) -> Option<&str> {
+        self.tool_use_id.as_deref()
+    }
+
+    #[must_use]
+    pub fn with_tool_use_id(mut self, tool_use_id: impl Into<String>) -> Self {
+        self.tool_use_id = Some(tool_use_id.into());
+        self
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -129,6 +150,10 @@ impl PostToolUseOutput {
         self
     }
 
+    /// The hook output the CLI expects. Additional context is sent as
+    /// `hookSpecificOutput.additionalContext`, which the CLI adds to the
+    /// conversation after the tool result so the model sees it on its next
+    /// turn.
     pub fn to_hook_response(&self) -> Value {
         let mut result = json!({});
 
@@ -146,7 +171,7 @@ impl PostToolUseOutput {
             "hookEventName": "PostToolUse"
         });
 
-        if let Some(context) = self.additional_context() {
+        if let Some(context) = self.additional_context().filter(|c| !c.is_empty()) {
             hook_specific["additionalContext"] = json!(context);
         }
 
@@ -157,3 +182,41 @@ impl PostToolUseOutput {
 
 pub type PostToolUseCallback =
     Arc<dyn Fn(PostToolUseInput) -> BoxFuture<'static, PostToolUseOutput> + Send + Sync>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_tool_use_output_hook_response() {
+        assert_eq!(
+            PostToolUseOutput::continue_with_context("The file is generated; edit the template.")
+                .to_hook_response(),
+            json!({
+                "hookSpecificOutput": {
+                    "hookEventName": "PostToolUse",
+                    "additionalContext": "The file is generated; edit the template.",
+                }
+            })
+        );
+        assert_eq!(
+            PostToolUseOutput::block("tests failed")
+                .with_additional_context("3 tests failed in src/lib.rs")
+                .to_hook_response(),
+            json!({
+                "decision": "block",
+                "reason": "tests failed",
+                "hookSpecificOutput": {
+                    "hookEventName": "PostToolUse",
+                    "additionalContext": "3 tests failed in src/lib.rs",
+                }
+            })
+        );
+        assert_eq!(
+            PostToolUseOutput::pass()
+                .with_additional_context("")
+                .to_hook_response(),
+            json!({"hookSpecificOutput": {"hookEventName": "PostToolUse"}})
+        );
+    }
+}
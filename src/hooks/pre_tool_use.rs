@@ -3,7 +3,7 @@ use std::sync::Arc;
 use futures::future::BoxFuture;
 use serde_json::{Value, json};
 
-use crate::tool::ToolInput;
+use crate::tool::{ToolInput, ToolName};
 
 #[derive(Debug, Clone)]
 pub struct PreToolUseInput {
@@ -11,6 +11,7 @@ pub struct PreToolUseInput {
     transcript_path: String,
     tool_name: String,
     tool_input: ToolInput,
+    tool_use_id: Option<String>,
 }
 
 impl PreToolUseInput {
@@ -25,6 +26,7 @@ impl PreToolUseInput {
             transcript_path: transcript_path.into(),
             tool_name: tool_name.into(),
             tool_input,
+            tool_use_id: None,
         }
     }
 
@@ -40,9 +42,28 @@ impl PreToolUseInput {
         &self.tool_name
     }
 
+    /// The tool name split into its server and tool parts.
+    pub fn parsed_tool_name(&self) -> ToolName<'_> {
+        ToolName::parse(&self.tool_name)
+    }
+
     pub fn tool_input(&self) -> &ToolInput {
         &self.tool_input
     }
+
+    /// The id of the tool call, matching the
+    /// [`ToolUseResponse`](crate::ToolUseResponse) and
+    /// [`ToolResultResponse`](crate::ToolResultResponse) observed on the
+    /// response stream.
+    pub fn tool_use_id(&self) -> Option<&str> {
+        self.tool_use_id.as_deref()
+    }
+
+    #[must_use]
+    pub fn with_tool_use_id(mut self, tool_use_id: impl Into<String>) -> Self {
+        self.tool_use_id = Some(tool_use_id.into());
+        self
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -0,0 +1,162 @@
+//! A PreToolUse check that keeps a session from changing the workspace.
+
+use crate::policy::BashPolicy;
+use crate::tool::ToolInput;
+
+use super::PreToolUseOutput;
+
+/// Built-in tools that modify files.
+pub(crate) const WRITE_TOOLS: &[&str] = &["Write", "Edit", "MultiEdit", "NotebookEdit"];
+
+/// The hook matcher covering every tool the guard inspects.
+pub(crate) const READ_ONLY_MATCHER: &str = "Write|Edit|MultiEdit|NotebookEdit|Bash";
+
+/// Shell commands that only read, with any arguments.
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "cat *",
+    "cut *",
+    "diff *",
+    "du *",
+    "echo *",
+    "file *",
+    "git blame *",
+    "git diff *",
+    "git grep *",
+    "git log *",
+    "git ls-files *",
+    "git rev-parse *",
+    "git show *",
+    "git status *",
+    "grep *",
+    "head *",
+    "ls *",
+    "pwd",
+    "rg *",
+    "sort *",
+    "stat *",
+    "tail *",
+    "tree *",
+    "uniq *",
+    "wc *",
+    "which *",
+];
+
+/// Denies file edits and shell commands that are not known to be read-only.
+///
+/// Bash commands must each match an allow list of reading commands (`cat`,
+/// `grep`, `git log`, ...) and may not redirect output. The list is
+/// deliberately short: anything not on it is denied, so the guard errs on
+/// the side of refusing harmless commands rather than allowing writes.
+#[derive(Debug, Clone)]
+pub struct ReadOnlyGuard {
+    bash: BashPolicy,
+}
+
+impl ReadOnlyGuard {
+    pub fn new() -> Self {
+        let bash = READ_ONLY_COMMANDS
+            .iter()
+            .fold(BashPolicy::new(), |policy, command| policy.allow(*command));
+        Self { bash }
+    }
+
+    /// Also allows shell commands matching `pattern`, such as `"cargo tree *"`.
+    #[must_use]
+    pub fn allow_command(mut self, pattern: impl Into<String>) -> Self {
+        self.bash = self.bash.allow(pattern);
+        self
+    }
+
+    /// Checks a tool call, returning the reason it should be denied.
+    pub fn check(&self, tool_name: &str, input: &ToolInput) -> Result<(), String> {
+        if WRITE_TOOLS.contains(&tool_name) {
+            return Err(format!("{tool_name} is not available in read-only mode"));
+        }
+        if tool_name != "Bash" {
+            return Ok(());
+        }
+
+        let command = input.get_string("command").unwrap_or_default();
+        // `git diff --output=<file>` and friends write despite the allow list.
+        if has_redirection(command) || command.contains("--output") {
+            return Err(format!(
+                "`{command}` writes its output to a file, which is not allowed in read-only mode"
+            ));
+        }
+        self.bash.check(command).map_err(|violations| {
+            let commands = violations
+                .iter()
+                .map(|violation| format!("`{}`", violation.command()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{commands} may modify the workspace, which is not allowed in read-only mode")
+        })
+    }
+
+    /// Returns the hook output for a tool call: a denial with the reason, or
+    /// a pass-through that leaves the decision to other checks.
+    pub fn evaluate(&self, tool_name: &str, input: &ToolInput) -> PreToolUseOutput {
+        match self.check(tool_name, input) {
+            Ok(()) => PreToolUseOutput::new(),
+            Err(reason) => {
+                tracing::warn!(tool = %tool_name, %reason, "read-only guard denied tool call");
+                PreToolUseOutput::deny(reason)
+            }
+        }
+    }
+}
+
+impl Default for ReadOnlyGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `command` contains an unquoted `>`.
+fn has_redirection(command: &str) -> bool {
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None | Some('"'), '\\') => {
+                chars.next();
+            }
+            (None, '>') => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn bash(command: &str) -> ToolInput {
+        ToolInput::new(json!({ "command": command }))
+    }
+
+    #[test]
+    fn test_read_only_guard() {
+        let guard = ReadOnlyGuard::new();
+        assert!(guard.check("Read", &ToolInput::new(json!({}))).is_ok());
+        assert!(guard.check("Edit", &ToolInput::new(json!({}))).is_err());
+
+        assert!(guard.check("Bash", &bash("git log -p | head -n 5")).is_ok());
+        assert!(guard.check("Bash", &bash("grep '->' src/lib.rs")).is_ok());
+        assert!(guard.check("Bash", &bash("cat a > b")).is_err());
+        assert!(guard.check("Bash", &bash("git diff --output=x")).is_err());
+        assert!(guard.check("Bash", &bash("ls && rm -rf target")).is_err());
+        assert!(guard.check("Bash", &bash("cargo tree")).is_err());
+        assert!(
+            guard
+                .allow_command("cargo tree *")
+                .check("Bash", &bash("cargo tree"))
+                .is_ok()
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use futures::future::BoxFuture;
 use serde_json::{Value, json};
@@ -64,6 +65,12 @@ impl StopOutput {
         }
     }
 
+    /// Keeps the agent working: the CLI does not stop, and gives
+    /// `instruction` to the model as what to do next.
+    pub fn continue_with(instruction: impl Into<String>) -> Self {
+        Self::block(instruction)
+    }
+
     pub fn decision(&self) -> Option<StopDecision> {
         self.decision
     }
@@ -72,6 +79,15 @@ impl StopOutput {
         self.reason.as_deref()
     }
 
+    /// The instruction the agent continues with, if this output keeps it
+    /// from stopping.
+    pub fn continuation(&self) -> Option<&str> {
+        match self.decision {
+            Some(StopDecision::Block) => self.reason(),
+            _ => None,
+        }
+    }
+
     pub fn set_decision(&mut self, decision: StopDecision) {
         self.decision = Some(decision);
     }
@@ -111,4 +127,93 @@ impl StopOutput {
     }
 }
 
+/// Bounds how many times in a row Stop hooks keep the agent working.
+#[derive(Debug)]
+pub(crate) struct ContinuationLimit {
+    limit: u32,
+    continuations: AtomicU32,
+    exceeded: AtomicBool,
+}
+
+impl ContinuationLimit {
+    pub(crate) fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            continuations: AtomicU32::new(0),
+            exceeded: AtomicBool::new(false),
+        }
+    }
+
+    /// Counts a continuation in `output`, letting the agent stop instead
+    /// once there were more than the limit in a row.
+    pub(crate) fn check(&self, stop_hook_active: bool, output: StopOutput) -> StopOutput {
+        // The CLI sets `stop_hook_active` while continuing because of a hook.
+        if !stop_hook_active {
+            self.continuations.store(0, Ordering::Relaxed);
+        }
+        if output.continuation().is_none()
+            || self.continuations.fetch_add(1, Ordering::Relaxed) < self.limit
+        {
+            return output;
+        }
+        tracing::warn!(
+            limit = self.limit,
+            "Stop hooks exceeded the continuation limit; letting the agent stop"
+        );
+        self.exceeded.store(true, Ordering::Relaxed);
+        StopOutput::pass()
+    }
+
+    /// Returns the limit if it was exceeded since the last call.
+    pub(crate) fn take_exceeded(&self) -> Option<u32> {
+        self.exceeded
+            .swap(false, Ordering::Relaxed)
+            .then_some(self.limit)
+    }
+}
+
 pub type StopCallback = Arc<dyn Fn(StopInput) -> BoxFuture<'static, StopOutput> + Send + Sync>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_output_continuation() {
+        let output = StopOutput::continue_with("Run the tests and fix any failures.");
+        assert_eq!(
+            output.continuation(),
+            Some("Run the tests and fix any failures.")
+        );
+        assert_eq!(
+            output.to_hook_response(),
+            json!({
+                "decision": "block",
+                "reason": "Run the tests and fix any failures.",
+                "hookSpecificOutput": {"hookEventName": "Stop"},
+            })
+        );
+        assert_eq!(StopOutput::pass().with_reason("done").continuation(), None);
+    }
+
+    #[test]
+    fn test_continuation_limit() {
+        let limit = ContinuationLimit::new(2);
+        let keep_going = || StopOutput::continue_with("keep going");
+        assert!(limit.check(false, keep_going()).continuation().is_some());
+        assert!(limit.check(true, keep_going()).continuation().is_some());
+        assert!(limit.check(true, keep_going()).continuation().is_none());
+        assert_eq!(limit.take_exceeded(), Some(2));
+        assert_eq!(limit.take_exceeded(), None);
+
+        // The count starts again once the agent stops by itself.
+        assert!(limit.check(false, keep_going()).continuation().is_some());
+        assert!(
+            limit
+                .check(true, StopOutput::pass())
+                .continuation()
+                .is_none()
+        );
+        assert_eq!(limit.take_exceeded(), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! Splitting the elements out of a JSON array as its text streams in.
+//!
+//! Used to hand out the items of `Vec<T>`-shaped structured output one at a
+//! time, keeping only the element being read rather than the whole document.
+
+/// Finds the array of a streamed JSON document, either the root or the
+/// property `wrapper` of a root object, and returns the text of each of its
+/// elements once complete.
+#[derive(Debug, Default)]
+pub(crate) struct ItemSplitter {
+    wrapper: Option<&'static str>,
+    depth: usize,
+    /// The depth inside the array, once it has been found.
+    array_depth: Option<usize>,
+    closed: bool,
+    in_string: bool,
+    escaped: bool,
+    /// The last string read directly inside the root object.
+    key: String,
+    element: String,
+}
+
+impl ItemSplitter {
+    pub(crate) fn new(wrapper: Option<&'static str>) -> Self {
+        Self {
+            wrapper,
+            ..Self::default()
+        }
+    }
+
+    /// Reads the next fragment of the document, returning the elements it
+    /// completed.
+    pub(crate) fn push(&mut self, fragment: &str) -> Vec<String> {
+        let mut elements = Vec::new();
+        for c in fragment.chars() {
+            if self.closed {
+                break;
+            }
+            let in_array = self.array_depth == Some(self.depth);
+            let in_element = self.array_depth.is_some_and(|depth| self.depth >= depth);
+
+            if self.in_string {
+                match c {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    '"' => self.in_string = false,
+                    _ => {}
+                }
+                if in_element {
+                    self.element.push(c);
+                } else if self.in_string && self.depth == 1 {
+                    self.key.push(c);
+                }
+                continue;
+            }
+
+            match c {
+                ',' | ']' if in_array => {
+                    let element = self.element.trim();
+                    if !element.is_empty() {
+                        elements.push(element.to_owned());
+                    }
+                    self.element.clear();
+                    if c == ']' {
+                        self.closed = true;
+                    }
+                    continue;
+                }
+                '"' => {
+                    self.in_string = true;
+                    if self.depth == 1 && !in_element {
+                        self.key.clear();
+                    }
+                }
+                '[' if self.array_depth.is_none() => {
+                    self.depth += 1;
+                    let found = match self.wrapper {
+                        None => self.depth == 1,
+                        Some(wrapper) => self.depth == 2 && self.key == wrapper,
+                    };
+                    if found {
+                        self.array_depth = Some(self.depth);
+                        continue;
+                    }
+                }
+                '{' | '[' => self.depth += 1,
+                '}' | ']' => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+            if in_element {
+                self.element.push(c);
+            }
+        }
+        elements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(wrapper: Option<&'static str>, input: &str, chunk: usize) -> Vec<String> {
+        let mut splitter = ItemSplitter::new(wrapper);
+        let chars = input.chars().collect::<Vec<_>>();
+        chars
+            .chunks(chunk)
+            .flat_map(|fragment| splitter.push(&fragment.iter().collect::<String>()))
+            .collect()
+    }
+
+    #[test]
+    fn test_splits_wrapped_array_across_fragments() {
+        let input = r#"{"note": "[not, this]", "items": [{"name": "a, \"b\"", "tags": ["x", "]"]}, 2 , "c"], "after": [9]}"#;
+        for chunk in [1, 3, 7, input.len()] {
+            assert_eq!(
+                split(Some("items"), input, chunk),
+                [r#"{"name": "a, \"b\"", "tags": ["x", "]"]}"#, "2", r#""c""#],
+                "chunk size {chunk}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_splits_root_array() {
+        assert_eq!(split(None, "[1, [2, 3], {}]", 2), ["1", "[2, 3]", "{}"]);
+        assert!(split(None, "[]", 1).is_empty());
+        assert!(split(Some("items"), r#"{"other": [1]}"#, 1).is_empty());
+    }
+}
@@ -10,6 +10,14 @@
 //! - Hook-based interception of tool execution
 //! - Type-safe message handling with comprehensive error types
 //!
+//! # Stability
+//!
+//! The enums mirroring the CLI's protocol, [`Response`], [`Error`],
+//! [`ConfigError`], [`proto::Incoming`] and [`proto::ContentBlock`], are
+//! `#[non_exhaustive]`, and traits such as [`Extra`] that only this crate
+//! implements are sealed, so new message types and fields can be supported
+//! in minor releases. Matches on those enums need a wildcard arm.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -24,42 +32,134 @@
 //! ```
 
 pub mod agent;
+pub mod approvals;
+pub mod auth;
+pub mod blocks;
+pub mod bridge;
+pub mod channel;
+pub mod claude_md;
 pub mod client;
+#[cfg(feature = "compat")]
+pub mod compat;
 pub mod conversation;
+pub mod denials;
+pub mod deterministic;
 pub mod error;
+#[cfg(feature = "schema")]
+pub mod eval;
+#[cfg(all(test, unix))]
+mod fake_cli;
+pub mod git;
+#[cfg(feature = "guardrails")]
+pub mod guardrails;
 pub mod handler;
+pub mod handlers;
 pub mod hooks;
+#[cfg(feature = "schema")]
+mod json_items;
+pub mod limits;
+mod locate;
+#[cfg(feature = "logging")]
+pub mod logging;
+pub mod manager;
+mod mcp_peer;
 pub mod mcp_server;
+pub mod memory;
 pub mod model;
+pub mod network;
+#[cfg(feature = "notifications")]
+pub mod notifications;
 pub mod options;
+pub mod orphans;
+mod partial_json;
 pub mod permissions;
+#[cfg(feature = "pii")]
+pub mod pii;
+pub mod policy;
+pub mod prepared;
+pub mod profile;
+pub mod project;
 pub mod proto;
+pub mod provider;
+#[cfg(feature = "pty")]
+pub mod pty;
+pub mod quota;
+#[cfg(feature = "schema")]
+pub mod recipes;
+pub mod recovery;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod repl;
+pub mod resource;
 pub mod response;
+pub mod router;
+pub mod sampling;
+pub mod sandbox;
+pub mod signal;
+pub mod stall;
+#[cfg(feature = "store")]
+pub mod store;
+#[cfg(feature = "schema")]
+pub mod structured;
+mod sync;
+pub mod task_pool;
+pub mod tenant;
+pub mod testing;
+pub mod thinking;
 pub mod tool;
+pub mod tools;
 pub mod transport;
 mod util;
+pub mod watch;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
+pub mod wire_log;
 
-pub use agent::Agent;
+pub use agent::{Agent, ToolInheritance};
 pub use client::Client;
-pub use conversation::{Conversation, Turn, TurnBuilder};
-pub use error::Error;
-pub use handler::{DefaultHandler, Handler, dispatch};
+pub use conversation::{
+    Conversation, HistoryMode, OwnedConversation, QueueEvent, Turn, TurnBuilder,
+};
+pub use denials::{DenialEvent, DenialSource};
+pub use deterministic::{Clock, IdGenerator};
+pub use error::{ConfigError, Error};
+pub use handler::{Chain, DefaultHandler, DispatchQueue, Handler, Overflow, dispatch};
+pub use handlers::{ConsoleReporter, JsonlSink};
 pub use hooks::{
-    Hooks, PostToolUseCallback, PostToolUseDecision, PostToolUseInput, PostToolUseOutput,
-    PreToolUseCallback, PreToolUseDecision, PreToolUseInput, PreToolUseOutput, StopCallback,
-    StopDecision, StopInput, StopOutput, UserPromptSubmitCallback, UserPromptSubmitDecision,
-    UserPromptSubmitInput, UserPromptSubmitOutput,
+    HookDescription, HookErrorCallback, HookErrorEvent, Hooks, PostToolUseCallback,
+    PostToolUseDecision, PostToolUseInput, PostToolUseOutput, PreToolUseCallback,
+    PreToolUseDecision, PreToolUseInput, PreToolUseOutput, StopCallback, StopDecision, StopInput,
+    StopOutput, UserPromptSubmitCallback, UserPromptSubmitDecision, UserPromptSubmitInput,
+    UserPromptSubmitOutput,
 };
-pub use mcp_server::McpServer;
+pub use manager::ConversationManager;
+pub use mcp_server::{McpServer, ToolInvocation, ToolStats};
 pub use model::Model;
 pub use options::Options;
 pub use permissions::{
-    Callback as PermissionCallback, Decision, PermissionContext, PermissionMode, PermissionRule,
+    BypassAcknowledgement, Callback as PermissionCallback, Decision, DeferredDecision,
+    PermissionCallbacks, PermissionContext, PermissionMode, PermissionRule, PermissionUpdate,
 };
+pub use prepared::PreparedMessage;
+pub use profile::PermissionProfile;
+pub use proto::control::Capabilities;
+pub use proto::extra::Extra;
 pub use proto::incoming::RateLimitStatus;
-pub use proto::message::{AssistantError, Usage};
+pub use proto::message::{AssistantError, ModelUsage, PermissionDenial, Usage};
+pub use recovery::{RecoveryAction, RecoveryPolicy};
+pub use resource::ResourceTemplate;
 pub use response::{
-    CompleteResponse, ErrorResponse, HookLifecycleResponse, InitResponse, RateLimitResponse,
-    Response, Responses, TextResponse, ThinkingResponse, ToolResultResponse, ToolUseResponse,
+    CompleteResponse, ErrorResponse, HookLifecycleResponse, InitResponse, PartialResponse,
+    RateLimitResponse, Response, ResponseMeta, Responses, TextResponse, ThinkingResponse, Timings,
+    ToolResultResponse, ToolRoundTrip, ToolUseResponse,
+};
+pub use sampling::{SampledMessage, SamplingMessage, SamplingParams};
+pub use sandbox::Sandbox;
+pub use stall::{StallAction, StallPolicy, Stalled};
+pub use task_pool::TaskPoolStats;
+pub use thinking::{Effort, ThinkingConfig};
+pub use tool::{
+    BuiltinTool, Elicitation, Tool, ToolContext, ToolError, ToolErrorKind, ToolInput, ToolName,
+    ToolSelector,
 };
-pub use tool::{Tool, ToolError, ToolInput};
+pub use transport::CommandPreview;
@@ -0,0 +1,361 @@
+//! Resource limits for the CLI process.
+//!
+//! A multi-tenant host can contain runaway sessions with
+//! [`Options::process_limits`](crate::Options::process_limits). The limits
+//! are applied as the CLI starts and are inherited by the tools it runs:
+//!
+//! | Limit | Unix | Windows |
+//! |-------|------|---------|
+//! | [`max_memory`](ProcessLimits::max_memory) | `RLIMIT_AS` | Job object process memory limit |
+//! | [`nice`](ProcessLimits::nice) | `setpriority` | Job object priority class |
+//! | [`cpu_affinity`](ProcessLimits::cpu_affinity) | `sched_setaffinity` (Linux only) | Job object affinity |
+//!
+//! The buffers the client itself keeps between the CLI and the caller are
+//! sized with [`Options::limits`](crate::Options::limits), and
+//! [`Client::memory_stats`](crate::Client::memory_stats) reports how full
+//! they are, for tuning hosts with little memory to spare.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::limits::ProcessLimits;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let limits = ProcessLimits {
+//!     max_memory: Some(4 << 30),
+//!     nice: Some(10),
+//!     cpu_affinity: Some(vec![0, 1]),
+//! };
+//! let client = Client::new(Options::new().process_limits(limits)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::ConfigError;
+use crate::task_pool::TaskPoolStats;
+
+/// Limits on the CLI process and its children.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProcessLimits {
+    /// The most address space, in bytes, the process may use.
+    pub max_memory: Option<u64>,
+    /// Scheduling priority from -20 (highest) to 19 (lowest). Raising the
+    /// priority above the host's usually requires privileges.
+    pub nice: Option<i32>,
+    /// The CPUs the process may run on, numbered from 0.
+    pub cpu_affinity: Option<Vec<usize>>,
+}
+
+impl ProcessLimits {
+    /// Checks that the limits can be applied.
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: String| ConfigError::InvalidProcessLimits { reason };
+        if self.max_memory == Some(0) {
+            return Err(invalid("max_memory must be greater than zero".to_owned()));
+        }
+        if let Some(nice) = self.nice.filter(|nice| !(-20..=19).contains(nice)) {
+            return Err(invalid(format!("nice value {nice} is outside -20..=19")));
+        }
+        if let Some(cpus) = &self.cpu_affinity {
+            let available = std::thread::available_parallelism().map_or(1, usize::from);
+            if cpus.is_empty() {
+                return Err(invalid("cpu_affinity lists no CPUs".to_owned()));
+            }
+            if let Some(cpu) = cpus.iter().find(|cpu| **cpu >= available) {
+                return Err(invalid(format!(
+                    "CPU {cpu} is not available; this host has {available}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the limits to the process `pid`, or to the calling process
+    /// when `pid` is 0.
+    ///
+    /// Called between `fork` and `exec`, so it must not allocate.
+    #[cfg(unix)]
+    pub(crate) fn apply(&self, pid: libc::pid_t) -> std::io::Result<()> {
+        let check = |result: libc::c_int| {
+            if result == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        };
+        if let Some(nice) = self.nice {
+            // SAFETY: setpriority only reads its arguments.
+            check(unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, nice) })?;
+        }
+        if let Some(bytes) = self.max_memory {
+            let limit = libc::rlimit {
+                rlim_cur: bytes as libc::rlim_t,
+                rlim_max: bytes as libc::rlim_t,
+            };
+            #[cfg(target_os = "linux")]
+            // SAFETY: `limit` outlives the call and the old limit is not
+            // requested.
+            check(unsafe { libc::prlimit(pid, libc::RLIMIT_AS, &limit, std::ptr::null_mut()) })?;
+            #[cfg(not(target_os = "linux"))]
+            if pid == 0 {
+                // SAFETY: `limit` outlives the call.
+                check(unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) })?;
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(cpus) = &self.cpu_affinity {
+            // SAFETY: a zeroed cpu_set_t is an empty set, CPU_SET is given
+            // indices below CPU_SETSIZE, and the set outlives the call.
+            unsafe {
+                let mut set = std::mem::zeroed::<libc::cpu_set_t>();
+                for &cpu in cpus.iter().filter(|&&cpu| cpu < libc::CPU_SETSIZE as usize) {
+                    libc::CPU_SET(cpu, &mut set);
+                }
+                check(libc::sched_setaffinity(
+                    pid,
+                    std::mem::size_of::<libc::cpu_set_t>(),
+                    &set,
+                ))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sizes of the buffers a client keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The most responses [`Client::dispatch_to`](crate::Client::dispatch_to)
+    /// reads ahead of its handler. Defaults to 256.
+    pub dispatch_queue: usize,
+    /// The size of the buffer the CLI's output is read through, in bytes.
+    /// Defaults to 64 KiB.
+    pub read_buffer: usize,
+    /// The longest line of output accepted from the CLI, in bytes. A longer
+    /// message is skipped and fails the read with
+    /// [`Error::MessageTooLarge`](crate::Error::MessageTooLarge), rather than
+    /// buffered whole. Defaults to 256 MiB.
+    pub max_message_bytes: usize,
+}
+
+impl Limits {
+    pub const DEFAULT_DISPATCH_QUEUE: usize = 256;
+    pub const DEFAULT_READ_BUFFER: usize = 64 * 1024;
+    pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 256 * 1024 * 1024;
+
+    /// Checks that every buffer can hold something.
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field: &str| ConfigError::InvalidLimits {
+            reason: format!("{field} must be greater than zero"),
+        };
+        if self.dispatch_queue == 0 {
+            return Err(invalid("dispatch_queue"));
+        }
+        if self.read_buffer == 0 {
+            return Err(invalid("read_buffer"));
+        }
+        if self.max_message_bytes == 0 {
+            return Err(invalid("max_message_bytes"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            dispatch_queue: Self::DEFAULT_DISPATCH_QUEUE,
+            read_buffer: Self::DEFAULT_READ_BUFFER,
+            max_message_bytes: Self::DEFAULT_MAX_MESSAGE_BYTES,
+        }
+    }
+}
+
+/// How much a client was holding when
+/// [`Client::memory_stats`](crate::Client::memory_stats) was called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryStats {
+    /// Messages read from the CLI and not yet received.
+    pub pending_messages: usize,
+    /// Control requests sent to the CLI and not yet answered.
+    pub outstanding_requests: usize,
+    /// Tool calls answered in the session, remembered so none is answered
+    /// twice.
+    pub responded_tool_calls: usize,
+    /// Hook callbacks waiting, running, or done but not yet answered.
+    pub hook_pool: TaskPoolStats,
+    /// MCP tool calls waiting, running, or done but not yet answered.
+    pub tool_pool: TaskPoolStats,
+    /// Tool names, model names and session ids shared by every client of
+    /// the process.
+    pub interned_strings: usize,
+}
+
+/// A job object holding the CLI process, which enforces the limits on it
+/// and every process it starts, and kills them all when closed.
+#[cfg(windows)]
+#[derive(Debug)]
+pub(crate) struct JobObject(windows_sys::Win32::Foundation::HANDLE);
+
+// SAFETY: a job object handle can be used and closed from any thread.
+#[cfg(windows)]
+unsafe impl Send for JobObject {}
+#[cfg(windows)]
+unsafe impl Sync for JobObject {}
+
+#[cfg(windows)]
+impl JobObject {
+    /// Creates a job object enforcing `limits`, if any, and adds the process
+    /// with id `pid` to it.
+    pub(crate) fn assign(limits: Option<&ProcessLimits>, pid: u32) -> std::io::Result<Self> {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JOB_OBJECT_LIMIT_AFFINITY,
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOB_OBJECT_LIMIT_PRIORITY_CLASS,
+            JOB_OBJECT_LIMIT_PROCESS_MEMORY, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+            JobObjectExtendedLimitInformation, SetInformationJobObject,
+        };
+        use windows_sys::Win32::System::Threading::{
+            ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, IDLE_PRIORITY_CLASS,
+            NORMAL_PRIORITY_CLASS, OpenProcess, PROCESS_SET_QUOTA, PROCESS_TERMINATE,
+        };
+
+        // SAFETY: every handle is checked before use and closed on all
+        // paths except the job's, which is owned by the returned value; the
+        // limit information is fully initialised and outlives the call.
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if job.is_null() {
+                return Err(std::io::Error::last_os_error());
+            }
+            let job = Self(job);
+
+            let limits = limits.cloned().unwrap_or_default();
+            let mut info = std::mem::zeroed::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>();
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            if let Some(bytes) = limits.max_memory {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+                info.ProcessMemoryLimit = usize::try_from(bytes).unwrap_or(usize::MAX);
+            }
+            let basic = &mut info.BasicLimitInformation;
+            if let Some(nice) = limits.nice {
+                basic.LimitFlags |= JOB_OBJECT_LIMIT_PRIORITY_CLASS;
+                basic.PriorityClass = match nice {
+                    10.. => IDLE_PRIORITY_CLASS,
+                    1..=9 => BELOW_NORMAL_PRIORITY_CLASS,
+                    0 => NORMAL_PRIORITY_CLASS,
+                    _ => ABOVE_NORMAL_PRIORITY_CLASS,
+                };
+            }
+            if let Some(cpus) = &limits.cpu_affinity {
+                basic.LimitFlags |= JOB_OBJECT_LIMIT_AFFINITY;
+                basic.Affinity = cpus
+                    .iter()
+                    .filter(|&&cpu| cpu < usize::BITS as usize)
+                    .fold(0, |mask, cpu| mask | (1 << cpu));
+            }
+            if SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                std::ptr::from_ref(&info).cast(),
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            ) == 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            let process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0, pid);
+            if process.is_null() {
+                return Err(std::io::Error::last_os_error());
+            }
+            let assigned = AssignProcessToJobObject(job.0, process);
+            let error = std::io::Error::last_os_error();
+            CloseHandle(process);
+            if assigned == 0 {
+                return Err(error);
+            }
+            Ok(job)
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        // SAFETY: the handle is owned by this value and not used again.
+        unsafe {
+            windows_sys::Win32::Foundation::CloseHandle(self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_limits_validation() {
+        assert!(ProcessLimits::default().validate().is_ok());
+        assert!(
+            ProcessLimits {
+                max_memory: Some(1 << 30),
+                nice: Some(19),
+                cpu_affinity: Some(vec![0]),
+            }
+            .validate()
+            .is_ok()
+        );
+        for limits in [
+            ProcessLimits {
+                nice: Some(20),
+                ..Default::default()
+            },
+            ProcessLimits {
+                cpu_affinity: Some(Vec::new()),
+                ..Default::default()
+            },
+            ProcessLimits {
+                cpu_affinity: Some(vec![usize::MAX]),
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                limits.validate(),
+                Err(ConfigError::InvalidProcessLimits { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_limits_validation() {
+        assert!(Limits::default().validate().is_ok());
+        let limits = Limits {
+            read_buffer: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            limits.validate(),
+            Err(ConfigError::InvalidLimits { reason }) if reason.starts_with("read_buffer")
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_process_limits_apply_to_child() {
+        let limits = ProcessLimits {
+            max_memory: Some(1 << 34),
+            nice: Some(5),
+            cpu_affinity: Some(vec![0]),
+        };
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "ulimit -v; cat /proc/self/stat | cut -d' ' -f19"]);
+        // SAFETY: `apply` does not allocate.
+        unsafe {
+            command.pre_exec(move || limits.apply(0));
+        }
+        let output = command.output().await.unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout.lines().collect::<Vec<_>>(), ["16777216", "5"]);
+    }
+}
@@ -0,0 +1,165 @@
+//! Finding the `claude` executable.
+//!
+//! On Unix the CLI is a single `claude` file on `PATH`. On Windows npm
+//! installs it as `claude.cmd` and `claude.ps1` shims, which
+//! `Command::new("claude")` does not find, so the lookup tries each
+//! extension in `PATHEXT`, then `where claude`, then npm's global bin
+//! directory.
+
+use std::path::{Path, PathBuf};
+
+const NAME: &str = "claude";
+
+/// How to start the CLI: a program and the arguments that come before the
+/// CLI's own.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Launcher {
+    pub(crate) program: PathBuf,
+    pub(crate) args: Vec<String>,
+}
+
+impl Launcher {
+    fn new(path: PathBuf) -> Self {
+        // PowerShell scripts cannot be executed directly.
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ps1"))
+        {
+            return Self {
+                program: PathBuf::from("powershell.exe"),
+                args: vec![
+                    "-NoProfile".to_owned(),
+                    "-ExecutionPolicy".to_owned(),
+                    "Bypass".to_owned(),
+                    "-File".to_owned(),
+                    path.display().to_string(),
+                ],
+            };
+        }
+        Self {
+            program: path,
+            args: Vec::new(),
+        }
+    }
+}
+
+impl Launcher {
+    /// Whether the CLI file exists, rather than being left for the spawn to
+    /// look up.
+    pub(crate) fn is_found(&self) -> bool {
+        // A PowerShell launcher runs the script named last.
+        self.args
+            .last()
+            .map_or(self.program.as_path(), Path::new)
+            .is_file()
+    }
+}
+
+/// The CLI as given, without looking for it.
+pub(crate) fn unresolved(explicit: Option<&Path>) -> Launcher {
+    Launcher::new(explicit.map_or_else(|| PathBuf::from(NAME), Path::to_path_buf))
+}
+
+/// Resolves the CLI to run, preferring `explicit` when given. Falls back to
+/// the bare name, so a failure to find it surfaces when spawning.
+pub(crate) async fn resolve(explicit: Option<&Path>) -> Launcher {
+    if let Some(path) = explicit {
+        return Launcher::new(path.to_path_buf());
+    }
+    let dirs = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default();
+    if let Some(path) = find_in(&dirs, &extensions()) {
+        return Launcher::new(path);
+    }
+    if cfg!(windows) {
+        if let Some(path) = where_lookup().await {
+            return Launcher::new(path);
+        }
+        let npm = std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("npm"));
+        if let Some(path) = npm.and_then(|dir| find_in(&[dir], &extensions())) {
+            return Launcher::new(path);
+        }
+    } else if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        let dirs = [
+            home.join(".claude/local"),
+            home.join(".local/bin"),
+            home.join(".npm-global/bin"),
+        ];
+        if let Some(path) = find_in(&dirs, &extensions()) {
+            return Launcher::new(path);
+        }
+    }
+    Launcher::new(PathBuf::from(NAME))
+}
+
+/// File extensions the CLI may have, in order of preference.
+fn extensions() -> Vec<String> {
+    if !cfg!(windows) {
+        return vec![String::new()];
+    }
+    let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_owned());
+    let mut extensions = pathext
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    extensions.push(".ps1".to_owned());
+    extensions
+}
+
+fn find_in(dirs: &[PathBuf], extensions: &[String]) -> Option<PathBuf> {
+    dirs.iter().find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{NAME}{ext}")))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Asks `where` for the CLI, which also searches the App Paths registry.
+async fn where_lookup() -> Option<PathBuf> {
+    let output = tokio::process::Command::new("where")
+        .arg(NAME)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| PathBuf::from(line.trim()))
+        .filter(|path| path.is_file())
+        .min_by_key(|path| {
+            let ext = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase());
+            // Prefer shims that can be spawned without PowerShell.
+            matches!(ext.as_deref(), Some("ps1"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_cli_in_dirs() {
+        let dir = std::env::temp_dir().join(format!("clauders-locate-{}", crate::util::uuid_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let extensions = [".cmd".to_owned(), ".ps1".to_owned()];
+        assert_eq!(find_in(std::slice::from_ref(&dir), &extensions), None);
+
+        std::fs::write(dir.join("claude.ps1"), "").unwrap();
+        let script = find_in(std::slice::from_ref(&dir), &extensions).unwrap();
+        let launcher = Launcher::new(script.clone());
+        assert_eq!(launcher.program, PathBuf::from("powershell.exe"));
+        assert_eq!(launcher.args.last(), Some(&script.display().to_string()));
+
+        std::fs::write(dir.join("claude.cmd"), "").unwrap();
+        let shim = find_in(std::slice::from_ref(&dir), &extensions).unwrap();
+        assert_eq!(Launcher::new(shim.clone()).program, shim);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
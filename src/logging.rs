@@ -0,0 +1,158 @@
+//! Printing the crate's diagnostics without setting up `tracing`.
+//!
+//! The crate reports what it does through `tracing`. An application that
+//! does not install a subscriber of its own can call [`init_tracing`] with
+//! the `logging` feature to print those events to stderr, choosing a level
+//! per [`Subsystem`]:
+//!
+//! ```no_run
+//! use clauders::logging::{Subsystem, TracingConfig, init_tracing};
+//! use tracing::Level;
+//!
+//! init_tracing(
+//!     TracingConfig::new()
+//!         .subsystem(Subsystem::Transport, Level::DEBUG)
+//!         .subsystem(Subsystem::Hooks, Level::INFO),
+//! )
+//! .expect("no other subscriber is installed");
+//! ```
+//!
+//! [`TracingConfig::protocol_only`] instead prints just the messages
+//! exchanged with the CLI, one compact line each.
+
+use tracing::Level;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+
+/// A part of the crate whose events can be given their own level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    /// Starting, stopping and talking to the CLI process.
+    Transport,
+    /// Every message sent to and received from the CLI.
+    Protocol,
+    /// Hook callbacks.
+    Hooks,
+    /// In-process MCP servers and their tools.
+    Mcp,
+    /// What the CLI writes to stderr.
+    Cli,
+}
+
+impl Subsystem {
+    /// The `tracing` target of the subsystem's events.
+    pub fn target(self) -> &'static str {
+        match self {
+            Self::Transport => "clauders::transport",
+            Self::Protocol => crate::transport::PROTOCOL_TARGET,
+            Self::Hooks => "clauders::hooks",
+            Self::Mcp => "clauders::mcp_server",
+            Self::Cli => "claude_cli",
+        }
+    }
+}
+
+/// What [`init_tracing`] prints.
+#[derive(Debug, Clone)]
+pub struct TracingConfig {
+    level: Level,
+    subsystems: Vec<(Subsystem, Level)>,
+    protocol_only: bool,
+}
+
+impl TracingConfig {
+    /// Prints warnings and errors from every subsystem.
+    pub fn new() -> Self {
+        Self {
+            level: Level::WARN,
+            subsystems: Vec::new(),
+            protocol_only: false,
+        }
+    }
+
+    /// The level of subsystems not given one of their own.
+    #[must_use]
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    #[must_use]
+    pub fn subsystem(mut self, subsystem: Subsystem, level: Level) -> Self {
+        self.subsystems.retain(|(s, _)| *s != subsystem);
+        self.subsystems.push((subsystem, level));
+        self
+    }
+
+    /// Prints only the protocol messages, and errors, in a compact format.
+    /// Other levels are ignored.
+    #[must_use]
+    pub fn protocol_only(mut self) -> Self {
+        self.protocol_only = true;
+        self
+    }
+
+    fn targets(&self) -> Targets {
+        if self.protocol_only {
+            return Targets::new()
+                .with_default(LevelFilter::ERROR)
+                .with_target(Subsystem::Protocol.target(), Level::DEBUG);
+        }
+        // Events of other crates, such as the HTTP clients of tools, are
+        // left at the default level too.
+        self.subsystems.iter().fold(
+            Targets::new().with_default(self.level),
+            |targets, (subsystem, level)| targets.with_target(subsystem.target(), *level),
+        )
+    }
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Installs a global subscriber printing the crate's events to stderr as
+/// `config` describes. Fails if a subscriber is already installed.
+pub fn init_tracing(config: TracingConfig) -> Result<(), TryInitError> {
+    let targets = config.targets();
+    let registry = tracing_subscriber::registry().with(targets);
+    if config.protocol_only {
+        let layer = tracing_subscriber::fmt::layer()
+            .compact()
+            .with_target(false)
+            .with_writer(std::io::stderr);
+        registry.with(layer).try_init()
+    } else {
+        let layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+        registry.with(layer).try_init()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracing_config_levels() {
+        let targets = TracingConfig::new()
+            .subsystem(Subsystem::Hooks, Level::TRACE)
+            .subsystem(Subsystem::Transport, Level::INFO)
+            .subsystem(Subsystem::Transport, Level::DEBUG)
+            .targets();
+        assert!(targets.would_enable("clauders::hooks::path_guard", &Level::TRACE));
+        assert!(targets.would_enable("clauders::transport", &Level::DEBUG));
+        assert!(!targets.would_enable("clauders::mcp_server", &Level::INFO));
+        assert!(targets.would_enable("clauders::mcp_server", &Level::WARN));
+
+        let targets = TracingConfig::new()
+            .level(Level::TRACE)
+            .protocol_only()
+            .targets();
+        assert!(targets.would_enable(Subsystem::Protocol.target(), &Level::DEBUG));
+        assert!(!targets.would_enable("clauders::transport", &Level::WARN));
+        assert!(targets.would_enable("clauders::transport", &Level::ERROR));
+    }
+}
@@ -0,0 +1,335 @@
+//! Conversations of many users, kept by an id of their own.
+//!
+//! A [`ConversationManager`] gives each external id, such as a user or chat
+//! channel id, its own client and [`OwnedConversation`]. It bounds how many
+//! are open at once by closing the least recently used, and closes those
+//! left idle. A closed conversation is not lost: the next request for its id
+//! starts a client that resumes the same CLI session.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use clauders::Options;
+//! use clauders::manager::ConversationManager;
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let manager = ConversationManager::new(|_user_id: &u64| Options::new())
+//!     .capacity(100)
+//!     .idle_timeout(Duration::from_secs(15 * 60));
+//!
+//! let conversation = manager.get(42).await?;
+//! let reply = conversation.lock().await.say("Hello").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+use crate::client::Client;
+use crate::conversation::{Conversation, OwnedConversation};
+use crate::error::Error;
+use crate::options::Options;
+
+/// Number of conversations open at once unless set with
+/// [`ConversationManager::capacity`].
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// A conversation handed out by a [`ConversationManager`], locked for each
+/// use so turns of the same conversation do not interleave.
+pub type SharedConversation = Arc<Mutex<OwnedConversation>>;
+
+type OptionsFactory<K> = Box<dyn Fn(&K) -> Options + Send + Sync>;
+
+/// Open conversations keyed by external ids, with LRU eviction, idle
+/// timeouts and resumption of closed conversations.
+pub struct ConversationManager<K> {
+    options: OptionsFactory<K>,
+    capacity: usize,
+    idle_timeout: Option<Duration>,
+    state: Mutex<State<K>>,
+}
+
+struct State<K> {
+    open: HashMap<K, Open>,
+    /// Session ids of closed conversations, resumed when next requested.
+    closed: HashMap<K, String>,
+}
+
+struct Open {
+    conversation: SharedConversation,
+    client: Arc<Client>,
+    last_used: Instant,
+}
+
+impl<K> std::fmt::Debug for ConversationManager<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConversationManager")
+            .field("capacity", &self.capacity)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K: Eq + Hash + Clone> ConversationManager<K> {
+    /// Creates a manager starting each id's client with the options
+    /// `options` returns for it.
+    pub fn new<F>(options: F) -> Self
+    where
+        F: Fn(&K) -> Options + Send + Sync + 'static,
+    {
+        Self {
+            options: Box::new(options),
+            capacity: DEFAULT_CAPACITY,
+            idle_timeout: None,
+            state: Mutex::new(State {
+                open: HashMap::new(),
+                closed: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Sets how many conversations may be open at once, at least one.
+    #[must_use]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Closes conversations not used for `timeout`. They are closed as the
+    /// manager is next used, or by [`close_idle`](Self::close_idle).
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the conversation of `key`, opening it if needed.
+    ///
+    /// A conversation closed earlier resumes its CLI session. Opening one
+    /// when the manager is full closes the least recently used.
+    pub async fn get(&self, key: K) -> Result<SharedConversation, Error> {
+        close_all(self.take_idle(Instant::now()).await).await;
+        let resume = {
+            let mut state = self.state.lock().await;
+            if let Some(open) = state.open.get_mut(&key) {
+                open.last_used = Instant::now();
+                return Ok(Arc::clone(&open.conversation));
+            }
+            state.closed.remove(&key)
+        };
+
+        // The CLI is started without holding the lock, so other ids are
+        // served meanwhile.
+        let mut options = (self.options)(&key);
+        if let Some(session_id) = &resume {
+            tracing::debug!(%session_id, "resuming closed conversation");
+            options = options.resume(session_id);
+        }
+        let client = match Client::new(options).await {
+            Ok(client) => Arc::new(client),
+            Err(e) => {
+                if let Some(session_id) = resume {
+                    self.state.lock().await.closed.insert(key, session_id);
+                }
+                return Err(e);
+            }
+        };
+
+        let mut state = self.state.lock().await;
+        if let Some(open) = state.open.get_mut(&key) {
+            // Opened by another caller while this one was starting.
+            open.last_used = Instant::now();
+            return Ok(Arc::clone(&open.conversation));
+        }
+        let mut evicted = Vec::new();
+        while state.open.len() >= self.capacity {
+            let Some(lru) = least_recently_used(state.open.iter().map(|(k, o)| (k, o.last_used)))
+            else {
+                break;
+            };
+            if let Some(open) = state.open.remove(&lru) {
+                evicted.push((lru, open));
+            }
+        }
+        let conversation = Arc::new(Mutex::new(Conversation::owned(Arc::clone(&client))));
+        state.open.insert(
+            key,
+            Open {
+                conversation: Arc::clone(&conversation),
+                client,
+                last_used: Instant::now(),
+            },
+        );
+        for (key, open) in &evicted {
+            remember(&mut state, key, open).await;
+        }
+        drop(state);
+        close_all(evicted).await;
+        Ok(conversation)
+    }
+
+    /// Closes the conversation of `key`, keeping its session to resume.
+    /// Returns whether it was open.
+    pub async fn close(&self, key: &K) -> bool {
+        let mut state = self.state.lock().await;
+        let Some(open) = state.open.remove(key) else {
+            return false;
+        };
+        remember(&mut state, key, &open).await;
+        drop(state);
+        close_all(vec![(key.clone(), open)]).await;
+        true
+    }
+
+    /// Closes the conversation of `key` and forgets its session. Returns
+    /// whether the manager knew it.
+    pub async fn remove(&self, key: &K) -> bool {
+        let mut state = self.state.lock().await;
+        let closed = state.closed.remove(key).is_some();
+        let Some(open) = state.open.remove(key) else {
+            return closed;
+        };
+        drop(state);
+        close_all(vec![(key.clone(), open)]).await;
+        true
+    }
+
+    /// Closes the conversations idle for longer than the
+    /// [idle timeout](Self::idle_timeout), returning how many were closed.
+    pub async fn close_idle(&self) -> usize {
+        let idle = self.take_idle(Instant::now()).await;
+        let count = idle.len();
+        close_all(idle).await;
+        count
+    }
+
+    /// The number of open conversations.
+    pub async fn len(&self) -> usize {
+        self.state.lock().await.open.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// Whether `key` has a conversation, open or closed.
+    pub async fn contains(&self, key: &K) -> bool {
+        let state = self.state.lock().await;
+        state.open.contains_key(key) || state.closed.contains_key(key)
+    }
+
+    /// Removes the idle conversations from the open ones, remembering their
+    /// sessions.
+    async fn take_idle(&self, now: Instant) -> Vec<(K, Open)> {
+        let Some(timeout) = self.idle_timeout else {
+            return Vec::new();
+        };
+        let mut state = self.state.lock().await;
+        let keys = idle(
+            state.open.iter().map(|(k, o)| (k, o.last_used)),
+            now,
+            timeout,
+        );
+        let mut taken = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(open) = state.open.remove(&key) {
+                remember(&mut state, &key, &open).await;
+                taken.push((key, open));
+            }
+        }
+        taken
+    }
+}
+
+/// Records the session of a conversation being closed, to resume it later.
+async fn remember<K: Eq + Hash + Clone>(state: &mut State<K>, key: &K, open: &Open) {
+    if let Some(session_id) = open.client.session_id().await {
+        state.closed.insert(key.clone(), session_id);
+    }
+}
+
+/// Closes conversations taken out of the manager. One still in use by a
+/// caller closes once they drop it.
+async fn close_all<K>(closing: Vec<(K, Open)>) {
+    for (_, open) in closing {
+        let Open {
+            conversation,
+            client,
+            ..
+        } = open;
+        drop(conversation);
+        if let Ok(client) = Arc::try_unwrap(client) {
+            client.close().await;
+        }
+    }
+}
+
+fn least_recently_used<'a, K: Clone + 'a>(
+    entries: impl Iterator<Item = (&'a K, Instant)>,
+) -> Option<K> {
+    entries
+        .min_by_key(|(_, last_used)| *last_used)
+        .map(|(key, _)| key.clone())
+}
+
+fn idle<'a, K: Clone + 'a>(
+    entries: impl Iterator<Item = (&'a K, Instant)>,
+    now: Instant,
+    timeout: Duration,
+) -> Vec<K> {
+    entries
+        .filter(|(_, last_used)| now.saturating_duration_since(*last_used) > timeout)
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eviction_picks_least_recently_used_and_idle() {
+        let start = Instant::now();
+        let entries = [
+            ("a", start + Duration::from_secs(5)),
+            ("b", start),
+            ("c", start + Duration::from_secs(9)),
+        ];
+        let iter = || entries.iter().map(|(k, t)| (k, *t));
+
+        assert_eq!(least_recently_used(iter()), Some("b"));
+        assert_eq!(
+            least_recently_used(std::iter::empty::<(&&str, Instant)>()),
+            None
+        );
+
+        let mut stale = idle(
+            iter(),
+            start + Duration::from_secs(10),
+            Duration::from_secs(4),
+        );
+        stale.sort();
+        assert_eq!(stale, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_manager_bookkeeping_without_clients() {
+        let manager = ConversationManager::new(|_: &u64| Options::new()).capacity(0);
+        assert_eq!(manager.capacity, 1);
+        assert!(manager.is_empty().await);
+        assert!(!manager.close(&1).await);
+
+        manager.state.lock().await.closed.insert(1, "s1".to_owned());
+        assert!(manager.contains(&1).await);
+        assert!(manager.remove(&1).await);
+        assert!(!manager.contains(&1).await);
+        assert_eq!(manager.close_idle().await, 0);
+    }
+}
@@ -0,0 +1,101 @@
+//! Requests from in-process MCP servers to the CLI, their MCP client.
+//!
+//! Tool handlers run on tasks of their own, so they hand their requests to
+//! the client over a channel. The client sends each one as an `mcp_message`
+//! control request as it next reads, and the CLI's control response is routed
+//! back to the waiting handler.
+
+use serde_json::{Value, json};
+use tokio::sync::{Mutex, mpsc, oneshot};
+
+use crate::proto::Response;
+use crate::tool::ToolError;
+
+/// A JSON-RPC request for the CLI, and where to send its response.
+pub(crate) struct PeerRequest {
+    pub(crate) server_name: String,
+    pub(crate) message: Value,
+    pub(crate) reply: oneshot::Sender<Response>,
+}
+
+/// Sends requests to the CLI on behalf of one MCP server.
+#[derive(Debug, Clone)]
+pub(crate) struct Peer {
+    server_name: String,
+    tx: mpsc::UnboundedSender<PeerRequest>,
+}
+
+impl Peer {
+    /// Sends a JSON-RPC request and waits for its result.
+    pub(crate) async fn request(&self, method: &str, params: Value) -> Result<Value, ToolError> {
+        let (reply, rx) = oneshot::channel();
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": crate::util::uuid_v7(),
+            "method": method,
+            "params": params,
+        });
+        self.tx
+            .send(PeerRequest {
+                server_name: self.server_name.clone(),
+                message,
+                reply,
+            })
+            .map_err(|_| ToolError::execution_failed("client is closed"))?;
+        let response = rx.await.map_err(|_| {
+            ToolError::execution_failed(format!("no response to '{method}' from the CLI"))
+        })?;
+
+        let mcp_response = match response {
+            Response::Success(success) => success
+                .response()
+                .and_then(|r| r.get("mcp_response"))
+                .cloned()
+                .unwrap_or_default(),
+            Response::Error(err) => {
+                return Err(ToolError::execution_failed(err.error().message()));
+            }
+        };
+        if let Some(message) = mcp_response
+            .pointer("/error/message")
+            .and_then(Value::as_str)
+        {
+            return Err(ToolError::execution_failed(format!(
+                "'{method}' failed: {message}"
+            )));
+        }
+        Ok(mcp_response.get("result").cloned().unwrap_or_default())
+    }
+}
+
+/// The client's end of the channel its servers' requests arrive on.
+pub(crate) struct PeerRequests {
+    tx: mpsc::UnboundedSender<PeerRequest>,
+    rx: Mutex<mpsc::UnboundedReceiver<PeerRequest>>,
+}
+
+impl PeerRequests {
+    pub(crate) fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            tx,
+            rx: Mutex::new(rx),
+        }
+    }
+
+    pub(crate) fn peer(&self, server_name: impl Into<String>) -> Peer {
+        Peer {
+            server_name: server_name.into(),
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Waits for the next request. Cancellation safe.
+    pub(crate) async fn next(&self) -> PeerRequest {
+        match self.rx.lock().await.recv().await {
+            Some(request) => request,
+            // `self` holds a sender, so the channel never closes.
+            None => std::future::pending().await,
+        }
+    }
+}
@@ -1,8 +1,15 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
+use futures::future::BoxFuture;
 use serde_json::{Value, json};
 
-use crate::tool::{Tool, ToolInput};
+use crate::resource::ResourceTemplate;
+use crate::tool::{
+    ERROR_KIND_META_KEY, Tool, ToolContext, ToolError, ToolErrorKind, ToolInput, ToolName,
+};
 
 #[derive(Debug)]
 pub struct McpServer {
@@ -10,6 +17,150 @@ pub struct McpServer {
     version: String,
     tools: Vec<Tool>,
     tool_map: HashMap<String, usize>,
+    resource_templates: Vec<ResourceTemplate>,
+    spill: Option<OutputSpill>,
+    stats: Mutex<HashMap<String, ToolStats>>,
+    observer: Option<Observer>,
+    shutdown_hooks: Vec<ShutdownHook>,
+    protocol_version: Mutex<Option<String>>,
+}
+
+/// MCP revisions the server speaks, newest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = crate::proto::versions::MCP_PROTOCOL_VERSIONS;
+
+/// The first revision with tool output schemas and structured results.
+const STRUCTURED_OUTPUT_VERSION: &str = "2025-06-18";
+
+/// The first revision with argument completions.
+const COMPLETIONS_VERSION: &str = "2025-03-26";
+
+/// Key under a tool result's `_meta` holding the id of the call.
+pub const INVOCATION_ID_META_KEY: &str = "clauders/invocationId";
+
+/// Key under a tool result's `_meta` holding how long the tool ran, in
+/// milliseconds.
+pub const DURATION_META_KEY: &str = "clauders/durationMs";
+
+/// One call to a tool of an [`McpServer`], as passed to
+/// [`McpServer::on_call`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolInvocation {
+    id: String,
+    tool: String,
+    arguments: Value,
+    duration: Duration,
+    error: Option<ToolErrorKind>,
+}
+
+impl ToolInvocation {
+    /// The id sent back in the result's `_meta`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn tool(&self) -> &str {
+        &self.tool
+    }
+
+    pub fn arguments(&self) -> &Value {
+        &self.arguments
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The kind of failure, if the call failed.
+    pub fn error(&self) -> Option<ToolErrorKind> {
+        self.error
+    }
+}
+
+#[derive(Clone)]
+struct Observer(Arc<dyn Fn(&ToolInvocation) + Send + Sync>);
+
+impl std::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+#[derive(Clone)]
+struct ShutdownHook(Arc<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>);
+
+impl std::fmt::Debug for ShutdownHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ShutdownHook")
+    }
+}
+
+/// Whether a tool's content is an [`error_result`](Tool::error_result).
+fn is_error_result(content: &Value) -> bool {
+    content.as_array().is_some_and(|items| {
+        items
+            .iter()
+            .any(|item| item.get("is_error").and_then(Value::as_bool) == Some(true))
+    })
+}
+
+/// Usage statistics for a single tool on an [`McpServer`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolStats {
+    calls: u64,
+    errors: u64,
+    total_duration: Duration,
+    last_input: Option<Value>,
+}
+
+impl ToolStats {
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Calls that failed or returned an [`error_result`](Tool::error_result).
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Fraction of calls that returned an error, between 0 and 1.
+    pub fn error_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.calls as f64
+        }
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.total_duration
+    }
+
+    pub fn average_duration(&self) -> Duration {
+        if self.calls == 0 {
+            Duration::ZERO
+        } else {
+            self.total_duration.div_f64(self.calls as f64)
+        }
+    }
+
+    /// Arguments of the most recent call.
+    pub fn last_input(&self) -> Option<&Value> {
+        self.last_input.as_ref()
+    }
+
+    fn record(&mut self, input: Value, duration: Duration, failed: bool) {
+        self.calls += 1;
+        self.errors += u64::from(failed);
+        self.total_duration += duration;
+        self.last_input = Some(input);
+    }
+}
+
+/// Where oversized tool results are written, and the size that triggers it.
+#[derive(Debug, Clone)]
+struct OutputSpill {
+    max_bytes: usize,
+    dir: PathBuf,
 }
 
 impl McpServer {
@@ -33,6 +184,66 @@ impl McpServer {
             version: version.into(),
             tools,
             tool_map,
+            resource_templates: Vec::new(),
+            spill: None,
+            stats: Mutex::new(HashMap::new()),
+            observer: None,
+            shutdown_hooks: Vec::new(),
+            protocol_version: Mutex::new(None),
+        }
+    }
+
+    /// Caps the size of unstructured tool results.
+    ///
+    /// A result larger than `max_bytes` is written in full to a file in `dir`
+    /// (typically inside the session's working directory), and the model
+    /// receives a short preview along with the file's path instead.
+    #[must_use]
+    pub fn with_output_spill(mut self, max_bytes: usize, dir: impl Into<PathBuf>) -> Self {
+        self.spill = Some(OutputSpill {
+            max_bytes,
+            dir: dir.into(),
+        });
+        self
+    }
+
+    /// Serves resources matching `templates`, and completions for their
+    /// arguments.
+    #[must_use]
+    pub fn with_resource_templates(mut self, templates: Vec<ResourceTemplate>) -> Self {
+        self.resource_templates = templates;
+        self
+    }
+
+    /// Calls `observer` after every tool call, such as to trace slow tools.
+    #[must_use]
+    pub fn on_call<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&ToolInvocation) + Send + Sync + 'static,
+    {
+        self.observer = Some(Observer(Arc::new(observer)));
+        self
+    }
+
+    /// Runs `hook` when the CLI disconnects or the client is closed, so the
+    /// server can release files, connections and the like. Hooks run in the
+    /// order they were added.
+    #[must_use]
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown_hooks
+            .push(ShutdownHook(Arc::new(move || Box::pin(hook()))));
+        self
+    }
+
+    /// Runs the hooks added with [`on_shutdown`](Self::on_shutdown).
+    pub async fn shutdown(&self) {
+        tracing::debug!(server = %self.name, "shutting down MCP server");
+        for ShutdownHook(hook) in &self.shutdown_hooks {
+            hook().await;
         }
     }
 
@@ -48,6 +259,87 @@ impl McpServer {
         &self.tools
     }
 
+    pub fn resource_templates(&self) -> &[ResourceTemplate] {
+        &self.resource_templates
+    }
+
+    /// A Markdown reference of the server's tools, with their names,
+    /// descriptions and arguments, for embedding in a system prompt.
+    ///
+    /// Tools are named as the model sees them, assuming the server is
+    /// registered under its own [`name`](Self::name).
+    pub fn describe_for_prompt(&self) -> String {
+        self.describe_as(&self.name, |_| true)
+    }
+
+    /// [`describe_for_prompt`](Self::describe_for_prompt) for the server
+    /// registered as `server_name`, leaving out tools whose qualified name
+    /// `exposed` rejects.
+    pub(crate) fn describe_as(&self, server_name: &str, exposed: impl Fn(&str) -> bool) -> String {
+        let mut out = String::new();
+        for tool in &self.tools {
+            let name = ToolName::mcp(server_name, tool.name()).to_string();
+            if !exposed(&name) {
+                continue;
+            }
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("### {name}\n"));
+            if !tool.description().is_empty() {
+                out.push_str(&format!("{}\n", tool.description()));
+            }
+            let arguments = describe_arguments(tool.input_schema());
+            if !arguments.is_empty() {
+                out.push_str("\nArguments:\n");
+                out.push_str(&arguments);
+            }
+        }
+        out
+    }
+
+    /// The protocol revision agreed on in the last `initialize`, if any.
+    pub fn protocol_version(&self) -> Option<String> {
+        self.protocol_version
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Whether the negotiated revision is `version` or newer. Revisions are
+    /// dates, so they compare as strings; before `initialize` the newest is
+    /// assumed.
+    fn speaks(&self, version: &str) -> bool {
+        self.protocol_version
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_deref()
+            .is_none_or(|negotiated| negotiated >= version)
+    }
+
+    /// Returns usage statistics for every tool that has been called, keyed by tool name.
+    pub fn stats(&self) -> HashMap<String, ToolStats> {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn tool_stats(&self, name: &str) -> Option<ToolStats> {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
     fn jsonrpc_success(id: &Value, result: Value) -> Value {
         json!({
             "jsonrpc": "2.0",
@@ -67,12 +359,31 @@ impl McpServer {
         })
     }
 
-    fn handle_initialize(&self, id: &Value) -> Value {
+    /// Agrees on the revision the client asked for if it is supported, and
+    /// otherwise offers the newest one.
+    fn handle_initialize(&self, id: &Value, params: &Value) -> Value {
+        let requested = params.get("protocolVersion").and_then(Value::as_str);
+        let version = requested
+            .filter(|v| SUPPORTED_PROTOCOL_VERSIONS.contains(v))
+            .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0]);
+        tracing::debug!(server = %self.name, requested, version, "negotiated MCP protocol version");
+        *self
+            .protocol_version
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(version.to_owned());
+
+        let mut capabilities = json!({ "tools": {} });
+        if !self.resource_templates.is_empty() {
+            capabilities["resources"] = json!({});
+            if self.speaks(COMPLETIONS_VERSION) {
+                capabilities["completions"] = json!({});
+            }
+        }
         Self::jsonrpc_success(
             id,
             json!({
-                "protocolVersion": "2025-11-25",
-                "capabilities": { "tools": {} },
+                "protocolVersion": version,
+                "capabilities": capabilities,
                 "serverInfo": {
                     "name": self.name,
                     "version": self.version
@@ -82,11 +393,12 @@ impl McpServer {
     }
 
     fn handle_tools_list(&self, id: &Value) -> Value {
+        let structured = self.speaks(STRUCTURED_OUTPUT_VERSION);
         let tools_json = self
             .tools
             .iter()
             .map(|tool| {
-                if let Some(output_schema) = tool.output_schema() {
+                if let Some(output_schema) = tool.output_schema().filter(|_| structured) {
                     json!({
                         "name": tool.name(),
                         "description": tool.description(),
@@ -106,7 +418,64 @@ impl McpServer {
         Self::jsonrpc_success(id, json!({ "tools": tools_json }))
     }
 
-    async fn handle_tools_call(&self, id: &Value, params: &Value) -> Value {
+    fn handle_resource_templates_list(&self, id: &Value) -> Value {
+        let templates = self
+            .resource_templates
+            .iter()
+            .map(ResourceTemplate::to_json)
+            .collect::<Vec<_>>();
+
+        Self::jsonrpc_success(id, json!({ "resourceTemplates": templates }))
+    }
+
+    async fn handle_resources_read(&self, id: &Value, params: &Value) -> Value {
+        let Some(uri) = params.get("uri").and_then(Value::as_str) else {
+            return Self::jsonrpc_error(id, -32602, "missing 'uri' parameter");
+        };
+
+        for template in &self.resource_templates {
+            match template.read(uri).await {
+                Some(Ok(contents)) => {
+                    return Self::jsonrpc_success(id, json!({ "contents": [contents] }));
+                }
+                Some(Err(e)) => return Self::jsonrpc_error(id, -32603, &e.to_string()),
+                None => {}
+            }
+        }
+        Self::jsonrpc_error(id, -32002, &format!("resource '{uri}' not found"))
+    }
+
+    async fn handle_completion(&self, id: &Value, params: &Value) -> Value {
+        let reference = params.get("ref").unwrap_or(&Value::Null);
+        let argument = params.get("argument").unwrap_or(&Value::Null);
+        let (Some(name), Some(value)) = (
+            argument.get("name").and_then(Value::as_str),
+            argument.get("value").and_then(Value::as_str),
+        ) else {
+            return Self::jsonrpc_error(id, -32602, "missing 'argument' parameter");
+        };
+
+        let completion = match reference.get("type").and_then(Value::as_str) {
+            Some("ref/resource") => {
+                let uri = reference.get("uri").and_then(Value::as_str);
+                let template = self
+                    .resource_templates
+                    .iter()
+                    .find(|t| Some(t.uri_template()) == uri);
+                match template {
+                    Some(template) => template.completions(name, value).await,
+                    None => None,
+                }
+            }
+            _ => None,
+        };
+
+        let completion =
+            completion.unwrap_or_else(|| json!({ "values": [], "total": 0, "hasMore": false }));
+        Self::jsonrpc_success(id, json!({ "completion": completion }))
+    }
+
+    async fn handle_tools_call(&self, id: &Value, params: &Value, ctx: ToolContext) -> Value {
         let tool_name = match params.get("name").and_then(|v| v.as_str()) {
             Some(name) => name,
             None => return Self::jsonrpc_error(id, -32602, "missing 'name' parameter"),
@@ -124,12 +493,67 @@ impl McpServer {
             .get("arguments")
             .cloned()
             .unwrap_or_else(|| json!({}));
-        let input = ToolInput::new(arguments);
+        let input = ToolInput::new(arguments.clone());
+        let ctx = ctx.with_protocol_version(self.protocol_version());
+
+        let invocation_id = crate::util::uuid_v7();
+        let started = Instant::now();
+        let result = tool.call_isolated_with_context(input, ctx).await;
+        let duration = started.elapsed();
+        tracing::debug!(
+            server = %self.name,
+            tool = tool_name,
+            invocation_id,
+            duration_ms = duration.as_millis() as u64,
+            "tool call finished"
+        );
+        if let Some(Observer(observer)) = &self.observer {
+            observer(&ToolInvocation {
+                id: invocation_id.clone(),
+                tool: tool_name.to_owned(),
+                arguments: arguments.clone(),
+                duration,
+                error: result.as_ref().err().map(|e| e.kind()),
+            });
+        }
+        let failed = match &result {
+            Ok(content) => is_error_result(content),
+            Err(_) => true,
+        };
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(tool_name.to_owned())
+            .or_default()
+            .record(arguments, duration, failed);
+
+        let mut response = self.tool_result(id, tool, result).await;
+        if let Some(result) = response.get_mut("result").and_then(Value::as_object_mut) {
+            result.insert(
+                "_meta".to_owned(),
+                json!({
+                    INVOCATION_ID_META_KEY: invocation_id,
+                    DURATION_META_KEY: duration.as_millis() as u64,
+                }),
+            );
+        }
+        response
+    }
 
-        match tool.call(input).await {
+    async fn tool_result(
+        &self,
+        id: &Value,
+        tool: &Tool,
+        result: Result<Value, ToolError>,
+    ) -> Value {
+        match result {
             Ok(content) => Self::jsonrpc_success(
                 id,
                 if tool.output_schema().is_none() {
+                    let content = match &self.spill {
+                        Some(spill) => spill.apply(tool.name(), content).await,
+                        None => content,
+                    };
                     json!({ "content": content })
                 } else {
                     let Ok(text_content) = serde_json::to_string(&content) else {
@@ -142,19 +566,27 @@ impl McpServer {
                         );
                     };
 
-                    json!({
-                        "content": [{
-                            "type": "text",
-                            "text": text_content,
-                        }],
-                        "structuredContent": content,
-                    })
+                    if self.speaks(STRUCTURED_OUTPUT_VERSION) {
+                        json!({
+                            "content": [{
+                                "type": "text",
+                                "text": text_content,
+                            }],
+                            "structuredContent": content,
+                        })
+                    } else {
+                        json!({ "content": [{ "type": "text", "text": text_content }] })
+                    }
                 },
             ),
             Err(err) => Self::jsonrpc_success(
                 id,
                 json!({
-                    "content": [{"type": "text", "text": err.to_string()}],
+                    "content": [{
+                        "type": "text",
+                        "text": err.to_string(),
+                        "_meta": { ERROR_KIND_META_KEY: err.kind().as_str() },
+                    }],
                     "isError": true
                 }),
             ),
@@ -162,6 +594,12 @@ impl McpServer {
     }
 
     pub async fn handle_json_message(&self, msg: &Value) -> Value {
+        self.handle_json_message_with(msg, ToolContext::default())
+            .await
+    }
+
+    /// Handles `msg`, passing `ctx` to any tool it calls.
+    pub async fn handle_json_message_with(&self, msg: &Value, ctx: ToolContext) -> Value {
         let method = msg
             .get("method")
             .and_then(|v| v.as_str())
@@ -170,11 +608,453 @@ impl McpServer {
         let id = msg.get("id").cloned().unwrap_or(Value::Null);
 
         match method {
-            "initialize" => self.handle_initialize(&id),
+            "initialize" => self.handle_initialize(&id, &params),
+            "ping" => Self::jsonrpc_success(&id, json!({})),
             "tools/list" => self.handle_tools_list(&id),
-            "tools/call" => self.handle_tools_call(&id, &params).await,
+            "tools/call" => self.handle_tools_call(&id, &params, ctx).await,
+            "resources/list" => Self::jsonrpc_success(&id, json!({ "resources": [] })),
+            "resources/templates/list" => self.handle_resource_templates_list(&id),
+            "resources/read" => self.handle_resources_read(&id, &params).await,
+            "completion/complete" => self.handle_completion(&id, &params).await,
             method if method.starts_with("notifications/") => Value::Null,
             _ => Self::jsonrpc_error(&id, -32601, &format!("method '{method}' not found")),
         }
     }
 }
+
+/// Whether `msg` calls a tool, which may take a while.
+/// One Markdown list item per property of an input schema, required ones
+/// first, each by name.
+fn describe_arguments(schema: &Value) -> String {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return String::new();
+    };
+    let required = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut properties = properties.iter().collect::<Vec<_>>();
+    properties.sort_by_key(|(name, _)| (!required.contains(&name.as_str()), *name));
+
+    let mut out = String::new();
+    for (name, property) in properties {
+        let kind = match property.get("type") {
+            Some(Value::String(kind)) => kind.clone(),
+            Some(Value::Array(kinds)) => kinds
+                .iter()
+                .filter_map(Value::as_str)
+                .filter(|kind| *kind != "null")
+                .collect::<Vec<_>>()
+                .join(" or "),
+            _ => "any".to_owned(),
+        };
+        out.push_str(&format!("- `{name}` ({kind}"));
+        if required.contains(&name.as_str()) {
+            out.push_str(", required");
+        }
+        out.push(')');
+        if let Some(description) = property.get("description").and_then(Value::as_str) {
+            out.push_str(&format!(": {description}"));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub(crate) fn is_tool_call(msg: &Value) -> bool {
+    msg.get("method").and_then(Value::as_str) == Some("tools/call")
+}
+
+impl OutputSpill {
+    const PREVIEW_BYTES: usize = 2048;
+
+    async fn apply(&self, tool_name: &str, content: Value) -> Value {
+        let size = content.to_string().len();
+        if size <= self.max_bytes {
+            return content;
+        }
+
+        let full = Self::render(&content);
+        let mut end = full.len().min(self.max_bytes).min(Self::PREVIEW_BYTES);
+        while !full.is_char_boundary(end) {
+            end -= 1;
+        }
+        let preview = &full[..end];
+
+        let summary = match self.write(tool_name, &full).await {
+            Ok(path) => format!(
+                "Output of '{tool_name}' was {size} bytes, over the {} byte limit. \
+                 The full output was written to {}.\n\n{preview}",
+                self.max_bytes,
+                path.display(),
+            ),
+            Err(e) => {
+                tracing::warn!(tool = tool_name, error = %e, "failed to spill tool output");
+                format!(
+                    "Output of '{tool_name}' was {size} bytes, over the {} byte limit, \
+                     and has been truncated.\n\n{preview}",
+                    self.max_bytes,
+                )
+            }
+        };
+
+        json!([{"type": "text", "text": summary}])
+    }
+
+    /// Flattens content blocks to text, keeping non-text blocks as JSON lines.
+    fn render(content: &Value) -> String {
+        let Some(blocks) = content.as_array() else {
+            return content.to_string();
+        };
+
+        blocks
+            .iter()
+            .map(|block| match block.get("text").and_then(Value::as_str) {
+                Some(text) if block.get("type").is_some_and(|t| t == "text") => text.to_owned(),
+                _ => block.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    async fn write(&self, tool_name: &str, output: &str) -> std::io::Result<PathBuf> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let file_name = format!("{}-{}.txt", sanitize(tool_name), crate::util::uuid_v7());
+        let path = Path::new(&self.dir).join(file_name);
+        tokio::fs::write(&path, output).await?;
+        Ok(path)
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_oversized_output_is_spilled_to_file() {
+        let dir = std::env::temp_dir().join(format!("clauders-spill-{}", crate::util::uuid_v7()));
+        let tool = Tool::new("big", "big output", json!({}), None, |_| async {
+            Ok(Tool::text_result(&"x".repeat(1000)))
+        });
+        let server = McpServer::new("test", vec![tool]).with_output_spill(100, &dir);
+
+        let response = server
+            .handle_tools_call(&json!(1), &json!({"name": "big"}), ToolContext::default())
+            .await;
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("over the 100 byte limit"));
+
+        let spilled = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(spilled.path()).unwrap(),
+            "x".repeat(1000)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_describe_for_prompt_lists_tools_and_arguments() {
+        let ping = Tool::new(
+            "ping",
+            "Ping a host",
+            json!({
+                "type": "object",
+                "properties": {
+                    "count": {"type": ["integer", "null"]},
+                    "host": {"type": "string", "description": "Host to ping"}
+                },
+                "required": ["host"]
+            }),
+            None,
+            |_| async { Ok(Tool::text_result("")) },
+        );
+        let uptime = Tool::new("uptime", "", json!({}), None, |_| async {
+            Ok(Tool::text_result(""))
+        });
+        let server = McpServer::new("net", vec![ping, uptime]);
+        assert_eq!(
+            server.describe_for_prompt(),
+            "### mcp__net__ping\n\
+             Ping a host\n\
+             \n\
+             Arguments:\n\
+             - `host` (string, required): Host to ping\n\
+             - `count` (integer)\n\
+             \n\
+             ### mcp__net__uptime\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_are_recorded_in_stats() {
+        let tool = Tool::new(
+            "flaky",
+            "fails on odd input",
+            json!({}),
+            None,
+            |input| async move {
+                match input.get_i64("n") {
+                    Some(n) if n % 2 == 1 => Err(crate::tool::ToolError::msg("odd")),
+                    _ => Ok(Tool::text_result("ok")),
+                }
+            },
+        );
+        let server = McpServer::new("test", vec![tool]);
+
+        for n in 0..4 {
+            server
+                .handle_tools_call(
+                    &json!(n),
+                    &json!({"name": "flaky", "arguments": {"n": n}}),
+                    ToolContext::default(),
+                )
+                .await;
+        }
+
+        let stats = server.tool_stats("flaky").unwrap();
+        assert_eq!(stats.calls(), 4);
+        assert_eq!(stats.errors(), 2);
+        assert_eq!(stats.error_rate(), 0.5);
+        assert_eq!(stats.last_input(), Some(&json!({"n": 3})));
+    }
+
+    #[tokio::test]
+    async fn test_error_results_are_recorded_as_errors() {
+        let tool = Tool::new(
+            "picky",
+            "rejects odd input",
+            json!({}),
+            None,
+            |input| async move {
+                match input.get_i64("n") {
+                    Some(n) if n % 2 == 1 => Ok(Tool::error_result("odd")),
+                    _ => Ok(Tool::text_result("ok")),
+                }
+            },
+        );
+        let server = McpServer::new("test", vec![tool]);
+
+        for n in 0..4 {
+            server
+                .handle_tools_call(
+                    &json!(n),
+                    &json!({"name": "picky", "arguments": {"n": n}}),
+                    ToolContext::default(),
+                )
+                .await;
+        }
+
+        let stats = server.tool_stats("picky").unwrap();
+        assert_eq!(stats.calls(), 4);
+        assert_eq!(stats.errors(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tool_results_carry_invocation_meta() {
+        let tool = Tool::new("echo", "echoes", json!({}), None, |_| async {
+            Ok(Tool::text_result("hi"))
+        });
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let server = McpServer::new("test", vec![tool]).on_call({
+            let observed = observed.clone();
+            move |invocation| observed.lock().unwrap().push(invocation.clone())
+        });
+
+        let response = server
+            .handle_tools_call(
+                &json!(1),
+                &json!({"name": "echo", "arguments": {"a": 1}}),
+                ToolContext::default(),
+            )
+            .await;
+        let meta = &response["result"]["_meta"];
+        assert!(meta[DURATION_META_KEY].is_u64());
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(observed.len(), 1);
+        assert_eq!(meta[INVOCATION_ID_META_KEY], observed[0].id());
+        assert_eq!(observed[0].tool(), "echo");
+        assert_eq!(observed[0].arguments(), &json!({"a": 1}));
+        assert_eq!(observed[0].error(), None);
+    }
+
+    #[tokio::test]
+    async fn test_resource_templates_and_completion() {
+        let template = ResourceTemplate::new("ticket://{id}", "ticket", |args| async move {
+            Ok(format!("ticket {}", args["id"]))
+        })
+        .complete("id", |partial| async move {
+            ["ABC-1", "ABC-2", "XYZ-9"]
+                .into_iter()
+                .filter(|id| id.starts_with(&partial))
+                .map(str::to_owned)
+                .collect()
+        });
+        let server = McpServer::new("test", vec![]).with_resource_templates(vec![template]);
+
+        let init = server
+            .handle_json_message(&json!({"id": 0, "method": "initialize"}))
+            .await;
+        assert!(init["result"]["capabilities"]["completions"].is_object());
+
+        let list = server
+            .handle_json_message(&json!({"id": 1, "method": "resources/templates/list"}))
+            .await;
+        assert_eq!(
+            list["result"]["resourceTemplates"][0]["uriTemplate"],
+            "ticket://{id}"
+        );
+
+        let read = server
+            .handle_json_message(&json!({
+                "id": 2,
+                "method": "resources/read",
+                "params": {"uri": "ticket://ABC-2"}
+            }))
+            .await;
+        assert_eq!(read["result"]["contents"][0]["text"], "ticket ABC-2");
+
+        let completion = server
+            .handle_json_message(&json!({
+                "id": 3,
+                "method": "completion/complete",
+                "params": {
+                    "ref": {"type": "ref/resource", "uri": "ticket://{id}"},
+                    "argument": {"name": "id", "value": "ABC"}
+                }
+            }))
+            .await;
+        assert_eq!(
+            completion["result"]["completion"]["values"],
+            json!(["ABC-1", "ABC-2"])
+        );
+
+        let prompt = server
+            .handle_json_message(&json!({
+                "id": 4,
+                "method": "completion/complete",
+                "params": {
+                    "ref": {"type": "ref/prompt", "name": "review"},
+                    "argument": {"name": "file", "value": ""}
+                }
+            }))
+            .await;
+        assert_eq!(prompt["result"]["completion"]["values"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_protocol_version_negotiation() {
+        let tool = Tool::with_context(
+            "version",
+            "reports the protocol version",
+            json!({}),
+            json!({"type": "object", "properties": {"total": {"type": "integer"}}}),
+            |_, ctx| async move {
+                assert_eq!(ctx.protocol_version(), Some("2025-03-26"));
+                Ok(json!({"total": 1}))
+            },
+        );
+        let server = McpServer::new("test", vec![tool]);
+        let initialize = |version: &str| json!({"id": 0, "method": "initialize", "params": {"protocolVersion": version}});
+
+        let init = server.handle_json_message(&initialize("1999-01-01")).await;
+        assert_eq!(
+            init["result"]["protocolVersion"],
+            SUPPORTED_PROTOCOL_VERSIONS[0]
+        );
+
+        let init = server.handle_json_message(&initialize("2025-03-26")).await;
+        assert_eq!(init["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(server.protocol_version().as_deref(), Some("2025-03-26"));
+
+        let list = server
+            .handle_json_message(&json!({"id": 1, "method": "tools/list"}))
+            .await;
+        assert!(list["result"]["tools"][0].get("outputSchema").is_none());
+        let call = server
+            .handle_json_message(
+                &json!({"id": 2, "method": "tools/call", "params": {"name": "version"}}),
+            )
+            .await;
+        assert!(call["result"].get("structuredContent").is_none());
+        assert_eq!(call["result"]["content"][0]["text"], r#"{"total":1}"#);
+    }
+
+    #[tokio::test]
+    async fn test_ping_and_shutdown_hooks() {
+        let closed = Arc::new(Mutex::new(Vec::new()));
+        let server = McpServer::new("test", vec![])
+            .on_shutdown({
+                let closed = closed.clone();
+                move || {
+                    let closed = closed.clone();
+                    async move { closed.lock().unwrap().push("db") }
+                }
+            })
+            .on_shutdown({
+                let closed = closed.clone();
+                move || {
+                    let closed = closed.clone();
+                    async move { closed.lock().unwrap().push("files") }
+                }
+            });
+
+        let pong = server
+            .handle_json_message(&json!({"jsonrpc": "2.0", "id": 7, "method": "ping"}))
+            .await;
+        assert_eq!(pong, json!({"jsonrpc": "2.0", "id": 7, "result": {}}));
+
+        server.shutdown().await;
+        assert_eq!(*closed.lock().unwrap(), ["db", "files"]);
+    }
+
+    #[tokio::test]
+    async fn test_tools_see_declared_roots() {
+        let tool = Tool::with_context(
+            "read",
+            "reads a file",
+            json!({}),
+            None,
+            |input, ctx| async move {
+                let path = input.get_string("path").unwrap_or_default();
+                if ctx.is_within_roots(path) {
+                    Ok(Tool::text_result("ok"))
+                } else {
+                    Err(ToolError::PermissionDenied(path.to_owned()))
+                }
+            },
+        );
+        let server = McpServer::new("test", vec![tool]);
+        let ctx = ToolContext::new().with_roots(["/work/repo"]);
+
+        let call = |path: &str| {
+            json!({
+                "id": 1,
+                "method": "tools/call",
+                "params": {"name": "read", "arguments": {"path": path}}
+            })
+        };
+        let inside = server
+            .handle_json_message_with(&call("/work/repo/src/lib.rs"), ctx.clone())
+            .await;
+        assert!(inside["result"].get("isError").is_none());
+        let outside = server
+            .handle_json_message_with(&call("/etc/passwd"), ctx)
+            .await;
+        assert_eq!(outside["result"]["isError"], true);
+    }
+}
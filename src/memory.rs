@@ -0,0 +1,407 @@
+//! Persistent notes that agents can recall across sessions.
+//!
+//! A [`MemoryStore`] keeps notes keyed by [`Scope`]. [`MemoryHooks`] wires a
+//! store into a session: a UserPromptSubmit hook adds notes relevant to each
+//! prompt as additional context, and a Stop hook saves facts Claude marked
+//! with `<remember>` tags in its replies.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use clauders::memory::{FileMemory, MemoryHooks};
+//! use clauders::{Client, Hooks, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let store = Arc::new(FileMemory::new(".agent/memory"));
+//! let hooks = MemoryHooks::new(store).project("clauders").register(Hooks::new());
+//! let client = Client::new(Options::new().hooks(hooks)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::error::Error;
+use crate::hooks::{Hooks, StopOutput, UserPromptSubmitOutput};
+
+/// Hook name of the UserPromptSubmit hook registered by [`MemoryHooks`].
+pub const RECALL_HOOK: &str = "memory_recall";
+/// Hook name of the Stop hook registered by [`MemoryHooks`].
+pub const SAVE_HOOK: &str = "memory_save";
+
+/// The set of notes a key belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Notes for a single CLI session.
+    Session(String),
+    /// Notes shared by every session working on a project.
+    Project(String),
+}
+
+impl Scope {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Session(_) => "session",
+            Self::Project(_) => "project",
+        }
+    }
+
+    fn id(&self) -> &str {
+        match self {
+            Self::Session(id) | Self::Project(id) => id,
+        }
+    }
+}
+
+/// A stored note.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    key: String,
+    content: String,
+    updated_at: u64,
+}
+
+impl Note {
+    pub fn new(key: impl Into<String>, content: impl Into<String>) -> Self {
+        let updated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self {
+            key: key.into(),
+            content: content.into(),
+            updated_at,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Seconds since the Unix epoch when the note was last written.
+    pub fn updated_at(&self) -> u64 {
+        self.updated_at
+    }
+}
+
+/// Storage for notes.
+#[async_trait]
+pub trait MemoryStore: Send + Sync {
+    async fn get(&self, scope: &Scope, key: &str) -> Result<Option<Note>, Error>;
+
+    /// Creates or replaces the note stored under `key`.
+    async fn set(&self, scope: &Scope, key: &str, content: &str) -> Result<(), Error>;
+
+    /// Returns up to `limit` notes relevant to `query`, most relevant first.
+    async fn search(&self, scope: &Scope, query: &str, limit: usize) -> Result<Vec<Note>, Error>;
+}
+
+/// A [`MemoryStore`] keeping each scope's notes in a JSON file under a
+/// directory, at `<root>/<session|project>/<id>.json`.
+///
+/// Search ranks notes by how many words of the query appear in their key or
+/// content.
+#[derive(Debug)]
+pub struct FileMemory {
+    root: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileMemory {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn path(&self, scope: &Scope) -> PathBuf {
+        let id = scope
+            .id()
+            .chars()
+            .map(|c| match c {
+                c if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') => c,
+                _ => '_',
+            })
+            .collect::<String>();
+        self.root.join(scope.kind()).join(format!("{id}.json"))
+    }
+
+    async fn load(&self, scope: &Scope) -> Result<BTreeMap<String, Note>, Error> {
+        match tokio::fs::read(self.path(scope)).await {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl MemoryStore for FileMemory {
+    async fn get(&self, scope: &Scope, key: &str) -> Result<Option<Note>, Error> {
+        let _guard = self.lock.lock().await;
+        Ok(self.load(scope).await?.remove(key))
+    }
+
+    async fn set(&self, scope: &Scope, key: &str, content: &str) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let mut notes = self.load(scope).await?;
+        notes.insert(key.to_owned(), Note::new(key, content));
+
+        let path = self.path(scope);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, serde_json::to_vec_pretty(&notes)?).await?;
+        Ok(())
+    }
+
+    async fn search(&self, scope: &Scope, query: &str, limit: usize) -> Result<Vec<Note>, Error> {
+        let _guard = self.lock.lock().await;
+        let words = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.len() > 2)
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+
+        let mut scored = self
+            .load(scope)
+            .await?
+            .into_values()
+            .filter_map(|note| {
+                let text = format!("{} {}", note.key, note.content).to_lowercase();
+                let score = words.iter().filter(|word| text.contains(*word)).count();
+                (score > 0).then_some((score, note))
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|(a, x), (b, y)| b.cmp(a).then(y.updated_at.cmp(&x.updated_at)));
+        Ok(scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, note)| note)
+            .collect())
+    }
+}
+
+/// Hooks connecting a [`MemoryStore`] to a session.
+///
+/// Notes are scoped to the CLI session unless a project is set.
+#[derive(Clone)]
+pub struct MemoryHooks {
+    store: Arc<dyn MemoryStore>,
+    project: Option<String>,
+    limit: usize,
+}
+
+impl MemoryHooks {
+    pub fn new(store: Arc<dyn MemoryStore>) -> Self {
+        Self {
+            store,
+            project: None,
+            limit: 5,
+        }
+    }
+
+    /// Shares notes across every session working on `project`.
+    #[must_use]
+    pub fn project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+
+    /// Sets how many notes are added to each prompt. Defaults to 5.
+    #[must_use]
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    fn scope(&self, session_id: &str) -> Scope {
+        match &self.project {
+            Some(project) => Scope::Project(project.clone()),
+            None => Scope::Session(session_id.to_owned()),
+        }
+    }
+
+    /// Adds the recall and save hooks to `hooks`.
+    pub fn register(self, mut hooks: Hooks) -> Hooks {
+        let recall = self.clone();
+        hooks.add_user_prompt_submit_named(RECALL_HOOK, move |input| {
+            let hooks = recall.clone();
+            async move {
+                let scope = hooks.scope(input.session_id());
+                match hooks
+                    .store
+                    .search(&scope, input.prompt(), hooks.limit)
+                    .await
+                {
+                    Ok(notes) => UserPromptSubmitOutput::pass()
+                        .with_additional_context(recall_context(&notes)),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to search memory");
+                        UserPromptSubmitOutput::pass()
+                    }
+                }
+            }
+        });
+
+        hooks.add_stop_named(SAVE_HOOK, move |input| {
+            let hooks = self.clone();
+            async move {
+                let scope = hooks.scope(input.session_id());
+                let facts = match tokio::fs::read_to_string(input.transcript_path()).await {
+                    Ok(transcript) => remembered_facts(&last_assistant_text(&transcript)),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to read transcript for memory");
+                        Vec::new()
+                    }
+                };
+                for (key, content) in facts {
+                    if let Err(e) = hooks.store.set(&scope, &key, &content).await {
+                        tracing::warn!(error = %e, key, "failed to save memory");
+                    }
+                }
+                StopOutput::pass()
+            }
+        });
+
+        hooks
+    }
+}
+
+fn recall_context(notes: &[Note]) -> String {
+    let mut context = String::from(
+        "To remember a fact for later sessions, write it in your reply as \
+         <remember key=\"short-key\">the fact</remember>.",
+    );
+    if !notes.is_empty() {
+        context.push_str("\n\nRelevant notes from memory:");
+        for note in notes {
+            context.push_str(&format!("\n- {}: {}", note.key, note.content));
+        }
+    }
+    context
+}
+
+/// Returns the text Claude wrote since the last user prompt in a transcript.
+fn last_assistant_text(transcript: &str) -> String {
+    let mut text = Vec::new();
+    for entry in transcript
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+    {
+        let blocks = entry
+            .pointer("/message/content")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        match entry.get("type").and_then(Value::as_str) {
+            Some("user") if blocks.clone().all(|b| b["type"] != "tool_result") => text.clear(),
+            Some("assistant") => text.extend(
+                blocks
+                    .filter(|b| b["type"] == "text")
+                    .filter_map(|b| b["text"].as_str().map(str::to_owned)),
+            ),
+            _ => {}
+        }
+    }
+    text.join("\n")
+}
+
+/// Extracts `<remember key="...">fact</remember>` tags, deriving a key from
+/// the fact when none is given.
+fn remembered_facts(text: &str) -> Vec<(String, String)> {
+    let mut facts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<remember") {
+        let after = &rest[start + "<remember".len()..];
+        let Some(close) = after.find('>') else { break };
+        let Some(end) = after[close..].find("</remember>") else {
+            break;
+        };
+        let attrs = &after[..close];
+        let content = after[close + 1..close + end].trim();
+        rest = &after[close + end + "</remember>".len()..];
+
+        if content.is_empty() {
+            continue;
+        }
+        let key = attrs
+            .split_once("key=\"")
+            .and_then(|(_, value)| value.split_once('"'))
+            .map(|(key, _)| key.trim().to_owned())
+            .filter(|key| !key.is_empty())
+            .unwrap_or_else(|| {
+                content
+                    .split_whitespace()
+                    .take(6)
+                    .collect::<Vec<_>>()
+                    .join("-")
+                    .to_lowercase()
+            });
+        facts.push((key, content.to_owned()));
+    }
+    facts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_memory_round_trip_and_search() {
+        let dir = std::env::temp_dir().join(format!("clauders-memory-{}", crate::util::uuid_v7()));
+        let store = FileMemory::new(&dir);
+        let scope = Scope::Project("demo/app".to_owned());
+
+        store
+            .set(&scope, "build", "Run cargo build --release")
+            .await
+            .unwrap();
+        store
+            .set(&scope, "style", "Prefer early returns")
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get(&scope, "build").await.unwrap().unwrap().content(),
+            "Run cargo build --release"
+        );
+        assert!(
+            store
+                .get(&Scope::Session("s".into()), "build")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let found = store.search(&scope, "how do I build it?", 5).await.unwrap();
+        assert_eq!(found.iter().map(Note::key).collect::<Vec<_>>(), ["build"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remembered_facts() {
+        let text = "Done. <remember key=\"db\">Tests need DATABASE_URL set</remember> \
+                    and <remember>Use pnpm not npm</remember><remember key=\"x\"> </remember>";
+        assert_eq!(
+            remembered_facts(text),
+            [
+                ("db".to_owned(), "Tests need DATABASE_URL set".to_owned()),
+                ("use-pnpm-not-npm".to_owned(), "Use pnpm not npm".to_owned()),
+            ]
+        );
+    }
+}
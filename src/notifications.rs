@@ -0,0 +1,341 @@
+//! Chat notifications for people watching a session.
+//!
+//! With the `notifications` feature, a [`NotificationSink`] delivers short
+//! [`Notification`]s to a channel humans read. [`SlackWebhook`] and
+//! [`DiscordWebhook`] post to incoming webhooks; implement the trait for
+//! anything else. Attach a sink to a session with [`NotificationHooks`],
+//! which notifies when Claude stops responding, or [`CompletionNotifier`],
+//! a [`Handler`] that notifies when a turn completes or fails.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use clauders::notifications::{NotificationHooks, SlackWebhook};
+//! use clauders::{Client, Hooks, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let slack = Arc::new(SlackWebhook::new("https://hooks.slack.com/services/T000/B000/XXXX"));
+//! let hooks = NotificationHooks::new(slack).register(Hooks::new());
+//! let client = Client::new(Options::new().hooks(hooks)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{Value, json};
+
+use crate::error::Error;
+use crate::handler::Handler;
+use crate::hooks::{Hooks, StopOutput};
+use crate::response::{CompleteResponse, ErrorResponse};
+
+/// Hook name of the Stop hook registered by [`NotificationHooks`].
+pub const STOP_HOOK: &str = "notify_stop";
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a notification should stand out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Level {
+    #[default]
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn emoji(self) -> &'static str {
+        match self {
+            Self::Info => ":information_source:",
+            Self::Success => ":white_check_mark:",
+            Self::Warning => ":warning:",
+            Self::Error => ":x:",
+        }
+    }
+
+    fn color(self) -> u32 {
+        match self {
+            Self::Info => 0x3498db,
+            Self::Success => 0x2ecc71,
+            Self::Warning => 0xf1c40f,
+            Self::Error => 0xe74c3c,
+        }
+    }
+}
+
+/// A message for a human, with optional labelled fields.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Notification {
+    title: String,
+    body: String,
+    level: Level,
+    fields: Vec<(String, String)>,
+}
+
+impl Notification {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Summarises a completed turn: its outcome, cost, turns and duration.
+    pub fn from_completion(complete: &CompleteResponse) -> Self {
+        let (title, level) = if complete.is_error() {
+            ("Claude turn failed", Level::Error)
+        } else {
+            ("Claude turn completed", Level::Success)
+        };
+        let mut notification = Self::new(title)
+            .with_level(level)
+            .with_body(complete.result_text().unwrap_or_default())
+            .with_field("Session", complete.session_id())
+            .with_field("Turns", complete.num_turns().to_string())
+            .with_field(
+                "Duration",
+                format!("{:.1}s", complete.duration_ms() as f64 / 1000.0),
+            );
+        if let Some(cost) = complete.total_cost_usd() {
+            notification = notification.with_field("Cost", format!("${cost:.4}"));
+        }
+        let denied = complete.denied_tools();
+        if !denied.is_empty() {
+            notification = notification.with_field("Denied tools", denied.join(", "));
+        }
+        notification
+    }
+
+    #[must_use]
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    #[must_use]
+    pub fn with_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+}
+
+/// Somewhere notifications are delivered.
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    async fn send(&self, notification: &Notification) -> Result<(), Error>;
+}
+
+/// Posts notifications to a Slack incoming webhook.
+#[derive(Debug, Clone)]
+pub struct SlackWebhook {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl SlackWebhook {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: http_client(),
+        }
+    }
+
+    fn payload(notification: &Notification) -> Value {
+        let mut text = format!("{} *{}*", notification.level.emoji(), notification.title);
+        if !notification.body.is_empty() {
+            text.push_str(&format!("\n{}", notification.body));
+        }
+        for (name, value) in &notification.fields {
+            text.push_str(&format!("\n*{name}:* {value}"));
+        }
+        json!({ "text": text })
+    }
+}
+
+#[async_trait]
+impl NotificationSink for SlackWebhook {
+    async fn send(&self, notification: &Notification) -> Result<(), Error> {
+        post(&self.http, &self.url, &Self::payload(notification)).await
+    }
+}
+
+/// Posts notifications to a Discord webhook as embeds.
+#[derive(Debug, Clone)]
+pub struct DiscordWebhook {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl DiscordWebhook {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: http_client(),
+        }
+    }
+
+    fn payload(notification: &Notification) -> Value {
+        let fields = notification
+            .fields
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": value, "inline": true }))
+            .collect::<Vec<_>>();
+        json!({
+            "embeds": [{
+                "title": notification.title,
+                "description": notification.body,
+                "color": notification.level.color(),
+                "fields": fields,
+            }]
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationSink for DiscordWebhook {
+    async fn send(&self, notification: &Notification) -> Result<(), Error> {
+        post(&self.http, &self.url, &Self::payload(notification)).await
+    }
+}
+
+/// Notifies a sink whenever Claude stops responding, so someone can check
+/// on the session or approve its next step.
+#[derive(Clone)]
+pub struct NotificationHooks {
+    sink: Arc<dyn NotificationSink>,
+    title: String,
+}
+
+impl NotificationHooks {
+    pub fn new(sink: Arc<dyn NotificationSink>) -> Self {
+        Self {
+            sink,
+            title: "Claude is waiting for input".to_owned(),
+        }
+    }
+
+    /// The title of the notification sent on stop.
+    #[must_use]
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Adds the Stop hook to `hooks`. Delivery failures are logged and do
+    /// not affect the session.
+    pub fn register(self, mut hooks: Hooks) -> Hooks {
+        hooks.add_stop_named(STOP_HOOK, move |input| {
+            let hooks = self.clone();
+            async move {
+                let notification = Notification::new(hooks.title.as_str())
+                    .with_field("Session", input.session_id());
+                if let Err(e) = hooks.sink.send(&notification).await {
+                    tracing::warn!(error = %e, "failed to send stop notification");
+                }
+                StopOutput::pass()
+            }
+        });
+        hooks
+    }
+}
+
+/// A [`Handler`] that notifies a sink when a turn completes or an error is
+/// reported.
+pub struct CompletionNotifier {
+    sink: Arc<dyn NotificationSink>,
+}
+
+impl CompletionNotifier {
+    pub fn new(sink: Arc<dyn NotificationSink>) -> Self {
+        Self { sink }
+    }
+
+    async fn send(&self, notification: Notification) {
+        if let Err(e) = self.sink.send(&notification).await {
+            tracing::warn!(error = %e, "failed to send notification");
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for CompletionNotifier {
+    async fn on_error(&self, error: &ErrorResponse) {
+        self.send(
+            Notification::new("Claude reported an error")
+                .with_level(Level::Error)
+                .with_body(error.message()),
+        )
+        .await;
+    }
+
+    async fn on_complete(&self, complete: &CompleteResponse) {
+        self.send(Notification::from_completion(complete)).await;
+    }
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+async fn post(http: &reqwest::Client, url: &str, payload: &Value) -> Result<(), Error> {
+    http.post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| Error::ConnectionError(format!("notification delivery failed: {e}")))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payloads() {
+        let notification = Notification::new("Done")
+            .with_level(Level::Success)
+            .with_body("All tests pass.")
+            .with_field("Cost", "$0.12");
+
+        assert_eq!(
+            SlackWebhook::payload(&notification)["text"],
+            ":white_check_mark: *Done*\nAll tests pass.\n*Cost:* $0.12"
+        );
+        let discord = DiscordWebhook::payload(&notification);
+        assert_eq!(discord["embeds"][0]["color"], 0x2ecc71);
+        assert_eq!(discord["embeds"][0]["fields"][0]["name"], "Cost");
+    }
+}
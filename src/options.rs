@@ -1,16 +1,54 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
+#[cfg(feature = "schema")]
 use schemars::JsonSchema;
 
 use crate::agent::Agent;
+use crate::approvals::ApprovalBroker;
+use crate::auth::{AuthRefresh, AuthSource};
+use crate::denials::{DenialEvent, DenialReporter};
+use crate::deterministic::{Clock, IdGenerator};
+use crate::error::ConfigError;
+use crate::handler::DispatchQueue;
 use crate::hooks::Hooks;
+use crate::hooks::path_guard::{PATH_GUARD_MATCHER, PathGuard};
+use crate::hooks::read_only::{READ_ONLY_MATCHER, ReadOnlyGuard, WRITE_TOOLS};
+use crate::hooks::secrets::{SECRET_SCAN_MATCHER, SecretScanner};
+use crate::limits::{Limits, ProcessLimits};
 use crate::mcp_server::McpServer;
 use crate::model::Model;
+use crate::network::Network;
+use crate::permissions::{BypassAcknowledgement, PermissionCallbacks};
+#[cfg(feature = "pii")]
+use crate::pii::PiiDetector;
+#[cfg(feature = "policy-file")]
+use crate::policy::Policy;
+use crate::profile::{PermissionProfile, TurnProfile};
 use crate::proto::PermissionMode;
-use crate::transport::TransportOptions;
+use crate::provider::Provider;
+use crate::quota::QuotaBucket;
+use crate::sandbox::Sandbox;
+use crate::tenant::{INHERITED_ENV, Tenant, Workspace};
+use crate::thinking::{Effort, MIN_THINKING_BUDGET, ThinkingConfig};
+use crate::tool::{BuiltinTool, ToolName, ToolSelector};
+use crate::transport::{CommandPreview, Transport, TransportOptions};
+#[cfg(feature = "schema")]
 use crate::util;
+#[cfg(feature = "webhooks")]
+use crate::webhook::{Notifier, Webhook, WebhookEvent};
+use crate::wire_log::WireLog;
+
+const PATH_GUARD_HOOK: &str = "restrict_paths";
+const READ_ONLY_HOOK: &str = "read_only";
+const SECRET_SCAN_HOOK: &str = "scan_secrets";
+const PROFILE_HOOK: &str = "permission_profile";
+#[cfg(feature = "pii")]
+const PII_HOOK: &str = "block_pii";
+
+const INTERLEAVED_THINKING_BETA: &str = "interleaved-thinking-2025-05-14";
 
 #[derive(Debug, Clone)]
 pub(crate) enum Tools {
@@ -26,16 +64,26 @@ pub struct Options {
     tools: Option<Tools>,
     system_prompt: Option<String>,
     append_system_prompt: Option<String>,
+    auto_document_tools: bool,
     permission_mode: Option<PermissionMode>,
     model: Option<Model>,
     fallback_model: Option<Model>,
     debug: bool,
     cwd: Option<PathBuf>,
+    cli_path: Option<PathBuf>,
+    process_limits: Option<ProcessLimits>,
+    reap_orphans: Option<PathBuf>,
+    wire_log: Option<WireLog>,
+    labels: BTreeMap<String, String>,
+    #[cfg(feature = "pty")]
+    pty: bool,
     env: Vec<(String, String)>,
     max_budget_usd: Option<f64>,
     json_schema: Option<String>,
+    json_schema_wrapper: Option<&'static str>,
     mcp_servers: HashMap<String, Arc<McpServer>>,
-    agents: HashMap<String, Agent>,
+    agents: Vec<(String, Agent)>,
+    agent_variables: HashMap<String, String>,
     hooks: Option<Hooks>,
     max_turns: Option<u32>,
     resume: Option<String>,
@@ -43,6 +91,47 @@ pub struct Options {
     resume_session_at: Option<String>,
     strict_mcp_config: bool,
     disable_slash_commands: bool,
+    include_partial_messages: bool,
+    control_timeout: Option<Duration>,
+    log_tool_stats: bool,
+    warn_unknown_fields: bool,
+    hide_thinking: bool,
+    betas: Vec<String>,
+    thinking: Option<ThinkingConfig>,
+    effort: Option<Effort>,
+    bypass: Option<BypassAcknowledgement>,
+    sandbox: Option<Sandbox>,
+    plugin_dirs: Vec<PathBuf>,
+    raw_flags: Vec<(String, Option<String>)>,
+    mcp_roots: Vec<PathBuf>,
+    plugins: Vec<String>,
+    restrict_paths: Vec<PathBuf>,
+    read_only: bool,
+    scan_secrets: bool,
+    #[cfg(feature = "pii")]
+    block_pii: Option<PiiDetector>,
+    profile: Option<PermissionProfile>,
+    turn_profile: Option<TurnProfile>,
+    tenant: Option<Tenant>,
+    quotas: Vec<QuotaBucket>,
+    network: Network,
+    provider: Provider,
+    auth: Option<AuthSource>,
+    auth_refresh: Option<AuthRefresh>,
+    max_tool_calls: Option<u32>,
+    max_turn_duration: Option<Duration>,
+    max_continuations: Option<u32>,
+    max_concurrent_hooks: Option<usize>,
+    max_concurrent_tool_calls: Option<usize>,
+    #[cfg(feature = "webhooks")]
+    webhooks: Vec<Webhook>,
+    approvals: Option<ApprovalBroker>,
+    permission_callbacks: PermissionCallbacks,
+    denials: DenialReporter,
+    id_generator: IdGenerator,
+    clock: Clock,
+    dispatch_queue: DispatchQueue,
+    limits: Limits,
 }
 
 impl Options {
@@ -92,6 +181,20 @@ impl Options {
         self
     }
 
+    /// Allows a tool given as a typed [`ToolSelector`].
+    #[must_use]
+    pub fn allow(mut self, selector: impl Into<ToolSelector>) -> Self {
+        self.allowed_tools.push(selector.into().to_string());
+        self
+    }
+
+    /// Disallows a tool given as a typed [`ToolSelector`].
+    #[must_use]
+    pub fn disallow(mut self, selector: impl Into<ToolSelector>) -> Self {
+        self.disallowed_tools.push(selector.into().to_string());
+        self
+    }
+
     #[must_use]
     pub fn disallowed_tool(mut self, tool: impl Into<String>) -> Self {
         self.disallowed_tools.push(tool.into());
@@ -134,6 +237,25 @@ impl Options {
         self
     }
 
+    /// Restricts Claude to tools served over MCP.
+    ///
+    /// Removes the built-in tool set, drops built-in tools from the allowed
+    /// tools and lists each one as disallowed, so Bash, Read, Write and the
+    /// rest cannot be called even if settings files allow them.
+    #[must_use]
+    pub fn only_mcp_tools(mut self) -> Self {
+        self.tools = Some(Tools::None);
+        self.allowed_tools
+            .retain(|tool| ToolName::parse(tool).is_mcp());
+        for tool in BuiltinTool::ALL {
+            let name = tool.as_str();
+            if !self.disallowed_tools.iter().any(|t| t == name) {
+                self.disallowed_tools.push(name.to_owned());
+            }
+        }
+        self
+    }
+
     #[must_use]
     pub fn default_tools(mut self) -> Self {
         self.tools = Some(Tools::Default);
@@ -152,12 +274,301 @@ impl Options {
         self
     }
 
+    /// Appends a reference of the SDK server tools, generated from their
+    /// names, descriptions and input schemas, to the
+    /// [`append_system_prompt`](Self::append_system_prompt) the CLI is
+    /// started with. Disallowed tools are left out.
+    #[must_use]
+    pub fn auto_document_tools(mut self, enabled: bool) -> Self {
+        self.auto_document_tools = enabled;
+        self
+    }
+
+    /// Sets the permission mode.
+    ///
+    /// [`PermissionMode::BypassPermissions`] is rejected by
+    /// [`validate`](Self::validate) unless set through
+    /// [`bypass_permissions`](Self::bypass_permissions).
     #[must_use]
     pub fn permission_mode(mut self, mode: PermissionMode) -> Self {
         self.permission_mode = Some(mode);
         self
     }
 
+    /// Runs every tool without permission checks or prompts.
+    ///
+    /// The acknowledgement's reason is logged as a warning when the client
+    /// starts.
+    #[must_use]
+    pub fn bypass_permissions(mut self, acknowledgement: BypassAcknowledgement) -> Self {
+        self.permission_mode = Some(PermissionMode::BypassPermissions);
+        self.bypass = Some(acknowledgement);
+        self
+    }
+
+    /// Denies file and shell tool calls that reach outside `roots`.
+    ///
+    /// Installs a [`PathGuard`] as a PreToolUse hook, so the restriction is
+    /// enforced by the SDK regardless of the CLI's own settings. Relative
+    /// roots are resolved against [`cwd`](Self::cwd).
+    #[must_use]
+    pub fn restrict_paths(mut self, roots: impl IntoIterator<Item = impl AsRef<Path>>) -> Self {
+        self.restrict_paths = roots
+            .into_iter()
+            .map(|root| root.as_ref().to_path_buf())
+            .collect();
+        self
+    }
+
+    /// Keeps the session from modifying the workspace, for review and audit
+    /// agents.
+    ///
+    /// Resets the permission mode to the default (clearing `acceptEdits` or
+    /// bypass), disallows the file-editing tools and installs a
+    /// [`ReadOnlyGuard`] as a PreToolUse hook, which also denies Bash
+    /// commands that are not known to be read-only.
+    #[must_use]
+    pub fn read_only(mut self) -> Self {
+        self.permission_mode = Some(PermissionMode::Default);
+        self.bypass = None;
+        for tool in WRITE_TOOLS {
+            if !self.disallowed_tools.iter().any(|t| t == tool) {
+                self.disallowed_tools.push((*tool).to_owned());
+            }
+        }
+        self.allowed_tools
+            .retain(|tool| !WRITE_TOOLS.contains(&tool.as_str()));
+        self.read_only = true;
+        self
+    }
+
+    /// Stops Claude from leaving credentials, such as AWS keys and private
+    /// keys, in the files it writes.
+    ///
+    /// Installs a [`SecretScanner`] as a PostToolUse hook on the file
+    /// editing tools. The hook runs after the write, so it cannot prevent
+    /// it; it blocks the tool result with a reason asking Claude to remove
+    /// the credential again.
+    #[must_use]
+    pub fn scan_secrets(mut self) -> Self {
+        self.scan_secrets = true;
+        self
+    }
+
+    /// Applies a [`Policy`] loaded from a policy document: its tool lists,
+    /// path restrictions, shell rules, budgets and approvals.
+    ///
+    /// The policy's redaction rules apply to conversations, through
+    /// [`Policy::guardrails`].
+    #[cfg(feature = "policy-file")]
+    #[must_use]
+    pub fn policy(self, policy: &Policy) -> Self {
+        policy.apply(self)
+    }
+
+    /// Blocks prompts containing the personal data `detector` finds, before
+    /// they reach the model.
+    ///
+    /// Installs the detector as a UserPromptSubmit hook, which rejects the
+    /// prompt with a reason naming the kinds of data found.
+    #[cfg(feature = "pii")]
+    #[must_use]
+    pub fn block_pii(mut self, detector: PiiDetector) -> Self {
+        self.block_pii = Some(detector);
+        self
+    }
+
+    /// Applies a [`PermissionProfile`]: adds its allowed and disallowed tools
+    /// and path restrictions, and sets its permission mode and budget where
+    /// it has them.
+    ///
+    /// A client created with a profile can also narrow single turns with
+    /// [`TurnBuilder::profile`](crate::TurnBuilder::profile).
+    #[must_use]
+    pub fn profile(mut self, profile: PermissionProfile) -> Self {
+        for tool in profile.allowed_tools() {
+            let tool = tool.to_string();
+            if !self.allowed_tools.contains(&tool) {
+                self.allowed_tools.push(tool);
+            }
+        }
+        for tool in profile.disallowed_tools() {
+            let tool = tool.to_string();
+            if !self.disallowed_tools.contains(&tool) {
+                self.disallowed_tools.push(tool);
+            }
+        }
+        if let Some(mode) = profile.mode() {
+            self.permission_mode = Some(mode);
+        }
+        self.restrict_paths
+            .extend(profile.restrict_paths().iter().cloned());
+        if let Some(budget) = profile.budget_usd() {
+            self.max_budget_usd = Some(budget);
+        }
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Runs the client on behalf of `tenant`, applying its environment and
+    /// workspace settings. See [`tenant`](crate::tenant).
+    #[must_use]
+    pub fn tenant(mut self, tenant: Tenant) -> Self {
+        self.tenant = Some(tenant);
+        self
+    }
+
+    /// Charges the cost of every completed turn to `bucket`, and refuses new
+    /// prompts once it is exhausted. May be called more than once, such as
+    /// for a tenant's bucket and a profile's. See [`quota`](crate::quota).
+    #[must_use]
+    pub fn quota(mut self, bucket: QuotaBucket) -> Self {
+        self.quotas.push(bucket);
+        self
+    }
+
+    /// Configures the CLI's proxy and TLS settings. See
+    /// [`network`](crate::network) for the variables this sets.
+    #[must_use]
+    pub fn network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Sends the CLI's traffic through the HTTP proxy at `url`.
+    #[must_use]
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.network = self.network.proxy(url);
+        self
+    }
+
+    /// Trusts the certificates in the PEM file at `path`, such as a
+    /// corporate root for a TLS-intercepting proxy.
+    #[must_use]
+    pub fn ca_certificates(mut self, path: impl AsRef<Path>) -> Self {
+        self.network = self.network.ca_certificates(path);
+        self
+    }
+
+    /// Sends model requests through `provider`, such as Amazon Bedrock or
+    /// Google Vertex AI. See [`provider`](crate::provider).
+    #[must_use]
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    /// Authenticates the CLI with `key`, ignoring any saved login.
+    #[must_use]
+    pub fn api_key(self, key: impl Into<String>) -> Self {
+        self.auth(AuthSource::ApiKey(key.into()))
+    }
+
+    /// Pins where the CLI gets its credentials. See [`auth`](crate::auth).
+    #[must_use]
+    pub fn auth(mut self, source: AuthSource) -> Self {
+        self.auth = Some(source);
+        self
+    }
+
+    /// Restarts the CLI and retries the turn when its credentials expire
+    /// during a conversation. See [`AuthRefresh`].
+    #[must_use]
+    pub fn refresh_auth(mut self, refresh: AuthRefresh) -> Self {
+        self.auth_refresh = Some(refresh);
+        self
+    }
+
+    /// Limits the memory, priority and CPUs of the CLI process and the tools
+    /// it runs. See [`limits`](crate::limits).
+    #[must_use]
+    pub fn process_limits(mut self, limits: ProcessLimits) -> Self {
+        self.process_limits = Some(limits);
+        self
+    }
+
+    /// Records the CLI in a lockfile under `dir`, and first kills CLIs
+    /// recorded there by hosts that crashed. See [`orphans`](crate::orphans).
+    #[must_use]
+    pub fn reap_orphans(mut self, dir: impl AsRef<Path>) -> Self {
+        self.reap_orphans = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Appends every message exchanged with the CLI to a file, given as a
+    /// path or a [`WireLog`]. See [`wire_log`](crate::wire_log).
+    #[must_use]
+    pub fn wire_log(mut self, log: impl Into<WireLog>) -> Self {
+        self.wire_log = Some(log.into());
+        self
+    }
+
+    /// Runs the Bash tool's commands inside an OS-level sandbox.
+    #[must_use]
+    pub fn sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Loads a plugin from a local directory for this session only.
+    #[must_use]
+    pub fn plugin_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.plugin_dirs.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Passes `flag` and its `value` to the CLI as given, for CLI options
+    /// these options do not cover yet.
+    ///
+    /// The flag must start with `--` and neither it nor the value may
+    /// contain a line break; [`validate`](Self::validate) rejects them
+    /// otherwise, along with flags that would change the wire format.
+    #[must_use]
+    pub fn raw_flag(mut self, flag: impl Into<String>, value: impl Into<String>) -> Self {
+        self.raw_flags.push((flag.into(), Some(value.into())));
+        self
+    }
+
+    /// Like [`raw_flag`](Self::raw_flag), for a flag that takes no value.
+    #[must_use]
+    pub fn raw_switch(mut self, flag: impl Into<String>) -> Self {
+        self.raw_flags.push((flag.into(), None));
+        self
+    }
+
+    /// Attaches a label, such as a run or experiment id, to the session.
+    ///
+    /// Labels are sent with webhook events, logged with tool usage and quota
+    /// warnings, and can name the [wire log](crate::wire_log) file, so runs
+    /// can be told apart downstream. Setting a label again replaces it.
+    #[must_use]
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Declares a workspace root to in-process MCP servers, which their tools
+    /// see through [`ToolContext::roots`](crate::ToolContext::roots).
+    ///
+    /// Defaults to the working directory when no root is declared.
+    #[must_use]
+    pub fn mcp_root(mut self, path: impl AsRef<Path>) -> Self {
+        self.mcp_roots.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Enables an installed plugin, named `plugin@marketplace`, for this
+    /// session.
+    ///
+    /// Whether it loaded can be checked with
+    /// [`ServerInfo::plugin`](crate::proto::ServerInfo::plugin) or
+    /// [`InitResponse::plugins`](crate::InitResponse::plugins).
+    #[must_use]
+    pub fn with_plugin(mut self, name: impl Into<String>) -> Self {
+        self.plugins.push(name.into());
+        self
+    }
+
     #[must_use]
     pub fn model(mut self, model: impl Into<Model>) -> Self {
         self.model = Some(model.into());
@@ -170,27 +581,58 @@ impl Options {
         self
     }
 
+    /// Runs the CLI at `path` instead of looking for `claude` on `PATH` and
+    /// in the usual install locations.
+    #[must_use]
+    pub fn cli_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.cli_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Runs the CLI on a pseudo-terminal rather than pipes, as it would run
+    /// for a user. See [`pty`](crate::pty).
+    #[cfg(feature = "pty")]
+    #[must_use]
+    pub fn pty(mut self) -> Self {
+        self.pty = true;
+        self
+    }
+
     #[must_use]
     pub fn cwd(mut self, path: impl AsRef<Path>) -> Self {
         self.cwd = Some(path.as_ref().to_path_buf());
         self
     }
 
+    /// Replaces the environment variables set for the CLI. When a variable
+    /// is given more than once, the last value wins.
     #[must_use]
     pub fn env(
         mut self,
         vars: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
     ) -> Self {
-        self.env = vars
-            .into_iter()
-            .map(|(k, v)| (k.into(), v.into()))
-            .collect();
+        self.env.clear();
+        for (key, value) in vars {
+            self = self.env_var(key, value);
+        }
+        self
+    }
+
+    /// Sets one environment variable for the CLI, keeping those set before
+    /// and replacing an earlier value of the same variable.
+    #[must_use]
+    pub fn env_var(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.env.retain(|(set, _)| *set != key);
+        self.env.push((key, value.into()));
         self
     }
 
     #[must_use]
+    /// Caps the spend of the session. [`validate`](Self::validate) rejects
+    /// budgets that are not positive.
     pub fn max_budget_usd(mut self, budget: f64) -> Self {
-        self.max_budget_usd = if budget > 0.0 { Some(budget) } else { None };
+        self.max_budget_usd = Some(budget);
         self
     }
 
@@ -198,9 +640,36 @@ impl Options {
         self.json_schema.as_deref()
     }
 
+    pub(crate) fn json_schema_wrapper(&self) -> Option<&'static str> {
+        self.json_schema_wrapper
+    }
+
+    /// Asks for the turn's output as JSON matching `T`'s schema.
+    ///
+    /// The CLI expects an object at the root of the schema. Other roots,
+    /// such as `Vec<T>` or a newtype around a number, are sent wrapped in an
+    /// object, under `items` for arrays and `value` otherwise; the typed
+    /// helpers such as [`Client::query_once_as`](crate::Client::query_once_as)
+    /// unwrap them again, while
+    /// [`CompleteResponse::structured_output`](crate::CompleteResponse::structured_output)
+    /// returns the output as the CLI sent it.
+    #[cfg(feature = "schema")]
     #[must_use]
-    pub fn with_json_schema<T: JsonSchema>(mut self) -> Self {
-        self.json_schema = Some(util::schema_for_structured_output::<T>().to_string());
+    pub fn with_json_schema<T: JsonSchema>(self) -> Self {
+        let (schema, wrapper) = util::structured_output_schema::<T>();
+        self.with_raw_json_schema(schema, wrapper)
+    }
+
+    /// Asks for output matching a schema built at runtime, wrapped in the
+    /// `wrapper` property if one is given.
+    #[cfg(feature = "schema")]
+    pub(crate) fn with_raw_json_schema(
+        mut self,
+        schema: serde_json::Value,
+        wrapper: Option<&'static str>,
+    ) -> Self {
+        self.json_schema = Some(schema.to_string());
+        self.json_schema_wrapper = wrapper;
         self
     }
 
@@ -212,7 +681,7 @@ impl Options {
 
     #[must_use]
     pub fn with_agent(mut self, name: impl Into<String>, agent: Agent) -> Self {
-        self.agents.insert(name.into(), agent);
+        self.agents.push((name.into(), agent));
         self
     }
 
@@ -226,6 +695,30 @@ impl Options {
         self
     }
 
+    /// Fills the template variable `{{name}}` in agent descriptions and
+    /// prompts with `value`.
+    #[must_use]
+    pub fn with_agent_variable(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.agent_variables.insert(name.into(), value.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_agent_variables(
+        mut self,
+        iter: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.agent_variables.extend(
+            iter.into_iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+        self
+    }
+
     #[must_use]
     pub fn debug(mut self, enabled: bool) -> Self {
         self.debug = enabled;
@@ -238,6 +731,8 @@ impl Options {
         self
     }
 
+    /// Only use MCP servers configured through these options, ignoring any
+    /// servers from the user's or project's Claude Code settings.
     #[must_use]
     pub fn strict_mcp_config(mut self, enabled: bool) -> Self {
         self.strict_mcp_config = enabled;
@@ -250,76 +745,1321 @@ impl Options {
         self
     }
 
+    /// Streams raw API events as [`Response::Partial`](crate::Response::Partial)
+    /// while each message is generated.
+    #[must_use]
+    pub fn include_partial_messages(mut self, enabled: bool) -> Self {
+        self.include_partial_messages = enabled;
+        self
+    }
+
+    /// Sets how long to wait for the CLI to answer a control request such as
+    /// [`Client::set_model`](crate::Client::set_model). Defaults to 60 seconds.
+    #[must_use]
+    pub fn control_timeout(mut self, timeout: Duration) -> Self {
+        self.control_timeout = Some(timeout);
+        self
+    }
+
+    /// Logs each SDK MCP server's tool usage statistics once the client is
+    /// closed or dropped.
+    #[must_use]
+    pub fn log_tool_stats(mut self, enabled: bool) -> Self {
+        self.log_tool_stats = enabled;
+        self
+    }
+
+    /// Logs a warning the first time a field of the CLI's messages is not
+    /// modelled by the protocol types and lands in their `extra`. See
+    /// [`proto::extra`](crate::proto::extra).
+    #[must_use]
+    pub fn warn_unknown_fields(mut self, enabled: bool) -> Self {
+        self.warn_unknown_fields = enabled;
+        self
+    }
+
+    /// Drops [`Response::Thinking`](crate::Response::Thinking) from every
+    /// response stream, so it never reaches callbacks or turn history. The
+    /// tokens spent on thinking are still counted in the turn's usage.
+    #[must_use]
+    pub fn hide_thinking(mut self, enabled: bool) -> Self {
+        self.hide_thinking = enabled;
+        self
+    }
+
+    /// Limits how many tools Claude may call in a single conversation turn.
+    ///
+    /// Once exceeded, the turn is interrupted and fails with
+    /// [`Error::ToolCallLimitExceeded`](crate::Error::ToolCallLimitExceeded).
+    /// Individual turns can override this with
+    /// [`TurnBuilder::max_tool_calls`](crate::TurnBuilder::max_tool_calls).
+    #[must_use]
+    pub fn max_tool_calls(mut self, limit: u32) -> Self {
+        self.max_tool_calls = Some(limit);
+        self
+    }
+
+    /// Limits how long a single conversation turn may run, from sending the
+    /// prompt to the final result.
+    ///
+    /// The limit is on wall-clock time, not on time spent in API calls,
+    /// which the CLI reports only with the result. Once exceeded, the turn is
+    /// interrupted and fails with
+    /// [`Error::TurnDurationExceeded`](crate::Error::TurnDurationExceeded),
+    /// whether it is run by a [`Conversation`](crate::Conversation) or read
+    /// with [`Client::receive`](crate::Client::receive). Individual turns can
+    /// override this with
+    /// [`TurnBuilder::max_duration`](crate::TurnBuilder::max_duration).
+    #[must_use]
+    pub fn max_turn_duration(mut self, limit: Duration) -> Self {
+        self.max_turn_duration = Some(limit);
+        self
+    }
+
+    /// Limits how many times in a row Stop hooks may keep the agent from
+    /// stopping with [`StopOutput::continue_with`](crate::StopOutput::continue_with).
+    ///
+    /// Past the limit the agent is let stop, and the turn fails with
+    /// [`Error::ContinuationLimitExceeded`](crate::Error::ContinuationLimitExceeded).
+    /// Without a limit, only the hooks themselves, through
+    /// [`StopInput::stop_hook_active`](crate::StopInput::stop_hook_active),
+    /// end the loop.
+    #[must_use]
+    pub fn max_continuations(mut self, limit: u32) -> Self {
+        self.max_continuations = Some(limit);
+        self
+    }
+
+    /// Limits how many hook callbacks run at once. Each runs on its own
+    /// task, so slow hooks never hold up reading the CLI; callbacks past the
+    /// limit wait for a free slot. Defaults to 16.
+    #[must_use]
+    pub fn max_concurrent_hooks(mut self, limit: usize) -> Self {
+        self.max_concurrent_hooks = Some(limit);
+        self
+    }
+
+    /// Limits how many calls to in-process MCP tools run at once. Calls the
+    /// CLI makes in one assistant turn run side by side up to the limit.
+    /// Defaults to 8; 1 runs them one at a time.
+    #[must_use]
+    pub fn max_concurrent_tool_calls(mut self, limit: usize) -> Self {
+        self.max_concurrent_tool_calls = Some(limit);
+        self
+    }
+
+    /// POSTs a JSON payload to `url` on each of `events`, or on every event
+    /// if `events` is empty. See [`webhook`](crate::webhook) for the payload.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use clauders::Options;
+    /// use clauders::webhook::WebhookEvent;
+    ///
+    /// let options = Options::new().max_budget_usd(5.0).webhook(
+    ///     "https://ops.example.com/hooks/claude",
+    ///     [WebhookEvent::Error, WebhookEvent::BudgetThreshold],
+    /// );
+    /// ```
+    #[cfg(feature = "webhooks")]
+    #[must_use]
+    pub fn webhook(
+        mut self,
+        url: impl Into<String>,
+        events: impl IntoIterator<Item = WebhookEvent>,
+    ) -> Self {
+        self.webhooks.push(Webhook::new(url, events));
+        self
+    }
+
+    /// Forwards tool calls that need approval to `broker` instead of
+    /// refusing them: the CLI's permission prompts, and hooks returning
+    /// [`PreToolUseDecision::Ask`](crate::PreToolUseDecision::Ask). See
+    /// [`approvals`](crate::approvals).
+    #[must_use]
+    pub fn approvals(mut self, broker: ApprovalBroker) -> Self {
+        self.approvals = Some(broker);
+        self
+    }
+
+    /// Decides the CLI's permission prompts with `callbacks`, chosen by tool.
+    /// Calls they leave undecided, or answer with
+    /// [`Decision::Ask`](crate::Decision::Ask), go to the
+    /// [`approvals`](Self::approvals) broker.
+    #[must_use]
+    pub fn permission_callbacks(mut self, callbacks: PermissionCallbacks) -> Self {
+        self.permission_callbacks = callbacks;
+        self
+    }
+
+    /// Calls `callback` with every denied tool call, whether a hook, a
+    /// permission callback, the approval broker or the CLI denied it. See
+    /// [`denials`](crate::denials).
+    #[must_use]
+    pub fn on_denial<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&DenialEvent) + Send + Sync + 'static,
+    {
+        self.denials = DenialReporter::new(Arc::new(callback));
+        self
+    }
+
+    /// Sizes the queue [`Client::dispatch_to`](crate::Client::dispatch_to)
+    /// keeps between the CLI and a handler, and what happens when it fills.
+    /// Defaults to 256 responses, blocking when full.
+    #[must_use]
+    pub fn dispatch_queue(mut self, queue: DispatchQueue) -> Self {
+        self.limits.dispatch_queue = queue.capacity();
+        self.dispatch_queue = queue;
+        self
+    }
+
+    /// Sizes the buffers the client keeps between the CLI and the caller.
+    /// The dispatch queue keeps the overflow behaviour set with
+    /// [`dispatch_queue`](Self::dispatch_queue). See [`limits`](crate::limits).
+    #[must_use]
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.dispatch_queue =
+            DispatchQueue::new(limits.dispatch_queue, self.dispatch_queue.overflow());
+        self.limits = limits;
+        self
+    }
+
+    /// Sets how the client generates control request ids.
+    #[must_use]
+    pub fn id_generator(mut self, ids: IdGenerator) -> Self {
+        self.id_generator = ids;
+        self
+    }
+
+    /// Sets the clock used for time-dependent decisions such as rate limit
+    /// backoff.
+    #[must_use]
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Makes outgoing traffic reproducible: request ids are derived from
+    /// `seed` and the clock is stopped at the Unix epoch.
+    #[must_use]
+    pub fn deterministic(self, seed: u64) -> Self {
+        self.id_generator(IdGenerator::seeded(seed))
+            .clock(Clock::fixed(std::time::UNIX_EPOCH))
+    }
+
+    /// Configures extended thinking.
+    ///
+    /// [`validate`](Self::validate) rejects enabled budgets below
+    /// [`MIN_THINKING_BUDGET`] or above the selected model's
+    /// [`max_thinking_budget`](Model::max_thinking_budget).
+    #[must_use]
+    pub fn thinking(mut self, thinking: ThinkingConfig) -> Self {
+        self.thinking = Some(thinking);
+        self
+    }
+
+    #[must_use]
+    pub fn effort(mut self, effort: Effort) -> Self {
+        self.effort = Some(effort);
+        self
+    }
+
+    /// Adds beta headers to the CLI's API requests. Only honoured when the
+    /// CLI authenticates with an API key.
+    #[must_use]
+    pub fn betas<I, S>(mut self, betas: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.betas.extend(betas.into_iter().map(Into::into));
+        self
+    }
+
+    /// Lets Claude think between tool calls rather than only before its first
+    /// response, by enabling the interleaved thinking beta.
+    #[must_use]
+    pub fn interleaved_thinking(mut self, enabled: bool) -> Self {
+        self.betas.retain(|beta| beta != INTERLEAVED_THINKING_BETA);
+        if enabled {
+            self.betas.push(INTERLEAVED_THINKING_BETA.to_owned());
+        }
+        self
+    }
+
+    pub(crate) fn cwd_path(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    pub(crate) fn labels_ref(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+
+    pub(crate) fn mcp_roots_ref(&self) -> &[PathBuf] {
+        &self.mcp_roots
+    }
+
+    pub(crate) fn log_tool_stats_enabled(&self) -> bool {
+        self.log_tool_stats
+    }
+
+    pub(crate) fn hide_thinking_enabled(&self) -> bool {
+        self.hide_thinking
+    }
+
+    pub(crate) fn max_tool_calls_limit(&self) -> Option<u32> {
+        self.max_tool_calls
+    }
+
+    pub(crate) fn max_turn_duration_limit(&self) -> Option<Duration> {
+        self.max_turn_duration
+    }
+
+    pub(crate) fn max_continuations_limit(&self) -> Option<u32> {
+        self.max_continuations
+    }
+
+    pub(crate) fn max_concurrent_hooks_limit(&self) -> usize {
+        self.max_concurrent_hooks.unwrap_or(16)
+    }
+
+    pub(crate) fn max_concurrent_tool_calls_limit(&self) -> usize {
+        self.max_concurrent_tool_calls.unwrap_or(8)
+    }
+
+    /// A notifier for the configured webhooks, if any.
+    #[cfg(feature = "webhooks")]
+    pub(crate) fn webhook_notifier(&self) -> Option<Notifier> {
+        (!self.webhooks.is_empty()).then(|| {
+            Notifier::new(
+                self.webhooks.clone(),
+                self.max_budget_usd,
+                self.tenant_id().map(str::to_owned),
+                self.labels.clone(),
+                self.clock.clone(),
+            )
+        })
+    }
+
+    pub(crate) fn profile_ref(&self) -> Option<&PermissionProfile> {
+        self.profile.as_ref()
+    }
+
+    /// The per-turn profile check installed by [`take_hooks`](Self::take_hooks).
+    pub(crate) fn take_turn_profile(&mut self) -> Option<TurnProfile> {
+        self.turn_profile.take()
+    }
+
+    pub(crate) fn permission_mode_setting(&self) -> Option<PermissionMode> {
+        self.permission_mode
+    }
+
+    pub(crate) fn quotas(&self) -> &[QuotaBucket] {
+        &self.quotas
+    }
+
+    pub(crate) fn tenant_id(&self) -> Option<&str> {
+        self.tenant.as_ref().map(Tenant::id)
+    }
+
+    /// These options, for a session run alongside the one they started, such
+    /// as one summarising it: the session starts afresh, answers in plain
+    /// text and serves no tools or agents of its own.
+    pub(crate) fn side_session(mut self) -> Self {
+        self.resume = None;
+        self.fork_session = false;
+        self.resume_session_at = None;
+        self.json_schema = None;
+        self.json_schema_wrapper = None;
+        self.mcp_servers.clear();
+        self.agents.clear();
+        self
+    }
+
+    /// Creates the tenant's temporary workspace, if it asked for one, and
+    /// makes it the working directory.
+    pub(crate) fn create_workspace(&mut self) -> Result<Option<Workspace>, std::io::Error> {
+        let Some(tenant) = self.tenant.as_ref().filter(|t| t.has_temp_workspace()) else {
+            return Ok(None);
+        };
+        let workspace = Workspace::create(tenant.id())?;
+        self.cwd = Some(workspace.path().to_path_buf());
+        Ok(Some(workspace))
+    }
+
+    pub(crate) fn approval_broker(&self) -> Option<ApprovalBroker> {
+        self.approvals.clone()
+    }
+
+    pub(crate) fn permission_callbacks_ref(&self) -> &PermissionCallbacks {
+        &self.permission_callbacks
+    }
+
+    pub(crate) fn denial_reporter(&self) -> &DenialReporter {
+        &self.denials
+    }
+
+    #[cfg(feature = "policy-file")]
+    pub(crate) fn permission_callbacks_mut(&mut self) -> &mut PermissionCallbacks {
+        &mut self.permission_callbacks
+    }
+
+    pub(crate) fn dispatch_queue_config(&self) -> DispatchQueue {
+        self.dispatch_queue
+    }
+
+    pub(crate) fn id_generator_ref(&self) -> &IdGenerator {
+        &self.id_generator
+    }
+
+    pub(crate) fn clock_ref(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub(crate) fn bypass_acknowledgement(&self) -> Option<&BypassAcknowledgement> {
+        self.bypass.as_ref()
+    }
+
+    pub(crate) fn control_timeout_or_default(&self) -> Duration {
+        self.control_timeout.unwrap_or(Duration::from_secs(60))
+    }
+
+    /// Checks the options for conflicts that the CLI would otherwise resolve silently.
+    ///
+    /// Detects SDK server tools registered twice, tools from different servers
+    /// that map to the same `mcp__server__tool` name, and tools whose bare name
+    /// shadows a built-in tool listed in the allowed tools. Allowed and
+    /// disallowed tool strings must parse as a [`ToolSelector`], agent names
+    /// must be unique, agent template variables must have values, agent
+    /// tools must be allowed when the allowed tools are set and a budget
+    /// must be positive.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for tool in self.allowed_tools.iter().chain(&self.disallowed_tools) {
+            ToolSelector::parse(tool)?;
+        }
+
+        let mut qualified = HashMap::<String, String>::new();
+
+        let mut servers = self.mcp_servers.iter().collect::<Vec<_>>();
+        servers.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (server_name, server) in servers {
+            validate_mcp_server_name(server_name)?;
+            let mut seen = HashSet::new();
+            for tool in server.tools() {
+                if !seen.insert(tool.name()) {
+                    return Err(ConfigError::DuplicateTool {
+                        server: server_name.clone(),
+                        tool: tool.name().to_owned(),
+                    });
+                }
+
+                if self.allowed_tools.iter().any(|t| t == tool.name()) {
+                    return Err(ConfigError::BuiltinToolConflict {
+                        server: server_name.clone(),
+                        tool: tool.name().to_owned(),
+                    });
+                }
+
+                let name = ToolName::mcp(server_name, tool.name()).to_string();
+                let origin = format!("{server_name}/{}", tool.name());
+                if let Some(first) = qualified.insert(name.clone(), origin.clone()) {
+                    return Err(ConfigError::ToolNameConflict {
+                        qualified: name,
+                        first,
+                        second: origin,
+                    });
+                }
+            }
+        }
+
+        let allowed = if self.allowed_tools.is_empty() {
+            Vec::new()
+        } else {
+            self.effective_allowed_tools()
+                .iter()
+                .filter_map(|t| ToolSelector::parse(t).ok())
+                .collect()
+        };
+        let mut agents = HashSet::new();
+        for (name, agent) in &self.agents {
+            if !agents.insert(name) {
+                return Err(ConfigError::DuplicateAgent { name: name.clone() });
+            }
+            if let Some(variable) = agent
+                .variables()
+                .into_iter()
+                .find(|v| !self.agent_variables.contains_key(*v))
+            {
+                return Err(ConfigError::UndefinedAgentVariable {
+                    agent: name.clone(),
+                    variable: variable.to_owned(),
+                });
+            }
+            if let Some(tool) = agent
+                .tools()
+                .iter()
+                .find(|t| !allowed.is_empty() && !allowed.iter().any(|s| s.matches(t)))
+            {
+                return Err(ConfigError::AgentToolNotAllowed {
+                    agent: name.clone(),
+                    tool: tool.clone(),
+                });
+            }
+        }
+
+        if let Some(budget) = self.max_budget_usd
+            && !(budget.is_finite() && budget > 0.0)
+        {
+            return Err(ConfigError::InvalidBudget {
+                reason: format!("{budget} USD is not a positive amount"),
+            });
+        }
+
+        if let Some((event, name)) = self.hooks.as_ref().and_then(Hooks::duplicate_name) {
+            return Err(ConfigError::DuplicateHookName {
+                event: event.to_string(),
+                name,
+            });
+        }
+
+        for (flag, value) in &self.raw_flags {
+            validate_raw_flag(flag, value.as_deref())?;
+        }
+
+        self.network.validate()?;
+        if let Some(limits) = &self.process_limits {
+            limits.validate()?;
+        }
+        self.limits.validate()?;
+        self.provider.validate(&self.cli_env())?;
+        if let Some(auth) = &self.auth {
+            auth.validate(&self.cli_env())?;
+        }
+
+        #[cfg(feature = "webhooks")]
+        for webhook in &self.webhooks {
+            let url =
+                reqwest::Url::parse(webhook.url()).map_err(|e| ConfigError::InvalidWebhookUrl {
+                    url: webhook.url().to_owned(),
+                    reason: e.to_string(),
+                })?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(ConfigError::InvalidWebhookUrl {
+                    url: webhook.url().to_owned(),
+                    reason: "only http and https URLs are supported".to_owned(),
+                });
+            }
+        }
+
+        if self.permission_mode == Some(PermissionMode::BypassPermissions) && self.bypass.is_none()
+        {
+            return Err(ConfigError::UnacknowledgedBypass);
+        }
+
+        if let Some(ThinkingConfig::Enabled { budget_tokens }) = self.thinking {
+            // Without an explicit model the CLI defaults to Sonnet.
+            let model = self.model.clone().unwrap_or(Model::Sonnet);
+            let max = model.max_thinking_budget().unwrap_or(u32::MAX);
+            if !(MIN_THINKING_BUDGET..=max).contains(&budget_tokens) {
+                return Err(ConfigError::InvalidThinkingBudget {
+                    model: model.to_string(),
+                    budget: budget_tokens,
+                    min: MIN_THINKING_BUDGET,
+                    max,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn mcp_servers(&self) -> &HashMap<String, Arc<McpServer>> {
         &self.mcp_servers
     }
 
-    pub(crate) fn take_hooks(&mut self) -> Option<Hooks> {
-        self.hooks.take()
+    /// The configured hooks, created empty if none were set.
+    #[cfg(any(feature = "schema", feature = "policy-file"))]
+    pub(crate) fn hooks_mut(&mut self) -> &mut Hooks {
+        self.hooks.get_or_insert_with(Hooks::default)
     }
 
-    pub(crate) fn to_transport_options(&self) -> TransportOptions {
-        use crate::transport::TransportOptionsBuilder;
+    /// Takes the configured hooks, adding the path guard if
+    /// [`restrict_paths`](Self::restrict_paths) was set, the read-only
+    /// guard if [`read_only`](Self::read_only) was, the secret scanner if
+    /// [`scan_secrets`](Self::scan_secrets) was, the personal data check if
+    /// `block_pii` was, and the per-turn profile check if a
+    /// [`profile`](Self::profile) was.
+    pub(crate) fn take_hooks(&mut self) -> Result<Option<Hooks>, std::io::Error> {
+        #[cfg(feature = "pii")]
+        let blocks_pii = self.block_pii.is_some();
+        #[cfg(not(feature = "pii"))]
+        let blocks_pii = false;
+        if self.restrict_paths.is_empty()
+            && !self.read_only
+            && !self.scan_secrets
+            && !blocks_pii
+            && self.profile.is_none()
+        {
+            return Ok(self.hooks.take());
+        }
 
+        let mut hooks = self.hooks.take().unwrap_or_default();
+        let cwd = match &self.cwd {
+            Some(cwd) => cwd.clone(),
+            None => std::env::current_dir()?,
+        };
+        if !self.restrict_paths.is_empty() {
+            let guard = Arc::new(PathGuard::new(&self.restrict_paths, &cwd));
+            hooks.add_pre_tool_use_named(PATH_GUARD_HOOK, PATH_GUARD_MATCHER, move |input| {
+                let output = guard.evaluate(input.tool_name(), input.tool_input());
+                async move { output }
+            });
+        }
+        if self.read_only {
+            let guard = Arc::new(ReadOnlyGuard::new());
+            hooks.add_pre_tool_use_named(READ_ONLY_HOOK, READ_ONLY_MATCHER, move |input| {
+                let output = guard.evaluate(input.tool_name(), input.tool_input());
+                async move { output }
+            });
+        }
+        if self.scan_secrets {
+            let scanner = SecretScanner::new();
+            hooks.add_post_tool_use_named(SECRET_SCAN_HOOK, SECRET_SCAN_MATCHER, move |input| {
+                let output = scanner.evaluate(input.tool_name(), input.tool_input());
+                async move { output }
+            });
+        }
+        #[cfg(feature = "pii")]
+        if let Some(detector) = &self.block_pii {
+            let detector = Arc::new(detector.clone());
+            hooks.add_user_prompt_submit_named(PII_HOOK, move |input| {
+                let output = detector.evaluate_prompt(input.prompt());
+                async move { output }
+            });
+        }
+        if self.profile.is_some() {
+            let turn_profile = TurnProfile::new(cwd);
+            self.turn_profile = Some(turn_profile.clone());
+            hooks.add_pre_tool_use_named::<_, &str, _, _>(PROFILE_HOOK, None, move |input| {
+                let output = turn_profile.evaluate(input.tool_name(), input.tool_input());
+                async move { output }
+            });
+        }
+        Ok(Some(hooks))
+    }
+
+    pub(crate) fn auth_refresh(&self) -> Option<&AuthRefresh> {
+        self.auth_refresh.as_ref()
+    }
+
+    /// The variables set for the CLI process: network, provider and auth
+    /// settings, overridden by [`env`](Self::env), then the tenant's.
+    fn cli_env(&self) -> Vec<(String, String)> {
+        let mut env = self.network.env();
+        env.extend(self.provider.env());
+        env.extend(self.auth.iter().flat_map(AuthSource::env));
+        env.retain(|(key, _)| !self.env.iter().any(|(set, _)| set == key));
+        env.extend(self.env.iter().cloned());
+        if let Some(tenant) = &self.tenant {
+            env.extend(tenant.env_vars().iter().cloned());
+        }
+        env
+    }
+
+    /// The command line and environment a client would start the CLI with,
+    /// without looking for the CLI. [`Client::dry_run`](crate::Client::dry_run)
+    /// also checks that it exists.
+    pub fn preview_command(&self) -> CommandPreview {
+        Transport::preview(
+            &self.to_transport_options(),
+            crate::locate::unresolved(self.cli_path.as_deref()),
+        )
+    }
+
+    /// The allow list the CLI is started with: the allowed tools as given,
+    /// then the `mcp__server__tool` name of every SDK server tool, servers
+    /// in name order.
+    ///
+    /// Server tools covered by a disallowed `mcp__server` or
+    /// `mcp__server__tool` entry are left out, so a server can register many
+    /// tools and a session expose only some of them.
+    pub fn effective_allowed_tools(&self) -> Vec<String> {
         let mut allowed = self.allowed_tools.clone();
-        for (server_name, server) in &self.mcp_servers {
+        let disallowed = self.disallowed_mcp_selectors();
+
+        for (server_name, server) in self.sorted_mcp_servers() {
             for tool in server.tools() {
-                let name = format!("mcp__{server_name}__{}", tool.name());
+                let name = ToolName::mcp(server_name, tool.name()).to_string();
+                if disallowed.iter().any(|selector| selector.matches(&name)) {
+                    continue;
+                }
                 if !allowed.contains(&name) {
                     allowed.push(name);
                 }
             }
         }
+        allowed
+    }
 
-        let mut builder = TransportOptionsBuilder::default();
-        builder
-            .allowed_tools(allowed)
-            .disallowed_tools(self.disallowed_tools.clone())
-            .mcp_server_names(self.mcp_servers.keys().cloned().collect::<Vec<_>>())
-            .env(self.env.clone());
+    /// The disallowed entries naming MCP servers or their tools.
+    fn disallowed_mcp_selectors(&self) -> Vec<ToolSelector> {
+        self.disallowed_tools
+            .iter()
+            .filter(|t| t.starts_with("mcp__"))
+            .filter_map(|t| ToolSelector::parse(t).ok())
+            .collect()
+    }
 
-        if let Some(m) = &self.model {
-            builder.model(m.to_string());
-        }
-        if let Some(m) = &self.fallback_model {
-            builder.fallback_model(m.to_string());
-        }
-        if let Some(p) = &self.system_prompt {
-            builder.system_prompt(p.clone());
-        }
-        if let Some(p) = &self.append_system_prompt {
-            builder.append_system_prompt(p.clone());
-        }
-        if let Some(m) = self.permission_mode {
-            builder.permission_mode(m.to_string());
+    fn sorted_mcp_servers(&self) -> Vec<(&String, &Arc<McpServer>)> {
+        let mut servers = self.mcp_servers.iter().collect::<Vec<_>>();
+        servers.sort_by(|a, b| a.0.cmp(b.0));
+        servers
+    }
+
+    /// [`append_system_prompt`](Self::append_system_prompt), followed by the
+    /// reference of the exposed SDK server tools if
+    /// [`auto_document_tools`](Self::auto_document_tools) is set.
+    fn effective_append_system_prompt(&self) -> Option<String> {
+        if !self.auto_document_tools {
+            return self.append_system_prompt.clone();
         }
-        if let Some(b) = self.max_budget_usd {
-            builder.max_budget_usd(b);
+
+        let disallowed = self.disallowed_mcp_selectors();
+        let reference = self
+            .sorted_mcp_servers()
+            .into_iter()
+            .map(|(name, server)| {
+                server.describe_as(name, |tool| !disallowed.iter().any(|s| s.matches(tool)))
+            })
+            .filter(|doc| !doc.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if reference.is_empty() {
+            return self.append_system_prompt.clone();
         }
-        if let Some(c) = &self.cwd {
-            builder.cwd(c.clone());
+
+        let reference = format!("## Available tools\n\n{reference}");
+        Some(match &self.append_system_prompt {
+            Some(prompt) => format!("{prompt}\n\n{reference}"),
+            None => reference,
+        })
+    }
+
+    pub(crate) fn to_transport_options(&self) -> TransportOptions {
+        let inherit_env = self
+            .tenant
+            .as_ref()
+            .is_some_and(Tenant::is_env_isolated)
+            .then(|| {
+                INHERITED_ENV
+                    .iter()
+                    .chain(self.provider.inherited_env())
+                    .map(|name| (*name).to_owned())
+                    .collect()
+            });
+
+        let permission_prompt_tool = (self.approvals.is_some()
+            || !self.permission_callbacks.is_empty())
+        .then(|| "stdio".to_owned());
+
+        let mut settings = serde_json::Map::new();
+        if let Some(sandbox) = &self.sandbox {
+            settings.insert("sandbox".to_owned(), serde_json::json!(sandbox));
         }
-        if let Some(s) = &self.json_schema {
-            builder.json_schema(s.clone());
+        if !self.plugins.is_empty() {
+            let enabled = self
+                .plugins
+                .iter()
+                .map(|name| (name.clone(), serde_json::Value::Bool(true)))
+                .collect::<serde_json::Map<_, _>>();
+            settings.insert("enabledPlugins".to_owned(), enabled.into());
         }
-        if let Some(t) = &self.tools {
-            builder.tools(t.clone());
+
+        TransportOptions {
+            allowed_tools: self.effective_allowed_tools(),
+            disallowed_tools: self.disallowed_tools.clone(),
+            tools: self.tools.clone(),
+            model: self.model.as_ref().map(ToString::to_string),
+            fallback_model: self.fallback_model.as_ref().map(ToString::to_string),
+            system_prompt: self.system_prompt.clone(),
+            append_system_prompt: self.effective_append_system_prompt(),
+            permission_mode: self.permission_mode.map(|m| m.to_string()),
+            max_budget_usd: self.max_budget_usd,
+            debug: false,
+            cwd: self.cwd.clone(),
+            env: self.cli_env(),
+            json_schema: self.json_schema.clone(),
+            mcp_server_names: self.mcp_servers.keys().cloned().collect(),
+            max_turns: self.max_turns,
+            resume: self.resume.clone(),
+            fork_session: self.fork_session,
+            resume_session_at: self.resume_session_at.clone(),
+            agents: self
+                .agents
+                .iter()
+                .map(|(name, agent)| (name.clone(), agent.resolve(&self.agent_variables)))
+                .collect(),
+            strict_mcp_config: self.strict_mcp_config,
+            disable_slash_commands: self.disable_slash_commands,
+            include_partial_messages: self.include_partial_messages,
+            betas: self.betas.clone(),
+            max_thinking_tokens: self.thinking.and_then(|t| t.max_thinking_tokens()),
+            effort: self.effort.map(|e| e.to_string()),
+            settings: (!settings.is_empty())
+                .then(|| serde_json::Value::Object(settings).to_string()),
+            plugin_dirs: self.plugin_dirs.clone(),
+            raw_flags: self.raw_flags.clone(),
+            permission_prompt_tool,
+            inherit_env,
+            remove_env: self
+                .auth
+                .as_ref()
+                .map(AuthSource::removed_env)
+                .unwrap_or_default(),
+            cli_path: self.cli_path.clone(),
+            #[cfg(feature = "pty")]
+            pty: self.pty,
+            #[cfg(not(feature = "pty"))]
+            pty: false,
+            process_limits: self.process_limits.clone(),
+            reap_orphans: self.reap_orphans.clone(),
+            wire_log: self
+                .wire_log
+                .as_ref()
+                .map(|log| log.clone().with_labels(&self.labels)),
+            limits: self.limits,
+            warn_unknown_fields: self.warn_unknown_fields,
         }
+    }
+}
 
-        if let Some(turns) = self.max_turns {
-            builder.max_turns(turns);
+/// MCP server names become part of tool names, so they are kept to
+/// characters every tool name allows.
+fn validate_mcp_server_name(name: &str) -> Result<(), ConfigError> {
+    let invalid = |reason: &str| ConfigError::InvalidMcpServerName {
+        name: name.to_owned(),
+        reason: reason.to_owned(),
+    };
+    if name.is_empty() {
+        return Err(invalid("names cannot be empty"));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(invalid(
+            "names may only contain ASCII letters, digits, '_' and '-'",
+        ));
+    }
+    Ok(())
+}
+
+/// Flags the client sets itself to speak stream-json with the CLI.
+const PROTOCOL_FLAGS: &[&str] = &["--input-format", "--output-format", "--print", "-p"];
+
+fn validate_raw_flag(flag: &str, value: Option<&str>) -> Result<(), ConfigError> {
+    let invalid = |reason: &str| ConfigError::InvalidRawFlag {
+        flag: flag.to_owned(),
+        reason: reason.to_owned(),
+    };
+    if !flag.starts_with("--") || flag.len() == 2 {
+        return Err(invalid("flags must start with '--'"));
+    }
+    if flag.chars().any(char::is_whitespace) {
+        return Err(invalid("flags cannot contain whitespace"));
+    }
+    if value.is_some_and(|v| v.contains(['\n', '\r', '\0'])) {
+        return Err(invalid("values cannot contain line breaks or NUL"));
+    }
+    let name = flag.split_once('=').map_or(flag, |(name, _)| name);
+    if PROTOCOL_FLAGS.contains(&name) {
+        return Err(invalid("the client sets this flag itself"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::tool::Tool;
+
+    fn tool(name: &str) -> Tool {
+        Tool::new(name, "test tool", serde_json::json!({}), None, |_| async {
+            Ok(Value::Null)
+        })
+    }
+
+    #[test]
+    fn test_preview_command() {
+        let options = Options::new()
+            .model(Model::Haiku)
+            .env_var("A", "1")
+            .cli_path("/opt/claude");
+        let preview = options.preview_command();
+        assert_eq!(preview.program(), Path::new("/opt/claude"));
+        assert!(preview.args().windows(2).any(|w| w == ["--model", "haiku"]));
+        assert!(preview.env().contains(&("A".to_owned(), "1".to_owned())));
+        assert!(
+            preview
+                .to_string()
+                .starts_with("/opt/claude --output-format stream-json")
+        );
+    }
+
+    #[test]
+    fn test_validate_mcp_server_names() {
+        let server = Arc::new(McpServer::new("s", vec![]));
+        assert!(
+            Options::new()
+                .with_mcp_server("tickets-v2", server.clone())
+                .validate()
+                .is_ok()
+        );
+        assert!(matches!(
+            Options::new()
+                .with_mcp_server("my server", server)
+                .validate(),
+            Err(ConfigError::InvalidMcpServerName { .. })
+        ));
+    }
+
+    #[test]
+    fn test_raw_flags_are_validated_and_passed_on() {
+        let options = Options::new()
+            .raw_flag("--new-feature", "on")
+            .raw_switch("--experimental");
+        assert!(options.validate().is_ok());
+        assert_eq!(
+            options.to_transport_options().raw_flags(),
+            [
+                ("--new-feature".to_owned(), Some("on".to_owned())),
+                ("--experimental".to_owned(), None),
+            ]
+        );
+
+        for options in [
+            Options::new().raw_switch("-x"),
+            Options::new().raw_switch("--two words"),
+            Options::new().raw_flag("--note", "line\nbreak"),
+            Options::new().raw_flag("--output-format", "text"),
+        ] {
+            assert!(matches!(
+                options.validate(),
+                Err(ConfigError::InvalidRawFlag { .. })
+            ));
         }
-        if let Some(ref session_id) = self.resume {
-            builder.resume(session_id.clone());
+    }
+
+    #[test]
+    fn test_env_var_appends_and_last_value_wins() {
+        let options = Options::new()
+            .env_var("A", "1")
+            .env_var("B", "2")
+            .env_var("A", "3");
+        assert_eq!(
+            options.cli_env(),
+            [
+                ("B".to_owned(), "2".to_owned()),
+                ("A".to_owned(), "3".to_owned())
+            ]
+        );
+
+        let options = options.env([("C", "4"), ("C", "5")]).env_var("D", "6");
+        assert_eq!(
+            options.cli_env(),
+            [
+                ("C".to_owned(), "5".to_owned()),
+                ("D".to_owned(), "6".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_accepts_distinct_tools() {
+        let options = Options::new()
+            .allowed_tool("Read")
+            .with_mcp_server("a", Arc::new(McpServer::new("a", vec![tool("x")])))
+            .with_mcp_server("b", Arc::new(McpServer::new("b", vec![tool("x")])));
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_duplicate_hook_name() {
+        let hooks = Hooks::new()
+            .on_stop_named("audit", |_| async { crate::hooks::StopOutput::pass() })
+            .on_stop_named("audit", |_| async { crate::hooks::StopOutput::pass() });
+        let options = Options::new().hooks(hooks);
+        assert!(matches!(
+            options.validate(),
+            Err(ConfigError::DuplicateHookName { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_duplicate_agent() {
+        let agent = || Agent::new("reviews code", "You review code.");
+        let options = Options::new()
+            .with_agent("reviewer", agent())
+            .with_agents([("tester", agent()), ("reviewer", agent())]);
+        assert_eq!(
+            options.validate(),
+            Err(ConfigError::DuplicateAgent {
+                name: "reviewer".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_agent_tools_allowed() {
+        let agent = Agent::new("reviews code", "You review code.").with_tools(["Read", "Bash"]);
+        let options = Options::new().with_agent("reviewer", agent.clone());
+        assert!(options.validate().is_ok());
+
+        let options = options
+            .allowed_tool("Read")
+            .allowed_tool("Bash(git diff:*)");
+        assert!(options.validate().is_ok());
+
+        let options = Options::new()
+            .allowed_tool("Read")
+            .with_agent("reviewer", agent);
+        assert_eq!(
+            options.validate(),
+            Err(ConfigError::AgentToolNotAllowed {
+                agent: "reviewer".to_owned(),
+                tool: "Bash".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_agent_variables_resolved_for_transport() {
+        let agent = Agent::new("Works on {{ticket}}", "Push to {{branch}}.");
+        let options = Options::new().with_agent("dev", agent.clone());
+        assert_eq!(
+            options.validate(),
+            Err(ConfigError::UndefinedAgentVariable {
+                agent: "dev".to_owned(),
+                variable: "ticket".to_owned()
+            })
+        );
+
+        let options = options
+            .with_agent_variable("ticket", "ENG-42")
+            .with_agent_variables([("branch", "fix/eng-42")]);
+        assert!(options.validate().is_ok());
+        let transport = options.to_transport_options();
+        let resolved = &transport.agents["dev"];
+        assert_eq!(resolved.description(), "Works on ENG-42");
+        assert_eq!(resolved.prompt(), "Push to fix/eng-42.");
+    }
+
+    #[test]
+    fn test_validate_budget() {
+        assert!(Options::new().max_budget_usd(0.5).validate().is_ok());
+        for budget in [0.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                Options::new().max_budget_usd(budget).validate(),
+                Err(ConfigError::InvalidBudget { .. })
+            ));
         }
-        builder.fork_session(self.fork_session);
-        if let Some(ref id) = self.resume_session_at {
-            builder.resume_session_at(id.clone());
+    }
+
+    #[test]
+    fn test_validate_duplicate_tool() {
+        let options = Options::new().with_mcp_server(
+            "a",
+            Arc::new(McpServer::new("a", vec![tool("x"), tool("x")])),
+        );
+        assert!(matches!(
+            options.validate(),
+            Err(ConfigError::DuplicateTool { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_qualified_name_conflict() {
+        let options = Options::new()
+            .with_mcp_server("a__b", Arc::new(McpServer::new("a__b", vec![tool("c")])))
+            .with_mcp_server("a", Arc::new(McpServer::new("a", vec![tool("b__c")])));
+        assert!(matches!(
+            options.validate(),
+            Err(ConfigError::ToolNameConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_builtin_conflict() {
+        let options = Options::new()
+            .allowed_tool("Read")
+            .with_mcp_server("fs", Arc::new(McpServer::new("fs", vec![tool("Read")])));
+        assert!(matches!(
+            options.validate(),
+            Err(ConfigError::BuiltinToolConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_tool_selectors() {
+        let options = Options::new()
+            .allow(BuiltinTool::Read)
+            .allow(ToolSelector::builtin(BuiltinTool::Bash).with_rule("git diff:*"))
+            .disallow(ToolSelector::mcp_server("github"));
+        assert_eq!(options.allowed_tools, ["Read", "Bash(git diff:*)"]);
+        assert_eq!(options.disallowed_tools, ["mcp__github"]);
+        assert!(options.validate().is_ok());
+
+        let err = Options::new()
+            .disallowed_tool("Bash(rm")
+            .validate()
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidToolSelector { .. }));
+    }
+
+    #[test]
+    fn test_only_mcp_tools_removes_builtins() {
+        let options = Options::new()
+            .allowed_tool("Bash")
+            .allowed_tool("mcp__db__query")
+            .disallowed_tool("Write")
+            .only_mcp_tools();
+        assert_eq!(options.allowed_tools, ["mcp__db__query"]);
+        assert_eq!(options.disallowed_tools.len(), BuiltinTool::ALL.len());
+
+        let transport = options.to_transport_options();
+        assert_eq!(transport.tools().collect::<Vec<_>>(), [""]);
+    }
+
+    #[test]
+    fn test_effective_allowed_tools_adds_server_tools() {
+        let options = Options::new()
+            .allowed_tool("Read")
+            .allowed_tool("mcp__db__query")
+            .with_mcp_server("web", Arc::new(McpServer::new("web", vec![tool("fetch")])))
+            .with_mcp_server(
+                "db",
+                Arc::new(McpServer::new("db", vec![tool("query"), tool("schema")])),
+            );
+        assert_eq!(
+            options.effective_allowed_tools(),
+            [
+                "Read",
+                "mcp__db__query",
+                "mcp__db__schema",
+                "mcp__web__fetch"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disallowed_server_tools_are_not_auto_allowed() {
+        let server = Arc::new(McpServer::new(
+            "ops",
+            vec![tool("status"), tool("restart"), tool("wipe")],
+        ));
+        let options = Options::new()
+            .with_mcp_server("ops", server.clone())
+            .disallowed_tool("mcp__ops__wipe");
+        assert_eq!(
+            options.effective_allowed_tools(),
+            ["mcp__ops__status", "mcp__ops__restart"]
+        );
+        let transport = options.to_transport_options();
+        assert_eq!(transport.disallowed_tools(), ["mcp__ops__wipe"]);
+
+        let options = Options::new()
+            .with_mcp_server("ops", server.clone())
+            .disallow(ToolSelector::mcp_server("ops"));
+        assert!(options.effective_allowed_tools().is_empty());
+
+        let options = Options::new()
+            .with_mcp_server("ops", server)
+            .disallowed_tool("mcp__ops__*")
+            .allowed_tool("mcp__ops__status");
+        assert_eq!(options.effective_allowed_tools(), ["mcp__ops__status"]);
+    }
+
+    #[test]
+    fn test_auto_document_tools_appends_reference() {
+        let server = Arc::new(McpServer::new("ops", vec![tool("status"), tool("wipe")]));
+        let options = Options::new()
+            .with_mcp_server("ops", server)
+            .disallowed_tool("mcp__ops__wipe")
+            .append_system_prompt("Be brief.");
+        assert_eq!(
+            options.to_transport_options().append_system_prompt(),
+            Some("Be brief.")
+        );
+
+        let prompt = options
+            .auto_document_tools(true)
+            .to_transport_options()
+            .append_system_prompt()
+            .map(ToOwned::to_owned)
+            .unwrap();
+        assert!(prompt.starts_with("Be brief.\n\n## Available tools\n\n### mcp__ops__status\n"));
+        assert!(!prompt.contains("wipe"));
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn test_with_json_schema_wraps_non_object_roots() {
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Row {
+            name: String,
         }
-        builder.agents(self.agents.clone());
-        builder.strict_mcp_config(self.strict_mcp_config);
-        builder.disable_slash_commands(self.disable_slash_commands);
 
-        builder.build().expect("all fields have defaults")
+        let options = Options::new().with_json_schema::<Row>();
+        assert_eq!(options.json_schema_wrapper(), None);
+
+        let options = Options::new().with_json_schema::<Vec<Row>>();
+        let schema = serde_json::from_str::<Value>(options.json_schema().unwrap()).unwrap();
+        assert_eq!(options.json_schema_wrapper(), Some("items"));
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], serde_json::json!(["items"]));
+        assert_eq!(schema["properties"]["items"]["type"], "array");
+        assert!(schema["definitions"]["Row"].is_object());
+
+        let output = serde_json::json!({"items": [{"name": "a"}]});
+        assert_eq!(
+            util::unwrap_structured_output(output, options.json_schema_wrapper()),
+            serde_json::json!([{"name": "a"}])
+        );
+        let options = Options::new().with_json_schema::<u32>();
+        assert_eq!(options.json_schema_wrapper(), Some("value"));
+    }
+
+    #[test]
+    fn test_read_only_disallows_writes() {
+        let mut options = Options::new()
+            .permission_mode(PermissionMode::AcceptEdits)
+            .allowed_tool("Edit")
+            .allowed_tool("Read")
+            .read_only();
+        assert_eq!(options.permission_mode, Some(PermissionMode::Default));
+        assert_eq!(options.allowed_tools, ["Read"]);
+        assert_eq!(options.disallowed_tools, WRITE_TOOLS);
+        assert!(options.take_hooks().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_scan_secrets_installs_post_tool_use_hook() {
+        let hooks = Options::new().scan_secrets().take_hooks().unwrap().unwrap();
+        assert!(
+            hooks
+                .post_tool_use_hooks()
+                .any(|hook| hook.name() == Some(SECRET_SCAN_HOOK))
+        );
+    }
+
+    #[test]
+    fn test_profile_applies_its_permissions() {
+        let mut options = Options::new()
+            .allowed_tool("Read")
+            .profile(PermissionProfile::developer().max_budget_usd(2.0));
+        assert_eq!(options.permission_mode, Some(PermissionMode::AcceptEdits));
+        assert_eq!(
+            options
+                .allowed_tools
+                .iter()
+                .filter(|t| *t == "Read")
+                .count(),
+            1
+        );
+        assert!(options.allowed_tools.contains(&"Bash".to_owned()));
+        assert_eq!(options.restrict_paths, [PathBuf::from(".")]);
+        assert_eq!(options.max_budget_usd, Some(2.0));
+
+        let hooks = options.take_hooks().unwrap().unwrap();
+        assert!(
+            hooks
+                .pre_tool_use_hooks()
+                .any(|hook| hook.name() == Some(PROFILE_HOOK))
+        );
+        assert!(options.take_turn_profile().is_some());
+    }
+
+    #[test]
+    fn test_plugins_and_sandbox_share_settings() {
+        let transport = Options::new()
+            .sandbox(Sandbox::strict())
+            .with_plugin("formatter@tools")
+            .plugin_dir("plugins/local")
+            .to_transport_options();
+        let settings = serde_json::from_str::<Value>(transport.settings().unwrap()).unwrap();
+        assert_eq!(settings["enabledPlugins"]["formatter@tools"], true);
+        assert_eq!(settings["sandbox"]["enabled"], true);
+        assert_eq!(transport.plugin_dirs(), [PathBuf::from("plugins/local")]);
+    }
+
+    #[test]
+    fn test_interleaved_thinking_toggles_beta() {
+        let options = Options::new()
+            .betas(["other"])
+            .interleaved_thinking(true)
+            .interleaved_thinking(true);
+        assert_eq!(options.betas, ["other", INTERLEAVED_THINKING_BETA]);
+
+        let options = options.interleaved_thinking(false);
+        assert_eq!(options.betas, ["other"]);
+    }
+
+    #[test]
+    fn test_validate_thinking_budget() {
+        assert!(
+            Options::new()
+                .thinking(ThinkingConfig::enabled(8000))
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            Options::new()
+                .model("my-model")
+                .thinking(ThinkingConfig::enabled(200_000))
+                .validate()
+                .is_ok()
+        );
+
+        let err = Options::new()
+            .model(Model::Haiku)
+            .thinking(ThinkingConfig::enabled(100_000))
+            .validate()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::InvalidThinkingBudget {
+                budget: 100_000,
+                ..
+            }
+        ));
+        assert!(
+            Options::new()
+                .thinking(ThinkingConfig::enabled(10))
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_bypass_requires_acknowledgement() {
+        let err = Options::new()
+            .permission_mode(PermissionMode::BypassPermissions)
+            .validate()
+            .unwrap_err();
+        assert_eq!(err, ConfigError::UnacknowledgedBypass);
+
+        let options = Options::new()
+            .bypass_permissions(BypassAcknowledgement::new("runs in a disposable container"));
+        assert!(options.validate().is_ok());
+        assert_eq!(
+            options.to_transport_options().permission_mode(),
+            Some("bypassPermissions")
+        );
     }
 }
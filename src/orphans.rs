@@ -0,0 +1,298 @@
+//! Cleaning up CLI processes left behind by a crashed host.
+//!
+//! Each CLI runs in its own process group, and dropping or closing the
+//! client kills the whole group, including tool subprocesses the CLI
+//! started. A host that crashes never gets to do that, so its CLIs and their
+//! tools keep running.
+//!
+//! With [`Options::reap_orphans`](crate::Options::reap_orphans), every
+//! client records its process group in a lockfile in a shared directory and
+//! holds a lock on it for as long as it runs. When a client starts, it kills
+//! the groups of lockfiles no longer locked by anyone, whose hosts have
+//! died. [`reap`] does the same on demand.
+//!
+//! The lockfile also records when the group's leader started, and a group
+//! is only killed while its leader is still that process: once the CLI has
+//! exited, its process group id can be handed to an unrelated process. The
+//! start time is read from `/proc`, so where there is none, stale lockfiles
+//! are removed without killing anything.
+//!
+//! On Windows the CLI runs in a job object that the system kills with the
+//! host, so there is nothing to reap.
+
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// Kills the process groups recorded in `dir` by hosts that have exited,
+/// returning how many were found.
+pub fn reap(dir: impl AsRef<Path>) -> std::io::Result<usize> {
+    #[cfg(unix)]
+    {
+        unix::reap(dir.as_ref())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        Ok(0)
+    }
+}
+
+/// A lock on the lockfile recording one CLI's process group, released and
+/// removed when dropped.
+#[derive(Debug)]
+pub(crate) struct Lease {
+    #[cfg(unix)]
+    path: PathBuf,
+    #[cfg(unix)]
+    _file: std::fs::File,
+}
+
+impl Lease {
+    /// Reaps the orphans in `dir`, then records `pgid` there. Failing to
+    /// reap is only logged, so it never keeps a client from starting.
+    pub(crate) fn acquire(dir: &Path, pgid: u32) -> std::io::Result<Self> {
+        #[cfg(unix)]
+        {
+            match unix::reap(dir) {
+                Ok(0) => {}
+                Ok(reaped) => {
+                    tracing::warn!(reaped, dir = %dir.display(), "killed orphaned claude CLI processes");
+                }
+                Err(e) => {
+                    tracing::warn!(dir = %dir.display(), error = %e, "failed to reap orphaned claude CLI processes");
+                }
+            }
+            unix::lease(dir, pgid)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (dir, pgid);
+            Ok(Self {})
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Lease {
+    fn drop(&mut self) {
+        // A reaper that opened the file already must not find the group in
+        // it once the lock is released.
+        let _ = self._file.set_len(0);
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!(path = %self.path.display(), error = %e, "failed to remove lockfile");
+        }
+    }
+}
+
+/// Kills the process group `pgid`, ignoring groups that no longer exist.
+#[cfg(unix)]
+pub(crate) fn kill_group(pgid: u32) -> std::io::Result<()> {
+    let Ok(pgid) = libc::pid_t::try_from(pgid) else {
+        return Ok(());
+    };
+    // SAFETY: killpg only sends a signal.
+    if unsafe { libc::killpg(pgid, libc::SIGKILL) } == 0 {
+        return Ok(());
+    }
+    let error = std::io::Error::last_os_error();
+    if error.raw_os_error() == Some(libc::ESRCH) {
+        return Ok(());
+    }
+    Err(error)
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs::{File, OpenOptions};
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    use super::{Lease, kill_group};
+
+    const EXTENSION: &str = "lock";
+
+    fn try_lock(file: &File) -> bool {
+        // SAFETY: the descriptor is open for the duration of the call.
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+    }
+
+    pub(super) fn lease(dir: &Path, pgid: u32) -> std::io::Result<Lease> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}-{pgid}.{EXTENSION}", std::process::id()));
+        // Lock before writing, so `reap` never reads a lease half written.
+        // It may still have locked the file first, seen it empty and removed
+        // it, in which case the lock is on a file no longer there.
+        let mut file = loop {
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&path)?;
+            // SAFETY: the descriptor is open for the duration of the call.
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            match std::fs::metadata(&path) {
+                Ok(linked) if linked.ino() == file.metadata()?.ino() => break file,
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        };
+        file.set_len(0)?;
+        match start_time(pgid) {
+            Some(started) => writeln!(file, "{pgid} {started}")?,
+            None => writeln!(file, "{pgid}")?,
+        }
+        Ok(Lease { path, _file: file })
+    }
+
+    /// When process `pid` started, in clock ticks since boot, or `None` if it
+    /// does not exist or the system has no `/proc`.
+    fn start_time(pid: u32) -> Option<u64> {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        // The command name in parentheses may hold spaces; `starttime` is
+        // the 20th field after it.
+        let (_, fields) = stat.rsplit_once(')')?;
+        fields.split_whitespace().nth(19)?.parse().ok()
+    }
+
+    /// The process group recorded in a lockfile, if its leader is still the
+    /// process that started it.
+    fn recorded_group(content: &str) -> Option<u32> {
+        let mut fields = content.split_whitespace();
+        let pgid = fields.next()?.parse().ok()?;
+        let started = fields.next()?.parse::<u64>().ok()?;
+        (start_time(pgid) == Some(started)).then_some(pgid)
+    }
+
+    pub(super) fn reap(dir: &Path) -> std::io::Result<usize> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut reaped = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != EXTENSION) {
+                continue;
+            }
+            // A lockfile still locked belongs to a running host.
+            let Ok(file) = OpenOptions::new().read(true).open(&path) else {
+                continue;
+            };
+            if !try_lock(&file) {
+                continue;
+            }
+            // The lease may have ended since the file was opened, and a new
+            // one taken the path.
+            let linked = std::fs::metadata(&path)
+                .is_ok_and(|linked| file.metadata().is_ok_and(|m| m.ino() == linked.ino()));
+            if !linked {
+                continue;
+            }
+            let mut content = String::new();
+            let pgid = (&file)
+                .read_to_string(&mut content)
+                .ok()
+                .and_then(|_| recorded_group(&content));
+            if let Some(pgid) = pgid {
+                match kill_group(pgid) {
+                    Ok(()) => reaped += 1,
+                    Err(e) => {
+                        tracing::warn!(pgid, error = %e, "failed to kill orphaned process group")
+                    }
+                }
+            }
+            if let Err(e) = std::fs::remove_file(&path) {
+                tracing::warn!(path = %path.display(), error = %e, "failed to remove stale lockfile");
+            }
+        }
+        Ok(reaped)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn orphan() -> tokio::process::Child {
+        tokio::process::Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_reap_kills_unlocked_groups() {
+        let dir = std::env::temp_dir().join(format!("clauders-orphans-{}", crate::util::uuid_v7()));
+        let mut orphan = orphan();
+        let pgid = orphan.id().unwrap();
+
+        let lease = Lease::acquire(&dir, pgid).unwrap();
+        assert_eq!(reap(&dir).unwrap(), 0);
+        let recorded = std::fs::read_to_string(&lease.path).unwrap();
+        drop(lease);
+
+        // A crashed host leaves its lockfile behind, unlocked.
+        let path = dir.join(format!("1-{pgid}.lock"));
+        std::fs::write(&path, recorded).unwrap();
+        assert_eq!(reap(&dir).unwrap(), 1);
+        assert!(!path.exists());
+        assert!(!orphan.wait().await.unwrap().success());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reap_spares_groups_whose_leader_changed() {
+        let dir = std::env::temp_dir().join(format!("clauders-orphans-{}", crate::util::uuid_v7()));
+        let mut unrelated = orphan();
+        let pgid = unrelated.id().unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The recorded CLI exited and its group id went to another process,
+        // or the lockfile predates start times being recorded.
+        for (name, content) in [("1", format!("{pgid} 1\n")), ("2", format!("{pgid}\n"))] {
+            let path = dir.join(format!("{name}-{pgid}.lock"));
+            std::fs::write(&path, content).unwrap();
+            assert_eq!(reap(&dir).unwrap(), 0);
+            assert!(!path.exists());
+        }
+        assert!(unrelated.try_wait().unwrap().is_none());
+        unrelated.kill().await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_leases_survive_concurrent_reaping() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let dir = std::env::temp_dir().join(format!("clauders-orphans-{}", crate::util::uuid_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut leader = orphan();
+        let pgid = leader.id().unwrap();
+        let done = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    reap(&dir).unwrap();
+                }
+            });
+            for _ in 0..500 {
+                let lease = Lease::acquire(&dir, pgid).unwrap();
+                let recorded = std::fs::read_to_string(&lease.path).unwrap();
+                assert!(recorded.starts_with(&format!("{pgid}")), "{recorded:?}");
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        assert!(leader.try_wait().unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
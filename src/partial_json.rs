@@ -0,0 +1,144 @@
+//! Lenient parsing of truncated JSON documents.
+//!
+//! Used to turn the fragments of a streaming tool input into a best-effort
+//! snapshot of the value generated so far.
+
+use serde_json::Value;
+
+/// Parses `input` as JSON, completing it first if it was cut off.
+///
+/// A truncated string value is closed where it stops. Object keys, numbers
+/// and literals that were cut off are dropped, along with any key left
+/// without a value. Returns `None` if nothing usable has arrived yet.
+pub(crate) fn parse(input: &str) -> Option<Value> {
+    if let Ok(value) = serde_json::from_str(input) {
+        return Some(value);
+    }
+
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut string_is_key = false;
+    let mut escaped = false;
+    let mut in_scalar = false;
+    let mut expect_key = false;
+    // The longest prefix that only needs closing brackets, and those brackets.
+    let mut checkpoint: Option<(usize, Vec<u8>)> = None;
+
+    for (i, b) in input.bytes().enumerate() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => {
+                    in_string = false;
+                    if !string_is_key {
+                        checkpoint = Some((i + 1, stack.clone()));
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if in_scalar && !is_scalar_byte(b) {
+            in_scalar = false;
+            checkpoint = Some((i, stack.clone()));
+        }
+
+        match b {
+            b'{' | b'[' => {
+                stack.push(b);
+                expect_key = b == b'{';
+                checkpoint = Some((i + 1, stack.clone()));
+            }
+            b'}' | b']' => {
+                stack.pop();
+                expect_key = false;
+                checkpoint = Some((i + 1, stack.clone()));
+            }
+            b'"' => {
+                in_string = true;
+                string_is_key = expect_key && stack.last() == Some(&b'{');
+            }
+            b':' => expect_key = false,
+            b',' => expect_key = stack.last() == Some(&b'{'),
+            _ if b.is_ascii_whitespace() => {}
+            _ => in_scalar = true,
+        }
+    }
+
+    if in_string && !string_is_key {
+        let mut completed = input.to_owned();
+        if escaped {
+            completed.pop();
+        }
+        trim_partial_unicode_escape(&mut completed);
+        completed.push('"');
+        close(&mut completed, &stack);
+        if let Ok(value) = serde_json::from_str(&completed) {
+            return Some(value);
+        }
+    }
+
+    let (end, stack) = checkpoint?;
+    let mut completed = input[..end].to_owned();
+    close(&mut completed, &stack);
+    serde_json::from_str(&completed).ok()
+}
+
+fn is_scalar_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'+' | b'.')
+}
+
+/// Drops a `\uXXXX` escape that was cut off before its fourth hex digit.
+fn trim_partial_unicode_escape(s: &mut String) {
+    let tail_start = s.len().saturating_sub(5);
+    if let Some(pos) = s.get(tail_start..).and_then(|tail| tail.rfind("\\u")) {
+        let start = tail_start + pos;
+        if s[start + 2..].bytes().all(|b| b.is_ascii_hexdigit()) {
+            s.truncate(start);
+        }
+    }
+}
+
+fn close(s: &mut String, stack: &[u8]) {
+    for open in stack.iter().rev() {
+        s.push(if *open == b'{' { '}' } else { ']' });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_truncated_documents() {
+        assert_eq!(parse(r#"{"a": 1}"#), Some(json!({"a": 1})));
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("{"), Some(json!({})));
+        assert_eq!(parse(r#"{"title": "Hel"#), Some(json!({"title": "Hel"})));
+        assert_eq!(
+            parse(r#"{"title": "Hi", "ta"#),
+            Some(json!({"title": "Hi"}))
+        );
+        assert_eq!(
+            parse(r#"{"title": "Hi", "tags":"#),
+            Some(json!({"title": "Hi"}))
+        );
+        assert_eq!(parse(r#"{"n": 12, "m": 3"#), Some(json!({"n": 12})));
+        assert_eq!(parse(r#"{"ok": tr"#), Some(json!({})));
+        assert_eq!(
+            parse(r#"{"items": [{"id": 1}, {"id": 2, "name": "b"#),
+            Some(json!({"items": [{"id": 1}, {"id": 2, "name": "b"}]}))
+        );
+    }
+
+    #[test]
+    fn test_parse_truncated_escapes() {
+        assert_eq!(parse(r#"{"s": "a\"#), Some(json!({"s": "a"})));
+        assert_eq!(parse(r#"{"s": "a\u00"#), Some(json!({"s": "a"})));
+        assert_eq!(parse(r#"{"s": "a\n"#), Some(json!({"s": "a\n"})));
+    }
+}
@@ -1,14 +1,30 @@
+use std::future::Future;
 use std::sync::Arc;
 
-use crate::tool::ToolInput;
+use futures::FutureExt;
+use futures::future::{BoxFuture, Shared};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::oneshot;
+
+use crate::proto::control::PermissionRequest;
+use crate::tool::{ToolInput, ToolName, ToolSelector};
 
 pub use crate::proto::PermissionMode;
+pub use crate::proto::control::{PermissionBehavior, PermissionDestination, PermissionUpdate};
 
+/// A tool call the CLI asks permission for, with what is known about the
+/// session at the time.
 #[derive(Debug, Clone)]
 pub struct PermissionContext {
     tool_name: String,
     input: ToolInput,
     suggested_rules: Vec<PermissionRule>,
+    suggestions: Vec<PermissionUpdate>,
+    blocked_path: Option<String>,
+    permission_mode: Option<PermissionMode>,
+    session_id: Option<String>,
+    extra: Map<String, Value>,
 }
 
 impl PermissionContext {
@@ -21,14 +37,46 @@ impl PermissionContext {
             tool_name: tool_name.into(),
             input,
             suggested_rules,
+            suggestions: Vec::new(),
+            blocked_path: None,
+            permission_mode: None,
+            session_id: None,
+            extra: Map::new(),
         }
     }
 
+    /// The context of a `can_use_tool` request from the CLI.
+    pub(crate) fn from_request(request: &PermissionRequest) -> Self {
+        let suggestions = request
+            .permission_suggestions()
+            .unwrap_or_default()
+            .to_vec();
+        let suggested_rules = suggestions
+            .iter()
+            .flat_map(PermissionUpdate::rules)
+            .cloned()
+            .collect();
+        let mut context = Self::new(
+            request.tool_name(),
+            request.input().clone().into(),
+            suggested_rules,
+        )
+        .with_suggestions(suggestions)
+        .with_extra(request.extra().clone());
+        context.blocked_path = request.blocked_path().map(str::to_owned);
+        context
+    }
+
     // Getters
     pub fn tool_name(&self) -> &str {
         &self.tool_name
     }
 
+    /// The tool name split into its server and tool parts.
+    pub fn parsed_tool_name(&self) -> ToolName<'_> {
+        ToolName::parse(&self.tool_name)
+    }
+
     pub fn input(&self) -> &ToolInput {
         &self.input
     }
@@ -37,6 +85,34 @@ impl PermissionContext {
         &self.suggested_rules
     }
 
+    /// The permission updates the CLI suggests, such as always allowing
+    /// this command. Return the accepted ones with
+    /// [`Decision::allow_with_updates`] to apply them.
+    pub fn suggestions(&self) -> &[PermissionUpdate] {
+        &self.suggestions
+    }
+
+    /// The path outside the allowed directories that the call touches.
+    pub fn blocked_path(&self) -> Option<&str> {
+        self.blocked_path.as_deref()
+    }
+
+    /// The permission mode the session was in when the CLI asked, as last
+    /// set through the client.
+    pub fn permission_mode(&self) -> Option<PermissionMode> {
+        self.permission_mode
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// Fields of the request this version does not model, such as
+    /// `decision_reason`.
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
     // Setters
     pub fn set_tool_name(&mut self, tool_name: impl Into<String>) {
         self.tool_name = tool_name.into();
@@ -50,6 +126,26 @@ impl PermissionContext {
         self.suggested_rules = suggested_rules;
     }
 
+    pub fn set_suggestions(&mut self, suggestions: Vec<PermissionUpdate>) {
+        self.suggestions = suggestions;
+    }
+
+    pub fn set_blocked_path(&mut self, blocked_path: Option<String>) {
+        self.blocked_path = blocked_path;
+    }
+
+    pub fn set_permission_mode(&mut self, permission_mode: Option<PermissionMode>) {
+        self.permission_mode = permission_mode;
+    }
+
+    pub fn set_session_id(&mut self, session_id: Option<String>) {
+        self.session_id = session_id;
+    }
+
+    pub fn set_extra(&mut self, extra: Map<String, Value>) {
+        self.extra = extra;
+    }
+
     // Builders
     pub fn with_tool_name(mut self, tool_name: impl Into<String>) -> Self {
         self.set_tool_name(tool_name);
@@ -65,11 +161,42 @@ impl PermissionContext {
         self.set_suggested_rules(suggested_rules);
         self
     }
+
+    pub fn with_suggestions(mut self, suggestions: Vec<PermissionUpdate>) -> Self {
+        self.set_suggestions(suggestions);
+        self
+    }
+
+    pub fn with_blocked_path(mut self, blocked_path: impl Into<String>) -> Self {
+        self.set_blocked_path(Some(blocked_path.into()));
+        self
+    }
+
+    pub fn with_permission_mode(mut self, permission_mode: PermissionMode) -> Self {
+        self.set_permission_mode(Some(permission_mode));
+        self
+    }
+
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.set_session_id(Some(session_id.into()));
+        self
+    }
+
+    pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
+        self.set_extra(extra);
+        self
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PermissionRule {
+    #[serde(rename = "toolName")]
     tool_name: String,
+    #[serde(
+        rename = "ruleContent",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     rule: Option<String>,
 }
 
@@ -113,20 +240,48 @@ impl PermissionRule {
 
 #[derive(Debug, Clone)]
 pub enum Decision {
-    Allow { updated_input: Option<ToolInput> },
-    Deny { message: String, interrupt: bool },
+    Allow {
+        updated_input: Option<ToolInput>,
+        /// Permission updates for the CLI to apply, such as accepted
+        /// [suggestions](PermissionContext::suggestions).
+        updated_permissions: Vec<PermissionUpdate>,
+    },
+    Deny {
+        message: String,
+        interrupt: bool,
+    },
+    /// Leaves the call to a person, as a PreToolUse hook's
+    /// [`Ask`](crate::PreToolUseDecision::Ask) does: it goes to the
+    /// [`ApprovalBroker`](crate::approvals::ApprovalBroker), or is denied
+    /// with `reason` when there is none.
+    Ask {
+        reason: String,
+    },
+    /// A decision made later, such as by a reviewer in another service.
+    Deferred(DeferredDecision),
 }
 
 impl Decision {
     pub fn allow() -> Self {
         Self::Allow {
             updated_input: None,
+            updated_permissions: Vec::new(),
         }
     }
 
     pub fn allow_with_input(input: ToolInput) -> Self {
         Self::Allow {
             updated_input: Some(input),
+            updated_permissions: Vec::new(),
+        }
+    }
+
+    /// Allows the call and has the CLI apply `updates`, so that, for
+    /// example, the same call is not asked about again.
+    pub fn allow_with_updates(updates: Vec<PermissionUpdate>) -> Self {
+        Self::Allow {
+            updated_input: None,
+            updated_permissions: updates,
         }
     }
 
@@ -143,6 +298,67 @@ impl Decision {
             interrupt: true,
         }
     }
+
+    pub fn ask(reason: impl Into<String>) -> Self {
+        Self::Ask {
+            reason: reason.into(),
+        }
+    }
+
+    /// Decides with the result of `decision`, awaited when the CLI needs an
+    /// answer.
+    pub fn defer<F>(decision: F) -> Self
+    where
+        F: Future<Output = Decision> + Send + 'static,
+    {
+        Self::Deferred(DeferredDecision::new(decision))
+    }
+}
+
+/// A [`Decision`] that is not known yet.
+///
+/// Awaiting it is bounded by the broker's timeout when an approver returns
+/// one.
+#[derive(Clone)]
+pub struct DeferredDecision(Shared<BoxFuture<'static, Decision>>);
+
+impl DeferredDecision {
+    pub fn new<F>(decision: F) -> Self
+    where
+        F: Future<Output = Decision> + Send + 'static,
+    {
+        Self(decision.boxed().shared())
+    }
+
+    /// A decision sent later through the returned sender. Dropping the
+    /// sender denies the call.
+    pub fn channel() -> (Self, oneshot::Sender<Decision>) {
+        let (tx, rx) = oneshot::channel();
+        let decision = Self::new(async move {
+            rx.await
+                .unwrap_or_else(|_| Decision::deny("The decision was abandoned."))
+        });
+        (decision, tx)
+    }
+
+    /// Waits for the decision, following decisions deferred in turn.
+    pub async fn wait(self) -> Decision {
+        let mut deferred = self;
+        loop {
+            match deferred.0.await {
+                Decision::Deferred(next) => deferred = next,
+                decision => return decision,
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for DeferredDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DeferredDecision")
+            .field(&self.0.peek())
+            .finish()
+    }
 }
 
 pub type Callback = Arc<dyn Fn(PermissionContext) -> Decision + Send + Sync>;
@@ -154,3 +370,179 @@ pub fn default_allow(_ctx: PermissionContext) -> Decision {
 pub fn default_deny(ctx: PermissionContext) -> Decision {
     Decision::deny(format!("Tool '{}' not allowed", ctx.tool_name()))
 }
+
+/// Permission callbacks chosen by the tool being called, as hooks are.
+///
+/// The first callback registered for a matching [`ToolSelector`] decides;
+/// calls to other tools go to the fallback. A call nothing decides goes to
+/// the [`ApprovalBroker`](crate::approvals::ApprovalBroker), or is denied
+/// when there is none.
+///
+/// ```
+/// use clauders::permissions::{Decision, PermissionCallbacks, default_deny};
+/// use clauders::tool::{BuiltinTool, ToolSelector};
+///
+/// let callbacks = PermissionCallbacks::new()
+///     .on(BuiltinTool::Read, |_| Decision::allow())
+///     .on(ToolSelector::mcp_server("github"), |ctx| {
+///         Decision::ask(format!("{} touches GitHub", ctx.tool_name()))
+///     })
+///     .fallback(default_deny);
+/// ```
+#[derive(Clone, Default)]
+pub struct PermissionCallbacks {
+    callbacks: Vec<(ToolSelector, Callback)>,
+    fallback: Option<Callback>,
+}
+
+impl PermissionCallbacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decides calls to tools matching `tool` with `callback`.
+    #[must_use]
+    pub fn on<F>(mut self, tool: impl Into<ToolSelector>, callback: F) -> Self
+    where
+        F: Fn(PermissionContext) -> Decision + Send + Sync + 'static,
+    {
+        self.callbacks.push((tool.into(), Arc::new(callback)));
+        self
+    }
+
+    /// Decides calls no other callback matches.
+    #[must_use]
+    pub fn fallback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(PermissionContext) -> Decision + Send + Sync + 'static,
+    {
+        self.fallback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty() && self.fallback.is_none()
+    }
+
+    /// The callback deciding calls to `tool_name`, if any.
+    pub fn callback_for(&self, tool_name: &str) -> Option<&Callback> {
+        self.callbacks
+            .iter()
+            .find(|(tool, _)| tool.matches(tool_name))
+            .map(|(_, callback)| callback)
+            .or(self.fallback.as_ref())
+    }
+
+    /// The selector of the callback deciding calls to `tool_name`, or
+    /// `"fallback"` for the fallback callback.
+    pub(crate) fn rule_for(&self, tool_name: &str) -> Option<String> {
+        self.callbacks
+            .iter()
+            .find(|(tool, _)| tool.matches(tool_name))
+            .map(|(tool, _)| tool.to_string())
+            .or_else(|| self.fallback.as_ref().map(|_| "fallback".to_owned()))
+    }
+
+    /// Decides `context` with the matching callback, if any.
+    pub fn decide(&self, context: &PermissionContext) -> Option<Decision> {
+        self.callback_for(context.tool_name())
+            .map(|callback| callback(context.clone()))
+    }
+}
+
+impl std::fmt::Debug for PermissionCallbacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PermissionCallbacks")
+            .field(
+                "tools",
+                &self
+                    .callbacks
+                    .iter()
+                    .map(|(tool, _)| tool)
+                    .collect::<Vec<_>>(),
+            )
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+/// A deliberate opt-in to running without permission checks.
+///
+/// [`PermissionMode::BypassPermissions`] is only accepted alongside one of
+/// these, passed to [`Options::bypass_permissions`](crate::Options::bypass_permissions).
+/// The reason is logged when the client starts, so every bypass in a code
+/// base can be found and justified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BypassAcknowledgement {
+    reason: String,
+}
+
+impl BypassAcknowledgement {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_permission_context_from_request() {
+        let request: PermissionRequest = serde_json::from_value(json!({
+            "tool_name": "Write",
+            "input": {"file_path": "/etc/hosts"},
+            "blocked_path": "/etc/hosts",
+            "decision_reason": "outside the working directory",
+            "tool_use_id": "toolu_1"
+        }))
+        .unwrap();
+        let context = PermissionContext::from_request(&request)
+            .with_permission_mode(PermissionMode::AcceptEdits)
+            .with_session_id("session-1");
+        assert_eq!(context.tool_name(), "Write");
+        assert_eq!(context.blocked_path(), Some("/etc/hosts"));
+        assert_eq!(context.permission_mode(), Some(PermissionMode::AcceptEdits));
+        assert_eq!(context.session_id(), Some("session-1"));
+        assert_eq!(
+            context.extra()["decision_reason"],
+            "outside the working directory"
+        );
+        assert!(context.suggestions().is_empty());
+    }
+
+    #[test]
+    fn test_permission_callbacks_by_tool() {
+        use crate::tool::BuiltinTool;
+
+        let callbacks = PermissionCallbacks::new()
+            .on(BuiltinTool::Read, |_| Decision::allow())
+            .on(ToolSelector::mcp_server("github"), |ctx| {
+                Decision::ask(ctx.tool_name().to_owned())
+            });
+        let context = |tool: &str| PermissionContext::new(tool, json!({}).into(), Vec::new());
+        assert!(matches!(
+            callbacks.decide(&context("Read")),
+            Some(Decision::Allow { .. })
+        ));
+        assert!(matches!(
+            callbacks.decide(&context("mcp__github__delete_repo")),
+            Some(Decision::Ask { reason }) if reason == "mcp__github__delete_repo"
+        ));
+        assert!(callbacks.decide(&context("Bash")).is_none());
+
+        let callbacks = callbacks.fallback(default_deny);
+        assert!(matches!(
+            callbacks.decide(&context("Bash")),
+            Some(Decision::Deny { .. })
+        ));
+    }
+}
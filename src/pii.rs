@@ -0,0 +1,262 @@
+//! Detection of personal data in prompts and output.
+//!
+//! A [`PiiDetector`] finds the kinds of personal data a deployment cares
+//! about, from the built-in [`PiiKind`]s and patterns of its own. The same
+//! detector can keep prompts carrying personal data from being sent, with
+//! [`Options::block_pii`](crate::Options::block_pii), and check Claude's
+//! output as [guardrails](crate::guardrails) when that feature is enabled.
+//!
+//! Detection is pattern based: it catches the common formats, not every way
+//! of writing them, and can flag numbers that only look like personal data.
+//!
+//! ```no_run
+//! use clauders::pii::{PiiDetector, PiiKind};
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let detector = PiiDetector::new()
+//!     .detect(PiiKind::Email)
+//!     .detect(PiiKind::UsSsn);
+//! let client = Client::new(Options::new().block_pii(detector)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Range;
+
+use regex::Regex;
+
+#[cfg(feature = "guardrails")]
+use crate::guardrails::{Guardrail, GuardrailAction};
+use crate::hooks::UserPromptSubmitOutput;
+
+/// The kinds of personal data detected without further configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PiiKind {
+    Email,
+    /// Phone numbers written with separators, such as `(555) 123-4567` or
+    /// `+44 20 7946 0958`, or in international form without them.
+    PhoneNumber,
+    /// US social security numbers, as `123-45-6789`.
+    UsSsn,
+    /// UK national insurance numbers, such as `AB 12 34 56 C`.
+    UkNationalInsurance,
+}
+
+impl PiiKind {
+    pub const ALL: [Self; 4] = [
+        Self::Email,
+        Self::PhoneNumber,
+        Self::UsSsn,
+        Self::UkNationalInsurance,
+    ];
+
+    /// The name matches of this kind are reported under.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::PhoneNumber => "phone_number",
+            Self::UsSsn => "us_ssn",
+            Self::UkNationalInsurance => "uk_national_insurance",
+        }
+    }
+
+    fn pattern(self) -> &'static str {
+        match self {
+            Self::Email => r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b",
+            Self::PhoneNumber => {
+                r"\+\d{8,15}\b|(?:\+\d{1,3}[\s.-]?)?(?:\(\d{2,4}\)[\s.-]?)?\b(?:\d{2,4}[\s.-])?\d{3,4}[\s.-]\d{4}\b"
+            }
+            Self::UsSsn => r"\b\d{3}-\d{2}-\d{4}\b",
+            Self::UkNationalInsurance => {
+                r"\b[A-CEGHJ-PR-TW-Z][A-CEGHJ-NPR-TW-Z]\s?\d{2}\s?\d{2}\s?\d{2}\s?[A-D]\b"
+            }
+        }
+    }
+}
+
+/// Personal data found in a text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiMatch {
+    kind: String,
+    span: Range<usize>,
+    text: String,
+}
+
+impl PiiMatch {
+    /// The [name](PiiKind::name) of the kind, or of the custom pattern, that
+    /// matched.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The byte range of the match in the text.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Finds personal data of the configured kinds.
+#[derive(Debug, Clone, Default)]
+pub struct PiiDetector {
+    patterns: Vec<(String, Regex)>,
+}
+
+impl PiiDetector {
+    /// Creates a detector that detects nothing until kinds are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a detector for every [`PiiKind`].
+    pub fn all() -> Self {
+        PiiKind::ALL
+            .into_iter()
+            .fold(Self::new(), |detector, kind| detector.detect(kind))
+    }
+
+    #[must_use]
+    pub fn detect(self, kind: PiiKind) -> Self {
+        let regex = Regex::new(kind.pattern()).expect("built-in PII patterns are valid");
+        self.pattern(kind.name(), regex)
+    }
+
+    /// Also detects matches of `regex`, such as a national id format of the
+    /// deployment's country, reported under `name`.
+    #[must_use]
+    pub fn pattern(mut self, name: impl Into<String>, regex: Regex) -> Self {
+        let name = name.into();
+        self.patterns.retain(|(existing, _)| *existing != name);
+        self.patterns.push((name, regex));
+        self
+    }
+
+    /// The names of the kinds and patterns detected.
+    pub fn kinds(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Finds the personal data in `text`, ordered by position.
+    pub fn find(&self, text: &str) -> Vec<PiiMatch> {
+        let mut matches = self
+            .patterns
+            .iter()
+            .flat_map(|(name, regex)| {
+                regex.find_iter(text).map(|m| PiiMatch {
+                    kind: name.clone(),
+                    span: m.range(),
+                    text: m.as_str().to_owned(),
+                })
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|m| (m.span.start, m.span.end));
+        matches
+    }
+
+    pub fn contains_pii(&self, text: &str) -> bool {
+        self.patterns.iter().any(|(_, regex)| regex.is_match(text))
+    }
+
+    /// Decides a UserPromptSubmit hook: blocks a prompt containing personal
+    /// data, naming the kinds found but not the data itself.
+    pub fn evaluate_prompt(&self, prompt: &str) -> UserPromptSubmitOutput {
+        let mut kinds = self
+            .find(prompt)
+            .into_iter()
+            .map(|m| m.kind)
+            .collect::<Vec<_>>();
+        if kinds.is_empty() {
+            return UserPromptSubmitOutput::pass();
+        }
+        kinds.sort();
+        kinds.dedup();
+        tracing::warn!(kinds = ?kinds, "prompt blocked for containing personal data");
+        UserPromptSubmitOutput::block(format!(
+            "prompt contains personal data ({})",
+            kinds.join(", ")
+        ))
+    }
+
+    /// One guardrail per kind detected, named after it, each taking
+    /// `action`.
+    #[cfg(feature = "guardrails")]
+    pub fn guardrails(&self, action: GuardrailAction) -> Vec<Guardrail> {
+        self.patterns
+            .iter()
+            .map(|(name, regex)| Guardrail::regex(name.clone(), regex.clone()).action(action))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hooks::UserPromptSubmitDecision;
+
+    #[test]
+    fn test_detects_builtin_kinds() {
+        let detector = PiiDetector::all();
+        let kinds = |text: &str| {
+            detector
+                .find(text)
+                .into_iter()
+                .map(|m| (m.kind, m.text))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kinds("Mail jane.doe@example.co.uk or call (555) 123-4567."),
+            [
+                ("email".to_owned(), "jane.doe@example.co.uk".to_owned()),
+                ("phone_number".to_owned(), "(555) 123-4567".to_owned()),
+            ]
+        );
+        assert_eq!(
+            kinds("+44 20 7946 0958 and +15551234567"),
+            [
+                ("phone_number".to_owned(), "+44 20 7946 0958".to_owned()),
+                ("phone_number".to_owned(), "+15551234567".to_owned()),
+            ]
+        );
+        assert_eq!(
+            kinds("SSN 123-45-6789, NI AB 12 34 56 C"),
+            [
+                ("us_ssn".to_owned(), "123-45-6789".to_owned()),
+                (
+                    "uk_national_insurance".to_owned(),
+                    "AB 12 34 56 C".to_owned()
+                ),
+            ]
+        );
+        assert!(
+            kinds("Released 2024-10-16 on 192.168.1.10, version 1.2.3, issue #12345").is_empty()
+        );
+    }
+
+    #[test]
+    fn test_prompt_is_blocked_by_kind() {
+        let detector = PiiDetector::new()
+            .detect(PiiKind::Email)
+            .pattern("employee_id", Regex::new(r"\bEMP-\d{6}\b").unwrap());
+        assert_eq!(
+            detector.kinds().collect::<Vec<_>>(),
+            ["email", "employee_id"]
+        );
+
+        let output = detector.evaluate_prompt("Look up EMP-004211 and a@b.io, then b@c.io");
+        assert_eq!(output.decision(), Some(UserPromptSubmitDecision::Block));
+        assert_eq!(
+            output.reason(),
+            Some("prompt contains personal data (email, employee_id)")
+        );
+        assert_eq!(
+            detector.evaluate_prompt("Call 555-123-4567").decision(),
+            None
+        );
+        assert!(!PiiDetector::new().contains_pii("a@b.io"));
+    }
+}
@@ -0,0 +1,482 @@
+//! Allow/deny rules for the CLI's `Bash` tool.
+//!
+//! A command line is split into its individual commands on `&&`, `||`, `;`,
+//! `|`, `&`, newlines and subshell parentheses, with the contents of `$(...)`
+//! and backtick substitutions checked as commands of their own. Each command
+//! is then matched against the policy's patterns.
+//!
+//! ```
+//! use clauders::policy::BashPolicy;
+//!
+//! let policy = BashPolicy::new()
+//!     .allow("git status")
+//!     .allow("cargo *")
+//!     .deny("cargo publish *");
+//!
+//! assert!(policy.check("git status && cargo test --workspace").is_ok());
+//!
+//! let violations = policy.check("cargo publish --dry-run | tee log").unwrap_err();
+//! assert_eq!(violations.len(), 2);
+//! ```
+
+use std::fmt;
+
+use crate::hooks::PreToolUseOutput;
+use crate::permissions::{Decision, PermissionContext};
+use crate::tool::ToolInput;
+
+/// A pattern matched against a command's words.
+///
+/// Words are separated by whitespace and may contain `*` wildcards. A final
+/// `*` word matches any number of remaining arguments, including none, so
+/// `git *` matches both `git` and `git log -p`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPattern {
+    source: String,
+    words: Vec<String>,
+}
+
+impl CommandPattern {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        let source = pattern.into();
+        let words = source.split_whitespace().map(str::to_owned).collect();
+        Self { source, words }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, words: &[String]) -> bool {
+        for (i, pattern) in self.words.iter().enumerate() {
+            if pattern == "*" && i == self.words.len() - 1 {
+                return true;
+            }
+            match words.get(i) {
+                Some(word) if glob_match(pattern, word) => {}
+                _ => return false,
+            }
+        }
+        words.len() == self.words.len()
+    }
+}
+
+impl fmt::Display for CommandPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Why a command was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationReason {
+    /// The command matched a deny pattern.
+    Denied { pattern: String },
+    /// The policy has allow patterns and the command matched none of them.
+    NotAllowed,
+    /// The command line could not be split into commands.
+    Unparseable(String),
+}
+
+/// A command rejected by a [`BashPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    command: String,
+    reason: ViolationReason,
+}
+
+impl Violation {
+    /// The offending command, or the whole command line if it could not be parsed.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn reason(&self) -> &ViolationReason {
+        &self.reason
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            ViolationReason::Denied { pattern } => {
+                write!(f, "`{}` matches denied pattern `{pattern}`", self.command)
+            }
+            ViolationReason::NotAllowed => {
+                write!(f, "`{}` does not match any allowed pattern", self.command)
+            }
+            ViolationReason::Unparseable(message) => {
+                write!(f, "cannot parse `{}`: {message}", self.command)
+            }
+        }
+    }
+}
+
+/// Allow and deny patterns for shell commands.
+///
+/// Deny patterns take precedence. When no allow patterns are given, every
+/// command that is not denied is allowed.
+#[derive(Debug, Clone, Default)]
+pub struct BashPolicy {
+    allow: Vec<CommandPattern>,
+    deny: Vec<CommandPattern>,
+}
+
+impl BashPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn allow(mut self, pattern: impl Into<String>) -> Self {
+        self.allow.push(CommandPattern::new(pattern));
+        self
+    }
+
+    #[must_use]
+    pub fn deny(mut self, pattern: impl Into<String>) -> Self {
+        self.deny.push(CommandPattern::new(pattern));
+        self
+    }
+
+    pub fn allowed_patterns(&self) -> &[CommandPattern] {
+        &self.allow
+    }
+
+    pub fn denied_patterns(&self) -> &[CommandPattern] {
+        &self.deny
+    }
+
+    /// Checks every command in `command_line`, returning all violations.
+    pub fn check(&self, command_line: &str) -> Result<(), Vec<Violation>> {
+        let commands = match split_commands(command_line) {
+            Ok(commands) => commands,
+            Err(message) => {
+                return Err(vec![Violation {
+                    command: command_line.to_owned(),
+                    reason: ViolationReason::Unparseable(message),
+                }]);
+            }
+        };
+
+        let violations = commands
+            .iter()
+            .filter_map(|words| self.check_words(words))
+            .collect::<Vec<_>>();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Checks a `Bash` tool input. Inputs without a `command` pass.
+    pub fn check_input(&self, input: &ToolInput) -> Result<(), Vec<Violation>> {
+        match input.get_string("command") {
+            Some(command) => self.check(command),
+            None => Ok(()),
+        }
+    }
+
+    /// Evaluates a tool call for a PreToolUse hook: `Bash` calls that violate
+    /// the policy are denied, and everything else passes through.
+    pub fn hook_output(&self, tool_name: &str, input: &ToolInput) -> PreToolUseOutput {
+        if tool_name != "Bash" {
+            return PreToolUseOutput::new();
+        }
+        match self.check_input(input) {
+            Ok(()) => PreToolUseOutput::new(),
+            Err(violations) => PreToolUseOutput::deny(describe(&violations)),
+        }
+    }
+
+    /// Evaluates a tool call for a permission callback. Returns `None` for
+    /// tools other than `Bash`, leaving the decision to the caller.
+    pub fn decision(&self, ctx: &PermissionContext) -> Option<Decision> {
+        if ctx.tool_name() != "Bash" {
+            return None;
+        }
+        Some(match self.check_input(ctx.input()) {
+            Ok(()) => Decision::allow(),
+            Err(violations) => Decision::deny(describe(&violations)),
+        })
+    }
+
+    fn check_words(&self, words: &[String]) -> Option<Violation> {
+        // Leading `NAME=value` assignments only set the environment.
+        let start = words
+            .iter()
+            .position(|word| !is_assignment(word))
+            .unwrap_or(words.len());
+        let words = &words[start..];
+        if words.is_empty() {
+            return None;
+        }
+
+        let reason = if let Some(pattern) = self.deny.iter().find(|p| p.matches(words)) {
+            ViolationReason::Denied {
+                pattern: pattern.to_string(),
+            }
+        } else if !self.allow.is_empty() && !self.allow.iter().any(|p| p.matches(words)) {
+            ViolationReason::NotAllowed
+        } else {
+            return None;
+        };
+        Some(Violation {
+            command: words.join(" "),
+            reason,
+        })
+    }
+}
+
+fn describe(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// Splits a command line into commands, each a list of unquoted words.
+fn split_commands(line: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut commands = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars().peekable();
+
+    let end_word = |word: &mut String, in_word: &mut bool, words: &mut Vec<String>| {
+        if *in_word {
+            words.push(std::mem::take(word));
+            *in_word = false;
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_owned()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_owned()),
+                        },
+                        Some('$') if chars.peek() == Some(&'(') => {
+                            chars.next();
+                            let inner = take_substitution(&mut chars)?;
+                            commands.extend(split_commands(&inner)?);
+                            word.push_str(&format!("$({inner})"));
+                        }
+                        Some('`') => {
+                            let inner = take_backticks(&mut chars)?;
+                            commands.extend(split_commands(&inner)?);
+                            word.push_str(&format!("`{inner}`"));
+                        }
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_owned()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some('\n') | None => {}
+                    Some(c) => word.push(c),
+                }
+            }
+            '$' if chars.peek() == Some(&'(') => {
+                chars.next();
+                in_word = true;
+                let inner = take_substitution(&mut chars)?;
+                commands.extend(split_commands(&inner)?);
+                word.push_str(&format!("$({inner})"));
+            }
+            '`' => {
+                in_word = true;
+                let inner = take_backticks(&mut chars)?;
+                commands.extend(split_commands(&inner)?);
+                word.push_str(&format!("`{inner}`"));
+            }
+            ';' | '&' | '|' | '(' | ')' | '\n' => {
+                end_word(&mut word, &mut in_word, &mut words);
+                if !words.is_empty() {
+                    commands.push(std::mem::take(&mut words));
+                }
+            }
+            c if c.is_whitespace() => end_word(&mut word, &mut in_word, &mut words),
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    end_word(&mut word, &mut in_word, &mut words);
+    if !words.is_empty() {
+        commands.push(words);
+    }
+    Ok(commands)
+}
+
+/// Reads the body of a `$(...)` substitution, after the opening parenthesis.
+fn take_substitution(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut depth = 1;
+    let mut inner = String::new();
+    for c in chars {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(inner);
+                }
+            }
+            _ => {}
+        }
+        inner.push(c);
+    }
+    Err("unterminated command substitution".to_owned())
+}
+
+/// Reads the body of a backtick substitution, after the opening backtick.
+fn take_backticks(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut inner = String::new();
+    for c in chars {
+        if c == '`' {
+            return Ok(inner);
+        }
+        inner.push(c);
+    }
+    Err("unterminated backtick substitution".to_owned())
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = rest.split('*').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return remaining.len() >= part.len() && remaining.ends_with(part);
+        }
+        match remaining.find(part) {
+            Some(pos) => remaining = &remaining[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(s: &str) -> Vec<String> {
+        s.split_whitespace().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn test_split_commands() {
+        assert_eq!(
+            split_commands("cd src && ls -la | grep 'a b'; echo \"x;y\" &").unwrap(),
+            vec![
+                words("cd src"),
+                words("ls -la"),
+                vec!["grep".to_owned(), "a b".to_owned()],
+                vec!["echo".to_owned(), "x;y".to_owned()],
+            ]
+        );
+        assert_eq!(
+            split_commands("echo $(rm -rf x) `id`").unwrap(),
+            vec![
+                words("rm -rf x"),
+                words("id"),
+                vec![
+                    "echo".to_owned(),
+                    "$(rm -rf x)".to_owned(),
+                    "`id`".to_owned()
+                ],
+            ]
+        );
+        assert!(split_commands("echo 'oops").is_err());
+    }
+
+    #[test]
+    fn test_policy_violations() {
+        let policy = BashPolicy::new()
+            .allow("git *")
+            .allow("ls")
+            .allow("cargo t* *")
+            .deny("git push *");
+
+        assert!(policy.check("RUST_LOG=debug cargo test -q && ls").is_ok());
+        assert!(policy.check("git").is_ok());
+
+        let violations = policy
+            .check("git push origin main; ls -la; echo $(whoami)")
+            .unwrap_err();
+        let reasons = violations
+            .iter()
+            .map(|v| (v.command(), v.reason().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            [
+                (
+                    "git push origin main",
+                    ViolationReason::Denied {
+                        pattern: "git push *".to_owned()
+                    }
+                ),
+                ("ls -la", ViolationReason::NotAllowed),
+                ("whoami", ViolationReason::NotAllowed),
+                ("echo $(whoami)", ViolationReason::NotAllowed),
+            ]
+        );
+        assert!(matches!(
+            policy.check("echo \"unterminated").unwrap_err()[0].reason(),
+            ViolationReason::Unparseable(_)
+        ));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("t*", "test"));
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(glob_match("a*b*c", "aXbYc"));
+        assert!(!glob_match("a*b", "ab-"));
+        assert!(!glob_match("x", "xy"));
+    }
+}
@@ -0,0 +1,404 @@
+//! Policies loaded from a reviewable document.
+//!
+//! A [`Policy`] gathers the settings a security team signs off on (tool
+//! lists, path restrictions, shell rules, budgets, output redaction and
+//! approvals) into one TOML or JSON document, so the policy can be reviewed
+//! without reading the service's code. Loading compiles it into the hooks,
+//! permission callbacks and limits applied with
+//! [`Options::policy`](crate::Options::policy), and the guardrails a
+//! conversation checks with
+//! [`Conversation::with_guardrails`](crate::Conversation::with_guardrails).
+//!
+//! ```toml
+//! allowed_tools = ["Read", "Grep", "Edit", "Bash"]
+//! disallowed_tools = ["WebFetch"]
+//! restrict_paths = ["src", "tests"]
+//! scan_secrets = true
+//!
+//! [bash]
+//! allow = ["cargo *", "git status *", "git diff *"]
+//! deny = ["cargo publish *"]
+//!
+//! [budget]
+//! max_usd = 5.0
+//! max_tool_calls = 200
+//! max_turn_duration_secs = 900
+//!
+//! [[redact]]
+//! name = "api-key"
+//! pattern = "sk-[A-Za-z0-9]{20,}"
+//!
+//! [[redact]]
+//! name = "medical"
+//! topics = ["diagnosis", "prescription"]
+//! action = "interrupt"
+//!
+//! [approvals]
+//! tools = ["Edit"]
+//! approver = "cli"
+//! timeout_secs = 120
+//! ```
+//!
+//! Unknown keys are rejected, so a misspelt rule fails to load rather than
+//! being silently ignored.
+//!
+//! ```no_run
+//! use clauders::policy::Policy;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let policy = Policy::from_file("policy.toml")?;
+//! let client = Client::new(Options::new().policy(&policy)).await?;
+//! let mut conv = client.conversation().with_guardrails(policy.guardrails());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::approvals::{ApprovalBroker, CliApprover};
+use crate::error::{ConfigError, Error};
+use crate::guardrails::{Guardrail, GuardrailAction, Guardrails};
+use crate::hooks::PreToolUseOutput;
+use crate::options::Options;
+use crate::permissions::Decision;
+use crate::policy::BashPolicy;
+use crate::tool::BuiltinTool;
+
+const BASH_HOOK: &str = "policy_bash";
+const APPROVALS_HOOK: &str = "policy_approvals";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Document {
+    allowed_tools: Vec<String>,
+    disallowed_tools: Vec<String>,
+    restrict_paths: Vec<PathBuf>,
+    read_only: bool,
+    scan_secrets: bool,
+    bash: Option<BashRules>,
+    budget: Budget,
+    redact: Vec<RedactRule>,
+    approvals: Option<Approvals>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BashRules {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Budget {
+    max_usd: Option<f64>,
+    max_tool_calls: Option<u32>,
+    max_turn_duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RedactRule {
+    name: String,
+    #[serde(default)]
+    pattern: Option<String>,
+    #[serde(default)]
+    topics: Vec<String>,
+    #[serde(default)]
+    action: RuleAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RuleAction {
+    Flag,
+    #[default]
+    Redact,
+    Interrupt,
+}
+
+impl From<RuleAction> for GuardrailAction {
+    fn from(action: RuleAction) -> Self {
+        match action {
+            RuleAction::Flag => Self::Flag,
+            RuleAction::Redact => Self::Redact,
+            RuleAction::Interrupt => Self::Interrupt,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Approvals {
+    tools: Vec<String>,
+    #[serde(default)]
+    approver: ApproverKind,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ApproverKind {
+    #[default]
+    Cli,
+    Webhook,
+}
+
+/// A policy document, compiled and ready to apply.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    document: Document,
+    bash: Option<BashPolicy>,
+    guardrails: Guardrails,
+    approvals: Option<ApprovalBroker>,
+}
+
+impl Policy {
+    /// Loads the policy at `path`: JSON if its extension is `.json`, TOML
+    /// otherwise.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        let document = toml::from_str(text).map_err(|e| invalid(e.message()))?;
+        Self::compile(document)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, Error> {
+        let document = serde_json::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        Self::compile(document)
+    }
+
+    fn compile(document: Document) -> Result<Self, Error> {
+        let bash = document.bash.as_ref().map(|rules| {
+            let policy = rules
+                .allow
+                .iter()
+                .fold(BashPolicy::new(), |policy, pattern| policy.allow(pattern));
+            rules
+                .deny
+                .iter()
+                .fold(policy, |policy, pattern| policy.deny(pattern))
+        });
+
+        let mut guardrails = Guardrails::new();
+        for rule in &document.redact {
+            let guardrail = match (&rule.pattern, rule.topics.is_empty()) {
+                (Some(pattern), true) => {
+                    let regex = Regex::new(pattern).map_err(|e| {
+                        invalid(format!(
+                            "redact rule '{}' has an invalid pattern: {e}",
+                            rule.name
+                        ))
+                    })?;
+                    Guardrail::regex(&rule.name, regex)
+                }
+                (None, false) => Guardrail::topics(&rule.name, &rule.topics),
+                _ => {
+                    return Err(invalid(format!(
+                        "redact rule '{}' needs exactly one of pattern and topics",
+                        rule.name
+                    )));
+                }
+            };
+            guardrails = guardrails.guardrail(guardrail.action(rule.action.into()));
+        }
+
+        let approvals = document
+            .approvals
+            .as_ref()
+            .map(|approvals| {
+                let broker = broker(approvals)?;
+                Ok::<_, Error>(match approvals.timeout_secs {
+                    Some(secs) => broker.timeout(Duration::from_secs(secs)),
+                    None => broker,
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            document,
+            bash,
+            guardrails,
+            approvals,
+        })
+    }
+
+    /// The guardrails of the policy's redaction rules.
+    pub fn guardrails(&self) -> Guardrails {
+        self.guardrails.clone()
+    }
+
+    /// Applies the policy to `options`, adding to their tool lists, hooks
+    /// and permission callbacks and replacing their path restrictions,
+    /// limits and approval broker where the policy sets them.
+    pub(crate) fn apply(&self, mut options: Options) -> Options {
+        let document = &self.document;
+        for tool in &document.allowed_tools {
+            options = options.allowed_tool(tool);
+        }
+        for tool in &document.disallowed_tools {
+            options = options.disallowed_tool(tool);
+        }
+        if !document.restrict_paths.is_empty() {
+            options = options.restrict_paths(&document.restrict_paths);
+        }
+        if document.read_only {
+            options = options.read_only();
+        }
+        if document.scan_secrets {
+            options = options.scan_secrets();
+        }
+        if let Some(budget) = document.budget.max_usd {
+            options = options.max_budget_usd(budget);
+        }
+        if let Some(limit) = document.budget.max_tool_calls {
+            options = options.max_tool_calls(limit);
+        }
+        if let Some(secs) = document.budget.max_turn_duration_secs {
+            options = options.max_turn_duration(Duration::from_secs(secs));
+        }
+
+        if let Some(bash) = &self.bash {
+            let hook_policy = Arc::new(bash.clone());
+            options
+                .hooks_mut()
+                .add_pre_tool_use_named(BASH_HOOK, "Bash", move |input| {
+                    let output = hook_policy.hook_output(input.tool_name(), input.tool_input());
+                    async move { output }
+                });
+            let callback_policy = bash.clone();
+            let callbacks = std::mem::take(options.permission_callbacks_mut());
+            *options.permission_callbacks_mut() = callbacks.on(BuiltinTool::Bash, move |ctx| {
+                callback_policy
+                    .decision(&ctx)
+                    .unwrap_or_else(Decision::allow)
+            });
+        }
+
+        if let (Some(approvals), Some(broker)) = (&document.approvals, &self.approvals) {
+            let matcher = approvals.tools.join("|");
+            options
+                .hooks_mut()
+                .add_pre_tool_use_named(APPROVALS_HOOK, matcher, |input| {
+                    let reason = format!("policy requires approval for {}", input.tool_name());
+                    async move { PreToolUseOutput::ask(reason) }
+                });
+            options = options.approvals(broker.clone());
+        }
+        options
+    }
+}
+
+fn broker(approvals: &Approvals) -> Result<ApprovalBroker, Error> {
+    match (approvals.approver, &approvals.url) {
+        (ApproverKind::Cli, None) => Ok(ApprovalBroker::new(CliApprover::new())),
+        #[cfg(feature = "webhooks")]
+        (ApproverKind::Webhook, Some(url)) => Ok(ApprovalBroker::new(
+            crate::approvals::WebhookApprover::new(url),
+        )),
+        #[cfg(not(feature = "webhooks"))]
+        (ApproverKind::Webhook, Some(_)) => {
+            Err(invalid("webhook approvals require the webhooks feature"))
+        }
+        (ApproverKind::Cli, Some(_)) => Err(invalid("the cli approver takes no url")),
+        (ApproverKind::Webhook, None) => Err(invalid("the webhook approver needs a url")),
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::ConfigError(ConfigError::InvalidPolicy {
+        reason: reason.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"
+        allowed_tools = ["Read", "Bash"]
+        scan_secrets = true
+
+        [bash]
+        allow = ["cargo *"]
+        deny = ["cargo publish *"]
+
+        [budget]
+        max_tool_calls = 50
+
+        [[redact]]
+        name = "api-key"
+        pattern = "sk-[a-z0-9]{8,}"
+
+        [[redact]]
+        name = "topics"
+        topics = ["payroll"]
+        action = "flag"
+
+        [approvals]
+        tools = ["Edit", "Write"]
+        timeout_secs = 30
+    "#;
+
+    #[tokio::test]
+    async fn test_policy_compiles_into_hooks_and_guardrails() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+
+        let report = policy
+            .guardrails()
+            .check("key sk-abcd12345 for payroll")
+            .await;
+        assert_eq!(report.redacted(), Some("key [REDACTED] for payroll"));
+        assert_eq!(report.events().len(), 2);
+
+        let mut options = Options::new().policy(&policy);
+        assert!(!options.permission_callbacks_ref().is_empty());
+        let hooks = options.take_hooks().unwrap().unwrap();
+        let names = hooks
+            .pre_tool_use_hooks()
+            .filter_map(|hook| hook.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [BASH_HOOK, APPROVALS_HOOK]);
+        assert_eq!(hooks.post_tool_use_hooks().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_policies_are_rejected() {
+        for (document, reason) in [
+            ("alowed_tools = []", "unknown field `alowed_tools`"),
+            (
+                "[[redact]]\nname = \"x\"\npattern = \"(\"",
+                "redact rule 'x' has an invalid pattern",
+            ),
+            (
+                "[[redact]]\nname = \"x\"",
+                "redact rule 'x' needs exactly one of pattern and topics",
+            ),
+            (
+                "[approvals]\ntools = [\"Bash\"]\napprover = \"webhook\"",
+                "the webhook approver needs a url",
+            ),
+        ] {
+            let error = Policy::from_toml(document).unwrap_err().to_string();
+            assert!(error.contains(reason), "{error}");
+        }
+        assert!(Policy::from_json(r#"{"read_only": true}"#).is_ok());
+    }
+}
@@ -0,0 +1,14 @@
+//! Reusable policies for vetting tool calls.
+//!
+//! Policies only inspect tool inputs and report violations, so the same
+//! policy can back a PreToolUse hook and a permission callback. With the
+//! `policy-file` feature, a whole policy stack can be loaded from a
+//! document; see [`Policy`].
+
+pub mod bash;
+#[cfg(feature = "policy-file")]
+pub mod file;
+
+pub use bash::{BashPolicy, CommandPattern, Violation, ViolationReason};
+#[cfg(feature = "policy-file")]
+pub use file::Policy;
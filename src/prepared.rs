@@ -0,0 +1,81 @@
+//! User messages serialised once and sent many times.
+//!
+//! Batch workloads often send the same large prompt, such as a document or a
+//! long set of instructions, to many sessions or turns. A [`PreparedMessage`]
+//! holds the message as the line of JSON written to the CLI, so each
+//! [`Client::send_prepared`](crate::Client::send_prepared) writes it as is
+//! instead of serialising it again. Clones share the serialised line.
+//!
+//! ```no_run
+//! use clauders::{Client, PreparedMessage};
+//!
+//! # async fn example(clients: Vec<Client>, review: String) -> Result<(), clauders::Error> {
+//! // The same large prompt, sent to a session per repository.
+//! let prompt = PreparedMessage::text(review)?;
+//! for client in &clients {
+//!     client.send_prepared(&prompt).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::proto::{OutgoingUserMessage, UserContent};
+
+/// A user message serialised ahead of sending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedMessage {
+    line: Arc<str>,
+}
+
+impl PreparedMessage {
+    pub fn new(content: UserContent) -> Result<Self, Error> {
+        let message = OutgoingUserMessage::new(content);
+        #[cfg(debug_assertions)]
+        if let Err(mismatch) =
+            crate::proto::wire::validate_outgoing(&serde_json::to_value(&message)?)
+        {
+            return Err(Error::ProtocolError(format!(
+                "outgoing message does not match wire schema: {mismatch}"
+            )));
+        }
+        Ok(Self {
+            line: serde_json::to_string(&message)?.into(),
+        })
+    }
+
+    pub fn text(prompt: impl Into<String>) -> Result<Self, Error> {
+        Self::new(UserContent::Text(prompt.into()))
+    }
+
+    /// The message as written to the CLI, without the trailing newline.
+    pub fn as_str(&self) -> &str {
+        &self.line
+    }
+
+    /// The size of the serialised message in bytes.
+    pub fn byte_len(&self) -> usize {
+        self.line.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepared_message_matches_serialised_message_and_shares_it() {
+        let prepared = PreparedMessage::text("Summarise the attached logs").unwrap();
+        assert_eq!(
+            prepared.as_str(),
+            serde_json::to_string(&OutgoingUserMessage::text("Summarise the attached logs"))
+                .unwrap()
+        );
+        assert_eq!(prepared.byte_len(), prepared.as_str().len());
+
+        let clone = prepared.clone();
+        assert!(Arc::ptr_eq(&prepared.line, &clone.line));
+    }
+}
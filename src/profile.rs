@@ -0,0 +1,277 @@
+//! Named bundles of permissions.
+//!
+//! A [`PermissionProfile`] collects the tools a role may use, its permission
+//! mode, the directories it may touch and its budget under one name, so a
+//! service can map its own roles onto Claude's capabilities. Apply one to a
+//! whole client with [`Options::profile`](crate::Options::profile), or narrow
+//! a single turn with [`TurnBuilder::profile`](crate::TurnBuilder::profile).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::{Client, Options, PermissionProfile};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let client = Client::new(Options::new().profile(PermissionProfile::developer())).await?;
+//! let mut conversation = client.conversation();
+//!
+//! // Review a pull request without being able to change anything.
+//! let review = conversation
+//!     .turn("Review the changes on this branch")
+//!     .profile(PermissionProfile::analyst())
+//!     .send()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::hooks::PreToolUseOutput;
+use crate::hooks::path_guard::PathGuard;
+use crate::permissions::PermissionMode;
+use crate::tool::{BuiltinTool, ToolInput, ToolSelector};
+
+/// A named set of tools, permission mode, path restrictions and budget.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PermissionProfile {
+    name: String,
+    allowed_tools: Vec<ToolSelector>,
+    disallowed_tools: Vec<ToolSelector>,
+    permission_mode: Option<PermissionMode>,
+    restrict_paths: Vec<PathBuf>,
+    max_budget_usd: Option<f64>,
+}
+
+impl PermissionProfile {
+    /// An empty profile, which allows every tool.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            allowed_tools: Vec::new(),
+            disallowed_tools: Vec::new(),
+            permission_mode: None,
+            restrict_paths: Vec::new(),
+            max_budget_usd: None,
+        }
+    }
+
+    /// Reads and searches files and the web, and cannot change anything.
+    pub fn analyst() -> Self {
+        let mut profile = Self::new("analyst").permission_mode(PermissionMode::Default);
+        for tool in [
+            BuiltinTool::Read,
+            BuiltinTool::Glob,
+            BuiltinTool::Grep,
+            BuiltinTool::WebFetch,
+            BuiltinTool::WebSearch,
+        ] {
+            profile = profile.allow(tool);
+        }
+        for tool in [
+            BuiltinTool::Write,
+            BuiltinTool::Edit,
+            BuiltinTool::MultiEdit,
+            BuiltinTool::NotebookEdit,
+            BuiltinTool::Bash,
+        ] {
+            profile = profile.disallow(tool);
+        }
+        profile
+    }
+
+    /// Reads, edits and runs commands, confined to the working directory.
+    pub fn developer() -> Self {
+        let mut profile = Self::new("developer")
+            .permission_mode(PermissionMode::AcceptEdits)
+            .restrict_path(".");
+        for tool in [
+            BuiltinTool::Read,
+            BuiltinTool::Glob,
+            BuiltinTool::Grep,
+            BuiltinTool::Edit,
+            BuiltinTool::MultiEdit,
+            BuiltinTool::Write,
+            BuiltinTool::Bash,
+            BuiltinTool::TodoWrite,
+        ] {
+            profile = profile.allow(tool);
+        }
+        profile
+    }
+
+    /// Uses every tool anywhere, accepting edits without prompting.
+    pub fn admin() -> Self {
+        Self::new("admin").permission_mode(PermissionMode::AcceptEdits)
+    }
+
+    /// Allows a tool. A profile that allows no tools allows all of them.
+    #[must_use]
+    pub fn allow(mut self, selector: impl Into<ToolSelector>) -> Self {
+        self.allowed_tools.push(selector.into());
+        self
+    }
+
+    #[must_use]
+    pub fn disallow(mut self, selector: impl Into<ToolSelector>) -> Self {
+        self.disallowed_tools.push(selector.into());
+        self
+    }
+
+    #[must_use]
+    pub fn permission_mode(mut self, mode: PermissionMode) -> Self {
+        self.permission_mode = Some(mode);
+        self
+    }
+
+    /// Confines file tools to `root`, relative to the working directory.
+    #[must_use]
+    pub fn restrict_path(mut self, root: impl AsRef<Path>) -> Self {
+        self.restrict_paths.push(root.as_ref().to_path_buf());
+        self
+    }
+
+    #[must_use]
+    pub fn max_budget_usd(mut self, budget: f64) -> Self {
+        self.max_budget_usd = (budget > 0.0).then_some(budget);
+        self
+    }
+
+    // Getters
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn allowed_tools(&self) -> &[ToolSelector] {
+        &self.allowed_tools
+    }
+
+    pub fn disallowed_tools(&self) -> &[ToolSelector] {
+        &self.disallowed_tools
+    }
+
+    pub fn mode(&self) -> Option<PermissionMode> {
+        self.permission_mode
+    }
+
+    pub fn restrict_paths(&self) -> &[PathBuf] {
+        &self.restrict_paths
+    }
+
+    pub fn budget_usd(&self) -> Option<f64> {
+        self.max_budget_usd
+    }
+
+    /// Checks a tool call against the profile, returning the reason it
+    /// should be denied.
+    ///
+    /// Tools are matched by name; rules such as `Bash(git diff:*)` are left
+    /// to the CLI, so a disallowed rule does not deny the whole tool.
+    pub fn check(&self, tool_name: &str, input: &ToolInput, cwd: &Path) -> Result<(), String> {
+        let unconditional = |selector: &&ToolSelector| {
+            !matches!(
+                selector,
+                ToolSelector::Builtin { rule: Some(_), .. }
+                    | ToolSelector::Other { rule: Some(_), .. }
+            )
+        };
+        let denied = self
+            .disallowed_tools
+            .iter()
+            .filter(unconditional)
+            .any(|selector| selector.matches(tool_name));
+        let allowed = self.allowed_tools.is_empty()
+            || self
+                .allowed_tools
+                .iter()
+                .any(|selector| selector.matches(tool_name));
+        if denied || !allowed {
+            return Err(format!(
+                "{tool_name} is not available to the '{}' profile",
+                self.name
+            ));
+        }
+        if self.restrict_paths.is_empty() {
+            return Ok(());
+        }
+        PathGuard::new(&self.restrict_paths, cwd).check(tool_name, input)
+    }
+}
+
+/// The profile narrowing the current turn, shared with the hook enforcing it.
+#[derive(Debug, Clone)]
+pub(crate) struct TurnProfile {
+    active: Arc<RwLock<Option<PermissionProfile>>>,
+    cwd: PathBuf,
+}
+
+impl TurnProfile {
+    pub(crate) fn new(cwd: PathBuf) -> Self {
+        Self {
+            active: Arc::default(),
+            cwd,
+        }
+    }
+
+    pub(crate) fn set(&self, profile: Option<PermissionProfile>) {
+        *self.active.write().unwrap_or_else(PoisonError::into_inner) = profile;
+    }
+
+    pub(crate) fn evaluate(&self, tool_name: &str, input: &ToolInput) -> PreToolUseOutput {
+        let active = self.active.read().unwrap_or_else(PoisonError::into_inner);
+        match active
+            .as_ref()
+            .map(|profile| profile.check(tool_name, input, &self.cwd))
+        {
+            Some(Err(reason)) => PreToolUseOutput::deny(reason),
+            _ => PreToolUseOutput::pass(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_profile_checks_tools_and_paths() {
+        let cwd = std::env::temp_dir();
+        let input = |value: serde_json::Value| ToolInput::from(value);
+
+        let analyst = PermissionProfile::analyst();
+        assert!(analyst.check("Read", &input(json!({})), &cwd).is_ok());
+        assert!(analyst.check("Bash", &input(json!({})), &cwd).is_err());
+        assert!(
+            analyst
+                .check("mcp__github__list", &input(json!({})), &cwd)
+                .is_err()
+        );
+
+        let developer = PermissionProfile::developer();
+        assert!(
+            developer
+                .check("Write", &input(json!({"file_path": "src/lib.rs"})), &cwd)
+                .is_ok()
+        );
+        assert!(
+            developer
+                .check("Write", &input(json!({"file_path": "/etc/passwd"})), &cwd)
+                .is_err()
+        );
+
+        let admin = PermissionProfile::admin().disallow(ToolSelector::mcp_server("github"));
+        assert!(
+            admin
+                .check("mcp__github__delete_repo", &input(json!({})), &cwd)
+                .is_err()
+        );
+        assert!(
+            admin
+                .check("mcp__jira__list", &input(json!({})), &cwd)
+                .is_ok()
+        );
+    }
+}
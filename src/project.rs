@@ -0,0 +1,416 @@
+//! Scaffolding a Claude Code workspace on disk.
+//!
+//! [`init`] writes the files the CLI reads from a project directory:
+//! `.claude/settings.json`, one Markdown file per subagent under
+//! `.claude/agents/`, the `.mcp.json` server list and, optionally, a managed
+//! section of `CLAUDE.md`. Settings the [`ProjectConfig`] does not mention
+//! are left as they were, so `init` can be re-run to update a workspace.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::project::{self, McpServerConfig, ProjectConfig};
+//! use clauders::{Agent, Model};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let config = ProjectConfig::new()
+//!     .with_model(Model::Sonnet)
+//!     .allow("Bash(cargo test:*)")
+//!     .deny("WebFetch")
+//!     .with_agent(
+//!         "reviewer",
+//!         Agent::new("Reviews diffs", "You review code.").with_tools(["Read", "Grep"]),
+//!     )
+//!     .with_mcp_server("docs", McpServerConfig::http("https://docs.example.com/mcp"));
+//!
+//! project::init("/srv/workspace", &config).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::agent::Agent;
+use crate::claude_md::ClaudeMd;
+use crate::error::Error;
+use crate::model::Model;
+use crate::sandbox::Sandbox;
+
+/// The `CLAUDE.md` section [`init`] writes [`ProjectConfig::instructions`] to.
+pub const INSTRUCTIONS_SECTION: &str = "project";
+
+/// How the CLI reaches an external MCP server listed in `.mcp.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum McpServerConfig {
+    Stdio {
+        command: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        args: Vec<String>,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        env: BTreeMap<String, String>,
+    },
+    Http {
+        url: String,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+    },
+    Sse {
+        url: String,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+    },
+}
+
+impl McpServerConfig {
+    /// A server the CLI launches as a subprocess.
+    pub fn stdio(
+        command: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self::Stdio {
+            command: command.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            env: BTreeMap::new(),
+        }
+    }
+
+    /// A server reached over streamable HTTP.
+    pub fn http(url: impl Into<String>) -> Self {
+        Self::Http {
+            url: url.into(),
+            headers: BTreeMap::new(),
+        }
+    }
+
+    /// A server reached over server-sent events.
+    pub fn sse(url: impl Into<String>) -> Self {
+        Self::Sse {
+            url: url.into(),
+            headers: BTreeMap::new(),
+        }
+    }
+
+    /// Adds an environment variable (stdio) or request header (HTTP, SSE).
+    #[must_use]
+    pub fn with_var(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        match &mut self {
+            Self::Stdio { env, .. } => env.insert(key.into(), value.into()),
+            Self::Http { headers, .. } | Self::Sse { headers, .. } => {
+                headers.insert(key.into(), value.into())
+            }
+        };
+        self
+    }
+}
+
+/// The workspace [`init`] writes.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectConfig {
+    model: Option<Model>,
+    allow: Vec<String>,
+    deny: Vec<String>,
+    ask: Vec<String>,
+    env: BTreeMap<String, String>,
+    sandbox: Option<Sandbox>,
+    agents: BTreeMap<String, Agent>,
+    mcp_servers: BTreeMap<String, McpServerConfig>,
+    instructions: Option<String>,
+}
+
+impl ProjectConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn model(&self) -> Option<&Model> {
+        self.model.as_ref()
+    }
+
+    pub fn agents(&self) -> &BTreeMap<String, Agent> {
+        &self.agents
+    }
+
+    pub fn mcp_servers(&self) -> &BTreeMap<String, McpServerConfig> {
+        &self.mcp_servers
+    }
+
+    pub fn instructions(&self) -> Option<&str> {
+        self.instructions.as_deref()
+    }
+
+    /// The default model for sessions in the project.
+    #[must_use]
+    pub fn with_model(mut self, model: impl Into<Model>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Adds a permission rule that is allowed without prompting, such as
+    /// `"Bash(npm run test:*)"`.
+    #[must_use]
+    pub fn allow(mut self, rule: impl Into<String>) -> Self {
+        self.allow.push(rule.into());
+        self
+    }
+
+    /// Adds a permission rule that is always refused.
+    #[must_use]
+    pub fn deny(mut self, rule: impl Into<String>) -> Self {
+        self.deny.push(rule.into());
+        self
+    }
+
+    /// Adds a permission rule that always asks for confirmation.
+    #[must_use]
+    pub fn ask(mut self, rule: impl Into<String>) -> Self {
+        self.ask.push(rule.into());
+        self
+    }
+
+    /// Sets an environment variable for every session in the project.
+    #[must_use]
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Adds a subagent, written to `.claude/agents/<name>.md`.
+    #[must_use]
+    pub fn with_agent(mut self, name: impl Into<String>, agent: Agent) -> Self {
+        self.agents.insert(name.into(), agent);
+        self
+    }
+
+    /// Adds an external MCP server to `.mcp.json`.
+    #[must_use]
+    pub fn with_mcp_server(mut self, name: impl Into<String>, server: McpServerConfig) -> Self {
+        self.mcp_servers.insert(name.into(), server);
+        self
+    }
+
+    /// Project instructions, written to a managed section of `CLAUDE.md`.
+    #[must_use]
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// The keys this configuration sets in `.claude/settings.json`.
+    fn settings(&self) -> Map<String, Value> {
+        let mut settings = Map::new();
+        if let Some(model) = &self.model {
+            settings.insert("model".to_owned(), model.as_str().into());
+        }
+        let mut permissions = Map::new();
+        for (key, rules) in [
+            ("allow", &self.allow),
+            ("deny", &self.deny),
+            ("ask", &self.ask),
+        ] {
+            if !rules.is_empty() {
+                permissions.insert(key.to_owned(), rules.clone().into());
+            }
+        }
+        if !permissions.is_empty() {
+            settings.insert("permissions".to_owned(), permissions.into());
+        }
+        if !self.env.is_empty() {
+            settings.insert("env".to_owned(), serde_json::json!(self.env));
+        }
+        if let Some(sandbox) = &self.sandbox {
+            settings.insert("sandbox".to_owned(), serde_json::json!(sandbox));
+        }
+        settings
+    }
+}
+
+/// Writes `config` into the project directory `dir`, creating it if needed.
+///
+/// Top-level keys of an existing `.claude/settings.json` and servers in an
+/// existing `.mcp.json` are replaced only when `config` sets them. Agent
+/// files with the same name are overwritten.
+pub async fn init(dir: impl AsRef<Path>, config: &ProjectConfig) -> Result<(), Error> {
+    let dir = dir.as_ref();
+    let claude_dir = dir.join(".claude");
+    tokio::fs::create_dir_all(&claude_dir).await?;
+
+    let settings = config.settings();
+    if !settings.is_empty() {
+        merge_json(&claude_dir.join("settings.json"), None, settings).await?;
+    }
+
+    if !config.agents.is_empty() {
+        let agents_dir = claude_dir.join("agents");
+        tokio::fs::create_dir_all(&agents_dir).await?;
+        for (name, agent) in &config.agents {
+            validate_agent_name(name)?;
+            tokio::fs::write(
+                agents_dir.join(format!("{name}.md")),
+                agent_file(name, agent),
+            )
+            .await?;
+        }
+    }
+
+    if !config.mcp_servers.is_empty() {
+        let servers = config
+            .mcp_servers
+            .iter()
+            .map(|(name, server)| Ok((name.clone(), serde_json::to_value(server)?)))
+            .collect::<Result<Map<_, _>, Error>>()?;
+        merge_json(&dir.join(".mcp.json"), Some("mcpServers"), servers).await?;
+    }
+
+    if let Some(instructions) = &config.instructions {
+        let mut claude_md = ClaudeMd::load(dir).await?;
+        claude_md.set_section(INSTRUCTIONS_SECTION, instructions)?;
+        claude_md.save().await?;
+    }
+
+    Ok(())
+}
+
+/// Inserts `entries` into the JSON object at `path`, or into its `key`
+/// member, keeping whatever else the file holds.
+async fn merge_json(
+    path: &Path,
+    key: Option<&str>,
+    entries: Map<String, Value>,
+) -> Result<(), Error> {
+    let mut document = match tokio::fs::read_to_string(path).await {
+        Ok(content) => serde_json::from_str::<Value>(&content)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Value::Object(Map::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let not_an_object = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: expected a JSON object", path.display()),
+        )
+    };
+
+    let mut target = document.as_object_mut().ok_or_else(not_an_object)?;
+    if let Some(key) = key {
+        target = target
+            .entry(key)
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(not_an_object)?;
+    }
+    target.extend(entries);
+
+    let mut content = serde_json::to_string_pretty(&document)?;
+    content.push('\n');
+    tokio::fs::write(path, content).await?;
+    Ok(())
+}
+
+fn validate_agent_name(name: &str) -> Result<(), Error> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("agent name '{name}' must use lowercase letters, digits, '-' or '_'"),
+        )))
+    }
+}
+
+/// Renders a subagent as YAML front matter followed by its system prompt.
+fn agent_file(name: &str, agent: &Agent) -> String {
+    let mut file = format!(
+        "---\nname: {name}\ndescription: {}\n",
+        yaml_string(agent.description())
+    );
+    if !agent.tools().is_empty() {
+        file.push_str(&format!("tools: {}\n", agent.tools().join(", ")));
+    }
+    if let Some(model) = agent.model() {
+        file.push_str(&format!("model: {model}\n"));
+    }
+    file.push_str("---\n\n");
+    file.push_str(agent.prompt().trim_end());
+    file.push('\n');
+    file
+}
+
+/// Quotes a single-line YAML scalar; JSON strings are valid YAML.
+fn yaml_string(s: &str) -> String {
+    serde_json::to_string(s).expect("strings always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_file_has_front_matter() {
+        let agent = Agent::new("Reviews: diffs", "You review code.\n")
+            .with_model(Model::Haiku)
+            .with_tools(["Read", "Grep"]);
+        assert_eq!(
+            agent_file("reviewer", &agent),
+            "---\nname: reviewer\ndescription: \"Reviews: diffs\"\ntools: Read, Grep\n\
+             model: haiku\n---\n\nYou review code.\n"
+        );
+        assert!(validate_agent_name("../escape").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_init_merges_existing_settings() {
+        let dir = std::env::temp_dir().join(format!("clauders-project-{}", crate::util::uuid_v7()));
+        tokio::fs::create_dir_all(dir.join(".claude"))
+            .await
+            .unwrap();
+        tokio::fs::write(
+            dir.join(".claude/settings.json"),
+            r#"{"model":"opus","includeCoAuthoredBy":false}"#,
+        )
+        .await
+        .unwrap();
+
+        let config = ProjectConfig::new()
+            .with_model(Model::Sonnet)
+            .allow("Read")
+            .with_mcp_server(
+                "local",
+                McpServerConfig::stdio("server", ["--stdio"]).with_var("TOKEN", "x"),
+            );
+        init(&dir, &config).await.unwrap();
+
+        let read = |name: &str| {
+            let path = dir.join(name);
+            async move {
+                serde_json::from_str::<Value>(&tokio::fs::read_to_string(path).await.unwrap())
+                    .unwrap()
+            }
+        };
+        let settings = read(".claude/settings.json").await;
+        assert_eq!(settings["model"], "sonnet");
+        assert_eq!(settings["includeCoAuthoredBy"], false);
+        assert_eq!(settings["permissions"]["allow"][0], "Read");
+        let mcp = read(".mcp.json").await;
+        assert_eq!(
+            mcp["mcpServers"]["local"],
+            serde_json::json!({"type": "stdio", "command": "server", "args": ["--stdio"], "env": {"TOKEN": "x"}})
+        );
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -1,15 +1,25 @@
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
+use super::intern::{self, intern};
+
+/// A block of a message's content. Blocks are held behind an [`Arc`], so
+/// cloning a message or turning it into [`Response`](crate::Response)s
+/// shares their text and payloads instead of copying them.
+///
+/// The API adds block types over time, so the enum is non-exhaustive.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum ContentBlock {
-    Text(Text),
-    ToolUse(ToolUse),
-    ToolResult(ToolResult),
-    Thinking(Thinking),
-    Image(Image),
-    Document(Document),
+    Text(Arc<Text>),
+    ToolUse(Arc<ToolUse>),
+    ToolResult(Arc<ToolResult>),
+    Thinking(Arc<Thinking>),
+    Image(Arc<Image>),
+    Document(Arc<Document>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,7 +32,8 @@ pub struct Text {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolUse {
     id: String,
-    name: String,
+    #[serde(deserialize_with = "intern::deserialize")]
+    name: Arc<str>,
     input: Value,
     #[serde(flatten)]
     extra: Map<String, Value>,
@@ -31,8 +42,10 @@ pub struct ToolUse {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
     tool_use_id: String,
+    /// Shared, as tool results can be megabytes long and the block is cloned
+    /// into responses and turn history.
     #[serde(skip_serializing_if = "Option::is_none")]
-    content: Option<Value>,
+    content: Option<Arc<Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     is_error: Option<bool>,
     #[serde(flatten)]
@@ -113,7 +126,7 @@ impl ToolUse {
     pub fn new(id: impl Into<String>, name: impl Into<String>, input: Value) -> Self {
         Self {
             id: id.into(),
-            name: name.into(),
+            name: intern(&name.into()),
             input,
             extra: Map::new(),
         }
@@ -128,6 +141,11 @@ impl ToolUse {
         &self.name
     }
 
+    /// The tool name, shared with every other message naming the tool.
+    pub fn shared_name(&self) -> Arc<str> {
+        Arc::clone(&self.name)
+    }
+
     pub fn input(&self) -> &Value {
         &self.input
     }
@@ -142,7 +160,7 @@ impl ToolUse {
     }
 
     pub fn set_name(&mut self, name: impl Into<String>) {
-        self.name = name.into();
+        self.name = intern(&name.into());
     }
 
     pub fn set_input(&mut self, input: Value) {
@@ -191,7 +209,12 @@ impl ToolResult {
     }
 
     pub fn content(&self) -> Option<&Value> {
-        self.content.as_ref()
+        self.content.as_deref()
+    }
+
+    /// The content, shared rather than copied.
+    pub fn shared_content(&self) -> Option<Arc<Value>> {
+        self.content.clone()
     }
 
     pub fn is_error(&self) -> Option<bool> {
@@ -208,7 +231,7 @@ impl ToolResult {
     }
 
     pub fn set_content(&mut self, content: Option<Value>) {
-        self.content = content;
+        self.content = content.map(Arc::new);
     }
 
     pub fn set_is_error(&mut self, is_error: Option<bool>) {
@@ -441,26 +464,64 @@ impl Document {
 
 impl ContentBlock {
     pub fn text(text: impl Into<String>) -> Self {
-        Self::Text(Text::new(text))
+        Self::Text(Arc::new(Text::new(text)))
     }
 
     pub fn tool_use(id: impl Into<String>, name: impl Into<String>, input: Value) -> Self {
-        Self::ToolUse(ToolUse::new(id, name, input))
+        Self::ToolUse(Arc::new(ToolUse::new(id, name, input)))
     }
 
     pub fn tool_result(tool_use_id: impl Into<String>) -> Self {
-        Self::ToolResult(ToolResult::new(tool_use_id))
+        Self::ToolResult(Arc::new(ToolResult::new(tool_use_id)))
     }
 
     pub fn thinking(thinking: impl Into<String>, signature: impl Into<String>) -> Self {
-        Self::Thinking(Thinking::new(thinking, signature))
+        Self::Thinking(Arc::new(Thinking::new(thinking, signature)))
     }
 
     pub fn image(source: Value) -> Self {
-        Self::Image(Image::new(source))
+        Self::Image(Arc::new(Image::new(source)))
     }
 
     pub fn document(source: Value) -> Self {
-        Self::Document(Document::new(source))
+        Self::Document(Arc::new(Document::new(source)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_tool_result_content_is_shared_between_clones() {
+        let output = "x".repeat(1 << 20);
+        let block = serde_json::from_value::<ContentBlock>(json!({
+            "type": "tool_result",
+            "tool_use_id": "toolu_1",
+            "content": output,
+        }))
+        .unwrap();
+        let ContentBlock::ToolResult(result) = &block else {
+            panic!("expected a tool result, got {block:?}");
+        };
+        let ContentBlock::ToolResult(copy) = block.clone() else {
+            unreachable!();
+        };
+        assert!(Arc::ptr_eq(
+            &result.shared_content().unwrap(),
+            &copy.shared_content().unwrap()
+        ));
+        assert_eq!(
+            copy.content().and_then(Value::as_str),
+            Some(output.as_str())
+        );
+        assert_eq!(
+            serde_json::to_value(&block).unwrap()["content"]
+                .as_str()
+                .map(str::len),
+            Some(1 << 20)
+        );
     }
 }
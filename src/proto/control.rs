@@ -1,8 +1,13 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
+use super::intern::{self, intern};
+use super::message::PluginInfo;
+use crate::permissions::PermissionRule;
+
 /// Control protocol request types.
 ///
 /// These match the Python SDK's SDKControl*Request types exactly.
@@ -23,7 +28,8 @@ pub enum Request {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermissionRequest {
-    tool_name: String,
+    #[serde(deserialize_with = "intern::deserialize")]
+    tool_name: Arc<str>,
     input: Value,
     #[serde(skip_serializing_if = "Option::is_none")]
     permission_suggestions: Option<Vec<PermissionUpdate>>,
@@ -36,7 +42,7 @@ pub struct PermissionRequest {
 impl PermissionRequest {
     pub fn new(tool_name: impl Into<String>, input: Value) -> Self {
         Self {
-            tool_name: tool_name.into(),
+            tool_name: intern(&tool_name.into()),
             input,
             permission_suggestions: None,
             blocked_path: None,
@@ -67,7 +73,7 @@ impl PermissionRequest {
 
     // Setters
     pub fn set_tool_name(&mut self, tool_name: impl Into<String>) {
-        self.tool_name = tool_name.into();
+        self.tool_name = intern(&tool_name.into());
     }
 
     pub fn set_input(&mut self, input: Value) {
@@ -113,65 +119,111 @@ impl PermissionRequest {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PermissionUpdate {
-    tool_name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    rule: Option<String>,
-    #[serde(flatten)]
-    extra: Map<String, Value>,
+/// A change to the CLI's permission settings, suggested with a
+/// `can_use_tool` request and returned with an allow decision to apply it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PermissionUpdate {
+    AddRules {
+        rules: Vec<PermissionRule>,
+        behavior: PermissionBehavior,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionDestination>,
+    },
+    ReplaceRules {
+        rules: Vec<PermissionRule>,
+        behavior: PermissionBehavior,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionDestination>,
+    },
+    RemoveRules {
+        rules: Vec<PermissionRule>,
+        behavior: PermissionBehavior,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionDestination>,
+    },
+    SetMode {
+        mode: PermissionMode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionDestination>,
+    },
+    AddDirectories {
+        directories: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionDestination>,
+    },
+    RemoveDirectories {
+        directories: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PermissionDestination>,
+    },
+    /// An update of a kind this version does not know.
+    #[serde(other)]
+    Other,
 }
 
 impl PermissionUpdate {
-    pub fn new(tool_name: impl Into<String>) -> Self {
-        Self {
-            tool_name: tool_name.into(),
-            rule: None,
-            extra: Map::new(),
+    /// Always allows calls matching `rules` for the rest of the session.
+    pub fn allow_rules(rules: Vec<PermissionRule>) -> Self {
+        Self::AddRules {
+            rules,
+            behavior: PermissionBehavior::Allow,
+            destination: Some(PermissionDestination::Session),
         }
     }
 
-    // Getters
-    pub fn tool_name(&self) -> &str {
-        &self.tool_name
-    }
-
-    pub fn rule(&self) -> Option<&str> {
-        self.rule.as_deref()
-    }
-
-    pub fn extra(&self) -> &Map<String, Value> {
-        &self.extra
-    }
-
-    // Setters
-    pub fn set_tool_name(&mut self, tool_name: impl Into<String>) {
-        self.tool_name = tool_name.into();
-    }
-
-    pub fn set_rule(&mut self, rule: Option<String>) {
-        self.rule = rule;
+    /// Switches to `mode` for the rest of the session.
+    pub fn set_mode(mode: PermissionMode) -> Self {
+        Self::SetMode {
+            mode,
+            destination: Some(PermissionDestination::Session),
+        }
     }
 
-    pub fn set_extra(&mut self, extra: Map<String, Value>) {
-        self.extra = extra;
+    /// The rules the update adds, replaces or removes.
+    pub fn rules(&self) -> &[PermissionRule] {
+        match self {
+            Self::AddRules { rules, .. }
+            | Self::ReplaceRules { rules, .. }
+            | Self::RemoveRules { rules, .. } => rules,
+            _ => &[],
+        }
     }
 
-    // Builders
-    pub fn with_tool_name(mut self, tool_name: impl Into<String>) -> Self {
-        self.set_tool_name(tool_name);
-        self
+    pub fn destination(&self) -> Option<PermissionDestination> {
+        match self {
+            Self::AddRules { destination, .. }
+            | Self::ReplaceRules { destination, .. }
+            | Self::RemoveRules { destination, .. }
+            | Self::SetMode { destination, .. }
+            | Self::AddDirectories { destination, .. }
+            | Self::RemoveDirectories { destination, .. } => *destination,
+            Self::Other => None,
+        }
     }
+}
 
-    pub fn with_rule(mut self, rule: impl Into<String>) -> Self {
-        self.set_rule(Some(rule.into()));
-        self
-    }
+/// What a permission rule does to matching calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionBehavior {
+    Allow,
+    Deny,
+    Ask,
+}
 
-    pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
-        self.set_extra(extra);
-        self
-    }
+/// Where a permission update is saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionDestination {
+    /// `~/.claude/settings.json`.
+    UserSettings,
+    /// `.claude/settings.json` in the project.
+    ProjectSettings,
+    /// `.claude/settings.local.json` in the project.
+    LocalSettings,
+    /// Only the current session.
+    Session,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -239,6 +291,107 @@ impl Default for InitializeRequest {
     }
 }
 
+/// Payload of the CLI's success response to an [`InitializeRequest`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InitializeResponse {
+    #[serde(default)]
+    commands: Vec<SlashCommand>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_style: Option<String>,
+    #[serde(default)]
+    available_output_styles: Vec<String>,
+    #[serde(default)]
+    models: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hooks: Option<HashMap<String, Value>>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl InitializeResponse {
+    // Getters
+    pub fn commands(&self) -> &[SlashCommand] {
+        &self.commands
+    }
+
+    pub fn output_style(&self) -> Option<&str> {
+        self.output_style.as_deref()
+    }
+
+    pub fn available_output_styles(&self) -> &[String] {
+        &self.available_output_styles
+    }
+
+    pub fn models(&self) -> &[Value] {
+        &self.models
+    }
+
+    pub fn account(&self) -> Option<&Value> {
+        self.account.as_ref()
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    /// Whether a slash command with the given name (without the leading `/`) is available.
+    pub fn has_command(&self, name: &str) -> bool {
+        self.commands.iter().any(|c| c.name() == name)
+    }
+
+    /// Whether the CLI registered the hook callback `callback_id`, or `None`
+    /// when the response does not list the registered hooks.
+    ///
+    /// The list has the shape of [`InitializeRequest::with_hooks`]: the
+    /// matchers for each event, with the ids of their callbacks.
+    pub fn registers_hook_callback(&self, callback_id: &str) -> Option<bool> {
+        let hooks = self.hooks.as_ref()?;
+        Some(
+            hooks
+                .values()
+                .filter_map(Value::as_array)
+                .flatten()
+                .filter_map(|matcher| matcher["hookCallbackIds"].as_array())
+                .flatten()
+                .any(|id| id.as_str() == Some(callback_id)),
+        )
+    }
+}
+
+/// A slash command advertised by the CLI during initialization.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlashCommand {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    argument_hint: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl SlashCommand {
+    // Getters
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn argument_hint(&self) -> Option<&str> {
+        self.argument_hint.as_deref()
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SetPermissionModeRequest {
     mode: PermissionMode,
@@ -496,6 +649,16 @@ pub enum Response {
     Error(ErrorResponse),
 }
 
+impl Response {
+    /// The id of the request this response answers.
+    pub fn request_id(&self) -> &str {
+        match self {
+            Self::Success(s) => s.request_id(),
+            Self::Error(e) => e.request_id(),
+        }
+    }
+}
+
 /// Success response - all fields use snake_case.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SuccessResponse {
@@ -736,6 +899,35 @@ pub struct RequestEnvelope {
     request: Request,
 }
 
+/// Cancellation of an outstanding control request (SDK → CLI).
+///
+/// ```json
+/// { "type": "control_cancel_request", "request_id": "..." }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelRequestEnvelope {
+    #[serde(rename = "type")]
+    msg_type: String,
+    request_id: String,
+}
+
+impl CancelRequestEnvelope {
+    pub fn new(request_id: impl Into<String>) -> Self {
+        Self {
+            msg_type: "control_cancel_request".to_owned(),
+            request_id: request_id.into(),
+        }
+    }
+
+    pub fn msg_type(&self) -> &str {
+        &self.msg_type
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+}
+
 /// Control response envelope (SDK ↔ CLI).
 ///
 /// Structure matches Python SDK exactly:
@@ -754,7 +946,7 @@ pub struct ResponseEnvelope {
 
 impl RequestEnvelope {
     pub fn new(request: Request) -> Self {
-        Self::new_with(uuid::Uuid::now_v7(), request)
+        Self::new_with(crate::util::uuid_v7(), request)
     }
 
     pub fn new_with(request_id: impl std::fmt::Display, request: Request) -> Self {
@@ -876,6 +1068,8 @@ pub struct ServerInfo {
     commands: Vec<String>,
     #[serde(default, rename = "outputStyles")]
     output_styles: Vec<String>,
+    #[serde(default)]
+    plugins: Vec<PluginInfo>,
     #[serde(flatten)]
     extra: Map<String, Value>,
 }
@@ -887,6 +1081,7 @@ impl ServerInfo {
             capabilities: Vec::new(),
             commands: Vec::new(),
             output_styles: Vec::new(),
+            plugins: Vec::new(),
             extra: Map::new(),
         }
     }
@@ -908,6 +1103,20 @@ impl ServerInfo {
         &self.output_styles
     }
 
+    pub fn plugins(&self) -> &[PluginInfo] {
+        &self.plugins
+    }
+
+    /// The loaded plugin `name`, given bare or as `plugin@marketplace`.
+    pub fn plugin(&self, name: &str) -> Option<&PluginInfo> {
+        self.plugins.iter().find(|plugin| plugin.matches(name))
+    }
+
+    /// Whether the server advertises the given capability string.
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
     pub fn extra(&self) -> &Map<String, Value> {
         &self.extra
     }
@@ -929,6 +1138,10 @@ impl ServerInfo {
         self.output_styles = output_styles;
     }
 
+    pub fn set_plugins(&mut self, plugins: Vec<PluginInfo>) {
+        self.plugins = plugins;
+    }
+
     pub fn set_extra(&mut self, extra: Map<String, Value>) {
         self.extra = extra;
     }
@@ -954,8 +1167,74 @@ impl ServerInfo {
         self
     }
 
+    pub fn with_plugins(mut self, plugins: Vec<PluginInfo>) -> Self {
+        self.set_plugins(plugins);
+        self
+    }
+
     pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
         self.set_extra(extra);
         self
     }
 }
+
+/// Typed view over the capability strings advertised in [`ServerInfo`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    partial_messages: bool,
+    set_model: bool,
+    set_permission_mode: bool,
+    interrupt: bool,
+    hooks: bool,
+    sdk_mcp_servers: bool,
+    other: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn supports_partial_messages(&self) -> bool {
+        self.partial_messages
+    }
+
+    pub fn supports_set_model(&self) -> bool {
+        self.set_model
+    }
+
+    pub fn supports_set_permission_mode(&self) -> bool {
+        self.set_permission_mode
+    }
+
+    pub fn supports_interrupt(&self) -> bool {
+        self.interrupt
+    }
+
+    pub fn supports_hooks(&self) -> bool {
+        self.hooks
+    }
+
+    pub fn supports_sdk_mcp_servers(&self) -> bool {
+        self.sdk_mcp_servers
+    }
+
+    /// Capability strings that have no typed flag.
+    pub fn other(&self) -> &[String] {
+        &self.other
+    }
+}
+
+impl From<&ServerInfo> for Capabilities {
+    fn from(info: &ServerInfo) -> Self {
+        let mut caps = Self::default();
+        for capability in info.capabilities() {
+            match capability.as_str() {
+                "partial_messages" | "include_partial_messages" => caps.partial_messages = true,
+                "set_model" => caps.set_model = true,
+                "set_permission_mode" => caps.set_permission_mode = true,
+                "interrupt" => caps.interrupt = true,
+                "hooks" => caps.hooks = true,
+                "sdk_mcp_servers" | "mcp_message" => caps.sdk_mcp_servers = true,
+                other => caps.other.push(other.to_owned()),
+            }
+        }
+        caps
+    }
+}
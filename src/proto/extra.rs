@@ -0,0 +1,246 @@
+//! Fields of the CLI's messages that the protocol types do not model.
+//!
+//! Each wire type keeps the fields it does not know in an `extra` map, so
+//! nothing the CLI sends is lost and newer fields can be read before they are
+//! modelled, with [`Extra::extra_typed`]:
+//!
+//! ```
+//! use clauders::proto::extra::Extra;
+//! use clauders::proto::ResultMessage;
+//!
+//! # fn example(result: &ResultMessage) -> Result<(), clauders::Error> {
+//! if let Some(ttft_ms) = result.extra_typed::<u64>("ttft_ms")? {
+//!     println!("first token after {ttft_ms}ms");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With [`Options::warn_unknown_fields`](crate::Options::warn_unknown_fields)
+//! the client logs a warning the first time a field lands in a type's
+//! `extra`, which points at the protocol additions to model next.
+
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use super::ContentBlock;
+use super::content_block::{Document, Image, Text, Thinking, ToolResult, ToolUse};
+use super::control::{
+    ErrorResponse, HookCallbackRequest, InitializeRequest, InitializeResponse, McpMessageRequest,
+    PermissionRequest, Request, Response, ServerInfo, SetModelRequest, SetPermissionModeRequest,
+    SlashCommand, SuccessResponse,
+};
+use super::incoming::{
+    ControlCancelRequest, ControlRequestEnvelope, ControlResponseEnvelope, Incoming,
+    RateLimitEvent, StreamEvent,
+};
+use super::message::{
+    AssistantEnvelope, AssistantMessageInner, ErrorMessage, HookLifecycleMessage, InitMessage,
+    McpServerStatus, ModelUsage, PermissionDenial, PluginInfo, ResultMessage, SystemMessage, Usage,
+    UserContent, UserMessageInner,
+};
+use crate::error::Error;
+
+/// The most distinct unknown fields warned about, so a CLI sending fields
+/// with generated names cannot grow the set without bound.
+const MAX_WARNED: usize = 1024;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A wire type that keeps the fields it does not model.
+///
+/// Sealed: only this crate's protocol types implement it, so methods can be
+/// added to it without breaking anyone.
+pub trait Extra: sealed::Sealed {
+    /// The fields the type does not model, as sent.
+    fn extra_fields(&self) -> &Map<String, Value>;
+
+    /// Decodes the unmodelled field `key` as `T`, or `None` if it was not
+    /// sent.
+    fn extra_typed<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        self.extra_fields()
+            .get(key)
+            .map(|value| T::deserialize(value).map_err(Error::from))
+            .transpose()
+    }
+}
+
+macro_rules! impl_extra {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl sealed::Sealed for $ty {}
+
+            impl Extra for $ty {
+                fn extra_fields(&self) -> &Map<String, Value> {
+                    self.extra()
+                }
+            }
+        )*
+    };
+}
+
+impl_extra!(
+    Text,
+    ToolUse,
+    ToolResult,
+    Thinking,
+    Image,
+    Document,
+    ControlRequestEnvelope,
+    ControlCancelRequest,
+    ControlResponseEnvelope,
+    RateLimitEvent,
+    StreamEvent,
+    UserMessageInner,
+    AssistantEnvelope,
+    AssistantMessageInner,
+    HookLifecycleMessage,
+    InitMessage,
+    McpServerStatus,
+    PluginInfo,
+    ErrorMessage,
+    ResultMessage,
+    Usage,
+    ModelUsage,
+    PermissionDenial,
+    PermissionRequest,
+    InitializeRequest,
+    InitializeResponse,
+    SlashCommand,
+    SetPermissionModeRequest,
+    HookCallbackRequest,
+    McpMessageRequest,
+    SetModelRequest,
+    SuccessResponse,
+    ErrorResponse,
+    ServerInfo,
+);
+
+static WARNED: LazyLock<Mutex<HashSet<(&'static str, String)>>> = LazyLock::new(Mutex::default);
+
+/// Logs the unmodelled fields of `incoming` not logged before.
+pub(crate) fn warn_unknown_fields(incoming: &Incoming) {
+    unknown_fields(incoming, &mut |ty, field| {
+        let mut warned = WARNED.lock().unwrap_or_else(PoisonError::into_inner);
+        if warned.len() < MAX_WARNED && warned.insert((ty, field.to_owned())) {
+            tracing::warn!(ty, field, "unmodelled field in a message from the CLI");
+        }
+    });
+}
+
+/// Calls `report` with the type and name of every unmodelled field of
+/// `incoming` and the values it holds.
+fn unknown_fields(incoming: &Incoming, report: &mut dyn FnMut(&'static str, &str)) {
+    match incoming {
+        Incoming::User(envelope) => {
+            fields(envelope.message(), report);
+            if let UserContent::Blocks(blocks) = envelope.message().content() {
+                content_fields(blocks, report);
+            }
+        }
+        Incoming::Assistant(envelope) => {
+            fields(envelope, report);
+            fields(envelope.message(), report);
+            content_fields(envelope.message().content(), report);
+        }
+        Incoming::System(SystemMessage::Init(init)) => {
+            fields(init, report);
+            init.mcp_servers().iter().for_each(|s| fields(s, report));
+            init.plugins().iter().for_each(|p| fields(p, report));
+        }
+        Incoming::System(SystemMessage::Error(error)) => fields(error, report),
+        Incoming::System(SystemMessage::HookStarted(hook) | SystemMessage::HookResponse(hook)) => {
+            fields(hook, report)
+        }
+        Incoming::Result(result) => {
+            fields(result, report);
+            result.usage().into_iter().for_each(|u| fields(u, report));
+            result
+                .model_usage()
+                .into_iter()
+                .flat_map(|usage| usage.values())
+                .for_each(|u| fields(u, report));
+            result
+                .permission_denials()
+                .into_iter()
+                .flatten()
+                .for_each(|d| fields(d, report));
+        }
+        Incoming::ControlRequest(envelope) => {
+            fields(envelope, report);
+            match envelope.request() {
+                Request::CanUseTool(request) => fields(request, report),
+                Request::Initialize(request) => fields(request, report),
+                Request::SetPermissionMode(request) => fields(request, report),
+                Request::HookCallback(request) => fields(request, report),
+                Request::McpMessage(request) => fields(request, report),
+                Request::SetModel(request) => fields(request, report),
+                Request::Interrupt | Request::GetServerInfo => {}
+            }
+        }
+        Incoming::ControlResponse(envelope) => {
+            fields(envelope, report);
+            match envelope.response() {
+                Response::Success(response) => fields(response, report),
+                Response::Error(response) => fields(response, report),
+            }
+        }
+        Incoming::ControlCancelRequest(cancel) => fields(cancel, report),
+        Incoming::RateLimitEvent(event) => fields(event, report),
+        Incoming::StreamEvent(event) => fields(event, report),
+    }
+}
+
+fn content_fields(blocks: &[ContentBlock], report: &mut dyn FnMut(&'static str, &str)) {
+    for block in blocks {
+        match block {
+            ContentBlock::Text(block) => fields(&**block, report),
+            ContentBlock::ToolUse(block) => fields(&**block, report),
+            ContentBlock::ToolResult(block) => fields(&**block, report),
+            ContentBlock::Thinking(block) => fields(&**block, report),
+            ContentBlock::Image(block) => fields(&**block, report),
+            ContentBlock::Document(block) => fields(&**block, report),
+        }
+    }
+}
+
+fn fields<T: Extra>(value: &T, report: &mut dyn FnMut(&'static str, &str)) {
+    let ty = std::any::type_name::<T>();
+    let ty = ty.rsplit("::").next().unwrap_or(ty);
+    for field in value.extra_fields().keys() {
+        report(ty, field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_typed_and_unknown_fields() {
+        let incoming = Incoming::parse(
+            r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":1,"duration_api_ms":1,"num_turns":1,"session_id":"s1","ttft_ms":420,"usage":{"input_tokens":3,"output_tokens":1,"service_tier":"standard"}}"#,
+        )
+        .unwrap();
+        let Incoming::Result(result) = &incoming else {
+            panic!("expected a result, got {incoming:?}");
+        };
+        assert_eq!(result.extra_typed::<u64>("ttft_ms").unwrap(), Some(420));
+        assert_eq!(result.extra_typed::<u64>("missing").unwrap(), None);
+        assert!(matches!(
+            result.extra_typed::<String>("ttft_ms"),
+            Err(Error::Json(_))
+        ));
+
+        let mut unknown = Vec::new();
+        unknown_fields(&incoming, &mut |ty, field| {
+            unknown.push(format!("{ty}.{field}"))
+        });
+        assert_eq!(unknown, ["ResultMessage.ttft_ms", "Usage.service_tier"]);
+    }
+}
@@ -3,12 +3,15 @@ use serde_json::{Map, Value};
 
 use super::control::{Request, Response};
 use super::message::Message;
+use crate::error::Error;
 
 /// Incoming messages from CLI.
 ///
-/// The `type` field determines which variant to parse.
+/// The `type` field determines which variant to parse. New message types
+/// become new variants, so the enum is non-exhaustive.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum Incoming {
     User(super::message::UserEnvelope),
     Assistant(super::message::AssistantEnvelope),
@@ -16,7 +19,9 @@ pub enum Incoming {
     Result(super::message::ResultMessage),
     ControlRequest(ControlRequestEnvelope),
     ControlResponse(ControlResponseEnvelope),
+    ControlCancelRequest(ControlCancelRequest),
     RateLimitEvent(RateLimitEvent),
+    StreamEvent(StreamEvent),
 }
 
 /// Incoming control request envelope (CLI → SDK).
@@ -89,6 +94,36 @@ impl ControlRequestEnvelope {
     }
 }
 
+/// Incoming cancellation of a control request the CLI sent earlier
+/// (CLI → SDK), such as a `can_use_tool` prompt it no longer needs answered.
+///
+/// ```json
+/// { "type": "control_cancel_request", "request_id": "..." }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlCancelRequest {
+    request_id: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl ControlCancelRequest {
+    pub fn new(request_id: impl Into<String>) -> Self {
+        Self {
+            request_id: request_id.into(),
+            extra: Map::new(),
+        }
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+}
+
 /// Incoming control response envelope (CLI → SDK).
 ///
 /// Structure matches Python SDK's SDKControlResponse:
@@ -198,7 +233,68 @@ impl RateLimitEvent {
     }
 }
 
+/// A raw API streaming event, sent when partial messages are enabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamEvent {
+    event: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_tool_use_id: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl StreamEvent {
+    /// The event as sent by the API, e.g. a `content_block_delta`.
+    pub fn event(&self) -> &Value {
+        &self.event
+    }
+
+    /// The event's `type` field.
+    pub fn event_type(&self) -> Option<&str> {
+        self.event.get("type")?.as_str()
+    }
+
+    /// The content block index the event applies to, for block events.
+    pub fn index(&self) -> Option<u64> {
+        self.event.get("index")?.as_u64()
+    }
+
+    /// Set when the event belongs to a subagent's message.
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        self.parent_tool_use_id.as_deref()
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+}
+
 impl Incoming {
+    /// Parses a line of the CLI's output.
+    ///
+    /// With the `simd-json` feature, the line is parsed with SIMD
+    /// instructions where the CPU has them, which is faster for the large
+    /// tool results and transcripts of busy sessions.
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        #[cfg(feature = "simd-json")]
+        let parsed = {
+            thread_local! {
+                static BUFFERS: std::cell::RefCell<simd_json::Buffers> =
+                    std::cell::RefCell::new(simd_json::Buffers::default());
+            }
+            // simd-json parses in place.
+            let mut bytes = line.as_bytes().to_vec();
+            BUFFERS
+                .with_borrow_mut(|buffers| {
+                    simd_json::serde::from_slice_with_buffers::<Self>(&mut bytes, buffers)
+                })
+                .map_err(|e| e.to_string())
+        };
+        #[cfg(not(feature = "simd-json"))]
+        let parsed = serde_json::from_str::<Self>(line).map_err(|e| e.to_string());
+        parsed.map_err(|e| Error::ProtocolError(format!("failed to parse: {e}")))
+    }
+
     pub fn to_message(&self) -> Option<Message> {
         match self {
             Self::User(u) => Some(Message::User(u.clone())),
@@ -223,6 +319,13 @@ impl Incoming {
         }
     }
 
+    pub fn as_control_cancel_request(&self) -> Option<&ControlCancelRequest> {
+        match self {
+            Self::ControlCancelRequest(r) => Some(r),
+            _ => None,
+        }
+    }
+
     pub fn as_rate_limit_event(&self) -> Option<&RateLimitEvent> {
         match self {
             Self::RateLimitEvent(r) => Some(r),
@@ -230,3 +333,52 @@ impl Incoming {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_matches_serde_json() {
+        let lines = [
+            r#"{"type":"assistant","message":{"id":"msg_1","type":"message","role":"assistant","model":"claude-sonnet-4-5","content":[{"type":"text","text":"caf\u00e9 \"ok\"\n"},{"type":"tool_use","id":"toolu_1","name":"Read","input":{"file_path":"src/lib.rs"}}],"stop_reason":null,"usage":{"input_tokens":3,"output_tokens":1}},"parent_tool_use_id":null,"session_id":"s1"}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"line 1\nline 2","is_error":false}]},"parent_tool_use_id":null,"session_id":"s1"}"#,
+            r#"{"type":"control_request","request_id":"req_1","request":{"subtype":"can_use_tool","tool_name":"Bash","input":{"command":"ls"}}}"#,
+            r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":1200,"duration_api_ms":1000,"num_turns":1,"result":"done","session_id":"s1","total_cost_usd":0.01,"usage":{"input_tokens":3,"output_tokens":1}}"#,
+        ];
+        for line in lines {
+            let parsed = serde_json::to_value(Incoming::parse(line).unwrap()).unwrap();
+            let expected =
+                serde_json::to_value(serde_json::from_str::<Incoming>(line).unwrap()).unwrap();
+            assert_eq!(parsed, expected, "{line}");
+        }
+
+        assert!(matches!(
+            Incoming::parse(r#"{"type":"assistant""#),
+            Err(Error::ProtocolError(_))
+        ));
+    }
+
+    /// The fuzzing corpus is seeded with these, so they must stay valid as
+    /// the protocol types change.
+    #[test]
+    fn test_fuzz_corpus_seeds_round_trip() {
+        let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/incoming");
+        let mut seeds = 0;
+        for entry in std::fs::read_dir(corpus).unwrap() {
+            let path = entry.unwrap().path();
+            let line = std::fs::read_to_string(&path).unwrap();
+            let incoming = Incoming::parse(line.trim_end())
+                .unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            let written = serde_json::to_string(&incoming).unwrap();
+            assert_eq!(
+                serde_json::to_value(Incoming::parse(&written).unwrap()).unwrap(),
+                serde_json::to_value(&incoming).unwrap(),
+                "{}",
+                path.display()
+            );
+            seeds += 1;
+        }
+        assert!(seeds > 0);
+    }
+}
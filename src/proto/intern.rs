@@ -0,0 +1,128 @@
+//! Interning of the strings repeated across a session's messages.
+//!
+//! Tool names, model names and session ids recur in thousands of messages of
+//! a long session, and are copied again into every response and turn record
+//! made from them. The protocol types deserialise these fields through a
+//! process-wide cache, so every message shares one allocation per distinct
+//! string. The cache is bounded: once full, strings it does not hold are
+//! allocated as usual.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+
+use serde::Deserializer;
+use serde::de::{self, Visitor};
+
+/// The most strings kept. Tool and model names number in the tens, and a
+/// process rarely runs more than a few thousand sessions.
+const MAX_INTERNED: usize = 4096;
+
+static INTERNED: LazyLock<Mutex<HashSet<Arc<str>>>> = LazyLock::new(Mutex::default);
+
+/// The shared copy of `s`.
+pub(crate) fn intern(s: &str) -> Arc<str> {
+    let mut interned = INTERNED.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(shared) = interned.get(s) {
+        return Arc::clone(shared);
+    }
+    let shared = Arc::<str>::from(s);
+    if interned.len() < MAX_INTERNED {
+        interned.insert(Arc::clone(&shared));
+    }
+    shared
+}
+
+/// How many strings are interned.
+pub(crate) fn interned_count() -> usize {
+    INTERNED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .len()
+}
+
+/// Deserialises a string field into its shared copy, without allocating when
+/// the string is interned already.
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Arc<str>, D::Error> {
+    deserializer.deserialize_str(InternVisitor)
+}
+
+/// Like [`deserialize`], for optional fields. Use with `#[serde(default)]`.
+pub(crate) fn deserialize_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Arc<str>>, D::Error> {
+    deserializer.deserialize_option(OptionVisitor)
+}
+
+struct InternVisitor;
+
+impl Visitor<'_> for InternVisitor {
+    type Value = Arc<str>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(intern(v))
+    }
+}
+
+struct OptionVisitor;
+
+impl<'de> Visitor<'de> for OptionVisitor {
+    type Value = Option<Arc<str>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string or null")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserialize(deserializer).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Record {
+        #[serde(deserialize_with = "deserialize")]
+        name: Arc<str>,
+        #[serde(default, deserialize_with = "deserialize_option")]
+        model: Option<Arc<str>>,
+    }
+
+    #[test]
+    fn test_repeated_strings_share_one_allocation() {
+        let record = |value| serde_json::from_value::<Record>(value).unwrap();
+        let first = record(json!({"name": "intern_test_tool", "model": null}));
+        let second = serde_json::from_str::<Record>(
+            r#"{"name": "intern_test_tool", "model": "intern_test_model"}"#,
+        )
+        .unwrap();
+        assert!(Arc::ptr_eq(&first.name, &second.name));
+        assert!(first.model.is_none());
+        assert!(Arc::ptr_eq(
+            second.model.as_ref().unwrap(),
+            &intern("intern_test_model")
+        ));
+        assert!(record(json!({"name": "intern_test_tool"})).model.is_none());
+
+        assert!(serde_json::from_value::<Record>(json!({"name": 1})).is_err());
+    }
+}
@@ -1,8 +1,11 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-
 use super::content_block::ContentBlock;
+use super::intern::{self, intern};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -16,11 +19,16 @@ pub enum Message {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserEnvelope {
     message: UserMessageInner,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_tool_use_id: Option<String>,
 }
 
 impl UserEnvelope {
     pub fn new(message: UserMessageInner) -> Self {
-        Self { message }
+        Self {
+            message,
+            parent_tool_use_id: None,
+        }
     }
 
     // Getters
@@ -28,6 +36,12 @@ impl UserEnvelope {
         &self.message
     }
 
+    /// Set when the message belongs to a subagent, to the id of the Task
+    /// tool use that started it.
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        self.parent_tool_use_id.as_deref()
+    }
+
     // Setters
     pub fn set_message(&mut self, message: UserMessageInner) {
         self.message = message;
@@ -97,6 +111,8 @@ pub struct AssistantEnvelope {
     message: AssistantMessageInner,
     #[serde(skip_serializing_if = "Option::is_none")]
     uuid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_tool_use_id: Option<String>,
     #[serde(flatten)]
     extra: Map<String, Value>,
 }
@@ -106,6 +122,7 @@ impl AssistantEnvelope {
         Self {
             message,
             uuid: None,
+            parent_tool_use_id: None,
             extra: Map::new(),
         }
     }
@@ -118,6 +135,12 @@ impl AssistantEnvelope {
         self.uuid.as_deref()
     }
 
+    /// Set when the message belongs to a subagent, to the id of the Task
+    /// tool use that started it.
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        self.parent_tool_use_id.as_deref()
+    }
+
     pub fn extra(&self) -> &Map<String, Value> {
         &self.extra
     }
@@ -135,7 +158,8 @@ impl AssistantEnvelope {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssistantMessageInner {
     content: Vec<ContentBlock>,
-    model: String,
+    #[serde(deserialize_with = "intern::deserialize")]
+    model: Arc<str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<AssistantError>,
     #[serde(flatten)]
@@ -146,7 +170,7 @@ impl AssistantMessageInner {
     pub fn new(content: Vec<ContentBlock>, model: impl Into<String>) -> Self {
         Self {
             content,
-            model: model.into(),
+            model: intern(&model.into()),
             error: None,
             extra: Map::new(),
         }
@@ -175,7 +199,7 @@ impl AssistantMessageInner {
     }
 
     pub fn set_model(&mut self, model: impl Into<String>) {
-        self.model = model.into();
+        self.model = intern(&model.into());
     }
 
     pub fn set_error(&mut self, error: Option<AssistantError>) {
@@ -283,16 +307,32 @@ impl HookLifecycleMessage {
     pub fn exit_code(&self) -> Option<i32> {
         self.exit_code
     }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InitMessage {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    session_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    model: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "intern::deserialize_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    session_id: Option<Arc<str>>,
+    #[serde(
+        default,
+        deserialize_with = "intern::deserialize_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    model: Option<Arc<str>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mcp_servers: Vec<McpServerStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    plugins: Vec<PluginInfo>,
     #[serde(flatten)]
     extra: Map<String, Value>,
 }
@@ -303,6 +343,8 @@ impl InitMessage {
             session_id: None,
             model: None,
             cwd: None,
+            mcp_servers: Vec::new(),
+            plugins: Vec::new(),
             extra: Map::new(),
         }
     }
@@ -320,23 +362,43 @@ impl InitMessage {
         self.cwd.as_deref()
     }
 
+    pub fn mcp_servers(&self) -> &[McpServerStatus] {
+        &self.mcp_servers
+    }
+
+    pub fn mcp_server(&self, name: &str) -> Option<&McpServerStatus> {
+        self.mcp_servers.iter().find(|s| s.name() == name)
+    }
+
+    pub fn plugins(&self) -> &[PluginInfo] {
+        &self.plugins
+    }
+
     pub fn extra(&self) -> &Map<String, Value> {
         &self.extra
     }
 
     // Setters
     pub fn set_session_id(&mut self, session_id: Option<String>) {
-        self.session_id = session_id;
+        self.session_id = session_id.as_deref().map(intern);
     }
 
     pub fn set_model(&mut self, model: Option<String>) {
-        self.model = model;
+        self.model = model.as_deref().map(intern);
     }
 
     pub fn set_cwd(&mut self, cwd: Option<String>) {
         self.cwd = cwd;
     }
 
+    pub fn set_mcp_servers(&mut self, mcp_servers: Vec<McpServerStatus>) {
+        self.mcp_servers = mcp_servers;
+    }
+
+    pub fn set_plugins(&mut self, plugins: Vec<PluginInfo>) {
+        self.plugins = plugins;
+    }
+
     pub fn set_extra(&mut self, extra: Map<String, Value>) {
         self.extra = extra;
     }
@@ -357,6 +419,16 @@ impl InitMessage {
         self
     }
 
+    pub fn with_mcp_servers(mut self, mcp_servers: Vec<McpServerStatus>) -> Self {
+        self.set_mcp_servers(mcp_servers);
+        self
+    }
+
+    pub fn with_plugins(mut self, plugins: Vec<PluginInfo>) -> Self {
+        self.set_plugins(plugins);
+        self
+    }
+
     pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
         self.set_extra(extra);
         self
@@ -369,6 +441,173 @@ impl Default for InitMessage {
     }
 }
 
+/// Connection state of an MCP server as reported by the CLI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum McpServerState {
+    Connected,
+    Pending,
+    Failed,
+    NeedsAuth,
+    #[serde(other)]
+    Unknown,
+}
+
+impl McpServerState {
+    pub fn is_connected(&self) -> bool {
+        matches!(self, Self::Connected)
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed)
+    }
+}
+
+impl std::fmt::Display for McpServerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Connected => "connected",
+            Self::Pending => "pending",
+            Self::Failed => "failed",
+            Self::NeedsAuth => "needs-auth",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+/// An entry of the `mcp_servers` list in the init message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerStatus {
+    name: String,
+    status: McpServerState,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl McpServerStatus {
+    pub fn new(name: impl Into<String>, status: McpServerState) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            extra: Map::new(),
+        }
+    }
+
+    // Getters
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn status(&self) -> &McpServerState {
+        &self.status
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    // Setters
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    pub fn set_status(&mut self, status: McpServerState) {
+        self.status = status;
+    }
+
+    pub fn set_extra(&mut self, extra: Map<String, Value>) {
+        self.extra = extra;
+    }
+
+    // Builders
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn with_status(mut self, status: McpServerState) -> Self {
+        self.set_status(status);
+        self
+    }
+
+    pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
+        self.set_extra(extra);
+        self
+    }
+}
+
+/// A plugin the CLI loaded, as listed in the init message and server info.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginInfo {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl PluginInfo {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            path: None,
+            extra: Map::new(),
+        }
+    }
+
+    // Getters
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The directory the plugin was loaded from.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    /// Whether this is the plugin `name`, given either bare or as
+    /// `plugin@marketplace`.
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name
+            || name
+                .split_once('@')
+                .is_some_and(|(bare, _)| self.name == bare)
+    }
+
+    // Setters
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    pub fn set_path(&mut self, path: Option<String>) {
+        self.path = path;
+    }
+
+    pub fn set_extra(&mut self, extra: Map<String, Value>) {
+        self.extra = extra;
+    }
+
+    // Builders
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.set_path(Some(path.into()));
+        self
+    }
+
+    pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
+        self.set_extra(extra);
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorMessage {
     error: String,
@@ -421,7 +660,8 @@ pub struct ResultMessage {
     duration_api_ms: i64,
     is_error: bool,
     num_turns: i32,
-    session_id: String,
+    #[serde(deserialize_with = "intern::deserialize")]
+    session_id: Arc<str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     total_cost_usd: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -430,6 +670,10 @@ pub struct ResultMessage {
     result: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     structured_output: Option<Value>,
+    #[serde(rename = "modelUsage", skip_serializing_if = "Option::is_none")]
+    model_usage: Option<HashMap<String, ModelUsage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permission_denials: Option<Vec<PermissionDenial>>,
     #[serde(flatten)]
     extra: Map<String, Value>,
 }
@@ -442,11 +686,13 @@ impl ResultMessage {
             duration_api_ms: 0,
             is_error: false,
             num_turns: 0,
-            session_id: session_id.into(),
+            session_id: intern(&session_id.into()),
             total_cost_usd: None,
             usage: None,
             result: None,
             structured_output: None,
+            model_usage: None,
+            permission_denials: None,
             extra: Map::new(),
         }
     }
@@ -492,6 +738,15 @@ impl ResultMessage {
         self.structured_output.as_ref()
     }
 
+    /// Token usage and cost per model, keyed by model id.
+    pub fn model_usage(&self) -> Option<&HashMap<String, ModelUsage>> {
+        self.model_usage.as_ref()
+    }
+
+    pub fn permission_denials(&self) -> Option<&[PermissionDenial]> {
+        self.permission_denials.as_deref()
+    }
+
     pub fn extra(&self) -> &Map<String, Value> {
         &self.extra
     }
@@ -518,7 +773,7 @@ impl ResultMessage {
     }
 
     pub fn set_session_id(&mut self, session_id: impl Into<String>) {
-        self.session_id = session_id.into();
+        self.session_id = intern(&session_id.into());
     }
 
     pub fn set_total_cost_usd(&mut self, total_cost_usd: Option<f64>) {
@@ -537,6 +792,14 @@ impl ResultMessage {
         self.structured_output = structured_output;
     }
 
+    pub fn set_model_usage(&mut self, model_usage: Option<HashMap<String, ModelUsage>>) {
+        self.model_usage = model_usage;
+    }
+
+    pub fn set_permission_denials(&mut self, permission_denials: Option<Vec<PermissionDenial>>) {
+        self.permission_denials = permission_denials;
+    }
+
     pub fn set_extra(&mut self, extra: Map<String, Value>) {
         self.extra = extra;
     }
@@ -592,6 +855,16 @@ impl ResultMessage {
         self
     }
 
+    pub fn with_model_usage(mut self, model_usage: HashMap<String, ModelUsage>) -> Self {
+        self.set_model_usage(Some(model_usage));
+        self
+    }
+
+    pub fn with_permission_denials(mut self, permission_denials: Vec<PermissionDenial>) -> Self {
+        self.set_permission_denials(Some(permission_denials));
+        self
+    }
+
     pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
         self.set_extra(extra);
         self
@@ -726,6 +999,213 @@ impl Default for Usage {
     }
 }
 
+/// Usage for a single model within a result's `modelUsage` breakdown.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelUsage {
+    #[serde(default)]
+    input_tokens: i64,
+    #[serde(default)]
+    output_tokens: i64,
+    #[serde(default)]
+    cache_read_input_tokens: i64,
+    #[serde(default)]
+    cache_creation_input_tokens: i64,
+    #[serde(default)]
+    web_search_requests: i64,
+    #[serde(default, rename = "costUSD")]
+    cost_usd: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context_window: Option<i64>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl ModelUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Getters
+    pub fn input_tokens(&self) -> i64 {
+        self.input_tokens
+    }
+
+    pub fn output_tokens(&self) -> i64 {
+        self.output_tokens
+    }
+
+    pub fn cache_read_input_tokens(&self) -> i64 {
+        self.cache_read_input_tokens
+    }
+
+    pub fn cache_creation_input_tokens(&self) -> i64 {
+        self.cache_creation_input_tokens
+    }
+
+    pub fn web_search_requests(&self) -> i64 {
+        self.web_search_requests
+    }
+
+    pub fn cost_usd(&self) -> f64 {
+        self.cost_usd
+    }
+
+    pub fn context_window(&self) -> Option<i64> {
+        self.context_window
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    // Setters
+    pub fn set_input_tokens(&mut self, input_tokens: i64) {
+        self.input_tokens = input_tokens;
+    }
+
+    pub fn set_output_tokens(&mut self, output_tokens: i64) {
+        self.output_tokens = output_tokens;
+    }
+
+    pub fn set_cache_read_input_tokens(&mut self, tokens: i64) {
+        self.cache_read_input_tokens = tokens;
+    }
+
+    pub fn set_cache_creation_input_tokens(&mut self, tokens: i64) {
+        self.cache_creation_input_tokens = tokens;
+    }
+
+    pub fn set_web_search_requests(&mut self, requests: i64) {
+        self.web_search_requests = requests;
+    }
+
+    pub fn set_cost_usd(&mut self, cost_usd: f64) {
+        self.cost_usd = cost_usd;
+    }
+
+    pub fn set_context_window(&mut self, context_window: Option<i64>) {
+        self.context_window = context_window;
+    }
+
+    pub fn set_extra(&mut self, extra: Map<String, Value>) {
+        self.extra = extra;
+    }
+
+    // Builders
+    pub fn with_input_tokens(mut self, input_tokens: i64) -> Self {
+        self.set_input_tokens(input_tokens);
+        self
+    }
+
+    pub fn with_output_tokens(mut self, output_tokens: i64) -> Self {
+        self.set_output_tokens(output_tokens);
+        self
+    }
+
+    pub fn with_cache_read_input_tokens(mut self, tokens: i64) -> Self {
+        self.set_cache_read_input_tokens(tokens);
+        self
+    }
+
+    pub fn with_cache_creation_input_tokens(mut self, tokens: i64) -> Self {
+        self.set_cache_creation_input_tokens(tokens);
+        self
+    }
+
+    pub fn with_web_search_requests(mut self, requests: i64) -> Self {
+        self.set_web_search_requests(requests);
+        self
+    }
+
+    pub fn with_cost_usd(mut self, cost_usd: f64) -> Self {
+        self.set_cost_usd(cost_usd);
+        self
+    }
+
+    pub fn with_context_window(mut self, context_window: i64) -> Self {
+        self.set_context_window(Some(context_window));
+        self
+    }
+
+    pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
+        self.set_extra(extra);
+        self
+    }
+}
+
+impl std::ops::AddAssign<&ModelUsage> for ModelUsage {
+    fn add_assign(&mut self, other: &ModelUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.web_search_requests += other.web_search_requests;
+        self.cost_usd += other.cost_usd;
+        self.context_window = self.context_window.max(other.context_window);
+    }
+}
+
+/// A tool call the CLI refused during the turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionDenial {
+    #[serde(deserialize_with = "intern::deserialize")]
+    tool_name: Arc<str>,
+    tool_use_id: String,
+    #[serde(default)]
+    tool_input: Value,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl PermissionDenial {
+    pub fn new(tool_name: impl Into<String>, tool_use_id: impl Into<String>) -> Self {
+        Self {
+            tool_name: intern(&tool_name.into()),
+            tool_use_id: tool_use_id.into(),
+            tool_input: Value::Null,
+            extra: Map::new(),
+        }
+    }
+
+    // Getters
+    pub fn tool_name(&self) -> &str {
+        &self.tool_name
+    }
+
+    pub fn tool_use_id(&self) -> &str {
+        &self.tool_use_id
+    }
+
+    pub fn tool_input(&self) -> &Value {
+        &self.tool_input
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    // Setters
+    pub fn set_tool_input(&mut self, tool_input: Value) {
+        self.tool_input = tool_input;
+    }
+
+    pub fn set_extra(&mut self, extra: Map<String, Value>) {
+        self.extra = extra;
+    }
+
+    // Builders
+    pub fn with_tool_input(mut self, tool_input: Value) -> Self {
+        self.set_tool_input(tool_input);
+        self
+    }
+
+    pub fn with_extra(mut self, extra: Map<String, Value>) -> Self {
+        self.set_extra(extra);
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutgoingUserMessage {
     #[serde(rename = "type")]
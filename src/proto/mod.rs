@@ -1,18 +1,24 @@
 pub mod content_block;
 pub mod control;
+pub mod extra;
 pub mod incoming;
+pub(crate) mod intern;
 pub mod message;
+pub mod versions;
+pub mod wire;
 
 pub use content_block::ContentBlock;
 pub use control::{
-    ErrorCode, ErrorDetail, ErrorResponse, PermissionMode, Request, RequestEnvelope, Response,
-    ServerInfo, SuccessResponse,
+    CancelRequestEnvelope, Capabilities, ErrorCode, ErrorDetail, ErrorResponse, InitializeResponse,
+    PermissionMode, Request, RequestEnvelope, Response, ServerInfo, SlashCommand, SuccessResponse,
 };
+pub use extra::Extra;
 pub use incoming::{
-    ControlRequestEnvelope, ControlResponseEnvelope, Incoming, RateLimitEvent, RateLimitStatus,
+    ControlCancelRequest, ControlRequestEnvelope, ControlResponseEnvelope, Incoming,
+    RateLimitEvent, RateLimitStatus, StreamEvent,
 };
 pub use message::{
-    AssistantEnvelope, AssistantError, AssistantMessageInner, ErrorMessage, InitMessage, Message,
-    OutgoingUserMessage, ResultMessage, SystemMessage, Usage, UserContent, UserEnvelope,
-    UserMessageInner,
+    AssistantEnvelope, AssistantError, AssistantMessageInner, ErrorMessage, InitMessage,
+    McpServerState, McpServerStatus, Message, ModelUsage, OutgoingUserMessage, PermissionDenial,
+    PluginInfo, ResultMessage, SystemMessage, Usage, UserContent, UserEnvelope, UserMessageInner,
 };
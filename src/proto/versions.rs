@@ -0,0 +1,94 @@
+//! The protocols this crate speaks, so applications can report them.
+//!
+//! The CLI's stream-json protocol carries no version of its own. This crate
+//! numbers the revisions of it that it models in [`WIRE_PROTOCOL_REVISION`],
+//! raising it whenever it learns messages or control requests the CLI added;
+//! [`compatibility`] lists what each revision added.
+//!
+//! ```
+//! use clauders::proto::versions;
+//!
+//! println!(
+//!     "speaks stream-json revision {} and MCP {} as {}",
+//!     versions::WIRE_PROTOCOL_REVISION,
+//!     versions::MCP_PROTOCOL_VERSION,
+//!     versions::ENTRYPOINT,
+//! );
+//! ```
+
+/// The revision of the CLI's stream-json protocol this crate models.
+pub const WIRE_PROTOCOL_REVISION: u32 = 3;
+
+/// The MCP revision the SDK MCP servers offer when the CLI asks for none
+/// they know.
+pub const MCP_PROTOCOL_VERSION: &str = MCP_PROTOCOL_VERSIONS[0];
+
+/// The MCP revisions the SDK MCP servers speak, newest first.
+pub const MCP_PROTOCOL_VERSIONS: &[&str] =
+    &["2025-11-25", "2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// What the CLI is told it is driven by, in `CLAUDE_CODE_ENTRYPOINT`.
+pub const ENTRYPOINT: &str = "sdk-rust";
+
+/// A revision of the stream-json protocol and what it added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision {
+    revision: u32,
+    added: &'static [&'static str],
+}
+
+impl Revision {
+    /// The revision number.
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    /// The messages and control requests the revision added.
+    pub fn added(&self) -> &'static [&'static str] {
+        self.added
+    }
+}
+
+const REVISIONS: &[Revision] = &[
+    Revision {
+        revision: 1,
+        added: &[
+            "user, assistant, system/init and result messages",
+            "control requests: initialize, interrupt, can_use_tool, set_permission_mode, hook_callback, mcp_message",
+        ],
+    },
+    Revision {
+        revision: 2,
+        added: &[
+            "stream_event partial messages",
+            "control requests: set_model, get_server_info",
+            "system/error messages",
+        ],
+    },
+    Revision {
+        revision: 3,
+        added: &[
+            "rate_limit_event messages",
+            "control_cancel_request messages",
+            "system/hook_started and system/hook_response messages",
+        ],
+    },
+];
+
+/// Every revision, oldest first. The last is [`WIRE_PROTOCOL_REVISION`].
+pub fn compatibility() -> &'static [Revision] {
+    REVISIONS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compatibility_table_ends_at_current_revision() {
+        let revisions: Vec<u32> = compatibility().iter().map(Revision::revision).collect();
+        assert_eq!(revisions, (1..=WIRE_PROTOCOL_REVISION).collect::<Vec<_>>());
+        assert!(compatibility().iter().all(|r| !r.added().is_empty()));
+        assert_eq!(MCP_PROTOCOL_VERSION, "2025-11-25");
+    }
+}
@@ -0,0 +1,198 @@
+//! Validation of outgoing messages against the bundled wire-format schema.
+//!
+//! Only the JSON Schema keywords the bundled schema uses are supported:
+//! `$ref` into `$defs`, `oneOf`, `type`, `const`, `enum`, `required`,
+//! `properties`, `additionalProperties: false` and `items`.
+
+use std::sync::LazyLock;
+
+use serde_json::Value;
+
+static OUTGOING_SCHEMA: LazyLock<Value> = LazyLock::new(|| {
+    serde_json::from_str(include_str!("../../schemas/outgoing.json"))
+        .expect("bundled outgoing schema is valid JSON")
+});
+
+/// Checks a message about to be written to the CLI, returning where it
+/// departs from the schema.
+pub fn validate_outgoing(message: &Value) -> Result<(), String> {
+    let schema = &*OUTGOING_SCHEMA;
+    validate(schema, schema, message, "$")
+}
+
+fn validate(root: &Value, schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let schema = resolve(root, schema)?;
+
+    if let Some(variants) = schema.get("oneOf").and_then(Value::as_array) {
+        let variants = variants
+            .iter()
+            .map(|variant| resolve(root, variant))
+            .collect::<Result<Vec<_>, _>>()?;
+        let matching = variants
+            .iter()
+            .filter(|variant| validate(root, variant, value, path).is_ok())
+            .count();
+        return match matching {
+            1 => Ok(()),
+            0 => {
+                // Report against the variant whose discriminators match, if any.
+                let mut candidates = variants
+                    .iter()
+                    .filter(|variant| discriminators_match(variant, value));
+                match (candidates.next(), candidates.next()) {
+                    (Some(variant), None) => validate(root, variant, value, path),
+                    _ => Err(format!("{path}: matches no known message shape")),
+                }
+            }
+            _ => Err(format!("{path}: matches more than one message shape")),
+        };
+    }
+
+    if let Some(expected) = schema.get("const")
+        && value != expected
+    {
+        return Err(format!("{path}: expected {expected}, found {value}"));
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        return Err(format!("{path}: {value} is not one of {allowed:?}"));
+    }
+
+    if let Some(types) = schema.get("type") {
+        let matches = match types {
+            Value::Array(types) => types.iter().any(|t| has_type(value, t)),
+            t => has_type(value, t),
+        };
+        if !matches {
+            return Err(format!("{path}: expected type {types}, found {value}"));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for field in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(field) {
+                return Err(format!("{path}: missing field '{field}'"));
+            }
+        }
+        for (key, field) in object {
+            let field_path = format!("{path}.{key}");
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(field_schema) => validate(root, field_schema, field, &field_path)?,
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{field_path}: unexpected field"));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            validate(root, items, item, &format!("{path}[{index}]"))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn resolve<'a>(root: &'a Value, schema: &'a Value) -> Result<&'a Value, String> {
+    match schema.get("$ref").and_then(Value::as_str) {
+        Some(reference) => {
+            let name = reference.trim_start_matches("#/$defs/");
+            let target = root
+                .pointer(&format!("/$defs/{name}"))
+                .ok_or_else(|| format!("unknown schema reference '{reference}'"))?;
+            resolve(root, target)
+        }
+        None => Ok(schema),
+    }
+}
+
+/// Whether `value` agrees with every `const` property of `schema`, such as
+/// a message's `type` or `subtype`.
+fn discriminators_match(schema: &Value, value: &Value) -> bool {
+    schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, field)| field.get("const").map(|expected| (key, expected)))
+        .all(|(key, expected)| value.get(key) == Some(expected))
+}
+
+fn has_type(value: &Value, expected: &Value) -> bool {
+    match expected.as_str() {
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("boolean") => value.is_boolean(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        Some("null") => value.is_null(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::proto::control::{
+        CancelRequestEnvelope, InitializeRequest, McpMessageRequest, Request, RequestEnvelope,
+        SetPermissionModeRequest,
+    };
+    use crate::proto::{OutgoingUserMessage, PermissionMode};
+
+    #[test]
+    fn test_outgoing_messages_match_schema() {
+        let messages = [
+            serde_json::to_value(OutgoingUserMessage::text("hi")).unwrap(),
+            serde_json::to_value(RequestEnvelope::interrupt("1")).unwrap(),
+            serde_json::to_value(RequestEnvelope::new_with(
+                "2",
+                Request::Initialize(InitializeRequest::new()),
+            ))
+            .unwrap(),
+            serde_json::to_value(RequestEnvelope::new_with(
+                "3",
+                Request::SetPermissionMode(SetPermissionModeRequest::new(
+                    PermissionMode::AcceptEdits,
+                )),
+            ))
+            .unwrap(),
+            serde_json::to_value(CancelRequestEnvelope::new("5")).unwrap(),
+            serde_json::to_value(RequestEnvelope::new_with(
+                "6",
+                Request::McpMessage(McpMessageRequest::new(
+                    "tools",
+                    json!({"jsonrpc": "2.0", "id": 1, "method": "sampling/createMessage"}),
+                )),
+            ))
+            .unwrap(),
+        ];
+        for message in &messages {
+            assert_eq!(validate_outgoing(message), Ok(()), "{message}");
+        }
+
+        let miscased = json!({
+            "type": "control_request",
+            "request_id": "4",
+            "request": {"subtype": "initialize", "sdk_mcp_servers": []}
+        });
+        assert!(
+            validate_outgoing(&miscased)
+                .unwrap_err()
+                .contains("sdk_mcp_servers")
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Routing the CLI through a cloud provider.
+//!
+//! By default the CLI talks to the Anthropic API. [`Options::provider`](crate::Options::provider)
+//! sends it through Amazon Bedrock or Google Vertex AI instead, setting the
+//! variables the CLI reads and checking up front that credentials for the
+//! provider can be found, rather than failing on the first request.
+//!
+//! Credentials are looked for in the client's environment (the process
+//! environment plus [`Options::env`](crate::Options::env)) and in the
+//! provider's shared configuration files: `~/.aws/credentials` and
+//! `~/.aws/config` for Bedrock, and the gcloud application default
+//! credentials for Vertex. Setups that authenticate another way, such as an
+//! LLM gateway, can set `skip_auth`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::provider::Provider;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let options = Options::new().provider(Provider::Bedrock {
+//!     region: "us-east-1".to_owned(),
+//!     profile: Some("claude".to_owned()),
+//!     base_url: None,
+//!     skip_auth: false,
+//! });
+//! let client = Client::new(options).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::PathBuf;
+
+use crate::error::ConfigError;
+
+/// Variables that carry AWS credentials, any of which satisfies the check.
+const AWS_CREDENTIAL_ENV: &[&str] = &[
+    "AWS_ACCESS_KEY_ID",
+    "AWS_PROFILE",
+    "AWS_BEARER_TOKEN_BEDROCK",
+    "AWS_WEB_IDENTITY_TOKEN_FILE",
+    "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI",
+];
+
+/// Variables the AWS SDK reads, passed through to tenants with an isolated
+/// environment.
+const AWS_ENV: &[&str] = &[
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "AWS_PROFILE",
+    "AWS_BEARER_TOKEN_BEDROCK",
+    "AWS_WEB_IDENTITY_TOKEN_FILE",
+    "AWS_ROLE_ARN",
+    "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI",
+    "AWS_CONFIG_FILE",
+    "AWS_SHARED_CREDENTIALS_FILE",
+];
+
+/// Variables the Google auth library reads, passed through to tenants with
+/// an isolated environment.
+const GOOGLE_ENV: &[&str] = &["GOOGLE_APPLICATION_CREDENTIALS", "CLOUDSDK_CONFIG"];
+
+/// The service the CLI sends model requests to.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Provider {
+    /// The Anthropic API, authenticated as the CLI is logged in.
+    #[default]
+    Anthropic,
+    /// Amazon Bedrock.
+    Bedrock {
+        region: String,
+        /// The AWS profile to use, from `~/.aws/config`.
+        profile: Option<String>,
+        /// A Bedrock endpoint or gateway to use instead of the regional one.
+        base_url: Option<String>,
+        /// Sends requests without AWS credentials, for gateways that add
+        /// their own.
+        skip_auth: bool,
+    },
+    /// Google Vertex AI.
+    Vertex {
+        project: String,
+        region: String,
+        /// A Vertex endpoint or gateway to use instead of the regional one.
+        base_url: Option<String>,
+        /// Sends requests without Google credentials, for gateways that add
+        /// their own.
+        skip_auth: bool,
+    },
+}
+
+impl Provider {
+    /// Bedrock in `region`, with credentials from the environment.
+    pub fn bedrock(region: impl Into<String>) -> Self {
+        Self::Bedrock {
+            region: region.into(),
+            profile: None,
+            base_url: None,
+            skip_auth: false,
+        }
+    }
+
+    /// Vertex AI for `project` in `region`, with application default
+    /// credentials.
+    pub fn vertex(project: impl Into<String>, region: impl Into<String>) -> Self {
+        Self::Vertex {
+            project: project.into(),
+            region: region.into(),
+            base_url: None,
+            skip_auth: false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Anthropic => "anthropic",
+            Self::Bedrock { .. } => "bedrock",
+            Self::Vertex { .. } => "vertex",
+        }
+    }
+
+    /// The environment variables for the CLI process.
+    pub(crate) fn env(&self) -> Vec<(String, String)> {
+        let mut env = Vec::new();
+        let mut set = |key: &str, value: &str| env.push((key.to_owned(), value.to_owned()));
+        match self {
+            Self::Anthropic => {}
+            Self::Bedrock {
+                region,
+                profile,
+                base_url,
+                skip_auth,
+            } => {
+                set("CLAUDE_CODE_USE_BEDROCK", "1");
+                set("AWS_REGION", region);
+                if let Some(profile) = profile {
+                    set("AWS_PROFILE", profile);
+                }
+                if let Some(url) = base_url {
+                    set("ANTHROPIC_BEDROCK_BASE_URL", url);
+                }
+                if *skip_auth {
+                    set("CLAUDE_CODE_SKIP_BEDROCK_AUTH", "1");
+                }
+            }
+            Self::Vertex {
+                project,
+                region,
+                base_url,
+                skip_auth,
+            } => {
+                set("CLAUDE_CODE_USE_VERTEX", "1");
+                set("ANTHROPIC_VERTEX_PROJECT_ID", project);
+                set("CLOUD_ML_REGION", region);
+                if let Some(url) = base_url {
+                    set("ANTHROPIC_VERTEX_BASE_URL", url);
+                }
+                if *skip_auth {
+                    set("CLAUDE_CODE_SKIP_VERTEX_AUTH", "1");
+                }
+            }
+        }
+        env
+    }
+
+    /// Variables to pass through to a CLI process that otherwise inherits
+    /// nothing.
+    pub(crate) fn inherited_env(&self) -> &'static [&'static str] {
+        match self {
+            Self::Anthropic => &[],
+            Self::Bedrock { .. } => AWS_ENV,
+            Self::Vertex { .. } => GOOGLE_ENV,
+        }
+    }
+
+    /// Checks the provider's settings and that credentials can be found in
+    /// `env`, the variables set for the CLI on top of the process
+    /// environment.
+    pub(crate) fn validate(&self, env: &[(String, String)]) -> Result<(), ConfigError> {
+        let is_set = |name: &str| {
+            env.iter()
+                .any(|(key, value)| key == name && !value.is_empty())
+                || std::env::var_os(name).is_some_and(|value| !value.is_empty())
+        };
+        let missing = |reason: &str| ConfigError::ProviderCredentials {
+            provider: self.name().to_owned(),
+            reason: reason.to_owned(),
+        };
+        match self {
+            Self::Anthropic => Ok(()),
+            Self::Bedrock {
+                region,
+                profile,
+                skip_auth,
+                ..
+            } => {
+                if region.is_empty() {
+                    return Err(missing("no region set"));
+                }
+                if *skip_auth
+                    || profile.is_some()
+                    || AWS_CREDENTIAL_ENV.iter().any(|name| is_set(name))
+                    || home_file(".aws/credentials").is_some_and(|path| path.is_file())
+                    || home_file(".aws/config").is_some_and(|path| path.is_file())
+                {
+                    return Ok(());
+                }
+                Err(missing(
+                    "no AWS credentials found; set AWS_PROFILE or AWS_ACCESS_KEY_ID, or \
+                     configure ~/.aws/credentials",
+                ))
+            }
+            Self::Vertex {
+                project,
+                region,
+                skip_auth,
+                ..
+            } => {
+                if project.is_empty() || region.is_empty() {
+                    return Err(missing("project and region are required"));
+                }
+                if *skip_auth
+                    || is_set("GOOGLE_APPLICATION_CREDENTIALS")
+                    || home_file(".config/gcloud/application_default_credentials.json")
+                        .is_some_and(|path| path.is_file())
+                {
+                    return Ok(());
+                }
+                Err(missing(
+                    "no Google credentials found; set GOOGLE_APPLICATION_CREDENTIALS or run \
+                     `gcloud auth application-default login`",
+                ))
+            }
+        }
+    }
+}
+
+fn home_file(relative: &str) -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_env_and_credentials() {
+        let bedrock = Provider::Bedrock {
+            region: "eu-west-1".to_owned(),
+            profile: Some("claude".to_owned()),
+            base_url: None,
+            skip_auth: false,
+        };
+        assert_eq!(
+            bedrock.env(),
+            [
+                ("CLAUDE_CODE_USE_BEDROCK".to_owned(), "1".to_owned()),
+                ("AWS_REGION".to_owned(), "eu-west-1".to_owned()),
+                ("AWS_PROFILE".to_owned(), "claude".to_owned()),
+            ]
+        );
+        assert_eq!(bedrock.validate(&[]), Ok(()));
+
+        let vertex = Provider::vertex("my-project", "us-east5");
+        assert!(vertex.env().contains(&(
+            "ANTHROPIC_VERTEX_PROJECT_ID".to_owned(),
+            "my-project".to_owned()
+        )));
+        let credentials = [(
+            "GOOGLE_APPLICATION_CREDENTIALS".to_owned(),
+            "/secrets/sa.json".to_owned(),
+        )];
+        assert_eq!(vertex.validate(&credentials), Ok(()));
+        assert!(matches!(
+            Provider::vertex("", "us-east5").validate(&credentials),
+            Err(ConfigError::ProviderCredentials { .. })
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+//! Running the CLI attached to a pseudo-terminal.
+//!
+//! The CLI behaves slightly differently when its output is not a terminal.
+//! With the `pty` feature, [`Options::pty`](crate::Options::pty) starts it
+//! on a pseudo-terminal instead of pipes, so it runs as it would for a user,
+//! while the client still exchanges stream-json with it.
+//!
+//! The terminal is put in raw mode, so input is neither echoed nor limited
+//! to the line discipline's maximum line length. Output is the CLI's stdout
+//! and stderr combined: lines that are not JSON, such as warnings and
+//! terminal escape sequences, are logged and skipped.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use portable_pty::{CommandBuilder, MasterPty, PtySize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+use crate::error::Error;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The process and command line to start on the terminal.
+pub(crate) struct PtyCommand<'a> {
+    pub(crate) program: &'a Path,
+    pub(crate) args: Vec<String>,
+    pub(crate) inherit_env: Option<&'a [String]>,
+    pub(crate) remove_env: &'a [String],
+    pub(crate) env: Vec<(String, String)>,
+    pub(crate) cwd: &'a Path,
+}
+
+/// A CLI process running on a pseudo-terminal.
+pub(crate) struct PtyProcess {
+    child: Box<dyn portable_pty::Child + Send + Sync>,
+    // Closing the master hangs up the terminal, so it lives as long as the
+    // process.
+    _master: Box<dyn MasterPty + Send>,
+}
+
+impl PtyProcess {
+    /// Starts `command`, returning the process with the client's ends of its
+    /// input and output.
+    pub(crate) fn spawn(
+        command: PtyCommand<'_>,
+    ) -> Result<(Self, DuplexStream, DuplexStream), Error> {
+        let pty_error = |e: anyhow::Error| Error::ProcessError(format!("pseudo-terminal: {e}"));
+        let pair = portable_pty::native_pty_system()
+            .openpty(PtySize {
+                rows: 50,
+                cols: 200,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(pty_error)?;
+        make_raw(pair.master.as_ref());
+
+        let mut builder = CommandBuilder::new(command.program);
+        builder.args(&command.args);
+        if let Some(names) = command.inherit_env {
+            builder.env_clear();
+            for name in names {
+                if let Some(value) = std::env::var_os(name) {
+                    builder.env(name, value);
+                }
+            }
+        }
+        for name in command.remove_env {
+            builder.env_remove(name);
+        }
+        for (key, value) in command.env {
+            builder.env(key, value);
+        }
+        builder.cwd(command.cwd);
+
+        let child = pair.slave.spawn_command(builder).map_err(|e| {
+            Error::CliNotFound(format!(
+                "failed to spawn claude CLI at '{}' on a pseudo-terminal: {e}",
+                command.program.display()
+            ))
+        })?;
+        drop(pair.slave);
+
+        let reader = pair.master.try_clone_reader().map_err(pty_error)?;
+        let writer = pair.master.take_writer().map_err(pty_error)?;
+        let (stdout, output) = tokio::io::duplex(BUFFER_SIZE);
+        let (stdin, input) = tokio::io::duplex(BUFFER_SIZE);
+        let runtime = tokio::runtime::Handle::current();
+        std::thread::spawn({
+            let runtime = runtime.clone();
+            move || pump_output(reader, output, &runtime)
+        });
+        std::thread::spawn(move || pump_input(input, writer, &runtime));
+
+        Ok((
+            Self {
+                child,
+                _master: pair.master,
+            },
+            stdin,
+            stdout,
+        ))
+    }
+
+    pub(crate) fn id(&self) -> Option<u32> {
+        self.child.process_id()
+    }
+
+    pub(crate) fn start_kill(&mut self) -> std::io::Result<()> {
+        self.child.kill()
+    }
+
+    /// Waits for the process to exit after its input was closed, killing it
+    /// if it does not. A raw terminal has no end-of-file character, so the
+    /// CLI may not notice its input closing.
+    pub(crate) async fn wait(&mut self) -> Result<(), Error> {
+        let deadline = tokio::time::Instant::now() + CLOSE_TIMEOUT;
+        while self.child.try_wait()?.is_none() {
+            if tokio::time::Instant::now() >= deadline {
+                self.child.kill()?;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Ok(())
+    }
+}
+
+/// Turns off echo, line editing and output translation.
+fn make_raw(master: &dyn MasterPty) {
+    #[cfg(unix)]
+    if let Some(fd) = master.as_raw_fd() {
+        // SAFETY: `fd` is the open terminal owned by `master`, and `termios`
+        // is initialised by `tcgetattr` before it is read.
+        unsafe {
+            let mut termios = std::mem::zeroed::<libc::termios>();
+            if libc::tcgetattr(fd, &mut termios) == 0 {
+                libc::cfmakeraw(&mut termios);
+                libc::tcsetattr(fd, libc::TCSANOW, &termios);
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = master;
+}
+
+fn pump_output(
+    mut reader: Box<dyn Read + Send>,
+    mut output: DuplexStream,
+    runtime: &tokio::runtime::Handle,
+) {
+    let mut buf = vec![0; BUFFER_SIZE];
+    loop {
+        // Reading fails with EIO once the process has exited.
+        let n = match reader.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        if runtime.block_on(output.write_all(&buf[..n])).is_err() {
+            break;
+        }
+    }
+}
+
+fn pump_input(
+    mut input: DuplexStream,
+    mut writer: Box<dyn Write + Send>,
+    runtime: &tokio::runtime::Handle,
+) {
+    let mut buf = vec![0; BUFFER_SIZE];
+    loop {
+        let n = match runtime.block_on(input.read(&mut buf)) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        if writer
+            .write_all(&buf[..n])
+            .and_then(|()| writer.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// Removes terminal escape sequences from `line`.
+pub(crate) fn strip_escapes(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in @..~.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ST.
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_escapes() {
+        assert_eq!(
+            strip_escapes("\u{1b}[?25l\u{1b}[2K{\"type\":\"system\"}"),
+            "{\"type\":\"system\"}"
+        );
+        assert_eq!(
+            strip_escapes("\u{1b}]0;claude\u{7}\u{1b}[32m✓\u{1b}[0m done"),
+            "✓ done"
+        );
+        assert_eq!(strip_escapes("{\"a\":1}"), "{\"a\":1}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pty_round_trip() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (mut process, mut stdin, stdout) = PtyProcess::spawn(PtyCommand {
+            program: Path::new("/bin/sh"),
+            args: vec![
+                "-c".to_owned(),
+                r#"printf '\033[2Kready\n'; read line; echo "got $line""#.to_owned(),
+            ],
+            inherit_env: None,
+            remove_env: &[],
+            env: Vec::new(),
+            cwd: Path::new("."),
+        })
+        .unwrap();
+        let mut lines = BufReader::new(stdout).lines();
+        let ready = lines.next_line().await.unwrap().unwrap();
+        assert_eq!(strip_escapes(&ready), "ready");
+
+        stdin.write_all(b"{\"a\":1}\n").await.unwrap();
+        let reply = lines.next_line().await.unwrap().unwrap();
+        assert_eq!(reply, "got {\"a\":1}");
+        process.wait().await.unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+//! Spending limits shared by every client in the process.
+//!
+//! [`Options::max_budget_usd`](crate::Options::max_budget_usd) caps a single
+//! session. A [`QuotaBucket`] caps the combined spend of every client that
+//! draws from it, such as all sessions of one tenant or one
+//! [`PermissionProfile`](crate::PermissionProfile). Buckets are registered
+//! by name for the lifetime of the process, so clients created anywhere get
+//! the same bucket from [`QuotaBucket::named`].
+//!
+//! Each client charges its buckets with the cost of every completed turn.
+//! Once a bucket's spend reaches its limit, sending another prompt on any
+//! client drawing from it fails with [`Error::QuotaExceeded`]; the turn that
+//! crossed the limit is not interrupted.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::quota::QuotaBucket;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let acme = QuotaBucket::named("tenant:acme", 25.0);
+//! let client = Client::new(Options::new().quota(acme.clone())).await?;
+//! // ...
+//! println!("acme has ${:.2} left", acme.remaining_usd());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+use crate::error::Error;
+
+static BUCKETS: OnceLock<Mutex<HashMap<String, QuotaBucket>>> = OnceLock::new();
+
+/// A named spending limit in USD, shared by every handle to it.
+#[derive(Debug, Clone)]
+pub struct QuotaBucket {
+    inner: Arc<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    name: String,
+    ledger: Mutex<Ledger>,
+}
+
+#[derive(Debug)]
+struct Ledger {
+    limit_usd: f64,
+    spent_usd: f64,
+}
+
+impl QuotaBucket {
+    /// The process-wide bucket called `name`, created with a limit of
+    /// `limit_usd` if it does not exist yet. An existing bucket keeps its
+    /// spend and has its limit changed to `limit_usd`.
+    pub fn named(name: impl Into<String>, limit_usd: f64) -> Self {
+        let name = name.into();
+        let mut buckets = BUCKETS
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let bucket = buckets.entry(name.clone()).or_insert_with(|| Self {
+            inner: Arc::new(Bucket {
+                name,
+                ledger: Mutex::new(Ledger {
+                    limit_usd,
+                    spent_usd: 0.0,
+                }),
+            }),
+        });
+        bucket.set_limit_usd(limit_usd);
+        bucket.clone()
+    }
+
+    /// The process-wide bucket called `name`, if one has been created.
+    pub fn get(name: &str) -> Option<Self> {
+        BUCKETS
+            .get()?
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    pub fn limit_usd(&self) -> f64 {
+        self.ledger().limit_usd
+    }
+
+    pub fn spent_usd(&self) -> f64 {
+        self.ledger().spent_usd
+    }
+
+    pub fn remaining_usd(&self) -> f64 {
+        let ledger = self.ledger();
+        (ledger.limit_usd - ledger.spent_usd).max(0.0)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        let ledger = self.ledger();
+        ledger.spent_usd >= ledger.limit_usd
+    }
+
+    pub fn set_limit_usd(&self, limit_usd: f64) {
+        self.ledger().limit_usd = limit_usd;
+    }
+
+    /// Forgets the spend so far, such as at the start of a billing period.
+    pub fn reset(&self) {
+        self.ledger().spent_usd = 0.0;
+    }
+
+    /// Fails with [`Error::QuotaExceeded`] if the bucket is exhausted.
+    pub fn check(&self) -> Result<(), Error> {
+        let ledger = self.ledger();
+        if ledger.spent_usd >= ledger.limit_usd {
+            return Err(self.exceeded(&ledger));
+        }
+        Ok(())
+    }
+
+    /// Adds `cost_usd` to the spend, failing with [`Error::QuotaExceeded`]
+    /// if that exhausts the bucket. The cost is recorded either way.
+    pub fn charge(&self, cost_usd: f64) -> Result<(), Error> {
+        let mut ledger = self.ledger();
+        ledger.spent_usd += cost_usd;
+        if ledger.spent_usd >= ledger.limit_usd {
+            return Err(self.exceeded(&ledger));
+        }
+        Ok(())
+    }
+
+    fn exceeded(&self, ledger: &Ledger) -> Error {
+        Error::QuotaExceeded {
+            bucket: self.inner.name.clone(),
+            limit_usd: ledger.limit_usd,
+            spent_usd: ledger.spent_usd,
+        }
+    }
+
+    fn ledger(&self) -> std::sync::MutexGuard<'_, Ledger> {
+        self.inner
+            .ledger
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_is_shared_by_name() {
+        let name = format!("tenant:{}", crate::util::uuid_v7());
+        let first = QuotaBucket::named(&name, 1.0);
+        let second = QuotaBucket::named(&name, 1.0);
+
+        first.charge(0.6).unwrap();
+        assert!(second.check().is_ok());
+        let Err(Error::QuotaExceeded { spent_usd, .. }) = second.charge(0.5) else {
+            panic!("expected the quota to be exceeded");
+        };
+        assert!((spent_usd - 1.1).abs() < 1e-9);
+        assert!(first.check().is_err());
+        assert_eq!(first.remaining_usd(), 0.0);
+
+        QuotaBucket::get(&name).unwrap().set_limit_usd(2.0);
+        assert!(first.check().is_ok());
+        first.reset();
+        assert_eq!(second.spent_usd(), 0.0);
+    }
+}
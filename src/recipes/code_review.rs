@@ -0,0 +1,317 @@
+//! Structured code review of a diff or a directory.
+//!
+//! The review runs in a [`read_only`](crate::Options::read_only) session
+//! with the file-reading tools allowed, so Claude can open surrounding code
+//! for context but cannot change anything. Findings come back through
+//! structured output and are deserialized into [`Finding`]s.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::recipes::{CodeReview, Severity};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let diff = std::process::Command::new("git").arg("diff").output()?.stdout;
+//! let review = CodeReview::diff(String::from_utf8_lossy(&diff))
+//!     .cwd(".")
+//!     .focus("error handling")
+//!     .run()
+//!     .await?;
+//!
+//! for finding in review.at_least(Severity::Medium) {
+//!     println!("{finding}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::Reverse;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::options::Options;
+use crate::response::Responses;
+
+const REVIEW_TOOLS: &[&str] = &["Read", "Grep", "Glob"];
+
+const SYSTEM_PROMPT: &str = "You are a meticulous code reviewer. Report concrete problems: bugs, \
+     security issues, unhandled errors, races, misleading names and missing tests. Do not report \
+     style preferences a formatter would settle. Give every finding the file path relative to \
+     the working directory and, when it applies to specific code, the line number in the new \
+     version of the file. Use the tools to read surrounding code before reporting a finding \
+     you are unsure of.";
+
+/// How serious a finding is, from least to most.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        })
+    }
+}
+
+/// One problem reported by the review.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Finding {
+    /// Path of the file, relative to the working directory.
+    file: String,
+    /// Line in the new version of the file, if the finding is about specific code.
+    line: Option<u32>,
+    severity: Severity,
+    /// One-line summary of the problem.
+    title: String,
+    /// Why it is a problem.
+    detail: String,
+    /// How to fix it, if there is an obvious fix.
+    suggestion: Option<String>,
+}
+
+impl Finding {
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_deref()
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.severity, self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        write!(f, ": {}", self.title)
+    }
+}
+
+/// The shape Claude is asked to return.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ReviewOutput {
+    /// Two or three sentences on the overall state of the change.
+    summary: String,
+    findings: Vec<Finding>,
+}
+
+/// The result of a [`CodeReview`].
+#[derive(Debug, Clone)]
+pub struct Review {
+    summary: String,
+    findings: Vec<Finding>,
+    responses: Responses,
+}
+
+impl Review {
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+
+    /// Findings, most severe first.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Findings of at least `severity`.
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.severity >= severity)
+    }
+
+    /// The most severe finding's severity, or `None` for a clean review.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.first().map(Finding::severity)
+    }
+
+    /// The session's responses, for cost and timing details.
+    pub fn responses(&self) -> &Responses {
+        &self.responses
+    }
+
+    pub fn cost_usd(&self) -> Option<f64> {
+        self.responses
+            .completion()
+            .and_then(|complete| complete.total_cost_usd())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Target {
+    Diff(String),
+    Directory(PathBuf),
+}
+
+/// A configured review, started with [`run`](Self::run).
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct CodeReview {
+    target: Target,
+    focus: Vec<String>,
+    cwd: Option<PathBuf>,
+    options: Options,
+}
+
+impl CodeReview {
+    /// Reviews a unified diff. Set [`cwd`](Self::cwd) to the repository the
+    /// diff applies to so Claude can read the surrounding code.
+    pub fn diff(diff: impl Into<String>) -> Self {
+        Self::new(Target::Diff(diff.into()))
+    }
+
+    /// Reviews the code in a directory.
+    pub fn directory(path: impl AsRef<Path>) -> Self {
+        Self::new(Target::Directory(path.as_ref().to_path_buf()))
+    }
+
+    fn new(target: Target) -> Self {
+        Self {
+            target,
+            focus: Vec::new(),
+            cwd: None,
+            options: Options::new(),
+        }
+    }
+
+    /// Asks the reviewer to pay particular attention to `area`, such as
+    /// `"concurrency"` or `"SQL injection"`.
+    pub fn focus(mut self, area: impl Into<String>) -> Self {
+        self.focus.push(area.into());
+        self
+    }
+
+    /// The working directory of the review session. Defaults to the
+    /// reviewed directory, or the process's directory for diffs.
+    pub fn cwd(mut self, path: impl AsRef<Path>) -> Self {
+        self.cwd = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Base options for the review session, such as the model or budget.
+    /// The recipe adds its own tools, output schema and read-only guard.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub async fn run(self) -> Result<Review, Error> {
+        let prompt = self.prompt();
+        let cwd = match (&self.cwd, &self.target) {
+            (Some(cwd), _) => Some(cwd.clone()),
+            (None, Target::Directory(dir)) => Some(dir.clone()),
+            (None, Target::Diff(_)) => None,
+        };
+
+        let mut options = REVIEW_TOOLS
+            .iter()
+            .fold(self.options, |options, tool| options.allowed_tool(*tool))
+            .read_only()
+            .with_json_schema::<ReviewOutput>()
+            .append_system_prompt(SYSTEM_PROMPT);
+        if let Some(cwd) = cwd {
+            options = options.cwd(cwd);
+        }
+
+        let client = Client::new(options).await?;
+        let mut conversation = client.conversation();
+        let responses = conversation.turn(prompt).send().await?;
+        let output = responses
+            .completion()
+            .and_then(|complete| client.structured_output(complete))
+            .ok_or_else(|| Error::ProtocolError("no structured output in response".to_owned()))?;
+        let output = serde_json::from_value::<ReviewOutput>(output)?;
+
+        let mut findings = output.findings;
+        findings.sort_by_key(|finding| Reverse(finding.severity));
+        Ok(Review {
+            summary: output.summary,
+            findings,
+            responses,
+        })
+    }
+
+    fn prompt(&self) -> String {
+        let mut prompt = match &self.target {
+            Target::Diff(diff) => format!(
+                "Review this change. Only report problems the change introduces or \
+                 touches.\n\n```diff\n{}\n```\n",
+                diff.trim_end()
+            ),
+            Target::Directory(dir) => format!(
+                "Review the code in `{}`. Start by listing the files, then read the \
+                 ones most likely to contain problems.\n",
+                dir.display()
+            ),
+        };
+        if !self.focus.is_empty() {
+            prompt.push_str(&format!(
+                "\nPay particular attention to: {}.\n",
+                self.focus.join(", ")
+            ));
+        }
+        prompt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_findings_deserialize_and_display() {
+        let output = serde_json::from_value::<ReviewOutput>(serde_json::json!({
+            "summary": "One bug.",
+            "findings": [{
+                "file": "src/lib.rs",
+                "line": 12,
+                "severity": "high",
+                "title": "Unchecked index",
+                "detail": "Panics on empty input.",
+                "suggestion": null
+            }]
+        }))
+        .unwrap();
+
+        let finding = &output.findings[0];
+        assert_eq!(finding.severity(), Severity::High);
+        assert_eq!(finding.to_string(), "[high] src/lib.rs:12: Unchecked index");
+        assert!(Severity::Critical > Severity::Info);
+    }
+}
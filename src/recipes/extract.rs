@@ -0,0 +1,241 @@
+//! Structured extraction over many inputs at once.
+//!
+//! Each item gets its own one-shot session with a JSON schema for `T`, and
+//! at most [`concurrency`](ExtractAll::concurrency) sessions run at a time.
+//! Results come back in input order, with failures reported per item rather
+//! than failing the batch.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::recipes::extract_all;
+//! use clauders::{Model, Options};
+//! use schemars::JsonSchema;
+//! use serde::Deserialize;
+//!
+//! #[derive(Debug, Deserialize, JsonSchema)]
+//! struct Sentiment {
+//!     positive: bool,
+//!     confidence: f64,
+//! }
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let reviews = vec!["Loved it", "Never again"];
+//! let extraction = extract_all::<Sentiment, _, _>(
+//!     reviews,
+//!     |review| format!("Classify the sentiment of this review: {review}"),
+//!     Options::new().model(Model::Haiku).disable_tools(),
+//! )
+//! .await;
+//!
+//! println!("cost: ${:.4}", extraction.cost_usd());
+//! for result in extraction.results() {
+//!     println!("{result:?}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use futures::StreamExt;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::options::Options;
+use crate::proto::ModelUsage;
+use crate::response::Responses;
+
+/// Per-item results of an extraction, in input order.
+#[derive(Debug)]
+pub struct Extraction<T> {
+    results: Vec<Result<T, Error>>,
+    cost_usd: f64,
+    usage_by_model: HashMap<String, ModelUsage>,
+}
+
+impl<T> Extraction<T> {
+    pub fn results(&self) -> &[Result<T, Error>] {
+        &self.results
+    }
+
+    pub fn into_results(self) -> Vec<Result<T, Error>> {
+        self.results
+    }
+
+    /// Total cost in USD of the sessions that reported one, including those
+    /// whose output could not be extracted.
+    pub fn cost_usd(&self) -> f64 {
+        self.cost_usd
+    }
+
+    /// Per-model usage summed over every session, failed ones included.
+    pub fn usage_by_model(&self) -> &HashMap<String, ModelUsage> {
+        &self.usage_by_model
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|result| result.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+}
+
+/// A configured extraction, started with [`run`](Self::run).
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct ExtractAll {
+    options: Options,
+    concurrency: usize,
+}
+
+impl ExtractAll {
+    pub const DEFAULT_CONCURRENCY: usize = 4;
+
+    /// Runs each item with `options`, to which the recipe adds the schema.
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            concurrency: Self::DEFAULT_CONCURRENCY,
+        }
+    }
+
+    /// How many sessions may run at once. Values below 1 are treated as 1.
+    pub fn concurrency(mut self, sessions: usize) -> Self {
+        self.concurrency = sessions.max(1);
+        self
+    }
+
+    pub async fn run<T, I, F>(self, items: I, prompt_fn: F) -> Extraction<T>
+    where
+        T: DeserializeOwned + JsonSchema,
+        I: IntoIterator,
+        F: Fn(I::Item) -> String,
+    {
+        let options = self.options.with_json_schema::<T>();
+        let outcomes = futures::stream::iter(items)
+            .map(|item| extract_one::<T>(options.clone(), prompt_fn(item)))
+            .buffered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut cost_usd = 0.0;
+        let mut usage_by_model = HashMap::<String, ModelUsage>::new();
+        let results = outcomes
+            .into_iter()
+            .map(|(result, responses)| {
+                cost_usd += responses
+                    .completion()
+                    .and_then(|complete| complete.total_cost_usd())
+                    .unwrap_or_default();
+                for (model, usage) in responses.usage_by_model() {
+                    *usage_by_model.entry(model).or_default() += &usage;
+                }
+                result
+            })
+            .collect();
+        Extraction {
+            results,
+            cost_usd,
+            usage_by_model,
+        }
+    }
+}
+
+/// Runs one item, keeping whatever the session received even when
+/// extraction fails so its cost still counts.
+async fn extract_one<T: DeserializeOwned>(
+    options: Options,
+    prompt: String,
+) -> (Result<T, Error>, Responses) {
+    let wrapper = options.json_schema_wrapper();
+    let client = match Client::new(options).await {
+        Ok(client) => client,
+        Err(error) => return (Err(error), Responses::default()),
+    };
+    if let Err(error) = client.query(&prompt).await {
+        return (Err(error), Responses::default());
+    }
+
+    let mut received = Vec::new();
+    let mut failure = None;
+    let mut stream = std::pin::pin!(client.receive());
+    while let Some(result) = stream.next().await {
+        match result {
+            Ok(response) => received.push(response),
+            Err(error) => {
+                failure = Some(error);
+                break;
+            }
+        }
+    }
+    let responses = Responses::from(received);
+    let result = match failure {
+        Some(error) => Err(error),
+        None => client.parse_structured_output(&responses, wrapper),
+    };
+    (result, responses)
+}
+
+/// Runs [`ExtractAll`] with the default concurrency.
+pub async fn extract_all<T, I, F>(items: I, prompt_fn: F, options: Options) -> Extraction<T>
+where
+    T: DeserializeOwned + JsonSchema,
+    I: IntoIterator,
+    F: Fn(I::Item) -> String,
+{
+    ExtractAll::new(options).run(items, prompt_fn).await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::fake_cli::FakeCli;
+
+    #[derive(Debug, Deserialize, JsonSchema)]
+    struct Count {
+        n: i64,
+    }
+
+    /// A result costing `cost`, all of it spent on `claude-haiku`.
+    fn result_with(cost: f64, extra: &str) -> String {
+        format!(
+            r#"{{"type":"result","subtype":"success","is_error":false,"duration_ms":1,"duration_api_ms":1,"num_turns":1,"result":"no idea","session_id":"s1","total_cost_usd":{cost},"usage":{{"input_tokens":1,"output_tokens":1}},"modelUsage":{{"claude-haiku":{{"inputTokens":1,"outputTokens":2,"costUSD":{cost}}}}}{extra}}}"#
+        )
+    }
+
+    #[tokio::test]
+    async fn test_failed_items_count_towards_cost_and_usage() {
+        // Only the "good" item gets structured output back.
+        let cli = FakeCli::initialized(&format!(
+            r#"next
+case "$line" in
+*good*) emit '{}' ;;
+*) emit '{}' ;;
+esac
+drain"#,
+            result_with(0.01, r#","structured_output":{"n":1}"#),
+            result_with(0.02, ""),
+        ));
+        let options = Options::new().cli_path(cli.path()).cwd(cli.dir());
+
+        let extraction = ExtractAll::new(options)
+            .run::<Count, _, _>(["good", "bad"], str::to_owned)
+            .await;
+
+        assert_eq!(extraction.succeeded(), 1);
+        assert_eq!(extraction.failed(), 1);
+        assert_eq!(extraction.results()[0].as_ref().unwrap().n, 1);
+        assert!(extraction.results()[1].is_err());
+        assert!((extraction.cost_usd() - 0.03).abs() < 1e-9);
+        let usage = &extraction.usage_by_model()["claude-haiku"];
+        assert_eq!(usage.output_tokens(), 4);
+        assert!((usage.cost_usd() - 0.03).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,411 @@
+//! Iterating on a workspace until its test command passes.
+//!
+//! The session gets a `run_tests` tool that runs the caller's test command
+//! in the workspace, and Bash is disallowed so every test run goes through
+//! it and is counted. A Stop hook sends Claude back to work while the last
+//! run failed and test runs remain, and the recipe runs the command once
+//! more after the session ends so the report reflects the final state of
+//! the files.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::recipes::FixTests;
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let report = FixTests::new("./workspace", "cargo test --quiet")
+//!     .max_iterations(8)
+//!     .max_budget_usd(2.0)
+//!     .on_iteration(|run| println!("run {}: passed={}", run.number(), run.passed()))
+//!     .run()
+//!     .await?;
+//!
+//! println!("fixed: {}", report.passed());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::hooks::StopOutput;
+use crate::mcp_server::McpServer;
+use crate::options::Options;
+use crate::proto::PermissionMode;
+use crate::response::Responses;
+use crate::tool::{Tool, ToolError};
+
+const SERVER_NAME: &str = "fix_tests";
+
+const STOP_HOOK: &str = "fix_tests";
+
+/// How much of the end of the test output is kept for each run.
+const OUTPUT_TAIL_BYTES: usize = 16 * 1024;
+
+const EDIT_TOOLS: &[&str] = &["Read", "Glob", "Grep", "Edit", "MultiEdit", "Write"];
+
+/// One run of the test command.
+#[derive(Debug, Clone)]
+pub struct Iteration {
+    number: usize,
+    exit_code: Option<i32>,
+    output: String,
+    duration: Duration,
+}
+
+impl Iteration {
+    /// The run's position, starting at 1. The verification run after the
+    /// session comes last.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    pub fn passed(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// The exit code, or `None` if the command was killed or timed out.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// The end of the combined stdout and stderr.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// The outcome of a [`FixTests`] run.
+#[derive(Debug, Clone)]
+pub struct FixReport {
+    iterations: Vec<Iteration>,
+    responses: Responses,
+}
+
+impl FixReport {
+    /// Whether the test command passed after the session ended.
+    pub fn passed(&self) -> bool {
+        self.iterations.last().is_some_and(Iteration::passed)
+    }
+
+    /// Every test run, ending with the verification run.
+    pub fn iterations(&self) -> &[Iteration] {
+        &self.iterations
+    }
+
+    /// The session's responses, including Claude's closing summary.
+    pub fn responses(&self) -> &Responses {
+        &self.responses
+    }
+
+    pub fn cost_usd(&self) -> Option<f64> {
+        self.responses
+            .completion()
+            .and_then(|complete| complete.total_cost_usd())
+    }
+}
+
+type IterationCallback = Arc<dyn Fn(&Iteration) + Send + Sync>;
+
+/// A configured test-fixing session, started with [`run`](Self::run).
+#[derive(Clone)]
+#[must_use]
+pub struct FixTests {
+    workspace: PathBuf,
+    test_cmd: String,
+    max_iterations: usize,
+    test_timeout: Duration,
+    options: Options,
+    on_iteration: Option<IterationCallback>,
+}
+
+impl FixTests {
+    pub const DEFAULT_MAX_ITERATIONS: usize = 10;
+    pub const DEFAULT_TEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+    /// Fixes the code in `workspace` until `test_cmd`, run with `sh -c`,
+    /// exits successfully.
+    pub fn new(workspace: impl AsRef<Path>, test_cmd: impl Into<String>) -> Self {
+        Self {
+            workspace: workspace.as_ref().to_path_buf(),
+            test_cmd: test_cmd.into(),
+            max_iterations: Self::DEFAULT_MAX_ITERATIONS,
+            test_timeout: Self::DEFAULT_TEST_TIMEOUT,
+            options: Options::new(),
+            on_iteration: None,
+        }
+    }
+
+    /// Limits how many times Claude may run the tests.
+    pub fn max_iterations(mut self, iterations: usize) -> Self {
+        self.max_iterations = iterations;
+        self
+    }
+
+    /// Stops the session once it has cost `budget` USD.
+    pub fn max_budget_usd(mut self, budget: f64) -> Self {
+        self.options = self.options.max_budget_usd(budget);
+        self
+    }
+
+    /// Kills a test run that takes longer than `timeout`.
+    pub fn test_timeout(mut self, timeout: Duration) -> Self {
+        self.test_timeout = timeout;
+        self
+    }
+
+    /// Base options for the session, such as the model. The recipe sets the
+    /// working directory, tools, permission mode and a Stop hook.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Called after every test run, including the verification run.
+    pub fn on_iteration<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Iteration) + Send + Sync + 'static,
+    {
+        self.on_iteration = Some(Arc::new(f));
+        self
+    }
+
+    pub async fn run(self) -> Result<FixReport, Error> {
+        let runner = Arc::new(Runner {
+            workspace: self.workspace.clone(),
+            test_cmd: self.test_cmd.clone(),
+            timeout: self.test_timeout,
+            max_iterations: self.max_iterations,
+            on_iteration: self.on_iteration.clone(),
+            state: Mutex::new(RunnerState::default()),
+        });
+
+        let tool_runner = Arc::clone(&runner);
+        let tool = Tool::unstructured::<RunTestsInput, _, _>(
+            "run_tests",
+            format!(
+                "Run the test command `{}` in the workspace and return its exit code and output.",
+                self.test_cmd
+            ),
+            move |_| {
+                let runner = Arc::clone(&tool_runner);
+                async move { runner.run_for_tool().await }
+            },
+        );
+        let server = Arc::new(McpServer::new(SERVER_NAME, vec![tool]));
+
+        let mut options = EDIT_TOOLS
+            .iter()
+            .fold(self.options, |options, tool| options.allowed_tool(*tool))
+            .disallowed_tool("Bash")
+            .permission_mode(PermissionMode::AcceptEdits)
+            .cwd(&self.workspace)
+            .with_mcp_server(SERVER_NAME, server);
+        let hook_runner = Arc::clone(&runner);
+        options.hooks_mut().add_stop_named(STOP_HOOK, move |_| {
+            let output = hook_runner.stop_decision();
+            async move { output }
+        });
+
+        let client = Client::new(options).await?;
+        let mut conversation = client.conversation();
+        let responses = conversation
+            .turn(format!(
+                "The test command `{}` is failing in this workspace. Call the run_tests tool to \
+                 see the failures, fix the code, and call run_tests again after each change \
+                 until it passes. You may run the tests at most {} times. Fix the code under \
+                 test rather than weakening or deleting tests. When you finish, summarise what \
+                 you changed.",
+                self.test_cmd, self.max_iterations
+            ))
+            .send()
+            .await?;
+
+        runner.run().await?;
+        let iterations = runner.state.lock().expect("runner state").runs.clone();
+        Ok(FixReport {
+            iterations,
+            responses,
+        })
+    }
+}
+
+impl fmt::Debug for FixTests {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixTests")
+            .field("workspace", &self.workspace)
+            .field("test_cmd", &self.test_cmd)
+            .field("max_iterations", &self.max_iterations)
+            .field("test_timeout", &self.test_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Runs [`FixTests`] with the default limits.
+pub async fn fix_tests(
+    workspace: impl AsRef<Path>,
+    test_cmd: impl Into<String>,
+) -> Result<FixReport, Error> {
+    FixTests::new(workspace, test_cmd).run().await
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct RunTestsInput {}
+
+#[derive(Default)]
+struct RunnerState {
+    runs: Vec<Iteration>,
+    /// How many runs there had been when the Stop hook last sent Claude back.
+    runs_at_last_block: Option<usize>,
+}
+
+struct Runner {
+    workspace: PathBuf,
+    test_cmd: String,
+    timeout: Duration,
+    max_iterations: usize,
+    on_iteration: Option<IterationCallback>,
+    state: Mutex<RunnerState>,
+}
+
+impl Runner {
+    async fn run_for_tool(&self) -> Result<serde_json::Value, ToolError> {
+        if self.state.lock().expect("runner state").runs.len() >= self.max_iterations {
+            return Ok(Tool::error_result(&format!(
+                "All {} test runs have been used. Stop and summarise what is still failing.",
+                self.max_iterations
+            )));
+        }
+
+        let run = self
+            .run()
+            .await
+            .map_err(|e| ToolError::execution_failed(e.to_string()))?;
+        let text = match run.exit_code {
+            Some(code) => format!("exit code: {code}\n{}", run.output),
+            None => format!("the test command did not finish\n{}", run.output),
+        };
+        Ok(if run.passed() {
+            Tool::text_result(&text)
+        } else {
+            Tool::error_result(&text)
+        })
+    }
+
+    async fn run(&self) -> Result<Iteration, Error> {
+        let started = Instant::now();
+        let command = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(&self.test_cmd)
+            .current_dir(&self.workspace)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        let (exit_code, output) = match tokio::time::timeout(self.timeout, command).await {
+            Ok(output) => {
+                let output = output?;
+                let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                (output.status.code(), tail(&text, OUTPUT_TAIL_BYTES))
+            }
+            Err(_) => (
+                None,
+                format!("timed out after {}s", self.timeout.as_secs_f64()),
+            ),
+        };
+
+        let run = {
+            let mut state = self.state.lock().expect("runner state");
+            let run = Iteration {
+                number: state.runs.len() + 1,
+                exit_code,
+                output,
+                duration: started.elapsed(),
+            };
+            state.runs.push(run.clone());
+            run
+        };
+        tracing::info!(
+            iteration = run.number,
+            passed = run.passed(),
+            "test command finished"
+        );
+        if let Some(callback) = &self.on_iteration {
+            callback(&run);
+        }
+        Ok(run)
+    }
+
+    /// Sends Claude back to work while the tests fail and runs remain, but
+    /// only once per new run so a session that stops testing cannot loop.
+    fn stop_decision(&self) -> StopOutput {
+        let mut state = self.state.lock().expect("runner state");
+        let runs = state.runs.len();
+        let failing = state.runs.last().is_none_or(|run| !run.passed());
+        if !failing || runs >= self.max_iterations || state.runs_at_last_block == Some(runs) {
+            return StopOutput::pass();
+        }
+        state.runs_at_last_block = Some(runs);
+        StopOutput::block(if runs == 0 {
+            "The tests have not been run yet. Call run_tests.".to_owned()
+        } else {
+            format!(
+                "The last test run failed and {} runs remain. Keep fixing and call run_tests.",
+                self.max_iterations - runs
+            )
+        })
+    }
+}
+
+/// The last `max` bytes of `text`, cut at a character boundary.
+fn tail(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_owned();
+    }
+    let mut start = text.len() - max;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("[truncated]\n{}", &text[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stop_hook_blocks_once_per_failing_run() {
+        let runner = Runner {
+            workspace: std::env::temp_dir(),
+            test_cmd: "exit 1".to_owned(),
+            timeout: Duration::from_secs(10),
+            max_iterations: 2,
+            on_iteration: None,
+            state: Mutex::new(RunnerState::default()),
+        };
+
+        assert!(runner.stop_decision().decision().is_some());
+        assert!(runner.stop_decision().decision().is_none());
+
+        let run = runner.run().await.unwrap();
+        assert_eq!((run.number(), run.exit_code()), (1, Some(1)));
+        assert!(runner.stop_decision().decision().is_some());
+
+        runner.run().await.unwrap();
+        assert!(runner.stop_decision().decision().is_none());
+        let exhausted = runner.run_for_tool().await.unwrap();
+        assert_eq!(exhausted[0]["is_error"], true);
+    }
+}
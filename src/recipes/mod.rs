@@ -0,0 +1,16 @@
+//! Ready-made workflows built from the crate's lower-level pieces.
+//!
+//! Each recipe configures its own [`Client`](crate::Client) from a base
+//! [`Options`](crate::Options), so callers keep control of the model,
+//! working directory and budget while the recipe supplies prompts, tools
+//! and output schemas.
+
+pub mod code_review;
+pub mod extract;
+pub mod fix_tests;
+pub mod tabular;
+
+pub use code_review::{CodeReview, Finding, Review, Severity};
+pub use extract::{ExtractAll, Extraction, extract_all};
+pub use fix_tests::{FixReport, FixTests, Iteration, fix_tests};
+pub use tabular::{Column, ColumnType, Table, Tabular, tabular};
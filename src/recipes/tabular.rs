@@ -0,0 +1,327 @@
+//! Extracting a table of rows with known columns.
+//!
+//! The columns are described at runtime, so there is no type to derive a
+//! schema from; the recipe builds one asking for an array of rows with
+//! exactly those columns. The [`Table`] it returns holds each row as a map
+//! from column name to value and can be written out as CSV. When the
+//! columns are known at compile time, `with_json_schema::<Vec<T>>()` gives
+//! typed rows instead.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::recipes::{Column, tabular};
+//! use clauders::{Model, Options};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let table = tabular(
+//!     "List every dependency in Cargo.toml",
+//!     [
+//!         Column::string("name", "The crate name"),
+//!         Column::string("version", "The version requirement"),
+//!         Column::boolean("optional", "Whether it is optional"),
+//!     ],
+//!     Options::new().model(Model::Haiku),
+//! )
+//! .await?;
+//!
+//! table.write_csv(std::io::stdout())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::io;
+
+use serde_json::{Map, Value, json};
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::options::Options;
+
+/// The property of the structured output holding the rows.
+const ROWS: &str = "rows";
+
+/// The JSON type of a column's values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+impl ColumnType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+        }
+    }
+}
+
+/// A column of the table, described to the model by its name and
+/// description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    name: String,
+    description: String,
+    kind: ColumnType,
+    nullable: bool,
+}
+
+impl Column {
+    pub fn new(name: impl Into<String>, description: impl Into<String>, kind: ColumnType) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            kind,
+            nullable: false,
+        }
+    }
+
+    pub fn string(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self::new(name, description, ColumnType::String)
+    }
+
+    pub fn integer(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self::new(name, description, ColumnType::Integer)
+    }
+
+    pub fn number(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self::new(name, description, ColumnType::Number)
+    }
+
+    pub fn boolean(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self::new(name, description, ColumnType::Boolean)
+    }
+
+    /// Lets the model leave the column empty with `null` when a row has no
+    /// value for it.
+    #[must_use]
+    pub fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> ColumnType {
+        self.kind
+    }
+
+    fn schema(&self) -> Value {
+        let kind = if self.nullable {
+            json!([self.kind.as_str(), "null"])
+        } else {
+            json!(self.kind.as_str())
+        };
+        json!({ "type": kind, "description": self.description })
+    }
+}
+
+/// The schema asking for an object whose `rows` are objects with exactly
+/// `columns`.
+fn schema(columns: &[Column]) -> Value {
+    let properties = columns
+        .iter()
+        .map(|column| (column.name.clone(), column.schema()))
+        .collect::<Map<_, _>>();
+    let names = columns.iter().map(Column::name).collect::<Vec<_>>();
+    json!({
+        "type": "object",
+        "properties": {
+            ROWS: {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": properties,
+                    "required": names,
+                    "additionalProperties": false,
+                },
+            },
+        },
+        "required": [ROWS],
+    })
+}
+
+/// Extracted rows, with the columns in the order they were given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    columns: Vec<String>,
+    rows: Vec<HashMap<String, Value>>,
+    cost_usd: f64,
+}
+
+impl Table {
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &[HashMap<String, Value>] {
+        &self.rows
+    }
+
+    pub fn into_rows(self) -> Vec<HashMap<String, Value>> {
+        self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The session's cost in USD, if it reported one.
+    pub fn cost_usd(&self) -> f64 {
+        self.cost_usd
+    }
+
+    /// Each row as text cells in column order, ready for a CSV writer such
+    /// as `csv::Writer::write_record`. Strings are written as is, missing
+    /// values and `null` as empty cells, and anything else as JSON.
+    pub fn records(&self) -> impl Iterator<Item = Vec<String>> + '_ {
+        self.rows.iter().map(|row| {
+            self.columns
+                .iter()
+                .map(|column| match row.get(column) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                })
+                .collect()
+        })
+    }
+
+    /// Writes the column names and then every row as RFC 4180 CSV.
+    pub fn write_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        write_record(&mut writer, &self.columns)?;
+        for record in self.records() {
+            write_record(&mut writer, &record)?;
+        }
+        writer.flush()
+    }
+}
+
+fn write_record(writer: &mut impl io::Write, cells: &[String]) -> io::Result<()> {
+    let line = cells
+        .iter()
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    write!(writer, "{line}\r\n")
+}
+
+/// A configured table extraction, started with [`run`](Self::run).
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Tabular {
+    options: Options,
+    columns: Vec<Column>,
+}
+
+impl Tabular {
+    /// Runs the extraction with `options`, to which the recipe adds the
+    /// schema.
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn column(mut self, column: Column) -> Self {
+        self.columns.push(column);
+        self
+    }
+
+    pub fn columns(mut self, columns: impl IntoIterator<Item = Column>) -> Self {
+        self.columns.extend(columns);
+        self
+    }
+
+    pub async fn run(self, prompt: &str) -> Result<Table, Error> {
+        let options = self
+            .options
+            .with_raw_json_schema(schema(&self.columns), Some(ROWS));
+        let client = Client::new(options).await?;
+        let (_, responses) = client.query_once(prompt).await?;
+
+        let complete = responses
+            .completion()
+            .ok_or_else(|| Error::ProtocolError("no result in response".to_owned()))?;
+        let rows = client
+            .structured_output(complete)
+            .and_then(|mut output| output.get_mut(ROWS).map(Value::take))
+            .ok_or_else(|| Error::ProtocolError("no structured output in response".to_owned()))?;
+
+        Ok(Table {
+            columns: self.columns.into_iter().map(|c| c.name).collect(),
+            rows: serde_json::from_value(rows)?,
+            cost_usd: complete.total_cost_usd().unwrap_or_default(),
+        })
+    }
+}
+
+/// Runs [`Tabular`] with `columns`.
+pub async fn tabular(
+    prompt: &str,
+    columns: impl IntoIterator<Item = Column>,
+    options: Options,
+) -> Result<Table, Error> {
+    Tabular::new(options).columns(columns).run(prompt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_requires_every_column() {
+        let schema = schema(&[
+            Column::string("name", "The crate name"),
+            Column::integer("downloads", "Total downloads").nullable(),
+        ]);
+        let row = &schema["properties"]["rows"]["items"];
+        assert_eq!(row["required"], json!(["name", "downloads"]));
+        assert_eq!(
+            row["properties"]["downloads"]["type"],
+            json!(["integer", "null"])
+        );
+        assert_eq!(row["additionalProperties"], false);
+    }
+
+    #[test]
+    fn test_write_csv_quotes_cells() {
+        let table = Table {
+            columns: vec!["name".to_owned(), "note".to_owned(), "count".to_owned()],
+            rows: vec![
+                serde_json::from_value(
+                    json!({"name": "serde", "note": "fast, \"zero-copy\"", "count": 3}),
+                )
+                .unwrap(),
+                serde_json::from_value(json!({"name": "tokio", "note": null})).unwrap(),
+            ],
+            cost_usd: 0.0,
+        };
+
+        let mut csv = Vec::new();
+        table.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "name,note,count\r\nserde,\"fast, \"\"zero-copy\"\"\",3\r\ntokio,,\r\n"
+        );
+    }
+}
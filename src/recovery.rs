@@ -0,0 +1,265 @@
+//! Recovery from tools that keep failing.
+//!
+//! Left alone, Claude often retries a failing tool call with small
+//! variations until it gives up. A [`RecoveryPolicy`] set with
+//! [`Conversation::with_recovery`](crate::Conversation::with_recovery)
+//! counts consecutive failures of each tool and, once a tool has failed
+//! [`max_failures`](RecoveryPolicy::max_failures) times in a row, steers
+//! Claude towards another approach, takes the tool away for the rest of the
+//! turn, or aborts the turn.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::response::Response;
+use crate::tool::ToolErrorKind;
+
+/// What a conversation turn does when a tool keeps failing.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum RecoveryAction {
+    /// Tells Claude the tool keeps failing, with its last error, and asks it
+    /// to try a different approach.
+    #[default]
+    Correct,
+    /// Sends a user message of your own while the turn continues.
+    Steer(String),
+    /// Tells Claude not to call the tool again during the turn, and aborts
+    /// the turn if it does.
+    DisableTool,
+    /// Interrupts the turn, which fails with
+    /// [`Error::ToolRecoveryAborted`](crate::Error::ToolRecoveryAborted).
+    Abort,
+}
+
+/// How many consecutive failures of a tool to tolerate, and how to react.
+///
+/// A tool's count resets when a call to it succeeds, and after the policy
+/// acts, so a tool that keeps failing is acted on again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryPolicy {
+    max_failures: u32,
+    action: RecoveryAction,
+    by_kind: Vec<(ToolErrorKind, RecoveryAction)>,
+}
+
+impl RecoveryPolicy {
+    pub const DEFAULT_MAX_FAILURES: u32 = 3;
+
+    pub fn new() -> Self {
+        Self {
+            max_failures: Self::DEFAULT_MAX_FAILURES,
+            action: RecoveryAction::default(),
+            by_kind: Vec::new(),
+        }
+    }
+
+    /// Acts once a tool has failed `failures` times in a row. Values below 1
+    /// are treated as 1.
+    #[must_use]
+    pub fn max_failures(mut self, failures: u32) -> Self {
+        self.max_failures = failures.max(1);
+        self
+    }
+
+    #[must_use]
+    pub fn action(mut self, action: RecoveryAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Takes `action` instead when the latest failure is of `kind`, such as
+    /// aborting on [`ToolErrorKind::PermissionDenied`].
+    #[must_use]
+    pub fn on_kind(mut self, kind: ToolErrorKind, action: RecoveryAction) -> Self {
+        self.by_kind.retain(|(existing, _)| *existing != kind);
+        self.by_kind.push((kind, action));
+        self
+    }
+
+    fn action_for(&self, kind: ToolErrorKind) -> &RecoveryAction {
+        self.by_kind
+            .iter()
+            .find(|(existing, _)| *existing == kind)
+            .map_or(&self.action, |(_, action)| action)
+    }
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the turn should do in response to a tool result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RecoveryStep {
+    Steer(String),
+    Abort { tool: String, failures: u32 },
+}
+
+/// Tracks tool failures within a turn.
+#[derive(Debug)]
+pub(crate) struct RecoveryTracker {
+    policy: RecoveryPolicy,
+    names: HashMap<String, String>,
+    failures: HashMap<String, u32>,
+    disabled: HashSet<String>,
+}
+
+impl RecoveryTracker {
+    pub(crate) fn new(policy: RecoveryPolicy) -> Self {
+        Self {
+            policy,
+            names: HashMap::new(),
+            failures: HashMap::new(),
+            disabled: HashSet::new(),
+        }
+    }
+
+    /// Records a response, returning the step to take if the policy acts.
+    pub(crate) fn observe(&mut self, response: &Response) -> Option<RecoveryStep> {
+        match response {
+            Response::ToolUse(tool_use) => {
+                if self.disabled.contains(tool_use.name()) {
+                    return Some(RecoveryStep::Abort {
+                        tool: tool_use.name().to_owned(),
+                        failures: self.policy.max_failures,
+                    });
+                }
+                self.names
+                    .insert(tool_use.id().to_owned(), tool_use.name().to_owned());
+                None
+            }
+            Response::ToolResult(result) => {
+                let tool = self.names.get(result.tool_use_id())?.clone();
+                let Some(kind) = result.error_kind() else {
+                    self.failures.remove(&tool);
+                    return None;
+                };
+                let failures = self.failures.entry(tool.clone()).or_default();
+                *failures += 1;
+                if *failures < self.policy.max_failures {
+                    return None;
+                }
+                let failures = std::mem::take(failures);
+
+                match self.policy.action_for(kind) {
+                    RecoveryAction::Correct => Some(RecoveryStep::Steer(format!(
+                        "The {tool} tool has failed {failures} times in a row ({kind}). The last \
+                         error was:\n{}\nStop retrying the same call; try a different approach.",
+                        error_text(result.content()),
+                    ))),
+                    RecoveryAction::Steer(message) => Some(RecoveryStep::Steer(message.clone())),
+                    RecoveryAction::DisableTool => {
+                        self.disabled.insert(tool.clone());
+                        Some(RecoveryStep::Steer(format!(
+                            "The {tool} tool has failed {failures} times in a row and is disabled \
+                             for the rest of this task. Do not call it again."
+                        )))
+                    }
+                    RecoveryAction::Abort => Some(RecoveryStep::Abort { tool, failures }),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn error_text(content: Option<&serde_json::Value>) -> String {
+    match content {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|block| block.get("text")?.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::proto::message::Message;
+
+    fn responses(message: serde_json::Value) -> Vec<Response> {
+        Response::from_message(&serde_json::from_value::<Message>(message).unwrap())
+    }
+
+    fn call(id: &str, name: &str) -> Response {
+        responses(json!({
+            "type": "assistant",
+            "message": {
+                "model": "claude-sonnet-4-5",
+                "content": [{"type": "tool_use", "id": id, "name": name, "input": {}}]
+            }
+        }))
+        .remove(0)
+    }
+
+    fn result(id: &str, error: Option<&str>) -> Response {
+        responses(json!({
+            "type": "user",
+            "message": {
+                "content": [{
+                    "type": "tool_result",
+                    "tool_use_id": id,
+                    "content": error.unwrap_or("ok"),
+                    "is_error": error.is_some()
+                }]
+            }
+        }))
+        .remove(0)
+    }
+
+    #[test]
+    fn test_recovery_counts_consecutive_failures() {
+        let mut tracker = RecoveryTracker::new(
+            RecoveryPolicy::new()
+                .max_failures(2)
+                .on_kind(ToolErrorKind::PermissionDenied, RecoveryAction::Abort),
+        );
+        let mut run = |id: &str, name: &str, error: Option<&str>| {
+            assert_eq!(tracker.observe(&call(id, name)), None);
+            tracker.observe(&result(id, error))
+        };
+
+        assert_eq!(run("1", "Bash", Some("exit code 1")), None);
+        assert_eq!(run("2", "Bash", None), None);
+        assert_eq!(run("3", "Bash", Some("exit code 1")), None);
+        let Some(RecoveryStep::Steer(message)) = run("4", "Bash", Some("exit code 2")) else {
+            panic!("expected a correction");
+        };
+        assert!(message.contains("exit code 2"));
+
+        assert_eq!(run("5", "Read", Some("Permission denied")), None);
+        assert_eq!(
+            run("6", "Read", Some("Permission denied")),
+            Some(RecoveryStep::Abort {
+                tool: "Read".to_owned(),
+                failures: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_recovery_disables_tool() {
+        let mut tracker =
+            RecoveryTracker::new(RecoveryPolicy::new().action(RecoveryAction::DisableTool));
+        for id in ["1", "2"] {
+            tracker.observe(&call(id, "WebFetch"));
+            assert_eq!(tracker.observe(&result(id, Some("timed out"))), None);
+        }
+        tracker.observe(&call("3", "WebFetch"));
+        assert!(matches!(
+            tracker.observe(&result("3", Some("timed out"))),
+            Some(RecoveryStep::Steer(_))
+        ));
+        assert!(matches!(
+            tracker.observe(&call("4", "WebFetch")),
+            Some(RecoveryStep::Abort { .. })
+        ));
+    }
+}
@@ -0,0 +1,261 @@
+//! ANSI rendering of streamed Markdown for terminal frontends.
+//!
+//! [`MarkdownRenderer`] takes text as it streams in, for instance from
+//! [`TurnBuilder::on_text`](crate::TurnBuilder::on_text), and returns styled
+//! output one complete line at a time. The start of a line is held back until
+//! its end arrives, since whether it is a heading, list item or fence is only
+//! known once it is whole.
+//!
+//! ```no_run
+//! use clauders::render::MarkdownRenderer;
+//!
+//! let mut renderer = MarkdownRenderer::new();
+//! for chunk in ["# Plan\n- read the `config", "`\n- fix it\n"] {
+//!     print!("{}", renderer.push(chunk));
+//! }
+//! print!("{}", renderer.finish());
+//! ```
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const CYAN: &str = "\x1b[36m";
+const YELLOW: &str = "\x1b[33m";
+const MAGENTA: &str = "\x1b[35m";
+
+/// Number of spaces each level of list nesting is indented by.
+const LIST_INDENT: usize = 2;
+
+/// Converts streamed Markdown into ANSI-styled terminal output.
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownRenderer {
+    pending: String,
+    fence: Option<String>,
+}
+
+impl MarkdownRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the renderer is inside a code fence.
+    pub fn in_code_block(&self) -> bool {
+        self.fence.is_some()
+    }
+
+    /// Adds streamed text, returning the rendering of every line it completes.
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        let Some(end) = self.pending.rfind('\n') else {
+            return String::new();
+        };
+        let complete = self.pending[..=end].to_owned();
+        self.pending.drain(..=end);
+
+        let mut output = String::new();
+        for line in complete.split_inclusive('\n') {
+            self.render_line(line.trim_end_matches(['\n', '\r']), &mut output);
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Renders any unterminated last line and resets the renderer.
+    pub fn finish(&mut self) -> String {
+        let mut output = String::new();
+        let line = std::mem::take(&mut self.pending);
+        if !line.is_empty() {
+            self.render_line(&line, &mut output);
+        }
+        self.fence = None;
+        output
+    }
+
+    fn render_line(&mut self, line: &str, output: &mut String) {
+        let trimmed = line.trim_start();
+
+        if let Some(fence) = &self.fence {
+            if trimmed.starts_with(fence.as_str()) && trimmed.trim_end() == fence {
+                self.fence = None;
+                styled(output, DIM, line);
+            } else {
+                output.push_str("  ");
+                styled(output, YELLOW, line);
+            }
+            return;
+        }
+
+        if let Some(fence) = fence_marker(trimmed) {
+            let language = trimmed[fence.len()..].trim();
+            self.fence = Some(fence.to_owned());
+            styled(output, DIM, fence);
+            if !language.is_empty() {
+                output.push(' ');
+                styled(output, &format!("{DIM}{ITALIC}"), language);
+            }
+            return;
+        }
+
+        if let Some((level, title)) = heading(trimmed) {
+            let style = if level == 1 {
+                format!("{BOLD}{MAGENTA}")
+            } else {
+                BOLD.to_owned()
+            };
+            styled(output, &style, title);
+            return;
+        }
+
+        if let Some(quote) = trimmed.strip_prefix('>') {
+            styled(output, DIM, "│ ");
+            styled(output, ITALIC, quote.trim_start());
+            return;
+        }
+
+        if is_rule(trimmed) {
+            styled(output, DIM, &"─".repeat(40));
+            return;
+        }
+
+        if let Some((marker, item)) = list_item(trimmed) {
+            let depth = (line.len() - trimmed.len()) / LIST_INDENT;
+            output.push_str(&" ".repeat(LIST_INDENT * (depth + 1)));
+            styled(output, CYAN, &marker);
+            output.push(' ');
+            render_inline(item, output);
+            return;
+        }
+
+        render_inline(line, output);
+    }
+}
+
+fn styled(output: &mut String, style: &str, text: &str) {
+    output.push_str(style);
+    output.push_str(text);
+    output.push_str(RESET);
+}
+
+fn fence_marker(line: &str) -> Option<&str> {
+    ["```", "~~~"]
+        .into_iter()
+        .find(|fence| line.starts_with(fence))
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    line[level..]
+        .strip_prefix(' ')
+        .map(|title| (level, title.trim()))
+}
+
+fn is_rule(line: &str) -> bool {
+    let mut marks = line.chars().filter(|c| !c.is_whitespace());
+    match marks.next() {
+        Some(mark @ ('-' | '*' | '_')) => marks.clone().count() >= 2 && marks.all(|c| c == mark),
+        _ => false,
+    }
+}
+
+/// Splits a list item into the marker to display and the item's text.
+fn list_item(line: &str) -> Option<(String, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(bullet) {
+            return Some(("•".to_owned(), item));
+        }
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    let rest = &line[digits..];
+    let item = rest
+        .strip_prefix(". ")
+        .or_else(|| rest.strip_prefix(") "))?;
+    Some((line[..digits + 1].to_owned(), item))
+}
+
+/// Styles `code` spans and `**bold**` runs, leaving unmatched markers as-is.
+fn render_inline(text: &str, output: &mut String) {
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (marker, delimiter, style) = match (rest.find('`'), rest.find("**")) {
+            (Some(code), Some(bold)) if bold < code => (bold, "**", BOLD),
+            (Some(code), _) => (code, "`", CYAN),
+            (None, Some(bold)) => (bold, "**", BOLD),
+            (None, None) => break,
+        };
+        let body = &rest[marker + delimiter.len()..];
+        let Some(end) = body.find(delimiter) else {
+            break;
+        };
+        output.push_str(&rest[..marker]);
+        styled(output, style, &body[..end]);
+        rest = &body[end + delimiter.len()..];
+    }
+    output.push_str(rest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(chunks: &[&str]) -> String {
+        let mut renderer = MarkdownRenderer::new();
+        let mut output = chunks.iter().map(|c| renderer.push(c)).collect::<String>();
+        output.push_str(&renderer.finish());
+        output
+    }
+
+    #[test]
+    fn test_lines_are_rendered_once_complete() {
+        let mut renderer = MarkdownRenderer::new();
+        assert_eq!(renderer.push("# Ti"), "");
+        assert_eq!(
+            renderer.push("tle\nplain"),
+            format!("{BOLD}{MAGENTA}Title{RESET}\n")
+        );
+        assert_eq!(renderer.finish(), "plain");
+        assert_eq!(renderer.finish(), "");
+    }
+
+    #[test]
+    fn test_code_fences_and_lists() {
+        let output = render(&[
+            "```rust\nlet x",
+            " = 1;\n```\n- one `a`\n  - two\n",
+            "3. three\n",
+        ]);
+        assert_eq!(
+            output,
+            format!(
+                "{DIM}```{RESET} {DIM}{ITALIC}rust{RESET}\n  {YELLOW}let x = 1;{RESET}\n{DIM}```{RESET}\n  {CYAN}•{RESET} one {CYAN}a{RESET}\n    {CYAN}•{RESET} two\n  {CYAN}3.{RESET} three\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_markdown_inside_fences_is_left_alone() {
+        let mut renderer = MarkdownRenderer::new();
+        renderer.push("~~~\n");
+        assert!(renderer.in_code_block());
+        assert_eq!(
+            renderer.push("# not a heading\n"),
+            format!("  {YELLOW}# not a heading{RESET}\n")
+        );
+        renderer.push("~~~\n");
+        assert!(!renderer.in_code_block());
+    }
+
+    #[test]
+    fn test_inline_styles() {
+        assert_eq!(
+            render(&["**bold** and `code` and ` unmatched"]),
+            format!("{BOLD}bold{RESET} and {CYAN}code{RESET} and ` unmatched")
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! An interactive terminal chat loop.
+//!
+//! ```no_run
+//! use clauders::{Client, Options};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), clauders::Error> {
+//!     let client = Client::new(Options::new()).await?;
+//!     clauders::repl::run(&client).await
+//! }
+//! ```
+//!
+//! Responses stream to stdout as they arrive. Pressing Ctrl-C while Claude is
+//! responding interrupts the turn; pressing it at the prompt exits. Lines
+//! starting with `/` are passed to the CLI as slash commands, except `/exit`
+//! and `/quit`, which end the loop.
+
+use std::io::Write;
+
+use futures::StreamExt;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::response::Response;
+
+/// Runs the chat loop with the default settings until the user exits or stdin closes.
+pub async fn run(client: &Client) -> Result<(), Error> {
+    Repl::new().run(client).await
+}
+
+/// What a line typed at the prompt asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Input<'a> {
+    /// A blank line; the prompt is shown again.
+    Empty,
+    /// `/exit` or `/quit`, with or without arguments.
+    Exit,
+    /// A slash command, passed to the CLI as typed.
+    SlashCommand(&'a str),
+    Prompt(&'a str),
+}
+
+impl<'a> Input<'a> {
+    fn parse(line: &'a str) -> Self {
+        let line = line.trim();
+        if line.is_empty() {
+            return Self::Empty;
+        }
+        match line.split_whitespace().next() {
+            Some("/exit" | "/quit") => Self::Exit,
+            Some(name) if name.len() > 1 && name.starts_with('/') => Self::SlashCommand(line),
+            _ => Self::Prompt(line),
+        }
+    }
+}
+
+/// Settings for the chat loop.
+#[derive(Debug, Clone)]
+pub struct Repl {
+    prompt: String,
+    show_tool_use: bool,
+    show_cost: bool,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self {
+            prompt: "> ".to_owned(),
+            show_tool_use: true,
+            show_cost: true,
+        }
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Prints a line to stderr for each tool Claude calls. Enabled by default.
+    #[must_use]
+    pub fn show_tool_use(mut self, enabled: bool) -> Self {
+        self.show_tool_use = enabled;
+        self
+    }
+
+    /// Prints the cost and duration of each turn. Enabled by default.
+    #[must_use]
+    pub fn show_cost(mut self, enabled: bool) -> Self {
+        self.show_cost = enabled;
+        self
+    }
+
+    pub async fn run(&self, client: &Client) -> Result<(), Error> {
+        self.run_lines(client, BufReader::new(tokio::io::stdin()))
+            .await
+    }
+
+    /// Runs the loop over the lines of `input`.
+    async fn run_lines<R>(&self, client: &Client, input: R) -> Result<(), Error>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let mut lines = input.lines();
+        let mut total_cost = 0.0;
+
+        loop {
+            print!("{}", self.prompt);
+            std::io::stdout().flush()?;
+
+            let line = tokio::select! {
+                line = lines.next_line() => line?,
+                _ = tokio::signal::ctrl_c() => {
+                    println!();
+                    break;
+                }
+            };
+            let Some(line) = line else {
+                break;
+            };
+
+            match Input::parse(&line) {
+                Input::Empty => continue,
+                Input::Exit => break,
+                Input::SlashCommand(text) | Input::Prompt(text) => client.query(text).await?,
+            }
+            if let Some(cost) = self.stream_turn(client).await? {
+                total_cost += cost;
+                if self.show_cost {
+                    eprintln!("[cost: ${cost:.4}, session: ${total_cost:.4}]");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints a turn's responses as they arrive, returning its cost.
+    async fn stream_turn(&self, client: &Client) -> Result<Option<f64>, Error> {
+        let mut stream = std::pin::pin!(client.receive());
+        let mut interrupted = false;
+        let mut stdout = std::io::stdout();
+
+        loop {
+            let next = tokio::select! {
+                next = stream.next() => next,
+                _ = tokio::signal::ctrl_c(), if !interrupted => {
+                    interrupted = true;
+                    eprintln!("\n[interrupting]");
+                    client.interrupt().await?;
+                    continue;
+                }
+            };
+            let Some(response) = next else {
+                println!();
+                return Ok(None);
+            };
+
+            match response? {
+                Response::Text(text) => {
+                    write!(stdout, "{}", text.content())?;
+                    stdout.flush()?;
+                }
+                Response::ToolUse(tool_use) if self.show_tool_use => {
+                    eprintln!("\n[tool: {}]", tool_use.name());
+                }
+                Response::Error(error) => {
+                    eprintln!("\n[error: {}]", error.message());
+                }
+                Response::Complete(complete) => {
+                    println!();
+                    if let Some(result) = complete.result_text()
+                        && complete.is_error()
+                    {
+                        eprintln!("[{result}]");
+                    }
+                    if self.show_cost {
+                        eprintln!(
+                            "[{:.1}s, {} turns]",
+                            complete.duration_ms() as f64 / 1000.0,
+                            complete.num_turns()
+                        );
+                    }
+                    return Ok(complete.total_cost_usd());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::fake_cli::{FakeCli, result};
+    #[cfg(unix)]
+    use crate::options::Options;
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(Input::parse(""), Input::Empty);
+        assert_eq!(Input::parse("  \t"), Input::Empty);
+        assert_eq!(Input::parse("/exit"), Input::Exit);
+        assert_eq!(Input::parse(" /quit now "), Input::Exit);
+        assert_eq!(
+            Input::parse("/compact keep the plan\n"),
+            Input::SlashCommand("/compact keep the plan")
+        );
+        assert_eq!(Input::parse("/exits"), Input::SlashCommand("/exits"));
+        assert_eq!(Input::parse("/"), Input::Prompt("/"));
+        assert_eq!(
+            Input::parse(" what is 2 + 2? "),
+            Input::Prompt("what is 2 + 2?")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_lines_are_sent_until_exit() {
+        // The CLI records each user message it is sent.
+        let cli = FakeCli::initialized(&format!(
+            "while :; do\nnext\nprintf '%s\\n' \"$line\" >> sent\nemit '{}'\ndone",
+            result(),
+        ));
+        let client = Client::new(Options::new().cli_path(cli.path()).cwd(cli.dir()))
+            .await
+            .unwrap();
+
+        let input = "hello\n\n/compact\n/exit\nunsent\n".as_bytes();
+        Repl::new().run_lines(&client, input).await.unwrap();
+
+        let sent = std::fs::read_to_string(cli.dir().join("sent")).unwrap();
+        let contents = sent
+            .lines()
+            .map(|line| {
+                let message: serde_json::Value = serde_json::from_str(line).unwrap();
+                message["message"]["content"].as_str().unwrap().to_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(contents, ["hello", "/compact"]);
+    }
+}
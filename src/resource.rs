@@ -0,0 +1,232 @@
+//! Resource templates served by an in-process [`McpServer`](crate::McpServer).
+//!
+//! A template describes a family of resources by a URI template such as
+//! `ticket://{id}`. Its arguments can be given completers, so the CLI can
+//! offer suggestions while the user types them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde_json::{Value, json};
+
+use crate::tool::ToolError;
+
+/// Maximum number of values returned for a single completion request.
+pub const MAX_COMPLETION_VALUES: usize = 100;
+
+type Reader = Arc<
+    dyn Fn(HashMap<String, String>) -> BoxFuture<'static, Result<String, ToolError>> + Send + Sync,
+>;
+type Completer = Arc<dyn Fn(String) -> BoxFuture<'static, Vec<String>> + Send + Sync>;
+
+pub struct ResourceTemplate {
+    uri_template: String,
+    name: String,
+    description: Option<String>,
+    mime_type: Option<String>,
+    reader: Reader,
+    completers: HashMap<String, Completer>,
+}
+
+impl std::fmt::Debug for ResourceTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResourceTemplate")
+            .field("uri_template", &self.uri_template)
+            .field("name", &self.name)
+            .field("description", &self.description)
+            .field("mime_type", &self.mime_type)
+            .field("reader", &"<fn>")
+            .field("completers", &self.completers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ResourceTemplate {
+    /// Creates a template whose resources are read by `reader`.
+    ///
+    /// `uri_template` uses `{name}` placeholders; `reader` receives the value
+    /// of each placeholder in the requested URI and returns its text.
+    pub fn new<F, Fut>(uri_template: impl Into<String>, name: impl Into<String>, reader: F) -> Self
+    where
+        F: Fn(HashMap<String, String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, ToolError>> + Send + 'static,
+    {
+        Self {
+            uri_template: uri_template.into(),
+            name: name.into(),
+            description: None,
+            mime_type: None,
+            reader: Arc::new(move |args| Box::pin(reader(args))),
+            completers: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    #[must_use]
+    pub fn mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    /// Offers completions for the `argument` placeholder.
+    ///
+    /// `completer` receives what the user has typed so far and returns
+    /// candidate values, best first.
+    #[must_use]
+    pub fn complete<F, Fut>(mut self, argument: impl Into<String>, completer: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Vec<String>> + Send + 'static,
+    {
+        self.completers.insert(
+            argument.into(),
+            Arc::new(move |partial| Box::pin(completer(partial))),
+        );
+        self
+    }
+
+    pub fn uri_template(&self) -> &str {
+        &self.uri_template
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Extracts the placeholder values from `uri`, if it matches this template.
+    pub fn matches(&self, uri: &str) -> Option<HashMap<String, String>> {
+        let mut args = HashMap::new();
+        let mut rest = uri;
+        let mut segments = Segments::new(&self.uri_template).peekable();
+        while let Some(segment) = segments.next() {
+            match segment {
+                Segment::Literal(literal) => rest = rest.strip_prefix(literal)?,
+                Segment::Variable(name) => {
+                    let end = match segments.peek() {
+                        Some(Segment::Literal(next)) => rest.find(next)?,
+                        _ => rest.len(),
+                    };
+                    args.insert(name.to_owned(), rest[..end].to_owned());
+                    rest = &rest[end..];
+                }
+            }
+        }
+        rest.is_empty().then_some(args)
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let mut template = json!({
+            "uriTemplate": self.uri_template,
+            "name": self.name,
+        });
+        if let Some(description) = &self.description {
+            template["description"] = json!(description);
+        }
+        if let Some(mime_type) = &self.mime_type {
+            template["mimeType"] = json!(mime_type);
+        }
+        template
+    }
+
+    pub(crate) async fn read(&self, uri: &str) -> Option<Result<Value, ToolError>> {
+        let args = self.matches(uri)?;
+        Some((self.reader)(args).await.map(|text| {
+            let mut contents = json!({ "uri": uri, "text": text });
+            if let Some(mime_type) = &self.mime_type {
+                contents["mimeType"] = json!(mime_type);
+            }
+            contents
+        }))
+    }
+
+    /// Completes `argument`, or returns `None` if it has no completer.
+    pub(crate) async fn completions(&self, argument: &str, partial: &str) -> Option<Value> {
+        let completer = self.completers.get(argument)?;
+        let values = completer(partial.to_owned()).await;
+        let total = values.len();
+        Some(json!({
+            "values": values.into_iter().take(MAX_COMPLETION_VALUES).collect::<Vec<_>>(),
+            "total": total,
+            "hasMore": total > MAX_COMPLETION_VALUES,
+        }))
+    }
+}
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Variable(&'a str),
+}
+
+/// Splits a URI template into literal text and `{name}` placeholders.
+struct Segments<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Segments<'a> {
+    fn new(template: &'a str) -> Self {
+        Self { rest: template }
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Segment<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        if let Some(body) = self.rest.strip_prefix('{')
+            && let Some(end) = body.find('}')
+        {
+            self.rest = &body[end + 1..];
+            return Some(Segment::Variable(&body[..end]));
+        }
+        let first = self.rest.chars().next().map_or(0, char::len_utf8);
+        let end = self.rest[first..]
+            .find('{')
+            .map_or(self.rest.len(), |i| i + first);
+        let (literal, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(Segment::Literal(literal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_template_matching_and_completion() {
+        let template =
+            ResourceTemplate::new("ticket://{project}/{id}", "ticket", |args| async move {
+                Ok(format!("{}-{}", args["project"], args["id"]))
+            })
+            .complete("id", |partial| async move {
+                (0..150).map(|n| format!("{partial}{n}")).collect()
+            });
+
+        let args = template.matches("ticket://core/42").unwrap();
+        assert_eq!(args["project"], "core");
+        assert_eq!(args["id"], "42");
+        assert!(template.matches("issue://core/42").is_none());
+
+        let contents = template.read("ticket://core/42").await.unwrap().unwrap();
+        assert_eq!(contents["text"], "core-42");
+
+        let completion = template.completions("id", "4").await.unwrap();
+        assert_eq!(
+            completion["values"].as_array().unwrap().len(),
+            MAX_COMPLETION_VALUES
+        );
+        assert_eq!(completion["values"][0], "40");
+        assert_eq!(completion["total"], 150);
+        assert_eq!(completion["hasMore"], true);
+        assert!(template.completions("project", "").await.is_none());
+    }
+}
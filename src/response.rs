@@ -1,19 +1,29 @@
 use std::borrow::Cow;
-use std::time::Duration;
-
-use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 
+use crate::error::Error;
 use crate::proto::content_block::{
     Text as ProtoText, Thinking as ProtoThinking, ToolResult as ProtoToolResult,
     ToolUse as ProtoToolUse,
 };
 use crate::proto::message::{
-    AssistantError, HookLifecycleMessage, InitMessage, ResultMessage, SystemMessage, Usage,
+    AssistantError, HookLifecycleMessage, InitMessage, McpServerStatus, ModelUsage,
+    PermissionDenial, PluginInfo, ResultMessage, SystemMessage, Usage,
 };
-use crate::proto::{Message, RateLimitEvent};
-
-#[derive(Debug, Clone)]
+use crate::proto::{Message, RateLimitEvent, StreamEvent, UserContent};
+use crate::tool::{ERROR_KIND_META_KEY, ToolErrorKind};
+
+/// A message of a turn, as handed to callers and [`Handler`](crate::Handler)s.
+///
+/// Variants are added as the CLI's protocol grows; match with a wildcard arm.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum Response {
     Text(TextResponse),
     ToolUse(ToolUseResponse),
@@ -24,13 +34,18 @@ pub enum Response {
     RateLimit(RateLimitResponse),
     HookStarted(HookLifecycleResponse),
     HookResponse(HookLifecycleResponse),
+    Partial(PartialResponse),
     Complete(CompleteResponse),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextResponse {
-    inner: ProtoText,
+    #[serde(flatten)]
+    inner: Arc<ProtoText>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     message_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_tool_use_id: Option<String>,
 }
 
 impl TextResponse {
@@ -41,12 +56,32 @@ impl TextResponse {
     pub fn message_id(&self) -> Option<&str> {
         self.message_id.as_deref()
     }
+
+    /// The id of the Task tool use whose subagent sent this, or `None` for
+    /// the main agent.
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        self.parent_tool_use_id.as_deref()
+    }
+
+    /// The same response with its text replaced by `content`.
+    #[cfg(feature = "guardrails")]
+    pub(crate) fn with_content(&self, content: String) -> Self {
+        Self {
+            inner: Arc::new(ProtoText::new(content)),
+            message_id: self.message_id.clone(),
+            parent_tool_use_id: self.parent_tool_use_id.clone(),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolUseResponse {
-    inner: ProtoToolUse,
+    #[serde(flatten)]
+    inner: Arc<ProtoToolUse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     message_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_tool_use_id: Option<String>,
 }
 
 impl ToolUseResponse {
@@ -62,42 +97,143 @@ impl ToolUseResponse {
         self.inner.input()
     }
 
+    /// Decodes the input into `T`, such as the argument type of a
+    /// [`Tool::structured`](crate::Tool::structured) tool.
+    pub fn input_as<T: serde::de::DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(T::deserialize(self.input())?)
+    }
+
     pub fn message_id(&self) -> Option<&str> {
         self.message_id.as_deref()
     }
+
+    /// The id of the Task tool use whose subagent sent this, or `None` for
+    /// the main agent.
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        self.parent_tool_use_id.as_deref()
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct ToolResultResponse(pub(crate) ProtoToolResult);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolResultResponse {
+    #[serde(flatten)]
+    inner: Arc<ProtoToolResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_tool_use_id: Option<String>,
+}
 
 impl ToolResultResponse {
     pub fn tool_use_id(&self) -> &str {
-        self.0.tool_use_id()
+        self.inner.tool_use_id()
     }
 
     pub fn content(&self) -> Option<&Value> {
-        self.0.content()
+        self.inner.content()
+    }
+
+    /// The content, shared rather than copied, to keep past the response.
+    pub fn shared_content(&self) -> Option<Arc<Value>> {
+        self.inner.shared_content()
     }
 
     pub fn is_error(&self) -> bool {
-        self.0.is_error().unwrap_or(false)
+        self.inner.is_error().unwrap_or(false)
+    }
+
+    /// The id of the Task tool use whose subagent sent this, or `None` for
+    /// the main agent.
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        self.parent_tool_use_id.as_deref()
+    }
+
+    /// The content blocks of the result. Content given as a plain string is
+    /// a single block.
+    pub fn blocks(&self) -> &[Value] {
+        match self.content() {
+            Some(Value::Array(blocks)) => blocks,
+            Some(block) => std::slice::from_ref(block),
+            None => &[],
+        }
+    }
+
+    /// The text of the result's text blocks, joined by newlines, or `None`
+    /// if it has none.
+    pub fn text(&self) -> Option<String> {
+        let texts = self
+            .blocks()
+            .iter()
+            .filter_map(|block| match block {
+                Value::String(text) => Some(text.as_str()),
+                block if block.get("type").is_some_and(|t| t == "text") => {
+                    block.get("text")?.as_str()
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        (!texts.is_empty()).then(|| texts.join("\n"))
+    }
+
+    /// Decodes the result's text as JSON into `T`, such as the output of a
+    /// [`Tool::structured`](crate::Tool::structured) tool.
+    pub fn structured<T: serde::de::DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(serde_json::from_str(&self.text().unwrap_or_default())?)
+    }
+
+    /// Classifies a failed result, or returns `None` if it succeeded.
+    ///
+    /// In-process SDK tools report the kind of their [`ToolError`] in the
+    /// result's metadata; for other tools it is guessed from the error text,
+    /// so treat anything but [`ToolErrorKind::Other`] as a hint.
+    ///
+    /// [`ToolError`]: crate::ToolError
+    pub fn error_kind(&self) -> Option<ToolErrorKind> {
+        if !self.is_error() {
+            return None;
+        }
+        let blocks = self.blocks();
+        let reported = |meta: Option<&Value>| {
+            ToolErrorKind::from_name(meta?.get(ERROR_KIND_META_KEY)?.as_str()?)
+        };
+        let reported = blocks
+            .iter()
+            .find_map(|block| reported(block.get("_meta")))
+            .or_else(|| reported(self.inner.extra().get("_meta")));
+        Some(reported.unwrap_or_else(|| {
+            let text = blocks
+                .iter()
+                .filter_map(|block| block.as_str().or_else(|| block.get("text")?.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            ToolErrorKind::classify(&text)
+        }))
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct ThinkingResponse(pub(crate) ProtoThinking);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThinkingResponse {
+    #[serde(flatten)]
+    inner: Arc<ProtoThinking>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_tool_use_id: Option<String>,
+}
 
 impl ThinkingResponse {
     pub fn content(&self) -> &str {
-        self.0.thinking()
+        self.inner.thinking()
     }
 
     pub fn signature(&self) -> &str {
-        self.0.signature()
+        self.inner.signature()
+    }
+
+    /// The id of the Task tool use whose subagent sent this, or `None` for
+    /// the main agent.
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        self.parent_tool_use_id.as_deref()
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HookLifecycleResponse(pub(crate) HookLifecycleMessage);
 
 impl HookLifecycleResponse {
@@ -120,9 +256,20 @@ impl HookLifecycleResponse {
     pub fn exit_code(&self) -> Option<i32> {
         self.0.exit_code()
     }
+
+    fn to_json(&self, event_type: &str) -> Value {
+        json!({
+            "type": event_type,
+            "hook_id": self.hook_id(),
+            "hook_name": self.hook_name(),
+            "hook_event": self.hook_event(),
+            "outcome": self.outcome(),
+            "exit_code": self.exit_code(),
+        })
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InitResponse(pub(crate) InitMessage);
 
 impl InitResponse {
@@ -137,9 +284,18 @@ impl InitResponse {
     pub fn cwd(&self) -> Option<&str> {
         self.0.cwd()
     }
+
+    pub fn mcp_servers(&self) -> &[McpServerStatus] {
+        self.0.mcp_servers()
+    }
+
+    pub fn plugins(&self) -> &[PluginInfo] {
+        self.0.plugins()
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "source", content = "error", rename_all = "snake_case")]
 pub enum ErrorResponse {
     System(String),
     Assistant(AssistantError),
@@ -188,7 +344,7 @@ impl ErrorResponse {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RateLimitResponse(pub(crate) RateLimitEvent);
 
 impl RateLimitResponse {
@@ -213,13 +369,18 @@ impl RateLimitResponse {
     }
 
     pub fn backoff_delay(&self) -> Option<Duration> {
+        self.backoff_delay_at(std::time::SystemTime::now())
+    }
+
+    /// Like [`backoff_delay`](Self::backoff_delay), measured from `now`.
+    pub fn backoff_delay_at(&self, now: std::time::SystemTime) -> Option<Duration> {
         if !self.is_rejected() {
             return None;
         }
 
         match self.resets_at() {
             Some(resets_at) => {
-                let now = std::time::SystemTime::now()
+                let now = now
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_secs_f64();
@@ -236,8 +397,73 @@ impl From<RateLimitEvent> for RateLimitResponse {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct CompleteResponse(pub(crate) ResultMessage);
+/// A raw streaming event for a message still being generated.
+///
+/// Only sent when [`Options::include_partial_messages`](crate::Options::include_partial_messages)
+/// is enabled. The complete message still follows as the usual responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialResponse(pub(crate) StreamEvent);
+
+impl PartialResponse {
+    pub fn event(&self) -> &Value {
+        self.0.event()
+    }
+
+    pub fn event_type(&self) -> Option<&str> {
+        self.0.event_type()
+    }
+
+    pub fn index(&self) -> Option<u64> {
+        self.0.index()
+    }
+
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        self.0.parent_tool_use_id()
+    }
+
+    /// The text appended by a `text_delta` event.
+    pub fn text_delta(&self) -> Option<&str> {
+        self.delta("text_delta", "text")
+    }
+
+    /// The JSON fragment appended to a tool call's input by an
+    /// `input_json_delta` event.
+    pub fn input_json_delta(&self) -> Option<&str> {
+        self.delta("input_json_delta", "partial_json")
+    }
+
+    /// The tool name of a `content_block_start` event opening a tool call.
+    pub fn tool_use_started(&self) -> Option<&str> {
+        if self.event_type() != Some("content_block_start") {
+            return None;
+        }
+        let block = self.event().get("content_block")?;
+        if block.get("type")?.as_str()? != "tool_use" {
+            return None;
+        }
+        block.get("name")?.as_str()
+    }
+
+    fn delta(&self, delta_type: &str, field: &str) -> Option<&str> {
+        if self.event_type() != Some("content_block_delta") {
+            return None;
+        }
+        let delta = self.event().get("delta")?;
+        if delta.get("type")?.as_str()? != delta_type {
+            return None;
+        }
+        delta.get(field)?.as_str()
+    }
+}
+
+impl From<StreamEvent> for PartialResponse {
+    fn from(event: StreamEvent) -> Self {
+        Self(event)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompleteResponse(pub(crate) Box<ResultMessage>);
 
 impl CompleteResponse {
     pub fn subtype(&self) -> &str {
@@ -276,12 +502,100 @@ impl CompleteResponse {
         self.0.structured_output()
     }
 
+    /// Token usage and cost per model, keyed by model id. Includes models
+    /// used by subagents as well as the main conversation.
+    pub fn model_usage(&self) -> Option<&HashMap<String, ModelUsage>> {
+        self.0.model_usage()
+    }
+
+    /// Tool calls the CLI refused during the turn.
+    pub fn permission_denials(&self) -> &[PermissionDenial] {
+        self.0.permission_denials().unwrap_or_default()
+    }
+
+    /// Names of the tools whose calls were refused, without duplicates.
+    pub fn denied_tools(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for denial in self.permission_denials() {
+            if !names.contains(&denial.tool_name()) {
+                names.push(denial.tool_name());
+            }
+        }
+        names
+    }
+
     pub fn is_error(&self) -> bool {
         self.0.is_error()
     }
 }
 
 impl Response {
+    /// Converts the response to a JSON event object tagged with a `type` field,
+    /// suitable for forwarding to other processes or frontends.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Self::Text(text) => json!({
+                "type": "text",
+                "content": text.content(),
+                "message_id": text.message_id(),
+            }),
+            Self::ToolUse(tool_use) => json!({
+                "type": "tool_use",
+                "id": tool_use.id(),
+                "name": tool_use.name(),
+                "input": tool_use.input(),
+                "message_id": tool_use.message_id(),
+            }),
+            Self::ToolResult(result) => json!({
+                "type": "tool_result",
+                "tool_use_id": result.tool_use_id(),
+                "content": result.content(),
+                "is_error": result.is_error(),
+            }),
+            Self::Thinking(thinking) => json!({
+                "type": "thinking",
+                "content": thinking.content(),
+            }),
+            Self::Init(init) => json!({
+                "type": "init",
+                "session_id": init.session_id(),
+                "model": init.model(),
+                "cwd": init.cwd(),
+            }),
+            Self::Error(error) => json!({
+                "type": "error",
+                "message": error.message(),
+            }),
+            Self::RateLimit(rate_limit) => json!({
+                "type": "rate_limit",
+                "status": rate_limit.status(),
+                "resets_at": rate_limit.resets_at(),
+                "utilization": rate_limit.utilization(),
+            }),
+            Self::HookStarted(hook) => hook.to_json("hook_started"),
+            Self::HookResponse(hook) => hook.to_json("hook_response"),
+            Self::Partial(partial) => json!({
+                "type": "partial",
+                "event": partial.event(),
+                "parent_tool_use_id": partial.parent_tool_use_id(),
+            }),
+            Self::Complete(complete) => json!({
+                "type": "complete",
+                "subtype": complete.subtype(),
+                "session_id": complete.session_id(),
+                "is_error": complete.is_error(),
+                "duration_ms": complete.duration_ms(),
+                "num_turns": complete.num_turns(),
+                "total_cost_usd": complete.total_cost_usd(),
+                "usage": complete.usage(),
+                "result": complete.result_text(),
+                "structured_output": complete.structured_output(),
+                "model_usage": complete.model_usage(),
+                "permission_denials": complete.permission_denials(),
+            }),
+        }
+    }
+
     pub fn is_text(&self) -> bool {
         matches!(self, Self::Text(_))
     }
@@ -314,6 +628,19 @@ impl Response {
         matches!(self, Self::Complete(_))
     }
 
+    /// The id of the Task tool use whose subagent sent this, or `None` for
+    /// the main agent and for responses about the session as a whole.
+    pub fn parent_tool_use_id(&self) -> Option<&str> {
+        match self {
+            Self::Text(t) => t.parent_tool_use_id(),
+            Self::ToolUse(t) => t.parent_tool_use_id(),
+            Self::ToolResult(t) => t.parent_tool_use_id(),
+            Self::Thinking(t) => t.parent_tool_use_id(),
+            Self::Partial(p) => p.parent_tool_use_id(),
+            _ => None,
+        }
+    }
+
     pub fn as_text(&self) -> Option<&TextResponse> {
         match self {
             Self::Text(t) => Some(t),
@@ -426,40 +753,98 @@ impl Response {
         }
     }
 
+    /// The response with its content no longer shared with the
+    /// [`Message`] it was made from.
+    ///
+    /// [`from_message`](Self::from_message) shares text, thinking and tool
+    /// payloads with the message rather than copying them. A response kept
+    /// after the message is dropped owns them already; otherwise they are
+    /// copied here.
+    pub fn into_owned(self) -> Self {
+        fn own<T: Clone>(mut shared: Arc<T>) -> Arc<T> {
+            Arc::make_mut(&mut shared);
+            shared
+        }
+        match self {
+            Self::Text(t) => Self::Text(TextResponse {
+                inner: own(t.inner),
+                ..t
+            }),
+            Self::ToolUse(t) => Self::ToolUse(ToolUseResponse {
+                inner: own(t.inner),
+                ..t
+            }),
+            Self::ToolResult(t) => Self::ToolResult(ToolResultResponse {
+                inner: own(t.inner),
+                ..t
+            }),
+            Self::Thinking(t) => Self::Thinking(ThinkingResponse {
+                inner: own(t.inner),
+                ..t
+            }),
+            other => other,
+        }
+    }
+
     pub fn from_message(msg: &Message) -> Vec<Self> {
         match msg {
-            Message::User(_) => vec![],
+            // The CLI reports the results of tool calls in user messages.
+            Message::User(envelope) => match envelope.message().content() {
+                UserContent::Blocks(blocks) => blocks
+                    .iter()
+                    .filter_map(|block| match block {
+                        crate::proto::ContentBlock::ToolResult(t) => {
+                            Some(Self::ToolResult(ToolResultResponse {
+                                inner: Arc::clone(t),
+                                parent_tool_use_id: envelope.parent_tool_use_id().map(String::from),
+                            }))
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+                UserContent::Text(_) => vec![],
+            },
             Message::Assistant(envelope) => {
                 if let Some(err) = envelope.message().error() {
                     return vec![Self::Error(ErrorResponse::Assistant(err.clone()))];
                 }
                 let message_id = envelope.uuid().map(String::from);
+                let parent_tool_use_id = envelope.parent_tool_use_id().map(String::from);
                 envelope
                     .message()
                     .content()
                     .iter()
                     .map(|block| match block {
                         crate::proto::ContentBlock::Text(t) => Self::Text(TextResponse {
-                            inner: t.clone(),
+                            inner: Arc::clone(t),
                             message_id: message_id.clone(),
+                            parent_tool_use_id: parent_tool_use_id.clone(),
                         }),
                         crate::proto::ContentBlock::ToolUse(t) => {
                             Self::ToolUse(ToolUseResponse {
-                                inner: t.clone(),
+                                inner: Arc::clone(t),
                                 message_id: message_id.clone(),
+                                parent_tool_use_id: parent_tool_use_id.clone(),
                             })
                         }
                         crate::proto::ContentBlock::ToolResult(t) => {
-                            Self::ToolResult(ToolResultResponse(t.clone()))
+                            Self::ToolResult(ToolResultResponse {
+                                inner: Arc::clone(t),
+                                parent_tool_use_id: parent_tool_use_id.clone(),
+                            })
                         }
                         crate::proto::ContentBlock::Thinking(t) => {
-                            Self::Thinking(ThinkingResponse(t.clone()))
+                            Self::Thinking(ThinkingResponse {
+                                inner: Arc::clone(t),
+                                parent_tool_use_id: parent_tool_use_id.clone(),
+                            })
                         }
                         crate::proto::ContentBlock::Image(_)
                         | crate::proto::ContentBlock::Document(_) => {
                             Self::Text(TextResponse {
-                                inner: ProtoText::new("[media]"),
+                                inner: Arc::new(ProtoText::new("[media]")),
                                 message_id: message_id.clone(),
+                                parent_tool_use_id: parent_tool_use_id.clone(),
                             })
                         }
                     })
@@ -477,13 +862,106 @@ impl Response {
                     vec![Self::HookResponse(HookLifecycleResponse(msg.clone()))]
                 }
             },
-            Message::Result(result) => vec![Self::Complete(CompleteResponse(result.clone()))],
+            Message::Result(result) => {
+                vec![Self::Complete(CompleteResponse(Box::new(result.clone())))]
+            }
+        }
+    }
+}
+
+/// When and in what order the client received a [`Response`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseMeta {
+    sequence: u64,
+    received_at: SystemTime,
+    instant: Instant,
+}
+
+impl ResponseMeta {
+    pub(crate) fn new(sequence: u64, received_at: SystemTime) -> Self {
+        Self {
+            sequence,
+            received_at,
+            instant: Instant::now(),
         }
     }
+
+    /// The position of the response among everything the client has
+    /// received, increasing across turns and streams.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The wall-clock time the response was received, read from the client's
+    /// [`Clock`](crate::Clock).
+    pub fn received_at(&self) -> SystemTime {
+        self.received_at
+    }
+
+    /// The monotonic time elapsed between receiving `earlier` and this response.
+    pub fn since(&self, earlier: &ResponseMeta) -> Duration {
+        self.instant.saturating_duration_since(earlier.instant)
+    }
+}
+
+/// Client-observed timings of a turn, from [`Responses::timings`].
+///
+/// These complement the CLI-reported `duration_ms` with what the client saw,
+/// including transport and process overhead.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Timings {
+    time_to_first_text: Option<Duration>,
+    total: Option<Duration>,
+    tool_round_trips: Vec<ToolRoundTrip>,
+}
+
+impl Timings {
+    /// Time from sending the prompt (or the first response) to the first text.
+    pub fn time_to_first_text(&self) -> Option<Duration> {
+        self.time_to_first_text
+    }
+
+    /// Time from sending the prompt (or the first response) to the last response.
+    pub fn total(&self) -> Option<Duration> {
+        self.total
+    }
+
+    pub fn tool_round_trips(&self) -> &[ToolRoundTrip] {
+        &self.tool_round_trips
+    }
+}
+
+/// How long one tool call took to come back.
+///
+/// Measured to the matching tool result when the CLI reports one, otherwise
+/// to Claude's next output, which also includes the model's time to respond.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolRoundTrip {
+    tool_use_id: String,
+    name: Arc<str>,
+    duration: Duration,
+}
+
+impl ToolRoundTrip {
+    pub fn tool_use_id(&self) -> &str {
+        &self.tool_use_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
 }
 
 #[derive(Debug, Clone, Default)]
-pub struct Responses(Vec<Response>);
+pub struct Responses {
+    responses: Vec<Response>,
+    meta: Vec<Option<ResponseMeta>>,
+    started: Option<Instant>,
+}
 
 impl Responses {
     pub fn new() -> Self {
@@ -495,27 +973,138 @@ impl Responses {
     }
 
     pub fn as_slice(&self) -> &[Response] {
-        &self.0
+        &self.responses
     }
 
     pub fn push(&mut self, response: Response) {
-        self.0.push(response);
+        self.responses.push(response);
+        self.meta.push(None);
+    }
+
+    /// Adds a response along with its receipt metadata.
+    pub fn push_with_meta(&mut self, meta: ResponseMeta, response: Response) {
+        self.responses.push(response);
+        self.meta.push(Some(meta));
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &Response> {
-        self.0.iter()
+        self.responses.iter()
+    }
+
+    /// Writes one JSON object per response, each on its own line, for tools
+    /// like `jq` or for loading back with [`read_ndjson`](Self::read_ndjson).
+    pub fn write_ndjson(&self, mut writer: impl std::io::Write) -> Result<(), Error> {
+        for response in &self.responses {
+            serde_json::to_writer(&mut writer, response)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads responses written by [`write_ndjson`](Self::write_ndjson),
+    /// skipping blank lines. Receipt metadata is not preserved.
+    pub fn read_ndjson(reader: impl std::io::BufRead) -> Result<Self, Error> {
+        let mut responses = Self::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            responses.push(serde_json::from_str(&line)?);
+        }
+        Ok(responses)
+    }
+
+    /// The responses of the subagent started by the Task tool use
+    /// `tool_use_id`, with their receipt metadata.
+    pub fn subagent(&self, tool_use_id: &str) -> Self {
+        self.project(|response| {
+            (response.parent_tool_use_id() == Some(tool_use_id)).then(|| response.clone())
+        })
+    }
+
+    /// Returns the receipt metadata of the response at `index`, if recorded.
+    pub fn meta(&self, index: usize) -> Option<&ResponseMeta> {
+        self.meta.get(index)?.as_ref()
+    }
+
+    /// Records when the prompt for these responses was sent, the origin of
+    /// [`timings`](Self::timings).
+    pub(crate) fn set_started(&mut self, started: Instant) {
+        self.started = Some(started);
+    }
+
+    /// Computes timings from the receipt metadata of the responses.
+    ///
+    /// Responses pushed without metadata are ignored, so collections built
+    /// from plain responses have no timings.
+    pub fn timings(&self) -> Timings {
+        let stamped = self
+            .iter_with_meta()
+            .filter_map(|(meta, response)| Some((meta?.instant, response)))
+            .collect::<Vec<_>>();
+        let Some(origin) = self.started.or(stamped.first().map(|(at, _)| *at)) else {
+            return Timings::default();
+        };
+        let since_origin = |at: Instant| at.saturating_duration_since(origin);
+
+        let time_to_first_text = stamped
+            .iter()
+            .find(|(_, response)| response.as_text().is_some())
+            .map(|(at, _)| since_origin(*at));
+        let total = stamped.last().map(|(at, _)| since_origin(*at));
+
+        let tool_round_trips = stamped
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (at, response))| {
+                let tool_use = response.as_tool_use()?;
+                let later = &stamped[index + 1..];
+                let returned = later
+                    .iter()
+                    .find(|(_, r)| {
+                        r.as_tool_result()
+                            .is_some_and(|result| result.tool_use_id() == tool_use.id())
+                    })
+                    .or_else(|| {
+                        later.iter().find(|(_, r)| {
+                            matches!(
+                                r,
+                                Response::Text(_) | Response::Thinking(_) | Response::Complete(_)
+                            )
+                        })
+                    })?;
+                Some(ToolRoundTrip {
+                    tool_use_id: tool_use.id().to_owned(),
+                    name: tool_use.inner.shared_name(),
+                    duration: returned.0.saturating_duration_since(*at),
+                })
+            })
+            .collect();
+
+        Timings {
+            time_to_first_text,
+            total,
+            tool_round_trips,
+        }
+    }
+
+    /// Iterates over the responses with their receipt metadata, if recorded.
+    pub fn iter_with_meta(&self) -> impl Iterator<Item = (Option<&ResponseMeta>, &Response)> {
+        self.meta.iter().map(Option::as_ref).zip(&self.responses)
     }
 
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.responses.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.responses.is_empty()
     }
 
     pub fn text_content(&self) -> String {
-        self.0
+        self.responses
             .iter()
             .filter_map(|r| r.as_text())
             .map(|t| t.content())
@@ -524,7 +1113,7 @@ impl Responses {
     }
 
     pub fn thinking_content(&self) -> String {
-        self.0
+        self.responses
             .iter()
             .filter_map(|r| r.as_thinking())
             .map(|t| t.content())
@@ -533,27 +1122,27 @@ impl Responses {
     }
 
     pub fn texts(&self) -> impl Iterator<Item = &TextResponse> {
-        self.0.iter().filter_map(|r| r.as_text())
+        self.responses.iter().filter_map(|r| r.as_text())
     }
 
     pub fn tool_uses(&self) -> impl Iterator<Item = &ToolUseResponse> {
-        self.0.iter().filter_map(|r| r.as_tool_use())
+        self.responses.iter().filter_map(|r| r.as_tool_use())
     }
 
     pub fn tool_results(&self) -> impl Iterator<Item = &ToolResultResponse> {
-        self.0.iter().filter_map(|r| r.as_tool_result())
+        self.responses.iter().filter_map(|r| r.as_tool_result())
     }
 
     pub fn thinkings(&self) -> impl Iterator<Item = &ThinkingResponse> {
-        self.0.iter().filter_map(|r| r.as_thinking())
+        self.responses.iter().filter_map(|r| r.as_thinking())
     }
 
     pub fn errors(&self) -> impl Iterator<Item = &ErrorResponse> {
-        self.0.iter().filter_map(|r| r.as_error())
+        self.responses.iter().filter_map(|r| r.as_error())
     }
 
     pub fn rate_limits(&self) -> impl Iterator<Item = &RateLimitResponse> {
-        self.0.iter().filter_map(|r| r.as_rate_limit())
+        self.responses.iter().filter_map(|r| r.as_rate_limit())
     }
 
     pub fn tool_use_by_name(&self, name: &str) -> Option<&ToolUseResponse> {
@@ -565,31 +1154,113 @@ impl Responses {
     }
 
     pub fn completion(&self) -> Option<&CompleteResponse> {
-        self.0.iter().filter_map(|r| r.as_complete()).next_back()
+        self.responses
+            .iter()
+            .filter_map(|r| r.as_complete())
+            .next_back()
     }
 
     pub fn init(&self) -> Option<&InitResponse> {
-        self.0.iter().filter_map(|r| r.as_init()).next()
+        self.responses.iter().filter_map(|r| r.as_init()).next()
     }
 
     pub fn has_error(&self) -> bool {
-        self.0.iter().any(|r| r.is_error())
+        self.responses.iter().any(|r| r.is_error())
     }
 
     pub fn first_error(&self) -> Option<&ErrorResponse> {
-        self.0.iter().filter_map(|r| r.as_error()).next()
+        self.responses.iter().filter_map(|r| r.as_error()).next()
+    }
+
+    /// The responses strictly between the tool call `from` and the tool call
+    /// `to`, such as everything Claude did between two edits. Empty unless
+    /// both calls are present, in that order.
+    pub fn between(&self, from: &str, to: &str) -> Self {
+        let position = |id: &str| {
+            self.responses
+                .iter()
+                .position(|r| r.as_tool_use().is_some_and(|t| t.id() == id))
+        };
+        match (position(from), position(to)) {
+            (Some(start), Some(end)) if start < end => self.slice(start + 1..end),
+            _ => Self::new(),
+        }
+    }
+
+    /// The responses after the last tool result, typically Claude's final
+    /// answer. All of them if no tool returned.
+    pub fn after_last_tool_result(&self) -> Self {
+        let start = self
+            .responses
+            .iter()
+            .rposition(|r| r.as_tool_result().is_some())
+            .map_or(0, |index| index + 1);
+        self.slice(start..self.responses.len())
+    }
+
+    /// The text Claude wrote after it last thought, leaving out what it
+    /// said before reasoning. All text if it did not think.
+    pub fn texts_after_thinking(&self) -> impl Iterator<Item = &TextResponse> {
+        let start = self
+            .responses
+            .iter()
+            .rposition(|r| r.as_thinking().is_some())
+            .map_or(0, |index| index + 1);
+        self.responses[start..].iter().filter_map(|r| r.as_text())
+    }
+
+    /// Maps each response to another or drops it, keeping the receipt
+    /// metadata of those kept.
+    pub fn project<F>(&self, mut f: F) -> Self
+    where
+        F: FnMut(&Response) -> Option<Response>,
+    {
+        let mut projected = Self {
+            started: self.started,
+            ..Self::default()
+        };
+        for (meta, response) in self.meta.iter().zip(&self.responses) {
+            if let Some(response) = f(response) {
+                projected.responses.push(response);
+                projected.meta.push(*meta);
+            }
+        }
+        projected
+    }
+
+    fn slice(&self, range: std::ops::Range<usize>) -> Self {
+        Self {
+            responses: self.responses[range.clone()].to_vec(),
+            meta: self.meta[range].to_vec(),
+            started: self.started,
+        }
+    }
+
+    /// Sums the per-model usage reported by every completion, keyed by model id.
+    pub fn usage_by_model(&self) -> HashMap<String, ModelUsage> {
+        let mut totals = HashMap::<String, ModelUsage>::new();
+        for complete in self.responses.iter().filter_map(|r| r.as_complete()) {
+            for (model, usage) in complete.model_usage().into_iter().flatten() {
+                *totals.entry(model.clone()).or_default() += usage;
+            }
+        }
+        totals
     }
 }
 
 impl From<Vec<Response>> for Responses {
     fn from(responses: Vec<Response>) -> Self {
-        Self(responses)
+        Self {
+            meta: vec![None; responses.len()],
+            responses,
+            started: None,
+        }
     }
 }
 
 impl From<Responses> for Vec<Response> {
     fn from(responses: Responses) -> Self {
-        responses.0
+        responses.responses
     }
 }
 
@@ -598,7 +1269,7 @@ impl IntoIterator for Responses {
     type IntoIter = std::vec::IntoIter<Response>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.responses.into_iter()
     }
 }
 
@@ -607,7 +1278,7 @@ impl<'a> IntoIterator for &'a Responses {
     type IntoIter = std::slice::Iter<'a, Response>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.iter()
+        self.responses.iter()
     }
 }
 
@@ -615,6 +1286,412 @@ impl std::ops::Index<usize> for Responses {
     type Output = Response;
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.0[index]
+        &self.responses[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn complete(value: Value) -> CompleteResponse {
+        CompleteResponse(Box::new(serde_json::from_value(value).unwrap()))
+    }
+
+    #[test]
+    fn test_responses_keep_meta_aligned() {
+        let complete = || {
+            Response::Complete(complete(json!({
+                "type": "result",
+                "subtype": "success",
+                "duration_ms": 10,
+                "duration_api_ms": 8,
+                "is_error": false,
+                "num_turns": 1,
+                "session_id": "s"
+            })))
+        };
+        let first = ResponseMeta::new(0, SystemTime::UNIX_EPOCH);
+        let second = ResponseMeta::new(1, SystemTime::UNIX_EPOCH);
+
+        let mut responses = Responses::new();
+        responses.push_with_meta(first, complete());
+        responses.push(complete());
+        responses.push_with_meta(second, complete());
+
+        assert_eq!(responses.meta(0).map(ResponseMeta::sequence), Some(0));
+        assert!(responses.meta(1).is_none());
+        assert_eq!(responses.meta(2).map(ResponseMeta::sequence), Some(1));
+        assert_eq!(responses.iter_with_meta().count(), 3);
+    }
+
+    #[test]
+    fn test_tool_result_content_accessors() {
+        let result = |value: Value| serde_json::from_value::<ToolResultResponse>(value).unwrap();
+
+        let blocks = result(json!({
+            "tool_use_id": "t",
+            "content": [
+                {"type": "text", "text": "{\"total\":"},
+                {"type": "image", "data": "", "mimeType": "image/png"},
+                {"type": "text", "text": "3}"}
+            ]
+        }));
+        assert_eq!(blocks.blocks().len(), 3);
+        assert_eq!(blocks.text().as_deref(), Some("{\"total\":\n3}"));
+        assert_eq!(blocks.structured::<Value>().unwrap(), json!({"total": 3}));
+
+        let plain = result(json!({"tool_use_id": "t", "content": "ok"}));
+        assert_eq!(plain.text().as_deref(), Some("ok"));
+        assert!(plain.structured::<Value>().is_err());
+
+        let empty = result(json!({"tool_use_id": "t"}));
+        assert!(empty.blocks().is_empty());
+        assert_eq!(empty.text(), None);
+    }
+
+    #[test]
+    fn test_tool_use_input_as() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Search {
+            query: String,
+            limit: Option<u32>,
+        }
+
+        let response = serde_json::from_value::<Response>(json!({
+            "type": "tool_use",
+            "id": "t1",
+            "name": "mcp__kb__search",
+            "input": {"query": "rust"}
+        }))
+        .unwrap();
+        let tool_use = response.as_tool_use().unwrap();
+        assert_eq!(
+            tool_use.input_as::<Search>().unwrap(),
+            Search {
+                query: "rust".to_owned(),
+                limit: None
+            }
+        );
+        assert!(matches!(
+            tool_use.input_as::<Vec<String>>(),
+            Err(Error::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_filtering_and_projection() {
+        let responses = Responses::from(
+            [
+                json!({"type": "text", "text": "Let me look"}),
+                json!({"type": "tool_use", "id": "t1", "name": "Read", "input": {}}),
+                json!({"type": "tool_result", "tool_use_id": "t1", "content": "x"}),
+                json!({"type": "thinking", "thinking": "hmm", "signature": ""}),
+                json!({"type": "tool_use", "id": "t2", "name": "Edit", "input": {}}),
+                json!({"type": "tool_result", "tool_use_id": "t2", "content": "ok"}),
+                json!({"type": "text", "text": "Done"}),
+            ]
+            .into_iter()
+            .map(|value| serde_json::from_value::<Response>(value).unwrap())
+            .collect::<Vec<_>>(),
+        );
+
+        let between = responses.between("t1", "t2");
+        assert_eq!(between.len(), 2);
+        assert!(between[1].as_thinking().is_some());
+        assert!(responses.between("t2", "t1").is_empty());
+
+        assert_eq!(responses.after_last_tool_result().text_content(), "Done");
+        assert_eq!(
+            responses
+                .texts_after_thinking()
+                .map(TextResponse::content)
+                .collect::<Vec<_>>(),
+            ["Done"]
+        );
+
+        let tools = responses.project(|r| r.as_tool_use().map(|_| r.clone()));
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools.tool_uses().last().unwrap().name(), "Edit");
+    }
+
+    #[test]
+    fn test_from_message_shares_blocks_until_owned() {
+        let message = Message::Assistant(crate::proto::AssistantEnvelope::new(
+            crate::proto::AssistantMessageInner::new(
+                vec![
+                    crate::proto::ContentBlock::text("x".repeat(1 << 16)),
+                    crate::proto::ContentBlock::tool_use("t1", "Read", json!({"path": "a"})),
+                ],
+                "claude-sonnet-4-5",
+            ),
+        ));
+        let Message::Assistant(envelope) = &message else {
+            unreachable!();
+        };
+        let crate::proto::ContentBlock::Text(block) = &envelope.message().content()[0] else {
+            unreachable!();
+        };
+
+        let [text, tool_use] = <[Response; 2]>::try_from(Response::from_message(&message)).unwrap();
+        let shared = text.as_text().unwrap();
+        assert!(Arc::ptr_eq(&shared.inner, block));
+
+        let owned = text.clone().into_owned();
+        let owned = owned.as_text().unwrap();
+        assert!(!Arc::ptr_eq(&owned.inner, block));
+        assert_eq!(owned.content(), shared.content());
+
+        // Once the message is gone the response owns its blocks, so nothing is
+        // copied.
+        drop(message);
+        let tool_use = tool_use.into_tool_use().unwrap();
+        let inner = Arc::as_ptr(&tool_use.inner);
+        let owned = Response::ToolUse(tool_use)
+            .into_owned()
+            .into_tool_use()
+            .unwrap();
+        assert_eq!(Arc::as_ptr(&owned.inner), inner);
+        assert_eq!(owned.input(), &json!({"path": "a"}));
+    }
+
+    #[test]
+    fn test_subagent_responses() {
+        let messages = [
+            json!({"type": "assistant", "message": {"model": "claude-sonnet-4-5", "content": [
+                {"type": "tool_use", "id": "task_1", "name": "Task", "input": {"subagent_type": "reviewer"}}
+            ]}, "parent_tool_use_id": null}),
+            json!({"type": "assistant", "message": {"model": "claude-sonnet-4-5", "content": [
+                {"type": "tool_use", "id": "read_1", "name": "Read", "input": {}}
+            ]}, "parent_tool_use_id": "task_1"}),
+            json!({"type": "user", "message": {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "read_1", "content": "fn main() {}"}
+            ]}, "parent_tool_use_id": "task_1"}),
+            json!({"type": "assistant", "message": {"model": "claude-sonnet-4-5", "content": [
+                {"type": "text", "text": "Looks fine."}
+            ]}, "parent_tool_use_id": "task_1"}),
+            json!({"type": "user", "message": {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "task_1", "content": "Looks fine."}
+            ]}}),
+        ];
+        let mut responses = Responses::new();
+        for message in messages {
+            for response in Response::from_message(&serde_json::from_value(message).unwrap()) {
+                responses.push(response);
+            }
+        }
+
+        let subagent = responses.subagent("task_1");
+        assert_eq!(subagent.len(), 3);
+        assert_eq!(
+            subagent.as_slice()[1]
+                .as_tool_result()
+                .unwrap()
+                .tool_use_id(),
+            "read_1"
+        );
+        assert_eq!(subagent.text_content(), "Looks fine.");
+        assert!(responses.as_slice()[0].parent_tool_use_id().is_none());
+        assert!(responses.subagent("task_2").is_empty());
+    }
+
+    #[test]
+    fn test_timings_from_meta() {
+        let message = Message::Assistant(crate::proto::AssistantEnvelope::new(
+            crate::proto::AssistantMessageInner::new(
+                vec![
+                    crate::proto::ContentBlock::tool_use("t1", "Read", json!({})),
+                    crate::proto::ContentBlock::text("done"),
+                ],
+                "claude-sonnet-4-5",
+            ),
+        ));
+        let [tool_use, text] = <[Response; 2]>::try_from(Response::from_message(&message)).unwrap();
+        let start = Instant::now();
+        let at = |ms| ResponseMeta {
+            sequence: ms,
+            received_at: SystemTime::UNIX_EPOCH,
+            instant: start + Duration::from_millis(ms),
+        };
+
+        let mut responses = Responses::new();
+        responses.set_started(start);
+        responses.push_with_meta(at(100), tool_use);
+        responses.push_with_meta(at(350), text);
+
+        let timings = responses.timings();
+        assert_eq!(
+            timings.time_to_first_text(),
+            Some(Duration::from_millis(350))
+        );
+        assert_eq!(timings.total(), Some(Duration::from_millis(350)));
+        let [round_trip] = timings.tool_round_trips() else {
+            panic!("expected one tool round trip");
+        };
+        assert_eq!(round_trip.name(), "Read");
+        assert_eq!(round_trip.duration(), Duration::from_millis(250));
+        assert_eq!(Responses::new().timings(), Timings::default());
+    }
+
+    #[test]
+    fn test_ndjson_round_trip() {
+        let messages = [
+            json!({
+                "type": "assistant",
+                "message": {
+                    "model": "claude-sonnet-4-5",
+                    "content": [
+                        {"type": "text", "text": "Reading"},
+                        {"type": "tool_use", "id": "t1", "name": "Read", "input": {"path": "a"}}
+                    ]
+                }
+            }),
+            json!({
+                "type": "user",
+                "message": {"content": [{"type": "tool_result", "tool_use_id": "t1", "content": "x"}]}
+            }),
+            json!({
+                "type": "result",
+                "subtype": "success",
+                "duration_ms": 10,
+                "duration_api_ms": 8,
+                "is_error": false,
+                "num_turns": 1,
+                "session_id": "s",
+                "total_cost_usd": 0.5
+            }),
+        ];
+        let mut responses = Responses::from(
+            messages
+                .into_iter()
+                .flat_map(|message| {
+                    Response::from_message(&serde_json::from_value(message).unwrap())
+                })
+                .collect::<Vec<_>>(),
+        );
+        responses.push(Response::Error(ErrorResponse::System("boom".to_owned())));
+
+        let mut buffer = Vec::new();
+        responses.write_ndjson(&mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().count(), 5);
+        assert!(text.starts_with(r#"{"type":"text","text":"Reading"}"#));
+
+        let read = Responses::read_ndjson(format!("{text}\n").as_bytes()).unwrap();
+        assert_eq!(read.len(), 5);
+        assert_eq!(read.tool_uses().next().unwrap().input()["path"], "a");
+        assert_eq!(read.tool_results().next().unwrap().tool_use_id(), "t1");
+        assert_eq!(read.completion().unwrap().total_cost_usd(), Some(0.5));
+        assert_eq!(read.first_error().unwrap().message(), "boom");
+    }
+
+    #[test]
+    fn test_tool_result_error_kind() {
+        let result = |value: Value| serde_json::from_value::<ToolResultResponse>(value).unwrap();
+
+        assert_eq!(
+            result(json!({"tool_use_id": "t", "content": "ok"})).error_kind(),
+            None
+        );
+        assert_eq!(
+            result(json!({
+                "tool_use_id": "t",
+                "content": "cat: x.txt: No such file or directory",
+                "is_error": true
+            }))
+            .error_kind(),
+            Some(ToolErrorKind::NotFound)
+        );
+        assert_eq!(
+            result(json!({
+                "tool_use_id": "t",
+                "content": [{"type": "text", "text": "Command timed out after 2m"}],
+                "is_error": true
+            }))
+            .error_kind(),
+            Some(ToolErrorKind::Timeout)
+        );
+        assert_eq!(
+            result(json!({
+                "tool_use_id": "t",
+                "content": [{
+                    "type": "text",
+                    "text": "not found: config",
+                    "_meta": {ERROR_KIND_META_KEY: "permission_denied"}
+                }],
+                "is_error": true
+            }))
+            .error_kind(),
+            Some(ToolErrorKind::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn test_complete_typed_result_fields() {
+        let complete = complete(json!({
+            "type": "result",
+            "subtype": "success",
+            "duration_ms": 10,
+            "duration_api_ms": 8,
+            "is_error": false,
+            "num_turns": 1,
+            "session_id": "s",
+            "modelUsage": {
+                "claude-sonnet-4-5": {
+                    "inputTokens": 100,
+                    "outputTokens": 20,
+                    "cacheReadInputTokens": 5,
+                    "cacheCreationInputTokens": 0,
+                    "webSearchRequests": 0,
+                    "costUSD": 0.01,
+                    "contextWindow": 200000
+                }
+            },
+            "permission_denials": [
+                {"tool_name": "Bash", "tool_use_id": "t1", "tool_input": {"command": "rm -rf /"}},
+                {"tool_name": "Bash", "tool_use_id": "t2", "tool_input": {}},
+                {"tool_name": "Write", "tool_use_id": "t3", "tool_input": {}}
+            ]
+        }));
+
+        let usage = &complete.model_usage().unwrap()["claude-sonnet-4-5"];
+        assert_eq!(usage.input_tokens(), 100);
+        assert_eq!(usage.output_tokens(), 20);
+        assert_eq!(usage.cost_usd(), 0.01);
+        assert_eq!(usage.context_window(), Some(200000));
+        assert_eq!(complete.permission_denials().len(), 3);
+        assert_eq!(complete.denied_tools(), ["Bash", "Write"]);
+        assert!(!complete.0.extra().contains_key("modelUsage"));
+    }
+
+    #[test]
+    fn test_usage_by_model_sums_completions() {
+        let result = |cost: f64| {
+            json!({
+                "type": "result",
+                "subtype": "success",
+                "duration_ms": 1,
+                "duration_api_ms": 1,
+                "is_error": false,
+                "num_turns": 1,
+                "session_id": "s",
+                "modelUsage": {
+                    "sonnet": {"inputTokens": 10, "outputTokens": 1, "costUSD": cost},
+                    "haiku": {"inputTokens": 5, "outputTokens": 2, "costUSD": 0.001}
+                }
+            })
+        };
+        let responses = Responses::from(vec![
+            Response::Complete(complete(result(0.5))),
+            Response::Complete(complete(result(0.25))),
+        ]);
+
+        let usage = responses.usage_by_model();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage["sonnet"].input_tokens(), 20);
+        assert_eq!(usage["sonnet"].cost_usd(), 0.75);
+        assert_eq!(usage["haiku"].output_tokens(), 4);
     }
 }
@@ -0,0 +1,399 @@
+//! Picking one of several configurations per task.
+//!
+//! A [`Router`] holds [`Route`]s, each an [`Options`] with rules saying which
+//! tasks it takes: by prompt length, by keywords, by how often it failed in a
+//! row and by how much it has spent. Each task goes to the first route whose
+//! rules accept it or, with [`Router::round_robin`], to the accepting routes
+//! in turn. [`Router::run`] starts a client for the chosen route, sends the
+//! task and records how it went; callers driving their own clients or
+//! conversations use [`Router::select`] and report back with
+//! [`Router::record_success`] and [`Router::record_failure`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::router::{Route, Router};
+//! use clauders::{Model, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let router = Router::new()
+//!     .route(
+//!         Route::new("deep", Options::new().model(Model::Opus))
+//!             .keywords(["refactor", "architecture"])
+//!             .cost_ceiling_usd(20.0)
+//!             .max_consecutive_failures(3),
+//!     )
+//!     .route(Route::new("quick", Options::new().model(Model::Haiku)).max_prompt_len(500))
+//!     .route(Route::new("default", Options::new().model(Model::Sonnet)));
+//!
+//! let routed = router.run("Refactor the parser into its own crate").await?;
+//! println!("{} answered: {}", routed.route(), routed.text());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::options::Options;
+use crate::response::Responses;
+
+/// A configuration and the tasks it takes.
+#[derive(Debug, Clone)]
+pub struct Route {
+    name: String,
+    options: Options,
+    min_prompt_len: Option<usize>,
+    max_prompt_len: Option<usize>,
+    keywords: Vec<String>,
+    max_consecutive_failures: Option<u32>,
+    cost_ceiling_usd: Option<f64>,
+}
+
+impl Route {
+    /// A route called `name` that takes every task with `options`.
+    pub fn new(name: impl Into<String>, options: Options) -> Self {
+        Self {
+            name: name.into(),
+            options,
+            min_prompt_len: None,
+            max_prompt_len: None,
+            keywords: Vec::new(),
+            max_consecutive_failures: None,
+            cost_ceiling_usd: None,
+        }
+    }
+
+    /// Takes only prompts of at least `len` characters.
+    #[must_use]
+    pub fn min_prompt_len(mut self, len: usize) -> Self {
+        self.min_prompt_len = Some(len);
+        self
+    }
+
+    /// Takes only prompts of at most `len` characters.
+    #[must_use]
+    pub fn max_prompt_len(mut self, len: usize) -> Self {
+        self.max_prompt_len = Some(len);
+        self
+    }
+
+    /// Takes only prompts containing `keyword`, ignoring case. With several
+    /// keywords, any one of them is enough.
+    #[must_use]
+    pub fn keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.keywords.push(keyword.into().to_lowercase());
+        self
+    }
+
+    #[must_use]
+    pub fn keywords(mut self, keywords: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.keywords
+            .extend(keywords.into_iter().map(|k| k.into().to_lowercase()));
+        self
+    }
+
+    /// Stops taking tasks once it has failed `failures` times in a row,
+    /// until a success is recorded or the router is [reset](Router::reset).
+    #[must_use]
+    pub fn max_consecutive_failures(mut self, failures: u32) -> Self {
+        self.max_consecutive_failures = Some(failures);
+        self
+    }
+
+    /// Stops taking tasks once its runs have cost `ceiling_usd` in total.
+    #[must_use]
+    pub fn cost_ceiling_usd(mut self, ceiling_usd: f64) -> Self {
+        self.cost_ceiling_usd = Some(ceiling_usd);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Whether the route's prompt rules accept `prompt`.
+    fn accepts(&self, prompt: &str) -> bool {
+        let len = prompt.chars().count();
+        if self.min_prompt_len.is_some_and(|min| len < min)
+            || self.max_prompt_len.is_some_and(|max| len > max)
+        {
+            return false;
+        }
+        if self.keywords.is_empty() {
+            return true;
+        }
+        let prompt = prompt.to_lowercase();
+        self.keywords.iter().any(|keyword| prompt.contains(keyword))
+    }
+
+    /// Whether the route's history still lets it take tasks.
+    fn is_available(&self, stats: &RouteStats) -> bool {
+        self.max_consecutive_failures
+            .is_none_or(|max| stats.consecutive_failures < max)
+            && self
+                .cost_ceiling_usd
+                .is_none_or(|ceiling| stats.spent_usd < ceiling)
+    }
+}
+
+/// What a route has done so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RouteStats {
+    runs: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    spent_usd: f64,
+}
+
+impl RouteStats {
+    /// The number of tasks recorded, successful or not.
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// The failures since the last success.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    pub fn spent_usd(&self) -> f64 {
+        self.spent_usd
+    }
+}
+
+/// A task answered through a [`Router`].
+#[derive(Debug, Clone)]
+pub struct Routed {
+    route: String,
+    text: String,
+    responses: Responses,
+}
+
+impl Routed {
+    /// The name of the route that took the task.
+    pub fn route(&self) -> &str {
+        &self.route
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn responses(&self) -> &Responses {
+        &self.responses
+    }
+
+    pub fn into_responses(self) -> Responses {
+        self.responses
+    }
+}
+
+/// Routes tasks to one of several configurations by rules.
+#[derive(Debug, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    round_robin: bool,
+    next: AtomicUsize,
+    stats: Mutex<HashMap<String, RouteStats>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a route, tried after those added before it.
+    #[must_use]
+    pub fn route(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    /// Spreads tasks over every route accepting them in turn, rather than
+    /// giving each to the first.
+    #[must_use]
+    pub fn round_robin(mut self) -> Self {
+        self.round_robin = true;
+        self
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// The route to take `prompt`, or `None` if no route accepts it.
+    pub fn select(&self, prompt: &str) -> Option<&Route> {
+        let stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        let eligible = self
+            .routes
+            .iter()
+            .filter(|route| route.accepts(prompt))
+            .filter(|route| {
+                route.is_available(&stats.get(&route.name).copied().unwrap_or_default())
+            })
+            .collect::<Vec<_>>();
+        if eligible.is_empty() {
+            return None;
+        }
+        let index = if self.round_robin {
+            self.next.fetch_add(1, Ordering::Relaxed) % eligible.len()
+        } else {
+            0
+        };
+        Some(eligible[index])
+    }
+
+    /// Sends `prompt` on a new client for the route that takes it and
+    /// records the outcome.
+    ///
+    /// Fails with [`Error::NoRoute`] if no route accepts the prompt. A turn
+    /// ending in an error result counts as a failure of the route but is
+    /// still returned.
+    pub async fn run(&self, prompt: &str) -> Result<Routed, Error> {
+        let route = self.select(prompt).ok_or(Error::NoRoute)?;
+        let name = route.name.clone();
+        tracing::debug!(route = %name, "routing task");
+
+        let result = async {
+            let client = Client::new(route.options.clone()).await?;
+            client.query_once(prompt).await
+        }
+        .await;
+
+        match result {
+            Ok((text, responses)) => {
+                let completion = responses.completion();
+                let cost_usd = completion
+                    .and_then(|c| c.total_cost_usd())
+                    .unwrap_or_default();
+                if completion.is_some_and(|c| !c.is_error()) {
+                    self.record_success(&name, cost_usd);
+                } else {
+                    self.record(&name, cost_usd, false);
+                }
+                Ok(Routed {
+                    route: name,
+                    text,
+                    responses,
+                })
+            }
+            Err(e) => {
+                self.record_failure(&name);
+                Err(e)
+            }
+        }
+    }
+
+    /// Records a task the route `name` completed at a cost of `cost_usd`.
+    pub fn record_success(&self, name: &str, cost_usd: f64) {
+        self.record(name, cost_usd, true);
+    }
+
+    /// Records a task the route `name` failed.
+    pub fn record_failure(&self, name: &str) {
+        self.record(name, 0.0, false);
+    }
+
+    fn record(&self, name: &str, cost_usd: f64, succeeded: bool) {
+        let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        let stats = stats.entry(name.to_owned()).or_default();
+        stats.runs += 1;
+        stats.spent_usd += cost_usd;
+        if succeeded {
+            stats.consecutive_failures = 0;
+        } else {
+            stats.failures += 1;
+            stats.consecutive_failures += 1;
+        }
+    }
+
+    /// What the route `name` has done so far.
+    pub fn stats(&self, name: &str) -> RouteStats {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Forgets every route's failures and spend.
+    pub fn reset(&self) {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected<'a>(router: &'a Router, prompt: &str) -> Option<&'a str> {
+        router.select(prompt).map(Route::name)
+    }
+
+    #[test]
+    fn test_first_accepting_route_wins() {
+        let router = Router::new()
+            .route(Route::new("deep", Options::new()).keywords(["Refactor", "architecture"]))
+            .route(Route::new("quick", Options::new()).max_prompt_len(20))
+            .route(Route::new("long", Options::new()).min_prompt_len(21));
+
+        assert_eq!(selected(&router, "Please REFACTOR this"), Some("deep"));
+        assert_eq!(selected(&router, "What is 2 + 2?"), Some("quick"));
+        assert_eq!(
+            selected(&router, "Summarise the changes on this branch"),
+            Some("long")
+        );
+        assert!(Router::new().select("anything").is_none());
+    }
+
+    #[test]
+    fn test_failures_and_cost_ceiling_take_routes_out() {
+        let router = Router::new()
+            .route(Route::new("flaky", Options::new()).max_consecutive_failures(2))
+            .route(Route::new("pricey", Options::new()).cost_ceiling_usd(1.0))
+            .route(Route::new("last", Options::new()).max_prompt_len(0));
+
+        router.record_failure("flaky");
+        assert_eq!(selected(&router, "task"), Some("flaky"));
+        router.record_failure("flaky");
+        assert_eq!(selected(&router, "task"), Some("pricey"));
+        assert_eq!(router.stats("flaky").consecutive_failures(), 2);
+
+        router.record_success("pricey", 0.6);
+        router.record_success("pricey", 0.6);
+        assert_eq!(selected(&router, "task"), None);
+
+        router.reset();
+        assert_eq!(selected(&router, "task"), Some("flaky"));
+    }
+
+    #[test]
+    fn test_round_robin_rotates_over_accepting_routes() {
+        let router = Router::new()
+            .route(Route::new("a", Options::new()))
+            .route(Route::new("b", Options::new()).keyword("deploy"))
+            .route(Route::new("c", Options::new()))
+            .round_robin();
+
+        let picks = (0..4)
+            .map(|_| selected(&router, "write docs").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(picks, ["a", "c", "a", "c"]);
+    }
+}
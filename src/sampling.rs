@@ -0,0 +1,156 @@
+//! Model requests made by tools through [`ToolContext::sample`](crate::ToolContext::sample).
+//!
+//! The request is sent to the CLI as an MCP `sampling/createMessage` call,
+//! so tools can use the session's model without credentials of their own.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplingRole {
+    User,
+    Assistant,
+}
+
+/// A text message of the conversation to sample from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingMessage {
+    role: SamplingRole,
+    text: String,
+}
+
+impl SamplingMessage {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: SamplingRole::User,
+            text: text.into(),
+        }
+    }
+
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self {
+            role: SamplingRole::Assistant,
+            text: text.into(),
+        }
+    }
+
+    pub fn role(&self) -> SamplingRole {
+        self.role
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "role": self.role,
+            "content": { "type": "text", "text": self.text },
+        })
+    }
+}
+
+/// How to sample, beyond the messages themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingParams {
+    max_tokens: u32,
+    system_prompt: Option<String>,
+    temperature: Option<f64>,
+    stop_sequences: Vec<String>,
+    model_hints: Vec<String>,
+}
+
+impl SamplingParams {
+    pub fn new(max_tokens: u32) -> Self {
+        Self {
+            max_tokens,
+            system_prompt: None,
+            temperature: None,
+            stop_sequences: Vec::new(),
+            model_hints: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    #[must_use]
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    #[must_use]
+    pub fn stop_sequence(mut self, sequence: impl Into<String>) -> Self {
+        self.stop_sequences.push(sequence.into());
+        self
+    }
+
+    /// Suggests a model, such as `haiku`. The CLI is free to ignore it.
+    #[must_use]
+    pub fn model_hint(mut self, name: impl Into<String>) -> Self {
+        self.model_hints.push(name.into());
+        self
+    }
+
+    pub(crate) fn to_request(&self, messages: &[SamplingMessage]) -> Value {
+        let mut request = json!({
+            "messages": messages.iter().map(SamplingMessage::to_json).collect::<Vec<_>>(),
+            "maxTokens": self.max_tokens,
+        });
+        if let Some(prompt) = &self.system_prompt {
+            request["systemPrompt"] = json!(prompt);
+        }
+        if let Some(temperature) = self.temperature {
+            request["temperature"] = json!(temperature);
+        }
+        if !self.stop_sequences.is_empty() {
+            request["stopSequences"] = json!(self.stop_sequences);
+        }
+        if !self.model_hints.is_empty() {
+            let hints = self
+                .model_hints
+                .iter()
+                .map(|name| json!({ "name": name }))
+                .collect::<Vec<_>>();
+            request["modelPreferences"] = json!({ "hints": hints });
+        }
+        request
+    }
+}
+
+/// The model's reply to a sampling request.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SampledMessage {
+    model: String,
+    #[serde(default)]
+    stop_reason: Option<String>,
+    content: Value,
+}
+
+impl SampledMessage {
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn stop_reason(&self) -> Option<&str> {
+        self.stop_reason.as_deref()
+    }
+
+    /// The reply's text, or an empty string if it is not text.
+    pub fn text(&self) -> &str {
+        self.content
+            .get("text")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    }
+
+    pub fn content(&self) -> &Value {
+        &self.content
+    }
+}
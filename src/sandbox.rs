@@ -0,0 +1,99 @@
+//! OS-level sandboxing for the CLI's Bash tool.
+//!
+//! The settings are passed to the CLI through `--settings`, which runs shell
+//! commands inside a filesystem and network sandbox where the platform
+//! supports it.
+
+use serde::Serialize;
+
+/// Sandbox settings for the CLI's Bash tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sandbox {
+    enabled: bool,
+    auto_allow_bash_if_sandboxed: bool,
+    allow_unsandboxed_commands: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    excluded_commands: Vec<String>,
+    network: SandboxNetwork,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SandboxNetwork {
+    allow_local_binding: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    allow_unix_sockets: Vec<String>,
+}
+
+impl Sandbox {
+    /// Sandboxes every command and never falls back to running one outside
+    /// the sandbox. Commands still go through the usual permission checks.
+    pub fn strict() -> Self {
+        Self {
+            enabled: true,
+            auto_allow_bash_if_sandboxed: false,
+            allow_unsandboxed_commands: false,
+            excluded_commands: Vec::new(),
+            network: SandboxNetwork::default(),
+        }
+    }
+
+    /// Sandboxes commands and approves them without prompting, since the
+    /// sandbox bounds what they can reach.
+    pub fn auto_allow() -> Self {
+        Self {
+            auto_allow_bash_if_sandboxed: true,
+            ..Self::strict()
+        }
+    }
+
+    // Getters
+    pub fn auto_allow_bash_if_sandboxed(&self) -> bool {
+        self.auto_allow_bash_if_sandboxed
+    }
+
+    pub fn allow_unsandboxed_commands(&self) -> bool {
+        self.allow_unsandboxed_commands
+    }
+
+    pub fn excluded_commands(&self) -> &[String] {
+        &self.excluded_commands
+    }
+
+    // Builders
+    /// Lets commands that fail inside the sandbox be retried outside it,
+    /// subject to the usual permission checks.
+    #[must_use]
+    pub fn with_unsandboxed_fallback(mut self, allowed: bool) -> Self {
+        self.allow_unsandboxed_commands = allowed;
+        self
+    }
+
+    /// Runs commands starting with `command` outside the sandbox.
+    #[must_use]
+    pub fn with_excluded_command(mut self, command: impl Into<String>) -> Self {
+        self.excluded_commands.push(command.into());
+        self
+    }
+
+    /// Lets sandboxed commands listen on local ports.
+    #[must_use]
+    pub fn with_local_binding(mut self, allowed: bool) -> Self {
+        self.network.allow_local_binding = allowed;
+        self
+    }
+
+    /// Lets sandboxed commands connect to the Unix socket at `path`.
+    #[must_use]
+    pub fn with_unix_socket(mut self, path: impl Into<String>) -> Self {
+        self.network.allow_unix_sockets.push(path.into());
+        self
+    }
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
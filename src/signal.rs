@@ -0,0 +1,153 @@
+//! Interrupting turns from the terminal.
+//!
+//! [`interrupt_on_ctrl_c`] makes Ctrl-C interrupt the running turn, as it
+//! does in the interactive CLI, and a second Ctrl-C during the same turn kill
+//! the CLI. The client's reader holds the transport while it waits for the
+//! CLI, so the signal handler never writes to the CLI itself: it leaves a
+//! request that the reader acts on, dropping its wait to do so.
+//!
+//! ```no_run
+//! use clauders::{Client, Options, signal};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let client = Client::new(Options::new()).await?;
+//! let _guard = signal::interrupt_on_ctrl_c(&client);
+//! let (text, _) = client.query_once("Refactor the parser").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::client::Client;
+
+/// What the client's reader has been asked to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signal {
+    /// Interrupt the running turn.
+    Interrupt,
+    /// Kill the CLI.
+    ForceClose,
+}
+
+/// Requests for the client's reader, made from outside it.
+#[derive(Debug, Default)]
+pub(crate) struct Signals {
+    interrupt: AtomicBool,
+    force_close: AtomicBool,
+    turns_completed: AtomicU64,
+    wake: Notify,
+}
+
+impl Signals {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn request(&self, flag: &AtomicBool) {
+        flag.store(true, Ordering::Release);
+        self.wake.notify_one();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn request_interrupt(&self) {
+        self.request(&self.interrupt);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn turns_completed(&self) -> u64 {
+        self.turns_completed.load(Ordering::Acquire)
+    }
+
+    /// Forgets an interrupt requested while no turn was running.
+    pub(crate) fn turn_started(&self) {
+        self.interrupt.store(false, Ordering::Release);
+    }
+
+    pub(crate) fn turn_completed(&self) {
+        self.turns_completed.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Waits for the next request. Cancellation safe.
+    pub(crate) async fn next(&self) -> Signal {
+        loop {
+            let notified = self.wake.notified();
+            if self.force_close.swap(false, Ordering::AcqRel) {
+                return Signal::ForceClose;
+            }
+            if self.interrupt.swap(false, Ordering::AcqRel) {
+                return Signal::Interrupt;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Stops handling Ctrl-C when dropped.
+///
+/// Once a Ctrl-C handler has been installed, Ctrl-C no longer terminates the
+/// program, even after the guard is dropped.
+#[derive(Debug)]
+#[must_use = "Ctrl-C is handled only while the guard is alive"]
+pub struct CtrlCGuard {
+    task: JoinHandle<()>,
+}
+
+impl Drop for CtrlCGuard {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Interrupts `client`'s running turn on Ctrl-C, and kills its CLI on a
+/// second Ctrl-C before the turn ends.
+///
+/// The request is carried out by whichever task is receiving the client's
+/// responses, or the next one to, so the turn ends on its stream as usual.
+/// An interrupt requested between turns is dropped when the next one starts.
+///
+/// # Panics
+///
+/// Panics if called outside a Tokio runtime.
+pub fn interrupt_on_ctrl_c(client: &Client) -> CtrlCGuard {
+    let signals = client.signals();
+    let task = tokio::spawn(async move {
+        let mut interrupted_turn = None;
+        while tokio::signal::ctrl_c().await.is_ok() {
+            let turn = signals.turns_completed.load(Ordering::Acquire);
+            if interrupted_turn == Some(turn) {
+                tracing::warn!("second Ctrl-C, killing the claude CLI");
+                signals.request(&signals.force_close);
+            } else {
+                tracing::info!("Ctrl-C, interrupting the turn");
+                interrupted_turn = Some(turn);
+                signals.request(&signals.interrupt);
+            }
+        }
+    });
+    CtrlCGuard { task }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_signals_wake_reader_and_stale_interrupts_are_dropped() {
+        let signals = Signals::new();
+        signals.request(&signals.interrupt);
+        signals.request(&signals.force_close);
+        assert_eq!(signals.next().await, Signal::ForceClose);
+        assert_eq!(signals.next().await, Signal::Interrupt);
+
+        signals.request(&signals.interrupt);
+        signals.turn_started();
+        let waited = tokio::time::timeout(Duration::from_millis(20), signals.next()).await;
+        assert!(waited.is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Detection of turns that keep calling tools without making progress.
+//!
+//! A turn is considered stalled when Claude produces a run of tool calls and
+//! results without writing any text, either for too many events or for too
+//! long. Configure detection per turn with
+//! [`TurnBuilder::detect_stalls`](crate::TurnBuilder::detect_stalls).
+
+use std::time::{Duration, Instant};
+
+use crate::response::Response;
+
+/// What a conversation turn does when it detects a stall.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum StallAction {
+    /// Only reports the stall through [`TurnBuilder::on_stalled`](crate::TurnBuilder::on_stalled).
+    #[default]
+    Notify,
+    /// Sends a user message to steer Claude while the turn continues.
+    Steer(String),
+    /// Interrupts the turn.
+    Interrupt,
+}
+
+/// Thresholds for deciding a turn has stalled, and how to react.
+///
+/// A stall is reported once either threshold is reached; the counters then
+/// reset, so a turn that keeps spinning is reported again.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StallPolicy {
+    max_events: Option<u32>,
+    max_duration: Option<Duration>,
+    action: StallAction,
+}
+
+impl StallPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports a stall after `events` tool calls and results without text.
+    #[must_use]
+    pub fn max_events(mut self, events: u32) -> Self {
+        self.max_events = Some(events);
+        self
+    }
+
+    /// Reports a stall when no text has been produced for `duration`.
+    ///
+    /// Checked as tool events arrive, so a single long-running tool call is
+    /// not reported until it returns.
+    #[must_use]
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    #[must_use]
+    pub fn action(mut self, action: StallAction) -> Self {
+        self.action = action;
+        self
+    }
+}
+
+/// Reported when a turn stalls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stalled {
+    events: u32,
+    elapsed: Duration,
+}
+
+impl Stalled {
+    /// The number of tool calls and results since Claude last wrote text.
+    pub fn events(&self) -> u32 {
+        self.events
+    }
+
+    /// The time since Claude last wrote text, or since the turn started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Tracks tool activity since the last text response.
+#[derive(Debug)]
+pub(crate) struct StallDetector {
+    policy: StallPolicy,
+    events: u32,
+    since: Instant,
+}
+
+impl StallDetector {
+    pub(crate) fn new(policy: StallPolicy, now: Instant) -> Self {
+        Self {
+            policy,
+            events: 0,
+            since: now,
+        }
+    }
+
+    pub(crate) fn action(&self) -> &StallAction {
+        &self.policy.action
+    }
+
+    /// Records a response, returning a stall if a threshold has been reached.
+    pub(crate) fn observe(&mut self, response: &Response, now: Instant) -> Option<Stalled> {
+        match response {
+            Response::Text(_) => {
+                self.events = 0;
+                self.since = now;
+                None
+            }
+            Response::ToolUse(_) | Response::ToolResult(_) => {
+                self.events += 1;
+                let elapsed = now.saturating_duration_since(self.since);
+                let stalled = self.policy.max_events.is_some_and(|max| self.events >= max)
+                    || self.policy.max_duration.is_some_and(|max| elapsed >= max);
+                if !stalled {
+                    return None;
+                }
+                let stall = Stalled {
+                    events: self.events,
+                    elapsed,
+                };
+                self.events = 0;
+                self.since = now;
+                Some(stall)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::proto::ContentBlock;
+    use crate::proto::message::{AssistantEnvelope, AssistantMessageInner, Message};
+
+    #[test]
+    fn test_stall_detector_resets_on_text() {
+        let message = Message::Assistant(AssistantEnvelope::new(AssistantMessageInner::new(
+            vec![
+                ContentBlock::text("progress"),
+                ContentBlock::tool_use("id", "Read", json!({})),
+            ],
+            "claude-sonnet-4-5",
+        )));
+        let [text, tool_use] = <[Response; 2]>::try_from(Response::from_message(&message)).unwrap();
+        let start = Instant::now();
+        let mut detector = StallDetector::new(StallPolicy::new().max_events(3), start);
+
+        assert!(detector.observe(&tool_use, start).is_none());
+        assert!(detector.observe(&tool_use, start).is_none());
+        assert!(detector.observe(&text, start).is_none());
+        assert!(detector.observe(&tool_use, start).is_none());
+        assert!(detector.observe(&tool_use, start).is_none());
+        let stall = detector.observe(&tool_use, start).unwrap();
+        assert_eq!(stall.events(), 3);
+        assert!(detector.observe(&tool_use, start).is_none());
+
+        let mut detector = StallDetector::new(
+            StallPolicy::new().max_duration(Duration::from_secs(30)),
+            start,
+        );
+        let later = start + Duration::from_secs(31);
+        assert!(detector.observe(&tool_use, start).is_none());
+        assert_eq!(
+            detector.observe(&tool_use, later).unwrap().elapsed(),
+            Duration::from_secs(31)
+        );
+    }
+}
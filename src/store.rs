@@ -0,0 +1,487 @@
+//! Local analytics over past sessions, kept in SQLite.
+//!
+//! With the `store` feature, a [`SessionStore`] records each turn's prompt,
+//! responses, token usage, cost and tool calls in a SQLite database with a
+//! fixed schema, so spend and tool usage can be queried without building a
+//! pipeline first. The tables (`sessions`, `turns`, `responses` and
+//! `tool_calls`) are plain SQL and can also be queried directly.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::store::SessionStore;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let store = SessionStore::open(".agent/sessions.db")?;
+//! let client = Client::new(Options::new()).await?;
+//! let mut conversation = client.conversation();
+//! conversation.say("Summarize the README").await?;
+//!
+//! for turn in conversation.history() {
+//!     store.record_turn("docs-bot", turn).await?;
+//! }
+//! for (project, spend) in store.spend_by_project().await? {
+//!     println!("{project}: ${spend:.2}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::conversation::Turn;
+use crate::error::Error;
+use crate::response::{Response, Responses};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        session_id TEXT PRIMARY KEY,
+        project TEXT NOT NULL,
+        model TEXT,
+        started_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS turns (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT NOT NULL REFERENCES sessions (session_id),
+        project TEXT NOT NULL,
+        prompt TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        duration_ms INTEGER,
+        num_turns INTEGER,
+        cost_usd REAL,
+        input_tokens INTEGER,
+        output_tokens INTEGER,
+        cache_creation_input_tokens INTEGER,
+        cache_read_input_tokens INTEGER,
+        is_error INTEGER NOT NULL,
+        result TEXT
+    );
+    CREATE TABLE IF NOT EXISTS responses (
+        turn_id INTEGER NOT NULL REFERENCES turns (id),
+        position INTEGER NOT NULL,
+        type TEXT NOT NULL,
+        body TEXT NOT NULL,
+        PRIMARY KEY (turn_id, position)
+    );
+    CREATE TABLE IF NOT EXISTS tool_calls (
+        turn_id INTEGER NOT NULL REFERENCES turns (id),
+        tool_use_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        input TEXT NOT NULL,
+        is_error INTEGER,
+        error_kind TEXT
+    );
+    CREATE INDEX IF NOT EXISTS turns_started_at ON turns (started_at);
+    CREATE INDEX IF NOT EXISTS turns_project ON turns (project);
+";
+
+/// A session's totals across its recorded turns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    session_id: String,
+    project: String,
+    model: Option<String>,
+    started_at: SystemTime,
+    updated_at: SystemTime,
+    turns: u64,
+    cost_usd: f64,
+}
+
+impl SessionSummary {
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn project(&self) -> &str {
+        &self.project
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    /// When the session's most recent turn was recorded as starting.
+    pub fn updated_at(&self) -> SystemTime {
+        self.updated_at
+    }
+
+    pub fn turns(&self) -> u64 {
+        self.turns
+    }
+
+    pub fn cost_usd(&self) -> f64 {
+        self.cost_usd
+    }
+}
+
+/// How often a tool was called and how often it failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolUsage {
+    name: String,
+    calls: u64,
+    errors: u64,
+}
+
+impl ToolUsage {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+}
+
+/// Turns recorded in a SQLite database.
+///
+/// Statements run on a blocking thread. The store is cheap to clone; clones
+/// share the connection.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SessionStore {
+    /// Opens the database at `path`, creating it and its tables if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    /// Uses an existing connection, creating the tables if needed.
+    pub fn new(connection: Connection) -> Result<Self, Error> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// Records a turn of a conversation under `project`, returning its row id.
+    pub async fn record_turn(&self, project: &str, turn: &Turn) -> Result<i64, Error> {
+        self.record(project, &turn.prompt, &turn.responses).await
+    }
+
+    /// Records the responses to `prompt` under `project`, returning the
+    /// turn's row id. Turns without a session id, such as ones that failed
+    /// before the CLI started a session, are recorded under an empty one.
+    pub async fn record(
+        &self,
+        project: &str,
+        prompt: &str,
+        responses: &Responses,
+    ) -> Result<i64, Error> {
+        let project = project.to_owned();
+        let prompt = prompt.to_owned();
+        let responses = responses.clone();
+        self.blocking(move |connection| insert_turn(connection, &project, &prompt, &responses))
+            .await
+    }
+
+    /// Sessions with a turn started in `from..to`, most recent first.
+    pub async fn sessions_between(
+        &self,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Result<Vec<SessionSummary>, Error> {
+        let (from, to) = (unix_secs(from), unix_secs(to));
+        self.blocking(move |connection| {
+            let mut stmt = connection.prepare(
+                "SELECT s.session_id, s.project, s.model, s.started_at, s.updated_at,
+                        COUNT(t.id), COALESCE(SUM(t.cost_usd), 0)
+                 FROM sessions s JOIN turns t ON t.session_id = s.session_id
+                 WHERE s.session_id IN
+                     (SELECT session_id FROM turns WHERE started_at >= ?1 AND started_at < ?2)
+                 GROUP BY s.session_id
+                 ORDER BY s.updated_at DESC",
+            )?;
+            let rows = stmt.query_map(params![from, to], |row| {
+                Ok(SessionSummary {
+                    session_id: row.get(0)?,
+                    project: row.get(1)?,
+                    model: row.get(2)?,
+                    started_at: from_unix_secs(row.get(3)?),
+                    updated_at: from_unix_secs(row.get(4)?),
+                    turns: count(row.get(5)?),
+                    cost_usd: row.get(6)?,
+                })
+            })?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    /// Total spend in USD per project, highest first.
+    pub async fn spend_by_project(&self) -> Result<Vec<(String, f64)>, Error> {
+        self.blocking(|connection| {
+            let mut stmt = connection.prepare(
+                "SELECT project, COALESCE(SUM(cost_usd), 0) AS spend FROM turns
+                 GROUP BY project ORDER BY spend DESC",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    /// Total spend in USD on `project`.
+    pub async fn project_spend(&self, project: &str) -> Result<f64, Error> {
+        let project = project.to_owned();
+        self.blocking(move |connection| {
+            let spend = connection
+                .query_row(
+                    "SELECT SUM(cost_usd) FROM turns WHERE project = ?1",
+                    [project],
+                    |row| row.get::<_, Option<f64>>(0),
+                )
+                .optional()?;
+            Ok(spend.flatten().unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Calls and failures per tool, most called first.
+    pub async fn tool_usage(&self) -> Result<Vec<ToolUsage>, Error> {
+        self.blocking(|connection| {
+            let mut stmt = connection.prepare(
+                "SELECT name, COUNT(*) AS calls, COALESCE(SUM(is_error), 0) FROM tool_calls
+                 GROUP BY name ORDER BY calls DESC, name",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok(ToolUsage {
+                    name: row.get(0)?,
+                    calls: count(row.get(1)?),
+                    errors: count(row.get(2)?),
+                })
+            })?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn blocking<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap_or_else(PoisonError::into_inner);
+            f(&mut connection)
+        })
+        .await
+        .map_err(|e| Error::ProcessError(crate::util::join_error_message(e)))?
+    }
+}
+
+fn insert_turn(
+    connection: &mut Connection,
+    project: &str,
+    prompt: &str,
+    responses: &Responses,
+) -> Result<i64, Error> {
+    let complete = responses.completion();
+    let init = responses.init();
+    let session_id = complete
+        .map(|complete| complete.session_id())
+        .or_else(|| init.and_then(|init| init.session_id()))
+        .unwrap_or_default();
+    let model = init.and_then(|init| init.model());
+    let started_at = unix_secs(
+        responses
+            .meta(0)
+            .map_or_else(SystemTime::now, |meta| meta.received_at()),
+    );
+    let usage = complete.and_then(|complete| complete.usage());
+
+    let tx = connection.transaction()?;
+    tx.execute(
+        "INSERT INTO sessions (session_id, project, model, started_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT (session_id) DO UPDATE SET
+             model = COALESCE(excluded.model, model),
+             updated_at = MAX(updated_at, excluded.updated_at)",
+        params![session_id, project, model, started_at],
+    )?;
+    tx.execute(
+        "INSERT INTO turns (session_id, project, prompt, started_at, duration_ms, num_turns,
+             cost_usd, input_tokens, output_tokens, cache_creation_input_tokens,
+             cache_read_input_tokens, is_error, result)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            session_id,
+            project,
+            prompt,
+            started_at,
+            complete.map(|complete| complete.duration_ms()),
+            complete.map(|complete| complete.num_turns()),
+            complete.and_then(|complete| complete.total_cost_usd()),
+            usage.and_then(|usage| usage.input_tokens()),
+            usage.and_then(|usage| usage.output_tokens()),
+            usage.and_then(|usage| usage.cache_creation_input_tokens()),
+            usage.and_then(|usage| usage.cache_read_input_tokens()),
+            complete.is_none_or(|complete| complete.is_error()) || responses.has_error(),
+            complete.and_then(|complete| complete.result_text()),
+        ],
+    )?;
+    let turn_id = tx.last_insert_rowid();
+
+    for (position, response) in (0_i64..).zip(responses.iter()) {
+        let body = serde_json::to_value(response)?;
+        let kind = body["type"].as_str().unwrap_or_default().to_owned();
+        tx.execute(
+            "INSERT INTO responses (turn_id, position, type, body) VALUES (?1, ?2, ?3, ?4)",
+            params![turn_id, position, kind, body.to_string()],
+        )?;
+    }
+
+    for tool_use in responses.tool_uses() {
+        let result = responses.iter().find_map(|response| match response {
+            Response::ToolResult(result) if result.tool_use_id() == tool_use.id() => Some(result),
+            _ => None,
+        });
+        tx.execute(
+            "INSERT INTO tool_calls (turn_id, tool_use_id, name, input, is_error, error_kind)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                turn_id,
+                tool_use.id(),
+                tool_use.name(),
+                tool_use.input().to_string(),
+                result.map(|result| result.is_error()),
+                result
+                    .and_then(|result| result.error_kind())
+                    .map(|kind| kind.as_str()),
+            ],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(turn_id)
+}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |since| {
+        i64::try_from(since.as_secs()).unwrap_or(i64::MAX)
+    })
+}
+
+fn count(n: i64) -> u64 {
+    u64::try_from(n).unwrap_or_default()
+}
+
+fn from_unix_secs(secs: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::proto::Message;
+
+    fn responses(session: &str, cost: f64, error: bool) -> Responses {
+        let messages = [
+            json!({
+                "type": "assistant",
+                "message": {
+                    "model": "claude-sonnet-4-5",
+                    "content": [{"type": "tool_use", "id": "t1", "name": "Bash", "input": {}}]
+                }
+            }),
+            json!({
+                "type": "user",
+                "message": {
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": "t1",
+                        "content": if error { "Permission denied" } else { "ok" },
+                        "is_error": error
+                    }]
+                }
+            }),
+            json!({
+                "type": "result",
+                "subtype": "success",
+                "duration_ms": 10,
+                "duration_api_ms": 8,
+                "is_error": false,
+                "num_turns": 1,
+                "session_id": session,
+                "total_cost_usd": cost,
+                "usage": {"input_tokens": 100, "output_tokens": 20}
+            }),
+        ];
+        Responses::from(
+            messages
+                .into_iter()
+                .flat_map(|message| {
+                    Response::from_message(&serde_json::from_value::<Message>(message).unwrap())
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_store_records_turns() {
+        let store = SessionStore::open_in_memory().unwrap();
+        store
+            .record("api", "one", &responses("s1", 0.25, false))
+            .await
+            .unwrap();
+        store
+            .record("api", "two", &responses("s1", 0.5, true))
+            .await
+            .unwrap();
+        store
+            .record("docs", "three", &responses("s2", 0.1, false))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.spend_by_project().await.unwrap(),
+            vec![("api".to_owned(), 0.75), ("docs".to_owned(), 0.1)]
+        );
+        assert_eq!(store.project_spend("docs").await.unwrap(), 0.1);
+        assert_eq!(store.project_spend("none").await.unwrap(), 0.0);
+
+        let now = SystemTime::now();
+        let sessions = store
+            .sessions_between(now - Duration::from_secs(60), now + Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 2);
+        let api = sessions.iter().find(|s| s.session_id() == "s1").unwrap();
+        assert_eq!((api.turns(), api.cost_usd()), (2, 0.75));
+        assert!(
+            store
+                .sessions_between(UNIX_EPOCH, UNIX_EPOCH + Duration::from_secs(1))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let usage = store.tool_usage().await.unwrap();
+        assert_eq!(
+            (usage[0].name(), usage[0].calls(), usage[0].errors()),
+            ("Bash", 3, 1)
+        );
+    }
+}
@@ -0,0 +1,504 @@
+//! Checking structured output against the schema it was asked for.
+//!
+//! The CLI asks the model to follow the schema but does not enforce it, so
+//! output can arrive with numbers as strings, enum values in the wrong case
+//! or fields missing. [`TurnBuilder::send_as_validated`] repairs what it
+//! safely can and reports the rest in a [`Validated`], so a pipeline can send
+//! anything short of an exact match to a person instead of trusting it.
+//!
+//! ```no_run
+//! use clauders::{Client, Options};
+//! use schemars::JsonSchema;
+//! use serde::Deserialize;
+//!
+//! #[derive(Debug, Deserialize, JsonSchema)]
+//! struct Invoice {
+//!     number: String,
+//!     total_cents: u64,
+//! }
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let client = Client::new(Options::new().with_json_schema::<Invoice>()).await?;
+//! let mut conv = client.conversation();
+//! let invoice = conv
+//!     .turn("Extract the invoice from invoice.txt")
+//!     .send_as_validated::<Invoice>()
+//!     .await?;
+//! if !invoice.is_exact() {
+//!     eprintln!("needs review: {:?}", invoice.violations());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`TurnBuilder::send_as_validated`]: crate::TurnBuilder::send_as_validated
+
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::Error;
+
+/// Structured output with how well it matched its schema.
+#[derive(Debug, Clone)]
+pub struct Validated<T> {
+    value: T,
+    raw: Value,
+    violations: Vec<Violation>,
+    coercions: Vec<Coercion>,
+}
+
+impl<T> Validated<T> {
+    /// The output, after coercions.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// The output as the model produced it.
+    pub fn raw(&self) -> &Value {
+        &self.raw
+    }
+
+    /// Where the output, after coercions, still breaks the schema.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// The values that were converted to the type the schema asked for.
+    pub fn coercions(&self) -> &[Coercion] {
+        &self.coercions
+    }
+
+    /// Whether the output matches the schema once coerced.
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Whether the output matched the schema without any coercion.
+    pub fn is_exact(&self) -> bool {
+        self.is_valid() && self.coercions.is_empty()
+    }
+}
+
+/// A place where output does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    path: String,
+    message: String,
+}
+
+impl Violation {
+    /// The JSON pointer to the offending value, empty for the root.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{path}: {}", self.message)
+    }
+}
+
+/// A value converted to the type its schema asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coercion {
+    path: String,
+    from: Value,
+    to: Value,
+}
+
+impl Coercion {
+    /// The JSON pointer to the converted value, empty for the root.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn from(&self) -> &Value {
+        &self.from
+    }
+
+    pub fn to(&self) -> &Value {
+        &self.to
+    }
+}
+
+/// Checks `raw` against `T`'s schema, coercing what it can, and
+/// deserializes the result.
+pub(crate) fn validate<T>(raw: Value) -> Result<Validated<T>, Error>
+where
+    T: DeserializeOwned + JsonSchema,
+{
+    let schema = crate::util::schema_for_structured_output::<T>();
+    let mut checker = Checker {
+        root: &schema,
+        violations: Vec::new(),
+        coercions: Vec::new(),
+    };
+    let mut value = raw.clone();
+    checker.check(&schema, &mut value, String::new());
+
+    let Checker {
+        violations,
+        coercions,
+        ..
+    } = checker;
+    match serde_json::from_value(value) {
+        Ok(value) => Ok(Validated {
+            value,
+            raw,
+            violations,
+            coercions,
+        }),
+        Err(source) => Err(Error::StructuredOutputRejected {
+            raw,
+            violations,
+            source,
+        }),
+    }
+}
+
+struct Checker<'s> {
+    root: &'s Value,
+    violations: Vec<Violation>,
+    coercions: Vec<Coercion>,
+}
+
+impl<'s> Checker<'s> {
+    fn check(&mut self, schema: &'s Value, value: &mut Value, path: String) {
+        let schema = self.resolve(schema);
+
+        for key in ["anyOf", "oneOf"] {
+            if let Some(alternatives) = schema.get(key).and_then(Value::as_array) {
+                self.check_alternatives(alternatives, value, &path);
+            }
+        }
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for part in all {
+                self.check(part, value, path.clone());
+            }
+        }
+
+        if let Some(types) = schema.get("type") {
+            let types = match types {
+                Value::String(kind) => vec![kind.as_str()],
+                Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|kind| is_type(value, kind)) {
+                match types.iter().find_map(|kind| coerce(value, kind)) {
+                    Some(coerced) => self.coerce(value, coerced, &path),
+                    None => {
+                        let message =
+                            format!("expected {}, found {}", types.join(" or "), type_of(value));
+                        self.violate(&path, message);
+                        return;
+                    }
+                }
+            }
+        }
+
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+            && !allowed.contains(value)
+        {
+            let same_but_case = value.as_str().and_then(|found| {
+                allowed
+                    .iter()
+                    .find(|a| a.as_str().is_some_and(|a| a.eq_ignore_ascii_case(found)))
+            });
+            match same_but_case {
+                Some(allowed) => self.coerce(value, allowed.clone(), &path),
+                None => {
+                    let allowed = allowed.iter().map(Value::to_string).collect::<Vec<_>>();
+                    self.violate(
+                        &path,
+                        format!("{value} is not one of {}", allowed.join(", ")),
+                    );
+                }
+            }
+        }
+
+        self.check_bounds(schema, value, &path);
+
+        if let Value::Object(object) = value {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for name in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !object.contains_key(name) {
+                    self.violate(&path, format!("missing required property '{name}'"));
+                }
+            }
+            for (name, property) in object.iter_mut() {
+                match properties.and_then(|p| p.get(name)) {
+                    Some(property_schema) => {
+                        self.check(property_schema, property, format!("{path}/{name}"));
+                    }
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        self.violate(&path, format!("unexpected property '{name}'"));
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+            for (i, item) in items.iter_mut().enumerate() {
+                self.check(item_schema, item, format!("{path}/{i}"));
+            }
+        }
+    }
+
+    /// Checks the limits schemars emits for `range` and `length` attributes.
+    fn check_bounds(&mut self, schema: &Value, value: &Value, path: &str) {
+        let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+        let (size, min_key, max_key) = match value {
+            Value::Number(n) => (n.as_f64(), "minimum", "maximum"),
+            Value::String(s) => (Some(s.chars().count() as f64), "minLength", "maxLength"),
+            Value::Array(items) => (Some(items.len() as f64), "minItems", "maxItems"),
+            _ => (None, "", ""),
+        };
+        let Some(size) = size else {
+            return;
+        };
+        if let Some(min) = bound(min_key)
+            && size < min
+        {
+            self.violate(path, format!("{value} is below the {min_key} of {min}"));
+        }
+        if let Some(max) = bound(max_key)
+            && size > max
+        {
+            self.violate(path, format!("{value} is above the {max_key} of {max}"));
+        }
+    }
+
+    /// Keeps the first alternative the value matches, coerced if need be.
+    fn check_alternatives(&mut self, alternatives: &'s [Value], value: &mut Value, path: &str) {
+        let mut coerced = None;
+        for alternative in alternatives {
+            let mut attempt = Checker {
+                root: self.root,
+                violations: Vec::new(),
+                coercions: Vec::new(),
+            };
+            let mut candidate = value.clone();
+            attempt.check(alternative, &mut candidate, path.to_owned());
+            if !attempt.violations.is_empty() {
+                continue;
+            }
+            if attempt.coercions.is_empty() {
+                return;
+            }
+            coerced.get_or_insert((candidate, attempt.coercions));
+        }
+        match coerced {
+            Some((candidate, coercions)) => {
+                *value = candidate;
+                self.coercions.extend(coercions);
+            }
+            None => self.violate(path, "matches none of the allowed shapes".to_owned()),
+        }
+    }
+
+    /// Follows a `$ref` into the root's definitions.
+    fn resolve(&self, schema: &'s Value) -> &'s Value {
+        schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix("#/"))
+            .and_then(|pointer| self.root.pointer(&format!("/{pointer}")))
+            .unwrap_or(schema)
+    }
+
+    fn coerce(&mut self, value: &mut Value, to: Value, path: &str) {
+        self.coercions.push(Coercion {
+            path: path.to_owned(),
+            from: std::mem::replace(value, to.clone()),
+            to,
+        });
+    }
+
+    fn violate(&mut self, path: &str, message: String) {
+        self.violations.push(Violation {
+            path: path.to_owned(),
+            message,
+        });
+    }
+}
+
+fn is_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// `value` as `kind`, for conversions that lose nothing.
+fn coerce(value: &Value, kind: &str) -> Option<Value> {
+    match (kind, value) {
+        ("integer", Value::String(s)) => s.trim().parse::<i64>().ok().map(Value::from),
+        ("number", Value::String(s)) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .map(Value::from),
+        ("boolean", Value::String(s)) => match s.trim() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        ("string", Value::Number(n)) => Some(Value::String(n.to_string())),
+        ("string", Value::Bool(b)) => Some(Value::String(b.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Priority {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct Ticket {
+        title: String,
+        estimate: u32,
+        priority: Priority,
+        labels: Vec<String>,
+        assignee: Option<String>,
+    }
+
+    #[test]
+    fn test_exact_output_is_exact() {
+        let raw = json!({
+            "title": "Fix login",
+            "estimate": 3,
+            "priority": "high",
+            "labels": ["auth"],
+            "assignee": null
+        });
+        let ticket = validate::<Ticket>(raw.clone()).unwrap();
+        assert!(ticket.is_exact());
+        assert_eq!(ticket.raw(), &raw);
+        assert_eq!(ticket.value().priority, Priority::High);
+    }
+
+    #[test]
+    fn test_lossless_mismatches_are_coerced() {
+        let ticket = validate::<Ticket>(json!({
+            "title": "Fix login",
+            "estimate": "3",
+            "priority": "High",
+            "labels": [42],
+            "assignee": null
+        }))
+        .unwrap();
+        assert!(ticket.is_valid());
+        assert!(!ticket.is_exact());
+        let paths = ticket
+            .coercions()
+            .iter()
+            .map(Coercion::path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/estimate", "/labels/0", "/priority"]);
+        assert_eq!(ticket.value().estimate, 3);
+        assert_eq!(ticket.value().labels, ["42"]);
+        assert_eq!(ticket.raw()["estimate"], "3");
+    }
+
+    #[test]
+    fn test_violations_are_reported() {
+        #[derive(Debug, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Review {
+            #[schemars(range(min = 1, max = 5))]
+            rating: u8,
+            #[schemars(length(min = 1))]
+            summary: String,
+        }
+
+        let review = validate::<Review>(json!({"rating": 9, "summary": ""})).unwrap();
+        assert!(!review.is_valid());
+        assert_eq!(
+            review
+                .violations()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "/rating: 9 is above the maximum of 5",
+                "/summary: \"\" is below the minLength of 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreadable_output_keeps_the_report() {
+        let err = validate::<Ticket>(json!({"title": "Fix login", "estimate": "soon"}));
+        let Err(Error::StructuredOutputRejected {
+            raw, violations, ..
+        }) = err
+        else {
+            panic!("expected the output to be rejected");
+        };
+        assert_eq!(raw["estimate"], "soon");
+        assert!(
+            violations
+                .iter()
+                .any(|v| v.path() == "/estimate" && v.message() == "expected integer, found string")
+        );
+        assert!(
+            violations
+                .iter()
+                .any(|v| v.message() == "missing required property 'priority'")
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! The state the client's tasks share, kept behind small types whose
+//! invariants are checked with [loom](https://docs.rs/loom).
+//!
+//! The reader, the pools, the writer task and the caller all touch the map of
+//! outstanding control requests, the set of answered tool calls and the
+//! shutdown flag. Each is a type here with its locking inside, so the
+//! interleavings that matter can be explored exhaustively:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg clauders_loom" cargo test --lib --release sync::loom_tests
+//! ```
+//!
+//! The flag is not plain `loom`, which tokio reads as well.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::PoisonError;
+
+#[cfg(clauders_loom)]
+use loom::sync::Mutex;
+#[cfg(clauders_loom)]
+use loom::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(clauders_loom))]
+use std::sync::Mutex;
+#[cfg(not(clauders_loom))]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Waiters for responses, keyed by request id. Each waiter is taken at most
+/// once: by the response, a cancellation, a timeout or a restart, whichever
+/// comes first.
+#[derive(Debug)]
+pub(crate) struct Correlation<K, T> {
+    waiters: Mutex<HashMap<K, T>>,
+}
+
+impl<K: Eq + Hash + Clone, T> Correlation<K, T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            waiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registers the waiter for `id`, replacing any earlier one.
+    pub(crate) fn register(&self, id: K, waiter: T) {
+        self.lock().insert(id, waiter);
+    }
+
+    /// Takes the waiter for `id`, if nothing took it first.
+    pub(crate) fn take(&self, id: &K) -> Option<T> {
+        self.lock().remove(id)
+    }
+
+    /// Takes every waiter.
+    pub(crate) fn drain(&self) -> Vec<T> {
+        self.lock().drain().map(|(_, waiter)| waiter).collect()
+    }
+
+    pub(crate) fn ids(&self) -> Vec<K> {
+        self.lock().keys().cloned().collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> impl std::ops::DerefMut<Target = HashMap<K, T>> + '_ {
+        self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Ids that may be acted on once. Claiming an id is atomic, so of several
+/// tasks claiming it, exactly one goes ahead.
+#[derive(Debug)]
+pub(crate) struct ClaimSet {
+    claimed: Mutex<HashSet<String>>,
+}
+
+impl ClaimSet {
+    pub(crate) fn new() -> Self {
+        Self {
+            claimed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Claims `id`, returning whether this call claimed it.
+    pub(crate) fn claim(&self, id: &str) -> bool {
+        let mut claimed = self.lock();
+        !claimed.contains(id) && claimed.insert(id.to_owned())
+    }
+
+    /// Gives `id` up, so it can be claimed again, such as after the action
+    /// failed.
+    pub(crate) fn release(&self, id: &str) {
+        self.lock().remove(id);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.lock().clear();
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> impl std::ops::DerefMut<Target = HashSet<String>> + '_ {
+        self.claimed.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Set by the first of several paths that may each shut something down.
+#[derive(Debug)]
+pub(crate) struct Latch {
+    set: AtomicBool,
+}
+
+impl Latch {
+    pub(crate) fn new() -> Self {
+        Self {
+            set: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the latch, returning whether this call set it.
+    pub(crate) fn set(&self) -> bool {
+        !self.set.swap(true, Ordering::AcqRel)
+    }
+}
+
+#[cfg(all(test, not(clauders_loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claims_and_waiters_are_taken_once() {
+        let claims = ClaimSet::new();
+        assert!(claims.claim("toolu_1"));
+        assert!(!claims.claim("toolu_1"));
+        claims.release("toolu_1");
+        assert!(claims.claim("toolu_1"));
+        assert_eq!(claims.len(), 1);
+
+        let waiters = Correlation::new();
+        waiters.register("req_1".to_owned(), 1);
+        waiters.register("req_2".to_owned(), 2);
+        assert_eq!(waiters.take(&"req_1".to_owned()), Some(1));
+        assert_eq!(waiters.take(&"req_1".to_owned()), None);
+        assert_eq!(waiters.ids(), ["req_2"]);
+        assert_eq!(waiters.drain(), [2]);
+        assert_eq!(waiters.len(), 0);
+
+        let latch = Latch::new();
+        assert!(latch.set());
+        assert!(!latch.set());
+    }
+}
+
+#[cfg(all(test, clauders_loom))]
+mod loom_tests {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use super::*;
+
+    /// A response and a timeout racing for the same waiter: exactly one
+    /// of them gets it.
+    #[test]
+    fn test_waiter_is_taken_by_exactly_one_task() {
+        loom::model(|| {
+            let waiters = Arc::new(Correlation::new());
+            waiters.register("req_1".to_owned(), ());
+            let taken = [0, 1].map(|_| {
+                let waiters = Arc::clone(&waiters);
+                thread::spawn(move || waiters.take(&"req_1".to_owned()).is_some())
+            });
+            let taken = taken
+                .into_iter()
+                .map(|task| task.join().unwrap())
+                .filter(|taken| *taken)
+                .count();
+            assert_eq!(taken, 1);
+            assert_eq!(waiters.len(), 0);
+        });
+    }
+
+    /// A restart draining the map while a response arrives and a new
+    /// request registers: the old waiter is taken once, and the new one is
+    /// either drained or still registered, never lost.
+    #[test]
+    fn test_drain_races_response_and_register() {
+        loom::model(|| {
+            let waiters = Arc::new(Correlation::new());
+            waiters.register("req_1".to_owned(), 1);
+            let response = {
+                let waiters = Arc::clone(&waiters);
+                thread::spawn(move || waiters.take(&"req_1".to_owned()))
+            };
+            let register = {
+                let waiters = Arc::clone(&waiters);
+                thread::spawn(move || waiters.register("req_2".to_owned(), 2))
+            };
+            let mut drained = waiters.drain();
+            let answered = response.join().unwrap();
+            register.join().unwrap();
+            drained.extend(waiters.drain());
+            drained.sort_unstable();
+
+            let expected: &[i32] = if answered.is_some() { &[2] } else { &[1, 2] };
+            assert_eq!(drained, expected);
+        });
+    }
+
+    /// Two answers to the same tool call: exactly one is sent.
+    #[test]
+    fn test_tool_call_is_claimed_once() {
+        loom::model(|| {
+            let claims = Arc::new(ClaimSet::new());
+            let tasks = [0, 1].map(|_| {
+                let claims = Arc::clone(&claims);
+                thread::spawn(move || claims.claim("toolu_1"))
+            });
+            let claimed = tasks
+                .into_iter()
+                .map(|task| task.join().unwrap())
+                .filter(|taken| *taken)
+                .count();
+            assert_eq!(claimed, 1);
+        });
+    }
+
+    /// A failed answer releasing its claim while another is attempted: the
+    /// call is never left claimed by nobody and answered by nobody.
+    #[test]
+    fn test_released_claim_can_be_taken_again() {
+        loom::model(|| {
+            let claims = Arc::new(ClaimSet::new());
+            assert!(claims.claim("toolu_1"));
+            let retry = {
+                let claims = Arc::clone(&claims);
+                thread::spawn(move || claims.claim("toolu_1"))
+            };
+            claims.release("toolu_1");
+            let retried = retry.join().unwrap();
+            assert_eq!(claims.len(), usize::from(retried));
+            assert_eq!(claims.claim("toolu_1"), !retried);
+        });
+    }
+
+    /// `close` and `Drop` both shutting down the MCP servers: only one
+    /// runs the shutdown hooks.
+    #[test]
+    fn test_latch_is_set_once() {
+        loom::model(|| {
+            let latch = Arc::new(Latch::new());
+            let other = {
+                let latch = Arc::clone(&latch);
+                thread::spawn(move || latch.set())
+            };
+            let set = latch.set();
+            assert!(set != other.join().unwrap());
+        });
+    }
+}
@@ -0,0 +1,278 @@
+//! Answering the CLI's control requests off the reader.
+//!
+//! The client runs hook callbacks and MCP tool calls on tasks of their own,
+//! at most [`Options::max_concurrent_hooks`](crate::Options::max_concurrent_hooks)
+//! and [`Options::max_concurrent_tool_calls`](crate::Options::max_concurrent_tool_calls)
+//! at a time, and keeps reading the CLI while they run. Their responses are
+//! sent as they finish, without waiting for the reader. A task whose request
+//! the CLI cancels is aborted, and no response is sent for it.
+//!
+//! Every task a client starts in the background is owned by it: dropping the
+//! client aborts them, and [`Client::shutdown_gracefully`](crate::Client::shutdown_gracefully)
+//! gives them a deadline to finish first.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::time::Duration;
+
+use futures::future::{AbortHandle, BoxFuture};
+use tokio::sync::{Mutex, Semaphore, mpsc};
+use tokio::task::JoinSet;
+
+/// How many tasks of a pool were waiting, running or done when
+/// [`Client::hook_pool_stats`](crate::Client::hook_pool_stats) or
+/// [`Client::tool_pool_stats`](crate::Client::tool_pool_stats) was called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TaskPoolStats {
+    /// Tasks waiting for a free slot.
+    pub queued: usize,
+    /// Tasks running.
+    pub running: usize,
+    /// Tasks done whose responses are yet to be sent.
+    pub completed: usize,
+}
+
+/// Tasks owned by a client, aborted when it is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct BackgroundTasks {
+    tasks: std::sync::Mutex<JoinSet<()>>,
+}
+
+impl BackgroundTasks {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `task` on the current runtime.
+    pub(crate) fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let mut tasks = self.tasks.lock().unwrap_or_else(PoisonError::into_inner);
+        // Finished tasks stay in the set until joined.
+        while tasks.try_join_next().is_some() {}
+        tasks.spawn(task);
+    }
+
+    /// Waits up to `timeout` for the tasks to finish, then aborts the rest.
+    /// Returns how many were aborted. Tasks spawned meanwhile are left
+    /// running.
+    pub(crate) async fn shutdown(&self, timeout: Duration) -> usize {
+        let mut tasks =
+            std::mem::take(&mut *self.tasks.lock().unwrap_or_else(PoisonError::into_inner));
+        let _ = tokio::time::timeout(timeout, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await;
+        let aborted = tasks.len();
+        tasks.shutdown().await;
+        aborted
+    }
+}
+
+/// A bounded set of tasks answering control requests.
+pub(crate) struct TaskPool<T> {
+    permits: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+    running: Arc<AtomicUsize>,
+    completed: Arc<AtomicUsize>,
+    tx: mpsc::UnboundedSender<T>,
+    rx: Mutex<mpsc::UnboundedReceiver<T>>,
+    tasks: BackgroundTasks,
+    /// Aborts the unfinished tasks, by the id of the request they answer.
+    aborts: Arc<std::sync::Mutex<HashMap<String, AbortHandle>>>,
+}
+
+impl<T: Send + 'static> TaskPool<T> {
+    pub(crate) fn new(max_concurrent: usize) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            queued: Arc::new(AtomicUsize::new(0)),
+            running: Arc::new(AtomicUsize::new(0)),
+            completed: Arc::new(AtomicUsize::new(0)),
+            tx,
+            rx: Mutex::new(rx),
+            tasks: BackgroundTasks::new(),
+            aborts: Arc::default(),
+        }
+    }
+
+    /// Runs `task`, answering the request `id`, once a slot is free.
+    pub(crate) fn spawn(&self, id: &str, task: BoxFuture<'static, T>) {
+        let (task, abort) = futures::future::abortable(task);
+        self.aborts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id.to_owned(), abort);
+        let permits = self.permits.clone();
+        let queued = self.queued.clone();
+        let running = self.running.clone();
+        let completed = self.completed.clone();
+        let tx = self.tx.clone();
+        let aborts = self.aborts.clone();
+        let id = id.to_owned();
+        queued.fetch_add(1, Ordering::Relaxed);
+        self.tasks.spawn(async move {
+            let permit = permits.acquire_owned().await;
+            queued.fetch_sub(1, Ordering::Relaxed);
+            running.fetch_add(1, Ordering::Relaxed);
+            let output = task.await;
+            drop(permit);
+            running.fetch_sub(1, Ordering::Relaxed);
+            aborts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&id);
+            let Ok(output) = output else {
+                return;
+            };
+            completed.fetch_add(1, Ordering::Relaxed);
+            let _ = tx.send(output);
+        });
+    }
+
+    /// Aborts the task answering the request `id`, so its output is never
+    /// sent. Returns whether it had yet to finish.
+    pub(crate) fn cancel(&self, id: &str) -> bool {
+        let abort = self
+            .aborts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(id);
+        abort.inspect(AbortHandle::abort).is_some()
+    }
+
+    /// Waits for the output of the next task to finish. Cancellation safe.
+    pub(crate) async fn next_completed(&self) -> T {
+        let output = self.rx.lock().await.recv().await;
+        match output {
+            Some(output) => {
+                self.completed.fetch_sub(1, Ordering::Relaxed);
+                output
+            }
+            // The pool holds a sender, so the channel never closes.
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Waits up to `timeout` for the queued and running tasks to finish,
+    /// then aborts the rest, returning how many were aborted. Their
+    /// responses are not sent.
+    pub(crate) async fn shutdown(&self, timeout: Duration) -> usize {
+        let aborted = self.tasks.shutdown(timeout).await;
+        self.queued.store(0, Ordering::Relaxed);
+        self.running.store(0, Ordering::Relaxed);
+        aborted
+    }
+
+    pub(crate) fn stats(&self) -> TaskPoolStats {
+        TaskPoolStats {
+            queued: self.queued.load(Ordering::Relaxed),
+            running: self.running.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::FutureExt;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_task_pool_bounds_concurrency() {
+        let pool = TaskPool::new(1);
+        let (release, released) = oneshot::channel::<()>();
+        pool.spawn(
+            "1",
+            async move {
+                let _ = released.await;
+                1
+            }
+            .boxed(),
+        );
+        pool.spawn("2", async { 2 }.boxed());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            pool.stats(),
+            TaskPoolStats {
+                queued: 1,
+                running: 1,
+                completed: 0,
+            }
+        );
+
+        release.send(()).unwrap();
+        assert_eq!(pool.next_completed().await, 1);
+        assert_eq!(pool.next_completed().await, 2);
+        assert_eq!(pool.stats(), TaskPoolStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_then_aborts() {
+        let pool = TaskPool::new(2);
+        let (release, released) = oneshot::channel::<()>();
+        let (_hold, held) = oneshot::channel::<()>();
+        pool.spawn(
+            "1",
+            async move {
+                let _ = released.await;
+                1
+            }
+            .boxed(),
+        );
+        pool.spawn(
+            "2",
+            async move {
+                let _ = held.await;
+                2
+            }
+            .boxed(),
+        );
+        pool.spawn("3", async { 3 }.boxed());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        release.send(()).unwrap();
+
+        assert_eq!(pool.shutdown(Duration::from_millis(50)).await, 1);
+        assert_eq!(pool.stats().running, 0);
+        let mut done = vec![pool.next_completed().await, pool.next_completed().await];
+        done.sort();
+        assert_eq!(done, [1, 3]);
+
+        let tasks = BackgroundTasks::new();
+        let (tx, rx) = oneshot::channel::<()>();
+        tasks.spawn(async move {
+            let _keep = tx;
+            std::future::pending::<()>().await;
+        });
+        drop(tasks);
+        // Dropping the tasks aborts them, dropping their sender.
+        assert!(rx.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_tasks_are_aborted_without_output() {
+        let pool = TaskPool::new(1);
+        let (tx, rx) = oneshot::channel::<()>();
+        pool.spawn(
+            "held",
+            async move {
+                let _keep = tx;
+                std::future::pending::<u32>().await
+            }
+            .boxed(),
+        );
+        pool.spawn("next", async { 2 }.boxed());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert!(pool.cancel("held"));
+        assert!(rx.await.is_err());
+        assert_eq!(pool.next_completed().await, 2);
+        assert!(!pool.cancel("held") && !pool.cancel("next"));
+        assert_eq!(pool.stats(), TaskPoolStats::default());
+    }
+}
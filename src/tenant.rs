@@ -0,0 +1,195 @@
+//! Isolation between the users of a multi-tenant service.
+//!
+//! A [`Tenant`] set with [`Options::tenant`](crate::Options::tenant) tags a
+//! client with the id of the user or organisation it works for, and can give
+//! it its own environment and a throwaway working directory:
+//!
+//! - [`Tenant::env`] adds variables seen only by this tenant's CLI process,
+//!   and [`Tenant::isolate_env`] stops the process inheriting anything from
+//!   the service's environment beyond [`INHERITED_ENV`].
+//! - [`Tenant::temp_workspace`] runs the client in a fresh [`Workspace`]
+//!   under the system temp directory, deleted when the client is dropped.
+//!
+//! The tenant id is reported by [`Client::tenant_id`](crate::Client::tenant_id),
+//! logged with tool usage statistics and, with the `webhooks` feature, sent
+//! as `tenant_id` in every webhook payload.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::tenant::Tenant;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let tenant = Tenant::new("acme")
+//!     .env("GITHUB_TOKEN", "ghp_acme")
+//!     .isolate_env()
+//!     .temp_workspace();
+//! let client = Client::new(Options::new().tenant(tenant)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// Variables a tenant with [`isolate_env`](Tenant::isolate_env) still
+/// inherits, which the CLI needs to run and authenticate.
+pub const INHERITED_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "SHELL",
+    "LANG",
+    "LC_ALL",
+    "TERM",
+    "TMPDIR",
+    "ANTHROPIC_API_KEY",
+    "ANTHROPIC_BASE_URL",
+    "CLAUDE_CODE_USE_BEDROCK",
+    "CLAUDE_CODE_USE_VERTEX",
+];
+
+/// The user or organisation a client works for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tenant {
+    id: String,
+    env: Vec<(String, String)>,
+    isolate_env: bool,
+    temp_workspace: bool,
+}
+
+impl Tenant {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            env: Vec::new(),
+            isolate_env: false,
+            temp_workspace: false,
+        }
+    }
+
+    /// Sets an environment variable for this tenant's CLI process, on top of
+    /// [`Options::env`](crate::Options::env).
+    #[must_use]
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Starts the CLI with an empty environment apart from [`INHERITED_ENV`]
+    /// and the variables set explicitly, so secrets of the service or of
+    /// other tenants cannot leak into the session.
+    #[must_use]
+    pub fn isolate_env(mut self) -> Self {
+        self.isolate_env = true;
+        self
+    }
+
+    /// Runs each client in a new, empty [`Workspace`] instead of
+    /// [`Options::cwd`](crate::Options::cwd).
+    #[must_use]
+    pub fn temp_workspace(mut self) -> Self {
+        self.temp_workspace = true;
+        self
+    }
+
+    // Getters
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn env_vars(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    pub fn is_env_isolated(&self) -> bool {
+        self.isolate_env
+    }
+
+    pub fn has_temp_workspace(&self) -> bool {
+        self.temp_workspace
+    }
+}
+
+/// A working directory owned by one client, removed with its contents when
+/// dropped.
+#[derive(Debug)]
+pub struct Workspace {
+    path: PathBuf,
+}
+
+impl Workspace {
+    /// Creates an empty directory under the system temp directory, named
+    /// after `tenant_id`.
+    pub fn create(tenant_id: &str) -> std::io::Result<Self> {
+        let tenant = tenant_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let path =
+            std::env::temp_dir().join(format!("clauders-{tenant}-{}", crate::util::uuid_v7()));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            tracing::warn!(path = %self.path.display(), error = %e, "failed to remove workspace");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Options;
+
+    #[test]
+    fn test_tenant_scopes_env_and_workspace() {
+        let workspace = Workspace::create("acme/eu").unwrap();
+        let path = workspace.path().to_path_buf();
+        assert!(path.is_dir());
+        assert!(
+            path.file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with("clauders-acme_eu-")
+        );
+        drop(workspace);
+        assert!(!path.exists());
+
+        let mut options = Options::new().env([("LOG", "1")]).tenant(
+            Tenant::new("acme")
+                .env("GITHUB_TOKEN", "t")
+                .isolate_env()
+                .temp_workspace(),
+        );
+        let workspace = options.create_workspace().unwrap().unwrap();
+        let transport = options.to_transport_options();
+        assert_eq!(
+            transport.cwd().map(PathBuf::as_path),
+            Some(workspace.path())
+        );
+        assert_eq!(
+            transport.env(),
+            [
+                ("LOG".to_owned(), "1".to_owned()),
+                ("GITHUB_TOKEN".to_owned(), "t".to_owned())
+            ]
+        );
+        assert_eq!(transport.inherit_env().unwrap(), INHERITED_ENV);
+    }
+}
@@ -0,0 +1,305 @@
+//! Snapshot testing of agent behaviour.
+//!
+//! [`snapshot`] renders a turn's responses as pretty-printed JSON with the
+//! fields that change on every run (ids, session ids, durations, costs and
+//! token counts) replaced by placeholders, then compares the result against
+//! a file on disk. Ids are numbered in order of first appearance, so a tool
+//! result still points at the tool call it answers. Streaming partials and
+//! rate-limit events are left out.
+//!
+//! A missing snapshot file is written on first use. When a snapshot differs,
+//! the new rendering is written next to it with a `.new` extension so the
+//! change can be reviewed with any diff tool; set `CLAUDERS_UPDATE_SNAPSHOTS=1`
+//! to accept new renderings instead.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::testing::assert_snapshot;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let client = Client::new(Options::new()).await?;
+//! let mut conversation = client.conversation();
+//! let responses = conversation.turn("List the files in src/").send().await?;
+//! assert_snapshot("tests/snapshots/list_files.json", &responses);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::response::{Response, Responses};
+
+/// Environment variable that makes [`snapshot`] overwrite changed snapshots.
+pub const UPDATE_ENV: &str = "CLAUDERS_UPDATE_SNAPSHOTS";
+
+/// Fields holding ids that differ on every run.
+const ID_FIELDS: &[&str] = &[
+    "id",
+    "tool_use_id",
+    "message_id",
+    "hook_id",
+    "parent_tool_use_id",
+];
+
+/// Fields replaced by a fixed placeholder named after the field.
+const VOLATILE_FIELDS: &[&str] = &[
+    "session_id",
+    "cwd",
+    "duration_ms",
+    "duration_api_ms",
+    "total_cost_usd",
+    "usage",
+    "model_usage",
+];
+
+/// The outcome of comparing responses against a snapshot file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotStatus {
+    /// The file did not exist and has been written.
+    Created,
+    /// The file differed and was overwritten because [`UPDATE_ENV`] is set.
+    Updated,
+    /// The file matches the responses.
+    Matched,
+    /// The file differs; the new rendering was written to `pending`.
+    Changed { pending: PathBuf },
+}
+
+/// Renders `responses` with volatile fields replaced by placeholders.
+pub fn normalise(responses: &Responses) -> Value {
+    let mut ids = HashMap::new();
+    Value::Array(
+        responses
+            .iter()
+            .filter(|response| !matches!(response, Response::Partial(_) | Response::RateLimit(_)))
+            .map(|response| {
+                let mut value = response.to_json();
+                redact(&mut value, &mut ids);
+                value
+            })
+            .collect(),
+    )
+}
+
+/// Renders `responses` as the text stored in a snapshot file.
+pub fn render(responses: &Responses) -> String {
+    let mut text = serde_json::to_string_pretty(&normalise(responses))
+        .expect("normalised responses are valid JSON");
+    text.push('\n');
+    text
+}
+
+/// Compares `responses` against the snapshot at `path`, writing it if it is
+/// missing or, with [`UPDATE_ENV`] set, if it differs.
+pub fn snapshot(path: impl AsRef<Path>, responses: &Responses) -> Result<SnapshotStatus, Error> {
+    let path = path.as_ref();
+    let actual = render(responses);
+    let pending = pending_path(path);
+
+    let expected = match std::fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            write(path, &actual)?;
+            return Ok(SnapshotStatus::Created);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if expected == actual {
+        remove_stale(&pending)?;
+        return Ok(SnapshotStatus::Matched);
+    }
+    if std::env::var_os(UPDATE_ENV).is_some_and(|value| value != "0") {
+        write(path, &actual)?;
+        remove_stale(&pending)?;
+        return Ok(SnapshotStatus::Updated);
+    }
+    write(&pending, &actual)?;
+    Ok(SnapshotStatus::Changed { pending })
+}
+
+/// Like [`snapshot`], but panics when the snapshot differs or cannot be
+/// read, pointing at the first line that changed.
+pub fn assert_snapshot(path: impl AsRef<Path>, responses: &Responses) {
+    let path = path.as_ref();
+    match snapshot(path, responses) {
+        Ok(SnapshotStatus::Changed { pending }) => {
+            let expected = std::fs::read_to_string(path).unwrap_or_default();
+            let actual = std::fs::read_to_string(&pending).unwrap_or_default();
+            panic!(
+                "snapshot {} changed; new rendering written to {} (set {UPDATE_ENV}=1 to \
+                 accept it)\n{}",
+                path.display(),
+                pending.display(),
+                first_difference(&expected, &actual)
+            );
+        }
+        Ok(_) => {}
+        Err(e) => panic!("snapshot {} could not be checked: {e}", path.display()),
+    }
+}
+
+fn redact(value: &mut Value, ids: &mut HashMap<String, usize>) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if field.is_null() {
+                    continue;
+                }
+                if ID_FIELDS.contains(&key.as_str()) {
+                    if let Some(id) = field.as_str() {
+                        let next = ids.len() + 1;
+                        let n = *ids.entry(id.to_owned()).or_insert(next);
+                        *field = Value::String(format!("<id-{n}>"));
+                    }
+                } else if VOLATILE_FIELDS.contains(&key.as_str()) {
+                    *field = Value::String(format!("<{key}>"));
+                } else {
+                    redact(field, ids);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact(item, ids);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn pending_path(path: &Path) -> PathBuf {
+    let mut pending = path.as_os_str().to_owned();
+    pending.push(".new");
+    PathBuf::from(pending)
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+fn remove_stale(pending: &Path) -> Result<(), Error> {
+    match std::fs::remove_file(pending) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(a), Some(b)) if a == b => line += 1,
+            (None, None) => return String::new(),
+            (a, b) => {
+                return format!(
+                    "line {line}:\n- {}\n+ {}",
+                    a.unwrap_or("<end of file>"),
+                    b.unwrap_or("<end of file>")
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::proto::Message;
+
+    fn responses(session: &str, tool_id: &str, cost: f64) -> Responses {
+        let messages = [
+            json!({
+                "type": "assistant",
+                "message": {
+                    "model": "claude-sonnet-4-5",
+                    "content": [{"type": "tool_use", "id": tool_id, "name": "Read", "input": {}}]
+                }
+            }),
+            json!({
+                "type": "assistant",
+                "message": {
+                    "model": "claude-sonnet-4-5",
+                    "content": [
+                        {"type": "tool_use", "id": "toolu_other", "name": "Grep", "input": {}},
+                        {"type": "tool_use", "id": tool_id, "name": "Read", "input": {}}
+                    ]
+                }
+            }),
+            json!({
+                "type": "result",
+                "subtype": "success",
+                "duration_ms": 1234,
+                "duration_api_ms": 1000,
+                "is_error": false,
+                "num_turns": 2,
+                "session_id": session,
+                "total_cost_usd": cost,
+                "result": "done"
+            }),
+        ];
+        let mut responses = Responses::new();
+        for message in messages {
+            let message = serde_json::from_value::<Message>(message).unwrap();
+            for response in Response::from_message(&message) {
+                responses.push(response);
+            }
+        }
+        responses
+    }
+
+    #[test]
+    fn test_snapshot_ignores_volatile_fields() {
+        let first = responses("s1", "toolu_1", 0.01);
+        let normalised = normalise(&first);
+        assert_eq!(normalised[0]["id"], "<id-1>");
+        assert_eq!(normalised[1]["id"], "<id-2>");
+        assert_eq!(normalised[2]["id"], "<id-1>");
+        assert_eq!(normalised[3]["total_cost_usd"], "<total_cost_usd>");
+        assert_eq!(normalised[3]["result"], "done");
+
+        let dir =
+            std::env::temp_dir().join(format!("clauders-snapshot-{}", crate::util::uuid_v7()));
+        let path = dir.join("turn.json");
+        assert_eq!(snapshot(&path, &first).unwrap(), SnapshotStatus::Created);
+        assert_eq!(
+            snapshot(&path, &responses("s2", "toolu_2", 0.02)).unwrap(),
+            SnapshotStatus::Matched
+        );
+
+        let mut changed = responses("s3", "toolu_3", 0.03);
+        changed.push(
+            Response::from_message(
+                &serde_json::from_value::<Message>(json!({
+                    "type": "assistant",
+                    "message": {
+                        "model": "claude-sonnet-4-5",
+                        "content": [{"type": "text", "text": "extra"}]
+                    }
+                }))
+                .unwrap(),
+            )[0]
+            .clone(),
+        );
+        let SnapshotStatus::Changed { pending } = snapshot(&path, &changed).unwrap() else {
+            panic!("expected the snapshot to change");
+        };
+        assert!(std::fs::read_to_string(&pending).unwrap().contains("extra"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use crate::model::Model;
+
+/// The smallest thinking budget the API accepts.
+pub const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// How much extended thinking Claude may do before responding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThinkingConfig {
+    /// Lets the CLI choose the budget.
+    #[default]
+    Adaptive,
+    /// Allows up to `budget_tokens` of thinking per response.
+    Enabled { budget_tokens: u32 },
+    /// Turns extended thinking off.
+    Disabled,
+}
+
+impl ThinkingConfig {
+    pub fn adaptive() -> Self {
+        Self::Adaptive
+    }
+
+    pub fn enabled(budget_tokens: u32) -> Self {
+        Self::Enabled { budget_tokens }
+    }
+
+    pub fn disabled() -> Self {
+        Self::Disabled
+    }
+
+    /// The value passed as `--max-thinking-tokens`, if any.
+    pub(crate) fn max_thinking_tokens(&self) -> Option<u32> {
+        match self {
+            Self::Adaptive => None,
+            Self::Enabled { budget_tokens } => Some(*budget_tokens),
+            Self::Disabled => Some(0),
+        }
+    }
+}
+
+/// How much effort Claude puts into a response, trading speed for quality.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effort {
+    Low,
+    Medium,
+    High,
+    Max,
+}
+
+impl Effort {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Max => "max",
+        }
+    }
+}
+
+impl fmt::Display for Effort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Model {
+    /// The largest thinking budget the model supports, or `None` when unknown.
+    ///
+    /// The budget must leave room for a response within the model's output
+    /// limit.
+    pub fn max_thinking_budget(&self) -> Option<u32> {
+        match self {
+            Self::Sonnet | Self::Opus | Self::Haiku => Some(63_999),
+            Self::Inherit | Self::Custom(_) => None,
+        }
+    }
+}
@@ -1,13 +1,21 @@
 use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use futures::future::BoxFuture;
+use futures::{Stream, StreamExt};
+#[cfg(feature = "schema")]
 use schemars::JsonSchema;
+#[cfg(feature = "schema")]
 use serde::Serialize;
+#[cfg(feature = "schema")]
 use serde::de::DeserializeOwned;
 use serde_json::{Map, Value, json};
 use thiserror::Error;
 
+use crate::error::ConfigError;
+use crate::mcp_peer::Peer;
+use crate::sampling::{SampledMessage, SamplingMessage, SamplingParams};
 use crate::util;
 
 #[derive(Error, Debug)]
@@ -24,6 +32,8 @@ pub enum ToolError {
     PermissionDenied(String),
     #[error("deserialization failed: {0}")]
     DeserializationFailed(String),
+    #[error("tool handler {0}")]
+    Aborted(String),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -66,6 +76,127 @@ impl ToolError {
     pub fn msg(msg: impl Into<String>) -> Self {
         Self::Other(anyhow::Error::msg(msg.into()))
     }
+
+    /// The category reported alongside the error in the tool result.
+    pub fn kind(&self) -> ToolErrorKind {
+        match self {
+            Self::MissingParameter(_)
+            | Self::InvalidParameter { .. }
+            | Self::DeserializationFailed(_) => ToolErrorKind::InvalidInput,
+            Self::ExecutionFailed(_) => ToolErrorKind::CommandFailed,
+            Self::NotFound(_) => ToolErrorKind::NotFound,
+            Self::PermissionDenied(_) => ToolErrorKind::PermissionDenied,
+            Self::Aborted(_) | Self::Other(_) => ToolErrorKind::Other,
+        }
+    }
+}
+
+/// Key under a result content block's `_meta` holding the [`ToolErrorKind`]
+/// of a failed in-process tool call.
+pub(crate) const ERROR_KIND_META_KEY: &str = "clauders/errorKind";
+
+/// Broad categories of tool failure, for deciding how to recover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ToolErrorKind {
+    /// A file, resource or command does not exist.
+    NotFound,
+    /// The tool was refused access, by the OS or by a permission check.
+    PermissionDenied,
+    /// The tool did not finish in time.
+    Timeout,
+    /// The tool ran but failed, such as a command exiting non-zero.
+    CommandFailed,
+    /// The tool was called with missing or malformed arguments.
+    InvalidInput,
+    Other,
+}
+
+impl ToolErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NotFound => "not_found",
+            Self::PermissionDenied => "permission_denied",
+            Self::Timeout => "timeout",
+            Self::CommandFailed => "command_failed",
+            Self::InvalidInput => "invalid_input",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::NotFound,
+            Self::PermissionDenied,
+            Self::Timeout,
+            Self::CommandFailed,
+            Self::InvalidInput,
+            Self::Other,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str() == name)
+    }
+
+    /// Guesses the kind of failure from an error message.
+    pub fn classify(message: &str) -> Self {
+        const PATTERNS: &[(ToolErrorKind, &[&str])] = &[
+            (
+                ToolErrorKind::Timeout,
+                &["timed out", "timeout", "deadline exceeded"],
+            ),
+            (
+                ToolErrorKind::PermissionDenied,
+                &[
+                    "permission denied",
+                    "operation not permitted",
+                    "access denied",
+                    "not allowed",
+                    "eacces",
+                    "eperm",
+                ],
+            ),
+            (
+                ToolErrorKind::NotFound,
+                &[
+                    "no such file",
+                    "not found",
+                    "does not exist",
+                    "enoent",
+                    "unknown tool",
+                ],
+            ),
+            (
+                ToolErrorKind::InvalidInput,
+                &[
+                    "missing required parameter",
+                    "invalid parameter",
+                    "invalid input",
+                    "deserialization failed",
+                    "inputvalidationerror",
+                ],
+            ),
+            (
+                ToolErrorKind::CommandFailed,
+                &[
+                    "exit code",
+                    "exited with",
+                    "command failed",
+                    "execution failed",
+                ],
+            ),
+        ];
+
+        let message = message.to_lowercase();
+        PATTERNS
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| message.contains(pattern)))
+            .map_or(Self::Other, |(kind, _)| *kind)
+    }
+}
+
+impl std::fmt::Display for ToolErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -169,14 +300,458 @@ impl From<ToolInput> for Value {
     }
 }
 
+/// What a tool handler can learn about, and ask of, the session calling it.
+///
+/// Handlers created with [`Tool::with_context`] receive one per call.
+#[derive(Debug, Clone, Default)]
+pub struct ToolContext {
+    roots: Arc<[PathBuf]>,
+    peer: Option<Peer>,
+    protocol_version: Option<String>,
+}
+
+/// The user's answer to [`ToolContext::elicit`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Elicitation<T> {
+    Accepted(T),
+    Declined,
+    Cancelled,
+}
+
+impl ToolContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_roots(mut self, roots: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.roots = roots.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The workspace roots declared by the host. Tools that touch the
+    /// filesystem should stay within them.
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Whether `path` lies within one of the roots, compared lexically.
+    ///
+    /// Every path is allowed when no roots were declared.
+    pub fn is_within_roots(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.roots.is_empty() || self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    /// The MCP revision the server negotiated with the CLI, if any.
+    pub fn protocol_version(&self) -> Option<&str> {
+        self.protocol_version.as_deref()
+    }
+
+    pub(crate) fn with_protocol_version(mut self, version: Option<String>) -> Self {
+        self.protocol_version = version;
+        self
+    }
+
+    pub(crate) fn with_peer(mut self, peer: Peer) -> Self {
+        self.peer = Some(peer);
+        self
+    }
+
+    /// Asks the user, through the CLI, to fill in a `T` while the tool runs.
+    ///
+    /// `T`'s schema should be a flat object of strings, numbers, booleans
+    /// and enums, as MCP elicitation allows nothing richer. Fails when the
+    /// tool is not being called by a [`Client`](crate::Client).
+    #[cfg(feature = "schema")]
+    pub async fn elicit<T>(&self, prompt: impl Into<String>) -> Result<Elicitation<T>, ToolError>
+    where
+        T: JsonSchema + DeserializeOwned,
+    {
+        let result = self
+            .peer("elicitation")?
+            .request(
+                "elicitation/create",
+                json!({
+                    "message": prompt.into(),
+                    "requestedSchema": util::schema_for_structured_output::<T>(),
+                }),
+            )
+            .await?;
+
+        match result.get("action").and_then(Value::as_str) {
+            Some("accept") => {
+                let content = result.get("content").cloned().unwrap_or_default();
+                serde_json::from_value(content)
+                    .map(Elicitation::Accepted)
+                    .map_err(|e| ToolError::deserialization_failed(e.to_string()))
+            }
+            Some("decline") => Ok(Elicitation::Declined),
+            _ => Ok(Elicitation::Cancelled),
+        }
+    }
+
+    /// Asks the session's model, through the CLI, to reply to `messages`,
+    /// such as to classify or summarise something for the tool.
+    ///
+    /// Fails when the tool is not being called by a [`Client`](crate::Client).
+    pub async fn sample(
+        &self,
+        messages: &[SamplingMessage],
+        params: &SamplingParams,
+    ) -> Result<SampledMessage, ToolError> {
+        let result = self
+            .peer("sampling")?
+            .request("sampling/createMessage", params.to_request(messages))
+            .await?;
+        serde_json::from_value(result).map_err(|e| ToolError::deserialization_failed(e.to_string()))
+    }
+
+    fn peer(&self, feature: &str) -> Result<&Peer, ToolError> {
+        self.peer.as_ref().ok_or_else(|| {
+            ToolError::execution_failed(format!(
+                "{feature} is only available within a client session"
+            ))
+        })
+    }
+}
+
+/// A tool name split into its parts.
+///
+/// Tools served by an MCP server are named `mcp__<server>__<tool>`; every
+/// other name is a built-in tool.
+///
+/// # Example
+///
+/// ```
+/// use clauders::ToolName;
+///
+/// let name = ToolName::parse("mcp__github__create_issue");
+/// assert_eq!(name.server(), Some("github"));
+/// assert_eq!(name.tool(), "create_issue");
+/// assert!(name.is_from_server("github"));
+/// assert!(!ToolName::parse("Bash").is_mcp());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ToolName<'a> {
+    Builtin(&'a str),
+    Mcp { server: &'a str, tool: &'a str },
+}
+
+impl<'a> ToolName<'a> {
+    pub fn parse(name: &'a str) -> Self {
+        name.strip_prefix(MCP_TOOL_PREFIX)
+            .and_then(|rest| rest.split_once("__"))
+            .map_or(Self::Builtin(name), |(server, tool)| Self::Mcp {
+                server,
+                tool,
+            })
+    }
+
+    /// The name of `tool` on the MCP server `server`.
+    pub fn mcp(server: &'a str, tool: &'a str) -> Self {
+        Self::Mcp { server, tool }
+    }
+
+    /// The MCP server providing the tool, or `None` for built-in tools.
+    pub fn server(&self) -> Option<&'a str> {
+        match self {
+            Self::Builtin(_) => None,
+            Self::Mcp { server, .. } => Some(server),
+        }
+    }
+
+    /// The tool's name without its server prefix.
+    pub fn tool(&self) -> &'a str {
+        match self {
+            Self::Builtin(tool) | Self::Mcp { tool, .. } => tool,
+        }
+    }
+
+    pub fn is_mcp(&self) -> bool {
+        matches!(self, Self::Mcp { .. })
+    }
+
+    /// Whether this is any tool of the MCP server `server`.
+    pub fn is_from_server(&self, server: &str) -> bool {
+        self.server() == Some(server)
+    }
+}
+
+impl std::fmt::Display for ToolName<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Builtin(tool) => f.write_str(tool),
+            Self::Mcp { server, tool } => write!(f, "{MCP_TOOL_PREFIX}{server}__{tool}"),
+        }
+    }
+}
+
+const MCP_TOOL_PREFIX: &str = "mcp__";
+
+/// The CLI's built-in tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinTool {
+    Bash,
+    BashOutput,
+    Edit,
+    ExitPlanMode,
+    Glob,
+    Grep,
+    KillShell,
+    MultiEdit,
+    NotebookEdit,
+    Read,
+    SlashCommand,
+    Task,
+    TodoWrite,
+    WebFetch,
+    WebSearch,
+    Write,
+}
+
+impl BuiltinTool {
+    pub const ALL: &[Self] = &[
+        Self::Bash,
+        Self::BashOutput,
+        Self::Edit,
+        Self::ExitPlanMode,
+        Self::Glob,
+        Self::Grep,
+        Self::KillShell,
+        Self::MultiEdit,
+        Self::NotebookEdit,
+        Self::Read,
+        Self::SlashCommand,
+        Self::Task,
+        Self::TodoWrite,
+        Self::WebFetch,
+        Self::WebSearch,
+        Self::Write,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bash => "Bash",
+            Self::BashOutput => "BashOutput",
+            Self::Edit => "Edit",
+            Self::ExitPlanMode => "ExitPlanMode",
+            Self::Glob => "Glob",
+            Self::Grep => "Grep",
+            Self::KillShell => "KillShell",
+            Self::MultiEdit => "MultiEdit",
+            Self::NotebookEdit => "NotebookEdit",
+            Self::Read => "Read",
+            Self::SlashCommand => "SlashCommand",
+            Self::Task => "Task",
+            Self::TodoWrite => "TodoWrite",
+            Self::WebFetch => "WebFetch",
+            Self::WebSearch => "WebSearch",
+            Self::Write => "Write",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|tool| tool.as_str() == name)
+    }
+}
+
+impl std::fmt::Display for BuiltinTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An entry of the allowed or disallowed tool lists.
+///
+/// Renders to the strings the CLI expects: `Bash`, `Bash(git diff:*)`,
+/// `mcp__server__tool`, or `mcp__server` for every tool of a server.
+///
+/// # Example
+///
+/// ```
+/// use clauders::tool::{BuiltinTool, ToolSelector};
+/// use clauders::Options;
+///
+/// let options = Options::new()
+///     .allow(ToolSelector::builtin(BuiltinTool::Read))
+///     .allow(ToolSelector::builtin(BuiltinTool::Bash).with_rule("cargo test:*"))
+///     .allow(ToolSelector::mcp_server("github"))
+///     .disallow(ToolSelector::mcp("github", "delete_repo"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ToolSelector {
+    Builtin {
+        tool: BuiltinTool,
+        rule: Option<String>,
+    },
+    Mcp {
+        server: String,
+        tool: String,
+    },
+    /// Every tool of an MCP server.
+    McpServer(String),
+    /// A tool this crate does not know about, such as one added by a newer
+    /// CLI.
+    Other {
+        name: String,
+        rule: Option<String>,
+    },
+}
+
+impl ToolSelector {
+    pub fn builtin(tool: BuiltinTool) -> Self {
+        Self::Builtin { tool, rule: None }
+    }
+
+    pub fn mcp(server: impl Into<String>, tool: impl Into<String>) -> Self {
+        Self::Mcp {
+            server: server.into(),
+            tool: tool.into(),
+        }
+    }
+
+    pub fn mcp_server(server: impl Into<String>) -> Self {
+        Self::McpServer(server.into())
+    }
+
+    /// Narrows a built-in tool to inputs matching `rule`, such as
+    /// `"npm run test:*"` for Bash or `"./src/**"` for Edit.
+    ///
+    /// MCP selectors take no rule and are returned unchanged.
+    #[must_use]
+    pub fn with_rule(mut self, rule: impl Into<String>) -> Self {
+        match &mut self {
+            Self::Builtin { rule: slot, .. } | Self::Other { rule: slot, .. } => {
+                *slot = Some(rule.into());
+            }
+            Self::Mcp { .. } | Self::McpServer(_) => {}
+        }
+        self
+    }
+
+    /// Whether a call to `tool_name` is covered by this selector. Rules are
+    /// not evaluated: `Bash(git diff:*)` matches every `Bash` call.
+    pub fn matches(&self, tool_name: &str) -> bool {
+        match (self, ToolName::parse(tool_name)) {
+            (Self::Builtin { tool, .. }, ToolName::Builtin(name)) => tool.as_str() == name,
+            (Self::Other { name, .. }, ToolName::Builtin(called)) => name == called,
+            (Self::Mcp { server, tool }, ToolName::Mcp { server: s, tool: t }) => {
+                server == s && tool == t
+            }
+            (Self::McpServer(server), ToolName::Mcp { server: s, .. }) => server == s,
+            _ => false,
+        }
+    }
+
+    /// Parses a CLI tool string.
+    ///
+    /// Malformed strings are rejected. Names that are neither built-in nor
+    /// MCP tools are accepted as [`Other`](Self::Other) with a warning, as
+    /// they are usually typos.
+    pub fn parse(s: &str) -> Result<Self, ConfigError> {
+        let invalid = |reason: &str| ConfigError::InvalidToolSelector {
+            selector: s.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        let (name, rule) = match s.split_once('(') {
+            Some((name, rest)) => {
+                let rule = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| invalid("rule is missing its closing ')'"))?;
+                if rule.trim().is_empty() {
+                    return Err(invalid("rule is empty"));
+                }
+                (name, Some(rule.to_owned()))
+            }
+            None => (s, None),
+        };
+        let bare = match name.strip_suffix("__*") {
+            Some(server) if name.starts_with(MCP_TOOL_PREFIX) => server,
+            _ => name,
+        };
+        if !is_identifier(bare) {
+            return Err(invalid(
+                "tool names may only contain letters, digits, '_' and '-'",
+            ));
+        }
+
+        if let Some(rest) = name.strip_prefix(MCP_TOOL_PREFIX) {
+            if rule.is_some() {
+                return Err(invalid("MCP tools do not take rules"));
+            }
+            return match rest.split_once("__") {
+                None if !rest.is_empty() => Ok(Self::McpServer(rest.to_owned())),
+                Some((server, "*")) if !server.is_empty() => Ok(Self::McpServer(server.to_owned())),
+                Some((server, tool)) if !server.is_empty() && !tool.is_empty() => {
+                    Ok(Self::mcp(server, tool))
+                }
+                _ => Err(invalid("expected mcp__<server> or mcp__<server>__<tool>")),
+            };
+        }
+
+        match BuiltinTool::from_name(name) {
+            Some(tool) => Ok(Self::Builtin { tool, rule }),
+            None => {
+                tracing::warn!(tool = name, "unknown built-in tool");
+                Ok(Self::Other {
+                    name: name.to_owned(),
+                    rule,
+                })
+            }
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+}
+
+impl std::fmt::Display for ToolSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (name, rule) = match self {
+            Self::Builtin { tool, rule } => (tool.as_str(), rule),
+            Self::Other { name, rule } => (name.as_str(), rule),
+            Self::Mcp { server, tool } => return ToolName::mcp(server, tool).fmt(f),
+            Self::McpServer(server) => return write!(f, "{MCP_TOOL_PREFIX}{server}"),
+        };
+        match rule {
+            Some(rule) => write!(f, "{name}({rule})"),
+            None => f.write_str(name),
+        }
+    }
+}
+
+impl From<BuiltinTool> for ToolSelector {
+    fn from(tool: BuiltinTool) -> Self {
+        Self::builtin(tool)
+    }
+}
+
+impl std::str::FromStr for ToolSelector {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
 pub struct Tool {
     name: String,
     description: String,
     input_schema: Value,
     output_schema: Option<Value>,
-    handler: Arc<dyn Fn(ToolInput) -> BoxFuture<'static, Result<Value, ToolError>> + Send + Sync>,
+    handler: Handler,
 }
 
+type Handler = Arc<
+    dyn Fn(ToolInput, ToolContext) -> BoxFuture<'static, Result<Value, ToolError>> + Send + Sync,
+>;
+
 impl std::fmt::Debug for Tool {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Tool")
@@ -206,10 +781,33 @@ impl Tool {
             description: description.into(),
             input_schema,
             output_schema: output_schema.into(),
-            handler: Arc::new(move |input| Box::pin(handler(input))),
+            handler: Arc::new(move |input, _| Box::pin(handler(input))),
+        }
+    }
+
+    /// Creates a tool whose handler also receives the [`ToolContext`] of
+    /// each call.
+    pub fn with_context<F, Fut>(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        input_schema: Value,
+        output_schema: impl Into<Option<Value>>,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(ToolInput, ToolContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, ToolError>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            input_schema,
+            output_schema: output_schema.into(),
+            handler: Arc::new(move |input, ctx| Box::pin(handler(input, ctx))),
         }
     }
 
+    #[cfg(feature = "schema")]
     pub fn structured<T, U, F, Fut>(
         name: impl Into<String>,
         description: impl Into<String>,
@@ -229,7 +827,7 @@ impl Tool {
             description: description.into(),
             input_schema,
             output_schema: Some(output_schema),
-            handler: Arc::new(move |input: ToolInput| {
+            handler: Arc::new(move |input: ToolInput, _| {
                 let value = input.into_value();
                 let deser_result = serde_json::from_value::<T>(value);
                 let handler = Arc::clone(&handler);
@@ -244,6 +842,7 @@ impl Tool {
         }
     }
 
+    #[cfg(feature = "schema")]
     pub fn unstructured<T, F, Fut>(
         name: impl Into<String>,
         description: impl Into<String>,
@@ -261,7 +860,7 @@ impl Tool {
             description: description.into(),
             input_schema,
             output_schema: None,
-            handler: Arc::new(move |input: ToolInput| {
+            handler: Arc::new(move |input: ToolInput, _| {
                 let value = input.into_value();
                 let deser_result = serde_json::from_value::<T>(value);
                 let handler = Arc::clone(&handler);
@@ -274,6 +873,34 @@ impl Tool {
         }
     }
 
+    /// Creates a tool whose handler produces its content blocks incrementally.
+    ///
+    /// Consecutive text blocks are concatenated into one. When `output_limit`
+    /// is set, collection stops once the output reaches that many bytes, and a
+    /// trailing marker block tells the model the output was truncated.
+    pub fn streaming<F, S>(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        input_schema: Value,
+        output_limit: impl Into<Option<usize>>,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(ToolInput) -> S + Send + Sync + 'static,
+        S: Stream<Item = Result<Value, ToolError>> + Send + 'static,
+    {
+        let output_limit = output_limit.into();
+        Self {
+            name: name.into(),
+            description: description.into(),
+            input_schema,
+            output_schema: None,
+            handler: Arc::new(move |input, _| {
+                Box::pin(collect_content(handler(input), output_limit))
+            }),
+        }
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -291,7 +918,33 @@ impl Tool {
     }
 
     pub fn call(&self, input: ToolInput) -> BoxFuture<'static, Result<Value, ToolError>> {
-        (self.handler)(input)
+        self.call_with_context(input, ToolContext::default())
+    }
+
+    pub fn call_with_context(
+        &self,
+        input: ToolInput,
+        ctx: ToolContext,
+    ) -> BoxFuture<'static, Result<Value, ToolError>> {
+        (self.handler)(input, ctx)
+    }
+
+    /// Calls the handler on its own task, so a panic in the handler becomes a
+    /// [`ToolError::Aborted`] instead of unwinding into the caller.
+    pub async fn call_isolated(&self, input: ToolInput) -> Result<Value, ToolError> {
+        self.call_isolated_with_context(input, ToolContext::default())
+            .await
+    }
+
+    pub(crate) async fn call_isolated_with_context(
+        &self,
+        input: ToolInput,
+        ctx: ToolContext,
+    ) -> Result<Value, ToolError> {
+        let handler = Arc::clone(&self.handler);
+        util::spawn_isolated(async move { handler(input, ctx).await })
+            .await
+            .unwrap_or_else(|e| Err(ToolError::Aborted(util::join_error_message(e))))
     }
 
     #[must_use]
@@ -305,184 +958,110 @@ impl Tool {
     }
 }
 
-#[cfg(test)]
-#[allow(unused)]
-mod tests {
-    use schemars::JsonSchema;
-    use serde::Deserialize;
-
-    use super::*;
-
-    #[test]
-    fn test_schema_for_optional_fields() {
-        #[derive(JsonSchema)]
-        struct OptionalInput {
-            required_field: String,
-            optional_field: Option<String>,
-        }
-
-        let schema = util::schema_for::<OptionalInput>();
-        let required = schema.get("required").and_then(|v| v.as_array());
-        assert!(required.is_some());
-        let required = required.unwrap();
-        assert!(
-            required
-                .iter()
-                .any(|v| v.as_str() == Some("required_field"))
-        );
-    }
-
-    #[test]
-    fn test_schema_for_nested_struct() {
-        #[derive(JsonSchema)]
-        struct Inner {
-            value: i32,
+async fn collect_content<S>(stream: S, limit: Option<usize>) -> Result<Value, ToolError>
+where
+    S: Stream<Item = Result<Value, ToolError>>,
+{
+    let mut stream = std::pin::pin!(stream);
+    let mut blocks = Vec::<Value>::new();
+    let mut text = String::new();
+    let mut size = 0;
+    let mut truncated = false;
+
+    while let Some(block) = stream.next().await {
+        let block = block?;
+        let remaining = limit.map_or(usize::MAX, |limit| limit.saturating_sub(size));
+
+        if let Some(chunk) = block
+            .get("type")
+            .filter(|t| *t == "text")
+            .and_then(|_| block.get("text"))
+            .and_then(Value::as_str)
+        {
+            let mut end = chunk.len().min(remaining);
+            while !chunk.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.push_str(&chunk[..end]);
+            size += end;
+            truncated = end < chunk.len();
+        } else {
+            let block_size = block.to_string().len();
+            truncated = block_size > remaining;
+            if !truncated {
+                if !text.is_empty() {
+                    blocks.push(json!({"type": "text", "text": std::mem::take(&mut text)}));
+                }
+                blocks.push(block);
+                size += block_size;
+            }
         }
 
-        #[derive(JsonSchema)]
-        struct Outer {
-            inner: Inner,
-            name: String,
+        if truncated {
+            break;
         }
-
-        let schema = util::schema_for::<Outer>();
-        let props = schema.get("properties").unwrap();
-        assert!(props.get("inner").is_some());
-        assert!(props.get("name").is_some());
     }
 
-    #[test]
-    fn test_schema_for_enum() {
-        #[derive(JsonSchema)]
-        #[serde(rename_all = "snake_case")]
-        enum Status {
-            Pending,
-            Active,
-            Completed,
-        }
-
-        #[derive(JsonSchema)]
-        struct TaskInput {
-            status: Status,
-        }
-
-        let schema = util::schema_for::<TaskInput>();
-        assert!(schema.get("properties").is_some());
+    if !text.is_empty() {
+        blocks.push(json!({"type": "text", "text": text}));
     }
 
-    #[test]
-    fn test_schema_for_vec() {
-        #[derive(JsonSchema)]
-        struct ListInput {
-            items: Vec<String>,
-            numbers: Vec<i32>,
-        }
-
-        let schema = util::schema_for::<ListInput>();
-        let props = schema.get("properties").unwrap();
-
-        let items_schema = props.get("items").unwrap();
-        assert_eq!(
-            items_schema.get("type").and_then(|v| v.as_str()),
-            Some("array")
-        );
+    if truncated {
+        blocks.push(json!({
+            "type": "text",
+            "text": format!("[output truncated after {size} bytes]"),
+        }));
     }
 
-    #[test]
-    fn test_schema_with_descriptions() {
-        #[derive(JsonSchema)]
-        struct DocumentedInput {
-            #[schemars(description = "The user's full name")]
-            name: String,
-            #[schemars(description = "Age in years")]
-            age: u32,
-        }
-
-        let schema = util::schema_for::<DocumentedInput>();
-        let props = schema.get("properties").unwrap();
+    Ok(Value::Array(blocks))
+}
 
-        let name_schema = props.get("name").unwrap();
-        assert_eq!(
-            name_schema.get("description").and_then(|v| v.as_str()),
-            Some("The user's full name")
-        );
-    }
+#[cfg(test)]
+#[allow(unused)]
+mod tests {
+    use super::*;
 
     #[test]
-    fn test_schema_with_defaults() {
-        #[derive(JsonSchema)]
-        struct DefaultsInput {
-            #[schemars(default = "default_name")]
-            name: String,
-        }
-
-        fn default_name() -> String {
-            "Anonymous".to_string()
+    fn test_tool_name_round_trips() {
+        for name in ["Bash", "mcp__fs__read_file", "mcp__a__b__c", "mcp__broken"] {
+            assert_eq!(ToolName::parse(name).to_string(), name);
         }
-
-        let schema = util::schema_for::<DefaultsInput>();
-        assert!(schema.get("properties").is_some());
+        assert_eq!(ToolName::parse("mcp__a__b__c"), ToolName::mcp("a", "b__c"));
+        assert_eq!(ToolName::parse("mcp__broken").server(), None);
     }
 
     #[test]
-    fn test_typed_tool_creation() {
-        #[derive(JsonSchema, Deserialize)]
-        struct GreetInput {
-            name: String,
-        }
-
-        let tool = Tool::unstructured("greet", "Greet a person", |input: GreetInput| async move {
-            Ok(Tool::text_result(&format!("Hello, {}!", input.name)))
-        });
-
-        assert_eq!(tool.name(), "greet");
-        assert_eq!(tool.description(), "Greet a person");
-
-        let schema = tool.input_schema();
-        assert_eq!(schema.get("type").and_then(|v| v.as_str()), Some("object"));
-    }
-
-    #[tokio::test]
-    async fn test_typed_tool_execution() {
-        #[derive(JsonSchema, Deserialize)]
-        struct AddInput {
-            a: i32,
-            b: i32,
+    fn test_tool_selector_parses_and_renders() {
+        for (input, rendered) in [
+            ("Read", "Read"),
+            ("Bash(git diff:*)", "Bash(git diff:*)"),
+            ("mcp__github", "mcp__github"),
+            ("mcp__github__*", "mcp__github"),
+            ("mcp__github__create_issue", "mcp__github__create_issue"),
+            ("FutureTool", "FutureTool"),
+        ] {
+            assert_eq!(
+                ToolSelector::parse(input).unwrap().to_string(),
+                rendered,
+                "{input}"
+            );
         }
-
-        let tool = Tool::unstructured("add", "Add two numbers", |input: AddInput| async move {
-            Ok(Tool::text_result(&format!("{}", input.a + input.b)))
-        });
-
-        let input = ToolInput::new(json!({"a": 5, "b": 3}));
-        let result = tool.call(input).await.unwrap();
-
-        let text = result
-            .as_array()
-            .and_then(|a| a.first())
-            .and_then(|v| v.get("text"))
-            .and_then(|v| v.as_str());
-        assert_eq!(text, Some("8"));
-    }
-
-    #[tokio::test]
-    async fn test_typed_tool_deserialization_error() {
-        #[derive(JsonSchema, Deserialize)]
-        struct StrictInput {
-            required_field: String,
+        assert!(matches!(
+            ToolSelector::parse("FutureTool").unwrap(),
+            ToolSelector::Other { .. }
+        ));
+
+        for input in [
+            "",
+            "Bash(git",
+            "Bash()",
+            "Read File",
+            "mcp__",
+            "mcp__a__b(x)",
+            "Re*d",
+        ] {
+            assert!(ToolSelector::parse(input).is_err(), "{input}");
         }
-
-        let tool = Tool::unstructured(
-            "strict",
-            "Requires field",
-            |_input: StrictInput| async move { Ok(Tool::text_result("ok")) },
-        );
-
-        let input = ToolInput::new(json!({}));
-        let result = tool.call(input).await;
-
-        assert!(result.is_err());
-        assert!(matches!(result, Err(ToolError::DeserializationFailed(_))));
     }
 
     #[test]
@@ -512,29 +1091,6 @@ mod tests {
         assert_eq!(item.get("is_error").and_then(|v| v.as_bool()), Some(true));
     }
 
-    #[test]
-    fn test_complex_nested_schema() {
-        #[derive(JsonSchema)]
-        struct Address {
-            street: String,
-            city: String,
-            zip: Option<String>,
-        }
-
-        #[derive(JsonSchema)]
-        struct Person {
-            name: String,
-            age: u32,
-            addresses: Vec<Address>,
-        }
-
-        let schema = util::schema_for::<Person>();
-        let props = schema.get("properties").unwrap();
-        assert!(props.get("name").is_some());
-        assert!(props.get("age").is_some());
-        assert!(props.get("addresses").is_some());
-    }
-
     #[test]
     fn test_tool_error_variants() {
         let err = ToolError::missing_parameter("name");
@@ -554,203 +1110,535 @@ mod tests {
         assert!(err.to_string().contains("read access"));
     }
 
-    #[test]
-    fn test_weather_tool_schema_matches_claude_api() {
-        #[derive(JsonSchema, Deserialize)]
-        #[serde(rename_all = "lowercase")]
-        enum TemperatureUnit {
-            Celsius,
-            Fahrenheit,
-        }
-
-        #[derive(JsonSchema, Deserialize)]
-        struct GetWeatherInput {
-            #[schemars(description = "The city and state, e.g. San Francisco, CA")]
-            location: String,
-            #[schemars(description = "The unit of temperature")]
-            unit: Option<TemperatureUnit>,
-        }
-
-        let tool = Tool::unstructured(
-            "get_weather",
-            "Get the current weather in a given location",
-            |_input: GetWeatherInput| async move { Ok(Tool::text_result("72°F")) },
-        );
+    #[tokio::test]
+    async fn test_call_isolated_catches_panic() {
+        let tool = Tool::new("boom", "panics", serde_json::json!({}), None, |_| async {
+            panic!("handler exploded")
+        });
 
-        assert_eq!(tool.name(), "get_weather");
-        assert_eq!(
-            tool.description(),
-            "Get the current weather in a given location"
-        );
+        let err = tool
+            .call_isolated(ToolInput::new(serde_json::json!({})))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::Aborted(_)));
+        assert!(err.to_string().contains("handler exploded"));
+    }
 
-        let schema = tool.input_schema();
+    #[tokio::test]
+    async fn test_streaming_tool_concatenates_text() {
+        let tool = Tool::streaming("stream", "streams", json!({}), None, |_| {
+            futures::stream::iter(["a", "b", "c"].map(|s| Ok(json!({"type": "text", "text": s}))))
+        });
 
-        assert_eq!(schema.get("type").and_then(|v| v.as_str()), Some("object"));
+        let content = tool.call(ToolInput::empty()).await.unwrap();
+        assert_eq!(content, json!([{"type": "text", "text": "abc"}]));
+    }
 
-        let props = schema.get("properties").expect("should have properties");
+    #[tokio::test]
+    async fn test_streaming_tool_truncates_at_limit() {
+        let tool = Tool::streaming("stream", "streams", json!({}), 4, |_| {
+            futures::stream::iter(
+                ["abc", "def", "ghi"].map(|s| Ok(json!({"type": "text", "text": s}))),
+            )
+        });
 
-        let location = props.get("location").expect("should have location");
-        assert_eq!(
-            location.get("type").and_then(|v| v.as_str()),
-            Some("string")
-        );
+        let content = tool.call(ToolInput::empty()).await.unwrap();
         assert_eq!(
-            location.get("description").and_then(|v| v.as_str()),
-            Some("The city and state, e.g. San Francisco, CA")
+            content,
+            json!([
+                {"type": "text", "text": "abcd"},
+                {"type": "text", "text": "[output truncated after 4 bytes]"},
+            ])
         );
-
-        let required = schema
-            .get("required")
-            .and_then(|v| v.as_array())
-            .expect("should have required array");
-        assert!(required.iter().any(|v| v.as_str() == Some("location")));
-        assert!(!required.iter().any(|v| v.as_str() == Some("unit")));
     }
 
-    #[test]
-    fn test_enum_generates_enum_values() {
-        #[derive(JsonSchema)]
-        #[serde(rename_all = "lowercase")]
-        enum Color {
-            Red,
-            Green,
-            Blue,
-        }
-
-        #[derive(JsonSchema)]
-        struct ColorInput {
-            color: Color,
-        }
-
-        let schema = util::schema_for::<ColorInput>();
-        let defs = schema.get("definitions").or_else(|| schema.get("$defs"));
-
-        if let Some(defs) = defs {
-            let color_def = defs.get("Color");
-            if let Some(color_def) = color_def {
-                if let Some(enum_values) = color_def.get("enum").and_then(|v| v.as_array()) {
-                    let values: Vec<&str> = enum_values.iter().filter_map(|v| v.as_str()).collect();
-                    assert!(values.contains(&"red"));
-                    assert!(values.contains(&"green"));
-                    assert!(values.contains(&"blue"));
+    #[tokio::test]
+    async fn test_sample_round_trip() {
+        use crate::sampling::{SamplingMessage, SamplingParams};
+
+        let requests = crate::mcp_peer::PeerRequests::new();
+        let ctx = ToolContext::new().with_peer(requests.peer("triage"));
+        let answer = async {
+            let request = requests.next().await;
+            let params = &request.message["params"];
+            assert_eq!(request.message["method"], "sampling/createMessage");
+            assert_eq!(params["maxTokens"], 16);
+            assert_eq!(params["messages"][0]["role"], "user");
+            assert_eq!(params["messages"][0]["content"]["text"], "Is this a bug?");
+            assert_eq!(params["modelPreferences"]["hints"][0]["name"], "haiku");
+            let response = crate::proto::SuccessResponse::new("1").with_response(json!({
+                "mcp_response": {
+                    "jsonrpc": "2.0",
+                    "id": request.message["id"],
+                    "result": {
+                        "role": "assistant",
+                        "content": {"type": "text", "text": "yes"},
+                        "model": "claude-haiku",
+                        "stopReason": "endTurn",
+                    },
                 }
+            }));
+            let _ = request
+                .reply
+                .send(crate::proto::Response::Success(response));
+        };
+
+        let messages = [SamplingMessage::user("Is this a bug?")];
+        let params = SamplingParams::new(16).model_hint("haiku");
+        let (sampled, ()) = tokio::join!(ctx.sample(&messages, &params), answer);
+        let sampled = sampled.unwrap();
+        assert_eq!(sampled.text(), "yes");
+        assert_eq!(sampled.model(), "claude-haiku");
+        assert_eq!(sampled.stop_reason(), Some("endTurn"));
+    }
+
+    #[cfg(feature = "schema")]
+    mod schema {
+        use schemars::JsonSchema;
+        use serde::Deserialize;
+
+        use super::*;
+
+        #[test]
+        fn test_schema_for_optional_fields() {
+            #[derive(JsonSchema)]
+            struct OptionalInput {
+                required_field: String,
+                optional_field: Option<String>,
             }
+
+            let schema = util::schema_for::<OptionalInput>();
+            let required = schema.get("required").and_then(|v| v.as_array());
+            assert!(required.is_some());
+            let required = required.unwrap();
+            assert!(
+                required
+                    .iter()
+                    .any(|v| v.as_str() == Some("required_field"))
+            );
         }
-    }
 
-    #[test]
-    fn test_tool_with_multiple_required_fields() {
-        #[derive(JsonSchema, Deserialize)]
-        struct MultiRequiredInput {
-            #[schemars(description = "First required field")]
-            field_a: String,
-            #[schemars(description = "Second required field")]
-            field_b: i32,
-            #[schemars(description = "Optional field")]
-            field_c: Option<bool>,
+        #[test]
+        fn test_schema_for_nested_struct() {
+            #[derive(JsonSchema)]
+            struct Inner {
+                value: i32,
+            }
+
+            #[derive(JsonSchema)]
+            struct Outer {
+                inner: Inner,
+                name: String,
+            }
+
+            let schema = util::schema_for::<Outer>();
+            let props = schema.get("properties").unwrap();
+            assert!(props.get("inner").is_some());
+            assert!(props.get("name").is_some());
         }
 
-        let schema = util::schema_for::<MultiRequiredInput>();
+        #[test]
+        fn test_schema_for_enum() {
+            #[derive(JsonSchema)]
+            #[serde(rename_all = "snake_case")]
+            enum Status {
+                Pending,
+                Active,
+                Completed,
+            }
 
-        let required = schema
-            .get("required")
-            .and_then(|v| v.as_array())
-            .expect("should have required");
+            #[derive(JsonSchema)]
+            struct TaskInput {
+                status: Status,
+            }
 
-        assert!(required.iter().any(|v| v.as_str() == Some("field_a")));
-        assert!(required.iter().any(|v| v.as_str() == Some("field_b")));
-        assert!(!required.iter().any(|v| v.as_str() == Some("field_c")));
-    }
+            let schema = util::schema_for::<TaskInput>();
+            assert!(schema.get("properties").is_some());
+        }
 
-    #[tokio::test]
-    async fn test_typed_tool_with_weather_input() {
-        #[derive(JsonSchema, Deserialize)]
-        struct WeatherInput {
-            location: String,
-            unit: Option<String>,
-        }
-
-        let tool = Tool::unstructured(
-            "get_weather",
-            "Get weather",
-            |input: WeatherInput| async move {
-                Ok(Tool::text_result(&format!("Weather in {}", input.location)))
-            },
-        );
+        #[test]
+        fn test_schema_for_vec() {
+            #[derive(JsonSchema)]
+            struct ListInput {
+                items: Vec<String>,
+                numbers: Vec<i32>,
+            }
 
-        let input = ToolInput::new(json!({"location": "San Francisco, CA"}));
-        let result = tool.call(input).await.unwrap();
-        let text = result
-            .as_array()
-            .and_then(|a| a.first())
-            .and_then(|v| v.get("text"))
-            .and_then(|v| v.as_str());
-        assert_eq!(text, Some("Weather in San Francisco, CA"));
-
-        let input_with_unit = ToolInput::new(json!({"location": "NYC", "unit": "celsius"}));
-        let result = tool.call(input_with_unit).await.unwrap();
-        let text = result
-            .as_array()
-            .and_then(|a| a.first())
-            .and_then(|v| v.get("text"))
-            .and_then(|v| v.as_str());
-        assert_eq!(text, Some("Weather in NYC"));
-    }
+            let schema = util::schema_for::<ListInput>();
+            let props = schema.get("properties").unwrap();
 
-    #[test]
-    fn test_schema_integer_types() {
-        #[derive(JsonSchema)]
-        struct NumberInput {
-            count: i32,
-            amount: i64,
-            small: u8,
+            let items_schema = props.get("items").unwrap();
+            assert_eq!(
+                items_schema.get("type").and_then(|v| v.as_str()),
+                Some("array")
+            );
         }
 
-        let schema = util::schema_for::<NumberInput>();
-        let props = schema.get("properties").unwrap();
+        #[test]
+        fn test_schema_with_descriptions() {
+            #[derive(JsonSchema)]
+            struct DocumentedInput {
+                #[schemars(description = "The user's full name")]
+                name: String,
+                #[schemars(description = "Age in years")]
+                age: u32,
+            }
 
-        let count = props.get("count").unwrap();
-        assert_eq!(count.get("type").and_then(|v| v.as_str()), Some("integer"));
+            let schema = util::schema_for::<DocumentedInput>();
+            let props = schema.get("properties").unwrap();
 
-        let amount = props.get("amount").unwrap();
-        assert_eq!(amount.get("type").and_then(|v| v.as_str()), Some("integer"));
-    }
+            let name_schema = props.get("name").unwrap();
+            assert_eq!(
+                name_schema.get("description").and_then(|v| v.as_str()),
+                Some("The user's full name")
+            );
+        }
 
-    #[test]
-    fn test_schema_boolean_type() {
-        #[derive(JsonSchema)]
-        struct FlagInput {
-            enabled: bool,
-            verbose: Option<bool>,
+        #[test]
+        fn test_schema_with_defaults() {
+            #[derive(JsonSchema)]
+            struct DefaultsInput {
+                #[schemars(default = "default_name")]
+                name: String,
+            }
+
+            fn default_name() -> String {
+                "Anonymous".to_string()
+            }
+
+            let schema = util::schema_for::<DefaultsInput>();
+            assert!(schema.get("properties").is_some());
         }
 
-        let schema = util::schema_for::<FlagInput>();
-        let props = schema.get("properties").unwrap();
+        #[test]
+        fn test_typed_tool_creation() {
+            #[derive(JsonSchema, Deserialize)]
+            struct GreetInput {
+                name: String,
+            }
 
-        let enabled = props.get("enabled").unwrap();
-        assert_eq!(
-            enabled.get("type").and_then(|v| v.as_str()),
-            Some("boolean")
-        );
-    }
+            let tool =
+                Tool::unstructured("greet", "Greet a person", |input: GreetInput| async move {
+                    Ok(Tool::text_result(&format!("Hello, {}!", input.name)))
+                });
 
-    #[test]
-    fn test_schema_array_of_objects() {
-        #[derive(JsonSchema)]
-        struct Item {
-            id: i32,
-            name: String,
+            assert_eq!(tool.name(), "greet");
+            assert_eq!(tool.description(), "Greet a person");
+
+            let schema = tool.input_schema();
+            assert_eq!(schema.get("type").and_then(|v| v.as_str()), Some("object"));
+        }
+
+        #[tokio::test]
+        async fn test_typed_tool_execution() {
+            #[derive(JsonSchema, Deserialize)]
+            struct AddInput {
+                a: i32,
+                b: i32,
+            }
+
+            let tool = Tool::unstructured("add", "Add two numbers", |input: AddInput| async move {
+                Ok(Tool::text_result(&format!("{}", input.a + input.b)))
+            });
+
+            let input = ToolInput::new(json!({"a": 5, "b": 3}));
+            let result = tool.call(input).await.unwrap();
+
+            let text = result
+                .as_array()
+                .and_then(|a| a.first())
+                .and_then(|v| v.get("text"))
+                .and_then(|v| v.as_str());
+            assert_eq!(text, Some("8"));
+        }
+
+        #[tokio::test]
+        async fn test_typed_tool_deserialization_error() {
+            #[derive(JsonSchema, Deserialize)]
+            struct StrictInput {
+                required_field: String,
+            }
+
+            let tool = Tool::unstructured(
+                "strict",
+                "Requires field",
+                |_input: StrictInput| async move { Ok(Tool::text_result("ok")) },
+            );
+
+            let input = ToolInput::new(json!({}));
+            let result = tool.call(input).await;
+
+            assert!(result.is_err());
+            assert!(matches!(result, Err(ToolError::DeserializationFailed(_))));
         }
 
-        #[derive(JsonSchema)]
-        struct ListInput {
-            items: Vec<Item>,
+        #[test]
+        fn test_complex_nested_schema() {
+            #[derive(JsonSchema)]
+            struct Address {
+                street: String,
+                city: String,
+                zip: Option<String>,
+            }
+
+            #[derive(JsonSchema)]
+            struct Person {
+                name: String,
+                age: u32,
+                addresses: Vec<Address>,
+            }
+
+            let schema = util::schema_for::<Person>();
+            let props = schema.get("properties").unwrap();
+            assert!(props.get("name").is_some());
+            assert!(props.get("age").is_some());
+            assert!(props.get("addresses").is_some());
         }
 
-        let schema = util::schema_for::<ListInput>();
-        let props = schema.get("properties").unwrap();
+        #[test]
+        fn test_weather_tool_schema_matches_claude_api() {
+            #[derive(JsonSchema, Deserialize)]
+            #[serde(rename_all = "lowercase")]
+            enum TemperatureUnit {
+                Celsius,
+                Fahrenheit,
+            }
 
-        let items = props.get("items").unwrap();
-        assert_eq!(items.get("type").and_then(|v| v.as_str()), Some("array"));
+            #[derive(JsonSchema, Deserialize)]
+            struct GetWeatherInput {
+                #[schemars(description = "The city and state, e.g. San Francisco, CA")]
+                location: String,
+                #[schemars(description = "The unit of temperature")]
+                unit: Option<TemperatureUnit>,
+            }
+
+            let tool = Tool::unstructured(
+                "get_weather",
+                "Get the current weather in a given location",
+                |_input: GetWeatherInput| async move { Ok(Tool::text_result("72°F")) },
+            );
+
+            assert_eq!(tool.name(), "get_weather");
+            assert_eq!(
+                tool.description(),
+                "Get the current weather in a given location"
+            );
+
+            let schema = tool.input_schema();
+
+            assert_eq!(schema.get("type").and_then(|v| v.as_str()), Some("object"));
+
+            let props = schema.get("properties").expect("should have properties");
+
+            let location = props.get("location").expect("should have location");
+            assert_eq!(
+                location.get("type").and_then(|v| v.as_str()),
+                Some("string")
+            );
+            assert_eq!(
+                location.get("description").and_then(|v| v.as_str()),
+                Some("The city and state, e.g. San Francisco, CA")
+            );
+
+            let required = schema
+                .get("required")
+                .and_then(|v| v.as_array())
+                .expect("should have required array");
+            assert!(required.iter().any(|v| v.as_str() == Some("location")));
+            assert!(!required.iter().any(|v| v.as_str() == Some("unit")));
+        }
+
+        #[test]
+        #[allow(clippy::collapsible_if)]
+        fn test_enum_generates_enum_values() {
+            #[derive(JsonSchema)]
+            #[serde(rename_all = "lowercase")]
+            enum Color {
+                Red,
+                Green,
+                Blue,
+            }
+
+            #[derive(JsonSchema)]
+            struct ColorInput {
+                color: Color,
+            }
+
+            let schema = util::schema_for::<ColorInput>();
+            let defs = schema.get("definitions").or_else(|| schema.get("$defs"));
+
+            if let Some(defs) = defs {
+                let color_def = defs.get("Color");
+                if let Some(color_def) = color_def {
+                    if let Some(enum_values) = color_def.get("enum").and_then(|v| v.as_array()) {
+                        let values: Vec<&str> =
+                            enum_values.iter().filter_map(|v| v.as_str()).collect();
+                        assert!(values.contains(&"red"));
+                        assert!(values.contains(&"green"));
+                        assert!(values.contains(&"blue"));
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn test_tool_with_multiple_required_fields() {
+            #[derive(JsonSchema, Deserialize)]
+            struct MultiRequiredInput {
+                #[schemars(description = "First required field")]
+                field_a: String,
+                #[schemars(description = "Second required field")]
+                field_b: i32,
+                #[schemars(description = "Optional field")]
+                field_c: Option<bool>,
+            }
+
+            let schema = util::schema_for::<MultiRequiredInput>();
+
+            let required = schema
+                .get("required")
+                .and_then(|v| v.as_array())
+                .expect("should have required");
+
+            assert!(required.iter().any(|v| v.as_str() == Some("field_a")));
+            assert!(required.iter().any(|v| v.as_str() == Some("field_b")));
+            assert!(!required.iter().any(|v| v.as_str() == Some("field_c")));
+        }
+
+        #[tokio::test]
+        async fn test_typed_tool_with_weather_input() {
+            #[derive(JsonSchema, Deserialize)]
+            struct WeatherInput {
+                location: String,
+                unit: Option<String>,
+            }
+
+            let tool = Tool::unstructured(
+                "get_weather",
+                "Get weather",
+                |input: WeatherInput| async move {
+                    Ok(Tool::text_result(&format!("Weather in {}", input.location)))
+                },
+            );
+
+            let input = ToolInput::new(json!({"location": "San Francisco, CA"}));
+            let result = tool.call(input).await.unwrap();
+            let text = result
+                .as_array()
+                .and_then(|a| a.first())
+                .and_then(|v| v.get("text"))
+                .and_then(|v| v.as_str());
+            assert_eq!(text, Some("Weather in San Francisco, CA"));
+
+            let input_with_unit = ToolInput::new(json!({"location": "NYC", "unit": "celsius"}));
+            let result = tool.call(input_with_unit).await.unwrap();
+            let text = result
+                .as_array()
+                .and_then(|a| a.first())
+                .and_then(|v| v.get("text"))
+                .and_then(|v| v.as_str());
+            assert_eq!(text, Some("Weather in NYC"));
+        }
+
+        #[test]
+        fn test_schema_integer_types() {
+            #[derive(JsonSchema)]
+            struct NumberInput {
+                count: i32,
+                amount: i64,
+                small: u8,
+            }
+
+            let schema = util::schema_for::<NumberInput>();
+            let props = schema.get("properties").unwrap();
+
+            let count = props.get("count").unwrap();
+            assert_eq!(count.get("type").and_then(|v| v.as_str()), Some("integer"));
+
+            let amount = props.get("amount").unwrap();
+            assert_eq!(amount.get("type").and_then(|v| v.as_str()), Some("integer"));
+        }
+
+        #[test]
+        fn test_schema_boolean_type() {
+            #[derive(JsonSchema)]
+            struct FlagInput {
+                enabled: bool,
+                verbose: Option<bool>,
+            }
+
+            let schema = util::schema_for::<FlagInput>();
+            let props = schema.get("properties").unwrap();
+
+            let enabled = props.get("enabled").unwrap();
+            assert_eq!(
+                enabled.get("type").and_then(|v| v.as_str()),
+                Some("boolean")
+            );
+        }
+
+        #[test]
+        fn test_schema_array_of_objects() {
+            #[derive(JsonSchema)]
+            struct Item {
+                id: i32,
+                name: String,
+            }
+
+            #[derive(JsonSchema)]
+            struct ListInput {
+                items: Vec<Item>,
+            }
+
+            let schema = util::schema_for::<ListInput>();
+            let props = schema.get("properties").unwrap();
+
+            let items = props.get("items").unwrap();
+            assert_eq!(items.get("type").and_then(|v| v.as_str()), Some("array"));
+        }
+
+        #[tokio::test]
+        async fn test_elicit_round_trip() {
+            #[derive(Debug, PartialEq, serde::Deserialize, JsonSchema)]
+            struct Credentials {
+                user: String,
+            }
+
+            let requests = crate::mcp_peer::PeerRequests::new();
+            let ctx = ToolContext::new().with_peer(requests.peer("auth"));
+            let answer = async {
+                let request = requests.next().await;
+                assert_eq!(request.server_name, "auth");
+                assert_eq!(request.message["method"], "elicitation/create");
+                assert_eq!(request.message["params"]["message"], "Log in");
+                assert!(
+                    request.message["params"]["requestedSchema"]["properties"]["user"].is_object()
+                );
+                let response = crate::proto::SuccessResponse::new("1").with_response(json!({
+                    "mcp_response": {
+                        "jsonrpc": "2.0",
+                        "id": request.message["id"],
+                        "result": {"action": "accept", "content": {"user": "ada"}},
+                    }
+                }));
+                let _ = request
+                    .reply
+                    .send(crate::proto::Response::Success(response));
+            };
+
+            let (elicitation, ()) = tokio::join!(ctx.elicit::<Credentials>("Log in"), answer);
+            assert_eq!(
+                elicitation.unwrap(),
+                Elicitation::Accepted(Credentials {
+                    user: "ada".to_owned()
+                })
+            );
+            assert!(
+                ToolContext::new()
+                    .elicit::<Credentials>("Log in")
+                    .await
+                    .is_err()
+            );
+        }
     }
 }
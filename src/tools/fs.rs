@@ -0,0 +1,450 @@
+//! Filesystem tools confined to a sandbox root.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use clauders::tools::fs::FsSandbox;
+//! use clauders::{McpServer, Options};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let sandbox = FsSandbox::new("./workspace")?.with_max_file_bytes(256 * 1024);
+//! let options = Options::new().with_mcp_server("fs", Arc::new(McpServer::new("fs", sandbox.tools())));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::tool::{Tool, ToolError};
+
+/// A directory that filesystem tools are allowed to operate in.
+///
+/// Every path a tool receives is resolved relative to the root; paths that
+/// escape it, either lexically with `..` or through symlinks, are rejected.
+#[derive(Debug, Clone)]
+pub struct FsSandbox {
+    root: PathBuf,
+    max_file_bytes: u64,
+    max_entries: usize,
+    max_search_results: usize,
+}
+
+impl FsSandbox {
+    pub const DEFAULT_MAX_FILE_BYTES: u64 = 1024 * 1024;
+    pub const DEFAULT_MAX_ENTRIES: usize = 1000;
+    pub const DEFAULT_MAX_SEARCH_RESULTS: usize = 100;
+
+    /// Creates a sandbox rooted at `root`, which must be an existing directory.
+    pub fn new(root: impl AsRef<Path>) -> std::io::Result<Self> {
+        let root = std::fs::canonicalize(root)?;
+        if !root.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+
+        Ok(Self {
+            root,
+            max_file_bytes: Self::DEFAULT_MAX_FILE_BYTES,
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+            max_search_results: Self::DEFAULT_MAX_SEARCH_RESULTS,
+        })
+    }
+
+    /// Limits how many bytes are read from, or may be written to, a single file.
+    #[must_use]
+    pub fn with_max_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_bytes = bytes;
+        self
+    }
+
+    /// Limits how many entries `list_dir` returns.
+    #[must_use]
+    pub fn with_max_entries(mut self, entries: usize) -> Self {
+        self.max_entries = entries;
+        self
+    }
+
+    /// Limits how many matching lines `search` returns.
+    #[must_use]
+    pub fn with_max_search_results(mut self, results: usize) -> Self {
+        self.max_search_results = results;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves `path` against the root, rejecting anything outside it.
+    ///
+    /// The path does not need to exist, but its nearest existing ancestor must
+    /// resolve to a location inside the root.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> Result<PathBuf, ToolError> {
+        let path = path.as_ref();
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+
+        let mut resolved = self.root.clone();
+        for component in relative.components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::CurDir => {}
+                Component::ParentDir if resolved != self.root => {
+                    resolved.pop();
+                }
+                _ => return Err(self.outside(path)),
+            }
+        }
+
+        // Looks for the nearest entry without following links, so a dangling
+        // symlink counts as existing rather than letting the walk climb past
+        // it; canonicalizing it then fails instead of writing through it.
+        let mut existing = resolved.as_path();
+        while existing.symlink_metadata().is_err() {
+            existing = existing.parent().ok_or_else(|| self.outside(path))?;
+        }
+        let canonical = std::fs::canonicalize(existing).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => self.outside(path),
+            _ => ToolError::execution_failed(e.to_string()),
+        })?;
+        if !canonical.starts_with(&self.root) {
+            return Err(self.outside(path));
+        }
+
+        Ok(resolved)
+    }
+
+    /// Returns all filesystem tools: `read_file`, `write_file`, `list_dir` and `search`.
+    pub fn tools(&self) -> Vec<Tool> {
+        vec![
+            self.read_file_tool(),
+            self.write_file_tool(),
+            self.list_dir_tool(),
+            self.search_tool(),
+        ]
+    }
+
+    pub fn read_file_tool(&self) -> Tool {
+        let sandbox = Arc::new(self.clone());
+        Tool::unstructured::<ReadFileInput, _, _>(
+            "read_file",
+            "Read a UTF-8 text file inside the workspace.",
+            move |input| {
+                let sandbox = Arc::clone(&sandbox);
+                async move { sandbox.read_file(&input.path).await }
+            },
+        )
+    }
+
+    pub fn write_file_tool(&self) -> Tool {
+        let sandbox = Arc::new(self.clone());
+        Tool::unstructured::<WriteFileInput, _, _>(
+            "write_file",
+            "Create or overwrite a text file inside the workspace.",
+            move |input| {
+                let sandbox = Arc::clone(&sandbox);
+                async move { sandbox.write_file(&input.path, &input.content).await }
+            },
+        )
+    }
+
+    pub fn list_dir_tool(&self) -> Tool {
+        let sandbox = Arc::new(self.clone());
+        Tool::unstructured::<ListDirInput, _, _>(
+            "list_dir",
+            "List the entries of a directory inside the workspace. Directories end with '/'.",
+            move |input| {
+                let sandbox = Arc::clone(&sandbox);
+                async move { sandbox.list_dir(input.path.as_deref().unwrap_or(".")).await }
+            },
+        )
+    }
+
+    pub fn search_tool(&self) -> Tool {
+        let sandbox = Arc::new(self.clone());
+        Tool::unstructured::<SearchInput, _, _>(
+            "search",
+            "Search text files inside the workspace for lines containing a string.",
+            move |input| {
+                let sandbox = Arc::clone(&sandbox);
+                async move {
+                    sandbox
+                        .search(&input.pattern, input.path.as_deref().unwrap_or("."))
+                        .await
+                }
+            },
+        )
+    }
+
+    async fn read_file(&self, path: &str) -> Result<Value, ToolError> {
+        use tokio::io::AsyncReadExt;
+
+        let resolved = self.resolve(path)?;
+        let file = tokio::fs::File::open(&resolved)
+            .await
+            .map_err(|e| io_error(path, e))?;
+        let size = file.metadata().await.map_err(|e| io_error(path, e))?.len();
+
+        let mut bytes = Vec::new();
+        file.take(self.max_file_bytes)
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|e| io_error(path, e))?;
+
+        let mut text = match String::from_utf8(bytes) {
+            Ok(text) => text,
+            // A truncated read may stop partway through a multi-byte character.
+            Err(e) if size > self.max_file_bytes && e.utf8_error().error_len().is_none() => {
+                let valid = e.utf8_error().valid_up_to();
+                let mut bytes = e.into_bytes();
+                bytes.truncate(valid);
+                String::from_utf8(bytes).expect("prefix is valid UTF-8")
+            }
+            Err(_) => {
+                return Err(ToolError::execution_failed(format!(
+                    "{path} is not a UTF-8 text file"
+                )));
+            }
+        };
+
+        if size > self.max_file_bytes {
+            text.push_str(&format!(
+                "\n[truncated: showing {} of {size} bytes]",
+                self.max_file_bytes
+            ));
+        }
+
+        Ok(Tool::text_result(&text))
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<Value, ToolError> {
+        if content.len() as u64 > self.max_file_bytes {
+            return Err(ToolError::invalid_parameter(
+                "content",
+                format!("exceeds the {} byte limit", self.max_file_bytes),
+            ));
+        }
+
+        let resolved = self.resolve(path)?;
+        if let Some(parent) = resolved.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| io_error(path, e))?;
+        }
+        tokio::fs::write(&resolved, content)
+            .await
+            .map_err(|e| io_error(path, e))?;
+
+        Ok(Tool::text_result(&format!(
+            "wrote {} bytes to {path}",
+            content.len()
+        )))
+    }
+
+    async fn list_dir(&self, path: &str) -> Result<Value, ToolError> {
+        let resolved = self.resolve(path)?;
+        let mut dir = tokio::fs::read_dir(&resolved)
+            .await
+            .map_err(|e| io_error(path, e))?;
+
+        let mut entries = Vec::new();
+        while let Some(entry) = dir.next_entry().await.map_err(|e| io_error(path, e))? {
+            let mut name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().await.is_ok_and(|t| t.is_dir()) {
+                name.push('/');
+            }
+            entries.push(name);
+        }
+        entries.sort();
+
+        let total = entries.len();
+        entries.truncate(self.max_entries);
+        let mut listing = entries.join("\n");
+        if total > self.max_entries {
+            listing.push_str(&format!(
+                "\n[truncated: showing {} of {total} entries]",
+                self.max_entries
+            ));
+        }
+
+        Ok(Tool::text_result(&listing))
+    }
+
+    async fn search(&self, pattern: &str, path: &str) -> Result<Value, ToolError> {
+        if pattern.is_empty() {
+            return Err(ToolError::invalid_parameter("pattern", "must not be empty"));
+        }
+
+        let start = self.resolve(path)?;
+        let mut pending = vec![start];
+        let mut matches = Vec::new();
+
+        'walk: while let Some(current) = pending.pop() {
+            let metadata = match tokio::fs::symlink_metadata(&current).await {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+
+            if metadata.is_dir() {
+                let Ok(mut dir) = tokio::fs::read_dir(&current).await else {
+                    continue;
+                };
+                let mut children = Vec::new();
+                while let Ok(Some(entry)) = dir.next_entry().await {
+                    children.push(entry.path());
+                }
+                children.sort_unstable_by(|a, b| b.cmp(a));
+                pending.extend(children);
+                continue;
+            }
+
+            if !metadata.is_file() || metadata.len() > self.max_file_bytes {
+                continue;
+            }
+            let Ok(text) = tokio::fs::read_to_string(&current).await else {
+                continue;
+            };
+
+            let display = current.strip_prefix(&self.root).unwrap_or(&current);
+            for (line_no, line) in text.lines().enumerate() {
+                if line.contains(pattern) {
+                    matches.push(format!("{}:{}: {line}", display.display(), line_no + 1));
+                    if matches.len() >= self.max_search_results {
+                        matches.push(format!(
+                            "[stopped after {} matches]",
+                            self.max_search_results
+                        ));
+                        break 'walk;
+                    }
+                }
+            }
+        }
+
+        if matches.is_empty() {
+            return Ok(Tool::text_result("no matches"));
+        }
+        Ok(Tool::text_result(&matches.join("\n")))
+    }
+
+    fn outside(&self, path: &Path) -> ToolError {
+        ToolError::permission_denied(format!(
+            "{} is outside the workspace {}",
+            path.display(),
+            self.root.display()
+        ))
+    }
+}
+
+fn io_error(path: &str, error: std::io::Error) -> ToolError {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => ToolError::not_found(path.to_owned()),
+        std::io::ErrorKind::PermissionDenied => ToolError::permission_denied(path.to_owned()),
+        _ => ToolError::execution_failed(format!("{path}: {error}")),
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ReadFileInput {
+    /// Path of the file, relative to the workspace root.
+    path: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct WriteFileInput {
+    /// Path of the file, relative to the workspace root.
+    path: String,
+    /// The full contents to write.
+    content: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ListDirInput {
+    /// Directory to list, relative to the workspace root. Defaults to the root.
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SearchInput {
+    /// Text to look for; matched literally against each line.
+    pattern: String,
+    /// File or directory to search, relative to the workspace root. Defaults to the root.
+    path: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox() -> FsSandbox {
+        let dir = std::env::temp_dir().join(format!("clauders-fs-{}", crate::util::uuid_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        FsSandbox::new(dir).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_rejects_escapes() {
+        let sandbox = sandbox();
+        assert!(sandbox.resolve("a/../b.txt").is_ok());
+        assert!(matches!(
+            sandbox.resolve("../outside.txt"),
+            Err(ToolError::PermissionDenied(_))
+        ));
+        assert!(matches!(
+            sandbox.resolve("/etc/passwd"),
+            Err(ToolError::PermissionDenied(_))
+        ));
+        std::fs::remove_dir_all(sandbox.root()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_resolve_rejects_dangling_symlinks() {
+        let sandbox = sandbox();
+        let outside = std::env::temp_dir().join(format!("clauders-fs-{}", crate::util::uuid_v7()));
+        std::os::unix::fs::symlink(outside.join("new.txt"), sandbox.root().join("link")).unwrap();
+        std::os::unix::fs::symlink(&outside, sandbox.root().join("dir")).unwrap();
+
+        for path in ["link", "dir/new.txt"] {
+            assert!(matches!(
+                sandbox.resolve(path),
+                Err(ToolError::PermissionDenied(_))
+            ));
+        }
+        assert!(sandbox.write_file("link", "escaped").await.is_err());
+        assert!(!outside.exists());
+
+        std::fs::remove_dir_all(sandbox.root()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_read_and_search() {
+        let sandbox = sandbox().with_max_file_bytes(64);
+
+        sandbox
+            .write_file("notes/todo.txt", "first\nsecond needle\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            sandbox.read_file("notes/todo.txt").await.unwrap(),
+            Tool::text_result("first\nsecond needle\n")
+        );
+        assert_eq!(
+            sandbox.search("needle", ".").await.unwrap(),
+            Tool::text_result("notes/todo.txt:2: second needle")
+        );
+        assert!(
+            sandbox
+                .write_file("big.txt", &"x".repeat(65))
+                .await
+                .is_err()
+        );
+
+        std::fs::remove_dir_all(sandbox.root()).unwrap();
+    }
+}
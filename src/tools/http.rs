@@ -0,0 +1,266 @@
+//! An HTTP fetch tool restricted to an allowlist of domains.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use clauders::tools::http::Fetch;
+//! use clauders::{McpServer, Options};
+//!
+//! let fetch = Fetch::new(["docs.rs", "crates.io"])
+//!     .with_timeout(Duration::from_secs(10))
+//!     .with_header("Authorization", "Bearer ...");
+//! let options = Options::new().with_mcp_server(
+//!     "web",
+//!     Arc::new(McpServer::new("web", vec![fetch.tool()])),
+//! );
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Url;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::tool::{Tool, ToolError};
+
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+/// Creates a fetch tool for the given domains using the default limits.
+pub fn fetch_tool<I, S>(allowlist: I) -> Tool
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    Fetch::new(allowlist).tool()
+}
+
+/// Configuration for the `fetch` tool.
+///
+/// A domain in the allowlist also admits its subdomains, so `example.com`
+/// allows `api.example.com`. Redirects are only followed to allowed hosts.
+#[derive(Debug, Clone)]
+pub struct Fetch {
+    allowlist: Vec<String>,
+    max_bytes: usize,
+    timeout: Duration,
+    headers: Vec<(String, String)>,
+}
+
+impl Fetch {
+    pub const DEFAULT_MAX_BYTES: usize = 512 * 1024;
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub fn new<I, S>(allowlist: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allowlist: allowlist
+                .into_iter()
+                .map(|domain| {
+                    let domain = domain.into().to_ascii_lowercase();
+                    domain
+                        .strip_prefix("*.")
+                        .map(str::to_owned)
+                        .unwrap_or(domain)
+                })
+                .collect(),
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+            timeout: Self::DEFAULT_TIMEOUT,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Limits how much of the response body is returned to the model.
+    #[must_use]
+    pub fn with_max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds a header sent with every request, such as an API token.
+    ///
+    /// Credential headers are never echoed back to the model or logged.
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn allowlist(&self) -> &[String] {
+        &self.allowlist
+    }
+
+    /// Returns whether requests to `url` are permitted.
+    pub fn allows(&self, url: &Url) -> bool {
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+
+        self.allowlist.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+
+    /// Builds the `fetch` tool.
+    pub fn tool(&self) -> Tool {
+        let fetch = Arc::new(self.clone());
+        let client = Arc::new(self.client());
+        Tool::unstructured::<FetchInput, _, _>(
+            "fetch",
+            format!(
+                "Fetch a URL with an HTTP GET request. Only these domains (and their subdomains) are allowed: {}.",
+                self.allowlist.join(", ")
+            ),
+            move |input| {
+                let fetch = Arc::clone(&fetch);
+                let client = Arc::clone(&client);
+                async move {
+                    let client = client.as_ref().as_ref().map_err(|e| {
+                        ToolError::execution_failed(format!("HTTP client unavailable: {e}"))
+                    })?;
+                    fetch.fetch(client, &input.url).await
+                }
+            },
+        )
+    }
+
+    fn client(&self) -> Result<reqwest::Client, String> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+            let mut value = HeaderValue::from_str(value).map_err(|e| e.to_string())?;
+            value.set_sensitive(is_redacted(name.as_str()));
+            headers.insert(name, value);
+        }
+
+        let redirect_policy = {
+            let fetch = self.clone();
+            reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= 10 {
+                    attempt.error("too many redirects")
+                } else if fetch.allows(attempt.url()) {
+                    attempt.follow()
+                } else {
+                    let message = format!("redirect to {} is not allowed", attempt.url());
+                    attempt.error(message)
+                }
+            })
+        };
+
+        reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(self.timeout)
+            .redirect(redirect_policy)
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    async fn fetch(&self, client: &reqwest::Client, url: &str) -> Result<Value, ToolError> {
+        let url =
+            Url::parse(url).map_err(|e| ToolError::invalid_parameter("url", e.to_string()))?;
+        if !self.allows(&url) {
+            return Err(ToolError::permission_denied(format!(
+                "{} is not in the allowlist",
+                url.host_str().unwrap_or_default()
+            )));
+        }
+
+        tracing::debug!(url = %url, "fetching");
+        let mut response = client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| ToolError::execution_failed(e.without_url().to_string()))?;
+
+        let mut output = format!("{} {}\n", response.status(), response.url());
+        for (name, value) in response.headers() {
+            let value = if is_redacted(name.as_str()) {
+                "[redacted]"
+            } else {
+                value.to_str().unwrap_or("[binary]")
+            };
+            output.push_str(&format!("{name}: {value}\n"));
+        }
+        output.push('\n');
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| ToolError::execution_failed(e.without_url().to_string()))?
+        {
+            let remaining = self.max_bytes - body.len();
+            if chunk.len() > remaining {
+                body.extend_from_slice(&chunk[..remaining]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        output.push_str(&String::from_utf8_lossy(&body));
+        if truncated {
+            output.push_str(&format!(
+                "\n[truncated: body exceeds {} bytes]",
+                self.max_bytes
+            ));
+        }
+
+        Ok(Tool::text_result(&output))
+    }
+}
+
+fn is_redacted(header: &str) -> bool {
+    REDACTED_HEADERS
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case(header))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct FetchInput {
+    /// The http or https URL to fetch.
+    url: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_matches_domain_and_subdomains() {
+        let fetch = Fetch::new(["example.com", "*.docs.rs"]);
+
+        assert!(fetch.allows(&Url::parse("https://example.com/a").unwrap()));
+        assert!(fetch.allows(&Url::parse("https://api.example.com/a").unwrap()));
+        assert!(fetch.allows(&Url::parse("https://docs.rs/serde").unwrap()));
+        assert!(!fetch.allows(&Url::parse("https://badexample.com/").unwrap()));
+        assert!(!fetch.allows(&Url::parse("https://example.com.evil.net/").unwrap()));
+        assert!(!fetch.allows(&Url::parse("file:///etc/passwd").unwrap()));
+    }
+}
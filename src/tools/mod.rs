@@ -0,0 +1,19 @@
+//! Ready-made tools for in-process MCP servers.
+//!
+//! Each kit is behind its own cargo feature so that only the tools (and
+//! dependencies) an application actually uses are compiled in.
+
+#[cfg(feature = "tools-fs")]
+pub mod fs;
+
+#[cfg(feature = "tools-http")]
+pub mod http;
+
+#[cfg(feature = "tools-retrieval")]
+pub mod retrieval;
+
+#[cfg(feature = "tools-shell")]
+pub mod shell;
+
+#[cfg(feature = "tools-sql")]
+pub mod sql;
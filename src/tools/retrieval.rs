@@ -0,0 +1,277 @@
+//! A standard search tool over any retrieval backend.
+//!
+//! Implement [`Retriever`] for a vector store (qdrant, pgvector, ...) and
+//! wrap it with [`retrieval_tool`] to give the model a `search` tool with a
+//! consistent schema regardless of backend.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::tool::{Tool, ToolError};
+
+/// A document returned by a [`Retriever`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Document {
+    id: String,
+    content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    score: Option<f64>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    metadata: Map<String, Value>,
+}
+
+impl Document {
+    pub fn new(id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            content: content.into(),
+            score: None,
+            metadata: Map::new(),
+        }
+    }
+
+    // Getters
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn score(&self) -> Option<f64> {
+        self.score
+    }
+
+    pub fn metadata(&self) -> &Map<String, Value> {
+        &self.metadata
+    }
+
+    // Setters
+    pub fn set_score(&mut self, score: f64) {
+        self.score = Some(score);
+    }
+
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: Value) {
+        self.metadata.insert(key.into(), value);
+    }
+
+    // Builders
+    #[must_use]
+    pub fn with_score(mut self, score: f64) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    #[must_use]
+    pub fn with_metadata(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.metadata.insert(key.into(), value);
+        self
+    }
+}
+
+/// A backend that finds the documents most relevant to a query.
+#[async_trait]
+pub trait Retriever: Send + Sync {
+    /// Returns up to `k` documents, most relevant first.
+    async fn search(&self, query: &str, k: usize) -> Result<Vec<Document>, ToolError>;
+}
+
+#[async_trait]
+impl<R> Retriever for Arc<R>
+where
+    R: Retriever + ?Sized,
+{
+    async fn search(&self, query: &str, k: usize) -> Result<Vec<Document>, ToolError> {
+        (**self).search(query, k).await
+    }
+}
+
+/// Creates a `search` tool returning up to 5 documents by default.
+pub fn retrieval_tool<R>(retriever: R) -> Tool
+where
+    R: Retriever + 'static,
+{
+    Retrieval::new(retriever).tool()
+}
+
+/// Configuration for the `search` tool.
+pub struct Retrieval<R> {
+    retriever: Arc<R>,
+    name: String,
+    description: String,
+    default_k: usize,
+    max_k: usize,
+}
+
+impl<R> Retrieval<R>
+where
+    R: Retriever + 'static,
+{
+    pub const DEFAULT_K: usize = 5;
+    pub const MAX_K: usize = 50;
+
+    pub fn new(retriever: R) -> Self {
+        Self {
+            retriever: Arc::new(retriever),
+            name: "search".to_owned(),
+            description: "Search the knowledge base for documents relevant to a query.".to_owned(),
+            default_k: Self::DEFAULT_K,
+            max_k: Self::MAX_K,
+        }
+    }
+
+    /// Renames the tool, e.g. when registering several knowledge bases.
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Describes what the knowledge base contains, so the model knows when to search it.
+    #[must_use]
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the number of documents returned when the model does not ask for a count,
+    /// and the most it may ask for. At least one document is always returned.
+    #[must_use]
+    pub fn with_k(mut self, default_k: usize, max_k: usize) -> Self {
+        self.default_k = default_k;
+        self.max_k = max_k.max(default_k).max(1);
+        self
+    }
+
+    pub fn tool(&self) -> Tool {
+        let retriever = Arc::clone(&self.retriever);
+        let (default_k, max_k) = (self.default_k, self.max_k);
+
+        Tool::structured::<SearchInput, SearchOutput, _, _>(
+            self.name.clone(),
+            self.description.clone(),
+            move |input| {
+                let retriever = Arc::clone(&retriever);
+                async move {
+                    let k = input.k.unwrap_or(default_k).clamp(1, max_k);
+                    let mut documents = retriever.search(&input.query, k).await?;
+                    documents.truncate(k);
+                    Ok(SearchOutput { documents })
+                }
+            },
+        )
+    }
+}
+
+/// A naive in-memory retriever that ranks documents by query term overlap.
+///
+/// Useful for tests and prototypes; real deployments should use a vector store.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryRetriever {
+    documents: Vec<Document>,
+}
+
+impl InMemoryRetriever {
+    pub fn new(documents: impl IntoIterator<Item = Document>) -> Self {
+        Self {
+            documents: documents.into_iter().collect(),
+        }
+    }
+
+    pub fn push(&mut self, document: Document) {
+        self.documents.push(document);
+    }
+}
+
+#[async_trait]
+impl Retriever for InMemoryRetriever {
+    async fn search(&self, query: &str, k: usize) -> Result<Vec<Document>, ToolError> {
+        let terms = query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+
+        let mut scored = self
+            .documents
+            .iter()
+            .filter_map(|doc| {
+                let content = doc.content.to_lowercase();
+                let hits = terms
+                    .iter()
+                    .filter(|t| content.contains(t.as_str()))
+                    .count();
+                (hits > 0).then(|| doc.clone().with_score(hits as f64 / terms.len() as f64))
+            })
+            .collect::<Vec<_>>();
+
+        scored.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        scored.truncate(k);
+        Ok(scored)
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SearchInput {
+    /// What to search for.
+    query: String,
+    /// How many documents to return.
+    k: Option<usize>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct SearchOutput {
+    documents: Vec<Document>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::tool::ToolInput;
+
+    #[tokio::test]
+    async fn test_retrieval_tool_ranks_documents() {
+        let retriever = InMemoryRetriever::new([
+            Document::new("1", "Rust ownership and borrowing"),
+            Document::new("2", "Python decorators"),
+            Document::new("3", "Rust async runtimes"),
+        ]);
+        let tool = retrieval_tool(retriever);
+
+        let output = tool
+            .call(ToolInput::new(json!({"query": "rust async", "k": 2})))
+            .await
+            .unwrap();
+        let ids = output["documents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["id"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["3", "1"]);
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_tool_returns_at_least_one_document() {
+        let retriever = InMemoryRetriever::new([
+            Document::new("1", "Rust ownership and borrowing"),
+            Document::new("2", "Rust async runtimes"),
+        ]);
+        let tool = Retrieval::new(retriever).with_k(0, 0).tool();
+
+        for input in [json!({"query": "rust"}), json!({"query": "rust", "k": 0})] {
+            let output = tool.call(ToolInput::new(input)).await.unwrap();
+            assert_eq!(output["documents"].as_array().unwrap().len(), 1);
+        }
+    }
+}
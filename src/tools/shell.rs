@@ -0,0 +1,359 @@
+//! A command execution tool governed by an allow/deny policy.
+//!
+//! Commands are split into words and run directly, without a shell, so
+//! pipes, redirections and command substitution are rejected rather than
+//! interpreted. This keeps the policy check on the program name meaningful.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use clauders::tools::shell::{CommandPolicy, Shell};
+//!
+//! let tool = Shell::new(CommandPolicy::allow(["cargo", "git"]).deny_args(["push"]))
+//!     .with_cwd("./workspace")
+//!     .with_timeout(Duration::from_secs(120))
+//!     .tool();
+//! ```
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::io::AsyncReadExt;
+
+use crate::tool::{Tool, ToolError};
+
+const SHELL_METACHARACTERS: &[char] = &['|', '&', ';', '<', '>', '`', '$', '(', ')', '\n'];
+
+/// Decides which programs the command tool may run.
+#[derive(Debug, Clone, Default)]
+pub struct CommandPolicy {
+    allow: Option<Vec<String>>,
+    deny: Vec<String>,
+    deny_args: Vec<String>,
+}
+
+impl CommandPolicy {
+    /// Allows only the listed programs.
+    pub fn allow<I, S>(programs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allow: Some(programs.into_iter().map(Into::into).collect()),
+            ..Self::default()
+        }
+    }
+
+    /// Allows any program except the listed ones.
+    pub fn deny<I, S>(programs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            deny: programs.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Additionally rejects any command containing one of these arguments.
+    #[must_use]
+    pub fn deny_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.deny_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Checks a program and its arguments against the policy.
+    ///
+    /// Denied programs are matched by file name, wherever they are run from.
+    /// Allowed programs must be named exactly as listed, so allowing `git`
+    /// does not allow `./git` or any other file called `git`; list a full
+    /// path to allow a program by its path.
+    pub fn check(&self, program: &str, args: &[String]) -> Result<(), ToolError> {
+        let name = std::path::Path::new(program)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(program);
+
+        if self.deny.iter().any(|p| p == name || p == program) {
+            return Err(ToolError::permission_denied(format!("'{name}' is denied")));
+        }
+        if let Some(allow) = &self.allow
+            && !allow.iter().any(|p| p == program)
+        {
+            return Err(ToolError::permission_denied(format!(
+                "'{program}' is not in the allowed commands: {}",
+                allow.join(", ")
+            )));
+        }
+        if let Some(arg) = args.iter().find(|a| self.deny_args.contains(a)) {
+            return Err(ToolError::permission_denied(format!(
+                "argument '{arg}' is denied"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Creates a command tool that runs in the current directory with default limits.
+pub fn command_tool(policy: CommandPolicy) -> Tool {
+    Shell::new(policy).tool()
+}
+
+/// Configuration for the `run_command` tool.
+#[derive(Debug, Clone)]
+pub struct Shell {
+    policy: CommandPolicy,
+    cwd: Option<PathBuf>,
+    timeout: Duration,
+    max_output_bytes: usize,
+}
+
+impl Shell {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+    pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+    pub fn new(policy: CommandPolicy) -> Self {
+        Self {
+            policy,
+            cwd: None,
+            timeout: Self::DEFAULT_TIMEOUT,
+            max_output_bytes: Self::DEFAULT_MAX_OUTPUT_BYTES,
+        }
+    }
+
+    /// Pins the directory commands run in. Defaults to the process's current directory.
+    #[must_use]
+    pub fn with_cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Kills commands that run longer than `timeout`.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Limits how much of stdout and stderr, each, is returned to the model.
+    #[must_use]
+    pub fn with_max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = bytes;
+        self
+    }
+
+    pub fn policy(&self) -> &CommandPolicy {
+        &self.policy
+    }
+
+    /// Builds the `run_command` tool.
+    pub fn tool(&self) -> Tool {
+        let shell = Arc::new(self.clone());
+        let description = match &self.policy.allow {
+            Some(allow) => format!(
+                "Run a command (no shell features such as pipes or redirection). Allowed programs: {}.",
+                allow.join(", ")
+            ),
+            None => "Run a command (no shell features such as pipes or redirection).".to_owned(),
+        };
+
+        Tool::unstructured::<RunCommandInput, _, _>("run_command", description, move |input| {
+            let shell = Arc::clone(&shell);
+            async move { shell.run(&input.command).await }
+        })
+    }
+
+    async fn run(&self, command: &str) -> Result<Value, ToolError> {
+        let words = split_words(command)?;
+        let Some((program, args)) = words.split_first() else {
+            return Err(ToolError::invalid_parameter("command", "must not be empty"));
+        };
+        self.policy.check(program, args)?;
+
+        let mut cmd = tokio::process::Command::new(program);
+        cmd.args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+
+        tracing::debug!(program, ?args, "running command");
+        let mut child = cmd.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => ToolError::not_found(program.clone()),
+            _ => ToolError::execution_failed(format!("failed to run {program}: {e}")),
+        })?;
+
+        let stdout = read_capped(child.stdout.take(), self.max_output_bytes);
+        let stderr = read_capped(child.stderr.take(), self.max_output_bytes);
+        let run = async {
+            let (stdout, stderr) = tokio::join!(stdout, stderr);
+            let status = child.wait().await?;
+            Ok::<_, std::io::Error>((status, stdout?, stderr?))
+        };
+
+        let (status, stdout, stderr) = tokio::time::timeout(self.timeout, run)
+            .await
+            .map_err(|_| {
+                ToolError::execution_failed(format!(
+                    "command timed out after {}s",
+                    self.timeout.as_secs_f64()
+                ))
+            })?
+            .map_err(|e| ToolError::execution_failed(e.to_string()))?;
+
+        let mut output = match status.code() {
+            Some(code) => format!("exit code: {code}\n"),
+            None => "terminated by signal\n".to_owned(),
+        };
+        if !stdout.is_empty() {
+            output.push_str(&format!("stdout:\n{stdout}\n"));
+        }
+        if !stderr.is_empty() {
+            output.push_str(&format!("stderr:\n{stderr}\n"));
+        }
+
+        if status.success() {
+            Ok(Tool::text_result(&output))
+        } else {
+            Ok(Tool::error_result(&output))
+        }
+    }
+}
+
+/// Reads a child stream to the end, keeping at most `cap` bytes.
+async fn read_capped<R>(reader: Option<R>, cap: usize) -> std::io::Result<String>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let Some(mut reader) = reader else {
+        return Ok(String::new());
+    };
+
+    let mut kept = Vec::new();
+    let mut total = 0;
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        let take = n.min(cap.saturating_sub(kept.len()));
+        kept.extend_from_slice(&buf[..take]);
+        total += n;
+    }
+
+    let mut text = String::from_utf8_lossy(&kept).into_owned();
+    if total > cap {
+        text.push_str(&format!("\n[truncated: {total} bytes total]"));
+    }
+    Ok(text)
+}
+
+/// Splits a command line into words, honouring single and double quotes.
+fn split_words(command: &str) -> Result<Vec<String>, ToolError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) if SHELL_METACHARACTERS.contains(&c) => {
+                return Err(ToolError::invalid_parameter(
+                    "command",
+                    format!("shell syntax '{c}' is not supported; run one command at a time"),
+                ));
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(ToolError::invalid_parameter(
+            "command",
+            "unterminated quote",
+        ));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct RunCommandInput {
+    /// The command line to run, e.g. `cargo test --lib`.
+    command: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            split_words(r#"git commit -m "a message" 'x y'"#).unwrap(),
+            ["git", "commit", "-m", "a message", "x y"]
+        );
+        assert!(split_words("ls | sh").is_err());
+        assert!(split_words("echo $(id)").is_err());
+        assert!(split_words("echo 'open").is_err());
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = CommandPolicy::allow(["git"]).deny_args(["push"]);
+        assert!(policy.check("git", &["status".to_owned()]).is_ok());
+        assert!(policy.check("/usr/bin/git", &["push".to_owned()]).is_err());
+        assert!(policy.check("rm", &[]).is_err());
+        assert!(policy.check("./git", &["status".to_owned()]).is_err());
+        assert!(policy.check("/tmp/x/git", &[]).is_err());
+        assert!(
+            CommandPolicy::allow(["/usr/bin/git"])
+                .check("/usr/bin/git", &[])
+                .is_ok()
+        );
+        assert!(CommandPolicy::deny(["rm"]).check("/bin/rm", &[]).is_err());
+        assert!(CommandPolicy::deny(["rm"]).check("rm", &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_captures_output() {
+        let shell = Shell::new(CommandPolicy::allow(["echo"]));
+        let result = shell.run("echo hello").await.unwrap();
+        assert_eq!(
+            result,
+            Tool::text_result("exit code: 0\nstdout:\nhello\n\n")
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! A database query tool that only accepts parameterized statements.
+//!
+//! The tool is generic over a [`SqlBackend`]; a SQLite backend is available
+//! with the `tools-sqlite` feature, and other databases (for example a sqlx
+//! pool) can be plugged in by implementing the trait.
+//!
+//! ```no_run
+//! # #[cfg(feature = "tools-sqlite")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use clauders::tools::sql::{SqliteBackend, query_tool};
+//!
+//! let tool = query_tool(SqliteBackend::open("app.db")?);
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "tools-sqlite"))]
+//! # fn main() {}
+//! ```
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::tool::{Tool, ToolError};
+
+/// A database the query tool can run statements against.
+#[async_trait]
+pub trait SqlBackend: Send + Sync {
+    /// Runs a single statement with positional parameters, returning at most
+    /// `max_rows` rows. Backends should refuse statements that modify data
+    /// unless they were explicitly configured to allow writes.
+    async fn query(
+        &self,
+        sql: &str,
+        params: &[Value],
+        max_rows: usize,
+    ) -> Result<QueryResult, ToolError>;
+}
+
+/// Rows returned by a [`SqlBackend`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueryResult {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    truncated: bool,
+}
+
+impl QueryResult {
+    pub fn new(columns: Vec<String>, rows: Vec<Vec<Value>>, truncated: bool) -> Self {
+        Self {
+            columns,
+            rows,
+            truncated,
+        }
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &[Vec<Value>] {
+        &self.rows
+    }
+
+    /// Whether more rows were available than the row limit allowed.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+/// Creates a query tool over `backend` with the default row limit.
+pub fn query_tool<B>(backend: B) -> Tool
+where
+    B: SqlBackend + 'static,
+{
+    SqlQuery::new(backend).tool()
+}
+
+/// Configuration for the `sql_query` tool.
+pub struct SqlQuery<B> {
+    backend: Arc<B>,
+    max_rows: usize,
+    description: Option<String>,
+}
+
+impl<B> SqlQuery<B>
+where
+    B: SqlBackend + 'static,
+{
+    pub const DEFAULT_MAX_ROWS: usize = 200;
+
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            max_rows: Self::DEFAULT_MAX_ROWS,
+            description: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_max_rows(mut self, rows: usize) -> Self {
+        self.max_rows = rows;
+        self
+    }
+
+    /// Describes the database (e.g. its schema) to the model.
+    #[must_use]
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Builds the `sql_query` tool.
+    pub fn tool(&self) -> Tool {
+        let backend = Arc::clone(&self.backend);
+        let max_rows = self.max_rows;
+        let mut description = format!(
+            "Run a single SQL query. Values must be passed through `params` using \
+             placeholders (e.g. `?`); string literals are rejected. At most {max_rows} rows are returned."
+        );
+        if let Some(extra) = &self.description {
+            description.push_str("\n\n");
+            description.push_str(extra);
+        }
+
+        Tool::unstructured::<SqlQueryInput, _, _>("sql_query", description, move |input| {
+            let backend = Arc::clone(&backend);
+            async move {
+                check_parameterized(&input.sql)?;
+                let result = backend.query(&input.sql, &input.params, max_rows).await?;
+                let text = serde_json::to_string(&result)
+                    .map_err(|e| ToolError::execution_failed(e.to_string()))?;
+                Ok(Tool::text_result(&text))
+            }
+        })
+    }
+}
+
+/// Rejects statements that embed string literals or contain more than one statement.
+pub fn check_parameterized(sql: &str) -> Result<(), ToolError> {
+    let sql = sql.trim().trim_end_matches(';');
+    if sql.is_empty() {
+        return Err(ToolError::invalid_parameter("sql", "must not be empty"));
+    }
+    if sql.contains('\'') {
+        return Err(ToolError::invalid_parameter(
+            "sql",
+            "string literals are not allowed; pass values through params",
+        ));
+    }
+    if sql.contains(';') {
+        return Err(ToolError::invalid_parameter(
+            "sql",
+            "only a single statement is allowed",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SqlQueryInput {
+    /// The SQL statement, using placeholders for every value.
+    sql: String,
+    /// Values bound to the statement's placeholders, in order.
+    #[serde(default)]
+    params: Vec<Value>,
+}
+
+#[cfg(feature = "tools-sqlite")]
+pub use sqlite::SqliteBackend;
+
+#[cfg(feature = "tools-sqlite")]
+mod sqlite {
+    use std::path::Path;
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    use async_trait::async_trait;
+    use rusqlite::types::{Value as SqlValue, ValueRef};
+    use serde_json::{Value, json};
+
+    use super::{QueryResult, SqlBackend};
+    use crate::tool::ToolError;
+
+    /// A SQLite database accessed through `rusqlite`.
+    ///
+    /// Statements run on a blocking thread. Only read-only statements are
+    /// accepted unless [`SqliteBackend::allow_writes`] is set.
+    #[derive(Debug, Clone)]
+    pub struct SqliteBackend {
+        connection: Arc<Mutex<rusqlite::Connection>>,
+        allow_writes: bool,
+    }
+
+    impl SqliteBackend {
+        pub fn new(connection: rusqlite::Connection) -> Self {
+            Self {
+                connection: Arc::new(Mutex::new(connection)),
+                allow_writes: false,
+            }
+        }
+
+        pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+            rusqlite::Connection::open(path).map(Self::new)
+        }
+
+        #[must_use]
+        pub fn allow_writes(mut self, allow: bool) -> Self {
+            self.allow_writes = allow;
+            self
+        }
+    }
+
+    #[async_trait]
+    impl SqlBackend for SqliteBackend {
+        async fn query(
+            &self,
+            sql: &str,
+            params: &[Value],
+            max_rows: usize,
+        ) -> Result<QueryResult, ToolError> {
+            let connection = Arc::clone(&self.connection);
+            let allow_writes = self.allow_writes;
+            let sql = sql.to_owned();
+            let params = params.iter().map(to_sql).collect::<Vec<_>>();
+
+            tokio::task::spawn_blocking(move || {
+                let connection = connection.lock().unwrap_or_else(PoisonError::into_inner);
+                run(&connection, &sql, params, max_rows, allow_writes)
+            })
+            .await
+            .map_err(|e| ToolError::Aborted(crate::util::join_error_message(e)))?
+        }
+    }
+
+    fn run(
+        connection: &rusqlite::Connection,
+        sql: &str,
+        params: Vec<SqlValue>,
+        max_rows: usize,
+        allow_writes: bool,
+    ) -> Result<QueryResult, ToolError> {
+        let mut stmt = connection.prepare(sql).map_err(sql_error)?;
+        if !allow_writes && !stmt.readonly() {
+            return Err(ToolError::permission_denied(
+                "only read-only statements are allowed",
+            ));
+        }
+
+        let columns = stmt
+            .column_names()
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let mut rows = stmt
+            .query(rusqlite::params_from_iter(params))
+            .map_err(sql_error)?;
+
+        let mut collected = Vec::new();
+        let mut truncated = false;
+        while let Some(row) = rows.next().map_err(sql_error)? {
+            if collected.len() == max_rows {
+                truncated = true;
+                break;
+            }
+            let values = (0..columns.len())
+                .map(|i| row.get_ref(i).map(from_sql))
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(sql_error)?;
+            collected.push(values);
+        }
+
+        Ok(QueryResult::new(columns, collected, truncated))
+    }
+
+    fn sql_error(error: rusqlite::Error) -> ToolError {
+        ToolError::execution_failed(error.to_string())
+    }
+
+    fn to_sql(value: &Value) -> SqlValue {
+        match value {
+            Value::Null => SqlValue::Null,
+            Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+            Value::Number(n) => n
+                .as_i64()
+                .map(SqlValue::Integer)
+                .or_else(|| n.as_f64().map(SqlValue::Real))
+                .unwrap_or(SqlValue::Null),
+            Value::String(s) => SqlValue::Text(s.clone()),
+            other => SqlValue::Text(other.to_string()),
+        }
+    }
+
+    fn from_sql(value: ValueRef<'_>) -> Value {
+        match value {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(i) => json!(i),
+            ValueRef::Real(f) => json!(f),
+            ValueRef::Text(t) => json!(String::from_utf8_lossy(t)),
+            ValueRef::Blob(b) => json!(format!("<{} byte blob>", b.len())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_parameterized() {
+        assert!(check_parameterized("SELECT * FROM users WHERE id = ?;").is_ok());
+        assert!(check_parameterized("SELECT * FROM users WHERE name = 'bob'").is_err());
+        assert!(check_parameterized("SELECT 1; DROP TABLE users").is_err());
+    }
+
+    #[cfg(feature = "tools-sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_backend() {
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE t (id INTEGER, name TEXT);
+                 INSERT INTO t VALUES (1, 'a'), (2, 'b'), (3, 'c');",
+            )
+            .unwrap();
+        let backend = SqliteBackend::new(connection);
+
+        let result = backend
+            .query(
+                "SELECT id, name FROM t WHERE id > ?",
+                &[serde_json::json!(1)],
+                1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.columns(), ["id", "name"]);
+        assert_eq!(
+            result.rows(),
+            [vec![serde_json::json!(2), serde_json::json!("b")]]
+        );
+        assert!(result.truncated());
+
+        assert!(matches!(
+            backend.query("DELETE FROM t", &[], 10).await,
+            Err(ToolError::PermissionDenied(_))
+        ));
+    }
+}
@@ -1,58 +1,338 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::{Arc, PoisonError};
+use std::time::Duration;
 
 use serde_json::Value;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, Command};
+use tokio::sync::Mutex;
 
 use crate::agent::Agent;
 use crate::error::Error;
+use crate::limits::{Limits, ProcessLimits};
 use crate::options::Tools;
-use crate::proto::control::ResponseEnvelope;
+use crate::proto::control::{CancelRequestEnvelope, ResponseEnvelope};
 use crate::proto::{Incoming, RequestEnvelope};
+use crate::wire_log::{Direction, WireLog, WireLogWriter};
+
+/// The `tracing` target of every message sent to and received from the CLI.
+pub(crate) const PROTOCOL_TARGET: &str = "clauders::protocol";
+
+/// How long the stderr logging task may take to log what the CLI wrote
+/// before exiting.
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
 
 pub struct Transport {
-    child: Child,
-    stdin: Option<ChildStdin>,
-    stdout: BufReader<ChildStdout>,
-    stderr_task: tokio::task::JoinHandle<()>,
+    process: Process,
+    writer: TransportWriter,
+    stdout: LineReader,
+    stderr_task: Option<tokio::task::JoinHandle<()>>,
+    #[cfg(windows)]
+    _job: Option<crate::limits::JobObject>,
+    _lease: Option<crate::orphans::Lease>,
+    wire_log: SharedWireLog,
+    warn_unknown_fields: bool,
+    /// Whether the CLI was shut down, so it is not killed again on drop.
+    shut_down: bool,
+}
+
+/// The CLI process, on pipes or a pseudo-terminal.
+enum Process {
+    Piped(Child),
+    #[cfg(feature = "pty")]
+    Pty(crate::pty::PtyProcess),
+}
+
+impl Process {
+    fn id(&self) -> Option<u32> {
+        match self {
+            Self::Piped(child) => child.id(),
+            #[cfg(feature = "pty")]
+            Self::Pty(process) => process.id(),
+        }
+    }
+
+    /// Kills the process and, on Unix, the tools it started, which share its
+    /// process group.
+    fn start_kill(&mut self) -> std::io::Result<()> {
+        #[cfg(unix)]
+        if let Some(pid) = self.id() {
+            return crate::orphans::kill_group(pid);
+        }
+        match self {
+            Self::Piped(child) => child.start_kill(),
+            #[cfg(feature = "pty")]
+            Self::Pty(process) => process.start_kill(),
+        }
+    }
+
+    async fn wait(&mut self) -> Result<(), Error> {
+        match self {
+            Self::Piped(child) => {
+                child.wait().await?;
+                Ok(())
+            }
+            #[cfg(feature = "pty")]
+            Self::Pty(process) => process.wait().await,
+        }
+    }
 }
 
 impl std::fmt::Debug for Transport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Transport")
-            .field("pid", &self.child.id())
-            .field("stdin", &self.stdin.is_some())
+            .field("pid", &self.process.id())
+            .field("writer", &self.writer)
             .finish_non_exhaustive()
     }
 }
 
-#[derive(Debug, Clone, Default, derive_builder::Builder)]
-#[builder(default, setter(into, strip_option))]
-pub struct TransportOptions {
-    allowed_tools: Vec<String>,
-    disallowed_tools: Vec<String>,
-    tools: Option<Tools>,
-    model: Option<String>,
-    fallback_model: Option<String>,
-    system_prompt: Option<String>,
-    append_system_prompt: Option<String>,
-    permission_mode: Option<String>,
-    max_budget_usd: Option<f64>,
-    debug: bool,
-    cwd: Option<PathBuf>,
+pub(crate) type SharedWireLog = Arc<std::sync::Mutex<Option<WireLogWriter>>>;
+
+/// The CLI's input, written without holding the [`Transport`], so messages
+/// are sent while another task waits for the CLI's output. Clones write to
+/// the same input, a whole message at a time.
+#[derive(Clone)]
+pub(crate) struct TransportWriter {
+    stdin: Arc<Mutex<Option<Box<dyn AsyncWrite + Send + Unpin>>>>,
+    wire_log: SharedWireLog,
+}
+
+impl TransportWriter {
+    pub(crate) fn new(stdin: Box<dyn AsyncWrite + Send + Unpin>, wire_log: SharedWireLog) -> Self {
+        Self {
+            stdin: Arc::new(Mutex::new(Some(stdin))),
+            wire_log,
+        }
+    }
+
+    pub(crate) async fn send(&self, json: &Value) -> Result<(), Error> {
+        #[cfg(debug_assertions)]
+        if let Err(mismatch) = crate::proto::wire::validate_outgoing(json) {
+            tracing::error!(%mismatch, message = %json, "outgoing message does not match wire schema");
+            return Err(Error::ProtocolError(format!(
+                "outgoing message does not match wire schema: {mismatch}"
+            )));
+        }
+        let data = serde_json::to_string(json)?;
+        self.send_line(&data).await
+    }
+
+    /// Sends a message already serialised to a single line of JSON.
+    pub(crate) async fn send_line(&self, data: &str) -> Result<(), Error> {
+        let mut stdin = self.stdin.lock().await;
+        let stdin = stdin
+            .as_mut()
+            .ok_or_else(|| Error::ProcessError("stdin closed".to_owned()))?;
+        tracing::debug!(target: PROTOCOL_TARGET, data = %data, "sending");
+        if let Some(log) = self
+            .wire_log
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            log.record(Direction::Out, data);
+        }
+        stdin.write_all(data.as_bytes()).await?;
+        stdin.write_all(b"\n").await?;
+        stdin.flush().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn send_request(&self, envelope: &RequestEnvelope) -> Result<(), Error> {
+        let json = serde_json::to_value(envelope)?;
+        self.send(&json).await
+    }
+
+    pub(crate) async fn send_response(&self, envelope: &ResponseEnvelope) -> Result<(), Error> {
+        let json = serde_json::to_value(envelope)?;
+        self.send(&json).await
+    }
+
+    pub(crate) async fn send_cancel(&self, envelope: &CancelRequestEnvelope) -> Result<(), Error> {
+        let json = serde_json::to_value(envelope)?;
+        self.send(&json).await
+    }
+
+    pub(crate) async fn interrupt(&self) -> Result<(), Error> {
+        tracing::info!("sending interrupt signal");
+        let envelope = RequestEnvelope::interrupt("");
+        self.send_request(&envelope).await
+    }
+
+    /// Closes the input, which the CLI takes as the end of the session.
+    async fn close(&self) {
+        self.stdin.lock().await.take();
+    }
+}
+
+impl std::fmt::Debug for TransportWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransportWriter")
+            .field(
+                "open",
+                &self.stdin.try_lock().map_or(true, |stdin| stdin.is_some()),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+/// The CLI's output, read a line at a time up to a length limit.
+struct LineReader {
+    reader: BufReader<Box<dyn AsyncRead + Send + Unpin>>,
+    /// The part of the current line read so far.
+    line: Vec<u8>,
+    /// Whether the current line went over the limit and is being skipped.
+    skipping: bool,
+    max_bytes: usize,
+}
+
+impl LineReader {
+    fn new(output: Box<dyn AsyncRead + Send + Unpin>, limits: &Limits) -> Self {
+        Self {
+            reader: BufReader::with_capacity(limits.read_buffer, output),
+            line: Vec::new(),
+            skipping: false,
+            max_bytes: limits.max_message_bytes,
+        }
+    }
+
+    /// Reads the next line, without its newline. A line longer than the
+    /// limit is read to its end and dropped, failing with
+    /// [`Error::MessageTooLarge`]. Cancellation safe: a partly read line is
+    /// kept for the next call.
+    async fn next_line(&mut self) -> Result<Option<String>, Error> {
+        loop {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                if self.line.is_empty() && !self.skipping {
+                    return Ok(None);
+                }
+                break;
+            }
+            let (chunk, consumed, ended) = match available.iter().position(|b| *b == b'\n') {
+                Some(end) => (&available[..end], end + 1, true),
+                None => (available, available.len(), false),
+            };
+            if !self.skipping && self.line.len() + chunk.len() > self.max_bytes {
+                self.skipping = true;
+                self.line = Vec::new();
+            }
+            if !self.skipping {
+                self.line.extend_from_slice(chunk);
+            }
+            self.reader.consume(consumed);
+            if ended {
+                break;
+            }
+        }
+        if std::mem::take(&mut self.skipping) {
+            return Err(Error::MessageTooLarge {
+                limit: self.max_bytes,
+            });
+        }
+        let line = String::from_utf8(std::mem::take(&mut self.line)).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+        })?;
+        Ok(Some(line))
+    }
+}
+
+/// The command line and environment the CLI would be started with, from
+/// [`Options::preview_command`](crate::Options::preview_command) or
+/// [`Client::dry_run`](crate::Client::dry_run).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPreview {
+    program: PathBuf,
+    args: Vec<String>,
     env: Vec<(String, String)>,
-    json_schema: Option<String>,
-    mcp_server_names: Vec<String>,
-    max_turns: Option<u32>,
-    resume: Option<String>,
-    fork_session: bool,
-    #[builder(default)]
-    resume_session_at: Option<String>,
-    agents: HashMap<String, Agent>,
-    strict_mcp_config: bool,
-    disable_slash_commands: bool,
+    cwd: Option<PathBuf>,
+}
+
+impl CommandPreview {
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Variables set for the CLI on top of those it inherits.
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    pub fn cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+}
+
+impl std::fmt::Display for CommandPreview {
+    /// Formats the command as a shell would take it, quoting arguments with
+    /// spaces or quotes.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let quote = |arg: &str| {
+            if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || "'\"$`\\".contains(c))
+            {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            } else {
+                arg.to_owned()
+            }
+        };
+        f.write_str(&quote(&self.program.display().to_string()))?;
+        for arg in &self.args {
+            write!(f, " {}", quote(arg))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransportOptions {
+    pub(crate) allowed_tools: Vec<String>,
+    pub(crate) disallowed_tools: Vec<String>,
+    pub(crate) tools: Option<Tools>,
+    pub(crate) model: Option<String>,
+    pub(crate) fallback_model: Option<String>,
+    pub(crate) system_prompt: Option<String>,
+    pub(crate) append_system_prompt: Option<String>,
+    pub(crate) permission_mode: Option<String>,
+    pub(crate) max_budget_usd: Option<f64>,
+    pub(crate) debug: bool,
+    pub(crate) cwd: Option<PathBuf>,
+    pub(crate) env: Vec<(String, String)>,
+    pub(crate) json_schema: Option<String>,
+    pub(crate) mcp_server_names: Vec<String>,
+    pub(crate) max_turns: Option<u32>,
+    pub(crate) resume: Option<String>,
+    pub(crate) fork_session: bool,
+    pub(crate) resume_session_at: Option<String>,
+    pub(crate) agents: HashMap<String, Agent>,
+    pub(crate) strict_mcp_config: bool,
+    pub(crate) disable_slash_commands: bool,
+    pub(crate) include_partial_messages: bool,
+    pub(crate) betas: Vec<String>,
+    pub(crate) max_thinking_tokens: Option<u32>,
+    pub(crate) effort: Option<String>,
+    pub(crate) settings: Option<String>,
+    pub(crate) plugin_dirs: Vec<PathBuf>,
+    pub(crate) raw_flags: Vec<(String, Option<String>)>,
+    pub(crate) permission_prompt_tool: Option<String>,
+    pub(crate) inherit_env: Option<Vec<String>>,
+    pub(crate) remove_env: Vec<String>,
+    pub(crate) cli_path: Option<PathBuf>,
+    pub(crate) pty: bool,
+    pub(crate) process_limits: Option<ProcessLimits>,
+    pub(crate) reap_orphans: Option<PathBuf>,
+    pub(crate) wire_log: Option<WireLog>,
+    pub(crate) limits: Limits,
+    pub(crate) warn_unknown_fields: bool,
 }
 
 impl TransportOptions {
@@ -100,6 +380,10 @@ impl TransportOptions {
         &self.env
     }
 
+    pub fn resume(&self) -> Option<&str> {
+        self.resume.as_deref()
+    }
+
     pub fn json_schema(&self) -> Option<&str> {
         self.json_schema.as_deref()
     }
@@ -115,6 +399,110 @@ impl TransportOptions {
     pub fn tools(&self) -> impl Iterator<Item = &str> {
         ToolsIter::new(self.tools.as_ref())
     }
+
+    pub fn settings(&self) -> Option<&str> {
+        self.settings.as_deref()
+    }
+
+    pub fn plugin_dirs(&self) -> &[PathBuf] {
+        &self.plugin_dirs
+    }
+
+    pub fn cli_path(&self) -> Option<&Path> {
+        self.cli_path.as_deref()
+    }
+
+    /// Flags passed to the CLI as given, with their values if they take one.
+    pub fn raw_flags(&self) -> &[(String, Option<String>)] {
+        &self.raw_flags
+    }
+
+    /// The only variables inherited from this process's environment, or
+    /// `None` to inherit all of them.
+    pub fn inherit_env(&self) -> Option<&[String]> {
+        self.inherit_env.as_deref()
+    }
+
+    /// Resumes `session_id` from its latest message, replacing any resume
+    /// or fork settings, and sets `env` on top of the existing variables.
+    pub(crate) fn resume_with(&mut self, session_id: Option<String>, env: Vec<(String, String)>) {
+        if session_id.is_some() {
+            self.resume = session_id;
+            self.resume_session_at = None;
+            self.fork_session = false;
+        }
+        self.env
+            .retain(|(key, _)| !env.iter().any(|(set, _)| set == key));
+        self.env.extend(env);
+    }
+
+    /// Forks `session_id` at the message `message_id`.
+    pub(crate) fn fork_at(&mut self, session_id: String, message_id: String) {
+        self.resume = Some(session_id);
+        self.resume_session_at = Some(message_id);
+        self.fork_session = true;
+    }
+
+    /// Whether the CLI runs on a pseudo-terminal.
+    pub fn pty(&self) -> bool {
+        self.pty
+    }
+
+    /// Variables removed from the inherited environment.
+    pub fn remove_env(&self) -> &[String] {
+        &self.remove_env
+    }
+
+    /// Moves the JSON schema from `--json-schema` into the appended system
+    /// prompt, for CLIs without the flag.
+    pub(crate) fn embed_json_schema(&mut self) {
+        let Some(schema) = self.json_schema.take() else {
+            return;
+        };
+        let instructions = crate::util::schema_instructions(&schema);
+        self.append_system_prompt = Some(match self.append_system_prompt.take() {
+            Some(prompt) => format!("{prompt}\n\n{instructions}"),
+            None => instructions,
+        });
+    }
+}
+
+/// Whether the CLI lists `flag` in its `--help` output, remembered per CLI
+/// for the life of the process.
+///
+/// A CLI whose help cannot be read is assumed to support the flag, so that
+/// starting it reports the actual problem.
+pub(crate) async fn cli_supports_flag(options: &TransportOptions, flag: &'static str) -> bool {
+    type Supported = HashMap<(crate::locate::Launcher, &'static str), bool>;
+    static SUPPORTED: std::sync::LazyLock<std::sync::Mutex<Supported>> =
+        std::sync::LazyLock::new(Default::default);
+
+    let launcher = crate::locate::resolve(options.cli_path.as_deref()).await;
+    let key = (launcher.clone(), flag);
+    if let Some(&supported) = SUPPORTED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&key)
+    {
+        return supported;
+    }
+
+    let help = Command::new(&launcher.program)
+        .args(&launcher.args)
+        .arg("--help")
+        .stdin(Stdio::null())
+        .output();
+    let supported = match tokio::time::timeout(Duration::from_secs(10), help).await {
+        Ok(Ok(output)) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).contains(flag)
+        }
+        _ => true,
+    };
+    SUPPORTED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, supported);
+    supported
 }
 
 enum ToolsIter<'a> {
@@ -151,24 +539,109 @@ impl<'a> Iterator for ToolsIter<'a> {
 }
 
 impl Transport {
+    /// The command [`new`](Self::new) would run for `options`, with the CLI
+    /// as resolved by `launcher`.
+    pub(crate) fn preview(
+        options: &TransportOptions,
+        launcher: crate::locate::Launcher,
+    ) -> CommandPreview {
+        CommandPreview {
+            program: launcher.program,
+            args: launcher
+                .args
+                .into_iter()
+                .chain(Self::build_command(options))
+                .collect(),
+            env: Self::build_env(options),
+            cwd: options.cwd.clone(),
+        }
+    }
+
     pub async fn new(options: &TransportOptions) -> Result<Self, Error> {
         let cmd = Self::build_command(options);
         let env = Self::build_env(options);
 
         tracing::info!(cmd = ?cmd, "spawning claude CLI");
 
-        let mut child = Command::new("claude")
+        let launcher = crate::locate::resolve(options.cli_path.as_deref()).await;
+        tracing::debug!(program = %launcher.program.display(), "resolved claude CLI");
+        let cwd = options
+            .cwd
+            .as_deref()
+            .unwrap_or_else(|| std::path::Path::new("."));
+
+        let wire_log = Arc::new(std::sync::Mutex::new(
+            options.wire_log.as_ref().map(WireLog::open).transpose()?,
+        ));
+
+        if options.pty {
+            #[cfg(feature = "pty")]
+            {
+                let (process, stdin, stdout) =
+                    crate::pty::PtyProcess::spawn(crate::pty::PtyCommand {
+                        program: &launcher.program,
+                        args: launcher.args.into_iter().chain(cmd).collect(),
+                        inherit_env: options.inherit_env.as_deref(),
+                        remove_env: &options.remove_env,
+                        env,
+                        cwd,
+                    })?;
+                let mut transport = Self {
+                    process: Process::Pty(process),
+                    writer: TransportWriter::new(Box::new(stdin), Arc::clone(&wire_log)),
+                    stdout: LineReader::new(Box::new(stdout), &options.limits),
+                    stderr_task: None,
+                    #[cfg(windows)]
+                    _job: None,
+                    _lease: None,
+                    wire_log,
+                    warn_unknown_fields: options.warn_unknown_fields,
+                    shut_down: false,
+                };
+                transport.contain(options)?;
+                return Ok(transport);
+            }
+            #[cfg(not(feature = "pty"))]
+            return Err(Error::ProcessError(
+                "running the CLI on a pseudo-terminal requires the `pty` feature".to_owned(),
+            ));
+        }
+
+        let mut command = Command::new(&launcher.program);
+        command.args(&launcher.args);
+        if let Some(names) = &options.inherit_env {
+            command.env_clear().envs(
+                names
+                    .iter()
+                    .filter_map(|name| Some((name, std::env::var_os(name)?))),
+            );
+        }
+        for name in &options.remove_env {
+            command.env_remove(name);
+        }
+        // A group of its own lets the CLI be killed along with its tools.
+        #[cfg(unix)]
+        command.process_group(0);
+        #[cfg(unix)]
+        if let Some(limits) = options.process_limits.clone() {
+            // SAFETY: `apply` makes only system calls and does not allocate.
+            unsafe {
+                command.pre_exec(move || limits.apply(0));
+            }
+        }
+        let mut child = command
             .args(&cmd)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .envs(env)
-            .current_dir(options.cwd.as_deref().unwrap_or_else(|| std::path::Path::new(".")))
+            .current_dir(cwd)
             .spawn()
             .map_err(|e| {
                 tracing::error!(error = %e, "failed to spawn claude CLI");
                 Error::CliNotFound(format!(
-                    "failed to spawn claude CLI: {e}; make sure 'claude' is installed and authenticated",
+                    "failed to spawn claude CLI at '{}': {e}; make sure 'claude' is installed and authenticated",
+                    launcher.program.display(),
                 ))
             })?;
 
@@ -187,12 +660,56 @@ impl Transport {
 
         let stderr_task = tokio::spawn(Self::log_stderr(stderr));
 
-        Ok(Self {
-            child,
-            stdin: Some(stdin),
-            stdout: BufReader::new(stdout),
-            stderr_task,
-        })
+        let mut transport = Self {
+            process: Process::Piped(child),
+            writer: TransportWriter::new(Box::new(stdin), Arc::clone(&wire_log)),
+            stdout: LineReader::new(Box::new(stdout), &options.limits),
+            stderr_task: Some(stderr_task),
+            #[cfg(windows)]
+            _job: None,
+            _lease: None,
+            wire_log,
+            warn_unknown_fields: options.warn_unknown_fields,
+            shut_down: false,
+        };
+        transport.contain(options)?;
+        Ok(transport)
+    }
+
+    /// Sets up what keeps the running process in check: the limits that
+    /// could not be applied as it started, the job object that kills its
+    /// tools with it on Windows, and its lease on the orphan lockfile.
+    fn contain(&mut self, options: &TransportOptions) -> Result<(), Error> {
+        let Some(pid) = self.process.id() else {
+            return Ok(());
+        };
+        let failed =
+            |e: std::io::Error| Error::ProcessError(format!("failed to apply process limits: {e}"));
+        // Piped processes had their limits applied before `exec`.
+        #[cfg(all(unix, feature = "pty"))]
+        if let Some(limits) = &options.process_limits
+            && matches!(self.process, Process::Pty(_))
+        {
+            limits.apply(pid as libc::pid_t).map_err(failed)?;
+        }
+        #[cfg(all(unix, not(feature = "pty")))]
+        let _ = failed;
+        #[cfg(windows)]
+        {
+            self._job = Some(
+                crate::limits::JobObject::assign(options.process_limits.as_ref(), pid)
+                    .map_err(failed)?,
+            );
+        }
+        if let Some(dir) = &options.reap_orphans {
+            self._lease = Some(crate::orphans::Lease::acquire(dir, pid).map_err(|e| {
+                Error::ProcessError(format!(
+                    "failed to record the CLI in '{}': {e}",
+                    dir.display()
+                ))
+            })?);
+        }
+        Ok(())
     }
 
     fn build_command(options: &TransportOptions) -> Vec<String> {
@@ -219,7 +736,10 @@ impl Transport {
         }
 
         if options.json_schema.is_some() {
-            cmd.extend(["--allowedTools".to_owned(), "StructuredOutput".to_owned()]);
+            cmd.extend([
+                "--allowedTools".to_owned(),
+                crate::util::STRUCTURED_OUTPUT_TOOL.to_owned(),
+            ]);
         }
 
         if !options.disallowed_tools.is_empty() {
@@ -284,6 +804,35 @@ impl Transport {
             cmd.push("--disable-slash-commands".to_owned());
         }
 
+        if options.include_partial_messages {
+            cmd.push("--include-partial-messages".to_owned());
+        }
+
+        if let Some(tokens) = options.max_thinking_tokens {
+            cmd.extend(["--max-thinking-tokens".to_owned(), tokens.to_string()]);
+        }
+
+        if let Some(effort) = &options.effort {
+            cmd.extend(["--effort".to_owned(), effort.clone()]);
+        }
+
+        if let Some(settings) = &options.settings {
+            cmd.extend(["--settings".to_owned(), settings.clone()]);
+        }
+
+        for dir in &options.plugin_dirs {
+            cmd.extend(["--plugin-dir".to_owned(), dir.display().to_string()]);
+        }
+
+        if let Some(tool) = &options.permission_prompt_tool {
+            cmd.extend(["--permission-prompt-tool".to_owned(), tool.clone()]);
+        }
+
+        if !options.betas.is_empty() {
+            cmd.push("--betas".to_owned());
+            cmd.extend(options.betas.iter().cloned());
+        }
+
         if let Some(turns) = options.max_turns {
             cmd.extend(["--max-turns".to_owned(), turns.to_string()]);
         }
@@ -307,12 +856,21 @@ impl Transport {
             ]);
         }
 
+        for (flag, value) in &options.raw_flags {
+            tracing::info!(flag, value, "passing raw CLI flag");
+            cmd.push(flag.clone());
+            cmd.extend(value.clone());
+        }
+
         cmd.extend(["--input-format".to_owned(), "stream-json".to_owned()]);
         cmd
     }
 
     fn build_env(options: &TransportOptions) -> Vec<(String, String)> {
-        let mut env = vec![("CLAUDE_CODE_ENTRYPOINT".to_owned(), "sdk-rust".to_owned())];
+        let mut env = vec![(
+            "CLAUDE_CODE_ENTRYPOINT".to_owned(),
+            crate::proto::versions::ENTRYPOINT.to_owned(),
+        )];
 
         for (k, v) in &options.env {
             env.push((k.clone(), v.clone()));
@@ -338,46 +896,76 @@ impl Transport {
     }
 
     pub async fn send(&mut self, json: &Value) -> Result<(), Error> {
-        let stdin = self
-            .stdin
-            .as_mut()
-            .ok_or_else(|| Error::ProcessError("stdin closed".to_owned()))?;
-        let data = serde_json::to_string(json)?;
-        tracing::debug!(data = %data, "sending");
-        stdin.write_all(data.as_bytes()).await?;
-        stdin.write_all(b"\n").await?;
-        stdin.flush().await?;
-        Ok(())
+        self.writer.send(json).await
     }
 
     pub async fn send_request(&mut self, envelope: &RequestEnvelope) -> Result<(), Error> {
-        let json = serde_json::to_value(envelope)?;
-        self.send(&json).await
+        self.writer.send_request(envelope).await
     }
 
     pub async fn send_response(&mut self, envelope: &ResponseEnvelope) -> Result<(), Error> {
-        let json = serde_json::to_value(envelope)?;
-        self.send(&json).await
+        self.writer.send_response(envelope).await
+    }
+
+    pub async fn send_cancel(&mut self, envelope: &CancelRequestEnvelope) -> Result<(), Error> {
+        self.writer.send_cancel(envelope).await
+    }
+
+    /// A handle writing to the CLI's input while this transport reads.
+    pub(crate) fn writer(&self) -> TransportWriter {
+        self.writer.clone()
     }
 
+    /// Reads the next line from the CLI's stdout.
+    ///
+    /// This is cancellation safe: if the future is dropped before a full line
+    /// is available, the partially read data is kept for the next call.
+    /// Blank lines are skipped and a trailing `\r` is removed, as the CLI
+    /// may end lines with CRLF on Windows.
     pub async fn receive_line(&mut self) -> Result<Option<String>, Error> {
-        let mut line = String::new();
-        match self.stdout.read_line(&mut line).await? {
-            0 => Ok(None),
-            _ => {
-                tracing::debug!(line = %line.trim(), "received");
-                Ok(Some(line))
+        loop {
+            let Some(mut line) = self.stdout.next_line().await? else {
+                return Ok(None);
+            };
+            while line.ends_with('\r') {
+                line.pop();
+            }
+            #[cfg(feature = "pty")]
+            if matches!(self.process, Process::Pty(_)) {
+                line = crate::pty::strip_escapes(&line);
+                if !line.trim_start().starts_with('{') {
+                    if !line.trim().is_empty() {
+                        tracing::warn!(target: "claude_cli", "{}", line.trim_end());
+                    }
+                    continue;
+                }
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            tracing::debug!(target: PROTOCOL_TARGET, line = %line.trim(), "received");
+            if let Some(log) = self
+                .wire_log
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_mut()
+            {
+                log.record(Direction::In, &line);
             }
+            return Ok(Some(line));
         }
     }
 
+    /// Reads and parses the next message from the CLI. Cancellation safe.
     pub async fn receive(&mut self) -> Result<Option<Incoming>, Error> {
         match self.receive_line().await? {
             Some(line) => {
-                let incoming = serde_json::from_str::<Incoming>(&line).map_err(|e| {
+                let incoming = Incoming::parse(&line).inspect_err(|e| {
                     tracing::error!(line = %line, error = %e, "failed to parse incoming message");
-                    Error::ProtocolError(format!("failed to parse: {e}"))
                 })?;
+                if self.warn_unknown_fields {
+                    crate::proto::extra::warn_unknown_fields(&incoming);
+                }
                 Ok(Some(incoming))
             }
             None => Ok(None),
@@ -385,23 +973,117 @@ impl Transport {
     }
 
     pub async fn interrupt(&mut self) -> Result<(), Error> {
-        tracing::info!("sending interrupt signal");
-        let envelope = RequestEnvelope::interrupt("");
-        self.send_request(&envelope).await
+        self.writer.interrupt().await
+    }
+
+    /// Kills the CLI without waiting for it to exit.
+    pub(crate) fn kill(&mut self) -> std::io::Result<()> {
+        tracing::info!("killing claude CLI");
+        self.process.start_kill()
     }
 
     pub async fn close(mut self) -> Result<(), Error> {
-        self.stdin.take();
-        self.child.wait().await?;
+        self.shutdown(Duration::MAX).await
+    }
+
+    /// Closes the CLI's input and waits up to `timeout` for it to exit,
+    /// killing it if it does not, then lets the stderr logging task finish.
+    pub(crate) async fn shutdown(&mut self, timeout: Duration) -> Result<(), Error> {
+        if self.shut_down {
+            return Ok(());
+        }
+        self.writer.close().await;
+        let pid = self.process.id();
+        if tokio::time::timeout(timeout, self.process.wait())
+            .await
+            .is_err()
+        {
+            tracing::warn!("claude CLI did not exit in time, killing it");
+            self.process.start_kill()?;
+            self.process.wait().await?;
+        }
+        self.shut_down = true;
+        // Tools the CLI left running outlive it in its process group.
+        #[cfg(unix)]
+        if let Some(pid) = pid {
+            crate::orphans::kill_group(pid)?;
+        }
+        #[cfg(not(unix))]
+        let _ = pid;
+
+        // The task ends with the CLI's stderr, unless something it started
+        // still holds it open.
+        if let Some(mut task) = self.stderr_task.take()
+            && tokio::time::timeout(STDERR_DRAIN_TIMEOUT, &mut task)
+                .await
+                .is_err()
+        {
+            task.abort();
+        }
         Ok(())
     }
 }
 
 impl Drop for Transport {
     fn drop(&mut self) {
-        self.stderr_task.abort();
-        if let Err(e) = self.child.start_kill() {
+        if let Some(task) = &self.stderr_task {
+            task.abort();
+        }
+        if self.shut_down {
+            return;
+        }
+        if let Err(e) = self.process.start_kill() {
             tracing::error!(error = %e, "failed to kill child process");
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_line_reader_skips_lines_over_the_limit() {
+        let (mut cli, output) = tokio::io::duplex(64);
+        let limits = Limits {
+            read_buffer: 4,
+            max_message_bytes: 8,
+            ..Default::default()
+        };
+        let mut reader = LineReader::new(Box::new(output), &limits);
+        tokio::spawn(async move {
+            cli.write_all(b"{\"a\":1}\n{\"long\":\"line\"}\n{}")
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(
+            reader.next_line().await.unwrap().as_deref(),
+            Some("{\"a\":1}")
+        );
+        assert!(matches!(
+            reader.next_line().await,
+            Err(Error::MessageTooLarge { limit: 8 })
+        ));
+        assert_eq!(reader.next_line().await.unwrap().as_deref(), Some("{}"));
+        assert_eq!(reader.next_line().await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_embed_json_schema_moves_schema_into_prompt() {
+        let mut options = TransportOptions {
+            json_schema: Some(r#"{"type":"object"}"#.to_owned()),
+            append_system_prompt: Some("Be brief.".to_owned()),
+            ..Default::default()
+        };
+        options.embed_json_schema();
+
+        let cmd = Transport::build_command(&options);
+        assert!(!cmd.iter().any(|arg| arg == "--json-schema"));
+        let prompt = options.append_system_prompt().unwrap();
+        assert!(prompt.starts_with("Be brief.\n\n"));
+        assert!(prompt.ends_with(r#"{"type":"object"}"#));
+    }
+}
@@ -1,6 +1,12 @@
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "schema")]
 use schemars::JsonSchema;
 use serde_json::Value;
 
+#[cfg(feature = "schema")]
 pub(crate) fn schema_for<T: JsonSchema>() -> Value {
     let root = schemars::schema_for!(T);
     match serde_json::to_value(root) {
@@ -12,6 +18,7 @@ pub(crate) fn schema_for<T: JsonSchema>() -> Value {
     }
 }
 
+#[cfg(feature = "schema")]
 fn strip_schema_metadata(value: &mut Value) {
     if let Some(obj) = value.as_object_mut() {
         obj.remove("title");
@@ -30,6 +37,10 @@ fn strip_schema_metadata(value: &mut Value) {
     }
 }
 
+/// The built-in tool the CLI uses to return output matching a JSON schema.
+pub(crate) const STRUCTURED_OUTPUT_TOOL: &str = "StructuredOutput";
+
+#[cfg(feature = "schema")]
 pub(crate) fn schema_for_structured_output<T: JsonSchema>() -> Value {
     let root = schemars::schema_for!(T);
     match serde_json::to_value(root) {
@@ -40,3 +51,203 @@ pub(crate) fn schema_for_structured_output<T: JsonSchema>() -> Value {
         Err(_) => serde_json::json!({}),
     }
 }
+
+/// The schema the CLI is given for structured output of type `T`, and the
+/// property the output is wrapped in, if any.
+///
+/// Some CLI versions reject schemas whose root is not an object, so other
+/// roots are moved into a required property of an object: `items` for
+/// arrays, such as `Vec<T>`, and `value` for anything else, such as a
+/// newtype around a number.
+#[cfg(feature = "schema")]
+pub(crate) fn structured_output_schema<T: JsonSchema>() -> (Value, Option<&'static str>) {
+    let mut schema = schema_for_structured_output::<T>();
+    let wrapper = match schema.get("type").and_then(Value::as_str) {
+        Some("object") => return (schema, None),
+        Some("array") => "items",
+        _ => "value",
+    };
+
+    // References resolve against the root, so definitions stay there.
+    let definitions = schema
+        .as_object_mut()
+        .and_then(|root| root.remove("definitions"));
+    let mut wrapped = serde_json::json!({
+        "type": "object",
+        "properties": { wrapper: schema },
+        "required": [wrapper],
+    });
+    if let Some(definitions) = definitions {
+        wrapped["definitions"] = definitions;
+    }
+    (wrapped, Some(wrapper))
+}
+
+/// Takes structured output out of the property
+/// [`structured_output_schema`] wrapped it in. Output without the property
+/// is returned as is.
+pub(crate) fn unwrap_structured_output(
+    mut output: serde_json::Value,
+    wrapper: Option<&str>,
+) -> serde_json::Value {
+    if let Some(inner) = wrapper.and_then(|key| output.get_mut(key)) {
+        return inner.take();
+    }
+    output
+}
+
+/// Instructions asking for output matching `schema` at the end of the reply,
+/// for CLIs without `--json-schema`.
+pub(crate) fn schema_instructions(schema: &str) -> String {
+    format!(
+        "End your final reply with a single JSON value matching the JSON schema below, \
+         in a ```json code block with nothing after it.\n\n{schema}"
+    )
+}
+
+/// The JSON value at the end of a reply following
+/// [`schema_instructions`]: the last ```json block, or else the whole text
+/// or the outermost braces in it.
+pub(crate) fn extract_json(text: &str) -> Option<Value> {
+    if let Some(start) = text.rfind("```json") {
+        let block = &text[start + "```json".len()..];
+        let block = block.find("```").map_or(block, |end| &block[..end]);
+        if let Ok(value) = serde_json::from_str(block.trim()) {
+            return Some(value);
+        }
+    }
+    if let Ok(value) = serde_json::from_str(text.trim()) {
+        return Some(value);
+    }
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    serde_json::from_str(text.get(start..=end)?).ok()
+}
+
+/// Runs `task` on a task of its own, so a panic in it is caught rather than
+/// unwinding into the caller. Dropping the returned future aborts the task.
+pub(crate) async fn spawn_isolated<T: Send + 'static>(
+    task: impl std::future::Future<Output = T> + Send + 'static,
+) -> Result<T, tokio::task::JoinError> {
+    struct AbortOnDrop(tokio::task::AbortHandle);
+
+    impl Drop for AbortOnDrop {
+        fn drop(&mut self) {
+            self.0.abort();
+        }
+    }
+
+    let handle = tokio::spawn(task);
+    let _abort = AbortOnDrop(handle.abort_handle());
+    handle.await
+}
+
+/// Describes why a spawned task did not complete, extracting the panic
+/// message when there is one.
+pub(crate) fn join_error_message(error: tokio::task::JoinError) -> String {
+    if !error.is_panic() {
+        return "task was cancelled".to_owned();
+    }
+
+    let payload = error.into_panic();
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        format!("panicked: {message}")
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        format!("panicked: {message}")
+    } else {
+        "panicked".to_owned()
+    }
+}
+
+/// A random, time-ordered UUIDv7 in hyphenated form, for request ids and
+/// unique file names. Ids made by this process sort in the order they were
+/// made, even within a millisecond.
+pub(crate) fn uuid_v7() -> String {
+    // The last 48-bit timestamp and 12-bit `rand_a` handed out, together.
+    static LAST: AtomicU64 = AtomicU64::new(0);
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64);
+    // RandomState is seeded randomly per thread and differs on every call.
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(millis);
+    let random = hasher.finish();
+
+    // Within a millisecond, or if the clock goes back, `rand_a` counts up
+    // from the last id, carrying into the timestamp when it overflows.
+    let candidate = ((millis & 0xffff_ffff_ffff) << 12) | (random & 0xfff);
+    let stamp = match LAST.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+        Some(candidate.max(last + 1))
+    }) {
+        Ok(last) | Err(last) => candidate.max(last + 1),
+    };
+    hasher.write_u64(stamp);
+    let low = hasher.finish();
+
+    let value = (u128::from(stamp >> 12) << 80)
+        | (0x7 << 76)
+        | (u128::from(stamp & 0xfff) << 64)
+        | (0b10 << 62)
+        | u128::from(low & 0x3fff_ffff_ffff_ffff);
+    format_uuid(value)
+}
+
+/// Formats `value` as a hyphenated UUID.
+pub(crate) fn format_uuid(value: u128) -> String {
+    let hex = format!("{value:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_json_from_reply() {
+        let reply = "Here you go:\n\n```json\n{\"score\": 3}\n```";
+        assert_eq!(extract_json(reply), Some(json!({"score": 3})));
+        assert_eq!(extract_json(" [1, 2] "), Some(json!([1, 2])));
+        assert_eq!(
+            extract_json("The answer is {\"ok\": true}."),
+            Some(json!({"ok": true}))
+        );
+        assert_eq!(extract_json("no JSON here"), None);
+    }
+
+    #[test]
+    fn test_uuid_v7_layout() {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let id = uuid_v7();
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+        let groups = id.split('-').map(str::len).collect::<Vec<_>>();
+        assert_eq!(groups, [8, 4, 4, 4, 12], "{id}");
+        let hex = id.replace('-', "");
+        let value = u128::from_str_radix(&hex, 16).unwrap();
+        assert_eq!((value >> 76) & 0xf, 7, "version of {id}");
+        assert_eq!((value >> 62) & 0b11, 0b10, "variant of {id}");
+        // Ids made in a burst, such as by other tests, can run a few
+        // milliseconds ahead of the clock.
+        let millis = u128::from_str_radix(&hex[..12], 16).unwrap();
+        assert!(
+            (before.as_millis()..=after.as_millis() + 10).contains(&millis),
+            "timestamp of {id}"
+        );
+    }
+
+    #[test]
+    fn test_uuid_v7_is_monotonic() {
+        let ids = (0..1_000).map(|_| uuid_v7()).collect::<Vec<_>>();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
@@ -0,0 +1,245 @@
+//! Tracking of files changed on disk during a conversation turn.
+//!
+//! With the `watch` feature, [`Conversation::watch_files`] records which files
+//! under the client's working directory changed while each turn ran, based on
+//! filesystem events rather than on the inputs of tool calls.
+//!
+//! [`Conversation::watch_files`]: crate::Conversation::watch_files
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// How a file changed during a turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+/// The files that changed during a turn, relative to the watched directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileChanges {
+    changes: BTreeMap<PathBuf, FileChangeKind>,
+}
+
+impl FileChanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<FileChangeKind> {
+        self.changes.get(path.as_ref()).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, FileChangeKind)> {
+        self.changes.iter().map(|(p, k)| (p.as_path(), *k))
+    }
+
+    pub fn created(&self) -> impl Iterator<Item = &Path> {
+        self.of_kind(FileChangeKind::Created)
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &Path> {
+        self.of_kind(FileChangeKind::Modified)
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &Path> {
+        self.of_kind(FileChangeKind::Removed)
+    }
+
+    /// Records a change, folding it into any earlier change to the same file.
+    ///
+    /// A file created and then removed within the turn is dropped, and one
+    /// removed and then recreated counts as modified.
+    pub fn record(&mut self, path: impl Into<PathBuf>, kind: FileChangeKind) {
+        use FileChangeKind::*;
+
+        let path = path.into();
+        let merged = match (self.changes.get(&path), kind) {
+            (None, kind) => Some(kind),
+            (Some(Created), Removed) => None,
+            (Some(Created), _) => Some(Created),
+            (Some(Removed), Created | Modified) => Some(Modified),
+            (Some(_), kind) => Some(kind),
+        };
+
+        match merged {
+            Some(kind) => self.changes.insert(path, kind),
+            None => self.changes.remove(&path),
+        };
+    }
+
+    fn of_kind(&self, kind: FileChangeKind) -> impl Iterator<Item = &Path> {
+        self.changes
+            .iter()
+            .filter(move |(_, k)| **k == kind)
+            .map(|(p, _)| p.as_path())
+    }
+}
+
+#[cfg(feature = "watch")]
+pub use watcher::FileWatcher;
+
+#[cfg(feature = "watch")]
+mod watcher {
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex, PoisonError};
+    use std::time::Duration;
+
+    use notify::event::{ModifyKind, RenameMode};
+    use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+    use super::{FileChangeKind, FileChanges};
+    use crate::error::Error;
+
+    /// Watches a directory tree and accumulates [`FileChanges`].
+    ///
+    /// Changes inside `.git` are ignored.
+    pub struct FileWatcher {
+        root: PathBuf,
+        changes: Arc<Mutex<FileChanges>>,
+        settle: Duration,
+        _watcher: notify::RecommendedWatcher,
+    }
+
+    impl std::fmt::Debug for FileWatcher {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("FileWatcher")
+                .field("root", &self.root)
+                .field("settle", &self.settle)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl FileWatcher {
+        /// Starts watching `root` recursively.
+        pub fn new(root: impl AsRef<Path>) -> Result<Self, Error> {
+            let root = std::fs::canonicalize(root)?;
+            let changes = Arc::new(Mutex::new(FileChanges::new()));
+
+            let mut watcher = {
+                let root = root.clone();
+                let changes = Arc::clone(&changes);
+                notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                    Ok(event) => {
+                        let mut changes = changes.lock().unwrap_or_else(PoisonError::into_inner);
+                        record(&mut changes, &root, event);
+                    }
+                    Err(e) => tracing::warn!(error = %e, "file watcher error"),
+                })
+            }
+            .map_err(watch_error)?;
+            watcher
+                .watch(&root, RecursiveMode::Recursive)
+                .map_err(watch_error)?;
+
+            Ok(Self {
+                root,
+                changes,
+                settle: Duration::from_millis(100),
+                _watcher: watcher,
+            })
+        }
+
+        /// Sets how long to wait for trailing filesystem events before
+        /// collecting a turn's changes. Defaults to 100ms.
+        #[must_use]
+        pub fn with_settle_delay(mut self, delay: Duration) -> Self {
+            self.settle = delay;
+            self
+        }
+
+        pub fn root(&self) -> &Path {
+            &self.root
+        }
+
+        /// Discards changes recorded so far.
+        pub fn clear(&self) {
+            *self.changes.lock().unwrap_or_else(PoisonError::into_inner) = FileChanges::new();
+        }
+
+        /// Waits for the settle delay, then returns and resets the recorded changes.
+        pub async fn take(&self) -> FileChanges {
+            tokio::time::sleep(self.settle).await;
+            std::mem::take(&mut *self.changes.lock().unwrap_or_else(PoisonError::into_inner))
+        }
+    }
+
+    fn record(changes: &mut FileChanges, root: &Path, event: Event) {
+        let kinds: Vec<FileChangeKind> = match event.kind {
+            EventKind::Create(_) => vec![FileChangeKind::Created],
+            EventKind::Remove(_) => vec![FileChangeKind::Removed],
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                vec![FileChangeKind::Removed]
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                vec![FileChangeKind::Created]
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                vec![FileChangeKind::Removed, FileChangeKind::Created]
+            }
+            EventKind::Modify(_) => vec![FileChangeKind::Modified],
+            _ => return,
+        };
+
+        for (i, path) in event.paths.iter().enumerate() {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            if relative.starts_with(".git") || path.is_dir() {
+                continue;
+            }
+            let kind = kinds.get(i).or(kinds.last()).copied();
+            if let Some(kind) = kind {
+                changes.record(relative, kind);
+            }
+        }
+    }
+
+    fn watch_error(error: notify::Error) -> Error {
+        Error::ProcessError(format!("failed to watch files: {error}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_folds_changes() {
+        let mut changes = FileChanges::new();
+        changes.record("a", FileChangeKind::Created);
+        changes.record("a", FileChangeKind::Modified);
+        changes.record("b", FileChangeKind::Created);
+        changes.record("b", FileChangeKind::Removed);
+        changes.record("c", FileChangeKind::Removed);
+        changes.record("c", FileChangeKind::Created);
+
+        assert_eq!(changes.get("a"), Some(FileChangeKind::Created));
+        assert_eq!(changes.get("b"), None);
+        assert_eq!(changes.get("c"), Some(FileChangeKind::Modified));
+    }
+
+    #[cfg(feature = "watch")]
+    #[tokio::test]
+    async fn test_watcher_records_changes() {
+        let dir = std::env::temp_dir().join(format!("clauders-watch-{}", crate::util::uuid_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let watcher = FileWatcher::new(&dir).unwrap();
+
+        std::fs::write(dir.join("new.txt"), "hello").unwrap();
+        let changes = watcher.take().await;
+        assert_eq!(changes.get("new.txt"), Some(FileChangeKind::Created));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
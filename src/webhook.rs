@@ -0,0 +1,316 @@
+//! HTTP notifications of session lifecycle events.
+//!
+//! With the `webhooks` feature, [`Options::webhook`](crate::Options::webhook)
+//! registers a URL that receives a JSON `POST` whenever one of the chosen
+//! [`WebhookEvent`]s happens. Each payload has the same envelope:
+//!
+//! ```json
+//! {"event": "turn_complete", "session_id": "…", "tenant_id": null, "labels": {}, "timestamp": 1760000000, "data": {…}}
+//! ```
+//!
+//! `tenant_id` is the id of the client's [`Tenant`](crate::tenant::Tenant),
+//! if it has one, and `labels` are those set with
+//! [`Options::label`](crate::Options::label).
+//!
+//! Deliveries run in the background and are not retried; failures are
+//! logged and never affect the session.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde_json::{Value, json};
+
+use crate::deterministic::Clock;
+use crate::response::Response;
+use crate::task_pool::BackgroundTasks;
+
+/// Fractions of [`Options::max_budget_usd`](crate::Options::max_budget_usd)
+/// that trigger [`WebhookEvent::BudgetThreshold`] when first reached.
+pub const BUDGET_THRESHOLDS: &[f64] = &[0.5, 0.8, 1.0];
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A lifecycle event a webhook can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WebhookEvent {
+    /// A turn finished; `data` is the completion as returned by
+    /// [`Response::to_json`].
+    TurnComplete,
+    /// The CLI or API reported an error, or a turn ended in error.
+    Error,
+    /// The session's cost reached one of the [`BUDGET_THRESHOLDS`].
+    BudgetThreshold,
+    /// The CLI refused a tool call.
+    PermissionDenied,
+}
+
+impl WebhookEvent {
+    pub const ALL: &[Self] = &[
+        Self::TurnComplete,
+        Self::Error,
+        Self::BudgetThreshold,
+        Self::PermissionDenied,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TurnComplete => "turn_complete",
+            Self::Error => "error",
+            Self::BudgetThreshold => "budget_threshold",
+            Self::PermissionDenied => "permission_denied",
+        }
+    }
+}
+
+impl std::fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A URL and the events it receives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    url: String,
+    events: Vec<WebhookEvent>,
+}
+
+impl Webhook {
+    /// Subscribes `url` to `events`, or to every event if `events` is empty.
+    pub fn new(url: impl Into<String>, events: impl IntoIterator<Item = WebhookEvent>) -> Self {
+        let events = events.into_iter().collect::<Vec<_>>();
+        Self {
+            url: url.into(),
+            events: if events.is_empty() {
+                WebhookEvent::ALL.to_vec()
+            } else {
+                events
+            },
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn events(&self) -> &[WebhookEvent] {
+        &self.events
+    }
+
+    pub fn subscribes_to(&self, event: WebhookEvent) -> bool {
+        self.events.contains(&event)
+    }
+}
+
+/// Turns responses into webhook deliveries for a client.
+#[derive(Debug)]
+pub(crate) struct Notifier {
+    webhooks: Vec<Webhook>,
+    http: reqwest::Client,
+    clock: Clock,
+    max_budget_usd: Option<f64>,
+    tenant_id: Option<String>,
+    labels: BTreeMap<String, String>,
+    state: Mutex<State>,
+    deliveries: BackgroundTasks,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    session_id: Option<String>,
+    cost_usd: f64,
+}
+
+impl Notifier {
+    pub(crate) fn new(
+        webhooks: Vec<Webhook>,
+        max_budget_usd: Option<f64>,
+        tenant_id: Option<String>,
+        labels: BTreeMap<String, String>,
+        clock: Clock,
+    ) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            webhooks,
+            http,
+            clock,
+            max_budget_usd,
+            tenant_id,
+            labels,
+            state: Mutex::new(State::default()),
+            deliveries: BackgroundTasks::new(),
+        }
+    }
+
+    /// Delivers the events `response` triggers to the webhooks subscribed to them.
+    pub(crate) fn observe(&self, response: &Response) {
+        for (event, payload) in self.events(response) {
+            let body = payload.to_string();
+            for webhook in self
+                .webhooks
+                .iter()
+                .filter(|hook| hook.subscribes_to(event))
+            {
+                let request = self
+                    .http
+                    .post(webhook.url())
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.clone());
+                let url = webhook.url().to_owned();
+                self.deliveries.spawn(async move {
+                    let result = request
+                        .send()
+                        .await
+                        .and_then(reqwest::Response::error_for_status);
+                    if let Err(e) = result {
+                        tracing::warn!(%url, %event, error = %e.without_url(), "webhook delivery failed");
+                    }
+                });
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for deliveries in flight, abandoning the rest.
+    pub(crate) async fn shutdown(&self, timeout: Duration) {
+        let abandoned = self.deliveries.shutdown(timeout).await;
+        if abandoned > 0 {
+            tracing::warn!(abandoned, "webhook deliveries abandoned at shutdown");
+        }
+    }
+
+    /// The events `response` triggers, with their payloads.
+    fn events(&self, response: &Response) -> Vec<(WebhookEvent, Value)> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut events = Vec::new();
+        match response {
+            Response::Init(init) => {
+                state.session_id = init.session_id().map(str::to_owned);
+            }
+            Response::Error(error) => {
+                events.push((WebhookEvent::Error, json!({ "message": error.message() })));
+            }
+            Response::Complete(complete) => {
+                state.session_id = Some(complete.session_id().to_owned());
+                events.push((WebhookEvent::TurnComplete, response.to_json()));
+                if complete.is_error() {
+                    events.push((
+                        WebhookEvent::Error,
+                        json!({
+                            "message": complete.result_text().unwrap_or(complete.subtype()),
+                            "subtype": complete.subtype(),
+                        }),
+                    ));
+                }
+                for denial in complete.permission_denials() {
+                    events.push((WebhookEvent::PermissionDenied, json!(denial)));
+                }
+                if let (Some(budget), Some(cost)) = (self.max_budget_usd, complete.total_cost_usd())
+                {
+                    let previous = std::mem::replace(&mut state.cost_usd, cost);
+                    if let Some(threshold) = BUDGET_THRESHOLDS.iter().rev().find(|threshold| {
+                        previous < budget * *threshold && cost >= budget * *threshold
+                    }) {
+                        events.push((
+                            WebhookEvent::BudgetThreshold,
+                            json!({
+                                "threshold": threshold,
+                                "cost_usd": cost,
+                                "max_budget_usd": budget,
+                            }),
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let timestamp = self
+            .clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        events
+            .into_iter()
+            .map(|(event, data)| {
+                let payload = json!({
+                    "event": event.as_str(),
+                    "session_id": state.session_id,
+                    "tenant_id": self.tenant_id,
+                    "labels": self.labels,
+                    "timestamp": timestamp,
+                    "data": data,
+                });
+                (event, payload)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::Message;
+
+    fn complete(cost: f64, denied: bool) -> Response {
+        let denials = if denied {
+            json!([{"tool_name": "Bash", "tool_use_id": "t1", "tool_input": {}}])
+        } else {
+            json!([])
+        };
+        let message = serde_json::from_value::<Message>(json!({
+            "type": "result",
+            "subtype": "success",
+            "duration_ms": 10,
+            "duration_api_ms": 8,
+            "is_error": false,
+            "num_turns": 1,
+            "session_id": "s",
+            "total_cost_usd": cost,
+            "permission_denials": denials
+        }))
+        .unwrap();
+        Response::from_message(&message).remove(0)
+    }
+
+    #[test]
+    fn test_webhook_events() {
+        let notifier = Notifier::new(
+            vec![Webhook::new("http://localhost/hook", [])],
+            Some(1.0),
+            Some("acme".to_owned()),
+            BTreeMap::from([("run".to_owned(), "7".to_owned())]),
+            Clock::fixed(UNIX_EPOCH + Duration::from_secs(42)),
+        );
+        let names = |response: &Response| {
+            notifier
+                .events(response)
+                .into_iter()
+                .map(|(event, _)| event)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&complete(0.1, false)), [WebhookEvent::TurnComplete]);
+        assert_eq!(
+            names(&complete(0.9, true)),
+            [
+                WebhookEvent::TurnComplete,
+                WebhookEvent::PermissionDenied,
+                WebhookEvent::BudgetThreshold
+            ]
+        );
+        assert_eq!(names(&complete(0.95, false)), [WebhookEvent::TurnComplete]);
+
+        let (_, payload) = notifier.events(&complete(1.2, false)).remove(1);
+        assert_eq!(payload["event"], "budget_threshold");
+        assert_eq!(payload["session_id"], "s");
+        assert_eq!(payload["tenant_id"], "acme");
+        assert_eq!(payload["labels"]["run"], "7");
+        assert_eq!(payload["timestamp"], 42);
+        assert_eq!(payload["data"]["threshold"], 1.0);
+    }
+}
@@ -0,0 +1,264 @@
+//! Recording every message exchanged with the CLI.
+//!
+//! Tracing shows what the client did; the wire log shows exactly what the
+//! CLI was sent and what it answered, which is what a protocol bug report
+//! needs. With [`Options::wire_log`](crate::Options::wire_log) each message
+//! is appended to a file as one JSON line:
+//!
+//! ```text
+//! {"at_ms":1760612345678,"direction":"out","message":{"type":"user",...}}
+//! {"at_ms":1760612345912,"direction":"in","message":{"type":"system",...}}
+//! ```
+//!
+//! `at_ms` is the time in milliseconds since the Unix epoch and `direction`
+//! is `out` for messages sent to the CLI and `in` for those received. A line
+//! that is not valid JSON is logged as a string. The file is appended to, so
+//! a restarted CLI continues the same log.
+//!
+//! The file name may refer to the client's
+//! [labels](crate::Options::label) as `{name}`, such as `wire-{run}.jsonl`,
+//! to keep the logs of different runs apart.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use clauders::wire_log::WireLog;
+//! use clauders::{Client, Options};
+//!
+//! # async fn example() -> Result<(), clauders::Error> {
+//! let log = WireLog::new("wire.jsonl").redact(["content", "text"]);
+//! let client = Client::new(Options::new().wire_log(log)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{Value, json};
+
+const REDACTED: &str = "[redacted]";
+
+/// Where to log the wire protocol and what to leave out of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireLog {
+    path: PathBuf,
+    redact: Vec<String>,
+}
+
+impl WireLog {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            redact: Vec::new(),
+        }
+    }
+
+    /// Replaces the values of the named fields, at any depth, with
+    /// `"[redacted]"`, such as `content` and `text` to keep prompts and
+    /// replies out of the log.
+    #[must_use]
+    pub fn redact<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.redact.extend(fields.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replaces `{name}` placeholders in the file name with the values of
+    /// `labels`, made safe for file names.
+    pub(crate) fn with_labels(mut self, labels: &BTreeMap<String, String>) -> Self {
+        let Some(name) = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+        else {
+            return self;
+        };
+        let expanded = labels.iter().fold(name.clone(), |name, (key, value)| {
+            let value = value
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>();
+            name.replace(&format!("{{{key}}}"), &value)
+        });
+        if expanded != name {
+            self.path.set_file_name(expanded);
+        }
+        self
+    }
+
+    /// Opens the log for appending, creating it if needed.
+    pub(crate) fn open(&self) -> std::io::Result<WireLogWriter> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = File::options().create(true).append(true).open(&self.path)?;
+        Ok(WireLogWriter {
+            file: LineWriter::new(file),
+            redact: self.redact.clone(),
+            failed: false,
+        })
+    }
+}
+
+impl From<&str> for WireLog {
+    fn from(path: &str) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<String> for WireLog {
+    fn from(path: String) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<&Path> for WireLog {
+    fn from(path: &Path) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<PathBuf> for WireLog {
+    fn from(path: PathBuf) -> Self {
+        Self::new(path)
+    }
+}
+
+/// The direction of a logged message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Out,
+    In,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Out => "out",
+            Self::In => "in",
+        }
+    }
+}
+
+/// An open wire log.
+#[derive(Debug)]
+pub(crate) struct WireLogWriter {
+    file: LineWriter<File>,
+    redact: Vec<String>,
+    failed: bool,
+}
+
+impl WireLogWriter {
+    /// Appends `line`, a message in `direction`. Failures are reported once
+    /// and never interrupt the session.
+    pub(crate) fn record(&mut self, direction: Direction, line: &str) {
+        let mut message =
+            serde_json::from_str::<Value>(line).unwrap_or_else(|_| Value::String(line.to_owned()));
+        if !self.redact.is_empty() {
+            redact(&mut message, &self.redact);
+        }
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let entry = json!({
+            "at_ms": at_ms,
+            "direction": direction.as_str(),
+            "message": message,
+        });
+        if let Err(e) = writeln!(self.file, "{entry}") {
+            if !self.failed {
+                tracing::warn!(error = %e, "failed to write wire log");
+            }
+            self.failed = true;
+        }
+    }
+}
+
+fn redact(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if fields.iter().any(|name| name == key) {
+                    *field = Value::String(REDACTED.to_owned());
+                } else {
+                    redact(field, fields);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact(item, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_log_records_and_redacts() {
+        let dir = std::env::temp_dir().join(format!("clauders-wire-{}", crate::util::uuid_v7()));
+        let log = WireLog::new(dir.join("wire.jsonl")).redact(["text"]);
+        let mut writer = log.open().unwrap();
+        writer.record(
+            Direction::Out,
+            r#"{"type":"user","message":{"content":[{"type":"text","text":"secret"}]}}"#,
+        );
+        writer.record(Direction::In, "not json");
+        drop(writer);
+        // Reopening appends.
+        log.open()
+            .unwrap()
+            .record(Direction::In, r#"{"type":"result"}"#);
+
+        let lines = std::fs::read_to_string(log.path())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["direction"], "out");
+        assert_eq!(
+            lines[0]["message"]["message"]["content"][0],
+            json!({"type": "text", "text": "[redacted]"})
+        );
+        assert!(lines[0]["at_ms"].as_u64().unwrap() > 0);
+        assert_eq!(lines[1]["message"], "not json");
+        assert_eq!(lines[2]["direction"], "in");
+        assert_eq!(lines[2]["message"]["type"], "result");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_wire_log_file_name_labels() {
+        let labels = BTreeMap::from([
+            ("run".to_owned(), "exp/42".to_owned()),
+            ("team".to_owned(), "infra".to_owned()),
+        ]);
+        let log = WireLog::new("logs/{team}/wire-{run}-{missing}.jsonl").with_labels(&labels);
+        assert_eq!(
+            log.path(),
+            Path::new("logs/{team}/wire-exp_42-{missing}.jsonl")
+        );
+    }
+}
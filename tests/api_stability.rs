@@ -0,0 +1,365 @@
+//! Snapshot of the crate's public API.
+//!
+//! Lists every public item, method, enum variant, field, trait impl and
+//! re-export reachable through public modules, in the spirit of
+//! `cargo public-api`, and compares the list with
+//! `tests/snapshots/public_api.txt`. A change to the public API fails this
+//! test until the snapshot is updated, so it shows up in review:
+//!
+//! ```sh
+//! UPDATE_SNAPSHOTS=1 cargo test --test api_stability
+//! ```
+//!
+//! The list is read from the source rather than from rustdoc, so it works on
+//! stable and covers items behind every feature, each marked with its `cfg`.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT: &str = "tests/snapshots/public_api.txt";
+
+/// The kind of block the scanner is inside at the top level of a module.
+#[derive(Debug, Clone, PartialEq)]
+enum Context {
+    None,
+    /// An inherent or trait impl for the named type, with its header.
+    Impl {
+        ty: String,
+        header: String,
+    },
+    Trait(String),
+    Enum(String),
+    Struct(String),
+    /// A private module, macro or other block whose contents are skipped.
+    Skip,
+}
+
+struct Module {
+    path: String,
+    file: PathBuf,
+}
+
+/// The file holding module `name` declared in `parent`.
+fn module_file(parent: &Path, name: &str) -> PathBuf {
+    let stem = parent.file_stem().unwrap().to_str().unwrap();
+    let dir = if matches!(stem, "lib" | "mod") {
+        parent.parent().unwrap().to_path_buf()
+    } else {
+        parent.with_extension("")
+    };
+    let file = dir.join(format!("{name}.rs"));
+    if file.exists() {
+        file
+    } else {
+        dir.join(name).join("mod.rs")
+    }
+}
+
+/// The attributes worth recording: `cfg`, `non_exhaustive` and
+/// `deprecated`.
+fn relevant_attribute(line: &str) -> Option<&str> {
+    let attribute = line.trim();
+    let recorded = (attribute.starts_with("#[cfg(") && attribute != "#[cfg(test)]")
+        || attribute == "#[non_exhaustive]"
+        || attribute.starts_with("#[deprecated");
+    recorded.then_some(attribute)
+}
+
+/// Joins a declaration spread over several lines and drops its body.
+fn declaration(lines: &[&str]) -> String {
+    let joined = lines
+        .iter()
+        .map(|line| line.trim())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(", )", ")")
+        .replace("{ ", "{")
+        .replace(", }", "}")
+        .replace("< ", "<")
+        .replace(", >", ">")
+        .replace("(mut ", "(")
+        .replace(", mut ", ", ");
+    let joined = joined.strip_suffix("{}").unwrap_or(&joined);
+    let joined = joined.trim_end_matches(['{', ';', ' ', ',']);
+    match joined.split_once(" = ") {
+        Some((name, _)) if joined.starts_with("pub const") || joined.starts_with("pub static") => {
+            name.to_owned()
+        }
+        _ => joined.to_owned(),
+    }
+}
+
+/// The lines making up the declaration starting at `start`: up to the one
+/// opening its body or ending it.
+fn declaration_end(lines: &[&str], start: usize) -> usize {
+    (start..lines.len())
+        .find(|&i| {
+            let line = lines[i].trim_end();
+            line.ends_with(['{', ';']) || line.ends_with("{}")
+        })
+        .unwrap_or(start)
+}
+
+/// The name of the type an impl header is for, without generics or path.
+fn impl_self_type(header: &str) -> String {
+    let mut rest = header
+        .trim_start_matches("unsafe ")
+        .trim_start_matches("impl");
+    if rest.starts_with('<') {
+        let mut depth = 0;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                rest = &rest[i + 1..];
+                break;
+            }
+        }
+    }
+    let rest = rest.split(" where ").next().unwrap();
+    let ty = rest.rsplit_once(" for ").map_or(rest, |(_, ty)| ty).trim();
+    let ty = ty.trim_start_matches('&').trim_start_matches("dyn ");
+    let ty = ty.split('<').next().unwrap();
+    ty.rsplit("::").next().unwrap().trim().to_owned()
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The modules reachable through `pub mod` from the crate root.
+fn public_modules() -> Vec<Module> {
+    let mut modules = Vec::new();
+    let mut pending = vec![Module {
+        path: "clauders".to_owned(),
+        file: PathBuf::from("src/lib.rs"),
+    }];
+    while let Some(module) = pending.pop() {
+        let source = fs::read_to_string(&module.file).unwrap();
+        for line in source.lines() {
+            if let Some(name) = line
+                .strip_prefix("pub mod ")
+                .and_then(|rest| rest.strip_suffix(';'))
+            {
+                pending.push(Module {
+                    path: format!("{}::{name}", module.path),
+                    file: module_file(&module.file, name),
+                });
+            }
+        }
+        modules.push(module);
+    }
+    modules
+}
+
+/// The names of the public types and traits declared in `modules`.
+fn public_types(modules: &[Module]) -> HashSet<String> {
+    let mut types = HashSet::new();
+    for module in modules {
+        let source = fs::read_to_string(&module.file).unwrap();
+        for line in source.lines() {
+            for keyword in ["pub struct ", "pub enum ", "pub trait ", "pub type "] {
+                if let Some(rest) = line.strip_prefix(keyword) {
+                    let name = rest
+                        .split(|c: char| !c.is_alphanumeric() && c != '_')
+                        .next()
+                        .unwrap();
+                    types.insert(name.to_owned());
+                }
+            }
+        }
+    }
+    types
+}
+
+/// The public API of `module`, one entry per item.
+fn module_api(module: &Module, types: &HashSet<String>, api: &mut BTreeSet<String>) {
+    let source = fs::read_to_string(&module.file).unwrap();
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut context = Context::None;
+    let mut attributes = Vec::<&str>::new();
+    // The enum variant whose fields are being read.
+    let mut variant = "";
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        let depth = indent(line);
+        i += 1;
+
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            continue;
+        }
+        if trimmed.starts_with("#[") || trimmed.starts_with("#![") {
+            if let Some(attribute) = relevant_attribute(line) {
+                attributes.push(attribute);
+            }
+            // Skips the rest of an attribute spread over several lines.
+            if !trimmed.ends_with(']') {
+                while i < lines.len() && !lines[i].trim_end().ends_with(']') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            continue;
+        }
+        if depth == 0 && trimmed.starts_with('}') {
+            context = Context::None;
+            attributes.clear();
+            continue;
+        }
+        if context == Context::Skip {
+            attributes.clear();
+            continue;
+        }
+
+        let end = declaration_end(&lines, i - 1);
+        let mut record = |entry: String, attributes: &mut Vec<&str>| {
+            let prefix = attributes
+                .drain(..)
+                .map(|attribute| format!("{attribute} "))
+                .collect::<String>();
+            api.insert(format!("{}: {prefix}{entry}", module.path));
+        };
+
+        if depth == 0 {
+            let has_body = lines[end].trim_end().ends_with('{');
+            let decl = declaration(&lines[i - 1..=end]);
+            i = end + 1;
+            context = if decl.starts_with("pub mod ") {
+                record(decl, &mut attributes);
+                Context::None
+            } else if decl.starts_with("pub(") || !decl.starts_with("pub ") {
+                if decl.starts_with("impl") || decl.starts_with("unsafe impl") {
+                    let ty = impl_self_type(&decl);
+                    if types.contains(&ty) {
+                        if decl.contains(" for ") {
+                            record(decl.clone(), &mut attributes);
+                        }
+                        Context::Impl { ty, header: decl }
+                    } else {
+                        Context::Skip
+                    }
+                } else {
+                    Context::Skip
+                }
+            } else {
+                let name = decl
+                    .split_whitespace()
+                    .nth(2)
+                    .unwrap_or_default()
+                    .split(|c: char| !c.is_alphanumeric() && c != '_')
+                    .next()
+                    .unwrap()
+                    .to_owned();
+                let context = if decl.starts_with("pub trait ") {
+                    Context::Trait(name)
+                } else if decl.starts_with("pub enum ") {
+                    Context::Enum(name)
+                } else if decl.starts_with("pub struct ") {
+                    Context::Struct(name)
+                } else {
+                    Context::Skip
+                };
+                record(decl, &mut attributes);
+                context
+            };
+            if !has_body {
+                context = Context::None;
+            }
+            attributes.clear();
+            continue;
+        }
+
+        if trimmed.starts_with(['}', ')']) {
+            attributes.clear();
+            continue;
+        }
+        match &context {
+            Context::Impl { ty, header } if depth == 4 && trimmed.starts_with("pub ") => {
+                let decl = declaration(&lines[i - 1..=end]);
+                let owner = if header.contains(" for ") { header } else { ty };
+                record(format!("{owner}: {decl}"), &mut attributes);
+                i = end + 1;
+            }
+            Context::Trait(name) if depth == 4 => {
+                let decl = declaration(&lines[i - 1..=end]);
+                record(format!("trait {name}: {decl}"), &mut attributes);
+                i = end + 1;
+            }
+            Context::Enum(name)
+                if depth == 4 && trimmed.starts_with(|c: char| c.is_ascii_uppercase()) =>
+            {
+                let declared = trimmed.trim_end_matches(',').trim_end_matches(" {");
+                variant = declared.split(['(', ' ']).next().unwrap();
+                record(format!("enum {name}: {declared}"), &mut attributes);
+            }
+            Context::Enum(name) if depth == 8 => {
+                let field = trimmed.trim_end_matches(',');
+                record(format!("enum {name}: {variant}.{field}"), &mut attributes);
+            }
+            Context::Struct(name) if depth == 4 && trimmed.starts_with("pub ") => {
+                let field = trimmed.trim_end_matches(',');
+                record(format!("struct {name}: {field}"), &mut attributes);
+            }
+            _ => attributes.clear(),
+        }
+    }
+}
+
+fn public_api() -> String {
+    let modules = public_modules();
+    let types = public_types(&modules);
+    let mut api = BTreeSet::new();
+    for module in &modules {
+        module_api(module, &types, &mut api);
+    }
+    api.into_iter().map(|entry| entry + "\n").collect()
+}
+
+#[test]
+fn test_public_api_matches_snapshot() {
+    let api = public_api();
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(Path::new(SNAPSHOT).parent().unwrap()).unwrap();
+        fs::write(SNAPSHOT, &api).unwrap();
+        return;
+    }
+
+    let snapshot = fs::read_to_string(SNAPSHOT).unwrap_or_default();
+    let expected = snapshot.lines().collect::<BTreeSet<_>>();
+    let actual = api.lines().collect::<BTreeSet<_>>();
+    let removed = expected.difference(&actual).collect::<Vec<_>>();
+    let added = actual.difference(&expected).collect::<Vec<_>>();
+    assert!(
+        removed.is_empty() && added.is_empty(),
+        "the public API changed; rerun with UPDATE_SNAPSHOTS=1 if intended\n\
+         removed:\n{removed:#?}\nadded:\n{added:#?}"
+    );
+}
+
+#[test]
+fn test_open_protocol_enums_are_non_exhaustive() {
+    let api = public_api();
+    for (module, name) in [
+        ("clauders::response", "Response"),
+        ("clauders::error", "Error"),
+        ("clauders::error", "ConfigError"),
+        ("clauders::proto::incoming", "Incoming"),
+        ("clauders::proto::content_block", "ContentBlock"),
+    ] {
+        assert!(
+            api.lines()
+                .any(|entry| entry.starts_with(&format!("{module}: "))
+                    && entry.contains("#[non_exhaustive] pub enum ")
+                    && entry.ends_with(&format!(" {name}"))),
+            "{module}::{name} is not non_exhaustive"
+        );
+    }
+}